@@ -0,0 +1,126 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Benchmarks for the code that runs every tick or on every request
+//! Run with `cargo bench`; criterion reports changes against the previous run
+#[macro_use]
+extern crate criterion;
+extern crate heroesoftheswarm;
+extern crate rand;
+extern crate serde_json;
+
+use criterion::Criterion;
+use heroesoftheswarm::boundary::Boundary;
+use heroesoftheswarm::entity::{Bullets, Swarm};
+use heroesoftheswarm::rpc::{Response, ResponseMessage};
+use heroesoftheswarm::swarm_language::SwarmProgram;
+use heroesoftheswarm::world::World;
+use rand::XorShiftRng;
+
+/// A program that exercises movement, turning, and firing
+const PROGRAM: &str = "MOVE\nTURN 10\nFIRE\nMOVE\nFORMATION SPREAD\nMOVE\nTURN -5\nFIRE\n";
+
+/// Builds a world with the given number of players, all running PROGRAM
+/// The world is updated for a while so it has bullets in flight
+fn populated_world(num_players: usize) -> World {
+    let program: SwarmProgram = PROGRAM.parse().expect("Benchmark program should compile");
+    // Seeded so every run starts from the same world
+    let mut world = World::new(4000.0, 4000.0).with_seed(0);
+    for id in 0..num_players {
+        world.add_player(id).expect("Benchmark players should be unique");
+        world.update_program(id, program.clone());
+    }
+    for _ in 0..30 {
+        world.update();
+    }
+    world
+}
+
+fn parse_program(c: &mut Criterion) {
+    c.bench_function("parse_program", |b| {
+        b.iter(|| PROGRAM.parse::<SwarmProgram>().unwrap())
+    });
+}
+
+fn update_world(c: &mut Criterion) {
+    c.bench_function_over_inputs(
+        "update_world",
+        |b, &num_players| {
+            let mut world = populated_world(num_players);
+            b.iter(|| world.update())
+        },
+        // Numbers of players
+        vec![10, 100, 1000],
+    );
+}
+
+fn move_swarms(c: &mut Criterion) {
+    let program: SwarmProgram = "MOVE\nTURN 1".parse().unwrap();
+    let mut swarms: Vec<Swarm> = (0..10000)
+        .map(|_| {
+            let mut swarm = Swarm::new(50000.0, 50000.0, 1);
+            swarm.program = program.clone();
+            swarm
+        })
+        .collect();
+    let mut bullets = Bullets::new();
+    let mut rng = XorShiftRng::new_unseeded();
+    c.bench_function("move_swarms_10k", move |b| {
+        b.iter(|| {
+            for swarm in swarms.iter_mut() {
+                swarm.update(0, 100000.0, 100000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+            }
+        })
+    });
+}
+
+fn serialize_state(c: &mut Criterion) {
+    c.bench_function_over_inputs(
+        "serialize_state",
+        |b, &num_players| {
+            let world = populated_world(num_players);
+            b.iter(|| {
+                Response::new(ResponseMessage::WORLD(world.state_ref()))
+                    .serialize()
+                    .unwrap()
+            })
+        },
+        // Numbers of players
+        vec![10, 100, 1000],
+    );
+}
+
+fn serialize_delta(c: &mut Criterion) {
+    c.bench_function_over_inputs(
+        "serialize_delta",
+        |b, &num_players| {
+            let world = populated_world(num_players);
+            b.iter(|| serde_json::to_string(&world.delta()).unwrap())
+        },
+        // Numbers of players
+        vec![10, 100, 1000],
+    );
+}
+
+criterion_group!(
+    benches,
+    parse_program,
+    update_world,
+    move_swarms,
+    serialize_state,
+    serialize_delta
+);
+criterion_main!(benches);
@@ -0,0 +1,64 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Compares scalar and packed position integration
+//! Run with `cargo bench --features simd` to measure the packed version
+#[macro_use]
+extern crate criterion;
+extern crate heroesoftheswarm;
+
+use criterion::Criterion;
+use heroesoftheswarm::entity::{Bullet, Bullets};
+use heroesoftheswarm::integrate::{integrate, integrate_scalar};
+
+/// Number of bullets to move in each iteration
+const NUM_BULLETS: usize = 50000;
+
+fn integrate_scalar_50k(c: &mut Criterion) {
+    let mut positions: Vec<f32> = (0..NUM_BULLETS).map(|i| i as f32).collect();
+    let velocities: Vec<f32> = vec![0.25; NUM_BULLETS];
+    c.bench_function("integrate_scalar_50k", move |b| {
+        b.iter(|| integrate_scalar(&mut positions, &velocities))
+    });
+}
+
+fn integrate_50k(c: &mut Criterion) {
+    let mut positions: Vec<f32> = (0..NUM_BULLETS).map(|i| i as f32).collect();
+    let velocities: Vec<f32> = vec![0.25; NUM_BULLETS];
+    c.bench_function("integrate_50k", move |b| {
+        b.iter(|| integrate(&mut positions, &velocities))
+    });
+}
+
+fn update_bullets_50k(c: &mut Criterion) {
+    let mut bullets = Bullets::with_capacity(NUM_BULLETS);
+    for i in 0..NUM_BULLETS {
+        bullets.push(Bullet::new(0, 0.0, 0.0, (i % 360) as f32));
+    }
+    // Keep every bullet alive for the whole benchmark
+    for ttl in bullets.ttl.iter_mut() {
+        *ttl = i64::max_value();
+    }
+    c.bench_function("update_bullets_50k", move |b| b.iter(|| bullets.update()));
+}
+
+criterion_group!(
+    benches,
+    integrate_scalar_50k,
+    integrate_50k,
+    update_bullets_50k
+);
+criterion_main!(benches);
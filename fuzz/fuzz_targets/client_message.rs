@@ -0,0 +1,28 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Decodes arbitrary bytes as a message from a client
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate heroesoftheswarm;
+extern crate serde_json;
+
+use heroesoftheswarm::rpc::ClientMessage;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = serde_json::from_slice::<ClientMessage>(data);
+});
@@ -0,0 +1,30 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Redeems arbitrary text as a handoff token
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate heroesoftheswarm;
+
+use heroesoftheswarm::handoff::redeem_token;
+use std::str;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(token) = str::from_utf8(data) {
+        let _ = redeem_token(token, b"secret", 1_000_000);
+    }
+});
@@ -0,0 +1,45 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Parses arbitrary text as a swarm program, then runs it in a world
+//! Programs come straight from players, so running one must not panic either
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate heroesoftheswarm;
+
+use heroesoftheswarm::swarm_language::SwarmProgram;
+use heroesoftheswarm::world::World;
+use std::str;
+
+fuzz_target!(|data: &[u8]| {
+    let program: SwarmProgram = match str::from_utf8(data).map(str::parse) {
+        Ok(Ok(program)) => program,
+        _ => return,
+    };
+    // Reparsing the printed program must give the same program
+    let printed = program.to_string();
+    let reparsed: SwarmProgram = printed.parse().expect("Printed program should parse");
+    assert_eq!(reparsed.commands, program.commands);
+    // Run every command a few times, fighting another swarm
+    let mut world = World::new(1000.0, 1000.0).with_seed(0);
+    world.add_player(0).unwrap();
+    world.add_player(1).unwrap();
+    world.update_program(0, program);
+    for _ in 0..60 {
+        world.update();
+    }
+});
@@ -0,0 +1,103 @@
+#ifndef HEROESOFTHESWARM_H
+#define HEROESOFTHESWARM_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Result of a call into the C API
+ */
+typedef enum {
+  /**
+   * The call succeeded
+   */
+  HOTS_STATUS_OK = 0,
+  /**
+   * A pointer argument was null
+   */
+  HOTS_STATUS_NULL_POINTER = 1,
+  /**
+   * A string argument wasn't valid UTF-8
+   */
+  HOTS_STATUS_INVALID_UTF8 = 2,
+  /**
+   * A program didn't compile
+   */
+  HOTS_STATUS_INVALID_PROGRAM = 3,
+  /**
+   * A player already has the ID
+   */
+  HOTS_STATUS_DUPLICATE_PLAYER = 4,
+  /**
+   * No player has the ID
+   */
+  HOTS_STATUS_NO_PLAYER = 5,
+  /**
+   * The buffer can't hold the state; the size it needs was written
+   */
+  HOTS_STATUS_BUFFER_TOO_SMALL = 6,
+  /**
+   * The state couldn't be serialized
+   */
+  HOTS_STATUS_SERIALIZATION = 7,
+  /**
+   * The simulation panicked, and the world should be freed
+   */
+  HOTS_STATUS_PANIC = 8,
+} HotsStatus;
+
+typedef struct HotsWorld HotsWorld;
+
+/**
+ * Adds a player, whose swarm spawns at a random position
+ */
+HotsStatus hots_world_add_player(HotsWorld *world, uintptr_t player_id);
+
+/**
+ * Frees a world created by hots_world_new
+ * Passing null does nothing
+ */
+void hots_world_free(HotsWorld *world);
+
+/**
+ * Creates a world
+ * Worlds with the same size, players, programs, and seed always play out the same way
+ * return: the world, to be freed with hots_world_free, or null if the size is invalid
+ */
+HotsWorld *hots_world_new(float width, float height, uint64_t seed);
+
+/**
+ * Removes a player and their swarm
+ */
+HotsStatus hots_world_remove_player(HotsWorld *world, uintptr_t player_id);
+
+/**
+ * Compiles swarm code and gives it to a player
+ * source: a NUL-terminated UTF-8 string
+ * The player keeps their old program if the source doesn't compile
+ */
+HotsStatus hots_world_set_program(HotsWorld *world, uintptr_t player_id, const char *source);
+
+/**
+ * Copies the world's state into a buffer, as the JSON the server sends clients
+ * The JSON is not NUL-terminated
+ * buffer: where to copy the state; may be null if capacity is 0
+ * written: set to the length of the state, even if it doesn't fit
+ * Call with a capacity of 0 to find out how big the buffer needs to be
+ */
+HotsStatus hots_world_state(const HotsWorld *world,
+                            uint8_t *buffer,
+                            uintptr_t capacity,
+                            uintptr_t *written);
+
+/**
+ * Performs one tick
+ * Events aren't exposed to C, so they're dropped instead of piling up
+ */
+HotsStatus hots_world_tick(HotsWorld *world);
+
+#endif /* HEROESOFTHESWARM_H */
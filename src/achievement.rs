@@ -0,0 +1,289 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use entity::{is_npc, Swarm};
+use event::WorldEvent;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use slab::Slab;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, RwLock};
+
+/// Length counted as one tile of travel, the distance of two swarm moves
+pub const TILE_SIZE: f32 = 10.0;
+/// Tiles a swarm travels to unlock TRAVELER
+pub const TRAVEL_TILES: f32 = 100.0;
+/// Fewest members a swarm has for wiping it out in one volley to count
+pub const VOLLEY_MEMBERS: usize = 2;
+
+/// Something a player can unlock by playing
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum Achievement {
+    /// Destroy a member of another swarm
+    #[serde(rename = "first_kill")]
+    FIRST_KILL,
+    /// Travel 100 tiles
+    #[serde(rename = "traveler")]
+    TRAVELER,
+    /// Destroy every member of a swarm in the same tick
+    #[serde(rename = "one_volley")]
+    ONE_VOLLEY,
+}
+
+/// Functions for Achievement
+impl Achievement {
+    /// Name shown to players
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Achievement::FIRST_KILL => "First Blood",
+            Achievement::TRAVELER => "Traveler",
+            Achievement::ONE_VOLLEY => "One Volley",
+        }
+    }
+}
+
+/// A player's progress towards each achievement
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct AchievementProgress {
+    /// Members of other swarms destroyed
+    pub kills: u64,
+    /// Distance traveled, in world units
+    pub distance: f32,
+    /// Achievements unlocked, in the order they were unlocked
+    pub unlocked: Vec<Achievement>,
+}
+
+/// Functions for AchievementProgress
+impl AchievementProgress {
+    /// Whether the achievement is unlocked
+    pub fn has(&self, achievement: Achievement) -> bool {
+        self.unlocked.contains(&achievement)
+    }
+    /// Unlocks an achievement
+    /// return: whether it wasn't already unlocked
+    pub fn unlock(&mut self, achievement: Achievement) -> bool {
+        if self.has(achievement) {
+            return false;
+        }
+        self.unlocked.push(achievement);
+        true
+    }
+}
+
+/// Keeps players' achievement progress between sessions
+/// Shared between room update threads, so implementations handle their own locking
+pub trait AchievementStore: Send + Sync + Debug {
+    /// The player's progress, empty if they haven't made any
+    fn load(&self, player_id: usize) -> AchievementProgress;
+    /// Saves the player's progress
+    fn save(&self, player_id: usize, progress: &AchievementProgress);
+}
+
+/// Progress kept in memory, lost when the server stops
+#[derive(Debug, Default)]
+pub struct MemoryAchievements {
+    /// Progress of each player
+    progress: RwLock<HashMap<usize, AchievementProgress>>,
+}
+
+/// Functions for MemoryAchievements
+impl MemoryAchievements {
+    /// Constructor
+    pub fn new() -> Self {
+        MemoryAchievements::default()
+    }
+}
+
+/// Keeps progress in a map, behind a lock
+impl AchievementStore for MemoryAchievements {
+    fn load(&self, player_id: usize) -> AchievementProgress {
+        match self.progress.read() {
+            Ok(progress) => progress.get(&player_id).cloned().unwrap_or_default(),
+            Err(_) => AchievementProgress::default(),
+        }
+    }
+    fn save(&self, player_id: usize, progress: &AchievementProgress) {
+        match self.progress.write() {
+            Ok(mut saved) => {
+                saved.insert(player_id, progress.clone());
+            }
+            Err(error) => error!("Error getting write lock on achievements: {}", error),
+        }
+    }
+}
+
+/// Tracks the achievements of a world's players
+/// Progress is loaded from the store the first time a player makes any,
+/// and saved when they unlock something or leave
+#[derive(Clone, Debug)]
+pub struct Achievements {
+    /// Where progress is kept
+    store: Arc<AchievementStore>,
+    /// Progress of each player in the world
+    progress: HashMap<usize, AchievementProgress>,
+    /// Unlocks not yet sent to each player
+    pending: HashMap<usize, Vec<Achievement>>,
+}
+
+/// Functions for Achievements
+impl Achievements {
+    /// Constructor
+    pub fn new(store: Arc<AchievementStore>) -> Self {
+        Achievements {
+            store: store,
+            progress: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+    /// The player's progress, loaded from the store if it isn't already
+    pub fn progress(&mut self, player_id: usize) -> &mut AchievementProgress {
+        let store = &self.store;
+        self.progress
+            .entry(player_id)
+            .or_insert_with(|| store.load(player_id))
+    }
+    /// Adds distance a player's swarm moved on its own
+    /// return: TRAVELER, if this unlocked it
+    pub fn travel(&mut self, player_id: usize, distance: f32) -> Option<Achievement> {
+        if is_npc(player_id) || distance <= 0.0 {
+            return None;
+        }
+        let unlocked = {
+            let progress = self.progress(player_id);
+            progress.distance += distance;
+            progress.distance >= TRAVEL_TILES * TILE_SIZE && progress.unlock(Achievement::TRAVELER)
+        };
+        if !unlocked {
+            return None;
+        }
+        self.unlocked(player_id, Achievement::TRAVELER);
+        Some(Achievement::TRAVELER)
+    }
+    /// Evaluates the kills of one tick
+    /// events: the events of the tick, after hits were applied
+    /// swarms: every swarm, after hits were applied
+    /// return: (player ID, achievement) for each achievement unlocked
+    pub fn evaluate(
+        &mut self,
+        events: &[WorldEvent],
+        swarms: &Slab<Swarm>,
+    ) -> Vec<(usize, Achievement)> {
+        let mut unlocked = Vec::new();
+        // Attackers of each victim, for telling whether one volley wiped it out
+        let mut attackers: HashMap<usize, (Option<usize>, usize)> = HashMap::new();
+        for event in events {
+            if let WorldEvent::KILL { attacker, victim } = *event {
+                let entry = attackers.entry(victim).or_insert((Some(attacker), 0));
+                if entry.0 != Some(attacker) {
+                    entry.0 = None;
+                }
+                entry.1 += 1;
+                if is_npc(attacker) || !swarms.contains_key(&attacker) {
+                    continue;
+                }
+                let progress = self.progress(attacker);
+                progress.kills += 1;
+                if progress.unlock(Achievement::FIRST_KILL) {
+                    unlocked.push((attacker, Achievement::FIRST_KILL));
+                }
+            }
+        }
+        let mut victims: Vec<_> = attackers.into_iter().collect();
+        victims.sort_by_key(|&(victim, _)| victim);
+        for (victim, (attacker, kills)) in victims {
+            let wiped = swarms
+                .get(&victim)
+                .map_or(false, |swarm| swarm.members.is_empty());
+            let attacker = match attacker {
+                Some(attacker) if wiped && kills >= VOLLEY_MEMBERS => attacker,
+                _ => continue,
+            };
+            if is_npc(attacker) || !swarms.contains_key(&attacker) {
+                continue;
+            }
+            if self.progress(attacker).unlock(Achievement::ONE_VOLLEY) {
+                unlocked.push((attacker, Achievement::ONE_VOLLEY));
+            }
+        }
+        for &(player_id, achievement) in unlocked.iter() {
+            self.unlocked(player_id, achievement);
+        }
+        unlocked
+    }
+    /// Saves an unlock and queues it to be sent to the player
+    pub fn unlocked(&mut self, player_id: usize, achievement: Achievement) {
+        let progress = self.progress(player_id).clone();
+        self.store.save(player_id, &progress);
+        self.pending
+            .entry(player_id)
+            .or_insert_with(Vec::new)
+            .push(achievement);
+    }
+    /// Saves a player's progress and stops tracking them
+    pub fn leave(&mut self, player_id: usize) {
+        if let Some(progress) = self.progress.remove(&player_id) {
+            self.store.save(player_id, &progress);
+        }
+        self.pending.remove(&player_id);
+    }
+    /// Whether a player has unlocks waiting to be sent
+    pub fn has_unlocks(&self, player_id: usize) -> bool {
+        self.pending.contains_key(&player_id)
+    }
+    /// Takes the unlocks waiting to be sent to a player
+    pub fn take_unlocks(&mut self, player_id: usize) -> Vec<Achievement> {
+        self.pending.remove(&player_id).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unlock_achievements() {
+        let store = Arc::new(MemoryAchievements::new());
+        let mut achievements = Achievements::new(store.clone());
+        let mut swarms = Slab::new();
+        swarms.insert(1, Swarm::new(0.0, 0.0, 3));
+        swarms.insert(2, Swarm::new(0.0, 0.0, 0));
+        let kill = WorldEvent::KILL {
+            attacker: 1,
+            victim: 2,
+        };
+        // Two kills in the tick that left player 2 with no members
+        let unlocked = achievements.evaluate(&[kill.clone(), kill], &swarms);
+        assert_eq!(
+            unlocked,
+            vec![(1, Achievement::FIRST_KILL), (1, Achievement::ONE_VOLLEY)]
+        );
+        assert_eq!(
+            achievements.take_unlocks(1),
+            vec![Achievement::FIRST_KILL, Achievement::ONE_VOLLEY]
+        );
+        assert!(!achievements.has_unlocks(1));
+
+        assert_eq!(achievements.travel(1, 600.0), None);
+        achievements.leave(1);
+        assert_eq!(store.load(1).kills, 2);
+        // Progress carries over to the next session
+        let mut achievements = Achievements::new(store.clone());
+        assert_eq!(achievements.travel(1, 400.0), Some(Achievement::TRAVELER));
+        assert!(store.load(1).has(Achievement::TRAVELER));
+    }
+}
@@ -0,0 +1,298 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use error::{Error, LanguageError, WorldError};
+use handicap::Handicap;
+use std::fmt::Write;
+use std::str::FromStr;
+use world::World;
+
+/// Represents a command an administrator can run against the world
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AdminCommand {
+    /// List every player in the world
+    PLAYERS,
+    /// Show a player's swarm, program, and program counter
+    INSPECT(usize),
+    /// Force a player's swarm to respawn
+    RESPAWN(usize),
+    /// Set a player's damage and speed multipliers
+    HANDICAP(usize, Handicap),
+    /// Slow down or speed up the world
+    TIME_SCALE(f32),
+    /// Dump statistics about the world
+    STATS,
+    /// List the available commands
+    HELP,
+}
+
+/// Allows conversion of a string to an admin command
+impl FromStr for AdminCommand {
+    /// The type of error returned if the conversion fails
+    type Err = LanguageError;
+    /// Converts a line of input to an AdminCommand
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let command: Vec<&str> = s.trim().split_whitespace().collect();
+
+        if command.len() == 0 {
+            return Err(LanguageError::EMPTY_COMMAND);
+        }
+
+        match command[0].to_lowercase().as_str() {
+            "players" => Ok(AdminCommand::PLAYERS),
+            "stats" => Ok(AdminCommand::STATS),
+            "help" => Ok(AdminCommand::HELP),
+            "inspect" | "respawn" => {
+                if command.len() != 2 {
+                    return Err(LanguageError::MISSING_PARAMETER(command[0].into()));
+                }
+                let player_id: usize = match command[1].parse() {
+                    Ok(player_id) => player_id,
+                    Err(_) => {
+                        return Err(LanguageError::INVALID_PARAMETER {
+                            command: command[0].into(),
+                            parameter: command[1].into(),
+                        })
+                    }
+                };
+                if command[0].to_lowercase() == "inspect" {
+                    Ok(AdminCommand::INSPECT(player_id))
+                } else {
+                    Ok(AdminCommand::RESPAWN(player_id))
+                }
+            }
+            "handicap" => {
+                if command.len() != 4 {
+                    return Err(LanguageError::MISSING_PARAMETER(command[0].into()));
+                }
+                let invalid = |parameter: &str| LanguageError::INVALID_PARAMETER {
+                    command: command[0].into(),
+                    parameter: parameter.into(),
+                };
+                let player_id: usize = command[1].parse().map_err(|_| invalid(command[1]))?;
+                let mut multipliers = [0.0f32; 2];
+                for (multiplier, parameter) in multipliers.iter_mut().zip(&command[2..]) {
+                    *multiplier = match parameter.parse::<f32>() {
+                        Ok(value) if value.is_finite() && value >= 0.0 => value,
+                        _ => return Err(invalid(parameter)),
+                    };
+                }
+                Ok(AdminCommand::HANDICAP(
+                    player_id,
+                    Handicap::new(multipliers[0], multipliers[1]),
+                ))
+            }
+            "timescale" => {
+                if command.len() != 2 {
+                    return Err(LanguageError::MISSING_PARAMETER(command[0].into()));
+                }
+                match command[1].parse::<f32>() {
+                    Ok(time_scale)
+                        if time_scale >= World::MIN_TIME_SCALE
+                            && time_scale <= World::MAX_TIME_SCALE =>
+                    {
+                        Ok(AdminCommand::TIME_SCALE(time_scale))
+                    }
+                    _ => Err(LanguageError::OUT_OF_RANGE {
+                        command: command[0].into(),
+                        min: World::MIN_TIME_SCALE,
+                        max: World::MAX_TIME_SCALE,
+                    }),
+                }
+            }
+            _ => Err(LanguageError::UNKNOWN_COMMAND(command[0].into())),
+        }
+    }
+}
+
+/// Functions for AdminCommand
+impl AdminCommand {
+    /// Runs the command against the world
+    /// return: human readable output of the command
+    pub fn execute(&self, world: &mut World) -> Result<String, Error> {
+        let mut output = String::new();
+        match *self {
+            AdminCommand::PLAYERS => {
+                for id in world.player_ids() {
+                    let swarm = &world.swarms[&id];
+                    writeln!(
+                        output,
+                        "{}: {} members, {} experience",
+                        id,
+                        swarm.members.len(),
+                        swarm.experience
+                    ).unwrap();
+                }
+                writeln!(output, "{} players", world.swarms.len()).unwrap();
+            }
+            AdminCommand::INSPECT(id) => match world.swarms.get(&id) {
+                Some(swarm) => {
+                    writeln!(
+                        output,
+                        "Player {} at ({:.1}, {:.1}) facing {:.1}",
+                        id, swarm.x, swarm.y, swarm.direction
+                    ).unwrap();
+                    writeln!(
+                        output,
+                        "{} members, {} experience",
+                        swarm.members.len(),
+                        swarm.experience
+                    ).unwrap();
+                    if !swarm.handicap.is_none() || !swarm.boost.is_none() {
+                        writeln!(
+                            output,
+                            "Handicap: {}x damage, {}x speed (comeback {}x damage, {}x speed)",
+                            swarm.handicap.damage,
+                            swarm.handicap.speed,
+                            swarm.boost.damage,
+                            swarm.boost.speed
+                        ).unwrap();
+                    }
+                    writeln!(
+                        output,
+                        "Program counter: {}",
+                        swarm.program.program_counter
+                    ).unwrap();
+                    // Print the program, marking the next command to execute
+                    for (index, command) in swarm.program.commands.iter().enumerate() {
+                        let marker = if index == swarm.program.program_counter {
+                            ">"
+                        } else {
+                            " "
+                        };
+                        writeln!(output, "{} {:2} {}", marker, index, command).unwrap();
+                    }
+                }
+                None => return Err(WorldError::NO_PLAYER(id).into()),
+            },
+            AdminCommand::RESPAWN(id) => {
+                if world.respawn_player(id) {
+                    writeln!(output, "Respawned player {}", id).unwrap();
+                } else {
+                    return Err(WorldError::NO_PLAYER(id).into());
+                }
+            }
+            AdminCommand::HANDICAP(id, handicap) => {
+                world.set_handicap(id, handicap)?;
+                writeln!(
+                    output,
+                    "Player {} now deals {}x damage and moves at {}x speed",
+                    id, handicap.damage, handicap.speed
+                ).unwrap();
+            }
+            AdminCommand::TIME_SCALE(time_scale) => {
+                world.set_time_scale(time_scale)?;
+                writeln!(output, "World now runs at {}x speed", time_scale).unwrap();
+            }
+            AdminCommand::STATS => {
+                let stats = world.stats();
+                writeln!(output, "Tick: {}", stats.tick).unwrap();
+                writeln!(output, "Time scale: {}x", stats.time_scale).unwrap();
+                writeln!(output, "Size: {}x{}", stats.width, stats.height).unwrap();
+                writeln!(output, "Players: {}", stats.num_players).unwrap();
+                writeln!(output, "Swarm members: {}", stats.num_members).unwrap();
+                writeln!(output, "Bullets: {}", stats.num_bullets).unwrap();
+                writeln!(output, "Updates over budget: {}", stats.over_budget).unwrap();
+                writeln!(output, "Ticks skipped: {}", stats.skipped_ticks).unwrap();
+                if stats.overloaded {
+                    writeln!(output, "Overloaded").unwrap();
+                }
+                writeln!(
+                    output,
+                    "Member index: {} moved, {} unchanged, {} rebuilds",
+                    stats.member_index.moved,
+                    stats.member_index.unchanged,
+                    stats.member_index.rebuilds
+                ).unwrap();
+                writeln!(
+                    output,
+                    "Memory: {} KiB (swarms {}, bullets {}, index {}, scratch {}, events {})",
+                    stats.memory.total / 1024,
+                    stats.memory.swarms / 1024,
+                    stats.memory.bullets / 1024,
+                    stats.memory.spatial_index / 1024,
+                    stats.memory.scratch / 1024,
+                    stats.memory.events / 1024
+                ).unwrap();
+                for player in stats.handicaps.iter() {
+                    writeln!(
+                        output,
+                        "Player {} handicap: {}x damage, {}x speed (comeback {}x damage, {}x speed)",
+                        player.player,
+                        player.handicap.damage,
+                        player.handicap.speed,
+                        player.boost.damage,
+                        player.boost.speed
+                    ).unwrap();
+                }
+            }
+            AdminCommand::HELP => {
+                writeln!(output, "players           list every player").unwrap();
+                writeln!(output, "inspect <id>      show a swarm and its program").unwrap();
+                writeln!(output, "respawn <id>      force a swarm to respawn").unwrap();
+                writeln!(output, "handicap <id> <damage> <speed>").unwrap();
+                writeln!(output, "                  set a swarm's damage and speed multipliers").unwrap();
+                writeln!(output, "timescale <scale> run the world from 0.1x to 4x speed").unwrap();
+                writeln!(output, "stats             dump world statistics").unwrap();
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn parse_admin_command() {
+        assert_eq!("players".parse::<AdminCommand>().unwrap(), AdminCommand::PLAYERS);
+        assert_eq!(
+            " INSPECT 4 ".parse::<AdminCommand>().unwrap(),
+            AdminCommand::INSPECT(4)
+        );
+        assert_eq!(
+            "respawn 2".parse::<AdminCommand>().unwrap(),
+            AdminCommand::RESPAWN(2)
+        );
+        assert!("respawn".parse::<AdminCommand>().is_err());
+        assert!("inspect four".parse::<AdminCommand>().is_err());
+        assert!("".parse::<AdminCommand>().is_err());
+        assert_eq!(
+            "handicap 3 0.5 1.2".parse::<AdminCommand>().unwrap(),
+            AdminCommand::HANDICAP(3, Handicap::new(0.5, 1.2))
+        );
+        assert!("handicap 3 -1 1".parse::<AdminCommand>().is_err());
+        assert!("handicap 3 1".parse::<AdminCommand>().is_err());
+        assert_eq!(
+            "timescale 0.25".parse::<AdminCommand>().unwrap(),
+            AdminCommand::TIME_SCALE(0.25)
+        );
+        assert!("timescale 10".parse::<AdminCommand>().is_err());
+    }
+
+    #[test]
+    fn execute_admin_command() {
+        let mut world = World::new(1000.0, 1000.0);
+        world.add_player(0).unwrap();
+        let output = AdminCommand::INSPECT(0).execute(&mut world).unwrap();
+        assert!(output.contains("Program counter: 0"));
+        assert!(AdminCommand::INSPECT(1).execute(&mut world).is_err());
+        assert!(AdminCommand::RESPAWN(0).execute(&mut world).is_ok());
+        let handicap = Handicap::new(0.5, 1.0);
+        assert!(AdminCommand::HANDICAP(0, handicap).execute(&mut world).is_ok());
+        assert_eq!(world.swarms[&0].handicap, handicap);
+        assert_eq!(world.stats().handicaps[0].handicap, handicap);
+    }
+}
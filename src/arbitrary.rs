@@ -0,0 +1,166 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate serde_json;
+
+use entity::{Bullet, Bullets, Swarm, INITIAL_SWARM_SIZE};
+use game_mode::GameMode;
+use minimap::Minimap;
+use rules::MatchPhase;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use slab::Slab;
+use swarm_language::{
+    Formation, SwarmCommand, SwarmProgram, MAX_MOVE_DISTANCE, MAX_NUM_COMMANDS,
+    MAX_SIERPINSKI_FOCI, MAX_TURN, MAX_WAIT_TICKS,
+};
+use world::WorldState;
+
+/// Any formation the parser accepts
+impl Arbitrary for Formation {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop_oneof![
+            Just(Formation::GATHER),
+            Just(Formation::SPREAD),
+            (0..MAX_SIERPINSKI_FOCI + 1).prop_map(Formation::SIERPINSKI),
+        ]
+        .boxed()
+    }
+}
+
+/// Any command the parser accepts that doesn't open or close a block,
+/// so any list of them is a program
+impl Arbitrary for SwarmCommand {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        prop_oneof![
+            Just(SwarmCommand::MOVE),
+            (0.0..MAX_MOVE_DISTANCE)
+                .prop_filter("MOVE needs a positive normal number", |distance| {
+                    distance.is_normal()
+                })
+                .prop_map(SwarmCommand::MOVE_BY),
+            Just(SwarmCommand::LEFT),
+            Just(SwarmCommand::RIGHT),
+            Just(SwarmCommand::UP),
+            Just(SwarmCommand::DOWN),
+            Just(SwarmCommand::FIRE),
+            Just(SwarmCommand::NOOP),
+            (1..MAX_WAIT_TICKS + 1).prop_map(SwarmCommand::WAIT),
+            (-MAX_TURN..MAX_TURN)
+                .prop_filter("TURN needs a normal number", |turn| turn.is_normal())
+                .prop_map(SwarmCommand::TURN),
+            any::<Formation>().prop_map(SwarmCommand::FORMATION),
+        ]
+        .boxed()
+    }
+}
+
+/// Any program the parser accepts
+impl Arbitrary for SwarmProgram {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        vec(any::<SwarmCommand>(), 0..MAX_NUM_COMMANDS + 1)
+            .prop_map(SwarmProgram::new)
+            .boxed()
+    }
+}
+
+/// A swarm somewhere in a 1000x1000 world
+fn swarm() -> BoxedStrategy<Swarm> {
+    (
+        0.0f32..1000.0,
+        0.0f32..1000.0,
+        0..INITIAL_SWARM_SIZE + 1,
+        any::<(u8, u8, u8)>(),
+        any::<i64>(),
+    )
+        .prop_map(|(x, y, num_members, color, experience)| {
+            let mut swarm = Swarm::new(x, y, num_members).with_color(color);
+            swarm.experience = experience;
+            swarm
+        })
+        .boxed()
+}
+
+/// A bullet somewhere in a 1000x1000 world
+/// Directions stay clear of 180 degrees, where the direction a bullet is sent with wraps around
+fn bullet() -> BoxedStrategy<Bullet> {
+    (0usize..64, 0.0f32..1000.0, 0.0f32..1000.0, -179.0f32..179.0)
+        .prop_map(|(owner, x, y, direction)| Bullet::new(owner, x, y, direction))
+        .boxed()
+}
+
+/// A world state with a few swarms and bullets
+impl Arbitrary for WorldState {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (vec((0usize..64, swarm()), 0..8), vec(bullet(), 0..32))
+            .prop_map(|(swarm_list, bullet_list)| {
+                let mut swarms = Slab::new();
+                for (id, swarm) in swarm_list {
+                    swarms.insert(id, swarm);
+                }
+                let mut bullets = Bullets::new();
+                for bullet in bullet_list {
+                    bullets.push(bullet);
+                }
+                WorldState {
+                    swarms: swarms,
+                    bullets: bullets,
+                    mode: GameMode::default(),
+                    phase: MatchPhase::default(),
+                    minimap: Minimap::default(),
+                    sounds: Vec::new(),
+                    hit_markers: Vec::new(),
+                    signals: Vec::new(),
+                    ghosts: Vec::new(),
+                    checksum: None,
+                }
+            })
+            .boxed()
+    }
+}
+
+proptest! {
+    #[test]
+    fn program_round_trip(program in any::<SwarmProgram>()) {
+        let parsed: SwarmProgram = program.to_string().parse().unwrap();
+        prop_assert_eq!(parsed.commands, program.commands);
+    }
+
+    #[test]
+    fn state_round_trip(state in any::<WorldState>()) {
+        let text = serde_json::to_string(&state).unwrap();
+        let decoded: WorldState = serde_json::from_str(&text).unwrap();
+        // Swarms are compared as JSON values, where the order of IDs doesn't matter
+        prop_assert_eq!(
+            serde_json::to_value(&decoded.swarms).unwrap(),
+            serde_json::to_value(&state.swarms).unwrap()
+        );
+        // Bullets only keep their velocity, so their direction is recalculated
+        prop_assert_eq!(decoded.bullets.len(), state.bullets.len());
+        for (decoded, bullet) in decoded.bullets.iter().zip(state.bullets.iter()) {
+            prop_assert_eq!(decoded.owner, bullet.owner);
+            prop_assert_eq!((decoded.x, decoded.y), (bullet.x, bullet.y));
+            prop_assert!((decoded.direction - bullet.direction).abs() < 0.1);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use collision::Hit;
+use spatial::Cell;
+use std::mem;
+
+/// Scratch space for temporaries that only live for one tick
+/// Every buffer is emptied at the start of a tick but keeps its capacity,
+/// so once a world reaches a steady size its updates stop allocating
+#[derive(Clone, Debug, Default)]
+pub struct FrameArena {
+    /// Player IDs in the order their swarms are updated
+    pub order: Vec<usize>,
+    /// Every bullet as (cell, bullet index), sorted by cell
+    pub bullets: Vec<(Cell, usize)>,
+    /// Each cell containing bullets, with the range of its bullets in `bullets`
+    pub cells: Vec<(Cell, usize, usize)>,
+    /// Hits found in each cell, in the same order as `cells`
+    pub cell_hits: Vec<Vec<Hit>>,
+    /// Every hit found this tick, sorted
+    pub hits: Vec<Hit>,
+    /// Indices of bullets that hit something, in ascending order
+    pub spent: Vec<usize>,
+    /// (player ID, member index) of each member destroyed
+    pub dead: Vec<(usize, usize)>,
+    /// (attacker ID, victim ID) for each member destroyed, in the order they died
+    pub kills: Vec<(usize, usize)>,
+    /// (attacker ID, victim ID, damage) for each hit, in the order they were applied
+    pub damage: Vec<(usize, usize, u32)>,
+    /// (x, y) of the member each hit landed on, in the same order as damage
+    pub hit_at: Vec<(f32, f32)>,
+    /// (x, y) of each member destroyed, in the order they died
+    pub lost: Vec<(f32, f32)>,
+}
+
+/// Functions for FrameArena
+impl FrameArena {
+    /// Constructor
+    pub fn new() -> Self {
+        FrameArena::default()
+    }
+    /// Empties every buffer, keeping the allocated space
+    pub fn reset(&mut self) {
+        self.order.clear();
+        self.bullets.clear();
+        self.cells.clear();
+        // The per-cell lists are kept so their space can be reused too
+        for hits in self.cell_hits.iter_mut() {
+            hits.clear();
+        }
+        self.hits.clear();
+        self.spent.clear();
+        self.dead.clear();
+        self.kills.clear();
+        self.damage.clear();
+        self.hit_at.clear();
+        self.lost.clear();
+    }
+    /// Approximate bytes allocated by the buffers
+    pub fn memory_usage(&self) -> usize {
+        self.order.capacity() * mem::size_of::<usize>()
+            + self.bullets.capacity() * mem::size_of::<(Cell, usize)>()
+            + self.cells.capacity() * mem::size_of::<(Cell, usize, usize)>()
+            + self.cell_hits.capacity() * mem::size_of::<Vec<Hit>>()
+            + self
+                .cell_hits
+                .iter()
+                .map(|hits| hits.capacity())
+                .sum::<usize>()
+                * mem::size_of::<Hit>()
+            + self.hits.capacity() * mem::size_of::<Hit>()
+            + self.spent.capacity() * mem::size_of::<usize>()
+            + (self.dead.capacity() + self.kills.capacity()) * mem::size_of::<(usize, usize)>()
+            + self.damage.capacity() * mem::size_of::<(usize, usize, u32)>()
+            + (self.hit_at.capacity() + self.lost.capacity()) * mem::size_of::<(f32, f32)>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn reset_keeps_space() {
+        let mut arena = FrameArena::new();
+        arena.kills.extend((0..100).map(|i| (i, i + 1)));
+        arena.cell_hits.push(Vec::with_capacity(8));
+        let capacity = arena.kills.capacity();
+        arena.reset();
+        assert!(arena.kills.is_empty());
+        assert_eq!(arena.kills.capacity(), capacity);
+        assert_eq!(arena.cell_hits.len(), 1);
+        assert!(arena.cell_hits[0].capacity() >= 8);
+    }
+}
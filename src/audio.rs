@@ -0,0 +1,124 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use entity::Swarm;
+use event::WorldEvent;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use slab::Slab;
+
+/// A sound clients play
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum Sound {
+    /// A swarm fired
+    #[serde(rename = "shot")]
+    SHOT,
+    /// A swarm member was destroyed
+    #[serde(rename = "member_lost")]
+    MEMBER_LOST,
+    /// A power-up appeared
+    /// Reserved for power-ups, which no mode spawns yet
+    #[serde(rename = "power_up_spawned")]
+    POWER_UP_SPAWNED,
+    /// Both teams entered a zone
+    #[serde(rename = "zone_contested")]
+    ZONE_CONTESTED,
+}
+
+/// A sound to play at a position in the world
+/// Clients decide how loud it is from how far it is from what they're looking at
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct SoundCue {
+    /// The tick the sound happened on, so clients that see a tick twice play it once
+    pub tick: u64,
+    /// The sound
+    pub sound: Sound,
+    /// X position of the sound
+    pub x: f32,
+    /// Y position of the sound
+    pub y: f32,
+}
+
+/// Functions for SoundCue
+impl SoundCue {
+    /// Constructor
+    pub fn new(tick: u64, sound: Sound, x: f32, y: f32) -> Self {
+        SoundCue {
+            tick: tick,
+            sound: sound,
+            x: x,
+            y: y,
+        }
+    }
+}
+
+/// Turns a tick's events into sound cues
+/// events: the events of the tick
+/// lost: (x, y) of each member destroyed during the tick
+pub fn collect_cues(
+    tick: u64,
+    events: &[WorldEvent],
+    swarms: &Slab<Swarm>,
+    lost: &[(f32, f32)],
+    cues: &mut Vec<SoundCue>,
+) {
+    for event in events {
+        match *event {
+            WorldEvent::FIRED { player, .. } => {
+                if let Some(swarm) = swarms.get(&player) {
+                    cues.push(SoundCue::new(tick, Sound::SHOT, swarm.x, swarm.y));
+                }
+            }
+            WorldEvent::ZONE_CONTESTED { x, y } => {
+                cues.push(SoundCue::new(tick, Sound::ZONE_CONTESTED, x, y));
+            }
+            _ => {}
+        }
+    }
+    for &(x, y) in lost {
+        cues.push(SoundCue::new(tick, Sound::MEMBER_LOST, x, y));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cues_from_events() {
+        let mut swarms = Slab::new();
+        swarms.insert(3, Swarm::new(40.0, 60.0, 1));
+        let events = vec![
+            WorldEvent::FIRED {
+                player: 3,
+                bullets: 1,
+            },
+            WorldEvent::JOIN(4),
+            WorldEvent::ZONE_CONTESTED { x: 5.0, y: 6.0 },
+        ];
+        let mut cues = Vec::new();
+        collect_cues(9, &events, &swarms, &[(1.0, 2.0)], &mut cues);
+        assert_eq!(
+            cues,
+            vec![
+                SoundCue::new(9, Sound::SHOT, 40.0, 60.0),
+                SoundCue::new(9, Sound::ZONE_CONTESTED, 5.0, 6.0),
+                SoundCue::new(9, Sound::MEMBER_LOST, 1.0, 2.0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,252 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate serde_json;
+
+use error::{Error, StorageError};
+use game_mode::Ruleset;
+use handoff::PlayerSnapshot;
+use rules::MatchPhase;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use world_manager::WorldManager;
+
+/// Seconds between autosaves unless another interval is given
+pub const DEFAULT_AUTOSAVE_INTERVAL: u64 = 30;
+
+/// A room as it was when the server was saved
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoomSave {
+    /// ID of the room
+    pub room_id: usize,
+    /// Name of the room
+    pub name: String,
+    /// The width of the room's world
+    pub width: f32,
+    /// The height of the room's world
+    pub height: f32,
+    /// The mode the room was made with
+    #[serde(default)]
+    pub ruleset: Ruleset,
+    /// The world's tick
+    pub tick: u64,
+    /// Whether a match was being played, and since when
+    pub phase: MatchPhase,
+    /// Every player in the room
+    pub players: Vec<PlayerSnapshot>,
+}
+
+/// Every room, as it was when the server was saved
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ServerSave {
+    /// Time of the save, in seconds since the unix epoch
+    pub saved_at: u64,
+    /// Every room, in room ID order
+    pub rooms: Vec<RoomSave>,
+}
+
+/// Functions for ServerSave
+impl ServerSave {
+    /// The lowest player ID that isn't used by a saved player
+    /// New players get IDs from here on, so their resume tokens can't claim a saved player
+    pub fn next_player_id(&self) -> usize {
+        self.rooms
+            .iter()
+            .flat_map(|room| room.players.iter())
+            .map(|player| player.player_id + 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Keeps the latest save, so rooms can be restored after the server restarts
+/// Written to by the autosave thread, so implementations handle their own locking
+pub trait SaveStore: Send + Sync {
+    /// Replaces the latest save
+    fn save(&self, save: &ServerSave) -> Result<(), Error>;
+    /// The latest save, if there is one
+    fn latest(&self) -> Result<Option<ServerSave>, Error>;
+}
+
+/// The latest save kept in memory, lost when the server stops
+#[derive(Debug, Default)]
+pub struct MemorySaves {
+    /// The latest save
+    latest: RwLock<Option<ServerSave>>,
+}
+
+/// Functions for MemorySaves
+impl MemorySaves {
+    /// Constructor
+    pub fn new() -> Self {
+        MemorySaves::default()
+    }
+}
+
+/// Keeps the latest save behind a lock
+impl SaveStore for MemorySaves {
+    fn save(&self, save: &ServerSave) -> Result<(), Error> {
+        match self.latest.write() {
+            Ok(mut latest) => *latest = Some(save.clone()),
+            Err(error) => error!("Error getting write lock on saves: {}", error),
+        }
+        Ok(())
+    }
+    fn latest(&self) -> Result<Option<ServerSave>, Error> {
+        match self.latest.read() {
+            Ok(latest) => Ok(latest.clone()),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+/// The latest save kept in a JSON file
+#[derive(Debug)]
+pub struct FileSaves {
+    /// Where the save is written
+    path: PathBuf,
+}
+
+/// Functions for FileSaves
+impl FileSaves {
+    /// Constructor
+    /// path: where the save is written
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        FileSaves { path: path.into() }
+    }
+    /// Wraps an IO error with the file it happened on
+    fn io_error(&self, error: ::std::io::Error) -> Error {
+        StorageError::IO {
+            path: self.path.display().to_string(),
+            error: error,
+        }
+        .into()
+    }
+}
+
+/// Writes each save to a temporary file, then moves it over the last one,
+/// so a crash while saving can't leave a partial save behind
+impl SaveStore for FileSaves {
+    fn save(&self, save: &ServerSave) -> Result<(), Error> {
+        let temporary = self.path.with_extension("tmp");
+        fs::write(&temporary, serde_json::to_vec(save)?).map_err(|error| self.io_error(error))?;
+        fs::rename(&temporary, &self.path).map_err(|error| self.io_error(error))
+    }
+    fn latest(&self) -> Result<Option<ServerSave>, Error> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(ref error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(self.io_error(error)),
+        };
+        match serde_json::from_slice(&data) {
+            Ok(save) => Ok(Some(save)),
+            Err(error) => Err(StorageError::INVALID_SAVE {
+                path: self.path.display().to_string(),
+                reason: error.to_string(),
+            }
+            .into()),
+        }
+    }
+}
+
+/// Starts a thread that saves every room at an interval
+/// The thread stops once the manager is dropped
+/// interval: time between saves
+pub fn spawn(
+    manager: &Arc<RwLock<WorldManager>>,
+    store: Arc<SaveStore>,
+    interval: Duration,
+) -> JoinHandle<()> {
+    let manager = Arc::downgrade(manager);
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        let save = match manager.upgrade() {
+            Some(manager) => match manager.read() {
+                Ok(manager) => manager.save(),
+                Err(error) => {
+                    error!("Error getting read lock on rooms: {}", error);
+                    continue;
+                }
+            },
+            None => return,
+        };
+        match store.save(&save) {
+            Ok(_) => debug!("Saved {} rooms", save.rooms.len()),
+            Err(error) => error!("Failed to autosave: {}", error),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use world::WorldConfig;
+    use world_manager::DEFAULT_ROOM;
+
+    #[test]
+    fn save_and_restore_rooms() {
+        let mut manager = WorldManager::manual(WorldConfig::new(1000.0, 1000.0), 60).unwrap();
+        manager.set_handoff_secret(b"secret".to_vec());
+        let room_id = manager
+            .create_room("practice".into(), WorldConfig::new(400.0, 400.0))
+            .unwrap();
+        {
+            let world = manager.room(room_id).unwrap();
+            let mut world = world.write().unwrap();
+            world.add_player(5).unwrap();
+            world.swarms.get_mut(&5).unwrap().experience = 40;
+        }
+        manager.tick();
+        let token = manager.resume_token(5).unwrap();
+        let save = manager.save();
+        assert_eq!(save.rooms.len(), 2);
+        assert_eq!(save.next_player_id(), 6);
+        // Round trip the save through a file, like a restart would
+        let store = FileSaves::new(env::temp_dir().join("heroesoftheswarm-autosave-test.json"));
+        store.save(&save).unwrap();
+        let save = store.latest().unwrap().unwrap();
+        // A new server restores the rooms, and the player gets their swarm back
+        let mut restarted = WorldManager::manual(WorldConfig::new(1000.0, 1000.0), 60).unwrap();
+        restarted.set_handoff_secret(b"secret".to_vec());
+        restarted.restore(save).unwrap();
+        let world = restarted.room(room_id).unwrap();
+        assert_eq!(world.read().unwrap().tick, 1);
+        assert_eq!(
+            restarted.room(DEFAULT_ROOM).unwrap().read().unwrap().tick,
+            1
+        );
+        // Players that haven't come back yet stay in later saves
+        assert_eq!(restarted.save().rooms[1].players.len(), 1);
+        let snapshot = restarted.resume(&token).unwrap();
+        assert_eq!(snapshot.experience, 40);
+        assert!(restarted.resume(&token).is_err());
+        assert!(restarted.save().rooms[1].players.is_empty());
+    }
+
+    #[test]
+    fn missing_save() {
+        let store = FileSaves::new(env::temp_dir().join("heroesoftheswarm-missing-save.json"));
+        assert!(store.latest().unwrap().is_none());
+        let store = MemorySaves::new();
+        assert!(store.latest().unwrap().is_none());
+        store.save(&ServerSave::default()).unwrap();
+        assert_eq!(store.latest().unwrap(), Some(ServerSave::default()));
+    }
+}
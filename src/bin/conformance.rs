@@ -0,0 +1,78 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate heroesoftheswarm;
+extern crate serde_json;
+
+use heroesoftheswarm::conformance::{check, vector, VECTORS};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::process;
+
+/// Checks another implementation of the protocol against the test vectors
+/// usage: conformance vectors
+///        conformance check FILE
+/// FILE is a JSON object mapping vector names to the implementation's re-encoding of each vector
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.iter().map(|arg| arg.as_str()).collect::<Vec<&str>>().as_slice() {
+        ["vectors"] => match serde_json::to_string_pretty(VECTORS) {
+            Ok(text) => println!("{}", text),
+            Err(error) => {
+                eprintln!("Failed to write the vectors: {}", error);
+                process::exit(1);
+            }
+        },
+        ["check", path] => {
+            let outputs: BTreeMap<String, String> = match File::open(path)
+                .map_err(|error| error.to_string())
+                .and_then(|file| serde_json::from_reader(file).map_err(|error| error.to_string()))
+            {
+                Ok(outputs) => outputs,
+                Err(error) => {
+                    eprintln!("{}: {}", path, error);
+                    process::exit(2);
+                }
+            };
+            let mut failed = false;
+            for (name, output) in outputs.iter() {
+                let result = match vector(name) {
+                    Some(vector) => check(vector, output).map_err(|error| error.to_string()),
+                    None => Err("no vector has this name".into()),
+                };
+                match result {
+                    Ok(()) => println!("PASS {}", name),
+                    Err(error) => {
+                        failed = true;
+                        println!("FAIL {}: {}", name, error);
+                    }
+                }
+            }
+            let skipped = VECTORS
+                .iter()
+                .filter(|vector| !outputs.contains_key(vector.name))
+                .count();
+            println!("{} checked, {} skipped", outputs.len(), skipped);
+            if failed {
+                process::exit(1);
+            }
+        }
+        _ => {
+            eprintln!("usage: conformance vectors | conformance check FILE");
+            process::exit(2);
+        }
+    }
+}
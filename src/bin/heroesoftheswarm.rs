@@ -16,13 +16,167 @@
 extern crate env_logger;
 extern crate heroesoftheswarm;
 
-use heroesoftheswarm::server;
+use heroesoftheswarm::server::{self, ServerConfig};
+#[cfg(feature = "tls")]
+use heroesoftheswarm::tls::{SniCertificate, TlsConfig};
+#[cfg(feature = "webhooks")]
+use heroesoftheswarm::webhook::WebhookConfig;
+use std::env;
+use std::time::Duration;
 
 fn main() {
     // Initialize the logger
     env_logger::init();
+    // Read options from the command line
+    let mut config = ServerConfig::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // Read admin commands from stdin
+            "--console" => config.console = true,
+            // Accept admin commands on a local TCP address
+            "--console-address" => match args.next() {
+                Some(address) => config.console_address = Some(address),
+                None => {
+                    eprintln!("--console-address requires an address");
+                    return;
+                }
+            },
+            // Accept control plane (JSON-RPC) connections on a TCP address
+            "--control-address" => match args.next() {
+                Some(address) => config.control_address = Some(address),
+                None => {
+                    eprintln!("--control-address requires an address");
+                    return;
+                }
+            },
+            // Secret shared with sibling servers for handing off players
+            "--handoff-secret" => match args.next() {
+                Some(secret) => config.handoff_secret = Some(secret),
+                None => {
+                    eprintln!("--handoff-secret requires a secret");
+                    return;
+                }
+            },
+            // Longest an update should take, in milliseconds
+            "--tick-budget" => match args.next().map(|budget| budget.parse::<u64>()) {
+                Some(Ok(budget)) => config.tick_budget = Some(Duration::from_millis(budget)),
+                _ => {
+                    eprintln!("--tick-budget requires a number of milliseconds");
+                    return;
+                }
+            },
+            // Skip optional work in rooms that keep going over budget
+            "--shed-load" => config.shed_load = true,
+            // Log anonymous gameplay metrics, one report per some number of ticks
+            "--telemetry" => match args.next().map(|interval| interval.parse::<u64>()) {
+                Some(Ok(interval)) => config.telemetry = Some(interval),
+                _ => {
+                    eprintln!("--telemetry requires a number of ticks");
+                    return;
+                }
+            },
+            // Save every room to a file periodically
+            "--autosave" => match args.next() {
+                Some(path) => config.autosave = Some(path),
+                None => {
+                    eprintln!("--autosave requires a path");
+                    return;
+                }
+            },
+            // Seconds between autosaves
+            "--autosave-interval" => match args.next().map(|interval| interval.parse::<u64>()) {
+                Some(Ok(interval)) if interval > 0 => {
+                    config.autosave_interval = Duration::from_secs(interval)
+                }
+                _ => {
+                    eprintln!("--autosave-interval requires a number of seconds");
+                    return;
+                }
+            },
+            // Restore the rooms in the autosave file
+            "--restore" => config.restore = true,
+            // POST match lifecycle events to a URL
+            #[cfg(feature = "webhooks")]
+            "--webhook" => match args.next() {
+                Some(url) => config
+                    .webhooks
+                    .get_or_insert_with(WebhookConfig::default)
+                    .urls
+                    .push(url),
+                None => {
+                    eprintln!("--webhook requires a URL");
+                    return;
+                }
+            },
+            // Experience totals to report as milestones, separated by commas
+            #[cfg(feature = "webhooks")]
+            "--milestones" => {
+                let milestones: Result<Vec<i64>, _> = match args.next() {
+                    Some(milestones) => milestones.split(',').map(|m| m.trim().parse()).collect(),
+                    None => {
+                        eprintln!("--milestones requires a list of experience totals");
+                        return;
+                    }
+                };
+                match milestones {
+                    Ok(milestones) => {
+                        config
+                            .webhooks
+                            .get_or_insert_with(WebhookConfig::default)
+                            .milestones = milestones
+                    }
+                    Err(error) => {
+                        eprintln!("Invalid milestones: {}", error);
+                        return;
+                    }
+                }
+            }
+            // Serve wss:// with a certificate chain and private key
+            #[cfg(feature = "tls")]
+            "--tls" => match (args.next(), args.next()) {
+                (Some(cert_path), Some(key_path)) => {
+                    config.tls = Some(TlsConfig::new(cert_path, key_path))
+                }
+                _ => {
+                    eprintln!("--tls requires a certificate path and a key path");
+                    return;
+                }
+            },
+            // Hostname of the main certificate, needed when using SNI
+            #[cfg(feature = "tls")]
+            "--tls-hostname" => match (config.tls.as_mut(), args.next()) {
+                (Some(tls), Some(hostname)) => tls.hostname = Some(hostname),
+                _ => {
+                    eprintln!("--tls-hostname requires --tls and a hostname");
+                    return;
+                }
+            },
+            // Serve an extra certificate to clients asking for a hostname
+            #[cfg(feature = "tls")]
+            "--tls-sni" => match (config.tls.as_mut(), args.next(), args.next(), args.next()) {
+                (Some(tls), Some(hostname), Some(cert_path), Some(key_path)) => {
+                    tls.sni.push(SniCertificate {
+                        hostname: hostname,
+                        cert_path: cert_path,
+                        key_path: key_path,
+                    })
+                }
+                _ => {
+                    eprintln!(
+                        "--tls-sni requires --tls, a hostname, a certificate path, and a key path"
+                    );
+                    return;
+                }
+            },
+            _ => {
+                eprintln!("Unrecognized argument: {}", arg);
+                return;
+            }
+        }
+    }
     // Initialize a server
     //let game_server = server::GameServer::new("127.0.0.1", 5977, 1000.0, 1000.0, 1);
     //TODO: change this once server is an object
-    server::run();
+    server::run(config);
 }
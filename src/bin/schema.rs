@@ -0,0 +1,31 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate heroesoftheswarm;
+
+use heroesoftheswarm::schema;
+use std::process;
+
+/// Prints the JSON Schema of the client protocol
+/// TypeScript definitions can be generated from it with json-schema-to-typescript
+fn main() {
+    match schema::protocol_schema() {
+        Ok(schema) => println!("{}", schema),
+        Err(error) => {
+            eprintln!("Failed to generate schema: {}", error);
+            process::exit(1);
+        }
+    }
+}
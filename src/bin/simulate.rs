@@ -0,0 +1,176 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate heroesoftheswarm;
+extern crate serde_json;
+
+use heroesoftheswarm::render;
+use heroesoftheswarm::simulation::Match;
+use heroesoftheswarm::swarm_language::SwarmProgram;
+use heroesoftheswarm::world::WorldConfig;
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::process;
+
+/// Reads and parses a program from a file
+fn load_program(path: &str) -> Result<SwarmProgram, String> {
+    let mut source = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut source))
+        .map_err(|error| format!("{}: {}", path, error))?;
+    source.parse().map_err(|error| format!("{}: {}", path, error))
+}
+
+/// Parses the value of a numeric option, or exits
+fn parse_option<T: std::str::FromStr>(option: &str, value: Option<String>) -> T {
+    match value.map(|value| value.parse()) {
+        Some(Ok(value)) => value,
+        _ => {
+            eprintln!("{} requires a number", option);
+            process::exit(2);
+        }
+    }
+}
+
+/// Draws the world to a file, as SVG, PNG, or PPM depending on the extension
+fn write_frame(path: &str, game: &Match, config: &WorldConfig) -> Result<(), String> {
+    let state = game.world.get_state();
+    let mut file = File::create(path).map_err(|error| format!("{}: {}", path, error))?;
+    let written = if path.ends_with(".svg") {
+        file.write_all(render::render_svg(&state, config.width, config.height).as_bytes())
+    } else if path.ends_with(".png") {
+        write_png(&render::render_image(&state, config.width, config.height, 1.0), &mut file)
+    } else {
+        render::render_image(&state, config.width, config.height, 1.0).write_ppm(&mut file)
+    };
+    written.map_err(|error| format!("{}: {}", path, error))
+}
+
+/// Writes an image as a PNG
+#[cfg(feature = "render_png")]
+fn write_png(image: &render::Image, file: &mut File) -> std::io::Result<()> {
+    image.write_png(file)
+}
+
+/// PNG frames need the render_png feature
+#[cfg(not(feature = "render_png"))]
+fn write_png(_: &render::Image, _: &mut File) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Other,
+        "PNG frames need the render_png feature",
+    ))
+}
+
+/// Pits programs against each other in a deterministic world and prints the scores
+/// usage: simulate [--seed N] [--ticks K] [--size W H] [--replay FILE] [--frame FILE] PROGRAM...
+fn main() {
+    let mut seed: u64 = 0;
+    let mut ticks: u64 = 3600;
+    let mut config = WorldConfig::new(1000.0, 1000.0);
+    let mut replay_path: Option<String> = None;
+    let mut frame_path: Option<String> = None;
+    let mut paths: Vec<String> = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => seed = parse_option("--seed", args.next()),
+            "--ticks" => ticks = parse_option("--ticks", args.next()),
+            "--size" => {
+                config.width = parse_option("--size", args.next());
+                config.height = parse_option("--size", args.next());
+            }
+            // Write the world state after every tick, one JSON line per tick
+            "--replay" => match args.next() {
+                Some(path) => replay_path = Some(path),
+                None => {
+                    eprintln!("--replay requires a path");
+                    process::exit(2);
+                }
+            },
+            // Draw the final state of the world
+            "--frame" => match args.next() {
+                Some(path) => frame_path = Some(path),
+                None => {
+                    eprintln!("--frame requires a path");
+                    process::exit(2);
+                }
+            },
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("usage: simulate [--seed N] [--ticks K] [--size W H] [--replay FILE] [--frame FILE] PROGRAM...");
+        process::exit(2);
+    }
+    let programs: Vec<SwarmProgram> = match paths.iter().map(|path| load_program(path)).collect() {
+        Ok(programs) => programs,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+    let mut game = match Match::new(&programs, &config, seed) {
+        Ok(game) => game,
+        Err(error) => {
+            eprintln!("Failed to start the match: {}", error);
+            process::exit(1);
+        }
+    };
+    let mut replay = match replay_path {
+        Some(ref path) => match File::create(path) {
+            Ok(file) => Some(BufWriter::new(file)),
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+    for _ in 0..ticks {
+        game.tick();
+        if let Some(ref mut replay) = replay {
+            let written = serde_json::to_writer(&mut *replay, &game.world.state_ref())
+                .map_err(|error| error.to_string())
+                .and_then(|_| replay.write_all(b"\n").map_err(|error| error.to_string()));
+            if let Err(error) = written {
+                eprintln!("Failed to write replay: {}", error);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(ref mut replay) = replay {
+        if let Err(error) = replay.flush() {
+            eprintln!("Failed to write replay: {}", error);
+            process::exit(1);
+        }
+    }
+    if let Some(ref path) = frame_path {
+        if let Err(error) = write_frame(path, &game, &config) {
+            eprintln!("Failed to write frame: {}", error);
+            process::exit(1);
+        }
+    }
+    println!("{:<6}{:<12}{:<10}{}", "Rank", "Experience", "Members", "Program");
+    for (rank, score) in game.scores().iter().enumerate() {
+        println!(
+            "{:<6}{:<12}{:<10}{}",
+            rank + 1,
+            score.experience,
+            score.members,
+            paths[score.player_id]
+        );
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate heroesoftheswarm;
+extern crate serde_json;
+extern crate websocket;
+
+use heroesoftheswarm::client::{ServerMessage, ServerMessageContents, WorldState};
+use heroesoftheswarm::spectator::{draw_map, scoreboard, MapStyle, Viewport};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+use websocket::{ClientBuilder, OwnedMessage};
+
+/// Options for drawing the world
+struct Display {
+    /// The part of the world to show
+    viewport: Viewport,
+    /// How to draw the map
+    style: MapStyle,
+    /// Whether to color the map
+    color: bool,
+    /// Player whose swarm the map scrolls to follow, if any
+    follow: Option<usize>,
+    /// Shortest time between frames
+    frame_time: Duration,
+}
+
+/// Functions for Display
+impl Display {
+    /// Clears the terminal and draws a frame
+    fn draw(&mut self, state: &WorldState) -> io::Result<()> {
+        if let Some(player_id) = self.follow {
+            self.viewport.follow(state, player_id);
+        }
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        // Move to the top left and clear the screen
+        write!(out, "\x1b[H\x1b[2J")?;
+        for line in draw_map(state, &self.viewport, self.style, self.color) {
+            writeln!(out, "{}", line)?;
+        }
+        writeln!(out)?;
+        for line in scoreboard(state, 10) {
+            writeln!(out, "{}", line)?;
+        }
+        out.flush()
+    }
+}
+
+/// Parses the value of a numeric option, or exits
+fn parse_option<T: std::str::FromStr>(option: &str, value: Option<String>) -> T {
+    match value.map(|value| value.parse()) {
+        Some(Ok(value)) => value,
+        _ => {
+            eprintln!("{} requires a number", option);
+            process::exit(2);
+        }
+    }
+}
+
+/// Plays back a replay written by simulate --replay, one frame per line
+fn play_replay(path: &str, display: &mut Display) -> Result<(), String> {
+    let file = File::open(path).map_err(|error| format!("{}: {}", path, error))?;
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|error| format!("{}: {}", path, error))?;
+        let state: WorldState = serde_json::from_str(&line)
+            .map_err(|error| format!("{}:{}: {}", path, number + 1, error))?;
+        display.draw(&state).map_err(|error| error.to_string())?;
+        thread::sleep(display.frame_time);
+    }
+    Ok(())
+}
+
+/// Watches a running server until it closes the connection
+/// The server gives every connection a swarm, so the spectator shows up as an idle player
+fn watch_server(url: &str, display: &mut Display) -> Result<(), String> {
+    let mut client = ClientBuilder::new(url)
+        .map_err(|error| format!("{}: {}", url, error))?
+        .add_protocol("heroesoftheswarm")
+        .connect_insecure()
+        .map_err(|error| format!("{}: {}", url, error))?;
+    let mut last_frame: Option<Instant> = None;
+    loop {
+        let text = match client.recv_message() {
+            Ok(OwnedMessage::Text(text)) => text,
+            Ok(OwnedMessage::Close(_)) => return Ok(()),
+            Ok(_) => continue,
+            Err(error) => return Err(format!("{}: {}", url, error)),
+        };
+        match ServerMessage::parse(&text).map(|message| message.message) {
+            Ok(ServerMessageContents::WORLD(state)) => {
+                // Skip frames that arrive faster than they can be watched
+                if last_frame.map_or(false, |last| last.elapsed() < display.frame_time) {
+                    continue;
+                }
+                last_frame = Some(Instant::now());
+                display.draw(&state).map_err(|error| error.to_string())?;
+            }
+            Ok(ServerMessageContents::HANDOFF(redirect)) => {
+                return Err(format!("The server handed off to {}", redirect.url));
+            }
+            Ok(_) => (),
+            Err(error) => return Err(format!("Failed to decode a message: {}", error)),
+        }
+    }
+}
+
+/// Draws a running match, or a replay, in the terminal
+/// usage: spectate [--size W H] [--view COLUMNS ROWS] [--follow ID] [--fps N] [--ascii] [--no-color] (URL | --replay FILE)
+fn main() {
+    let mut size = (1600.0, 900.0);
+    let mut view = (80, 24);
+    let mut follow: Option<usize> = None;
+    let mut fps: u64 = 10;
+    let mut style = MapStyle::BRAILLE;
+    let mut color = true;
+    let mut replay_path: Option<String> = None;
+    let mut url: Option<String> = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => {
+                size.0 = parse_option("--size", args.next());
+                size.1 = parse_option("--size", args.next());
+            }
+            "--view" => {
+                view.0 = parse_option("--view", args.next());
+                view.1 = parse_option("--view", args.next());
+            }
+            "--follow" => follow = Some(parse_option("--follow", args.next())),
+            "--fps" => fps = parse_option::<u64>("--fps", args.next()).max(1),
+            "--ascii" => style = MapStyle::ASCII,
+            "--no-color" => color = false,
+            "--replay" => match args.next() {
+                Some(path) => replay_path = Some(path),
+                None => {
+                    eprintln!("--replay requires a path");
+                    process::exit(2);
+                }
+            },
+            _ => url = Some(arg),
+        }
+    }
+    let mut display = Display {
+        viewport: Viewport::fit(size.0, size.1, view.0, view.1),
+        style: style,
+        color: color,
+        follow: follow,
+        frame_time: Duration::from_millis(1000 / fps),
+    };
+    let result = match (replay_path, url) {
+        (Some(path), None) => play_replay(&path, &mut display),
+        (None, Some(url)) => watch_server(&url, &mut display),
+        _ => {
+            eprintln!("usage: spectate [--size W H] [--view COLUMNS ROWS] [--follow ID] [--fps N] [--ascii] [--no-color] (URL | --replay FILE)");
+            process::exit(2);
+        }
+    };
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,134 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate heroesoftheswarm;
+extern crate serde_json;
+
+use heroesoftheswarm::swarm_language::{check_program, SwarmProgram, OPCODES};
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::process;
+
+/// What to print for a program that checks out
+enum Emit {
+    /// Nothing, only diagnostics
+    NOTHING,
+    /// The commands as JSON
+    JSON,
+    /// The program rewritten in canonical swarm code
+    SWARM,
+    /// How complex and aggressive the program is, as JSON
+    PROFILE,
+}
+
+/// Reads a program from a file, or from stdin if the path is "-"
+fn read_source(path: &str) -> io::Result<String> {
+    let mut source = String::new();
+    if path == "-" {
+        io::stdin().read_to_string(&mut source)?;
+    } else {
+        File::open(path)?.read_to_string(&mut source)?;
+    }
+    Ok(source)
+}
+
+/// Checks swarm programs and prints a diagnostic for each problem, with its line number
+/// Exits with 1 if any program has problems, or 2 if a file couldn't be read
+/// usage: swarmc [--emit json|swarm|profile] FILE...
+///        swarmc --syntax, to print every opcode as JSON, for editors
+fn main() {
+    let mut emit = Emit::NOTHING;
+    let mut paths: Vec<String> = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // Print each program that checks out
+            "--emit" => match args.next().as_ref().map(|format| format.as_str()) {
+                Some("json") => emit = Emit::JSON,
+                Some("swarm") => emit = Emit::SWARM,
+                Some("profile") => emit = Emit::PROFILE,
+                _ => {
+                    eprintln!("--emit requires json, swarm or profile");
+                    process::exit(2);
+                }
+            },
+            // Describe the language instead of checking programs
+            "--syntax" => match serde_json::to_string_pretty(OPCODES) {
+                Ok(json) => {
+                    println!("{}", json);
+                    return;
+                }
+                Err(error) => {
+                    eprintln!("Failed to describe the language: {}", error);
+                    process::exit(2);
+                }
+            },
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("usage: swarmc [--emit json|swarm|profile] FILE... | swarmc --syntax");
+        process::exit(2);
+    }
+    let mut failed = false;
+    for path in paths.iter() {
+        let source = match read_source(path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                process::exit(2);
+            }
+        };
+        let diagnostics = check_program(&source);
+        if !diagnostics.is_empty() {
+            failed = true;
+            for diagnostic in diagnostics.iter() {
+                eprintln!("{}:{}: {}", path, diagnostic.line, diagnostic.error);
+            }
+            continue;
+        }
+        let program: SwarmProgram = match source.parse() {
+            Ok(program) => program,
+            Err(error) => {
+                // Every line checked out, so this shouldn't happen
+                eprintln!("{}: {}", path, error);
+                failed = true;
+                continue;
+            }
+        };
+        match emit {
+            Emit::NOTHING => {}
+            Emit::JSON => match serde_json::to_string_pretty(&program.commands) {
+                Ok(json) => println!("{}", json),
+                Err(error) => {
+                    eprintln!("{}: {}", path, error);
+                    failed = true;
+                }
+            },
+            Emit::SWARM => print!("{}", program),
+            Emit::PROFILE => match serde_json::to_string_pretty(&program.profile()) {
+                Ok(json) => println!("{}", json),
+                Err(error) => {
+                    eprintln!("{}: {}", path, error);
+                    failed = true;
+                }
+            },
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use entity::Swarm;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+
+/// What happens to swarms at the edge of the world
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum Boundary {
+    /// Swarms stop at the edge
+    #[serde(rename = "clamp")]
+    CLAMP,
+    /// Swarms reflect their heading off the edge, like bullets off a wall
+    #[serde(rename = "bounce")]
+    BOUNCE,
+    /// Swarms may go past a soft boundary inside the edge, but every member
+    /// loses health each tick they're past it; they still stop at the edge
+    #[serde(rename = "damage")]
+    DAMAGE {
+        /// Distance from the edge to the soft boundary
+        margin: f32,
+        /// Health each member loses per tick past the soft boundary
+        damage: i32,
+    },
+}
+
+/// Functions for Boundary
+impl Boundary {
+    /// Keeps a swarm that has just moved inside a world of the given size
+    /// Positions that aren't finite are left for World::sanitize to report
+    /// return: whether any of the swarm's members were damaged
+    pub fn confine(&self, swarm: &mut Swarm, width: f32, height: f32) -> bool {
+        if !swarm.x.is_finite() || !swarm.y.is_finite() {
+            return false;
+        }
+        match *self {
+            Boundary::CLAMP => {
+                clamp(swarm, width, height);
+                false
+            }
+            Boundary::BOUNCE => {
+                if swarm.x < 0.0 || swarm.x > width {
+                    swarm.x = reflect(swarm.x, width);
+                    turn_to(swarm, |direction| 180.0 - direction);
+                }
+                if swarm.y < 0.0 || swarm.y > height {
+                    swarm.y = reflect(swarm.y, height);
+                    turn_to(swarm, |direction| -direction);
+                }
+                false
+            }
+            Boundary::DAMAGE { margin, damage } => {
+                clamp(swarm, width, height);
+                let outside = swarm.x < margin
+                    || swarm.x > width - margin
+                    || swarm.y < margin
+                    || swarm.y > height - margin;
+                if !outside || damage <= 0 || swarm.members.is_empty() {
+                    return false;
+                }
+                for member in swarm.members.iter_mut() {
+                    member.health -= damage;
+                }
+                swarm.members.retain(|member| member.health > 0);
+                true
+            }
+        }
+    }
+}
+
+/// Swarms stop at the edge unless told otherwise
+impl Default for Boundary {
+    fn default() -> Self {
+        Boundary::CLAMP
+    }
+}
+
+/// Moves a swarm back onto the nearest point of the world
+fn clamp(swarm: &mut Swarm, width: f32, height: f32) {
+    swarm.x = swarm.x.max(0.0).min(width);
+    swarm.y = swarm.y.max(0.0).min(height);
+}
+
+/// Mirrors a coordinate that went past 0 or the edge back inside
+fn reflect(position: f32, edge: f32) -> f32 {
+    let reflected = if position < 0.0 {
+        -position
+    } else {
+        2.0 * edge - position
+    };
+    reflected.max(0.0).min(edge)
+}
+
+/// Turns a swarm and its members by the same reflection
+fn turn_to<F: Fn(f32) -> f32>(swarm: &mut Swarm, reflection: F) {
+    swarm.direction = reflection(swarm.direction) % 360.0;
+    for member in swarm.members.iter_mut() {
+        member.direction = reflection(member.direction) % 360.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounce_reflects_heading() {
+        let mut swarm = Swarm::new(99.0, 50.0, 1);
+        swarm.x = 102.0;
+        swarm.direction = 30.0;
+        Boundary::BOUNCE.confine(&mut swarm, 100.0, 100.0);
+        assert_eq!(swarm.x, 98.0);
+        assert_eq!(swarm.direction, 150.0);
+        assert_eq!(swarm.members[0].direction, 180.0);
+
+        swarm.y = -4.0;
+        Boundary::BOUNCE.confine(&mut swarm, 100.0, 100.0);
+        assert_eq!(swarm.y, 4.0);
+        assert_eq!(swarm.direction, -150.0);
+    }
+
+    #[test]
+    fn damage_outside_soft_boundary() {
+        let boundary = Boundary::DAMAGE {
+            margin: 10.0,
+            damage: 2,
+        };
+        let mut swarm = Swarm::new(50.0, 50.0, 2);
+        assert!(!boundary.confine(&mut swarm, 100.0, 100.0));
+        assert_eq!(swarm.members[0].health, 5);
+
+        swarm.x = -3.0;
+        assert!(boundary.confine(&mut swarm, 100.0, 100.0));
+        assert_eq!(swarm.x, 0.0);
+        assert_eq!(swarm.members[0].health, 3);
+        boundary.confine(&mut swarm, 100.0, 100.0);
+        boundary.confine(&mut swarm, 100.0, 100.0);
+        assert!(swarm.members.is_empty());
+        assert!(!boundary.confine(&mut swarm, 100.0, 100.0));
+    }
+}
@@ -0,0 +1,248 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use entity::{is_npc, Swarm};
+use event::WorldEvent;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use slab::Slab;
+use std::mem;
+
+/// A bounty put on the leader, claimed by whoever wipes out their swarm,
+/// so long free for all matches don't settle into one player staying on top
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Bounty {
+    /// Least experience the leader needs before a bounty is put on them
+    pub min_lead: i64,
+    /// Ticks the leader has to stay in the lead for each point of their bounty
+    pub interval: u64,
+    /// Largest bounty
+    pub max: i64,
+}
+
+/// Functions for Bounty
+impl Bounty {
+    /// Constructor
+    /// Adds a point to the bounty of a leader with at least 20 experience
+    /// every 300 ticks, up to 10
+    pub fn new() -> Self {
+        Bounty {
+            min_lead: 20,
+            interval: 300,
+            max: 10,
+        }
+    }
+    /// Grows the leader's bounty and takes the bounty from everyone else
+    /// Nobody leads while players are tied, and computer-controlled swarms never lead
+    /// return: whether any player's bounty changed
+    pub fn apply(&self, swarms: &mut Slab<Swarm>, tick: u64) -> bool {
+        let best = swarms
+            .iter()
+            .filter(|&(id, _)| !is_npc(*id))
+            .map(|(_, swarm)| swarm.experience)
+            .max()
+            .unwrap_or(0);
+        let leaders: Vec<usize> = swarms
+            .iter()
+            .filter(|&(id, swarm)| !is_npc(*id) && swarm.experience == best)
+            .map(|(id, _)| *id)
+            .collect();
+        let leader = if leaders.len() == 1 && best >= self.min_lead {
+            leaders.first().cloned()
+        } else {
+            None
+        };
+        let grows = tick % self.interval.max(1) == 0;
+        let mut changed = false;
+        for (id, swarm) in swarms.iter_mut() {
+            let bounty = if Some(*id) == leader {
+                if grows {
+                    (swarm.bounty + 1).min(self.max)
+                } else {
+                    swarm.bounty
+                }
+            } else {
+                0
+            };
+            if swarm.bounty != bounty {
+                swarm.bounty = bounty;
+                swarm.dirty.scored = true;
+                changed = true;
+            }
+        }
+        changed
+    }
+    /// Gives the victim's bounty to the attacker, if the attacker wiped out the victim's swarm
+    /// return: the bounty claimed
+    pub fn claim(
+        &self,
+        swarms: &mut Slab<Swarm>,
+        attacker: usize,
+        victim: usize,
+        events: &mut Vec<WorldEvent>,
+    ) -> i64 {
+        if attacker == victim || !swarms.contains_key(&attacker) {
+            return 0;
+        }
+        let bounty = match swarms.get_mut(&victim) {
+            Some(swarm) => {
+                if !swarm.members.is_empty() || swarm.bounty <= 0 {
+                    return 0;
+                }
+                swarm.dirty.scored = true;
+                mem::replace(&mut swarm.bounty, 0)
+            }
+            None => return 0,
+        };
+        if let Some(swarm) = swarms.get_mut(&attacker) {
+            swarm.add_experience(&bounty);
+        }
+        events.push(WorldEvent::BOUNTY_CLAIMED {
+            player: attacker,
+            victim: victim,
+            bounty: bounty,
+        });
+        bounty
+    }
+}
+
+/// The default bounty is small next to the experience it takes to lead
+impl Default for Bounty {
+    fn default() -> Self {
+        Bounty::new()
+    }
+}
+
+/// Experience lost slowly by players that stop scoring, so nobody can sit on a lead
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct ScoreDecay {
+    /// Ticks a player can go without scoring before their experience decays
+    pub grace: u64,
+    /// Ticks between each point of experience lost after that
+    pub interval: u64,
+}
+
+/// Functions for ScoreDecay
+impl ScoreDecay {
+    /// Constructor
+    /// Takes a point every 120 ticks from players that haven't scored in 1800 ticks
+    pub fn new() -> Self {
+        ScoreDecay {
+            grace: 1800,
+            interval: 120,
+        }
+    }
+    /// Counts another tick without scoring for every player, and decays idle experience
+    /// Experience never decays below 0, and computer-controlled swarms never decay
+    /// return: whether any player's experience changed
+    pub fn apply(&self, swarms: &mut Slab<Swarm>) -> bool {
+        let mut changed = false;
+        for (id, swarm) in swarms.iter_mut().filter(|&(id, _)| !is_npc(*id)) {
+            swarm.ticks_since_scored += 1;
+            if swarm.experience > 0
+                && swarm.ticks_since_scored > self.grace
+                && (swarm.ticks_since_scored - self.grace) % self.interval.max(1) == 0
+            {
+                debug!("Player {} lost experience for being idle", id);
+                swarm.experience -= 1;
+                swarm.dirty.scored = true;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// The default decay only catches players idle for a long time
+impl Default for ScoreDecay {
+    fn default() -> Self {
+        ScoreDecay::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounty_on_leader() {
+        let bounty = Bounty {
+            min_lead: 10,
+            interval: 2,
+            max: 2,
+        };
+        let mut swarms = Slab::new();
+        swarms.insert(1, Swarm::new(0.0, 0.0, 1));
+        swarms.insert(2, Swarm::new(0.0, 0.0, 1));
+        swarms.get_mut(&1).unwrap().experience = 10;
+        swarms.get_mut(&2).unwrap().experience = 10;
+        // Tied players don't lead
+        assert!(!bounty.apply(&mut swarms, 0));
+        swarms.get_mut(&1).unwrap().experience = 12;
+        for tick in 0..6 {
+            bounty.apply(&mut swarms, tick);
+        }
+        assert_eq!(swarms[&1].bounty, 2);
+        assert_eq!(swarms[&2].bounty, 0);
+        // Only wiping out the swarm claims the bounty
+        let mut events = Vec::new();
+        assert_eq!(bounty.claim(&mut swarms, 2, 1, &mut events), 0);
+        swarms.get_mut(&1).unwrap().members.clear();
+        assert_eq!(bounty.claim(&mut swarms, 2, 1, &mut events), 2);
+        assert_eq!(swarms[&1].bounty, 0);
+        assert_eq!(swarms[&2].experience, 12);
+        assert_eq!(
+            events,
+            vec![WorldEvent::BOUNTY_CLAIMED {
+                player: 2,
+                victim: 1,
+                bounty: 2,
+            }]
+        );
+        // Losing the lead takes the bounty away
+        swarms.get_mut(&1).unwrap().experience = 0;
+        assert!(bounty.apply(&mut swarms, 6));
+        assert!(!bounty.apply(&mut swarms, 7));
+        assert_eq!(swarms[&2].bounty, 1);
+        swarms.get_mut(&1).unwrap().experience = 20;
+        assert!(bounty.apply(&mut swarms, 9));
+        assert_eq!(swarms[&2].bounty, 0);
+    }
+
+    #[test]
+    fn decay_idle_scores() {
+        let decay = ScoreDecay {
+            grace: 3,
+            interval: 2,
+        };
+        let mut swarms = Slab::new();
+        swarms.insert(1, Swarm::new(0.0, 0.0, 1));
+        swarms.get_mut(&1).unwrap().experience = 5;
+        for _ in 0..3 {
+            assert!(!decay.apply(&mut swarms));
+        }
+        assert!(!decay.apply(&mut swarms));
+        assert!(decay.apply(&mut swarms));
+        assert_eq!(swarms[&1].experience, 4);
+        // Scoring resets the grace period
+        swarms.get_mut(&1).unwrap().add_experience(&1);
+        for _ in 0..4 {
+            assert!(!decay.apply(&mut swarms));
+        }
+        assert_eq!(swarms[&1].experience, 5);
+    }
+}
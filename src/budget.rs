@@ -0,0 +1,237 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use std::time::{Duration, Instant};
+
+/// Tracks how long updates take compared to a per-tick time budget
+/// A world that goes over budget several updates in a row is overloaded,
+/// and can shed optional work until it catches up
+#[derive(Clone, Debug)]
+pub struct TickBudget {
+    /// Longest an update should take
+    pub limit: Duration,
+    /// Number of consecutive updates over the limit before the world is overloaded
+    pub overload_threshold: u32,
+    /// Whether to skip optional work while overloaded
+    pub shed_load: bool,
+    /// Number of consecutive updates over the limit
+    consecutive: u32,
+    /// Total number of updates over the limit
+    total: u64,
+    /// Total number of ticks skipped because updates fell too far behind
+    skipped: u64,
+}
+
+/// Functions for TickBudget
+impl TickBudget {
+    /// Number of consecutive updates over the limit before the world is overloaded, by default
+    pub const DEFAULT_OVERLOAD_THRESHOLD: u32 = 10;
+
+    /// Constructor
+    /// limit: longest an update should take
+    pub fn new(limit: Duration) -> Self {
+        TickBudget {
+            limit: limit,
+            overload_threshold: TickBudget::DEFAULT_OVERLOAD_THRESHOLD,
+            shed_load: false,
+            consecutive: 0,
+            total: 0,
+            skipped: 0,
+        }
+    }
+    /// Budget of one tick at the given number of updates per second
+    pub fn from_update_freq(update_freq: u64) -> Self {
+        TickBudget::new(Duration::from_micros(1000000 / update_freq))
+    }
+    /// Enables or disables load shedding
+    pub fn with_shed_load(mut self, shed_load: bool) -> Self {
+        self.shed_load = shed_load;
+        self
+    }
+    /// Records how long an update took
+    pub fn record(&mut self, elapsed: Duration) {
+        if elapsed > self.limit {
+            self.consecutive += 1;
+            self.total += 1;
+            if self.consecutive == self.overload_threshold {
+                warn!(
+                    "Overloaded: {} updates in a row took longer than {}us{}",
+                    self.consecutive,
+                    self.limit.as_secs() * 1000000 + self.limit.subsec_micros() as u64,
+                    if self.shed_load { ". Shedding load" } else { "" }
+                );
+            }
+        } else {
+            if self.is_overloaded() {
+                info!("Recovered from overload after {} updates", self.consecutive);
+            }
+            self.consecutive = 0;
+        }
+    }
+    /// Whether enough consecutive updates went over the limit
+    pub fn is_overloaded(&self) -> bool {
+        self.consecutive >= self.overload_threshold
+    }
+    /// Whether optional work should be skipped this tick
+    pub fn is_shedding(&self) -> bool {
+        self.shed_load && self.is_overloaded()
+    }
+    /// Total number of updates over the limit
+    pub fn total_over_budget(&self) -> u64 {
+        self.total
+    }
+    /// Records ticks that were dropped instead of being caught up on
+    pub fn record_skipped(&mut self, skipped: u64) {
+        self.skipped += skipped;
+    }
+    /// Total number of ticks skipped
+    pub fn total_skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+/// Schedules updates at a fixed rate
+/// Updates that run late are caught up on, a few at a time, so the world keeps its pace
+/// If the world falls further behind than that, the missed ticks are dropped and the
+/// schedule restarts from now, rather than every update making the next one later still
+#[derive(Clone, Debug)]
+pub struct TickClock {
+    /// Time between updates
+    pub interval: Duration,
+    /// Most updates to run at once when behind
+    pub max_catch_up: u32,
+    /// When the next update is due
+    next: Instant,
+}
+
+/// Functions for TickClock
+impl TickClock {
+    /// Most updates to run at once when behind, by default
+    pub const DEFAULT_MAX_CATCH_UP: u32 = 5;
+
+    /// Constructor
+    /// interval: time between updates
+    /// start: when the first update is due
+    pub fn new(interval: Duration, start: Instant) -> Self {
+        TickClock {
+            interval: interval,
+            max_catch_up: TickClock::DEFAULT_MAX_CATCH_UP,
+            next: start,
+        }
+    }
+    /// Sets the most updates to run at once when behind
+    pub fn with_max_catch_up(mut self, max_catch_up: u32) -> Self {
+        self.max_catch_up = max_catch_up.max(1);
+        self
+    }
+    /// How long until the next update is due, if it isn't due yet
+    pub fn until_next(&self, now: Instant) -> Option<Duration> {
+        if now < self.next {
+            Some(self.next - now)
+        } else {
+            None
+        }
+    }
+    /// Works out which updates are due, and advances the schedule past them
+    /// now: the current time
+    pub fn advance(&mut self, now: Instant) -> DueTicks {
+        if now < self.next {
+            return DueTicks { run: 0, skipped: 0 };
+        }
+        let late = nanos(now - self.next);
+        let interval = nanos(self.interval).max(1);
+        let due = late / interval + 1;
+        if due > self.max_catch_up as u64 {
+            // Too far behind to catch up: drop the rest and start over
+            self.next = now + self.interval;
+            DueTicks {
+                run: self.max_catch_up,
+                skipped: due - self.max_catch_up as u64,
+            }
+        } else {
+            self.next += self.interval * due as u32;
+            DueTicks {
+                run: due as u32,
+                skipped: 0,
+            }
+        }
+    }
+}
+
+/// Updates due according to a TickClock
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DueTicks {
+    /// Number of updates to run now
+    pub run: u32,
+    /// Number of updates dropped because they were too far behind
+    pub skipped: u64,
+}
+
+/// Length of a duration in nanoseconds
+fn nanos(duration: Duration) -> u64 {
+    duration.as_secs() * 1000000000 + duration.subsec_nanos() as u64
+}
+
+/// A budget of one tick at 60 updates per second
+impl Default for TickBudget {
+    fn default() -> Self {
+        TickBudget::from_update_freq(60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn detect_overload() {
+        let mut budget = TickBudget::new(Duration::from_millis(10)).with_shed_load(true);
+        budget.overload_threshold = 3;
+        for _ in 0..2 {
+            budget.record(Duration::from_millis(20));
+        }
+        assert!(!budget.is_overloaded());
+        budget.record(Duration::from_millis(20));
+        assert!(budget.is_overloaded());
+        assert!(budget.is_shedding());
+        // One fast update ends the overload
+        budget.record(Duration::from_millis(5));
+        assert!(!budget.is_overloaded());
+        assert_eq!(budget.total_over_budget(), 3);
+    }
+    #[test]
+    fn cap_catch_up() {
+        let start = Instant::now();
+        let interval = Duration::from_millis(10);
+        let mut clock = TickClock::new(interval, start).with_max_catch_up(3);
+        // Nothing is due before the start
+        assert_eq!(clock.advance(start - Duration::from_millis(1)).run, 0);
+        assert_eq!(
+            clock.until_next(start - Duration::from_millis(4)),
+            Some(Duration::from_millis(4))
+        );
+        // On time
+        assert_eq!(clock.advance(start), DueTicks { run: 1, skipped: 0 });
+        assert_eq!(clock.until_next(start), Some(interval));
+        // A slow tick leaves two updates due, which are caught up on
+        let now = start + Duration::from_millis(25);
+        assert_eq!(clock.advance(now), DueTicks { run: 2, skipped: 0 });
+        assert_eq!(clock.until_next(now), Some(Duration::from_millis(5)));
+        // A very slow tick only catches up on a few, and the schedule restarts
+        let now = start + Duration::from_millis(100);
+        assert_eq!(clock.advance(now), DueTicks { run: 3, skipped: 5 });
+        assert_eq!(clock.until_next(now), Some(interval));
+        assert_eq!(clock.advance(now).run, 0);
+    }
+}
@@ -0,0 +1,248 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use entity::{Bullets, Swarm};
+use fixed::from_coord;
+use slab::Slab;
+use std::collections::VecDeque;
+
+/// 32 bit FNV-1a, simple enough for clients to compute the same way,
+/// and small enough for JavaScript clients to compare exactly
+/// Numbers are fed in little endian order, whatever the platform
+struct Fnv(u32);
+
+/// Functions for Fnv
+impl Fnv {
+    /// Starting value of the hash
+    const OFFSET: u32 = 0x811c_9dc5;
+    /// Multiplied in after each byte
+    const PRIME: u32 = 0x0100_0193;
+    /// Constructor
+    fn new() -> Self {
+        Fnv(Fnv::OFFSET)
+    }
+    /// Feeds the bytes of a number in, lowest first
+    fn write(&mut self, value: u64, bytes: usize) {
+        for index in 0..bytes {
+            self.0 ^= (value >> (8 * index)) as u8 as u32;
+            self.0 = self.0.wrapping_mul(Fnv::PRIME);
+        }
+    }
+    /// Feeds an unsigned number in
+    fn write_u64(&mut self, value: u64) {
+        self.write(value, 8);
+    }
+    /// Feeds a signed number in
+    fn write_i64(&mut self, value: i64) {
+        self.write(value as u64, 8);
+    }
+    /// Feeds a cooldown or other count of ticks in
+    fn write_u32(&mut self, value: u32) {
+        self.write(value as u64, 4);
+    }
+    /// Feeds a register or health value in
+    fn write_i32(&mut self, value: i32) {
+        self.write(value as u32 as u64, 4);
+    }
+    /// Feeds a position or direction in, by its exact bits
+    fn write_f32(&mut self, value: f32) {
+        self.write(value.to_bits() as u64, 4);
+    }
+}
+
+/// Checksum of the parts of a world two runs of it have to agree on:
+/// the tick, each swarm's position, experience, members, cooldowns, recoil, pending trigger,
+/// execution limit counts and program state, and every bullet
+/// Swarms are taken in ascending player ID order, whatever slots they're stored in,
+/// and bullets in the order they were fired
+/// Cooldowns and program state aren't sent to clients, so only clients running the
+/// simulation themselves can compute the same checksum
+/// Loops and calls being run are left out, since a difference in them moves the
+/// program counter on the next tick
+pub fn checksum(tick: u64, swarms: &Slab<Swarm>, bullets: &Bullets) -> u32 {
+    let mut hash = Fnv::new();
+    hash.write_u64(tick);
+    hash.write_u64(swarms.len() as u64);
+    let mut ids: Vec<usize> = swarms.keys().cloned().collect();
+    ids.sort();
+    for id in ids {
+        let swarm = &swarms[&id];
+        hash.write_u64(id as u64);
+        hash.write_f32(swarm.x);
+        hash.write_f32(swarm.y);
+        hash.write_f32(swarm.direction);
+        hash.write_i64(swarm.experience);
+        hash.write_u32(swarm.fire_cooldown);
+        hash.write_u32(swarm.recoil);
+        hash.write_u32(swarm.recoil_timer);
+        hash.write_i64(swarm.formation_cooldown);
+        hash.write_u32(swarm.handlers_in_a_row);
+        hash.write_u32(swarm.control_steps);
+        // Options are fed in as 0 when empty, and 1 followed by the value otherwise
+        match swarm.pending {
+            Some(trigger) => hash.write_u32(trigger as u32 + 1),
+            None => hash.write_u32(0),
+        }
+        hash.write_u64(swarm.program.program_counter as u64);
+        for register in swarm.program.registers.iter() {
+            hash.write_i32(*register);
+        }
+        match swarm.program.moving {
+            Some((index, left)) => {
+                hash.write_u32(1);
+                hash.write_u64(index as u64);
+                hash.write_f32(left);
+            }
+            None => hash.write_u32(0),
+        }
+        match swarm.program.waiting {
+            Some((index, left)) => {
+                hash.write_u32(1);
+                hash.write_u64(index as u64);
+                hash.write_u32(left);
+            }
+            None => hash.write_u32(0),
+        }
+        hash.write_u64(swarm.members.len() as u64);
+        for member in swarm.members.iter() {
+            hash.write_f32(member.x);
+            hash.write_f32(member.y);
+            hash.write_f32(member.direction);
+            hash.write_i32(member.health);
+        }
+    }
+    hash.write_u64(bullets.len() as u64);
+    for index in 0..bullets.len() {
+        hash.write_f32(from_coord(bullets.x[index]));
+        hash.write_f32(from_coord(bullets.y[index]));
+        hash.write_f32(from_coord(bullets.dx[index]));
+        hash.write_f32(from_coord(bullets.dy[index]));
+        hash.write_u64(bullets.owner[index] as u64);
+        hash.write_i64(bullets.ttl[index]);
+    }
+    hash.0
+}
+
+/// Checksums of the last few ticks, so a desync can be traced back to the tick it started
+#[derive(Clone, Debug, Default)]
+pub struct ChecksumHistory {
+    /// Most ticks kept; none are kept when it's 0
+    capacity: usize,
+    /// Oldest first
+    /// Tuple of (tick, checksum)
+    entries: VecDeque<(u64, u32)>,
+}
+
+/// Functions for ChecksumHistory
+impl ChecksumHistory {
+    /// Constructor
+    /// capacity: the most ticks to keep checksums for
+    pub fn new(capacity: usize) -> Self {
+        ChecksumHistory {
+            capacity: capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+    /// Whether any checksums are kept
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+    /// Keeps a tick's checksum, forgetting the oldest once full
+    /// Checksums of the tick and later ones are replaced, since a rewound world runs them again
+    pub fn record(&mut self, tick: u64, checksum: u32) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.entries.back().map_or(false, |&(last, _)| last >= tick) {
+            self.entries.pop_back();
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((tick, checksum));
+    }
+    /// The checksum kept for a tick, if it's recent enough
+    pub fn get(&self, tick: u64) -> Option<u32> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|&&(kept, _)| kept == tick)
+            .map(|&(_, checksum)| checksum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entity::Bullet;
+
+    #[test]
+    fn checksum_changes_with_state() {
+        let mut swarms = Slab::new();
+        swarms.insert(2, Swarm::new(100.0, 100.0, 3));
+        let mut bullets = Bullets::new();
+        let before = checksum(5, &swarms, &bullets);
+        assert_eq!(checksum(5, &swarms, &bullets), before);
+        assert!(checksum(6, &swarms, &bullets) != before);
+        bullets.push(Bullet::new(2, 120.0, 100.0, 0.0));
+        let fired = checksum(5, &swarms, &bullets);
+        assert!(fired != before);
+        swarms.get_mut(&2).unwrap().members[0].health -= 1;
+        let hit = checksum(5, &swarms, &bullets);
+        assert!(hit != fired);
+        // State that only steers later ticks counts too
+        swarms.get_mut(&2).unwrap().fire_cooldown = 3;
+        let cooling = checksum(5, &swarms, &bullets);
+        assert!(cooling != hit);
+        swarms.get_mut(&2).unwrap().program.waiting = Some((0, 4));
+        assert!(checksum(5, &swarms, &bullets) != cooling);
+    }
+
+    #[test]
+    fn checksum_ignores_slot_order() {
+        // Joining out of order and reusing a slot puts the swarms out of ID order
+        let mut server = Slab::new();
+        for &id in [4, 2, 9].iter() {
+            server.insert(id, Swarm::new(id as f32 * 10.0, 100.0, 2));
+        }
+        server.remove(&2);
+        server.insert(1, Swarm::new(10.0, 100.0, 2));
+        let mut client = Slab::new();
+        for &id in [1, 4, 9].iter() {
+            client.insert(id, Swarm::new(id as f32 * 10.0, 100.0, 2));
+        }
+        assert!(server.keys().cloned().collect::<Vec<_>>() != vec![1, 4, 9]);
+        let bullets = Bullets::new();
+        assert_eq!(checksum(7, &server, &bullets), checksum(7, &client, &bullets));
+    }
+
+    #[test]
+    fn keep_recent_ticks() {
+        let mut history = ChecksumHistory::new(3);
+        for tick in 1..6 {
+            history.record(tick, tick as u32 * 10);
+        }
+        assert_eq!(history.get(2), None);
+        assert_eq!(history.get(3), Some(30));
+        assert_eq!(history.get(5), Some(50));
+        // Rewinding to tick 4 forgets the ticks after it
+        history.record(4, 41);
+        assert_eq!(history.get(4), Some(41));
+        assert_eq!(history.get(5), None);
+        let mut disabled = ChecksumHistory::new(0);
+        disabled.record(1, 10);
+        assert_eq!(disabled.get(1), None);
+    }
+}
@@ -0,0 +1,88 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate serde_json;
+
+pub use entity::{Bullet, Bullets, Swarm, SwarmMember};
+pub use event::WorldEvent;
+pub use game_mode::{
+    CaptureTheFlag, Checkpoint, ControlPoint, CustomMode, Duel, Flag, GameMode, KingOfTheHill,
+    Race, RaceProgress, Team, WaveScheduler, WaveSurvival,
+};
+pub use handoff::HandoffRedirect;
+pub use rpc::{ClientMessage, CompileRequest, CompileResult, Configuration, HandoffRequest, Vec2};
+pub use slab::Slab;
+pub use world::WorldState;
+
+/// A message sent by the server, as decoded by a client
+/// Owns its contents, unlike the Response the server sends it as
+#[derive(Clone, Debug, Deserialize)]
+pub struct ServerMessage {
+    /// The type of message
+    #[serde(rename = "mt")]
+    pub message_type: String,
+    /// The contents of the message
+    pub message: ServerMessageContents,
+}
+
+/// Functions for ServerMessage
+impl ServerMessage {
+    /// Decodes a message from the text of a websocket frame
+    pub fn parse(text: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(text)
+    }
+}
+
+/// The contents of a message sent by the server
+#[derive(Clone, Debug, Deserialize)]
+pub enum ServerMessageContents {
+    /// The world state
+    #[serde(rename = "world")]
+    WORLD(WorldState),
+    /// Configuration
+    #[serde(rename = "config")]
+    CONFIG(Configuration),
+    /// A compilation result
+    #[serde(rename = "compile")]
+    COMPILE(CompileResult),
+    /// An instruction to reconnect to another server
+    #[serde(rename = "handoff")]
+    HANDOFF(HandoffRedirect),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rpc::{Response, ResponseMessage};
+    use world::World;
+    #[test]
+    fn decode_world_round_trip() {
+        let mut world = World::new(1000.0, 1000.0).with_seed(1);
+        world.add_player(3).unwrap();
+        world.add_player(8).unwrap();
+        world.update();
+        let text = Response::new(ResponseMessage::WORLD(world.state_ref()))
+            .serialize()
+            .unwrap();
+        let state = match ServerMessage::parse(&text).unwrap().message {
+            ServerMessageContents::WORLD(state) => state,
+            other => panic!("Expected the world state, got {:?}", other),
+        };
+        assert_eq!(state.swarms.len(), 2);
+        assert_eq!(state.swarms[&8].x, world.swarms[&8].x);
+        assert_eq!(state.swarms[&8].members.len(), world.swarms[&8].members.len());
+        assert_eq!(state.bullets.len(), world.bullets.len());
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use arena::FrameArena;
+use entity::{Bullets, Swarm};
+use rayon::prelude::*;
+use slab::Slab;
+use spatial::{Cell, SpatialGrid};
+
+/// Half the width of the square hitbox around a swarm member
+pub const MEMBER_RADIUS: f32 = 10.0;
+/// Width of a cell in the broad-phase grid
+/// A hitbox can only overlap the cell its member is in and the neighbouring cells
+pub const CELL_SIZE: f32 = 2.0 * MEMBER_RADIUS;
+
+/// A bullet overlapping a member of another player's swarm
+/// Ordered by bullet, then player, then member, which is the order hits are resolved in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Hit {
+    /// Index of the bullet
+    pub bullet: usize,
+    /// ID of the player that owns the member
+    pub player: usize,
+    /// Index of the member in its swarm
+    pub member: usize,
+}
+
+/// Places every swarm member in the grid, keyed by (player ID, member index)
+/// Only members that crossed into another cell since the last tick are moved
+pub fn index_members(swarms: &Slab<Swarm>, grid: &mut SpatialGrid<(usize, usize)>) {
+    grid.begin();
+    for (id, swarm) in swarms.iter() {
+        for (index, member) in swarm.members.iter().enumerate() {
+            grid.place((*id, index), swarm.x + member.x, swarm.y + member.y);
+        }
+    }
+    grid.finish();
+}
+
+/// Finds every bullet overlapping a member of another player's swarm
+/// Bullets are grouped by grid cell, and the cells are checked on the thread pool
+/// The hits are put in arena.hits, sorted so the result doesn't depend on scheduling
+/// members: every member, as placed by index_members
+/// arena: scratch space, reset since the last call
+pub fn find_hits(
+    swarms: &Slab<Swarm>,
+    members: &SpatialGrid<(usize, usize)>,
+    bullets: &Bullets,
+    arena: &mut FrameArena,
+) {
+    let FrameArena {
+        bullets: ref mut bullet_cells,
+        ref mut cells,
+        ref mut cell_hits,
+        ref mut hits,
+        ..
+    } = *arena;
+    // Sort bullets by cell, then group them
+    for index in 0..bullets.len() {
+        let (x, y) = bullets.position(index);
+        bullet_cells.push((members.cell_of(x, y), index));
+    }
+    bullet_cells.sort_unstable();
+    let mut start: usize = 0;
+    while start < bullet_cells.len() {
+        let mut end = start + 1;
+        while end < bullet_cells.len() && bullet_cells[end].0 == bullet_cells[start].0 {
+            end += 1;
+        }
+        cells.push((bullet_cells[start].0, start, end));
+        start = end;
+    }
+    while cell_hits.len() < cells.len() {
+        cell_hits.push(Vec::new());
+    }
+    // Check each cell's bullets against the members in and around the cell
+    let bullet_cells: &[(Cell, usize)] = bullet_cells;
+    cells.par_iter().zip(cell_hits.par_iter_mut()).for_each(
+        |(&((cell_x, cell_y), start, end), cell_hits)| {
+            for &(_, bullet) in bullet_cells[start..end].iter() {
+                let (bullet_x, bullet_y) = bullets.position(bullet);
+                for neighbour_x in (cell_x - 1)..(cell_x + 2) {
+                    for neighbour_y in (cell_y - 1)..(cell_y + 2) {
+                        for &(player, member) in members.query((neighbour_x, neighbour_y)) {
+                            let (x, y) = match swarms.get(&player) {
+                                Some(swarm) => (
+                                    swarm.x + swarm.members[member].x,
+                                    swarm.y + swarm.members[member].y,
+                                ),
+                                None => continue,
+                            };
+                            // for now detects if the bullet passes within a
+                            // square hitbox around the swarm member
+                            if (bullet_x - x).abs() <= MEMBER_RADIUS
+                                && (bullet_y - y).abs() <= MEMBER_RADIUS
+                                && bullets.owner[bullet] != player
+                            {
+                                cell_hits.push(Hit {
+                                    bullet: bullet,
+                                    player: player,
+                                    member: member,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        },
+    );
+    for cell_hits in cell_hits[..cells.len()].iter() {
+        hits.extend_from_slice(cell_hits);
+    }
+    hits.sort_unstable();
+}
+
+/// Applies the hits in arena.hits in order
+/// Each bullet damages the first member it overlaps that is still alive, then is removed
+/// Damage follows the attacker's handicap and comeback buff
+/// Members whose health reaches 0 are removed
+/// (attacker ID, victim ID, damage) for each hit is put in arena.damage,
+/// with where the member was hit in arena.hit_at,
+/// and (attacker ID, victim ID) for each member destroyed is put in arena.kills
+pub fn resolve_hits(swarms: &mut Slab<Swarm>, bullets: &mut Bullets, arena: &mut FrameArena) {
+    let FrameArena {
+        ref hits,
+        ref mut spent,
+        ref mut dead,
+        ref mut kills,
+        ref mut damage,
+        ref mut hit_at,
+        ref mut lost,
+        ..
+    } = *arena;
+    for hit in hits.iter() {
+        if spent.last() == Some(&hit.bullet) {
+            continue;
+        }
+        match swarms.get(&hit.player) {
+            Some(swarm) if swarm.members[hit.member].health > 0 => {}
+            _ => continue,
+        }
+        // Bullets without a living owner hit for 1
+        let attacker = bullets.owner[hit.bullet];
+        let dealt = match swarms.get_mut(&attacker) {
+            Some(attacker) => attacker.hit_damage(),
+            None => 1,
+        };
+        damage.push((attacker, hit.player, dealt));
+        let swarm = swarms.get_mut(&hit.player).unwrap();
+        hit_at.push((
+            swarm.x + swarm.members[hit.member].x,
+            swarm.y + swarm.members[hit.member].y,
+        ));
+        swarm.members[hit.member].health -= dealt as i32;
+        swarm.dirty.damaged = true;
+        debug!("HIT");
+        spent.push(hit.bullet);
+        if swarm.members[hit.member].health <= 0 {
+            debug!("KILL");
+            dead.push((hit.player, hit.member));
+            kills.push((bullets.owner[hit.bullet], hit.player));
+            let member = &swarm.members[hit.member];
+            lost.push((swarm.x + member.x, swarm.y + member.y));
+        }
+    }
+    // Remove from the highest index down, so swap_remove never moves
+    // something that still has to be removed
+    dead.sort_unstable();
+    for &(player, member) in dead.iter().rev() {
+        if let Some(swarm) = swarms.get_mut(&player) {
+            swarm.members.swap_remove(member);
+        }
+    }
+    for &bullet in spent.iter().rev() {
+        bullets.swap_remove(bullet);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entity::Bullet;
+    #[test]
+    fn resolve_hits_in_order() {
+        let mut swarms: Slab<Swarm> = Slab::new();
+        swarms.insert(0, Swarm::new(100.0, 100.0, 1));
+        swarms.insert(1, Swarm::new(500.0, 500.0, 1));
+        swarms.get_mut(&1).unwrap().members[0].health = 1;
+        let (x, y) = (
+            500.0 + swarms[&1].members[0].x,
+            500.0 + swarms[&1].members[0].y,
+        );
+        let mut bullets = Bullets::new();
+        // Two bullets from player 0 on a member with 1 health, and one of player 1's own
+        bullets.push(Bullet::new(0, x + 3.0, y, 0.0));
+        bullets.push(Bullet::new(1, x, y, 0.0));
+        bullets.push(Bullet::new(0, x - 3.0, y, 0.0));
+        let mut arena = FrameArena::new();
+        let mut grid = SpatialGrid::new(CELL_SIZE);
+        index_members(&swarms, &mut grid);
+        find_hits(&swarms, &grid, &bullets, &mut arena);
+        assert_eq!(
+            arena.hits,
+            vec![
+                Hit {
+                    bullet: 0,
+                    player: 1,
+                    member: 0,
+                },
+                Hit {
+                    bullet: 2,
+                    player: 1,
+                    member: 0,
+                },
+            ]
+        );
+        resolve_hits(&mut swarms, &mut bullets, &mut arena);
+        assert_eq!(arena.kills, vec![(0, 1)]);
+        assert!(swarms[&1].members.is_empty());
+        // Only the first bullet was spent
+        assert_eq!(bullets.len(), 2);
+        assert!((bullets.position(0).0 - (x - 3.0)).abs() <= 0.01);
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use swarm_language::{OpcodeInfo, ParameterInfo, ParameterKind, MAX_LABEL_LENGTH, OPCODES};
+
+/// Something that could be typed at the cursor
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Candidate {
+    /// The text to insert
+    pub label: &'static str,
+    /// What it means
+    pub doc: &'static str,
+}
+
+/// Help for the command being typed
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SignatureHelp {
+    /// The command's opcode
+    pub opcode: &'static str,
+    /// Index of the parameter at the cursor, if the cursor is on a parameter
+    pub parameter: Option<usize>,
+    /// A description of what is expected at the cursor
+    pub help: String,
+}
+
+/// Completions for a cursor position in a line of swarm code
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Completions {
+    /// Byte offset where the word being completed starts
+    /// Candidates replace the line from here to the cursor
+    pub start: usize,
+    /// Everything that matches the word typed so far
+    pub candidates: Vec<Candidate>,
+    /// Help for the command, if the line has a known opcode
+    pub signature: Option<SignatureHelp>,
+}
+
+/// Finds completions and help for a cursor position in a line of swarm code
+/// line: the line being edited
+/// cursor: byte offset of the cursor in the line; only the text before it is considered
+pub fn complete(line: &str, cursor: usize) -> Completions {
+    // Only look at the text before the cursor, on a character boundary
+    let mut cursor = cursor.min(line.len());
+    while !line.is_char_boundary(cursor) {
+        cursor -= 1;
+    }
+    let before = &line[..cursor];
+    // Find the word being typed, which is empty after whitespace
+    let start = before
+        .rfind(char::is_whitespace)
+        .map(|index| index + before[index..].chars().next().map_or(1, char::len_utf8))
+        .unwrap_or(0);
+    let word = before[start..].to_uppercase();
+    let position = before[..start].split_whitespace().count();
+    if position == 0 {
+        return Completions {
+            start: start,
+            candidates: OPCODES
+                .iter()
+                .filter(|opcode| opcode.name.starts_with(word.as_str()))
+                .map(|opcode| Candidate {
+                    label: opcode.name,
+                    doc: opcode.doc,
+                })
+                .collect(),
+            signature: find_opcode(&word).map(|opcode| signature(opcode, None)),
+        };
+    }
+    let opcode = match before.split_whitespace().next().and_then(find_opcode) {
+        Some(opcode) => opcode,
+        None => {
+            return Completions {
+                start: start,
+                candidates: Vec::new(),
+                signature: None,
+            }
+        }
+    };
+    let index = position - 1;
+    let candidates = match opcode.parameters.get(index) {
+        Some(parameter) => parameter
+            .values
+            .iter()
+            .filter(|value| value.starts_with(word.as_str()))
+            .map(|value| Candidate {
+                label: value,
+                doc: parameter.doc,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    Completions {
+        start: start,
+        candidates: candidates,
+        signature: Some(signature(opcode, Some(index))),
+    }
+}
+
+/// Finds an opcode by name, ignoring case
+fn find_opcode(name: &str) -> Option<&'static OpcodeInfo> {
+    let name = name.to_uppercase();
+    OPCODES.iter().find(|opcode| opcode.name == name)
+}
+
+/// Describes what an opcode expects at a parameter, or the whole opcode
+fn signature(opcode: &'static OpcodeInfo, index: Option<usize>) -> SignatureHelp {
+    let help = match index {
+        None => format!("{}: {}", usage(opcode), opcode.doc),
+        Some(index) => match opcode.parameters.get(index) {
+            Some(parameter) => format!(
+                "{} expects {}{}: {}",
+                opcode.name,
+                parameter.name,
+                expected_values(parameter),
+                parameter.doc
+            ),
+            None if opcode.parameters.is_empty() => {
+                format!("{} takes no parameters", opcode.name)
+            }
+            None => format!(
+                "{} takes at most {} parameters",
+                opcode.name,
+                opcode.parameters.len()
+            ),
+        },
+    };
+    SignatureHelp {
+        opcode: opcode.name,
+        parameter: index.filter(|&index| index < opcode.parameters.len()),
+        help: help,
+    }
+}
+
+/// Writes an opcode with its parameters, such as "FORMATION formation [foci]"
+fn usage(opcode: &OpcodeInfo) -> String {
+    let mut usage = String::from(opcode.name);
+    for parameter in opcode.parameters.iter() {
+        if parameter.optional {
+            usage.push_str(&format!(" [{}]", parameter.name));
+        } else {
+            usage.push_str(&format!(" {}", parameter.name));
+        }
+    }
+    usage
+}
+
+/// Describes the values a parameter takes, such as " from -30 to 30"
+fn expected_values(parameter: &ParameterInfo) -> String {
+    match parameter.kind {
+        ParameterKind::NAME => format!(", one of {}", parameter.values.join(", ")),
+        ParameterKind::LABEL => format!(
+            " of up to {} letters, digits and underscores",
+            MAX_LABEL_LENGTH
+        ),
+        ParameterKind::NUMBER | ParameterKind::INTEGER => match (parameter.min, parameter.max) {
+            (Some(min), Some(max)) => format!(" from {} to {}", min, max),
+            _ => String::new(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn complete_lines() {
+        // Opcodes
+        let completions = complete("f", 1);
+        assert_eq!(completions.start, 0);
+        assert_eq!(completions.candidates.len(), 2);
+        assert_eq!(completions.candidates[0].label, "FIRE");
+        assert_eq!(completions.candidates[1].label, "FORMATION");
+        assert_eq!(complete("", 0).candidates.len(), OPCODES.len());
+        // Parameters
+        let completions = complete("FORMATION s", 11);
+        assert_eq!(completions.start, 10);
+        let labels: Vec<&str> = completions.candidates.iter().map(|c| c.label).collect();
+        assert_eq!(labels, vec!["SPREAD", "SIERPINSKI"]);
+        // Help for numbers, ignoring text after the cursor
+        let completions = complete("turn 45", 5);
+        assert!(completions.candidates.is_empty());
+        let signature = completions.signature.unwrap();
+        assert_eq!(signature.parameter, Some(0));
+        assert!(signature
+            .help
+            .starts_with("TURN expects degrees from -30 to 30"));
+        // Too many parameters, or an unknown opcode
+        assert_eq!(
+            complete("FIRE ", 5).signature.unwrap().help,
+            "FIRE takes no parameters"
+        );
+        assert_eq!(
+            complete("MOVE 150 ", 9).signature.unwrap().help,
+            "MOVE takes at most 1 parameters"
+        );
+        assert_eq!(complete("DANCE ", 6).signature, None);
+    }
+}
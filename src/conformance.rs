@@ -0,0 +1,329 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate serde_json;
+
+use error::ProtocolError;
+use serde_json::Value;
+
+/// Relative difference allowed between numbers, since positions are f32 on the server
+/// but may be read as doubles, or printed with a different number of digits
+const NUMBER_TOLERANCE: f64 = 1e-6;
+
+/// Which side of the connection sends a message
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum Sender {
+    /// Sent by clients and decoded by the server
+    #[serde(rename = "client")]
+    CLIENT,
+    /// Sent by the server and decoded by clients
+    #[serde(rename = "server")]
+    SERVER,
+}
+
+/// A message as it appears on the wire, for checking other implementations of the protocol
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct TestVector {
+    /// Unique name of the vector
+    pub name: &'static str,
+    /// Who sends the message
+    pub sender: Sender,
+    /// What the message means, and the values a decoder should find in it
+    pub description: &'static str,
+    /// The text of the websocket frame
+    pub encoded: &'static str,
+}
+
+/// Every test vector
+/// An implementation conforms if, for each vector it handles, decoding the encoded text
+/// and encoding the result again gives a message that matches the encoded text
+pub static VECTORS: &[TestVector] = &[
+    TestVector {
+        name: "config",
+        sender: Sender::SERVER,
+        description: "Tells a newly connected client that its player ID is 7",
+        encoded: r#"{"mt":"i","message":{"config":{"player_id":7}}}"#,
+    },
+    TestVector {
+        name: "compile_success",
+        sender: Sender::SERVER,
+        description: "The program compiled and is now running; error is empty",
+        encoded: r#"{"mt":"c","message":{"compile":{"success":true,"error":""}}}"#,
+    },
+    TestVector {
+        name: "compile_failure",
+        sender: Sender::SERVER,
+        description: "The program didn't compile, so the old program keeps running; message identifies the first error for translation, and diagnostics list every error with where it is and a hint if there is one",
+        encoded: r#"{"mt":"c","message":{"compile":{"success":false,"error":"Command not recognized: MOEV","message":{"id":"unknown_command","params":{"command":"MOEV"}},"diagnostics":[{"kind":"unknown_command","line":2,"column":1,"error":"Command not recognized: MOEV","message":{"id":"unknown_command","params":{"command":"MOEV"}},"hint":"Did you mean MOVE?"}]}}}"#,
+    },
+    TestVector {
+        name: "handoff",
+        sender: Sender::SERVER,
+        description: "Tells the client to reconnect to another server and present the token there",
+        encoded: r#"{"mt":"h","message":{"handoff":{"url":"ws://other.example:8080/rooms/2","token":"eyJzbmFwc2hvdCI6e319.c2lnbmF0dXJl"}}}"#,
+    },
+    TestVector {
+        name: "achievement",
+        sender: Sender::SERVER,
+        description: "Tells the client its player unlocked the first_kill achievement",
+        encoded: r#"{"mt":"a","message":{"achievement":"first_kill"}}"#,
+    },
+    TestVector {
+        name: "plugin_reply",
+        sender: Sender::SERVER,
+        description: "A reply from the server plugin named scores",
+        encoded: r#"{"mt":"p","message":{"plugin":{"plugin":"scores","data":{"top":[3,1]}}}}"#,
+    },
+    TestVector {
+        name: "remap",
+        sender: Sender::SERVER,
+        description: "Tells a client that asked for protanopia colors how to redraw a deuteranopia world",
+        encoded: r#"{"mt":"r","message":{"remap":{"world":"deuteranopia","palette":"protanopia","colors":[{"from":[230,159,0],"to":[100,143,255]},{"from":[86,180,233],"to":[255,176,0]},{"from":[0,158,115],"to":[220,38,127]},{"from":[240,228,66],"to":[255,255,255]},{"from":[0,114,178],"to":[120,94,240]},{"from":[213,94,0],"to":[254,97,0]},{"from":[204,121,167],"to":[0,158,115]},{"from":[255,255,255],"to":[240,228,66]}]}}}"#,
+    },
+    TestVector {
+        name: "world_empty",
+        sender: Sender::SERVER,
+        description: "A world with no swarms and no bullets",
+        encoded: r#"{"mt":"w","message":{"world":{"swarms":{},"bullets":[]}}}"#,
+    },
+    TestVector {
+        name: "world",
+        sender: Sender::SERVER,
+        description: "Player 3's swarm at (100, 50) with two members, color (200, 40, 40), and \
+                      12 experience; players are keyed by ID as a string. The member count, \
+                      centroid and average facing come first, for drawing distant swarms. \
+                      Member positions are relative to the swarm. One bullet fired by \
+                      player 3 at (130, 50), heading 0 degrees",
+        encoded: r#"{"mt":"w","message":{"world":{"swarms":{"3":{"count":2,"centroid":[100.0,50.0],"facing":0.0,"x":100.0,"y":50.0,"members":[{"x":-10.0,"y":0.0,"direction":0.0,"health":5},{"x":10.0,"y":0.0,"direction":0.0,"health":3}],"color":[200,40,40],"experience":12}},"bullets":[{"owner":3,"x":130.0,"y":50.0,"direction":0.0}]}}}"#,
+    },
+    TestVector {
+        name: "viewport",
+        sender: Sender::CLIENT,
+        description: "Asks for the world state between the corners (0, 0) and (1600, 900)",
+        encoded: r#"[{"x":0.0,"y":0.0},{"x":1600.0,"y":900.0}]"#,
+    },
+    TestVector {
+        name: "compile",
+        sender: Sender::CLIENT,
+        description: "Asks for a two line program to be compiled and run",
+        encoded: r#"{"program":"MOVE\nFIRE\n"}"#,
+    },
+    TestVector {
+        name: "squad",
+        sender: Sender::CLIENT,
+        description: "Asks for a program to be compiled and run by teammate 3",
+        encoded: r#"{"squad":[{"name":"flank","player_id":3,"program":"MOVE\nFIRE\n"}]}"#,
+    },
+    TestVector {
+        name: "squad_leader",
+        sender: Sender::CLIENT,
+        description: "Lets teammate 1 upload the player's programs as their squad leader",
+        encoded: r#"{"squad_leader":1}"#,
+    },
+    TestVector {
+        name: "handoff_token",
+        sender: Sender::CLIENT,
+        description: "Presents a handoff token received from another server",
+        encoded: r#"{"handoff_token":"eyJzbmFwc2hvdCI6e319.c2lnbmF0dXJl"}"#,
+    },
+    TestVector {
+        name: "resume_token",
+        sender: Sender::CLIENT,
+        description: "Presents a resume token for player 4, from before the server restarted",
+        encoded: r#"{"resume_token":"eyJwbGF5ZXJfaWQiOjR9.c2lnbmF0dXJl"}"#,
+    },
+    TestVector {
+        name: "pause",
+        sender: Sender::CLIENT,
+        description: "Pauses the player's program, so the swarm idles until it's resumed",
+        encoded: r#"{"pause":true}"#,
+    },
+    TestVector {
+        name: "ping",
+        sender: Sender::CLIENT,
+        description: "Marks (250, 300) for the player's team, shown with the next world state",
+        encoded: r#"{"ping":{"x":250.0,"y":300.0}}"#,
+    },
+    TestVector {
+        name: "plugin_message",
+        sender: Sender::CLIENT,
+        description: "Sends a message to the server plugin named scores",
+        encoded: r#"{"plugin":"scores","data":{"ask":"top"}}"#,
+    },
+    TestVector {
+        name: "input",
+        sender: Sender::CLIENT,
+        description: "Turns and fires for a second, taking turns with the program",
+        encoded: r#"{"input":{"turn":-15.0,"thrust":false,"fire":true,"ticks":60,"blend":"interleave"}}"#,
+    },
+    TestVector {
+        name: "palette",
+        sender: Sender::CLIENT,
+        description: "Asks how to show swarm colors in the protanopia palette",
+        encoded: r#"{"palette":"protanopia"}"#,
+    },
+];
+
+/// Finds a test vector by name
+pub fn vector(name: &str) -> Option<&'static TestVector> {
+    VECTORS.iter().find(|vector| vector.name == name)
+}
+
+/// Checks an implementation's encoding of a vector's message
+/// Key order and whitespace don't matter, and numbers only need to be nearly equal
+pub fn check(vector: &TestVector, output: &str) -> Result<(), ProtocolError> {
+    let expected: Value = serde_json::from_str(vector.encoded)?;
+    let actual: Value = serde_json::from_str(output)?;
+    compare(&expected, &actual, vector.name)
+}
+
+/// Compares two JSON values
+/// path: where the values are, for describing a mismatch
+pub fn compare(expected: &Value, actual: &Value, path: &str) -> Result<(), ProtocolError> {
+    let mismatch = || ProtocolError::MISMATCH {
+        path: path.into(),
+        expected: expected.to_string(),
+        actual: actual.to_string(),
+    };
+    match (expected, actual) {
+        (&Value::Number(ref expected), &Value::Number(ref actual)) => {
+            match (expected.as_f64(), actual.as_f64()) {
+                (Some(expected), Some(actual))
+                    if (expected - actual).abs()
+                        <= NUMBER_TOLERANCE * expected.abs().max(actual.abs()).max(1.0) =>
+                {
+                    Ok(())
+                }
+                _ => Err(mismatch()),
+            }
+        }
+        (&Value::Array(ref expected), &Value::Array(ref actual)) => {
+            if expected.len() != actual.len() {
+                return Err(mismatch());
+            }
+            for (index, (expected, actual)) in expected.iter().zip(actual.iter()).enumerate() {
+                compare(expected, actual, &format!("{}.{}", path, index))?;
+            }
+            Ok(())
+        }
+        (&Value::Object(ref expected), &Value::Object(ref actual)) => {
+            for (key, expected) in expected.iter() {
+                match actual.get(key) {
+                    Some(actual) => compare(expected, actual, &format!("{}.{}", path, key))?,
+                    None => {
+                        return Err(ProtocolError::MISMATCH {
+                            path: format!("{}.{}", path, key),
+                            expected: expected.to_string(),
+                            actual: "nothing".into(),
+                        })
+                    }
+                }
+            }
+            match actual.keys().find(|key| !expected.contains_key(*key)) {
+                Some(key) => Err(ProtocolError::MISMATCH {
+                    path: format!("{}.{}", path, key),
+                    expected: "nothing".into(),
+                    actual: actual[key].to_string(),
+                }),
+                None => Ok(()),
+            }
+        }
+        _ if expected == actual => Ok(()),
+        _ => Err(mismatch()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entity::{Bullet, Swarm, SwarmMember};
+    use achievement::Achievement;
+    use rpc::{
+        ClientMessage, ColorRemap, CompileResult, Configuration, PluginMessage, Response,
+        ResponseMessage,
+    };
+    use palette::Palette;
+    use serde_json::Map;
+    use swarm_language::SwarmProgram;
+    use world::World;
+
+    #[test]
+    fn server_matches_vectors() {
+        let mut world = World::new(1600.0, 900.0);
+        let mut swarm = Swarm::new(100.0, 50.0, 2).with_color((200, 40, 40));
+        swarm.members = vec![SwarmMember::new(-10.0, 0.0), SwarmMember::new(10.0, 0.0)];
+        swarm.members[1].health = 3;
+        swarm.experience = 12;
+        swarm.summarize();
+        world.swarms.insert(3, swarm);
+        world.bullets.push(Bullet::new(3, 130.0, 50.0, 0.0));
+        let source = "MOVE\nMOEV\n";
+        let error = source.parse::<SwarmProgram>().unwrap_err();
+        let mut top = Map::new();
+        top.insert("top".into(), Value::Array(vec![Value::from(3), Value::from(1)]));
+        let outputs = vec![
+            ("config", Response::new(ResponseMessage::CONFIG(Configuration::new(7)))),
+            (
+                "compile_success",
+                Response::new(ResponseMessage::COMPILE(CompileResult::new(true, "".into()))),
+            ),
+            (
+                "compile_failure",
+                Response::new(ResponseMessage::COMPILE(CompileResult::rejected(&error, source))),
+            ),
+            ("world", Response::new(ResponseMessage::WORLD(world.state_ref()))),
+            (
+                "achievement",
+                Response::new(ResponseMessage::ACHIEVEMENT(Achievement::FIRST_KILL)),
+            ),
+            (
+                "remap",
+                Response::new(ResponseMessage::REMAP(ColorRemap::new(
+                    Palette::DEUTERANOPIA,
+                    Palette::PROTANOPIA,
+                ))),
+            ),
+            (
+                "plugin_reply",
+                Response::new(ResponseMessage::PLUGIN(PluginMessage {
+                    plugin: "scores".into(),
+                    data: Value::Object(top),
+                })),
+            ),
+        ];
+        for (name, response) in outputs {
+            check(vector(name).unwrap(), &response.serialize().unwrap()).unwrap();
+        }
+        // The server decodes every client vector
+        for vector in VECTORS.iter().filter(|vector| vector.sender == Sender::CLIENT) {
+            let message: ClientMessage = serde_json::from_str(vector.encoded).unwrap();
+            let output = serde_json::to_string(&message).unwrap();
+            check(vector, &output).unwrap();
+        }
+    }
+
+    #[test]
+    fn compare_messages() {
+        let vector = vector("viewport").unwrap();
+        assert!(check(vector, r#"[ {"y": 0, "x": 0}, {"x": 1600.0000001, "y": 9e2} ]"#).is_ok());
+        match check(vector, r#"[{"x":0.0,"y":0.0},{"x":1600.0,"y":901.0}]"#) {
+            Err(ProtocolError::MISMATCH { path, .. }) => assert_eq!(path, "viewport.1.y"),
+            other => panic!("Expected a mismatch, got {:?}", other),
+        }
+        assert!(check(vector, r#"[{"x":0.0,"y":0.0}]"#).is_err());
+        assert!(check(vector, r#"[{"x":0.0,"y":0.0,"z":1.0},{"x":1600.0,"y":900.0}]"#).is_err());
+        assert!(check(vector, "not json").is_err());
+    }
+}
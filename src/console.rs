@@ -0,0 +1,130 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use admin::AdminCommand;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::ops::DerefMut;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use world::World;
+
+/// Handles a single line of console input
+/// return: the text to show the administrator
+pub fn handle_line(line: &str, world: &Arc<RwLock<World>>) -> String {
+    // Parse the command
+    let command: AdminCommand = match line.parse() {
+        Ok(command) => command,
+        Err(error) => return format!("{} Try \"help\".\n", error),
+    };
+    // Run it against the world
+    match world.write() {
+        Ok(mut write_lock) => match command.execute(write_lock.deref_mut()) {
+            Ok(output) => output,
+            Err(error) => format!("{}\n", error),
+        },
+        Err(error) => format!("Failed to get write lock on world: {}\n", error),
+    }
+}
+
+/// Reads admin commands from stdin until it is closed
+/// Blocks the calling thread
+pub fn run_stdin(world: Arc<RwLock<World>>) {
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        match line {
+            Ok(line) => {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                print!("{}", handle_line(&line, &world));
+            }
+            Err(error) => {
+                error!("Failed to read from stdin: {}", error);
+                break;
+            }
+        }
+    }
+    info!("Console input closed");
+}
+
+/// Resolves the address the console listens on
+/// Fails unless every address it resolves to is a loopback one, since there is no authentication
+fn local_addresses(address: &str) -> io::Result<Vec<SocketAddr>> {
+    let addresses: Vec<SocketAddr> = address.to_socket_addrs()?.collect();
+    if addresses.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} doesn't resolve to any address", address),
+        ));
+    }
+    match addresses.iter().find(|resolved| !resolved.ip().is_loopback()) {
+        Some(resolved) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("The admin console can only listen on loopback addresses, not {}", resolved),
+        )),
+        None => Ok(addresses),
+    }
+}
+
+/// Accepts admin connections on a local TCP address, one thread per connection
+/// Blocks the calling thread
+/// Fails without listening if the address isn't a loopback one
+pub fn run_tcp(address: &str, world: Arc<RwLock<World>>) -> io::Result<()> {
+    let listener = TcpListener::bind(&local_addresses(address)?[..])?;
+    info!("Admin console listening on {}", address);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let world = world.clone();
+                thread::spawn(move || match handle_connection(stream, &world) {
+                    Ok(_) => {}
+                    Err(error) => warn!("Admin console connection failed: {}", error),
+                });
+            }
+            Err(error) => warn!("Failed to accept admin connection: {}", error),
+        }
+    }
+    Ok(())
+}
+
+/// Handles commands from one admin connection until it closes
+fn handle_connection(stream: TcpStream, world: &Arc<RwLock<World>>) -> io::Result<()> {
+    info!("Admin console connection from {}", stream.peer_addr()?);
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        writer.write_all(handle_line(&line, world).as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_listen_locally() {
+        assert!(local_addresses("127.0.0.1:7000").is_ok());
+        assert!(local_addresses("[::1]:7000").is_ok());
+        for address in ["0.0.0.0:7000", "192.168.1.4:7000", "[::]:7000"].iter() {
+            let error = local_addresses(address).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
@@ -0,0 +1,424 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate serde_json;
+
+use bounty::{Bounty, ScoreDecay};
+use error::{Error, WorldError};
+use game_mode::Ruleset;
+use handicap::Comeback;
+use rules::MatchRules;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use world::{WorldConfig, WorldStats};
+use world_manager::{RoomInfo, WorldManager};
+
+/// Invalid JSON was received
+const PARSE_ERROR: i64 = -32700;
+/// The JSON sent is not a valid request object
+const INVALID_REQUEST: i64 = -32600;
+/// The method does not exist
+const METHOD_NOT_FOUND: i64 = -32601;
+/// Invalid method parameters
+const INVALID_PARAMS: i64 = -32602;
+/// The operation failed
+const SERVER_ERROR: i64 = -32000;
+
+/// A JSON-RPC request
+#[derive(Deserialize)]
+pub struct RpcRequest {
+    /// Protocol version, must be "2.0"
+    pub jsonrpc: String,
+    /// Identifies the request, echoed back in the response
+    #[serde(default)]
+    pub id: Value,
+    /// Name of the operation
+    pub method: String,
+    /// Parameters of the operation
+    #[serde(default)]
+    pub params: Value,
+}
+
+/// A JSON-RPC response
+#[derive(Serialize)]
+pub struct RpcResponse {
+    /// Protocol version, always "2.0"
+    jsonrpc: &'static str,
+    /// ID of the request this responds to
+    id: Value,
+    /// Result of a successful operation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    /// Error of a failed operation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+/// A JSON-RPC error
+#[derive(Serialize)]
+pub struct RpcError {
+    /// Category of error
+    code: i64,
+    /// Description of the error
+    message: String,
+}
+
+impl RpcResponse {
+    /// Constructor for a successful response
+    pub fn success(id: Value, result: Value) -> Self {
+        RpcResponse {
+            jsonrpc: "2.0",
+            id: id,
+            result: Some(result),
+            error: None,
+        }
+    }
+    /// Constructor for a failed response
+    pub fn failure(id: Value, code: i64, message: String) -> Self {
+        RpcResponse {
+            jsonrpc: "2.0",
+            id: id,
+            result: None,
+            error: Some(RpcError {
+                code: code,
+                message: message,
+            }),
+        }
+    }
+}
+
+/// Every operation the control plane supports
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControlRequest {
+    /// "create_room": starts a new room
+    CREATE(CreateRoomParams),
+    /// "close_room": stops a room
+    CLOSE(RoomParams),
+    /// "list_rooms": lists every room
+    ROOMS,
+    /// "stats": statistics about a room's world
+    STATS(RoomParams),
+    /// "ban": bans a player's address and removes them
+    BAN(PlayerParams),
+    /// "handoff": moves a player to a sibling server
+    HANDOFF(HandoffParams),
+    /// "time_scale": slows down or speeds up a room's world
+    TIME_SCALE(TimeScaleParams),
+}
+
+/// Parameters of "create_room"
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct CreateRoomParams {
+    pub name: String,
+    pub width: f32,
+    pub height: f32,
+    /// When the room's matches end; endless unless given
+    #[serde(default)]
+    pub rules: MatchRules,
+    /// Whether the room can be paused, rewound, and have targets added, for practice
+    #[serde(default)]
+    pub practice: bool,
+    /// Buffs for players far behind the leader; off unless given
+    #[serde(default)]
+    pub comeback: Option<Comeback>,
+    /// Bounty put on the leader; off unless given
+    #[serde(default)]
+    pub bounty: Option<Bounty>,
+    /// Decay of the experience of players that stop scoring; off unless given
+    #[serde(default)]
+    pub decay: Option<ScoreDecay>,
+    /// The mode the room is played under; free for all unless given
+    #[serde(default)]
+    pub ruleset: Ruleset,
+}
+
+/// Parameters of operations on a room
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct RoomParams {
+    pub room_id: usize,
+}
+
+/// Parameters of operations on a player
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct PlayerParams {
+    pub player_id: usize,
+}
+
+/// Parameters of "handoff"
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct HandoffParams {
+    pub player_id: usize,
+    /// Address of the server the player should reconnect to
+    pub url: String,
+}
+
+/// Parameters of "time_scale"
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct TimeScaleParams {
+    pub room_id: usize,
+    /// Ticks simulated per update, from 0.1 to 4
+    pub time_scale: f32,
+}
+
+/// Result of "create_room"
+#[derive(Serialize)]
+pub struct CreatedRoom {
+    pub room_id: usize,
+}
+
+/// Result of "ban"
+#[derive(Serialize)]
+pub struct Banned {
+    pub address: String,
+}
+
+/// Result of "handoff"
+#[derive(Serialize)]
+pub struct HandedOff {
+    /// Token the player will present to the other server
+    pub token: String,
+}
+
+/// Functions for ControlRequest
+impl ControlRequest {
+    /// Builds a request from a method name and its parameters
+    /// return: the request, or a JSON-RPC error code and message
+    pub fn from_rpc(method: &str, params: Value) -> Result<Self, (i64, String)> {
+        match method {
+            "create_room" => parse_params(params).map(ControlRequest::CREATE),
+            "close_room" => parse_params(params).map(ControlRequest::CLOSE),
+            "list_rooms" => Ok(ControlRequest::ROOMS),
+            "stats" => parse_params(params).map(ControlRequest::STATS),
+            "ban" => parse_params(params).map(ControlRequest::BAN),
+            "handoff" => parse_params(params).map(ControlRequest::HANDOFF),
+            "time_scale" => parse_params(params).map(ControlRequest::TIME_SCALE),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method: {}", method))),
+        }
+    }
+
+    /// Runs the operation against the server's rooms
+    /// return: the result of the operation as JSON
+    pub fn execute(&self, manager: &Arc<RwLock<WorldManager>>) -> Result<Value, Error> {
+        let mut manager = match manager.write() {
+            Ok(write_lock) => write_lock,
+            Err(_) => return Err(WorldError::LOCK_POISONED("the rooms").into()),
+        };
+        let result = match *self {
+            ControlRequest::CREATE(ref params) => {
+                let mut world_config = WorldConfig::new(params.width, params.height)
+                    .with_rules(params.rules)
+                    .with_ruleset(params.ruleset);
+                world_config.practice = params.practice;
+                world_config.comeback = params.comeback;
+                world_config.bounty = params.bounty;
+                world_config.decay = params.decay;
+                let room_id = manager.create_room(params.name.clone(), world_config)?;
+                serde_json::to_value(CreatedRoom { room_id: room_id })
+            }
+            ControlRequest::CLOSE(ref params) => {
+                manager.close_room(params.room_id)?;
+                Ok(Value::Null)
+            }
+            ControlRequest::ROOMS => {
+                let rooms: Vec<RoomInfo> = manager.rooms();
+                serde_json::to_value(rooms)
+            }
+            ControlRequest::STATS(ref params) => {
+                let world = match manager.room(params.room_id) {
+                    Some(world) => world,
+                    None => return Err(WorldError::NO_ROOM(params.room_id).into()),
+                };
+                let stats: WorldStats = match world.read() {
+                    Ok(world) => world.stats(),
+                    Err(_) => return Err(WorldError::LOCK_POISONED("the world").into()),
+                };
+                serde_json::to_value(stats)
+            }
+            ControlRequest::BAN(ref params) => {
+                let address = manager.ban(params.player_id)?;
+                serde_json::to_value(Banned {
+                    address: address.to_string(),
+                })
+            }
+            ControlRequest::HANDOFF(ref params) => {
+                let token = manager.handoff(params.player_id, params.url.clone())?;
+                serde_json::to_value(HandedOff { token: token })
+            }
+            ControlRequest::TIME_SCALE(ref params) => {
+                let world = match manager.room(params.room_id) {
+                    Some(world) => world,
+                    None => return Err(WorldError::NO_ROOM(params.room_id).into()),
+                };
+                match world.write() {
+                    Ok(mut world) => world.set_time_scale(params.time_scale)?,
+                    Err(_) => return Err(WorldError::LOCK_POISONED("the world").into()),
+                };
+                Ok(Value::Null)
+            }
+        };
+        Ok(result?)
+    }
+}
+
+/// Parses the parameters of a method
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, (i64, String)> {
+    serde_json::from_value(params).map_err(|error| (INVALID_PARAMS, error.to_string()))
+}
+
+/// Handles one line of JSON-RPC input
+/// return: the serialized response
+pub fn handle_line(line: &str, manager: &Arc<RwLock<WorldManager>>) -> String {
+    let response = match serde_json::from_str::<RpcRequest>(line) {
+        Ok(request) => {
+            if request.jsonrpc != "2.0" {
+                RpcResponse::failure(request.id, INVALID_REQUEST, "Unsupported version".into())
+            } else {
+                match ControlRequest::from_rpc(&request.method, request.params) {
+                    Ok(control_request) => match control_request.execute(manager) {
+                        Ok(result) => RpcResponse::success(request.id, result),
+                        Err(error) => {
+                            RpcResponse::failure(request.id, SERVER_ERROR, error.to_string())
+                        }
+                    },
+                    Err((code, message)) => RpcResponse::failure(request.id, code, message),
+                }
+            }
+        }
+        Err(error) => RpcResponse::failure(Value::Null, PARSE_ERROR, error.to_string()),
+    };
+    match serde_json::to_string(&response) {
+        Ok(serialized) => serialized,
+        Err(error) => {
+            error!("Failed to serialize control response: {}", error);
+            r#"{"jsonrpc": "2.0", "id": null, "error": {"code": -32603, "message": "Internal error"}}"#.into()
+        }
+    }
+}
+
+/// Accepts control plane connections on a TCP address, one thread per connection
+/// Requests and responses are JSON-RPC 2.0 objects, one per line
+/// Blocks the calling thread
+pub fn run_tcp(address: &str, manager: Arc<RwLock<WorldManager>>) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    info!("Control plane listening on {}", address);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let manager = manager.clone();
+                thread::spawn(move || match handle_connection(stream, &manager) {
+                    Ok(_) => {}
+                    Err(error) => warn!("Control plane connection failed: {}", error),
+                });
+            }
+            Err(error) => warn!("Failed to accept control plane connection: {}", error),
+        }
+    }
+    Ok(())
+}
+
+/// Handles requests from one control plane connection until it closes
+fn handle_connection(stream: TcpStream, manager: &Arc<RwLock<WorldManager>>) -> io::Result<()> {
+    info!("Control plane connection from {}", stream.peer_addr()?);
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        writer.write_all(handle_line(&line, manager).as_bytes())?;
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn create_room_params() {
+        let params = serde_json::from_str(r#"{"name": "arena", "width": 4000, "height": 3000}"#);
+        let request = ControlRequest::from_rpc("create_room", params.unwrap()).unwrap();
+        match request {
+            ControlRequest::CREATE(ref params) => assert_eq!(params.ruleset, Ruleset::FREE_FOR_ALL),
+            _ => panic!("Expected create_room, got {:?}", request),
+        }
+        let params = serde_json::from_str(
+            r#"{"name": "ctf", "width": 2000, "height": 1000, "ruleset": "capture_the_flag"}"#,
+        );
+        let manager = WorldManager::manual(WorldConfig::new(1000.0, 1000.0), 60).unwrap();
+        let manager = Arc::new(RwLock::new(manager));
+        let created = ControlRequest::from_rpc("create_room", params.unwrap())
+            .unwrap()
+            .execute(&manager)
+            .unwrap();
+        let rooms = ControlRequest::ROOMS.execute(&manager).unwrap();
+        assert_eq!(rooms[1]["room_id"], created["room_id"]);
+        assert_eq!(rooms[1]["mode"], "capture_the_flag");
+        assert_eq!(rooms[1]["width"], 2000.0);
+    }
+
+    #[test]
+    fn unknown_method() {
+        assert_eq!(
+            ControlRequest::from_rpc("reboot", Value::Null).unwrap_err().0,
+            METHOD_NOT_FOUND
+        );
+        assert_eq!(
+            ControlRequest::from_rpc("list_rooms", Value::Null).unwrap(),
+            ControlRequest::ROOMS
+        );
+    }
+
+    #[test]
+    fn execute_control_request() {
+        let manager = Arc::new(RwLock::new(WorldManager::new(WorldConfig::new(1000.0, 1000.0), 60).unwrap()));
+        let request = ControlRequest::CREATE(CreateRoomParams {
+            name: "duel".into(),
+            width: 0.0,
+            height: 400.0,
+            rules: MatchRules::default(),
+            practice: false,
+            comeback: None,
+            bounty: None,
+            decay: None,
+            ruleset: Ruleset::DUEL,
+        });
+        assert!(request.execute(&manager).is_err());
+        assert!(
+            ControlRequest::CLOSE(RoomParams { room_id: 5 })
+                .execute(&manager)
+                .is_err()
+        );
+        assert!(
+            ControlRequest::BAN(PlayerParams { player_id: 5 })
+                .execute(&manager)
+                .is_err()
+        );
+        let slow_motion = ControlRequest::TIME_SCALE(TimeScaleParams {
+            room_id: 0,
+            time_scale: 0.5,
+        });
+        assert!(slow_motion.execute(&manager).is_ok());
+        let too_fast = ControlRequest::TIME_SCALE(TimeScaleParams {
+            room_id: 0,
+            time_scale: 8.0,
+        });
+        assert!(too_fast.execute(&manager).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use entity::Swarm;
+use handicap::Handicap;
+use rand::Rng;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use swarm_language::{Formation, SwarmCommand, SwarmProgram, MAX_TURN};
+
+/// Highest sophistication of the programs swarms controlled by the game run
+pub const MAX_SOPHISTICATION: u32 = 3;
+
+/// How hard the swarms controlled by the game are, from learning the game to expert play
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum Difficulty {
+    /// Slow swarms that wander and never fire
+    #[serde(rename = "tutorial")]
+    TUTORIAL,
+    #[serde(rename = "easy")]
+    EASY,
+    #[serde(rename = "normal")]
+    NORMAL,
+    #[serde(rename = "hard")]
+    HARD,
+    /// Fast, accurate swarms that hit harder than players
+    #[serde(rename = "brutal")]
+    BRUTAL,
+}
+
+/// Functions for Difficulty
+impl Difficulty {
+    /// Every difficulty, easiest first
+    pub const ALL: [Difficulty; 5] = [
+        Difficulty::TUTORIAL,
+        Difficulty::EASY,
+        Difficulty::NORMAL,
+        Difficulty::HARD,
+        Difficulty::BRUTAL,
+    ];
+    /// How swarms at this difficulty behave
+    pub fn profile(&self) -> BehaviorProfile {
+        match *self {
+            Difficulty::TUTORIAL => BehaviorProfile::new(3, 30.0, 0, Handicap::new(0.5, 0.6)),
+            Difficulty::EASY => BehaviorProfile::new(2, 20.0, 1, Handicap::new(0.75, 0.8)),
+            Difficulty::NORMAL => BehaviorProfile::new(1, 10.0, 1, Handicap::default()),
+            Difficulty::HARD => BehaviorProfile::new(0, 5.0, 2, Handicap::new(1.25, 1.1)),
+            Difficulty::BRUTAL => BehaviorProfile::new(0, 0.0, 3, Handicap::new(1.5, 1.25)),
+        }
+    }
+    /// The difficulty some number of steps harder, stopping at the hardest
+    pub fn harder(&self, steps: usize) -> Difficulty {
+        let index = Difficulty::ALL
+            .iter()
+            .position(|difficulty| difficulty == self)
+            .unwrap_or(0);
+        Difficulty::ALL[(index + steps).min(Difficulty::ALL.len() - 1)]
+    }
+}
+
+/// How a swarm controlled by the game plays
+/// The language has no way to react to the world, so reactions and aim are approximated
+/// by the program the swarm is given
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct BehaviorProfile {
+    /// Ticks the swarm idles after each command
+    pub reaction_delay: u32,
+    /// Most degrees each of the program's turns can be off by, within the language's range
+    pub aim_error: f32,
+    /// Which of the standard programs is run, from 0 to MAX_SOPHISTICATION
+    pub sophistication: u32,
+    /// How hard the swarm hits and how fast it moves
+    pub handicap: Handicap,
+}
+
+/// Functions for BehaviorProfile
+impl BehaviorProfile {
+    /// Constructor
+    pub fn new(
+        reaction_delay: u32,
+        aim_error: f32,
+        sophistication: u32,
+        handicap: Handicap,
+    ) -> Self {
+        BehaviorProfile {
+            reaction_delay: reaction_delay,
+            aim_error: aim_error,
+            sophistication: sophistication.min(MAX_SOPHISTICATION),
+            handicap: handicap,
+        }
+    }
+    /// Builds a program for one swarm
+    /// Each swarm's turns are off by a different amount, so a wave doesn't move in lockstep
+    pub fn program<R: Rng>(&self, rng: &mut R) -> SwarmProgram {
+        let mut commands = Vec::new();
+        for command in standard_program(self.sophistication) {
+            commands.push(match command {
+                SwarmCommand::TURN(degrees) if self.aim_error > 0.0 => {
+                    let degrees = degrees + rng.gen_range(-self.aim_error, self.aim_error);
+                    SwarmCommand::TURN(degrees.max(-MAX_TURN).min(MAX_TURN))
+                }
+                command => command,
+            });
+            for _ in 0..self.reaction_delay {
+                commands.push(SwarmCommand::NOOP);
+            }
+        }
+        SwarmProgram::new(commands)
+    }
+    /// Gives a swarm the profile's program and handicap
+    pub fn apply<R: Rng>(&self, swarm: &mut Swarm, rng: &mut R) {
+        swarm.program = self.program(rng);
+        swarm.handicap = self.handicap;
+    }
+}
+
+/// The programs swarms controlled by the game run, from simplest to most sophisticated
+fn standard_program(sophistication: u32) -> Vec<SwarmCommand> {
+    match sophistication {
+        // Wander in a wide circle
+        0 => vec![SwarmCommand::MOVE, SwarmCommand::TURN(15.0)],
+        // Patrol, firing now and then
+        1 => vec![
+            SwarmCommand::MOVE,
+            SwarmCommand::MOVE,
+            SwarmCommand::FIRE,
+            SwarmCommand::TURN(25.0),
+        ],
+        // Weave back and forth while firing
+        2 => vec![
+            SwarmCommand::MOVE,
+            SwarmCommand::FIRE,
+            SwarmCommand::TURN(30.0),
+            SwarmCommand::MOVE,
+            SwarmCommand::FIRE,
+            SwarmCommand::TURN(-30.0),
+        ],
+        // Spread out to sweep, then gather to dodge
+        _ => vec![
+            SwarmCommand::FORMATION(Formation::SPREAD),
+            SwarmCommand::MOVE,
+            SwarmCommand::FIRE,
+            SwarmCommand::TURN(20.0),
+            SwarmCommand::FIRE,
+            SwarmCommand::MOVE,
+            SwarmCommand::FORMATION(Formation::GATHER),
+            SwarmCommand::FIRE,
+            SwarmCommand::TURN(-30.0),
+            SwarmCommand::MOVE,
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use world::World;
+
+    #[test]
+    fn difficulty_profiles() {
+        let mut rng = World::seeded_rng(4);
+        let tutorial = Difficulty::TUTORIAL.profile().program(&mut rng);
+        // Tutorial swarms never fire, and idle between commands
+        assert!(!tutorial.commands.contains(&SwarmCommand::FIRE));
+        assert_eq!(tutorial.commands.len(), 8);
+        assert_eq!(tutorial.commands[1], SwarmCommand::NOOP);
+        // Brutal swarms aim exactly and never idle
+        let brutal = Difficulty::BRUTAL.profile().program(&mut rng);
+        assert_eq!(brutal.commands, standard_program(MAX_SOPHISTICATION));
+        assert_eq!(Difficulty::EASY.harder(2), Difficulty::HARD);
+        assert_eq!(Difficulty::HARD.harder(5), Difficulty::BRUTAL);
+    }
+}
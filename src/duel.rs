@@ -0,0 +1,174 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+#[cfg(feature = "server")]
+use std::sync::Arc;
+use std::sync::RwLock;
+#[cfg(feature = "server")]
+use world_manager::{ServerEvent, ServerListener};
+
+/// Rating of a player that hasn't played a ranked duel
+pub const DEFAULT_RATING: f64 = 1500.0;
+/// Most a rating can change after one duel
+pub const K_FACTOR: f64 = 32.0;
+
+/// The chance a player wins against an opponent, from their ratings
+pub fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+/// New ratings for the winner and loser of a duel
+/// Beating a higher rated player is worth more than beating a lower rated one
+pub fn rate(winner: f64, loser: f64) -> (f64, f64) {
+    let change = K_FACTOR * (1.0 - expected_score(winner, loser));
+    (winner + change, loser - change)
+}
+
+/// Keeps players' ratings between duels
+/// Shared between room update threads, so implementations handle their own locking
+pub trait RatingStore: Send + Sync + Debug {
+    /// The player's rating, if they've played a ranked duel
+    fn rating(&self, player_id: usize) -> Option<f64>;
+    /// Saves the player's rating
+    fn set_rating(&self, player_id: usize, rating: f64);
+}
+
+/// Ratings kept in memory, lost when the server stops
+#[derive(Debug, Default)]
+pub struct MemoryRatings {
+    /// Rating of each player
+    ratings: RwLock<HashMap<usize, f64>>,
+}
+
+/// Functions for MemoryRatings
+impl MemoryRatings {
+    /// Constructor
+    pub fn new() -> Self {
+        MemoryRatings::default()
+    }
+}
+
+/// Keeps ratings in a map, behind a lock
+impl RatingStore for MemoryRatings {
+    fn rating(&self, player_id: usize) -> Option<f64> {
+        match self.ratings.read() {
+            Ok(ratings) => ratings.get(&player_id).cloned(),
+            Err(_) => None,
+        }
+    }
+    fn set_rating(&self, player_id: usize, rating: f64) {
+        match self.ratings.write() {
+            Ok(mut ratings) => {
+                ratings.insert(player_id, rating);
+            }
+            Err(error) => error!("Error getting write lock on ratings: {}", error),
+        }
+    }
+}
+
+/// Players waiting for a ranked duel, paired in the order they joined
+#[derive(Clone, Debug, Default)]
+pub struct DuelQueue {
+    /// IDs of the waiting players, first to join first
+    waiting: VecDeque<usize>,
+}
+
+/// Functions for DuelQueue
+impl DuelQueue {
+    /// Constructor
+    pub fn new() -> Self {
+        DuelQueue::default()
+    }
+    /// Adds a player to the queue
+    /// return: the two longest waiting players, once there are two
+    pub fn join(&mut self, player_id: usize) -> Option<(usize, usize)> {
+        if !self.waiting.contains(&player_id) {
+            self.waiting.push_back(player_id);
+        }
+        if self.waiting.len() < 2 {
+            return None;
+        }
+        match (self.waiting.pop_front(), self.waiting.pop_front()) {
+            (Some(first), Some(second)) => Some((first, second)),
+            _ => None,
+        }
+    }
+    /// Takes a player out of the queue
+    pub fn leave(&mut self, player_id: usize) {
+        self.waiting.retain(|&waiting| waiting != player_id);
+    }
+    /// Number of players waiting
+    pub fn len(&self) -> usize {
+        self.waiting.len()
+    }
+    /// Whether nobody is waiting
+    pub fn is_empty(&self) -> bool {
+        self.waiting.is_empty()
+    }
+}
+
+/// Saves the ratings from every finished duel
+#[cfg(feature = "server")]
+pub struct RatingListener {
+    /// Where ratings are kept
+    pub store: Arc<RatingStore>,
+}
+
+/// Saves both players' new ratings when a duel ends
+#[cfg(feature = "server")]
+impl ServerListener for RatingListener {
+    fn on_event(&self, event: &ServerEvent) {
+        if let ServerEvent::DUEL_OVER {
+            winner,
+            loser,
+            winner_rating,
+            loser_rating,
+            ..
+        } = *event
+        {
+            self.store.set_rating(winner, winner_rating);
+            self.store.set_rating(loser, loser_rating);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_and_queue() {
+        assert_eq!(rate(1500.0, 1500.0), (1516.0, 1484.0));
+        // An upset moves ratings further
+        let (winner, loser) = rate(1400.0, 1600.0);
+        assert!(winner - 1400.0 > 16.0);
+        assert_eq!(winner + loser, 3000.0);
+
+        let mut queue = DuelQueue::new();
+        assert_eq!(queue.join(4), None);
+        assert_eq!(queue.join(4), None);
+        queue.leave(4);
+        assert_eq!(queue.join(5), None);
+        assert_eq!(queue.join(6), Some((5, 6)));
+        assert!(queue.is_empty());
+
+        let store = MemoryRatings::new();
+        assert_eq!(store.rating(5), None);
+        store.set_rating(5, 1516.0);
+        assert_eq!(store.rating(5), Some(1516.0));
+    }
+}
@@ -13,63 +13,277 @@
 //
 // You should have received a copy of the GNU General Public License
 // along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
-use swarm_language::{Formation, SwarmCommand, SwarmProgram};
+use fixed::{from_coord, to_coord, Coord};
+#[cfg(feature = "fixed_point")]
+use fixed;
+#[cfg(feature = "fixed_point")]
+use fixed::Fixed;
+#[cfg(feature = "fixed_point")]
+use integrate::integrate_fixed;
+#[cfg(not(feature = "fixed_point"))]
+use integrate::integrate;
+use boundary::Boundary;
+use handicap::Handicap;
+use limits::Spread;
+use player_stats::Weapon;
+use swarm_language::{Condition, Formation, SwarmCommand, SwarmProgram, Trigger, MAX_TURN};
 use world::World;
 use std::f32;
-use rand::{thread_rng, Rng};
+use std::mem;
+use rand::Rng;
+#[cfg(feature = "schema")]
+use schemars::gen::SchemaGenerator;
+#[cfg(feature = "schema")]
+use schemars::schema::Schema;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, SerializeSeq, Serializer};
 
 /// The initial size of a swarm
-const INITIAL_SWARM_SIZE: usize = 10;
+pub const INITIAL_SWARM_SIZE: usize = 10;
+/// IDs from here up belong to swarms controlled by the game instead of a player
+pub const NPC_ID_START: usize = 1 << 30;
+
+/// Whether a swarm ID belongs to a swarm controlled by the game
+pub fn is_npc(id: usize) -> bool {
+    id >= NPC_ID_START
+}
+/// Whether a value is 0, so it can be left out of messages
+fn is_zero(value: &f32) -> bool {
+    *value == 0.0
+}
+/// Whether a swarm has no bounty on it
+fn no_bounty(bounty: &i64) -> bool {
+    *bounty == 0
+}
 /// The maximum size of a swarm
 const MAX_SWARM_SIZE: usize = 20;
 
+/// What a swarm senses of the world around it, for conditions a program branches on
+/// and sensor commands
+/// The world sets it before each update that senses, since a swarm can't see the others
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Senses {
+    /// Another swarm with members is within SENSE_RANGE
+    pub enemy_near: bool,
+    /// The edge of the world or an obstacle is within WALL_DISTANCE ahead
+    pub wall_ahead: bool,
+    /// Number of other swarms with members within SENSE_RANGE
+    pub enemies: usize,
+    /// (x, y) of the closest other swarm with members within SENSE_RANGE
+    pub nearest_enemy: Option<(f32, f32)>,
+    /// (x, y) of the closest bullet fired by another player within SENSE_RANGE
+    pub nearest_bullet: Option<(f32, f32)>,
+}
+
+/// What changed about a swarm since the last delta was recorded
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Dirty {
+    /// Position or direction changed
+    pub moved: bool,
+    /// Members were damaged or destroyed
+    pub damaged: bool,
+    /// Members were rearranged into a formation
+    pub formation: bool,
+    /// Experience changed
+    pub scored: bool,
+}
+/// Functions for dirty flags
+impl Dirty {
+    /// Every flag set, for swarms that are new to clients
+    pub fn all() -> Self {
+        Dirty {
+            moved: true,
+            damaged: true,
+            formation: true,
+            scored: true,
+        }
+    }
+    /// Whether any flag is set
+    pub fn any(&self) -> bool {
+        self.moved || self.damaged || self.formation || self.scored
+    }
+}
+
 /// Represents a player's swarm
-#[derive(Clone, Debug, Serialize)]
+/// Fields that aren't sent to clients get their defaults when deserialized
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct Swarm {
+    /// Number of members, so distant swarms can be drawn as one shape
+    #[serde(default)]
+    pub count: usize,
+    /// Center of the members, in world coordinates
+    #[serde(default)]
+    pub centroid: (f32, f32),
+    /// Average direction the members face
+    #[serde(default)]
+    pub facing: f32,
     /// X position
     pub x: f32,
     /// Y position
     pub y: f32,
     /// Direction the swarm is facing
-    #[serde(skip_serializing)]
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
     pub direction: f32,
+    /// Unit vector of the direction, as (direction it was computed for, cos, sin)
+    /// Recomputed only when the direction changes
+    #[serde(skip, default = "Swarm::default_heading")]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    heading: (f32, f32, f32),
+    /// Distance moved along the x axis during the last tick, left out when 0
+    /// Lets clients extrapolate between updates
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub vx: f32,
+    /// Distance moved along the y axis during the last tick, left out when 0
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub vy: f32,
+    /// Degrees the swarm and its members turned during the last tick, left out when 0
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub spin: f32,
     /// Members of the swarm
     pub members: Vec<SwarmMember>,
     /// Offsets
-    #[serde(skip_serializing)]
+    #[serde(skip, default = "Swarm::default_offsets")]
+    #[cfg_attr(feature = "schema", schemars(skip))]
     pub offsets: Vec<(f32, f32)>,
     /// Color of the swarm
     pub color: (u8, u8, u8),
     /// Experience gained by the swarm
     pub experience: i64,
-    /// Fire cooldown in ticks
-    #[serde(skip_serializing)]
-    pub fire_cooldown: i64,
+    /// Experience given to whoever wipes out the swarm, left out when 0
+    #[serde(default, skip_serializing_if = "no_bounty")]
+    pub bounty: i64,
+    /// Ticks since the swarm last gained experience
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub ticks_since_scored: u64,
+    /// Members destroyed since the swarm last lost one
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub streak: u32,
+    /// Handicap set by an admin
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub handicap: Handicap,
+    /// Comeback buff set by the world while the swarm is far behind
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub boost: Handicap,
+    /// Fractional damage not yet dealt
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    damage_carry: f32,
+    /// What the swarm fires
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub weapon: Weapon,
+    /// Ticks the swarm waits between volleys, set by the world from its limits
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub fire_rate: u32,
+    /// Ticks until the swarm's weapon is ready
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub fire_cooldown: u32,
+    /// How far the swarm's bullets stray, set by the world from its limits
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub spread: Spread,
+    /// Volleys fired in a row, each straying further than the last
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub recoil: u32,
+    /// Ticks left until the recoil resets, unless the swarm fires again
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub recoil_timer: u32,
+    /// What the swarm sensed of the world before its last update
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub senses: Senses,
+    /// Index of the map's spawn point the swarm last spawned at, whose safe zone it can be in
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub home: Option<usize>,
+    /// Emote the swarm's program ran during its last update, taken by the world
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub emote: Option<u32>,
+    /// The most urgent event the swarm's program has a handler for and hasn't run it for yet
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub pending: Option<Trigger>,
+    /// Event handlers started since the program last ran a command outside of one
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub handlers_in_a_row: u32,
+    /// Control flow commands run since the program last ran any other command
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub control_steps: u32,
     /// Formation cooldown in ticks
-    #[serde(skip_serializing)]
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
     pub formation_cooldown: i64,
     /// Program used to execute the swarm
-    #[serde(skip_serializing)]
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
     pub program: SwarmProgram,
+    /// What changed since the world last recorded a delta
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub dirty: Dirty,
 }
 /// Functions for a swarm
 impl Swarm {
     /// Swarm speed
     const UPDATE_DISTANCE: f32 = 5.0;
+    /// How close another swarm has to be for ENEMY_NEAR to hold
+    pub const SENSE_RANGE: f32 = 200.0;
+    /// How far ahead WALL_AHEAD looks for the edge of the world or an obstacle
+    pub const WALL_DISTANCE: f32 = 50.0;
     /// Constructor
     pub fn new(x: f32, y: f32, num_members: usize) -> Self {
         // Build the offsets
-        let offsets = Swarm::calculate_offsets(30.0);
+        let offsets = Swarm::default_offsets();
         // Create the object
-        Swarm {
+        let mut swarm = Swarm {
+            count: 0,
+            centroid: (x, y),
+            facing: 0.0,
             x: x,
             y: y,
             direction: 0.0,
+            heading: Swarm::default_heading(),
+            vx: 0.0,
+            vy: 0.0,
+            spin: 0.0,
             members: Swarm::build_swarm(num_members, &offsets),
             offsets: offsets,
             color: (0, 0, 0),
             experience: 0,
-            fire_cooldown: 0,      // start with no cooldown
+            bounty: 0,
+            ticks_since_scored: 0,
+            streak: 0,
+            handicap: Handicap::default(),
+            boost: Handicap::default(),
+            damage_carry: 0.0,
+            weapon: Weapon::default(),
+            fire_rate: SwarmMember::FIRE_COOLDOWN,
+            fire_cooldown: 0,
+            spread: Spread::none(),
+            recoil: 0,
+            recoil_timer: 0,
+            senses: Senses::default(),
+            home: None,
+            emote: None,
+            pending: None,
+            handlers_in_a_row: 0,
+            control_steps: 0,
             formation_cooldown: 0, // start with no cooldown
             program: SwarmProgram::new(vec![
                 SwarmCommand::MOVE,
@@ -82,7 +296,18 @@ impl Swarm {
                 SwarmCommand::TURN(10.0),
                 SwarmCommand::FIRE,
             ]),
-        }
+            dirty: Dirty::all(),
+        };
+        swarm.summarize();
+        swarm
+    }
+    /// Heading of a swarm facing direction 0
+    fn default_heading() -> (f32, f32, f32) {
+        (0.0, 1.0, 0.0)
+    }
+    /// Offsets of the default formation
+    fn default_offsets() -> Vec<(f32, f32)> {
+        Swarm::calculate_offsets(30.0)
     }
     /// Builds a swarm of N members
     pub fn build_swarm(num_members: usize, offsets: &Vec<(f32, f32)>) -> Vec<SwarmMember> {
@@ -99,6 +324,97 @@ impl Swarm {
     /// Adds experience based on stuff TODO TODO
     pub fn add_experience(&mut self, amt: &i64) {
         self.experience += amt;
+        if *amt > 0 {
+            self.ticks_since_scored = 0;
+        }
+        self.dirty.scored = true;
+    }
+
+    /// Resets values that aren't finite and moves the swarm back inside the world
+    /// The swarm goes to the center of the world if its position isn't finite
+    /// return: whether any value wasn't finite
+    pub fn sanitize(&mut self, world_width: f32, world_height: f32) -> bool {
+        let mut reset = false;
+        if !(self.x.is_finite() && self.y.is_finite()) {
+            self.x = world_width / 2.0;
+            self.y = world_height / 2.0;
+            reset = true;
+        }
+        if !self.direction.is_finite() {
+            self.direction = 0.0;
+            reset = true;
+        }
+        for (index, member) in self.members.iter_mut().enumerate() {
+            if !(member.x.is_finite() && member.y.is_finite()) {
+                member.x = self.offsets[index].0;
+                member.y = self.offsets[index].1;
+                reset = true;
+            }
+            if !member.direction.is_finite() {
+                member.direction = 0.0;
+                reset = true;
+            }
+        }
+        if !(self.vx.is_finite() && self.vy.is_finite() && self.spin.is_finite()) {
+            self.vx = 0.0;
+            self.vy = 0.0;
+            self.spin = 0.0;
+            reset = true;
+        }
+        let (x, y) = (
+            self.x.max(0.0).min(world_width),
+            self.y.max(0.0).min(world_height),
+        );
+        if reset || x != self.x || y != self.y {
+            self.x = x;
+            self.y = y;
+            self.dirty.moved = true;
+        }
+        reset
+    }
+
+    /// Records how far the swarm moved and turned since it was at (x, y) facing direction
+    /// A swarm whose velocity changed is marked as moved, so clients stop extrapolating it
+    /// once it stops
+    pub fn record_velocity(&mut self, x: f32, y: f32, direction: f32) {
+        let vx = self.x - x;
+        let vy = self.y - y;
+        // Turns are measured the short way round
+        let spin = ((self.direction - direction) % 360.0 + 540.0) % 360.0 - 180.0;
+        if vx != self.vx || vy != self.vy || spin != self.spin {
+            self.vx = vx;
+            self.vy = vy;
+            self.spin = spin;
+            self.dirty.moved = true;
+        }
+    }
+
+    /// Updates the member count, centroid and facing from the members
+    /// Directions are averaged as unit vectors, so 350 and 10 average to 0 rather than 180
+    pub fn summarize(&mut self) {
+        self.count = self.members.len();
+        if self.members.is_empty() {
+            self.centroid = (self.x, self.y);
+            self.facing = self.direction;
+            return;
+        }
+        let count = self.count as f32;
+        let (mut x, mut y, mut cos, mut sin) = (0.0, 0.0, 0.0, 0.0);
+        for member in self.members.iter() {
+            x += member.x;
+            y += member.y;
+            cos += member.direction.to_radians().cos();
+            sin += member.direction.to_radians().sin();
+        }
+        self.centroid = (self.x + x / count, self.y + y / count);
+        self.facing = (sin.atan2(cos).to_degrees() + 360.0) % 360.0;
+    }
+
+    /// Approximate bytes allocated by the swarm, not counting the swarm itself
+    pub fn memory_usage(&self) -> usize {
+        self.members.capacity() * mem::size_of::<SwarmMember>()
+            + self.offsets.capacity() * mem::size_of::<(f32, f32)>()
+            + self.program.commands.capacity() * mem::size_of::<SwarmCommand>()
     }
 
     /// Supplementary function to add color to a swarm. Typically used with the constructor
@@ -106,95 +422,188 @@ impl Swarm {
         self.color = color;
         self
     }
+    /// A new swarm at a position with this swarm's color and program,
+    /// but with the initial members and no experience
+    pub fn respawned(&self, x: f32, y: f32) -> Swarm {
+        let mut respawned = Swarm::new(x, y, INITIAL_SWARM_SIZE).with_color(self.color);
+        respawned.program = self.program.clone();
+        respawned.program.restart();
+        respawned.handicap = self.handicap;
+        respawned.weapon = self.weapon;
+        respawned.fire_rate = self.fire_rate;
+        respawned.spread = self.spread;
+        respawned.raise(Trigger::SPAWN);
+        respawned
+    }
+    /// Records that an event happened, for the program's handler to run on the next update
+    /// Events the program has no handler for are ignored, and less urgent ones are replaced
+    pub fn raise(&mut self, trigger: Trigger) {
+        if self.program.handles(trigger) && self.pending.map_or(true, |pending| trigger < pending)
+        {
+            self.pending = Some(trigger);
+        }
+    }
+    /// Interrupts the program with the handler for the pending event, if any
+    /// The event stays pending while another handler is being run
+    pub fn dispatch(&mut self) {
+        if self.program.interrupted.is_some() {
+            return;
+        }
+        if let Some(trigger) = self.pending.take() {
+            if self.program.interrupt(trigger) {
+                self.handlers_in_a_row += 1;
+            }
+        }
+    }
+    /// Stores what the swarm senses before an update,
+    /// raising ENEMY_SIGHTED if another swarm came close when none was
+    pub fn sense(&mut self, senses: Senses) {
+        if senses.enemy_near && !self.senses.enemy_near {
+            self.raise(Trigger::ENEMY_SIGHTED);
+        }
+        self.senses = senses;
+    }
+    /// Distance moved per tick, after handicaps and buffs
+    pub fn speed(&self) -> f32 {
+        Swarm::UPDATE_DISTANCE * self.handicap.speed * self.boost.speed
+    }
+    /// Damage dealt by the next hit, after handicaps and buffs
+    /// Fractions carry over to later hits, so a 0.5 multiplier deals 1 damage every other hit
+    pub fn hit_damage(&mut self) -> u32 {
+        self.damage_carry += (self.handicap.damage * self.boost.damage).max(0.0);
+        let damage = self.damage_carry.floor();
+        self.damage_carry -= damage;
+        damage as u32
+    }
     /// Performs 1 tick
-    pub fn update(
+    /// boundary: what happens to the swarm if it moves past the edge of the world
+    /// rng: the world's random number generator
+    pub fn update<R: Rng>(
         &mut self,
         swarm_id: usize,
         world_width: f32,
         world_height: f32,
-        bullets: &mut Vec<Bullet>,
+        boundary: Boundary,
+        bullets: &mut Bullets,
+        rng: &mut R,
     ) {
+        // Remember where the swarm was, to tell whether it moved
+        let (x, y, direction) = (self.x, self.y, self.direction);
+        let distance = self.speed();
+
         // TODO: put this somewhere else
 
-        if self.members.len() <= 0 {
+        if self.members.len() <= 0 && self.experience != 0 {
             self.experience = 0;
+            self.dirty.scored = true;
         }
 
         if self.program.commands.len() != 0 {
+            // Ending a handler goes back to a command that hasn't run yet
+            let mut resumed = false;
+            // A MOVE with a distance stays at the program counter until it's gone all of it,
+            // and a WAIT until its ticks are up
+            let mut held = false;
+            // Counted so limits can stop programs that only handle events or only jump around
+            if self.program.interrupted.is_none() {
+                self.handlers_in_a_row = 0;
+            }
+            if self.program.commands[self.program.program_counter].is_control_flow() {
+                self.control_steps += 1;
+            } else {
+                self.control_steps = 0;
+            }
             match self.program.commands[self.program.program_counter] {
                 SwarmCommand::MOVE => {
-                    // When within EPSILON of edge of the world, bounce off it
-                    const EPSILON: f32 = 10.0;
-                    if self.x - EPSILON <= 0.0 || self.x + EPSILON >= world_width
-                        || self.y - EPSILON <= 0.0
-                        || self.y + EPSILON >= world_height
-                    {
-                        self.direction = -self.direction;
-                    }
-
                     // Update the x and y position
-                    self.x += Swarm::UPDATE_DISTANCE * self.direction.to_radians().cos();
-                    self.y -= Swarm::UPDATE_DISTANCE * self.direction.to_radians().sin();
+                    let (cos, sin) = self.heading();
+                    self.x += distance * cos;
+                    self.y -= distance * sin;
+                }
+                SwarmCommand::MOVE_BY(total) => {
+                    let left = self.program.distance_left(total);
+                    let step = distance.min(left);
+                    let (cos, sin) = self.heading();
+                    self.x += step * cos;
+                    self.y -= step * sin;
+                    // A swarm that can't move gives up on the rest, so its program isn't stuck
+                    held = step > 0.0 && left - step > 0.0;
+                    self.program.moving = if held {
+                        Some((self.program.program_counter, left - step))
+                    } else {
+                        None
+                    };
                 }
                 SwarmCommand::LEFT => {
-                    // When within EPSILON of edge of the world, bounce off it
-                    const EPSILON: f32 = 10.0;
-                    if self.x - EPSILON >= 0.0 && self.x + EPSILON < world_width
-                        && self.y - EPSILON >= 0.0
-                        && self.y + EPSILON < world_height
-                    {
-                        self.x += Swarm::UPDATE_DISTANCE;
-                    }
+                    self.x += distance;
                 }
                 SwarmCommand::RIGHT => {
-                    // When within EPSILON of edge of the world, bounce off it
-                    const EPSILON: f32 = 10.0;
-                    if self.x - EPSILON >= 0.0 && self.x + EPSILON < world_width
-                        && self.y - EPSILON >= 0.0
-                        && self.y + EPSILON < world_height
-                    {
-                        self.x -= Swarm::UPDATE_DISTANCE;
-                    }
+                    self.x -= distance;
                 }
                 SwarmCommand::UP => {
-                    // When within EPSILON of edge of the world, bounce off it
-                    const EPSILON: f32 = 10.0;
-                    if self.x - EPSILON >= 0.0 && self.x + EPSILON < world_width
-                        && self.y - EPSILON >= 0.0
-                        && self.y + EPSILON < world_height
-                    {
-                        self.y -= Swarm::UPDATE_DISTANCE;
-                    }
+                    self.y -= distance;
                 }
                 SwarmCommand::DOWN => {
-                    // When within EPSILON of edge of the world, bounce off it
-                    const EPSILON: f32 = 10.0;
-                    if self.x - EPSILON >= 0.0 && self.x + EPSILON < world_width
-                        && self.y - EPSILON >= 0.0
-                        && self.y + EPSILON < world_height
-                    {
-                        self.y += Swarm::UPDATE_DISTANCE;
-                    }
+                    self.y += distance;
                 }
 
+                // The weapon's cooldown holds however often the program fires
                 SwarmCommand::FIRE => {
-                    // TODO maybe change the fire_cooldown scalar depending
-                    // on what kind of weapon is fired?
                     if self.fire_cooldown == 0 {
-                        self.fire(swarm_id, bullets);
-                        self.fire_cooldown = 10; // 60fps * 0.5 seconds
+                        // The random number generator is only used when bullets can stray,
+                        // so worlds without spread make the same choices as before
+                        let deviation = self.spread.deviation(self.recoil);
+                        let fired = self.fire_with(swarm_id, bullets, || {
+                            if deviation > 0.0 {
+                                rng.gen_range(-deviation, deviation)
+                            } else {
+                                0.0
+                            }
+                        });
+                        if fired > 0 {
+                            self.fire_cooldown = self.fire_rate;
+                            self.recoil += 1;
+                            self.recoil_timer = self.spread.reset;
+                        }
                     }
                 }
-                SwarmCommand::TURN(turn_amt) => {
-                    // turn logic
-                    self.direction += turn_amt;
-                    // Keep direction and program counter within their bounds
-                    self.direction %= 360.0;
-                    for member in self.members.iter_mut() {
-                        member.direction += turn_amt;
-                        member.direction %= 360.0;
-                    }
+                SwarmCommand::IF(condition) => {
+                    let holds = self.holds(condition);
+                    self.program.branch(holds);
+                }
+                SwarmCommand::ELSE => self.program.skip_else(),
+                SwarmCommand::ENDIF => {}
+                SwarmCommand::LOOP(count) => self.program.enter_loop(count),
+                SwarmCommand::ENDLOOP => self.program.end_loop(),
+                SwarmCommand::LABEL(_) => {}
+                SwarmCommand::JUMP(label) => self.program.jump(label),
+                SwarmCommand::SUB(_) => self.program.skip_sub(),
+                SwarmCommand::CALL(name) => self.program.call(name),
+                SwarmCommand::ENDSUB | SwarmCommand::RETURN => self.program.return_from_call(),
+                SwarmCommand::TURN(turn_amt) => self.turn(turn_amt),
+                SwarmCommand::TURN_REGISTER(register) => {
+                    let degrees = self.program.registers[register.index()] as f32;
+                    self.turn(degrees.max(-MAX_TURN).min(MAX_TURN));
+                }
+                SwarmCommand::CALC(operation, register, operand) => {
+                    self.program.calculate(operation, register, operand)
+                }
+                SwarmCommand::SCAN(count) => {
+                    self.program.registers[count.index()] = self.senses.enemies as i32;
+                }
+                SwarmCommand::NEAREST_ENEMY(distance, bearing) => {
+                    let (found_distance, found_bearing) = self.locate(self.senses.nearest_enemy);
+                    self.program.registers[distance.index()] = found_distance;
+                    self.program.registers[bearing.index()] = found_bearing;
+                }
+                SwarmCommand::NEAREST_BULLET(distance, bearing) => {
+                    let (found_distance, found_bearing) = self.locate(self.senses.nearest_bullet);
+                    self.program.registers[distance.index()] = found_distance;
+                    self.program.registers[bearing.index()] = found_bearing;
                 }
+                SwarmCommand::EMOTE(emote) => self.emote = Some(emote),
+                SwarmCommand::ON(_) => self.program.skip_handler(),
+                SwarmCommand::ENDON => resumed = self.program.end_handler(),
 
                 SwarmCommand::FORMATION(formation) => if self.formation_cooldown == 0 {
                     match formation {
@@ -212,44 +621,186 @@ impl Swarm {
                         }
 						
 						Formation::SIERPINSKI(val) => {
-							let sierpinski: Vec<(f32,f32)> = Swarm::sierpinski_offset(val);
+							let sierpinski: Vec<(f32,f32)> = Swarm::sierpinski_offset(val, rng);
 							for (index, member) in self.members.iter_mut().enumerate(){
 								member.x = sierpinski[sierpinski.len() - (1 + index)].0;
 								member.y = sierpinski[sierpinski.len() - (1 + index)].1;
 							}
 						}
                     };
-                    self.formation_cooldown = 30
+                    self.formation_cooldown = 30;
+                    self.dirty.formation = true;
                 },
                 SwarmCommand::NOOP => {}
+                SwarmCommand::WAIT(ticks) => {
+                    let left = self.program.ticks_left(ticks).saturating_sub(1);
+                    held = left > 0;
+                    self.program.waiting = if held {
+                        Some((self.program.program_counter, left))
+                    } else {
+                        None
+                    };
+                }
             }
 
             // Update program_counter to point to next command
-            self.program.program_counter += 1;
-            self.program.program_counter %= self.program.commands.len();
+            if !resumed && !held {
+                self.program.advance();
+            }
         }
-        self.fire_cooldown -= 1;
-        self.formation_cooldown -= 1;
-        if self.fire_cooldown < 0 {
-            self.fire_cooldown = 0;
+        // The edge of the world is handled the same way whichever command moved the swarm
+        if boundary.confine(self, world_width, world_height) {
+            self.dirty.damaged = true;
+            self.raise(Trigger::HIT);
+        }
+        self.cool_down();
+        if self.x != x || self.y != y || self.direction != direction {
+            self.dirty.moved = true;
+        }
+    }
+
+    /// Rounded distance and bearing of a sensed position from the swarm
+    /// The bearing is in degrees counterclockwise from the swarm's heading, from -180 to 180
+    /// return: (-1, 0) if nothing was sensed
+    fn locate(&self, position: Option<(f32, f32)>) -> (i32, i32) {
+        let (x, y) = match position {
+            Some(position) => position,
+            None => return (-1, 0),
+        };
+        let (dx, dy) = (x - self.x, y - self.y);
+        let distance = (dx * dx + dy * dy).sqrt();
+        // The y axis points down, while angles go counterclockwise
+        let bearing = (-dy).atan2(dx).to_degrees() - self.direction;
+        let bearing = ((bearing + 180.0) % 360.0 + 360.0) % 360.0 - 180.0;
+        (distance.round() as i32, bearing.round() as i32)
+    }
+    /// Rotates the swarm and its members
+    /// degrees: how far to turn, counterclockwise
+    fn turn(&mut self, degrees: f32) {
+        self.direction += degrees;
+        // Keep direction within its bounds
+        self.direction %= 360.0;
+        for member in self.members.iter_mut() {
+            member.direction += degrees;
+            member.direction %= 360.0;
+        }
+    }
+    /// The command the swarm will run on its next update, if it has a program
+    pub fn next_command(&self) -> Option<SwarmCommand> {
+        self.program
+            .commands
+            .get(self.program.program_counter)
+            .cloned()
+    }
+    /// Performs 1 tick without running a command or moving the program counter,
+    /// for swarms whose program is paused
+    pub fn idle(&mut self) {
+        self.cool_down();
+    }
+    /// Performs 1 tick as if the next command were NOOP
+    pub fn skip_command(&mut self) {
+        if self.program.commands.len() != 0 {
+            if self.program.interrupted.is_none() {
+                self.handlers_in_a_row = 0;
+            }
+            self.control_steps = 0;
+            self.program.advance();
+        }
+        self.cool_down();
+    }
+    /// Counts the cooldowns down by a tick
+    fn cool_down(&mut self) {
+        for member in self.members.iter_mut() {
+            member.cooldown = member.cooldown.saturating_sub(1);
         }
+        self.fire_cooldown = self.fire_cooldown.saturating_sub(1);
+        if self.recoil_timer == 0 {
+            self.recoil = 0;
+        } else {
+            self.recoil_timer -= 1;
+        }
+        self.formation_cooldown -= 1;
         if self.formation_cooldown < 0 {
             self.formation_cooldown = 0;
         }
     }
+    /// Gets the cosine and sine of the direction, recomputing them if it changed
+    fn heading(&mut self) -> (f32, f32) {
+        if self.heading.0 != self.direction {
+            let (sin, cos) = Swarm::sin_cos(self.direction);
+            self.heading = (self.direction, cos, sin);
+        }
+        (self.heading.1, self.heading.2)
+    }
+    /// Gets the sine and cosine of a direction in degrees
+    /// Looked up from a table in fixed-point mode, so moves are the same everywhere
+    #[cfg(feature = "fixed_point")]
+    fn sin_cos(degrees: f32) -> (f32, f32) {
+        fixed::sin_cos(degrees)
+    }
+    /// Gets the sine and cosine of a direction in degrees
+    #[cfg(not(feature = "fixed_point"))]
+    fn sin_cos(degrees: f32) -> (f32, f32) {
+        degrees.to_radians().sin_cos()
+    }
 
-    pub fn fire(&self, swarm_id: usize, bullets: &mut Vec<Bullet>) {
+    /// Each member that isn't cooling down fires from where it is, the way it faces,
+    /// so the formation decides where bullets come from
+    /// return: the number of bullets fired
+    pub fn fire(&mut self, swarm_id: usize, bullets: &mut Bullets) -> usize {
+        self.fire_with(swarm_id, bullets, || 0.0)
+    }
+    /// Fires like fire, turning each bullet by some degrees
+    /// deviation: the degrees to turn the next bullet by
+    fn fire_with<F: FnMut() -> f32>(
+        &mut self,
+        swarm_id: usize,
+        bullets: &mut Bullets,
+        mut deviation: F,
+    ) -> usize {
+        let mut fired = 0;
         // spawn bullet with velocity vector
-        for member in &self.members {
+        for member in self.members.iter_mut().filter(|member| member.cooldown == 0) {
             let new_bullet: Bullet = Bullet::new(
                 swarm_id,
                 self.x + member.x,
                 self.y + member.y,
-                self.direction,
+                member.direction + deviation(),
                 //self.bullet_duration,
             );
             bullets.push(new_bullet);
+            member.cooldown = SwarmMember::FIRE_COOLDOWN;
+            fired += 1;
         }
+        fired
+    }
+    /// Whether the swarm would fire anything if it fired now
+    pub fn can_fire(&self) -> bool {
+        self.fire_cooldown == 0 && self.ready_members() > 0
+    }
+    /// Whether the members have less than half the health of a new swarm's
+    pub fn health_low(&self) -> bool {
+        let health: i32 = self.members.iter().map(|member| member.health.max(0)).sum();
+        2 * health < INITIAL_SWARM_SIZE as i32 * SwarmMember::FULL_HEALTH
+    }
+    /// The point some distance in front of the swarm
+    pub fn ahead(&self, distance: f32) -> (f32, f32) {
+        let (sin, cos) = Swarm::sin_cos(self.direction);
+        (self.x + distance * cos, self.y - distance * sin)
+    }
+    /// Whether a condition a program branches on holds
+    /// Conditions on the rest of the world use what the swarm last sensed
+    pub fn holds(&self, condition: Condition) -> bool {
+        match condition {
+            Condition::CAN_FIRE => self.can_fire(),
+            Condition::ENEMY_NEAR => self.senses.enemy_near,
+            Condition::WALL_AHEAD => self.senses.wall_ahead,
+            Condition::HEALTH_LOW => self.health_low(),
+        }
+    }
+    /// Number of members that would fire if the swarm fired now
+    pub fn ready_members(&self) -> usize {
+        self.members.iter().filter(|member| member.cooldown == 0).count()
     }
 
     // Calculates the offset for a number of position parameters
@@ -275,7 +826,9 @@ impl Swarm {
         offset_list
     }
 	
-	pub fn sierpinski_offset(focal_count: u32) -> Vec<(f32,f32)>
+	/// Offsets of a sierpinski-like shape around a number of focal points
+	/// The focal point each step moves toward is drawn from rng
+	pub fn sierpinski_offset<R: Rng>(focal_count: u32, rng: &mut R) -> Vec<(f32,f32)>
 	{
 		if(focal_count < 3)
 		{
@@ -298,13 +851,14 @@ impl Swarm {
 			foci.push(((radians + f32::consts::PI/2.0).cos(),(radians + f32::consts::PI/2.0).sin()));
 		}
 		
+		// Draw the focal point for every step at once
+		let choices: Vec<u32> = (0..34).map(|_| rng.gen_range(0, focal_count)).collect();
+		
 		let mut point_set: Vec<(f32,f32)> = Vec::new();
 		point_set.push((0.0,0.0));
 		for n in 0..34
 		{
-			let mut rng = thread_rng();
-			let randint: u32 = rng.gen::<u32>() % focal_count;
-			//let randint: u32 = random::<u32>() % focal_count;0
+			let randint: u32 = choices[n];
 			let new_point = (((focal_count - 2) as f32)*((point_set[(n as usize)].0 + foci[randint as usize].0)/((focal_count as f32)-1.0))
 							, ((focal_count - 2) as f32)*((point_set[(n as usize)].1 + foci[randint as usize].1)/((focal_count as f32)-1.0)));
 			
@@ -329,8 +883,9 @@ fn test_offset_calc() {
     //let rad1: f32 = 1.0;
     //let rad2: f32 = 2.5;
 
-    let ooflist1: Vec<(f32, f32)> = Swarm::sierpinski_offset(3 as u32);
-    let ooflist2: Vec<(f32, f32)> = Swarm::sierpinski_offset(5 as u32);
+    let mut rng = ::rand::XorShiftRng::new_unseeded();
+    let ooflist1: Vec<(f32, f32)> = Swarm::sierpinski_offset(3 as u32, &mut rng);
+    let ooflist2: Vec<(f32, f32)> = Swarm::sierpinski_offset(5 as u32, &mut rng);
 
     println!("Offsets of radius 1.0:");
     for tuple in ooflist1 {
@@ -365,7 +920,8 @@ fn test_offset_calc() {
 }
 
 /// Represents a member of a swarm
-#[derive(Clone, Copy, Debug, Serialize)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct SwarmMember {
     /// X position
     pub x: f32,
@@ -375,21 +931,31 @@ pub struct SwarmMember {
     pub direction: f32,
     /// Health
     pub health: i32,
+    /// Ticks until the member can fire again
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    pub cooldown: u32,
 }
 /// Functions for SwarmMember
 impl SwarmMember {
+    /// Ticks a member waits between shots, half a second at 60 updates per second
+    pub const FIRE_COOLDOWN: u32 = 10;
+    /// Health of a new member
+    pub const FULL_HEALTH: i32 = 5;
     pub fn new(x: f32, y: f32) -> Self {
         SwarmMember {
             x: x,
             y: y,
             direction: 0.0,
-            health: 5,
+            health: SwarmMember::FULL_HEALTH,
+            cooldown: 0,
         }
     }
 }
 
 /// Represents a bullet
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct Bullet {
     /// ID of the player that fired this bullet
     /// This is used so the player can't shoot themself
@@ -401,16 +967,22 @@ pub struct Bullet {
     /// Direction in degrees
     pub direction: f32,
     /// Duration of bullet in ticks; counts down to 0
-    #[serde(skip_serializing)]
+    #[serde(skip, default = "Bullet::lifetime")]
+    #[cfg_attr(feature = "schema", schemars(skip))]
     pub duration: i64,
 }
 
 /// Functions for a bullet
 impl Bullet {
-    /// Bullet speed
-    const UPDATE_DISTANCE: f32 = 5.0;
+    /// Distance a bullet moves each tick
+    /// Bullets never change speed or direction, so clients extrapolate them with this
+    pub const UPDATE_DISTANCE: f32 = 5.0;
     /// Default lifetime of bullet
     const LIFETIME: i64 = 90;
+    /// Lifetime of a new bullet
+    fn lifetime() -> i64 {
+        Bullet::LIFETIME
+    }
     /// Constructor
     // TODO: add arguments
     pub fn new(owner: usize, x: f32, y: f32, direction: f32) -> Self {
@@ -436,9 +1008,254 @@ impl Bullet {
     }
 }
 
+/// Every bullet in the world, stored as parallel arrays so the movement and
+/// collision passes walk contiguous memory
+/// Element i of each array belongs to the same bullet; the arrays are only
+/// modified together so they always have the same length
+#[derive(Clone, Debug, Default)]
+pub struct Bullets {
+    /// X positions
+    pub x: Vec<Coord>,
+    /// Y positions
+    pub y: Vec<Coord>,
+    /// Distance moved along the x axis each tick
+    pub dx: Vec<Coord>,
+    /// Distance moved along the y axis each tick
+    pub dy: Vec<Coord>,
+    /// ID of the player that fired each bullet
+    pub owner: Vec<usize>,
+    /// Remaining lifetime of each bullet in ticks; counts down to 0
+    pub ttl: Vec<i64>,
+}
+
+/// Functions for the bullet arrays
+impl Bullets {
+    /// Constructor
+    pub fn new() -> Self {
+        Bullets::default()
+    }
+    /// Capacity constructor
+    /// capacity: the number of bullets to allocate space for
+    pub fn with_capacity(capacity: usize) -> Self {
+        Bullets {
+            x: Vec::with_capacity(capacity),
+            y: Vec::with_capacity(capacity),
+            dx: Vec::with_capacity(capacity),
+            dy: Vec::with_capacity(capacity),
+            owner: Vec::with_capacity(capacity),
+            ttl: Vec::with_capacity(capacity),
+        }
+    }
+    /// Number of bullets
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+    /// Whether there are no bullets
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+    /// Adds a bullet
+    /// Bullets whose position or direction isn't finite are dropped
+    pub fn push(&mut self, bullet: Bullet) {
+        if !(bullet.x.is_finite() && bullet.y.is_finite() && bullet.direction.is_finite()) {
+            warn!("Dropped a bullet that wasn't at a number");
+            return;
+        }
+        let (dx, dy) = Bullets::velocity(bullet.direction);
+        self.x.push(to_coord(bullet.x));
+        self.y.push(to_coord(bullet.y));
+        self.dx.push(dx);
+        self.dy.push(dy);
+        self.owner.push(bullet.owner);
+        self.ttl.push(bullet.duration);
+    }
+    /// Gets the distance a bullet moves along each axis each tick
+    #[cfg(feature = "fixed_point")]
+    fn velocity(direction: f32) -> (Coord, Coord) {
+        Fixed::from_polar(Fixed::from_f32(Bullet::UPDATE_DISTANCE), direction)
+    }
+    /// Gets the distance a bullet moves along each axis each tick
+    #[cfg(not(feature = "fixed_point"))]
+    fn velocity(direction: f32) -> (Coord, Coord) {
+        let radians = direction.to_radians();
+        (
+            Bullet::UPDATE_DISTANCE * radians.cos(),
+            -Bullet::UPDATE_DISTANCE * radians.sin(),
+        )
+    }
+    /// Gets the position of the bullet at the index
+    pub fn position(&self, index: usize) -> (f32, f32) {
+        (from_coord(self.x[index]), from_coord(self.y[index]))
+    }
+    /// Gets a view of the bullet at the index
+    pub fn get(&self, index: usize) -> Option<Bullet> {
+        if index >= self.len() {
+            return None;
+        }
+        let (x, y) = self.position(index);
+        let (dx, dy) = (from_coord(self.dx[index]), from_coord(self.dy[index]));
+        Some(Bullet {
+            owner: self.owner[index],
+            x: x,
+            y: y,
+            direction: (-dy).atan2(dx).to_degrees(),
+            duration: self.ttl[index],
+        })
+    }
+    /// Iterates over views of every bullet
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = Bullet> + 'a {
+        (0..self.len()).filter_map(move |index| self.get(index))
+    }
+    /// Removes the bullet at the index, replacing it with the last bullet
+    pub fn swap_remove(&mut self, index: usize) {
+        self.x.swap_remove(index);
+        self.y.swap_remove(index);
+        self.dx.swap_remove(index);
+        self.dy.swap_remove(index);
+        self.owner.swap_remove(index);
+        self.ttl.swap_remove(index);
+    }
+    /// Removes every bullet fired by the player
+    pub fn remove_owner(&mut self, owner: usize) {
+        let mut index: usize = 0;
+        while index < self.len() {
+            if self.owner[index] == owner {
+                self.swap_remove(index);
+            } else {
+                index += 1;
+            }
+        }
+    }
+    /// Removes every bullet whose position or velocity isn't finite
+    /// return: the number of bullets removed
+    pub fn remove_non_finite(&mut self) -> usize {
+        let mut removed: usize = 0;
+        let mut index: usize = 0;
+        while index < self.len() {
+            let (x, y) = self.position(index);
+            if x.is_finite()
+                && y.is_finite()
+                && from_coord(self.dx[index]).is_finite()
+                && from_coord(self.dy[index]).is_finite()
+            {
+                index += 1;
+            } else {
+                self.swap_remove(index);
+                removed += 1;
+            }
+        }
+        removed
+    }
+    /// Removes bullets at positions the predicate blocks
+    /// return: the number of bullets removed
+    pub fn remove_blocked<F: Fn(f32, f32) -> bool>(&mut self, blocked: F) -> usize {
+        self.remove_owned_blocked(|_, x, y| blocked(x, y))
+    }
+    /// Removes bullets the predicate blocks, given their owner and position
+    /// return: the number of bullets removed
+    pub fn remove_owned_blocked<F: Fn(usize, f32, f32) -> bool>(&mut self, blocked: F) -> usize {
+        let mut removed: usize = 0;
+        let mut index: usize = 0;
+        while index < self.len() {
+            let (x, y) = self.position(index);
+            if blocked(self.owner[index], x, y) {
+                self.swap_remove(index);
+                removed += 1;
+            } else {
+                index += 1;
+            }
+        }
+        removed
+    }
+    /// Approximate bytes allocated by the arrays
+    pub fn memory_usage(&self) -> usize {
+        (self.x.capacity() + self.y.capacity() + self.dx.capacity() + self.dy.capacity())
+            * mem::size_of::<Coord>()
+            + self.owner.capacity() * mem::size_of::<usize>()
+            + self.ttl.capacity() * mem::size_of::<i64>()
+    }
+    /// Removes every bullet
+    pub fn clear(&mut self) {
+        self.x.clear();
+        self.y.clear();
+        self.dx.clear();
+        self.dy.clear();
+        self.owner.clear();
+        self.ttl.clear();
+    }
+    /// Performs 1 tick
+    /// Moves every bullet, then removes the ones that expired
+    pub fn update(&mut self) {
+        self.integrate();
+        for ttl in self.ttl.iter_mut() {
+            *ttl -= 1;
+        }
+        let mut index: usize = 0;
+        while index < self.len() {
+            if self.ttl[index] <= 0 {
+                self.swap_remove(index);
+            } else {
+                index += 1;
+            }
+        }
+    }
+}
+
+/// Functions for moving the bullet arrays
+impl Bullets {
+    /// Adds each bullet's velocity to its position
+    #[cfg(feature = "fixed_point")]
+    fn integrate(&mut self) {
+        integrate_fixed(&mut self.x, &self.dx);
+        integrate_fixed(&mut self.y, &self.dy);
+    }
+    /// Adds each bullet's velocity to its position
+    #[cfg(not(feature = "fixed_point"))]
+    fn integrate(&mut self) {
+        integrate(&mut self.x, &self.dx);
+        integrate(&mut self.y, &self.dy);
+    }
+}
+
+/// Serializes the same way as a list of bullets
+impl Serialize for Bullets {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for bullet in self.iter() {
+            seq.serialize_element(&bullet)?;
+        }
+        seq.end()
+    }
+}
+
+/// Deserializes from a list of bullets
+impl<'de> Deserialize<'de> for Bullets {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let list = Vec::<Bullet>::deserialize(deserializer)?;
+        let mut bullets = Bullets::with_capacity(list.len());
+        for bullet in list {
+            bullets.push(bullet);
+        }
+        Ok(bullets)
+    }
+}
+
+/// Described the same way as a list of bullets
+#[cfg(feature = "schema")]
+impl JsonSchema for Bullets {
+    fn schema_name() -> String {
+        Vec::<Bullet>::schema_name()
+    }
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        Vec::<Bullet>::json_schema(gen)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::XorShiftRng;
+    use swarm_language::MAX_CALL_DEPTH;
     #[test]
     /// This test will start at the origin with 0 degrees, move, turn 45 degrees
     /// then move.  This will happen four times, and should return to the original
@@ -468,7 +1285,9 @@ mod tests {
                 0,
                 test_world.width,
                 test_world.height,
+                test_world.boundary,
                 &mut test_world.bullets,
+                &mut test_world.rng,
             );
             println!("x: {}, y: {}, dir: {}", swarm.x, swarm.y, swarm.direction);
         }
@@ -491,7 +1310,9 @@ mod tests {
                 swarm_id,
                 test_world.width,
                 test_world.height,
+                test_world.boundary,
                 &mut test_world.bullets,
+                &mut test_world.rng,
             );
             println!("x: {}, y: {}, dir: {}", swarm.x, swarm.y, swarm.direction);
         }
@@ -521,9 +1342,356 @@ mod tests {
             swarm_id,
             world.width,
             world.height,
+            world.boundary,
             &mut world.bullets,
+            &mut world.rng,
         );
 
         assert_eq!(world.bullets.len(), 6);
     }
+    #[test]
+    fn cached_heading() {
+        let mut swarm = Swarm::new(500.0, 500.0, 1);
+        swarm.program.commands = vec![SwarmCommand::MOVE];
+        let mut bullets = Bullets::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        assert!((swarm.x - 505.0).abs() <= 0.001);
+        // Changing the direction directly still invalidates the cached heading
+        swarm.direction = 90.0;
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        assert!((swarm.x - 505.0).abs() <= 0.001);
+        assert!((swarm.y - 495.0).abs() <= 0.001);
+    }
+    #[test]
+    fn branch_on_weapon_cooldown() {
+        let mut swarm = Swarm::new(500.0, 500.0, 1);
+        swarm.program = "FIRE\nIF CAN_FIRE\nTURN 10\nENDIF\nFIRE\n".parse().unwrap();
+        swarm.fire_rate = 20;
+        let mut bullets = Bullets::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        for _ in 0..4 {
+            swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        }
+        // The weapon was still cooling down, so the turn was skipped and FIRE did nothing
+        assert_eq!(bullets.len(), 1);
+        assert_eq!(swarm.direction, 0.0);
+        assert!(!swarm.holds(Condition::CAN_FIRE));
+        // The swarm's weapon takes longer to be ready than its member
+        assert_eq!(swarm.members[0].cooldown, 6);
+        assert_eq!(swarm.fire_cooldown, 16);
+    }
+    #[test]
+    fn move_over_several_ticks() {
+        let mut swarm = Swarm::new(500.0, 500.0, 1);
+        swarm.program = "MOVE 12\nTURN 30".parse().unwrap();
+        let mut bullets = Bullets::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        // 5 units a tick, so the last tick only goes the 2 left
+        for _ in 0..3 {
+            swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        }
+        assert!((swarm.x - 512.0).abs() <= 0.001);
+        assert_eq!(swarm.program.program_counter, 1);
+        assert_eq!(swarm.program.moving, None);
+        // Starting over goes the whole distance again
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        assert!((swarm.y - 497.5).abs() <= 0.001);
+        assert_eq!(swarm.program.moving, Some((0, 7.0)));
+    }
+    #[test]
+    fn move_without_speed() {
+        let mut swarm = Swarm::new(500.0, 500.0, 1);
+        swarm.program = "MOVE 150\nTURN 30".parse().unwrap();
+        swarm.handicap = Handicap::new(1.0, 0.0);
+        let mut bullets = Bullets::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        // The swarm stays put, and the program goes on to the next command
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        assert_eq!((swarm.x, swarm.y), (500.0, 500.0));
+        assert_eq!(swarm.program.program_counter, 1);
+        assert_eq!(swarm.program.moving, None);
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        assert!(swarm.direction != 0.0);
+        assert_eq!(swarm.program.program_counter, 0);
+    }
+    #[test]
+    fn wait_over_several_ticks() {
+        let mut swarm = Swarm::new(500.0, 500.0, 1);
+        swarm.program = "WAIT 3\nMOVE".parse().unwrap();
+        let mut bullets = Bullets::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        assert_eq!(swarm.program.program_counter, 0);
+        assert_eq!(swarm.program.waiting, Some((0, 1)));
+        // The third tick ends the wait, and the swarm only moves on the fourth
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        assert_eq!(swarm.program.program_counter, 1);
+        assert_eq!(swarm.program.waiting, None);
+        assert_eq!(swarm.x, 500.0);
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        assert!(swarm.x > 500.0);
+        // Starting over waits the whole time again
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        assert_eq!(swarm.program.waiting, Some((0, 2)));
+    }
+    #[test]
+    fn spread_grows_while_firing() {
+        let mut swarm = Swarm::new(500.0, 500.0, 1);
+        swarm.program = "FIRE".parse().unwrap();
+        swarm.fire_rate = 1;
+        swarm.spread = Spread::new(2.0, 5.0, 20);
+        let mut bullets = Bullets::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        // The member waits FIRE_COOLDOWN ticks between volleys
+        for _ in 0..SwarmMember::FIRE_COOLDOWN * 4 {
+            swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        }
+        assert_eq!(bullets.len(), 4);
+        assert_eq!(swarm.recoil, 4);
+        // The first volley flies straight, and later ones stray at most the spread's max
+        let degrees = |index: usize| {
+            let (dx, dy) = (from_coord(bullets.dx[index]), from_coord(bullets.dy[index]));
+            (-dy).atan2(dx).to_degrees()
+        };
+        assert_eq!(degrees(0), 0.0);
+        assert!((1..4).any(|index| degrees(index) != 0.0));
+        assert!((1..4).all(|index| degrees(index).abs() <= 5.0));
+        // Stopping for longer than the reset lets bullets fly straight again
+        swarm.program = "NOOP".parse().unwrap();
+        for _ in 0..21 {
+            swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        }
+        assert_eq!(swarm.recoil, 0);
+        assert_eq!(swarm.spread.deviation(swarm.recoil), 0.0);
+    }
+    #[test]
+    fn run_if_else() {
+        let mut swarm = Swarm::new(500.0, 500.0, 1);
+        swarm.program = "IF ENEMY_NEAR\nTURN 10\nELSE\nTURN -10\nENDIF\n\
+                         IF HEALTH_LOW\nTURN 1\nENDIF"
+            .parse()
+            .unwrap();
+        let mut bullets = Bullets::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        // Nothing near: the ELSE block runs, then a lone member is low on health
+        for _ in 0..6 {
+            swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        }
+        assert_eq!(swarm.direction, -9.0);
+        assert_eq!(swarm.program.program_counter, 0);
+        swarm.senses.enemy_near = true;
+        for _ in 0..3 {
+            swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        }
+        // The IF block ran, then the ELSE jumped to its ENDIF
+        assert_eq!(swarm.direction, 1.0);
+        assert_eq!(swarm.program.program_counter, 5);
+    }
+    #[test]
+    fn jump_to_label() {
+        let mut swarm = Swarm::new(500.0, 500.0, 1);
+        swarm.program = "LOOP 5\nTURN 10\nJUMP out\nENDLOOP\nTURN 1\nLABEL out\nTURN -1"
+            .parse()
+            .unwrap();
+        let mut bullets = Bullets::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        for _ in 0..3 {
+            swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        }
+        // The jump skipped the rest of the loop and the turn after it, and left the loop
+        assert_eq!(swarm.direction, 10.0);
+        assert_eq!(swarm.program.program_counter, 6);
+        assert!(swarm.program.loops.is_empty());
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        assert_eq!(swarm.direction, 9.0);
+    }
+    #[test]
+    fn call_subroutines() {
+        let mut swarm = Swarm::new(500.0, 500.0, 1);
+        swarm.program = "CALL turn\nCALL turn\nTURN 1\nSUB turn\nTURN 10\nENDSUB"
+            .parse()
+            .unwrap();
+        let mut bullets = Bullets::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        for _ in 0..7 {
+            swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        }
+        // Each call ran the subroutine and came back
+        assert_eq!(swarm.direction, 21.0);
+        assert!(swarm.program.calls.is_empty());
+        // The subroutine is skipped when the program reaches it on its own
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        assert_eq!(swarm.direction, 21.0);
+        assert_eq!(swarm.program.program_counter, 0);
+
+        // Recursion stops at the call depth limit
+        swarm.program = "SUB spin\nTURN 1\nCALL spin\nENDSUB\nCALL spin"
+            .parse()
+            .unwrap();
+        let mut deepest = 0;
+        for _ in 0..100 {
+            swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+            deepest = deepest.max(swarm.program.calls.len());
+        }
+        assert_eq!(deepest, MAX_CALL_DEPTH);
+    }
+    #[test]
+    fn run_handlers() {
+        let mut swarm = Swarm::new(500.0, 500.0, 1);
+        let source = "LOOP 3\nTURN 1\nENDLOOP\nON_HIT\nTURN 10\nENDON\nON_SPAWN\nFIRE\nENDON";
+        swarm.program = source.parse().unwrap();
+        let mut bullets = Bullets::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        // Events without a handler are ignored
+        swarm.raise(Trigger::ENEMY_SIGHTED);
+        assert_eq!(swarm.pending, None);
+        // Interrupt the loop on its second pass
+        for _ in 0..3 {
+            swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        }
+        swarm.raise(Trigger::HIT);
+        swarm.dispatch();
+        assert_eq!(swarm.pending, None);
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        assert_eq!(swarm.direction, 11.0);
+        // Events wait for the handler being run to end
+        swarm.raise(Trigger::HIT);
+        swarm.dispatch();
+        assert_eq!(swarm.pending, Some(Trigger::HIT));
+        // ENDON goes back to the loop, which finishes its passes
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        assert_eq!(swarm.program.program_counter, 1);
+        for _ in 0..4 {
+            swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        }
+        assert_eq!(swarm.direction, 13.0);
+        // Handlers are skipped when the program reaches them on its own
+        swarm.pending = None;
+        for _ in 0..2 {
+            swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        }
+        assert_eq!(swarm.direction, 13.0);
+        assert_eq!(swarm.program.program_counter, 0);
+        // Respawning raises SPAWN
+        assert_eq!(swarm.respawned(0.0, 0.0).pending, Some(Trigger::SPAWN));
+    }
+    #[test]
+    fn turn_by_register() {
+        let mut swarm = Swarm::new(500.0, 500.0, 1);
+        swarm.program = "ADD R0 5\nTURN R0".parse().unwrap();
+        let mut bullets = Bullets::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        for _ in 0..4 {
+            swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        }
+        // Registers keep their values when the program starts over, so each turn is wider
+        assert_eq!(swarm.program.registers[0], 10);
+        assert_eq!(swarm.direction, 15.0);
+        // Turns are no wider than TURN allows
+        swarm.program.registers[0] = 1000;
+        swarm.program.program_counter = 1;
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        assert_eq!(swarm.direction, 15.0 + MAX_TURN);
+    }
+    #[test]
+    fn run_nested_loops() {
+        let mut swarm = Swarm::new(500.0, 500.0, 1);
+        swarm.program = "LOOP 3\nTURN 10\nLOOP 2\nTURN 1\nENDLOOP\nENDLOOP\nTURN -30"
+            .parse()
+            .unwrap();
+        let mut bullets = Bullets::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        // Every command takes a tick, so each outer pass takes 7
+        for _ in 0..22 {
+            swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        }
+        assert_eq!(swarm.direction, 36.0);
+        assert!(swarm.program.loops.is_empty());
+        swarm.update(0, 1000.0, 1000.0, Boundary::CLAMP, &mut bullets, &mut rng);
+        assert_eq!(swarm.direction, 6.0);
+        assert_eq!(swarm.program.program_counter, 0);
+    }
+    #[test]
+    fn update_bullet_arrays() {
+        let mut bullets = Bullets::new();
+        bullets.push(Bullet::new(0, 0.0, 0.0, 90.0));
+        bullets.push(Bullet::new(1, 0.0, 0.0, 0.0));
+        bullets.ttl[1] = 1;
+        bullets.update();
+        // The second bullet expired
+        assert_eq!(bullets.len(), 1);
+        let bullet = bullets.get(0).unwrap();
+        assert!(bullet.x.abs() <= 0.001);
+        assert!((bullet.y + Bullet::UPDATE_DISTANCE).abs() <= 0.001);
+        assert!((bullet.direction - 90.0).abs() <= 0.001);
+        assert_eq!(bullet.duration, Bullet::LIFETIME - 1);
+        bullets.remove_owner(0);
+        assert!(bullets.is_empty());
+    }
+
+    #[test]
+    fn handicapped_damage_and_speed() {
+        let mut swarm = Swarm::new(500.0, 500.0, 1);
+        swarm.handicap = Handicap::new(0.5, 2.0);
+        let damage: Vec<u32> = (0..4).map(|_| swarm.hit_damage()).collect();
+        assert_eq!(damage, vec![0, 1, 0, 1]);
+        assert_eq!(swarm.speed(), 2.0 * Swarm::UPDATE_DISTANCE);
+        swarm.boost = Handicap::new(2.0, 1.0);
+        assert_eq!(swarm.hit_damage(), 1);
+    }
+
+    #[test]
+    fn members_fire_on_their_own_cooldowns() {
+        let mut swarm = Swarm::new(100.0, 50.0, 3);
+        let mut bullets = Bullets::new();
+        swarm.members[1].cooldown = 2;
+        assert_eq!(swarm.ready_members(), 2);
+        swarm.fire(4, &mut bullets);
+        assert_eq!(bullets.len(), 2);
+        assert_eq!(swarm.members[0].cooldown, SwarmMember::FIRE_COOLDOWN);
+        // The member that was cooling down is ready first, and fires alone
+        swarm.skip_command();
+        swarm.skip_command();
+        assert_eq!(swarm.ready_members(), 1);
+        swarm.fire(4, &mut bullets);
+        assert_eq!(bullets.len(), 3);
+        assert_eq!(bullets.owner, vec![4, 4, 4]);
+    }
+
+    #[test]
+    fn summarize() {
+        let mut swarm = Swarm::new(100.0, 50.0, 2);
+        swarm.members[0] = SwarmMember::new(-10.0, 4.0);
+        swarm.members[1] = SwarmMember::new(10.0, 0.0);
+        swarm.members[0].direction = 350.0;
+        swarm.members[1].direction = 10.0;
+        swarm.summarize();
+        assert_eq!(swarm.count, 2);
+        assert_eq!(swarm.centroid, (100.0, 52.0));
+        assert!(swarm.facing < 0.001 || swarm.facing > 359.999);
+        swarm.members.clear();
+        swarm.summarize();
+        assert_eq!((swarm.count, swarm.centroid), (0, (100.0, 50.0)));
+    }
+
+    #[test]
+    fn record_velocity() {
+        let mut swarm = Swarm::new(13.0, 14.0, 1);
+        swarm.direction = 10.0;
+        swarm.dirty = Dirty::default();
+        swarm.record_velocity(10.0, 10.0, 350.0);
+        assert_eq!((swarm.vx, swarm.vy, swarm.spin), (3.0, 4.0, 20.0));
+        assert!(swarm.dirty.moved);
+        // Stopping changes the velocity, so the swarm is still sent once more
+        swarm.dirty = Dirty::default();
+        swarm.record_velocity(13.0, 14.0, 10.0);
+        assert_eq!((swarm.vx, swarm.vy, swarm.spin), (0.0, 0.0, 0.0));
+        assert!(swarm.dirty.moved);
+        swarm.dirty = Dirty::default();
+        swarm.record_velocity(13.0, 14.0, 10.0);
+        assert!(!swarm.dirty.moved);
+    }
 }
@@ -13,41 +13,669 @@
 //
 // You should have received a copy of the GNU General Public License
 // along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
-use std::error::Error;
+extern crate serde_json;
+
+use messages::Message;
+use std::error::Error as StdError;
 use std::fmt;
+use std::io;
 
-/// A very generic error. Used so we can have an easy type of error to use for
-/// traits such as FromStr
+/// Any error returned by the library, grouped by what failed
+/// Match on the category, then on the error inside it for details
 #[derive(Debug)]
-pub struct GenericError {
-    pub description: String,
+#[non_exhaustive]
+pub enum Error {
+    /// A swarm program or admin command couldn't be parsed
+    LANGUAGE(LanguageError),
+    /// An operation on a world or room failed
+    WORLD(WorldError),
+    /// A token or message from outside the server couldn't be used
+    PROTOCOL(ProtocolError),
+    /// Stored data, such as certificates, couldn't be loaded
+    STORAGE(StorageError),
+}
+/// Allows Error to be printed
+impl fmt::Display for Error {
+    /// Writes the error using a formatter
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::LANGUAGE(ref error) => error.fmt(formatter),
+            Error::WORLD(ref error) => error.fmt(formatter),
+            Error::PROTOCOL(ref error) => error.fmt(formatter),
+            Error::STORAGE(ref error) => error.fmt(formatter),
+        }
+    }
+}
+/// Allows Error to be used where an error is wanted
+impl StdError for Error {
+    /// Description of the error
+    fn description(&self) -> &str {
+        match *self {
+            Error::LANGUAGE(ref error) => error.description(),
+            Error::WORLD(ref error) => error.description(),
+            Error::PROTOCOL(ref error) => error.description(),
+            Error::STORAGE(ref error) => error.description(),
+        }
+    }
+    /// The error of the category
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            Error::LANGUAGE(ref error) => Some(error),
+            Error::WORLD(ref error) => Some(error),
+            Error::PROTOCOL(ref error) => Some(error),
+            Error::STORAGE(ref error) => Some(error),
+        }
+    }
+}
+/// Allows ? to turn a LanguageError into an Error
+impl From<LanguageError> for Error {
+    fn from(error: LanguageError) -> Self {
+        Error::LANGUAGE(error)
+    }
+}
+/// Allows ? to turn a ParseError into an Error
+/// Error groups by what failed, so only the kind of problem is kept
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error::LANGUAGE(error.kind)
+    }
+}
+/// Allows ? to turn a WorldError into an Error
+impl From<WorldError> for Error {
+    fn from(error: WorldError) -> Self {
+        Error::WORLD(error)
+    }
+}
+/// Allows ? to turn a WorldConfigError into an Error
+impl From<WorldConfigError> for Error {
+    fn from(error: WorldConfigError) -> Self {
+        Error::WORLD(WorldError::INVALID_CONFIG(error))
+    }
+}
+/// Allows ? to turn a ProtocolError into an Error
+impl From<ProtocolError> for Error {
+    fn from(error: ProtocolError) -> Self {
+        Error::PROTOCOL(error)
+    }
+}
+/// Allows ? to turn a StorageError into an Error
+impl From<StorageError> for Error {
+    fn from(error: StorageError) -> Self {
+        Error::STORAGE(error)
+    }
 }
 
-/// Functions for GenericError
-impl GenericError {
+/// Reasons a swarm program or admin command can't be parsed
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum LanguageError {
+    /// A line had no command
+    EMPTY_COMMAND,
+    /// The command isn't in the language
+    UNKNOWN_COMMAND(String),
+    /// The formation isn't in the language
+    UNKNOWN_FORMATION(String),
+    /// The condition isn't in the language
+    UNKNOWN_CONDITION(String),
+    /// The command needs a parameter it wasn't given
+    MISSING_PARAMETER(String),
+    /// The command was given more parameters than it takes
+    TOO_MANY_PARAMETERS { command: String, max: usize },
+    /// The command's parameter couldn't be parsed
+    INVALID_PARAMETER { command: String, parameter: String },
+    /// The command's parameter is outside the range it allows
+    OUT_OF_RANGE { command: String, min: f32, max: f32 },
+    /// The program has more commands than allowed
+    TOO_LONG { commands: usize, max: usize },
+    /// A command opening or closing a block has no partner
+    UNMATCHED_BLOCK(String),
+    /// A JUMP names a label no LABEL has
+    UNDEFINED_LABEL(String),
+    /// Two LABELs have the same name
+    DUPLICATE_LABEL(String),
+    /// A CALL names a subroutine no SUB has
+    UNDEFINED_SUB(String),
+    /// Two SUBs have the same name
+    DUPLICATE_SUB(String),
+    /// Two ON blocks handle the same trigger
+    DUPLICATE_HANDLER(String),
+    /// A command is somewhere it can't be used, such as a SUB inside a block
+    MISPLACED(String),
+    /// A behavior script couldn't be compiled
+    SCRIPT(String),
+}
+/// Functions for LanguageError
+impl LanguageError {
+    /// The error as a message ID and parameters, so clients can show it in their own language
+    pub fn message(&self) -> Message {
+        match *self {
+            LanguageError::EMPTY_COMMAND => Message::new("empty_command"),
+            LanguageError::UNKNOWN_COMMAND(ref command) => {
+                Message::new("unknown_command").with_param("command", command)
+            }
+            LanguageError::UNKNOWN_FORMATION(ref formation) => {
+                Message::new("unknown_formation").with_param("formation", formation)
+            }
+            LanguageError::UNKNOWN_CONDITION(ref condition) => {
+                Message::new("unknown_condition").with_param("condition", condition)
+            }
+            LanguageError::MISSING_PARAMETER(ref command) => {
+                Message::new("missing_parameter").with_param("command", command)
+            }
+            LanguageError::TOO_MANY_PARAMETERS { ref command, max } => {
+                Message::new("too_many_parameters")
+                    .with_param("command", command)
+                    .with_param("max", max)
+            }
+            LanguageError::INVALID_PARAMETER {
+                ref command,
+                ref parameter,
+            } => Message::new("invalid_parameter")
+                .with_param("command", command)
+                .with_param("parameter", parameter),
+            LanguageError::OUT_OF_RANGE {
+                ref command,
+                min,
+                max,
+            } => Message::new("out_of_range")
+                .with_param("command", command)
+                .with_param("min", format!("{:.1}", min))
+                .with_param("max", format!("{:.1}", max)),
+            LanguageError::TOO_LONG { commands, max } => Message::new("too_long")
+                .with_param("commands", commands)
+                .with_param("max", max),
+            LanguageError::UNMATCHED_BLOCK(ref command) => {
+                Message::new("unmatched_block").with_param("command", command)
+            }
+            LanguageError::UNDEFINED_LABEL(ref label) => {
+                Message::new("undefined_label").with_param("label", label)
+            }
+            LanguageError::DUPLICATE_LABEL(ref label) => {
+                Message::new("duplicate_label").with_param("label", label)
+            }
+            LanguageError::UNDEFINED_SUB(ref name) => {
+                Message::new("undefined_sub").with_param("name", name)
+            }
+            LanguageError::DUPLICATE_SUB(ref name) => {
+                Message::new("duplicate_sub").with_param("name", name)
+            }
+            LanguageError::DUPLICATE_HANDLER(ref command) => {
+                Message::new("duplicate_handler").with_param("command", command)
+            }
+            LanguageError::MISPLACED(ref command) => {
+                Message::new("misplaced").with_param("command", command)
+            }
+            LanguageError::SCRIPT(ref error) => {
+                Message::new("invalid_script").with_param("error", error)
+            }
+        }
+    }
+}
+/// Allows LanguageError to be printed
+impl fmt::Display for LanguageError {
+    /// Writes the error in English
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(&self.message().english())
+    }
+}
+/// Allows LanguageError to be used where an error is wanted
+impl StdError for LanguageError {
+    /// Description of the error
+    fn description(&self) -> &str {
+        match *self {
+            LanguageError::EMPTY_COMMAND => "empty command",
+            LanguageError::UNKNOWN_COMMAND(_) => "unknown command",
+            LanguageError::UNKNOWN_FORMATION(_) => "unknown formation",
+            LanguageError::UNKNOWN_CONDITION(_) => "unknown condition",
+            LanguageError::MISSING_PARAMETER(_) => "missing parameter",
+            LanguageError::TOO_MANY_PARAMETERS { .. } => "too many parameters",
+            LanguageError::INVALID_PARAMETER { .. } => "invalid parameter",
+            LanguageError::OUT_OF_RANGE { .. } => "parameter out of range",
+            LanguageError::TOO_LONG { .. } => "program too long",
+            LanguageError::UNMATCHED_BLOCK(_) => "unmatched block",
+            LanguageError::UNDEFINED_LABEL(_) => "undefined label",
+            LanguageError::DUPLICATE_LABEL(_) => "duplicate label",
+            LanguageError::UNDEFINED_SUB(_) => "undefined subroutine",
+            LanguageError::DUPLICATE_SUB(_) => "duplicate subroutine",
+            LanguageError::DUPLICATE_HANDLER(_) => "duplicate handler",
+            LanguageError::MISPLACED(_) => "misplaced command",
+            LanguageError::SCRIPT(_) => "invalid script",
+        }
+    }
+}
+
+/// Where a swarm program couldn't be parsed, and why,
+/// so clients can point at the part of the program to fix
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    /// Line number, starting from 1
+    pub line: usize,
+    /// Column the problem starts at, in characters starting from 1
+    pub column: usize,
+    /// The word with the problem, or the command if a parameter is missing
+    pub token: String,
+    /// The problem
+    pub kind: LanguageError,
+}
+/// Functions for ParseError
+impl ParseError {
     /// Constructor
-    pub fn new(description: String) -> Self {
-        GenericError {
-            description: description,
+    pub fn new(line: usize, column: usize, token: String, kind: LanguageError) -> Self {
+        ParseError {
+            line: line,
+            column: column,
+            token: token,
+            kind: kind,
+        }
+    }
+}
+/// Allows ParseError to be printed
+impl fmt::Display for ParseError {
+    /// Writes the error in English, after where it is
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "line {}, column {}: {}", self.line, self.column, self.kind)
+    }
+}
+/// Allows ParseError to be used where an error is wanted
+impl StdError for ParseError {
+    /// Description of the error
+    fn description(&self) -> &str {
+        self.kind.description()
+    }
+    /// The problem, without where it is
+    fn cause(&self) -> Option<&StdError> {
+        Some(&self.kind)
+    }
+}
+
+/// Reasons an operation on a world or room failed
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum WorldError {
+    /// No player has the ID
+    NO_PLAYER(usize),
+    /// A player already has the ID
+    DUPLICATE_PLAYER(usize),
+    /// No room has the ID
+    NO_ROOM(usize),
+    /// The room can't be closed
+    PERMANENT_ROOM(usize),
+    /// The server wasn't given a handoff secret
+    HANDOFFS_DISABLED,
+    /// A lock was poisoned by a thread that panicked while holding it
+    /// Holds what the lock protects
+    LOCK_POISONED(&'static str),
+    /// The world couldn't be created with the given dimensions
+    INVALID_CONFIG(WorldConfigError),
+    /// The time scale is outside the range a world can run at
+    INVALID_TIME_SCALE(f32),
+    /// Ghosts can only be raced in races and practice worlds
+    GHOSTS_DISABLED,
+    /// The recording has more frames than can be played back
+    GHOST_TOO_LONG(usize),
+    /// The position is outside the world
+    OUT_OF_BOUNDS { x: f32, y: f32 },
+    /// The player isn't a teammate following the player uploading programs for their squad
+    NOT_SQUADMATE(usize),
+    /// The player was given more than one of a squad's programs
+    DUPLICATE_SQUADMATE(usize),
+}
+/// Allows WorldError to be printed
+impl fmt::Display for WorldError {
+    /// Writes the error using a formatter
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WorldError::NO_PLAYER(id) => write!(formatter, "No player with ID {}", id),
+            WorldError::DUPLICATE_PLAYER(id) => {
+                write!(formatter, "A player with ID {} already exists", id)
+            }
+            WorldError::NO_ROOM(id) => write!(formatter, "No room with ID {}", id),
+            WorldError::PERMANENT_ROOM(id) => write!(formatter, "Room {} can't be closed", id),
+            WorldError::HANDOFFS_DISABLED => write!(formatter, "Handoffs are not enabled"),
+            WorldError::LOCK_POISONED(what) => write!(formatter, "Failed to get lock on {}", what),
+            WorldError::INVALID_CONFIG(ref error) => error.fmt(formatter),
+            WorldError::INVALID_TIME_SCALE(time_scale) => {
+                write!(formatter, "Time scale {} is not between 0.1 and 4", time_scale)
+            }
+            WorldError::GHOSTS_DISABLED => {
+                write!(formatter, "Ghosts can only be raced in races and practice worlds")
+            }
+            WorldError::GHOST_TOO_LONG(frames) => {
+                write!(formatter, "A recording of {} ticks is too long to play back", frames)
+            }
+            WorldError::OUT_OF_BOUNDS { x, y } => {
+                write!(formatter, "({}, {}) is outside the world", x, y)
+            }
+            WorldError::NOT_SQUADMATE(id) => write!(formatter, "Player {} isn't in your squad", id),
+            WorldError::DUPLICATE_SQUADMATE(id) => {
+                write!(formatter, "Player {} was given more than one program", id)
+            }
+        }
+    }
+}
+/// Allows WorldError to be used where an error is wanted
+impl StdError for WorldError {
+    /// Description of the error
+    fn description(&self) -> &str {
+        match *self {
+            WorldError::NO_PLAYER(_) => "no such player",
+            WorldError::DUPLICATE_PLAYER(_) => "player already exists",
+            WorldError::NO_ROOM(_) => "no such room",
+            WorldError::PERMANENT_ROOM(_) => "room can't be closed",
+            WorldError::HANDOFFS_DISABLED => "handoffs are not enabled",
+            WorldError::LOCK_POISONED(_) => "lock poisoned",
+            WorldError::INVALID_CONFIG(ref error) => error.description(),
+            WorldError::INVALID_TIME_SCALE(_) => "invalid time scale",
+            WorldError::GHOSTS_DISABLED => "ghosts are disabled",
+            WorldError::GHOST_TOO_LONG(_) => "recording too long",
+            WorldError::OUT_OF_BOUNDS { .. } => "position outside the world",
+            WorldError::NOT_SQUADMATE(_) => "player not in the squad",
+            WorldError::DUPLICATE_SQUADMATE(_) => "player given more than one program",
+        }
+    }
+    /// Underlying cause of the error
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            WorldError::INVALID_CONFIG(ref error) => Some(error),
+            _ => None,
         }
     }
 }
-/// Allows GenericError to be printed
-impl fmt::Display for GenericError {
+
+/// Reasons a token or message from outside the server can't be used
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ProtocolError {
+    /// The handoff token isn't made of a payload and a signature
+    MALFORMED_TOKEN,
+    /// The handoff token wasn't signed with the shared secret
+    INVALID_SIGNATURE,
+    /// The handoff token is too old, or from the future
+    EXPIRED_TOKEN,
+    /// The handoff secret can't be used as a key
+    INVALID_SECRET,
+    /// JSON couldn't be read or written
+    JSON(serde_json::Error),
+    /// A message doesn't match what was expected
+    /// path: where in the message they differ, as a dotted list of keys and indices
+    MISMATCH {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+}
+/// Allows ProtocolError to be printed
+impl fmt::Display for ProtocolError {
     /// Writes the error using a formatter
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        write!(formatter, "{}", self.description)
+        match *self {
+            ProtocolError::MALFORMED_TOKEN => write!(formatter, "Malformed handoff token"),
+            ProtocolError::INVALID_SIGNATURE => {
+                write!(formatter, "Invalid handoff token signature")
+            }
+            ProtocolError::EXPIRED_TOKEN => write!(formatter, "Handoff token has expired"),
+            ProtocolError::INVALID_SECRET => write!(formatter, "Invalid handoff secret"),
+            ProtocolError::JSON(ref error) => error.fmt(formatter),
+            ProtocolError::MISMATCH {
+                ref path,
+                ref expected,
+                ref actual,
+            } => write!(
+                formatter,
+                "Mismatch at {}: expected {}, got {}",
+                path, expected, actual
+            ),
+        }
+    }
+}
+/// Allows ProtocolError to be used where an error is wanted
+impl StdError for ProtocolError {
+    /// Description of the error
+    fn description(&self) -> &str {
+        match *self {
+            ProtocolError::MALFORMED_TOKEN => "malformed handoff token",
+            ProtocolError::INVALID_SIGNATURE => "invalid handoff token signature",
+            ProtocolError::EXPIRED_TOKEN => "expired handoff token",
+            ProtocolError::INVALID_SECRET => "invalid handoff secret",
+            ProtocolError::JSON(_) => "invalid JSON",
+            ProtocolError::MISMATCH { .. } => "message mismatch",
+        }
+    }
+    /// Underlying cause of the error
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            ProtocolError::JSON(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+/// Allows ? to turn a JSON error into a ProtocolError
+impl From<serde_json::Error> for ProtocolError {
+    fn from(error: serde_json::Error) -> Self {
+        ProtocolError::JSON(error)
+    }
+}
+/// Allows ? to turn a JSON error into an Error
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::PROTOCOL(ProtocolError::JSON(error))
     }
 }
 
-/// Allows GenericError to be used where an error is wanted
-impl Error for GenericError {
+/// Reasons stored data can't be loaded
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum StorageError {
+    /// A file couldn't be read
+    IO { path: String, error: io::Error },
+    /// A file has no certificates
+    NO_CERTIFICATES(String),
+    /// A file's certificates couldn't be parsed
+    INVALID_CERTIFICATES(String),
+    /// A file has no private key
+    NO_PRIVATE_KEY(String),
+    /// A file's private key isn't a kind that can be used
+    UNSUPPORTED_KEY(String),
+    /// A certificate can't be served for the hostname
+    INVALID_CERTIFICATE { hostname: String, reason: String },
+    /// Certificates for other hostnames were given without a main hostname
+    MISSING_HOSTNAME,
+    /// A map couldn't be loaded
+    MAP(MapError),
+    /// An autosave couldn't be parsed
+    INVALID_SAVE { path: String, reason: String },
+}
+/// Allows StorageError to be printed
+impl fmt::Display for StorageError {
+    /// Writes the error using a formatter
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StorageError::IO {
+                ref path,
+                ref error,
+            } => write!(formatter, "{}: {}", path, error),
+            StorageError::NO_CERTIFICATES(ref path) => {
+                write!(formatter, "No certificates found in {}", path)
+            }
+            StorageError::INVALID_CERTIFICATES(ref path) => {
+                write!(formatter, "Invalid certificates in {}", path)
+            }
+            StorageError::NO_PRIVATE_KEY(ref path) => {
+                write!(formatter, "No private key found in {}", path)
+            }
+            StorageError::UNSUPPORTED_KEY(ref path) => {
+                write!(formatter, "Unsupported private key in {}", path)
+            }
+            StorageError::INVALID_CERTIFICATE {
+                ref hostname,
+                ref reason,
+            } => write!(formatter, "Invalid certificate for {}: {}", hostname, reason),
+            StorageError::MISSING_HOSTNAME => write!(
+                formatter,
+                "A hostname for the main certificate is required when using SNI"
+            ),
+            StorageError::MAP(ref error) => error.fmt(formatter),
+            StorageError::INVALID_SAVE {
+                ref path,
+                ref reason,
+            } => write!(formatter, "Invalid save in {}: {}", path, reason),
+        }
+    }
+}
+/// Allows StorageError to be used where an error is wanted
+impl StdError for StorageError {
     /// Description of the error
     fn description(&self) -> &str {
-        &self.description
+        match *self {
+            StorageError::IO { .. } => "file couldn't be read",
+            StorageError::NO_CERTIFICATES(_) => "no certificates",
+            StorageError::INVALID_CERTIFICATES(_) => "invalid certificates",
+            StorageError::NO_PRIVATE_KEY(_) => "no private key",
+            StorageError::UNSUPPORTED_KEY(_) => "unsupported private key",
+            StorageError::INVALID_CERTIFICATE { .. } => "invalid certificate",
+            StorageError::MISSING_HOSTNAME => "missing main certificate hostname",
+            StorageError::MAP(ref error) => error.description(),
+            StorageError::INVALID_SAVE { .. } => "invalid save",
+        }
     }
     /// Underlying cause of the error
-    fn cause(&self) -> Option<&Error> {
-        None
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            StorageError::IO { ref error, .. } => Some(error),
+            StorageError::MAP(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Reasons a map can't be loaded
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MapError {
+    /// The map file couldn't be read
+    IO { path: String, error: io::Error },
+    /// The map isn't JSON, or is missing fields a map needs
+    PARSE(serde_json::Error),
+    /// An entry of the map can't be used
+    /// path: where the entry is in the map, as a dotted list of keys and indices
+    INVALID_ENTRY { path: String, reason: String },
+}
+/// Allows MapError to be printed
+impl fmt::Display for MapError {
+    /// Writes the error using a formatter
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MapError::IO {
+                ref path,
+                ref error,
+            } => write!(formatter, "{}: {}", path, error),
+            MapError::PARSE(ref error) => write!(formatter, "Invalid map: {}", error),
+            MapError::INVALID_ENTRY {
+                ref path,
+                ref reason,
+            } => write!(formatter, "Invalid map entry {}: {}", path, reason),
+        }
+    }
+}
+/// Allows MapError to be used where an error is wanted
+impl StdError for MapError {
+    /// Description of the error
+    fn description(&self) -> &str {
+        match *self {
+            MapError::IO { .. } => "map file couldn't be read",
+            MapError::PARSE(_) => "invalid map",
+            MapError::INVALID_ENTRY { .. } => "invalid map entry",
+        }
+    }
+    /// Underlying cause of the error
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            MapError::IO { ref error, .. } => Some(error),
+            MapError::PARSE(ref error) => Some(error),
+            MapError::INVALID_ENTRY { .. } => None,
+        }
+    }
+}
+/// Allows ? to turn a MapError into an Error
+impl From<MapError> for Error {
+    fn from(error: MapError) -> Self {
+        Error::STORAGE(StorageError::MAP(error))
+    }
+}
+
+/// Reasons a world can't be created with the given dimensions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WorldConfigError {
+    /// The width or height is not a positive, finite number
+    INVALID_SIZE { width: f32, height: f32 },
+    /// The spawn margin is negative or not finite
+    INVALID_MARGIN(f32),
+    /// The world is too narrow or too short to spawn players inside the margin
+    TOO_SMALL {
+        width: f32,
+        height: f32,
+        spawn_margin: f32,
+    },
+}
+/// Allows WorldConfigError to be printed
+impl fmt::Display for WorldConfigError {
+    /// Writes the error using a formatter
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WorldConfigError::INVALID_SIZE { width, height } => write!(
+                formatter,
+                "World dimensions must be positive, got {}x{}",
+                width, height
+            ),
+            WorldConfigError::INVALID_MARGIN(margin) => write!(
+                formatter,
+                "Spawn margin must not be negative, got {}",
+                margin
+            ),
+            WorldConfigError::TOO_SMALL {
+                width,
+                height,
+                spawn_margin,
+            } => write!(
+                formatter,
+                "A {}x{} world has no room to spawn players {} from its edges",
+                width, height, spawn_margin
+            ),
+        }
+    }
+}
+/// Allows WorldConfigError to be used where an error is wanted
+impl StdError for WorldConfigError {
+    /// Description of the error
+    fn description(&self) -> &str {
+        match *self {
+            WorldConfigError::INVALID_SIZE { .. } => "invalid world size",
+            WorldConfigError::INVALID_MARGIN(_) => "invalid spawn margin",
+            WorldConfigError::TOO_SMALL { .. } => "world too small for spawn margin",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn errors_keep_their_category() {
+        let error: Error = WorldConfigError::INVALID_MARGIN(-1.0).into();
+        match error {
+            Error::WORLD(WorldError::INVALID_CONFIG(WorldConfigError::INVALID_MARGIN(_))) => {}
+            ref other => panic!("Wrong category: {:?}", other),
+        }
+        // The chain leads from the category down to the original error
+        let category = error.cause().unwrap();
+        assert_eq!(category.to_string(), error.to_string());
+        assert!(category.cause().is_some());
+        let error: Error = "SPIN".parse::<::swarm_language::SwarmCommand>().unwrap_err().into();
+        match error {
+            Error::LANGUAGE(LanguageError::UNKNOWN_COMMAND(ref command)) => {
+                assert_eq!(command, "SPIN")
+            }
+            ref other => panic!("Wrong category: {:?}", other),
+        }
     }
 }
@@ -0,0 +1,266 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use achievement::Achievement;
+use game_mode::Team;
+use limits::Limit;
+use player_stats::Weapon;
+use rules::MatchResults;
+
+/// Something that happened in the world
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum WorldEvent {
+    /// A player joined the world
+    #[serde(rename = "join")]
+    JOIN(usize),
+    /// A player left the world
+    #[serde(rename = "leave")]
+    LEAVE(usize),
+    /// A swarm member was destroyed by another player's bullet
+    #[serde(rename = "kill")]
+    KILL {
+        /// ID of the player that fired the bullet
+        attacker: usize,
+        /// ID of the player that lost a member
+        victim: usize,
+    },
+    /// A swarm's members fired
+    #[serde(rename = "fired")]
+    FIRED {
+        /// ID of the player whose swarm fired
+        player: usize,
+        /// Number of bullets fired
+        bullets: usize,
+    },
+    /// A bullet hit a member of another player's swarm
+    #[serde(rename = "hit")]
+    HIT {
+        /// ID of the player that fired the bullet
+        attacker: usize,
+        /// ID of the player that was hit
+        victim: usize,
+        /// What the hit was dealt with
+        weapon: Weapon,
+        /// Health the member lost
+        damage: u32,
+        /// X position of the member that was hit
+        x: f32,
+        /// Y position of the member that was hit
+        y: f32,
+    },
+    /// A swarm had a position or direction that wasn't a number, and was reset
+    #[serde(rename = "sanitized")]
+    SANITIZED(usize),
+    /// A player picked up the other team's flag
+    #[serde(rename = "flag_taken")]
+    FLAG_TAKEN {
+        /// ID of the player carrying the flag
+        player: usize,
+        /// Team the flag belongs to
+        team: Team,
+    },
+    /// A player dropped the flag they were carrying, by being hit or leaving
+    #[serde(rename = "flag_dropped")]
+    FLAG_DROPPED {
+        /// ID of the player that was carrying the flag
+        player: usize,
+        /// Team the flag belongs to
+        team: Team,
+    },
+    /// A player sent their team's dropped flag back to its base
+    #[serde(rename = "flag_returned")]
+    FLAG_RETURNED {
+        /// ID of the player that touched the flag
+        player: usize,
+        /// Team the flag belongs to
+        team: Team,
+    },
+    /// A player brought the other team's flag to their base, scoring for their team
+    #[serde(rename = "flag_captured")]
+    FLAG_CAPTURED {
+        /// ID of the player that scored
+        player: usize,
+        /// Team the captured flag belongs to
+        team: Team,
+    },
+    /// The king of the hill zone changed hands
+    #[serde(rename = "zone_controlled")]
+    ZONE_CONTROLLED {
+        /// Team now holding the zone, or None if it's contested or empty
+        team: Option<Team>,
+    },
+    /// Members of both teams entered the king of the hill zone
+    #[serde(rename = "zone_contested")]
+    ZONE_CONTESTED {
+        /// X position of the center
+        x: f32,
+        /// Y position of the center
+        y: f32,
+    },
+    /// The king of the hill zone moved, and nobody holds it until the next tick
+    #[serde(rename = "zone_moved")]
+    ZONE_MOVED {
+        /// X position of the new center
+        x: f32,
+        /// Y position of the new center
+        y: f32,
+    },
+    /// A team game reached its win condition
+    #[serde(rename = "match_over")]
+    MATCH_OVER {
+        /// The team that won, or None for a tie
+        winner: Option<Team>,
+    },
+    /// A match ended, and the world froze on its results
+    #[serde(rename = "match_ended")]
+    MATCH_ENDED(MatchResults),
+    /// The freeze after a match ended, and a new match started
+    #[serde(rename = "match_started")]
+    MATCH_STARTED,
+    /// A player touched the next checkpoint of a race
+    #[serde(rename = "checkpoint")]
+    CHECKPOINT {
+        /// ID of the player
+        player: usize,
+        /// Index of the checkpoint
+        index: usize,
+    },
+    /// A player finished a lap of a race
+    #[serde(rename = "lap")]
+    LAP {
+        /// ID of the player
+        player: usize,
+        /// Laps the player has completed
+        lap: u32,
+    },
+    /// A player finished a race
+    #[serde(rename = "race_finished")]
+    RACE_FINISHED {
+        /// ID of the player
+        player: usize,
+        /// The player's place, starting at 1
+        place: usize,
+    },
+    /// A wave of swarms controlled by the game spawned
+    #[serde(rename = "wave_started")]
+    WAVE_STARTED {
+        /// The wave, counting from 1
+        wave: u32,
+        /// Number of swarms in the wave
+        swarms: usize,
+    },
+    /// Every swarm in a wave was destroyed
+    #[serde(rename = "wave_cleared")]
+    WAVE_CLEARED {
+        /// The wave, counting from 1
+        wave: u32,
+    },
+    /// A player was wiped out and respawned, using up one of the shared lives
+    #[serde(rename = "life_lost")]
+    LIFE_LOST {
+        /// ID of the player
+        player: usize,
+        /// Lives left
+        lives: u32,
+    },
+    /// Every player was wiped out with no lives left
+    #[serde(rename = "survival_over")]
+    SURVIVAL_OVER {
+        /// The wave the players reached
+        wave: u32,
+    },
+    /// A duelist won a round
+    #[serde(rename = "round_won")]
+    ROUND_WON {
+        /// ID of the duelist
+        player: usize,
+        /// The round, counting from 1
+        round: u32,
+    },
+    /// A duel ended, and the duelists' ratings changed
+    #[serde(rename = "duel_over")]
+    DUEL_OVER {
+        /// ID of the duelist that won
+        winner: usize,
+        /// ID of the duelist that lost
+        loser: usize,
+        /// The winner's new rating
+        winner_rating: f64,
+        /// The loser's new rating
+        loser_rating: f64,
+    },
+    /// A player reached one of the announced kill streaks
+    #[serde(rename = "streak")]
+    STREAK {
+        /// ID of the player
+        player: usize,
+        /// Members the player has destroyed without losing one
+        streak: u32,
+    },
+    /// A player with a kill streak lost a member
+    #[serde(rename = "streak_broken")]
+    STREAK_BROKEN {
+        /// ID of the player that lost the streak
+        player: usize,
+        /// The streak that was lost
+        streak: u32,
+        /// ID of the player that broke it
+        by: usize,
+    },
+    /// A player wiped out the swarm of a player with a bounty on them
+    #[serde(rename = "bounty_claimed")]
+    BOUNTY_CLAIMED {
+        /// ID of the player that claimed the bounty
+        player: usize,
+        /// ID of the player the bounty was on
+        victim: usize,
+        /// Experience the bounty was worth
+        bounty: i64,
+    },
+    /// A player unlocked an achievement
+    #[serde(rename = "achievement")]
+    ACHIEVEMENT {
+        /// ID of the player
+        player: usize,
+        /// What they unlocked
+        achievement: Achievement,
+    },
+    /// A player's program ran EMOTE
+    #[serde(rename = "emote")]
+    EMOTE {
+        /// ID of the player
+        player: usize,
+        /// Number of the emote
+        emote: u32,
+    },
+    /// A player marked a position for their team
+    #[serde(rename = "ping")]
+    PING {
+        /// ID of the player
+        player: usize,
+        /// X position of the mark
+        x: f32,
+        /// Y position of the mark
+        y: f32,
+    },
+    /// A player's program went over an execution limit, and ran NOOP instead
+    #[serde(rename = "limit_exceeded")]
+    LIMIT_EXCEEDED {
+        /// ID of the player
+        player: usize,
+        /// The limit it went over
+        limit: Limit,
+    },
+}
@@ -0,0 +1,237 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate serde_json;
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+use swarm_language::SwarmProgram;
+use world::{World, WorldConfig};
+
+/// Result of a call into the C API
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HotsStatus {
+    /// The call succeeded
+    OK = 0,
+    /// A pointer argument was null
+    NULL_POINTER = 1,
+    /// A string argument wasn't valid UTF-8
+    INVALID_UTF8 = 2,
+    /// A program didn't compile
+    INVALID_PROGRAM = 3,
+    /// A player already has the ID
+    DUPLICATE_PLAYER = 4,
+    /// No player has the ID
+    NO_PLAYER = 5,
+    /// The buffer can't hold the state; the size it needs was written
+    BUFFER_TOO_SMALL = 6,
+    /// The state couldn't be serialized
+    SERIALIZATION = 7,
+    /// The simulation panicked, and the world should be freed
+    PANIC = 8,
+}
+
+/// Creates a world
+/// Worlds with the same size, players, programs, and seed always play out the same way
+/// return: the world, to be freed with hots_world_free, or null if the size is invalid
+#[no_mangle]
+pub extern "C" fn hots_world_new(width: f32, height: f32, seed: u64) -> *mut World {
+    match World::from_config(&WorldConfig::new(width, height)) {
+        Ok(world) => Box::into_raw(Box::new(world.with_seed(seed))),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Frees a world created by hots_world_new
+/// Passing null does nothing
+#[no_mangle]
+pub unsafe extern "C" fn hots_world_free(world: *mut World) {
+    if !world.is_null() {
+        drop(Box::from_raw(world));
+    }
+}
+
+/// Adds a player, whose swarm spawns at a random position
+#[no_mangle]
+pub unsafe extern "C" fn hots_world_add_player(world: *mut World, player_id: usize) -> HotsStatus {
+    let world = match world.as_mut() {
+        Some(world) => world,
+        None => return HotsStatus::NULL_POINTER,
+    };
+    match world.add_player(player_id) {
+        Ok(()) => HotsStatus::OK,
+        Err(_) => HotsStatus::DUPLICATE_PLAYER,
+    }
+}
+
+/// Removes a player and their swarm
+#[no_mangle]
+pub unsafe extern "C" fn hots_world_remove_player(
+    world: *mut World,
+    player_id: usize,
+) -> HotsStatus {
+    let world = match world.as_mut() {
+        Some(world) => world,
+        None => return HotsStatus::NULL_POINTER,
+    };
+    if !world.has_player(player_id) {
+        return HotsStatus::NO_PLAYER;
+    }
+    world.remove_player(player_id);
+    HotsStatus::OK
+}
+
+/// Compiles swarm code and gives it to a player
+/// source: a NUL-terminated UTF-8 string
+/// The player keeps their old program if the source doesn't compile
+#[no_mangle]
+pub unsafe extern "C" fn hots_world_set_program(
+    world: *mut World,
+    player_id: usize,
+    source: *const c_char,
+) -> HotsStatus {
+    let world = match world.as_mut() {
+        Some(world) => world,
+        None => return HotsStatus::NULL_POINTER,
+    };
+    if source.is_null() {
+        return HotsStatus::NULL_POINTER;
+    }
+    let source = match CStr::from_ptr(source).to_str() {
+        Ok(source) => source,
+        Err(_) => return HotsStatus::INVALID_UTF8,
+    };
+    if !world.has_player(player_id) {
+        return HotsStatus::NO_PLAYER;
+    }
+    match source.parse::<SwarmProgram>() {
+        Ok(program) => {
+            world.update_program(player_id, program);
+            HotsStatus::OK
+        }
+        Err(_) => HotsStatus::INVALID_PROGRAM,
+    }
+}
+
+/// Performs one tick
+/// Events aren't exposed to C, so they're dropped instead of piling up
+#[no_mangle]
+pub unsafe extern "C" fn hots_world_tick(world: *mut World) -> HotsStatus {
+    let world = match world.as_mut() {
+        Some(world) => world,
+        None => return HotsStatus::NULL_POINTER,
+    };
+    // Unwinding into C is undefined behavior
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        world.update();
+        world.clear_events();
+    }));
+    match result {
+        Ok(()) => HotsStatus::OK,
+        Err(_) => HotsStatus::PANIC,
+    }
+}
+
+/// Copies the world's state into a buffer, as the JSON the server sends clients
+/// The JSON is not NUL-terminated
+/// buffer: where to copy the state; may be null if capacity is 0
+/// written: set to the length of the state, even if it doesn't fit
+/// Call with a capacity of 0 to find out how big the buffer needs to be
+#[no_mangle]
+pub unsafe extern "C" fn hots_world_state(
+    world: *const World,
+    buffer: *mut u8,
+    capacity: usize,
+    written: *mut usize,
+) -> HotsStatus {
+    let world = match world.as_ref() {
+        Some(world) => world,
+        None => return HotsStatus::NULL_POINTER,
+    };
+    let written = match written.as_mut() {
+        Some(written) => written,
+        None => return HotsStatus::NULL_POINTER,
+    };
+    let state = match serde_json::to_vec(&world.state_ref()) {
+        Ok(state) => state,
+        Err(_) => return HotsStatus::SERIALIZATION,
+    };
+    *written = state.len();
+    if state.len() > capacity {
+        return HotsStatus::BUFFER_TOO_SMALL;
+    }
+    if buffer.is_null() {
+        return HotsStatus::NULL_POINTER;
+    }
+    slice::from_raw_parts_mut(buffer, state.len()).copy_from_slice(&state);
+    HotsStatus::OK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    #[test]
+    fn embed_world() {
+        unsafe {
+            let world = hots_world_new(500.0, 500.0, 7);
+            assert!(!world.is_null());
+            assert_eq!(hots_world_add_player(world, 3), HotsStatus::OK);
+            assert_eq!(hots_world_add_player(world, 3), HotsStatus::DUPLICATE_PLAYER);
+
+            let program = CString::new("MOVE\nTURN 10\nFIRE\n").unwrap();
+            let invalid = CString::new("JUMP\n").unwrap();
+            assert_eq!(hots_world_set_program(world, 3, program.as_ptr()), HotsStatus::OK);
+            assert_eq!(
+                hots_world_set_program(world, 3, invalid.as_ptr()),
+                HotsStatus::INVALID_PROGRAM
+            );
+            assert_eq!(
+                hots_world_set_program(world, 4, program.as_ptr()),
+                HotsStatus::NO_PLAYER
+            );
+            assert_eq!(hots_world_tick(world), HotsStatus::OK);
+
+            // Ask for the size, then copy into a buffer that fits
+            let mut needed = 0;
+            assert_eq!(
+                hots_world_state(world, ptr::null_mut(), 0, &mut needed),
+                HotsStatus::BUFFER_TOO_SMALL
+            );
+            let mut buffer = vec![0u8; needed];
+            let mut written = 0;
+            assert_eq!(
+                hots_world_state(world, buffer.as_mut_ptr(), buffer.len(), &mut written),
+                HotsStatus::OK
+            );
+            assert_eq!(written, needed);
+            assert_eq!(buffer, serde_json::to_vec(&(*world).state_ref()).unwrap());
+
+            assert_eq!(hots_world_remove_player(world, 3), HotsStatus::OK);
+            assert_eq!(hots_world_remove_player(world, 3), HotsStatus::NO_PLAYER);
+            hots_world_free(world);
+        }
+        assert!(hots_world_new(-1.0, 500.0, 7).is_null());
+        assert_eq!(
+            unsafe { hots_world_tick(ptr::null_mut()) },
+            HotsStatus::NULL_POINTER
+        );
+    }
+}
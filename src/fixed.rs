@@ -0,0 +1,220 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+#[cfg(feature = "schema")]
+use schemars::gen::SchemaGenerator;
+#[cfg(feature = "schema")]
+use schemars::schema::Schema;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::ser::{Serialize, Serializer};
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+/// A coordinate or velocity as stored by the simulation
+/// With the fixed_point feature this is a Fixed, so updates only use integer
+/// arithmetic and give bit-identical results on every platform
+#[cfg(feature = "fixed_point")]
+pub type Coord = Fixed;
+/// A coordinate or velocity as stored by the simulation
+#[cfg(not(feature = "fixed_point"))]
+pub type Coord = f32;
+
+/// Converts a float to a coordinate
+#[cfg(feature = "fixed_point")]
+pub fn to_coord(value: f32) -> Coord {
+    Fixed::from_f32(value)
+}
+/// Converts a float to a coordinate
+#[cfg(not(feature = "fixed_point"))]
+pub fn to_coord(value: f32) -> Coord {
+    value
+}
+/// Converts a coordinate to a float
+#[cfg(feature = "fixed_point")]
+pub fn from_coord(coord: Coord) -> f32 {
+    coord.to_f32()
+}
+/// Converts a coordinate to a float
+#[cfg(not(feature = "fixed_point"))]
+pub fn from_coord(coord: Coord) -> f32 {
+    coord
+}
+
+/// sin of each whole degree from 0 to 90, scaled by 2^16
+/// Written out so no platform's sin is involved in fixed-point trigonometry
+const SIN_TABLE: [i64; 91] = [
+    0, 1144, 2287, 3430, 4572, 5712, 6850, 7987, 9121, 10252, 11380, 12505, 13626, 14742, 15855,
+    16962, 18064, 19161, 20252, 21336, 22415, 23486, 24550, 25607, 26656, 27697, 28729, 29753,
+    30767, 31772, 32768, 33754, 34729, 35693, 36647, 37590, 38521, 39441, 40348, 41243, 42126,
+    42995, 43852, 44695, 45525, 46341, 47143, 47930, 48703, 49461, 50203, 50931, 51643, 52339,
+    53020, 53684, 54332, 54963, 55578, 56175, 56756, 57319, 57865, 58393, 58903, 59396, 59870,
+    60326, 60764, 61183, 61584, 61966, 62328, 62672, 62997, 63303, 63589, 63856, 64104, 64332,
+    64540, 64729, 64898, 65048, 65177, 65287, 65376, 65446, 65496, 65526, 65536,
+];
+/// Number of bits after the point in SIN_TABLE
+const SIN_BITS: u32 = 16;
+/// Number of steps each degree is divided into when looking up a sin
+const DEGREE_STEPS: i64 = 256;
+
+/// A signed fixed-point number with 8 bits after the point
+/// Covers about +/- 8 million in steps of 1/256, and every value in a
+/// world's bounds converts to and from f32 exactly
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(i32);
+
+/// Functions for Fixed
+impl Fixed {
+    /// Number of bits after the point
+    pub const FRACTION_BITS: u32 = 8;
+    /// Zero
+    pub const ZERO: Fixed = Fixed(0);
+
+    /// Constructor
+    /// raw: the value multiplied by 2^FRACTION_BITS
+    pub fn from_raw(raw: i32) -> Self {
+        Fixed(raw)
+    }
+    /// Gets the value multiplied by 2^FRACTION_BITS
+    pub fn raw(self) -> i32 {
+        self.0
+    }
+    /// Converts a float, rounding to the nearest step
+    pub fn from_f32(value: f32) -> Self {
+        Fixed((value * (1 << Fixed::FRACTION_BITS) as f32).round() as i32)
+    }
+    /// Converts to a float
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / (1 << Fixed::FRACTION_BITS) as f32
+    }
+    /// Gets the absolute value
+    pub fn abs(self) -> Self {
+        Fixed(self.0.abs())
+    }
+    /// Gets the (x, y) of a vector with the given length and direction
+    /// Like the rest of the game, y points down and degrees are counterclockwise
+    pub fn from_polar(length: Fixed, degrees: f32) -> (Fixed, Fixed) {
+        let steps = (degrees * DEGREE_STEPS as f32).round() as i64;
+        let (sin, cos) = (sin_steps(steps), sin_steps(steps + 90 * DEGREE_STEPS));
+        (
+            Fixed(((length.0 as i64 * cos) >> SIN_BITS) as i32),
+            Fixed((-(length.0 as i64 * sin) >> SIN_BITS) as i32),
+        )
+    }
+}
+
+/// Gets the sin and cos of an angle in degrees from the table
+/// Every result is a multiple of 2^-16, so it's the same on every platform
+pub fn sin_cos(degrees: f32) -> (f32, f32) {
+    let steps = (degrees * DEGREE_STEPS as f32).round() as i64;
+    let scale = (1 << SIN_BITS) as f32;
+    (
+        sin_steps(steps) as f32 / scale,
+        sin_steps(steps + 90 * DEGREE_STEPS) as f32 / scale,
+    )
+}
+
+/// sin of an angle in 1/DEGREE_STEPS of a degree, scaled by 2^SIN_BITS
+/// Uses the table, interpolating between whole degrees
+fn sin_steps(steps: i64) -> i64 {
+    let full = 360 * DEGREE_STEPS;
+    let half = 180 * DEGREE_STEPS;
+    let quarter = 90 * DEGREE_STEPS;
+    let steps = ((steps % full) + full) % full;
+    // Fold the angle into the first quadrant
+    let (steps, sign) = if steps <= quarter {
+        (steps, 1)
+    } else if steps <= half {
+        (half - steps, 1)
+    } else if steps <= half + quarter {
+        (steps - half, -1)
+    } else {
+        (full - steps, -1)
+    };
+    let degree = (steps / DEGREE_STEPS) as usize;
+    let fraction = steps % DEGREE_STEPS;
+    let sin = if degree == 90 {
+        SIN_TABLE[90]
+    } else {
+        SIN_TABLE[degree] + (SIN_TABLE[degree + 1] - SIN_TABLE[degree]) * fraction / DEGREE_STEPS
+    };
+    sign * sin
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+    fn add(self, other: Fixed) -> Fixed {
+        Fixed(self.0.wrapping_add(other.0))
+    }
+}
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, other: Fixed) {
+        self.0 = self.0.wrapping_add(other.0);
+    }
+}
+impl Sub for Fixed {
+    type Output = Fixed;
+    fn sub(self, other: Fixed) -> Fixed {
+        Fixed(self.0.wrapping_sub(other.0))
+    }
+}
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, other: Fixed) {
+        self.0 = self.0.wrapping_sub(other.0);
+    }
+}
+impl Neg for Fixed {
+    type Output = Fixed;
+    fn neg(self) -> Fixed {
+        Fixed(self.0.wrapping_neg())
+    }
+}
+
+/// Serializes as a float, so clients see the same numbers in either mode
+impl Serialize for Fixed {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f32(self.to_f32())
+    }
+}
+
+/// Described the same way as a float
+#[cfg(feature = "schema")]
+impl JsonSchema for Fixed {
+    fn schema_name() -> String {
+        f32::schema_name()
+    }
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        f32::json_schema(gen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn fixed_polar() {
+        let length = Fixed::from_f32(5.0);
+        assert_eq!(Fixed::from_polar(length, 0.0), (length, Fixed::ZERO));
+        assert_eq!(Fixed::from_polar(length, 90.0), (Fixed::ZERO, -length));
+        assert_eq!(Fixed::from_polar(length, -180.0), (-length, Fixed::ZERO));
+        assert_eq!(Fixed::from_polar(length, 630.0), (Fixed::ZERO, length));
+        let (x, y) = Fixed::from_polar(length, 33.3);
+        assert!((x.to_f32() - 5.0 * 33.3f32.to_radians().cos()).abs() <= 0.01);
+        assert!((y.to_f32() + 5.0 * 33.3f32.to_radians().sin()).abs() <= 0.01);
+        assert_eq!(sin_cos(-90.0), (-1.0, 0.0));
+        // Values in a world's bounds survive a round trip through f32
+        let position = Fixed::from_raw(4000 * 256 + 37);
+        assert_eq!(Fixed::from_f32(position.to_f32()), position);
+    }
+}
@@ -0,0 +1,1671 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate serde_json;
+
+use difficulty::Difficulty;
+use duel::{self, DEFAULT_RATING};
+use entity::{is_npc, Swarm, INITIAL_SWARM_SIZE, NPC_ID_START};
+use event::WorldEvent;
+use rand::Rng;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde_json::Value;
+use slab::Slab;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+use streak::StreakConfig;
+
+/// How close a swarm member has to be to a flag to touch it
+pub const FLAG_RADIUS: f32 = 20.0;
+/// Experience given to the player that captures a flag
+pub const CAPTURE_EXPERIENCE: i64 = 10;
+/// Radius of the king of the hill zone
+pub const ZONE_RADIUS: f32 = 100.0;
+/// Ticks between moves of the king of the hill zone, 30 seconds at 60 ticks per second
+pub const ZONE_INTERVAL: u64 = 1800;
+/// Points a team needs to win king of the hill, 100 seconds of control at 60 ticks per second
+pub const ZONE_SCORE_LIMIT: u32 = 6000;
+/// Radius of a race checkpoint
+pub const CHECKPOINT_RADIUS: f32 = 40.0;
+/// Experience given for passing a race checkpoint
+pub const CHECKPOINT_EXPERIENCE: i64 = 1;
+/// Experience given for finishing a race
+pub const FINISH_EXPERIENCE: i64 = 10;
+/// Ticks between waves in wave survival, 5 seconds at 60 ticks per second
+pub const WAVE_DELAY: u64 = 300;
+/// Lives shared by the players in wave survival
+pub const SHARED_LIVES: u32 = 3;
+/// Experience given to each player per wave number when a wave is cleared
+pub const WAVE_EXPERIENCE: i64 = 5;
+/// Color of the swarms in a wave
+pub const NPC_COLOR: (u8, u8, u8) = (128, 128, 128);
+/// Width and height of a duel's world
+pub const DUEL_SIZE: f32 = 800.0;
+/// Rounds in a duel started from a ruleset, rather than by matchmaking
+pub const DEFAULT_DUEL_ROUNDS: u32 = 3;
+
+/// Rules layered on top of the simulation, and their state
+/// The mode is updated after bullets hit, so it sees the tick's kills
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum GameMode {
+    /// Every player for themself, with no objectives besides experience
+    #[serde(rename = "free_for_all")]
+    FREE_FOR_ALL,
+    /// Two teams steal each other's flag and bring it home
+    #[serde(rename = "capture_the_flag")]
+    CAPTURE_THE_FLAG(CaptureTheFlag),
+    /// Two teams fight over a zone that scores for whoever holds it
+    #[serde(rename = "king_of_the_hill")]
+    KING_OF_THE_HILL(KingOfTheHill),
+    /// Every player for themself, passing checkpoints in order for a number of laps
+    #[serde(rename = "race")]
+    RACE(Race),
+    /// Every player together, surviving waves of swarms controlled by the game
+    #[serde(rename = "wave_survival")]
+    WAVE_SURVIVAL(WaveSurvival),
+    /// Two players fight rounds until one has won most of them
+    #[serde(rename = "duel")]
+    DUEL(Duel),
+    /// Rules defined outside this crate
+    #[serde(rename = "custom")]
+    CUSTOM(CustomMode),
+}
+
+/// Worlds are free for all unless given a mode
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::FREE_FOR_ALL
+    }
+}
+
+/// Modes a room can be created with, before there are any players
+/// Races need a map's checkpoints and custom modes need their rules, so they aren't here
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum Ruleset {
+    #[serde(rename = "free_for_all")]
+    FREE_FOR_ALL,
+    #[serde(rename = "capture_the_flag")]
+    CAPTURE_THE_FLAG,
+    #[serde(rename = "king_of_the_hill")]
+    KING_OF_THE_HILL,
+    #[serde(rename = "wave_survival")]
+    WAVE_SURVIVAL,
+    /// An unranked duel of DEFAULT_DUEL_ROUNDS rounds between the first two players to join
+    #[serde(rename = "duel")]
+    DUEL,
+}
+
+/// Rooms are free for all unless given a ruleset
+impl Default for Ruleset {
+    fn default() -> Self {
+        Ruleset::FREE_FOR_ALL
+    }
+}
+
+/// Functions for Ruleset
+impl Ruleset {
+    /// The mode for a world of the given size
+    pub fn mode(&self, width: f32, height: f32) -> GameMode {
+        match *self {
+            Ruleset::FREE_FOR_ALL => GameMode::FREE_FOR_ALL,
+            Ruleset::CAPTURE_THE_FLAG => {
+                GameMode::CAPTURE_THE_FLAG(CaptureTheFlag::new(width, height))
+            }
+            Ruleset::KING_OF_THE_HILL => {
+                GameMode::KING_OF_THE_HILL(KingOfTheHill::new(width, height))
+            }
+            Ruleset::WAVE_SURVIVAL => GameMode::WAVE_SURVIVAL(WaveSurvival::new(width, height)),
+            Ruleset::DUEL => GameMode::DUEL(Duel::new(width, height, DEFAULT_DUEL_ROUNDS)),
+        }
+    }
+}
+
+/// Functions for GameMode
+impl GameMode {
+    /// Whether the mode has no rules of its own
+    pub fn is_free_for_all(&self) -> bool {
+        *self == GameMode::FREE_FOR_ALL
+    }
+    /// Name of the mode, as it's serialized, or the name of a custom mode
+    pub fn name(&self) -> &str {
+        match *self {
+            GameMode::FREE_FOR_ALL => "free_for_all",
+            GameMode::CAPTURE_THE_FLAG(_) => "capture_the_flag",
+            GameMode::KING_OF_THE_HILL(_) => "king_of_the_hill",
+            GameMode::RACE(_) => "race",
+            GameMode::WAVE_SURVIVAL(_) => "wave_survival",
+            GameMode::DUEL(_) => "duel",
+            GameMode::CUSTOM(ref custom) => &custom.name,
+        }
+    }
+    /// The team a player is on, in modes with teams
+    pub fn team(&self, player_id: usize) -> Option<Team> {
+        match *self {
+            GameMode::CAPTURE_THE_FLAG(ref mode) => mode.team(player_id),
+            GameMode::KING_OF_THE_HILL(ref mode) => mode.team(player_id),
+            _ => None,
+        }
+    }
+    /// Called after a player joins the world
+    pub fn add_player(&mut self, player_id: usize) {
+        match *self {
+            GameMode::FREE_FOR_ALL => {}
+            GameMode::CAPTURE_THE_FLAG(ref mut ctf) => join_smaller_team(&mut ctf.teams, player_id),
+            GameMode::KING_OF_THE_HILL(ref mut koth) => {
+                join_smaller_team(&mut koth.teams, player_id)
+            }
+            GameMode::RACE(ref mut race) => {
+                race.progress.insert(player_id, RaceProgress::default());
+            }
+            GameMode::WAVE_SURVIVAL(ref mut survival) => {
+                if !is_npc(player_id) {
+                    survival.players.insert(player_id);
+                }
+            }
+            GameMode::DUEL(ref mut duel) => duel.add_player(player_id),
+            GameMode::CUSTOM(ref mut custom) => {
+                custom.hooks(|hooks| hooks.on_player_join(player_id))
+            }
+        }
+    }
+    /// Called after a player leaves the world
+    pub fn remove_player(&mut self, player_id: usize, events: &mut Vec<WorldEvent>) {
+        match *self {
+            GameMode::FREE_FOR_ALL => {}
+            GameMode::CAPTURE_THE_FLAG(ref mut ctf) => ctf.remove_player(player_id, events),
+            GameMode::KING_OF_THE_HILL(ref mut koth) => {
+                koth.teams.remove(&player_id);
+            }
+            GameMode::RACE(ref mut race) => {
+                race.progress.remove(&player_id);
+            }
+            GameMode::WAVE_SURVIVAL(ref mut survival) => {
+                survival.players.remove(&player_id);
+                survival.npcs.remove(&player_id);
+            }
+            GameMode::DUEL(ref mut duel) => duel.remove_player(player_id, events),
+            GameMode::CUSTOM(ref mut custom) => {
+                custom.hooks(|hooks| hooks.on_player_leave(player_id, events))
+            }
+        }
+    }
+    /// Applies the mode's rules for one tick
+    /// kills: (attacker, victim) for each member destroyed this tick
+    /// rng: the world's random number generator, so modes stay reproducible
+    pub fn update<R: Rng>(
+        &mut self,
+        swarms: &mut Slab<Swarm>,
+        kills: &[(usize, usize)],
+        events: &mut Vec<WorldEvent>,
+        rng: &mut R,
+    ) {
+        match *self {
+            GameMode::FREE_FOR_ALL => {}
+            GameMode::CAPTURE_THE_FLAG(ref mut ctf) => ctf.update(swarms, kills, events),
+            GameMode::KING_OF_THE_HILL(ref mut koth) => koth.update(swarms, events, rng),
+            GameMode::RACE(ref mut race) => race.update(swarms, events),
+            GameMode::WAVE_SURVIVAL(ref mut survival) => survival.update(swarms, events, rng),
+            GameMode::DUEL(ref mut duel) => duel.update(swarms, events),
+            GameMode::CUSTOM(ref mut custom) => custom.update(swarms, kills, events),
+        }
+    }
+    /// Whether the mode says the match is over
+    /// Checked after each tick's update, alongside the world's match rules
+    pub fn check_win_condition(&self, swarms: &Slab<Swarm>) -> bool {
+        match *self {
+            GameMode::CUSTOM(ref custom) => custom
+                .rules
+                .as_ref()
+                .map_or(false, |hooks| hooks.check_win_condition(swarms)),
+            _ => false,
+        }
+    }
+    /// How kill streaks are scored under the mode
+    /// Races and co-op waves aren't about kills, so they have no streaks
+    pub fn streak_config(&self) -> StreakConfig {
+        match *self {
+            GameMode::RACE(_) | GameMode::WAVE_SURVIVAL(_) => StreakConfig::disabled(),
+            GameMode::CUSTOM(ref custom) => custom
+                .rules
+                .as_ref()
+                .map_or_else(StreakConfig::default, |hooks| hooks.streak_config()),
+            _ => StreakConfig::default(),
+        }
+    }
+    /// Called when the world starts a new match, after every player respawns
+    pub fn start_match(&mut self) {
+        if let GameMode::CUSTOM(ref mut custom) = *self {
+            custom.hooks(|hooks| hooks.on_match_start());
+        }
+    }
+}
+
+/// A side in a team mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum Team {
+    #[serde(rename = "red")]
+    RED,
+    #[serde(rename = "blue")]
+    BLUE,
+}
+
+/// Functions for Team
+impl Team {
+    /// Every team, in index order
+    pub const ALL: [Team; 2] = [Team::RED, Team::BLUE];
+    /// Position of the team in per-team lists
+    pub fn index(&self) -> usize {
+        match *self {
+            Team::RED => 0,
+            Team::BLUE => 1,
+        }
+    }
+}
+
+/// Puts a new player on the team with fewer players, or red if they're even
+fn join_smaller_team(teams: &mut BTreeMap<usize, Team>, player_id: usize) {
+    let reds = teams.values().filter(|&&team| team == Team::RED).count();
+    let team = if reds * 2 <= teams.len() {
+        Team::RED
+    } else {
+        Team::BLUE
+    };
+    teams.insert(player_id, team);
+}
+
+/// Whether a member of a swarm is within a distance of a point
+fn swarm_within(swarm: &Swarm, x: f32, y: f32, radius: f32) -> bool {
+    swarm.members.iter().any(|member| {
+        let dx = swarm.x + member.x - x;
+        let dy = swarm.y + member.y - y;
+        dx * dx + dy * dy <= radius * radius
+    })
+}
+
+/// A team's flag
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Flag {
+    /// The team the flag belongs to
+    pub team: Team,
+    /// The team's base, where the flag starts and returns to
+    pub home: (f32, f32),
+    /// X position
+    pub x: f32,
+    /// Y position
+    pub y: f32,
+    /// ID of the player carrying the flag, if anyone is
+    pub carrier: Option<usize>,
+}
+
+/// Functions for Flag
+impl Flag {
+    /// Constructor
+    /// The flag starts at home
+    pub fn new(team: Team, home: (f32, f32)) -> Self {
+        Flag {
+            team: team,
+            home: home,
+            x: home.0,
+            y: home.1,
+            carrier: None,
+        }
+    }
+    /// Whether the flag is sitting at its base
+    pub fn at_home(&self) -> bool {
+        self.carrier.is_none() && (self.x, self.y) == self.home
+    }
+    /// Puts the flag back at its base
+    fn reset(&mut self) {
+        self.x = self.home.0;
+        self.y = self.home.1;
+        self.carrier = None;
+    }
+    /// Whether any member of a swarm is touching the flag
+    fn touches(&self, swarm: &Swarm) -> bool {
+        swarm_within(swarm, self.x, self.y, FLAG_RADIUS)
+    }
+}
+
+/// State of a capture the flag match
+/// A player takes the other team's flag by touching it, and scores by bringing it to their
+/// own base while their own flag is there. A carrier that loses a member drops the flag
+/// where they are, and a dropped flag goes home when a player on its team touches it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct CaptureTheFlag {
+    /// Each team's flag, by team index
+    pub flags: Vec<Flag>,
+    /// Captures made by each team, by team index
+    pub scores: Vec<u32>,
+    /// The team of each player
+    pub teams: BTreeMap<usize, Team>,
+}
+
+/// Functions for CaptureTheFlag
+impl CaptureTheFlag {
+    /// Constructor
+    /// Bases are at the middle of the world's left and right edges, a tenth of the width in
+    /// width: the width of the world
+    /// height: the height of the world
+    pub fn new(width: f32, height: f32) -> Self {
+        CaptureTheFlag {
+            flags: vec![
+                Flag::new(Team::RED, (width * 0.1, height * 0.5)),
+                Flag::new(Team::BLUE, (width * 0.9, height * 0.5)),
+            ],
+            scores: vec![0; Team::ALL.len()],
+            teams: BTreeMap::new(),
+        }
+    }
+    /// The team a player is on
+    pub fn team(&self, player_id: usize) -> Option<Team> {
+        self.teams.get(&player_id).cloned()
+    }
+    /// Drops any flag the player was carrying, and takes them off their team
+    fn remove_player(&mut self, player_id: usize, events: &mut Vec<WorldEvent>) {
+        for flag in self.flags.iter_mut() {
+            if flag.carrier == Some(player_id) {
+                flag.carrier = None;
+                events.push(WorldEvent::FLAG_DROPPED {
+                    player: player_id,
+                    team: flag.team,
+                });
+            }
+        }
+        self.teams.remove(&player_id);
+    }
+    /// Moves carried flags, then handles drops, pickups, returns, and captures
+    /// Players are handled in ascending ID order, so the lowest ID wins a flag both touch
+    fn update(
+        &mut self,
+        swarms: &mut Slab<Swarm>,
+        kills: &[(usize, usize)],
+        events: &mut Vec<WorldEvent>,
+    ) {
+        // Carried flags follow their carrier, unless the carrier was hit
+        // A carrier can't take back a flag in the tick they dropped it
+        let mut dropped_by: Vec<usize> = Vec::new();
+        for flag in self.flags.iter_mut() {
+            let carrier = match flag.carrier {
+                Some(carrier) => carrier,
+                None => continue,
+            };
+            let swarm = match swarms.get(&carrier) {
+                Some(swarm) if !swarm.members.is_empty() => swarm,
+                _ => {
+                    flag.carrier = None;
+                    dropped_by.push(carrier);
+                    events.push(WorldEvent::FLAG_DROPPED {
+                        player: carrier,
+                        team: flag.team,
+                    });
+                    continue;
+                }
+            };
+            flag.x = swarm.x;
+            flag.y = swarm.y;
+            if kills.iter().any(|&(_, victim)| victim == carrier) {
+                flag.carrier = None;
+                dropped_by.push(carrier);
+                events.push(WorldEvent::FLAG_DROPPED {
+                    player: carrier,
+                    team: flag.team,
+                });
+            }
+        }
+        let mut ids: Vec<usize> = self.teams.keys().cloned().collect();
+        ids.sort();
+        for id in ids {
+            let team = self.teams[&id];
+            let swarm = match swarms.get_mut(&id) {
+                Some(swarm) => swarm,
+                None => continue,
+            };
+            for index in 0..self.flags.len() {
+                if self.flags[index].carrier.is_some() || !self.flags[index].touches(swarm) {
+                    continue;
+                }
+                if self.flags[index].team != team {
+                    if dropped_by.contains(&id) {
+                        continue;
+                    }
+                    // Take the other team's flag
+                    let flag = &mut self.flags[index];
+                    flag.carrier = Some(id);
+                    flag.x = swarm.x;
+                    flag.y = swarm.y;
+                    events.push(WorldEvent::FLAG_TAKEN {
+                        player: id,
+                        team: flag.team,
+                    });
+                } else if !self.flags[index].at_home() {
+                    // Return our own flag
+                    self.flags[index].reset();
+                    events.push(WorldEvent::FLAG_RETURNED {
+                        player: id,
+                        team: team,
+                    });
+                } else if let Some(carried) =
+                    self.flags.iter().position(|flag| flag.carrier == Some(id))
+                {
+                    // Bring the other team's flag home to our own
+                    self.flags[carried].reset();
+                    self.scores[team.index()] += 1;
+                    swarm.add_experience(&CAPTURE_EXPERIENCE);
+                    events.push(WorldEvent::FLAG_CAPTURED {
+                        player: id,
+                        team: self.flags[carried].team,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// A zone held by whichever team has more swarm members inside it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct ControlPoint {
+    /// X position of the center
+    pub x: f32,
+    /// Y position of the center
+    pub y: f32,
+    /// Radius of the zone
+    pub radius: f32,
+    /// The team holding the zone, if either is
+    pub controller: Option<Team>,
+    /// Whether both teams have members inside
+    #[serde(default)]
+    pub contested: bool,
+}
+
+/// Functions for ControlPoint
+impl ControlPoint {
+    /// Constructor
+    /// Nobody holds a new zone
+    pub fn new(x: f32, y: f32, radius: f32) -> Self {
+        ControlPoint {
+            x: x,
+            y: y,
+            radius: radius,
+            controller: None,
+            contested: false,
+        }
+    }
+    /// Number of members each team has inside the zone, by team index
+    pub fn presence(&self, swarms: &Slab<Swarm>, teams: &BTreeMap<usize, Team>) -> Vec<usize> {
+        let mut presence = vec![0; Team::ALL.len()];
+        for (id, team) in teams.iter() {
+            if let Some(swarm) = swarms.get(id) {
+                presence[team.index()] += swarm
+                    .members
+                    .iter()
+                    .filter(|member| {
+                        let dx = swarm.x + member.x - self.x;
+                        let dy = swarm.y + member.y - self.y;
+                        dx * dx + dy * dy <= self.radius * self.radius
+                    })
+                    .count();
+            }
+        }
+        presence
+    }
+    /// Gives the zone to the team with more members inside, or to nobody if they're even
+    /// return: whether the controller changed
+    pub fn update(&mut self, swarms: &Slab<Swarm>, teams: &BTreeMap<usize, Team>) -> bool {
+        let presence = self.presence(swarms, teams);
+        self.contested = presence.iter().all(|&members| members > 0);
+        let controller = if presence[Team::RED.index()] > presence[Team::BLUE.index()] {
+            Some(Team::RED)
+        } else if presence[Team::BLUE.index()] > presence[Team::RED.index()] {
+            Some(Team::BLUE)
+        } else {
+            None
+        };
+        let changed = controller != self.controller;
+        self.controller = controller;
+        changed
+    }
+}
+
+/// State of a king of the hill match
+/// The team holding the zone scores a point each tick. The zone moves to a random spot
+/// every interval, and the match ends when a team reaches the score limit, or when the
+/// tick limit passes, in which case the team with more points wins
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct KingOfTheHill {
+    /// The contested zone
+    pub zone: ControlPoint,
+    /// Points scored by each team, by team index
+    pub scores: Vec<u32>,
+    /// The team of each player
+    pub teams: BTreeMap<usize, Team>,
+    /// Ticks played so far
+    pub ticks: u64,
+    /// Ticks between moves of the zone
+    pub interval: u64,
+    /// Points needed to win
+    pub score_limit: u32,
+    /// Longest the match can last, in ticks, if it has a limit
+    pub tick_limit: Option<u64>,
+    /// Whether the match is over; scores stop changing once it is
+    pub finished: bool,
+    /// The team that won, or None if the match isn't over or ended in a tie
+    pub winner: Option<Team>,
+    /// The width of the world, which the zone stays inside
+    pub width: f32,
+    /// The height of the world, which the zone stays inside
+    pub height: f32,
+}
+
+/// Functions for KingOfTheHill
+impl KingOfTheHill {
+    /// Constructor
+    /// The zone starts at the center of the world, and the match has no tick limit
+    /// width: the width of the world
+    /// height: the height of the world
+    pub fn new(width: f32, height: f32) -> Self {
+        KingOfTheHill {
+            zone: ControlPoint::new(width / 2.0, height / 2.0, ZONE_RADIUS),
+            scores: vec![0; Team::ALL.len()],
+            teams: BTreeMap::new(),
+            ticks: 0,
+            interval: ZONE_INTERVAL,
+            score_limit: ZONE_SCORE_LIMIT,
+            tick_limit: None,
+            finished: false,
+            winner: None,
+            width: width,
+            height: height,
+        }
+    }
+    /// Changes when the match ends
+    pub fn with_limits(mut self, score_limit: u32, tick_limit: Option<u64>) -> Self {
+        self.score_limit = score_limit;
+        self.tick_limit = tick_limit;
+        self
+    }
+    /// The team a player is on
+    pub fn team(&self, player_id: usize) -> Option<Team> {
+        self.teams.get(&player_id).cloned()
+    }
+    /// Ends the match
+    fn finish(&mut self, winner: Option<Team>, events: &mut Vec<WorldEvent>) {
+        self.finished = true;
+        self.winner = winner;
+        events.push(WorldEvent::MATCH_OVER { winner: winner });
+    }
+    /// Scores the zone, checks the win conditions, and moves the zone when it's time
+    fn update<R: Rng>(&mut self, swarms: &Slab<Swarm>, events: &mut Vec<WorldEvent>, rng: &mut R) {
+        if self.finished {
+            return;
+        }
+        self.ticks += 1;
+        let contested = self.zone.contested;
+        if self.zone.update(swarms, &self.teams) {
+            events.push(WorldEvent::ZONE_CONTROLLED {
+                team: self.zone.controller,
+            });
+        }
+        if self.zone.contested && !contested {
+            events.push(WorldEvent::ZONE_CONTESTED {
+                x: self.zone.x,
+                y: self.zone.y,
+            });
+        }
+        if let Some(team) = self.zone.controller {
+            self.scores[team.index()] += 1;
+            if self.scores[team.index()] >= self.score_limit {
+                self.finish(Some(team), events);
+                return;
+            }
+        }
+        if self.tick_limit.map_or(false, |limit| self.ticks >= limit) {
+            let (red, blue) = (
+                self.scores[Team::RED.index()],
+                self.scores[Team::BLUE.index()],
+            );
+            let winner = if red > blue {
+                Some(Team::RED)
+            } else if blue > red {
+                Some(Team::BLUE)
+            } else {
+                None
+            };
+            self.finish(winner, events);
+            return;
+        }
+        if self.interval > 0 && self.ticks % self.interval == 0 {
+            // Keep the whole zone inside the world
+            let radius = self
+                .zone
+                .radius
+                .min(self.width / 2.0)
+                .min(self.height / 2.0);
+            let x = rng.gen_range(radius, self.width - radius + ::std::f32::EPSILON);
+            let y = rng.gen_range(radius, self.height - radius + ::std::f32::EPSILON);
+            self.zone = ControlPoint::new(x, y, self.zone.radius);
+            events.push(WorldEvent::ZONE_MOVED { x: x, y: y });
+        }
+    }
+}
+
+/// A point on a race course that swarms have to touch
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Checkpoint {
+    /// X position of the center
+    pub x: f32,
+    /// Y position of the center
+    pub y: f32,
+    /// How close a swarm member has to be to pass the checkpoint
+    pub radius: f32,
+}
+
+/// Functions for Checkpoint
+impl Checkpoint {
+    /// Constructor
+    pub fn new(x: f32, y: f32, radius: f32) -> Self {
+        Checkpoint {
+            x: x,
+            y: y,
+            radius: radius,
+        }
+    }
+}
+
+/// How far a player is through a race
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct RaceProgress {
+    /// Index of the checkpoint the player has to touch next
+    pub next: usize,
+    /// Laps completed
+    pub lap: u32,
+    /// The race tick the player finished on, if they have
+    pub finished: Option<u64>,
+}
+
+/// State of a race
+/// Each player touches the checkpoints in order, going back to the first after the last,
+/// until they've done every lap
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Race {
+    /// The course, in the order it's run
+    pub checkpoints: Vec<Checkpoint>,
+    /// Laps needed to finish
+    pub laps: u32,
+    /// How far each player is through the race
+    pub progress: BTreeMap<usize, RaceProgress>,
+    /// IDs of the players that finished, in the order they finished
+    pub finishers: Vec<usize>,
+    /// Ticks raced so far
+    pub ticks: u64,
+}
+
+/// Functions for Race
+impl Race {
+    /// Constructor
+    /// checkpoints: the course, in the order it's run
+    /// laps: laps needed to finish
+    pub fn new(checkpoints: Vec<Checkpoint>, laps: u32) -> Self {
+        Race {
+            checkpoints: checkpoints,
+            laps: laps,
+            progress: BTreeMap::new(),
+            finishers: Vec::new(),
+            ticks: 0,
+        }
+    }
+    /// Circuit constructor
+    /// Spaces checkpoints evenly around an oval in the middle of the world, run clockwise
+    /// width: the width of the world
+    /// height: the height of the world
+    /// count: the number of checkpoints
+    /// laps: laps needed to finish
+    pub fn circuit(width: f32, height: f32, count: usize, laps: u32) -> Self {
+        let checkpoints = (0..count)
+            .map(|i| {
+                let angle = 2.0 * ::std::f32::consts::PI * i as f32 / count as f32;
+                Checkpoint::new(
+                    width / 2.0 + 0.35 * width * angle.cos(),
+                    height / 2.0 + 0.35 * height * angle.sin(),
+                    CHECKPOINT_RADIUS,
+                )
+            })
+            .collect();
+        Race::new(checkpoints, laps)
+    }
+    /// Player IDs from first place to last
+    /// Finishers come first in the order they finished, then everyone else by laps
+    /// and checkpoints passed, with ties going to the lower ID
+    pub fn standings(&self) -> Vec<usize> {
+        let mut racing: Vec<(&usize, &RaceProgress)> = self
+            .progress
+            .iter()
+            .filter(|&(_, progress)| progress.finished.is_none())
+            .collect();
+        racing.sort_by(|&(a_id, a), &(b_id, b)| {
+            b.lap
+                .cmp(&a.lap)
+                .then(b.next.cmp(&a.next))
+                .then(a_id.cmp(b_id))
+        });
+        self.finishers
+            .iter()
+            .cloned()
+            .chain(racing.into_iter().map(|(&id, _)| id))
+            .collect()
+    }
+    /// Advances each player that touches their next checkpoint, in ascending player ID order
+    /// A player passes at most one checkpoint per tick
+    fn update(&mut self, swarms: &mut Slab<Swarm>, events: &mut Vec<WorldEvent>) {
+        self.ticks += 1;
+        if self.checkpoints.is_empty() {
+            return;
+        }
+        for (&player, progress) in self.progress.iter_mut() {
+            if progress.finished.is_some() {
+                continue;
+            }
+            let swarm = match swarms.get_mut(&player) {
+                Some(swarm) => swarm,
+                None => continue,
+            };
+            let checkpoint = self.checkpoints[progress.next];
+            if !swarm_within(swarm, checkpoint.x, checkpoint.y, checkpoint.radius) {
+                continue;
+            }
+            swarm.add_experience(&CHECKPOINT_EXPERIENCE);
+            events.push(WorldEvent::CHECKPOINT {
+                player: player,
+                index: progress.next,
+            });
+            progress.next += 1;
+            if progress.next < self.checkpoints.len() {
+                continue;
+            }
+            progress.next = 0;
+            progress.lap += 1;
+            events.push(WorldEvent::LAP {
+                player: player,
+                lap: progress.lap,
+            });
+            if progress.lap >= self.laps {
+                progress.finished = Some(self.ticks);
+                self.finishers.push(player);
+                swarm.add_experience(&FINISH_EXPERIENCE);
+                events.push(WorldEvent::RACE_FINISHED {
+                    player: player,
+                    place: self.finishers.len(),
+                });
+            }
+        }
+    }
+}
+
+/// How many swarms each wave of wave survival has
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct WaveScheduler {
+    /// Swarms in the first wave
+    pub base: usize,
+    /// Swarms added to each wave after the first
+    pub growth: usize,
+    /// Members of each swarm in the first wave
+    pub members: usize,
+    /// Ticks between clearing a wave and the next one starting
+    pub delay: u64,
+    /// How hard the first wave's swarms are
+    /// Without one, swarms run the default program with no handicap
+    #[serde(default)]
+    pub difficulty: Option<Difficulty>,
+    /// Waves between each step up in difficulty, or 0 to keep it the same
+    #[serde(default)]
+    pub ramp: u32,
+}
+
+/// Functions for WaveScheduler
+impl WaveScheduler {
+    /// Swarms in a wave, counting from 1
+    pub fn swarms(&self, wave: u32) -> usize {
+        self.base + self.growth * (wave as usize - 1)
+    }
+    /// Members of each swarm in a wave, counting from 1
+    /// Swarms get one more member every other wave
+    pub fn members(&self, wave: u32) -> usize {
+        self.members + (wave as usize - 1) / 2
+    }
+    /// How hard a wave's swarms are, counting from 1
+    pub fn difficulty(&self, wave: u32) -> Option<Difficulty> {
+        let steps = match self.ramp {
+            0 => 0,
+            ramp => (wave as usize - 1) / ramp as usize,
+        };
+        self.difficulty.map(|difficulty| difficulty.harder(steps))
+    }
+}
+
+/// Two swarms at first, one more each wave, and the default delay
+impl Default for WaveScheduler {
+    fn default() -> Self {
+        WaveScheduler {
+            base: 2,
+            growth: 1,
+            members: INITIAL_SWARM_SIZE / 2,
+            delay: WAVE_DELAY,
+            difficulty: None,
+            ramp: 0,
+        }
+    }
+}
+
+/// State of a wave survival game
+/// Players work together against waves of swarms controlled by the game, spawned at the
+/// edges of the world. A player that loses every member respawns while the team has lives
+/// left, and the game is over once every player is wiped out with no lives left
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct WaveSurvival {
+    /// How many swarms each wave has
+    pub scheduler: WaveScheduler,
+    /// The current wave, counting from 1, or 0 before the first wave
+    pub wave: u32,
+    /// Lives the players have left between them
+    pub lives: u32,
+    /// The tick the next wave starts on, while waiting between waves
+    pub next_wave: Option<u64>,
+    /// Whether every player has been wiped out
+    pub over: bool,
+    /// Ticks played so far
+    pub ticks: u64,
+    /// IDs of the players
+    pub players: BTreeSet<usize>,
+    /// IDs of the current wave's swarms that haven't been removed
+    pub npcs: BTreeSet<usize>,
+    /// ID the next swarm controlled by the game gets
+    pub next_npc: usize,
+    /// The width of the world, which waves spawn at the edges of
+    pub width: f32,
+    /// The height of the world, which waves spawn at the edges of
+    pub height: f32,
+}
+
+/// Functions for WaveSurvival
+impl WaveSurvival {
+    /// Constructor
+    /// The first wave starts after the scheduler's delay
+    /// width: the width of the world
+    /// height: the height of the world
+    pub fn new(width: f32, height: f32) -> Self {
+        let scheduler = WaveScheduler::default();
+        WaveSurvival {
+            scheduler: scheduler,
+            wave: 0,
+            lives: SHARED_LIVES,
+            next_wave: Some(scheduler.delay),
+            over: false,
+            ticks: 0,
+            players: BTreeSet::new(),
+            npcs: BTreeSet::new(),
+            next_npc: NPC_ID_START,
+            width: width,
+            height: height,
+        }
+    }
+    /// Changes how many swarms each wave has
+    pub fn with_scheduler(mut self, scheduler: WaveScheduler) -> Self {
+        self.scheduler = scheduler;
+        self.next_wave = Some(scheduler.delay);
+        self
+    }
+    /// Respawns wiped out players, ends the game, clears and starts waves
+    fn update<R: Rng>(
+        &mut self,
+        swarms: &mut Slab<Swarm>,
+        events: &mut Vec<WorldEvent>,
+        rng: &mut R,
+    ) {
+        if self.over || self.players.is_empty() {
+            return;
+        }
+        self.ticks += 1;
+        // Players wiped out use up the shared lives, in ascending player ID order
+        for &player in self.players.iter() {
+            let swarm = match swarms.get_mut(&player) {
+                Some(swarm) if swarm.members.is_empty() && self.lives > 0 => swarm,
+                _ => continue,
+            };
+            self.lives -= 1;
+            let (x, y) = (
+                rng.gen_range(0.0, self.width),
+                rng.gen_range(0.0, self.height),
+            );
+            *swarm = swarm.respawned(x, y);
+            events.push(WorldEvent::LIFE_LOST {
+                player: player,
+                lives: self.lives,
+            });
+        }
+        let wiped_out = self.players.iter().all(|player| {
+            swarms
+                .get(player)
+                .map_or(true, |swarm| swarm.members.is_empty())
+        });
+        if wiped_out {
+            self.over = true;
+            events.push(WorldEvent::SURVIVAL_OVER { wave: self.wave });
+            return;
+        }
+        match self.next_wave {
+            Some(tick) if self.ticks >= tick => self.spawn_wave(swarms, events, rng),
+            Some(_) => {}
+            None => {
+                // The world removes the wave's swarms once they're destroyed
+                let cleared = self.npcs.iter().all(|npc| {
+                    swarms
+                        .get(npc)
+                        .map_or(true, |swarm| swarm.members.is_empty())
+                });
+                if cleared {
+                    let bonus = WAVE_EXPERIENCE * self.wave as i64;
+                    for player in self.players.iter() {
+                        if let Some(swarm) = swarms.get_mut(player) {
+                            swarm.add_experience(&bonus);
+                        }
+                    }
+                    events.push(WorldEvent::WAVE_CLEARED { wave: self.wave });
+                    self.next_wave = Some(self.ticks + self.scheduler.delay);
+                }
+            }
+        }
+    }
+    /// Spawns the next wave's swarms at random points on the edges of the world
+    fn spawn_wave<R: Rng>(
+        &mut self,
+        swarms: &mut Slab<Swarm>,
+        events: &mut Vec<WorldEvent>,
+        rng: &mut R,
+    ) {
+        self.wave += 1;
+        self.next_wave = None;
+        self.npcs.clear();
+        let count = self.scheduler.swarms(self.wave);
+        let members = self.scheduler.members(self.wave);
+        let profile = self
+            .scheduler
+            .difficulty(self.wave)
+            .map(|difficulty| difficulty.profile());
+        for _ in 0..count {
+            let along = rng.gen_range(0.0, 1.0);
+            let (x, y) = match rng.gen_range(0, 4) {
+                0 => (along * self.width, 0.0),
+                1 => (along * self.width, self.height),
+                2 => (0.0, along * self.height),
+                _ => (self.width, along * self.height),
+            };
+            let mut swarm = Swarm::new(x, y, members).with_color(NPC_COLOR);
+            swarm.direction = rng.gen_range(0.0, 360.0);
+            if let Some(ref profile) = profile {
+                profile.apply(&mut swarm, rng);
+            }
+            swarms.insert(self.next_npc, swarm);
+            self.npcs.insert(self.next_npc);
+            self.next_npc += 1;
+        }
+        events.push(WorldEvent::WAVE_STARTED {
+            wave: self.wave,
+            swarms: count,
+        });
+    }
+}
+
+/// State of a ranked duel
+/// Each round starts with both players on opposite sides of the world, and ends when one
+/// is wiped out. The first to win most of the rounds wins the duel, and both players'
+/// ratings change. Players besides the two duelists can watch, but don't take part
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Duel {
+    /// IDs of the duelists, in the order they joined
+    pub players: Vec<usize>,
+    /// Rounds in the duel; the winner is the first to win more than half
+    pub rounds: u32,
+    /// The round being played, counting from 1, or 0 before the duelists are both in
+    pub round: u32,
+    /// Rounds won by each duelist
+    pub wins: BTreeMap<usize, u32>,
+    /// Each duelist's rating, updated when the duel ends
+    pub ratings: BTreeMap<usize, f64>,
+    /// The duelist that won, once the duel is over
+    pub winner: Option<usize>,
+    /// The width of the world, which duelists start on either side of
+    pub width: f32,
+    /// The height of the world
+    pub height: f32,
+}
+
+/// Functions for Duel
+impl Duel {
+    /// Constructor
+    /// The first two players to join are the duelists, each with the default rating
+    /// width: the width of the world
+    /// height: the height of the world
+    /// rounds: rounds in the duel, best of
+    pub fn new(width: f32, height: f32, rounds: u32) -> Self {
+        Duel {
+            players: Vec::new(),
+            rounds: rounds,
+            round: 0,
+            wins: BTreeMap::new(),
+            ratings: BTreeMap::new(),
+            winner: None,
+            width: width,
+            height: height,
+        }
+    }
+    /// Sets who the duelists are and their ratings, so nobody else can take their place
+    pub fn with_players(mut self, players: [(usize, f64); 2]) -> Self {
+        for &(player_id, rating) in players.iter() {
+            self.players.push(player_id);
+            self.wins.insert(player_id, 0);
+            self.ratings.insert(player_id, rating);
+        }
+        self
+    }
+    /// Makes the player a duelist if there's room
+    fn add_player(&mut self, player_id: usize) {
+        if self.players.len() < 2 && !self.players.contains(&player_id) {
+            self.players.push(player_id);
+            self.wins.insert(player_id, 0);
+            self.ratings.insert(player_id, DEFAULT_RATING);
+        }
+    }
+    /// A duelist leaving forfeits the duel
+    fn remove_player(&mut self, player_id: usize, events: &mut Vec<WorldEvent>) {
+        if self.winner.is_some() || self.round == 0 || !self.players.contains(&player_id) {
+            return;
+        }
+        if let Some(winner) = self.opponent(player_id) {
+            self.finish(winner, player_id, events);
+        }
+    }
+    /// The other duelist
+    fn opponent(&self, player_id: usize) -> Option<usize> {
+        self.players.iter().cloned().find(|&id| id != player_id)
+    }
+    /// Starts the duel's first round once both duelists are in the world,
+    /// and scores the round when a duelist is wiped out
+    fn update(&mut self, swarms: &mut Slab<Swarm>, events: &mut Vec<WorldEvent>) {
+        if self.winner.is_some()
+            || self.players.len() < 2
+            || !self.players.iter().all(|id| swarms.contains_key(id))
+        {
+            return;
+        }
+        if self.round == 0 {
+            self.start_round(swarms);
+            return;
+        }
+        let (first, second) = (self.players[0], self.players[1]);
+        let wiped_out = |id: &usize| swarms[id].members.is_empty();
+        let round_winner = match (wiped_out(&first), wiped_out(&second)) {
+            (false, false) => return,
+            // Wiping each other out on the same tick replays the round
+            (true, true) => None,
+            (false, true) => Some(first),
+            (true, false) => Some(second),
+        };
+        if let Some(player) = round_winner {
+            let wins = {
+                let wins = self.wins.entry(player).or_insert(0);
+                *wins += 1;
+                *wins
+            };
+            events.push(WorldEvent::ROUND_WON {
+                player: player,
+                round: self.round,
+            });
+            if wins > self.rounds / 2 {
+                let loser = if player == first { second } else { first };
+                self.finish(player, loser, events);
+                return;
+            }
+        }
+        self.start_round(swarms);
+    }
+    /// Respawns the duelists facing each other from opposite sides of the world
+    fn start_round(&mut self, swarms: &mut Slab<Swarm>) {
+        self.round += 1;
+        let y = self.height / 2.0;
+        let starts = [(self.width / 4.0, 0.0), (self.width * 3.0 / 4.0, 180.0)];
+        for (id, &(x, direction)) in self.players.iter().zip(starts.iter()) {
+            if let Some(swarm) = swarms.get_mut(id) {
+                *swarm = swarm.respawned(x, y);
+                swarm.direction = direction;
+            }
+        }
+    }
+    /// Ends the duel and updates both duelists' ratings
+    fn finish(&mut self, winner: usize, loser: usize, events: &mut Vec<WorldEvent>) {
+        let rating = |id| self.ratings.get(&id).cloned().unwrap_or(DEFAULT_RATING);
+        let (winner_rating, loser_rating) = duel::rate(rating(winner), rating(loser));
+        self.ratings.insert(winner, winner_rating);
+        self.ratings.insert(loser, loser_rating);
+        self.winner = Some(winner);
+        events.push(WorldEvent::DUEL_OVER {
+            winner: winner,
+            loser: loser,
+            winner_rating: winner_rating,
+            loser_rating: loser_rating,
+        });
+    }
+}
+
+/// Hooks for rules defined outside this crate
+/// Wrap an implementation in a CustomMode to play a world under it. Every hook but
+/// box_clone does nothing by default
+pub trait ModeHooks: Send + Sync + Debug {
+    /// Called after a player joins the world
+    fn on_player_join(&mut self, _player_id: usize) {}
+    /// Called after a player leaves the world
+    fn on_player_leave(&mut self, _player_id: usize, _events: &mut Vec<WorldEvent>) {}
+    /// Called for each swarm member destroyed during a tick, in the order they were hit,
+    /// before on_tick
+    fn on_kill(
+        &mut self,
+        _attacker: usize,
+        _victim: usize,
+        _swarms: &mut Slab<Swarm>,
+        _events: &mut Vec<WorldEvent>,
+    ) {
+    }
+    /// Called once per tick, after bullets hit
+    fn on_tick(&mut self, _swarms: &mut Slab<Swarm>, _events: &mut Vec<WorldEvent>) {}
+    /// Whether the match is over, which makes the world end it as it would at a time limit
+    fn check_win_condition(&self, _swarms: &Slab<Swarm>) -> bool {
+        false
+    }
+    /// Called when the world starts a new match
+    fn on_match_start(&mut self) {}
+    /// How kill streaks are scored under the mode
+    fn streak_config(&self) -> StreakConfig {
+        StreakConfig::default()
+    }
+    /// State sent to clients with the world, updated after each tick
+    fn extra_state(&self) -> Value {
+        Value::Null
+    }
+    /// Copies the rules, so worlds playing under them can be cloned
+    fn box_clone(&self) -> Box<ModeHooks>;
+}
+
+/// A mode with rules defined outside this crate
+/// Clients only receive the name and state, so a mode deserialized from a message has no rules
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct CustomMode {
+    /// Name of the mode, for clients to tell modes apart
+    pub name: String,
+    /// The rules' extra state, as of the last tick
+    #[serde(default)]
+    pub state: Value,
+    /// The rules, if this is where the mode is played
+    #[serde(skip)]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    rules: Option<Box<ModeHooks>>,
+}
+
+/// Functions for CustomMode
+impl CustomMode {
+    /// Constructor
+    /// name: name of the mode, sent to clients
+    /// rules: hooks the world calls
+    pub fn new(name: &str, rules: Box<ModeHooks>) -> Self {
+        let state = rules.extra_state();
+        CustomMode {
+            name: name.into(),
+            state: state,
+            rules: Some(rules),
+        }
+    }
+    /// The rules, if this is where the mode is played
+    pub fn rules(&self) -> Option<&ModeHooks> {
+        self.rules.as_ref().map(|rules| &**rules)
+    }
+    /// Calls a hook, if there are rules
+    fn hooks<F: FnOnce(&mut ModeHooks)>(&mut self, hook: F) {
+        if let Some(ref mut rules) = self.rules {
+            hook(&mut **rules);
+        }
+    }
+    /// Calls on_kill for each kill, then on_tick, then records the extra state
+    fn update(
+        &mut self,
+        swarms: &mut Slab<Swarm>,
+        kills: &[(usize, usize)],
+        events: &mut Vec<WorldEvent>,
+    ) {
+        if let Some(ref mut rules) = self.rules {
+            for &(attacker, victim) in kills.iter() {
+                rules.on_kill(attacker, victim, swarms, events);
+            }
+            rules.on_tick(swarms, events);
+            self.state = rules.extra_state();
+        }
+    }
+}
+
+/// Copies the rules with box_clone
+impl Clone for CustomMode {
+    fn clone(&self) -> Self {
+        CustomMode {
+            name: self.name.clone(),
+            state: self.state.clone(),
+            rules: self.rules.as_ref().map(|rules| rules.box_clone()),
+        }
+    }
+}
+
+/// Custom modes are compared by what clients see, since rules can't be compared
+impl PartialEq for CustomMode {
+    fn eq(&self, other: &CustomMode) -> bool {
+        self.name == other.name && self.state == other.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entity::SwarmMember;
+    use rand::XorShiftRng;
+
+    /// A swarm with one member at its center
+    fn swarm_at(x: f32, y: f32) -> Swarm {
+        let mut swarm = Swarm::new(x, y, 1);
+        swarm.members = vec![SwarmMember::new(0.0, 0.0)];
+        swarm
+    }
+
+    #[test]
+    fn capture_the_flag() {
+        let mut mode = GameMode::CAPTURE_THE_FLAG(CaptureTheFlag::new(1000.0, 500.0));
+        let mut swarms = Slab::new();
+        let mut events = Vec::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        // Red's base is at (100, 250) and blue's at (900, 250)
+        for id in 1..4 {
+            mode.add_player(id);
+        }
+        swarms.insert(1, swarm_at(900.0, 250.0));
+        swarms.insert(2, swarm_at(500.0, 250.0));
+        swarms.insert(3, swarm_at(905.0, 255.0));
+        {
+            let ctf = match mode {
+                GameMode::CAPTURE_THE_FLAG(ref ctf) => ctf,
+                _ => unreachable!(),
+            };
+            assert_eq!(ctf.team(1), Some(Team::RED));
+            assert_eq!(ctf.team(2), Some(Team::BLUE));
+            assert_eq!(ctf.team(3), Some(Team::RED));
+        }
+
+        // Both reds touch blue's flag, and the lower ID takes it
+        mode.update(&mut swarms, &[], &mut events, &mut rng);
+        assert_eq!(
+            events.drain(..).collect::<Vec<_>>(),
+            vec![WorldEvent::FLAG_TAKEN {
+                player: 1,
+                team: Team::BLUE,
+            }]
+        );
+
+        // The carrier brings it home and scores
+        swarms.get_mut(&1).unwrap().x = 100.0;
+        swarms.get_mut(&3).unwrap().x = 700.0;
+        mode.update(&mut swarms, &[], &mut events, &mut rng);
+        assert_eq!(
+            events.drain(..).collect::<Vec<_>>(),
+            vec![WorldEvent::FLAG_CAPTURED {
+                player: 1,
+                team: Team::BLUE,
+            }]
+        );
+        assert_eq!(swarms[&1].experience, CAPTURE_EXPERIENCE);
+
+        // Player 3 takes the flag, and drops it when hit on the way home
+        swarms.get_mut(&3).unwrap().x = 900.0;
+        mode.update(&mut swarms, &[], &mut events, &mut rng);
+        swarms.get_mut(&3).unwrap().x = 600.0;
+        mode.update(&mut swarms, &[(2, 3)], &mut events, &mut rng);
+        // Blue's own player touches the dropped flag and sends it home
+        swarms.get_mut(&2).unwrap().x = 600.0;
+        mode.update(&mut swarms, &[], &mut events, &mut rng);
+        assert_eq!(
+            events,
+            vec![
+                WorldEvent::FLAG_TAKEN {
+                    player: 3,
+                    team: Team::BLUE,
+                },
+                WorldEvent::FLAG_DROPPED {
+                    player: 3,
+                    team: Team::BLUE,
+                },
+                WorldEvent::FLAG_RETURNED {
+                    player: 2,
+                    team: Team::BLUE,
+                },
+            ]
+        );
+        match mode {
+            GameMode::CAPTURE_THE_FLAG(ref ctf) => {
+                assert_eq!(ctf.scores, vec![1, 0]);
+                assert!(ctf.flags.iter().all(|flag| flag.at_home()));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn king_of_the_hill() {
+        let mut koth = KingOfTheHill::new(1000.0, 1000.0).with_limits(3, None);
+        koth.interval = 2;
+        let mut mode = GameMode::KING_OF_THE_HILL(koth);
+        let mut swarms = Slab::new();
+        let mut events = Vec::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        // Players 1 and 3 are red, 2 is blue; two reds outnumber one blue in the zone
+        for id in 1..4 {
+            mode.add_player(id);
+        }
+        swarms.insert(1, swarm_at(500.0, 500.0));
+        swarms.insert(2, swarm_at(520.0, 500.0));
+        swarms.insert(3, swarm_at(480.0, 500.0));
+        mode.update(&mut swarms, &[], &mut events, &mut rng);
+        assert_eq!(
+            events.drain(..).collect::<Vec<_>>(),
+            vec![
+                WorldEvent::ZONE_CONTROLLED {
+                    team: Some(Team::RED),
+                },
+                WorldEvent::ZONE_CONTESTED { x: 500.0, y: 500.0 },
+            ]
+        );
+
+        // The zone moves after the interval, and nobody holds the new one yet
+        mode.update(&mut swarms, &[], &mut events, &mut rng);
+        let (x, y) = match mode {
+            GameMode::KING_OF_THE_HILL(ref koth) => (koth.zone.x, koth.zone.y),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            events.drain(..).collect::<Vec<_>>(),
+            vec![WorldEvent::ZONE_MOVED { x: x, y: y }]
+        );
+        assert!(x >= ZONE_RADIUS && x <= 1000.0 - ZONE_RADIUS);
+
+        // Red takes the new zone and reaches the score limit
+        swarms.get_mut(&1).unwrap().x = x;
+        swarms.get_mut(&1).unwrap().y = y;
+        mode.update(&mut swarms, &[], &mut events, &mut rng);
+        assert_eq!(
+            events,
+            vec![
+                WorldEvent::ZONE_CONTROLLED {
+                    team: Some(Team::RED),
+                },
+                WorldEvent::MATCH_OVER {
+                    winner: Some(Team::RED),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn race() {
+        let checkpoints = vec![
+            Checkpoint::new(100.0, 100.0, 10.0),
+            Checkpoint::new(200.0, 100.0, 10.0),
+        ];
+        let mut mode = GameMode::RACE(Race::new(checkpoints, 2));
+        let mut swarms = Slab::new();
+        let mut events = Vec::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        mode.add_player(1);
+        mode.add_player(2);
+        swarms.insert(1, swarm_at(100.0, 100.0));
+        swarms.insert(2, swarm_at(200.0, 100.0));
+        // Player 2 is on the second checkpoint, which doesn't count until they pass the first
+        mode.update(&mut swarms, &[], &mut events, &mut rng);
+        assert_eq!(
+            events.drain(..).collect::<Vec<_>>(),
+            vec![WorldEvent::CHECKPOINT {
+                player: 1,
+                index: 0,
+            }]
+        );
+        // Player 1 runs both laps, one checkpoint per tick
+        for &x in [200.0, 100.0, 200.0].iter() {
+            swarms.get_mut(&1).unwrap().x = x;
+            mode.update(&mut swarms, &[], &mut events, &mut rng);
+        }
+        assert_eq!(
+            events,
+            vec![
+                WorldEvent::CHECKPOINT {
+                    player: 1,
+                    index: 1,
+                },
+                WorldEvent::LAP { player: 1, lap: 1 },
+                WorldEvent::CHECKPOINT {
+                    player: 1,
+                    index: 0,
+                },
+                WorldEvent::CHECKPOINT {
+                    player: 1,
+                    index: 1,
+                },
+                WorldEvent::LAP { player: 1, lap: 2 },
+                WorldEvent::RACE_FINISHED {
+                    player: 1,
+                    place: 1,
+                },
+            ]
+        );
+        assert_eq!(
+            swarms[&1].experience,
+            4 * CHECKPOINT_EXPERIENCE + FINISH_EXPERIENCE
+        );
+        match mode {
+            GameMode::RACE(ref race) => {
+                assert_eq!(race.progress[&1].finished, Some(4));
+                assert_eq!(race.standings(), vec![1, 2]);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn wave_survival() {
+        let scheduler = WaveScheduler {
+            base: 1,
+            growth: 1,
+            members: 1,
+            delay: 2,
+            difficulty: Some(Difficulty::TUTORIAL),
+            ramp: 1,
+        };
+        let mut survival = WaveSurvival::new(1000.0, 1000.0).with_scheduler(scheduler);
+        survival.lives = 1;
+        let mut mode = GameMode::WAVE_SURVIVAL(survival);
+        let mut swarms = Slab::new();
+        let mut events = Vec::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        mode.add_player(1);
+        swarms.insert(1, swarm_at(500.0, 500.0));
+        // The first wave of one swarm starts after the delay
+        mode.update(&mut swarms, &[], &mut events, &mut rng);
+        mode.update(&mut swarms, &[], &mut events, &mut rng);
+        assert_eq!(
+            events.drain(..).collect::<Vec<_>>(),
+            vec![WorldEvent::WAVE_STARTED { wave: 1, swarms: 1 }]
+        );
+        assert_eq!(swarms[&NPC_ID_START].members.len(), 1);
+        assert_eq!(
+            swarms[&NPC_ID_START].handicap,
+            Difficulty::TUTORIAL.profile().handicap
+        );
+        assert_eq!(scheduler.difficulty(3), Some(Difficulty::NORMAL));
+
+        // Destroying it clears the wave
+        swarms.get_mut(&NPC_ID_START).unwrap().members.clear();
+        mode.update(&mut swarms, &[], &mut events, &mut rng);
+        assert_eq!(
+            events.drain(..).collect::<Vec<_>>(),
+            vec![WorldEvent::WAVE_CLEARED { wave: 1 }]
+        );
+        assert_eq!(swarms[&1].experience, WAVE_EXPERIENCE);
+
+        // The player is wiped out, uses the last life, then is wiped out again
+        swarms.get_mut(&1).unwrap().members.clear();
+        mode.update(&mut swarms, &[], &mut events, &mut rng);
+        assert_eq!(swarms[&1].members.len(), INITIAL_SWARM_SIZE);
+        swarms.get_mut(&1).unwrap().members.clear();
+        mode.update(&mut swarms, &[], &mut events, &mut rng);
+        assert_eq!(
+            events,
+            vec![
+                WorldEvent::LIFE_LOST {
+                    player: 1,
+                    lives: 0,
+                },
+                WorldEvent::SURVIVAL_OVER { wave: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn duel() {
+        let mut mode = GameMode::DUEL(Duel::new(DUEL_SIZE, DUEL_SIZE, 3));
+        let mut swarms = Slab::new();
+        let mut events = Vec::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        for id in 1..4 {
+            mode.add_player(id);
+            swarms.insert(id, swarm_at(0.0, 0.0));
+        }
+        // Both duelists start the first round on opposite sides
+        mode.update(&mut swarms, &[], &mut events, &mut rng);
+        assert_eq!(swarms[&1].x, DUEL_SIZE / 4.0);
+        assert_eq!(swarms[&2].x, DUEL_SIZE * 3.0 / 4.0);
+        assert_eq!(swarms[&2].direction, 180.0);
+        // Player 2 wins two rounds, ending the best of 3
+        for _ in 0..2 {
+            swarms.get_mut(&1).unwrap().members.clear();
+            mode.update(&mut swarms, &[], &mut events, &mut rng);
+        }
+        assert_eq!(
+            events,
+            vec![
+                WorldEvent::ROUND_WON {
+                    player: 2,
+                    round: 1,
+                },
+                WorldEvent::ROUND_WON {
+                    player: 2,
+                    round: 2,
+                },
+                WorldEvent::DUEL_OVER {
+                    winner: 2,
+                    loser: 1,
+                    winner_rating: 1516.0,
+                    loser_rating: 1484.0,
+                },
+            ]
+        );
+        match mode {
+            GameMode::DUEL(ref duel) => {
+                assert_eq!(duel.players, vec![1, 2]);
+                assert_eq!(duel.ratings[&2], 1516.0);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Ends the match once a player has a number of kills
+    #[derive(Clone, Debug)]
+    struct FirstTo {
+        kills: BTreeMap<usize, u32>,
+        target: u32,
+    }
+
+    impl ModeHooks for FirstTo {
+        fn on_kill(
+            &mut self,
+            attacker: usize,
+            _victim: usize,
+            _swarms: &mut Slab<Swarm>,
+            _events: &mut Vec<WorldEvent>,
+        ) {
+            *self.kills.entry(attacker).or_insert(0) += 1;
+        }
+        fn check_win_condition(&self, _swarms: &Slab<Swarm>) -> bool {
+            self.kills.values().any(|&kills| kills >= self.target)
+        }
+        fn on_match_start(&mut self) {
+            self.kills.clear();
+        }
+        fn extra_state(&self) -> Value {
+            Value::Number(self.kills.values().sum::<u32>().into())
+        }
+        fn box_clone(&self) -> Box<ModeHooks> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn custom_mode() {
+        let rules = FirstTo {
+            kills: BTreeMap::new(),
+            target: 2,
+        };
+        let mut mode = GameMode::CUSTOM(CustomMode::new("first_to", Box::new(rules)));
+        let mut swarms = Slab::new();
+        let mut events = Vec::new();
+        let mut rng = XorShiftRng::new_unseeded();
+        mode.update(&mut swarms, &[(1, 2)], &mut events, &mut rng);
+        assert!(!mode.check_win_condition(&swarms));
+        mode.update(&mut swarms, &[(1, 2)], &mut events, &mut rng);
+        assert!(mode.check_win_condition(&swarms));
+        // Copies keep their rules, and compare by state
+        let copy = mode.clone();
+        assert_eq!(copy, mode);
+        assert!(copy.check_win_condition(&swarms));
+        match mode {
+            GameMode::CUSTOM(ref custom) => assert_eq!(custom.state, Value::Number(2.into())),
+            _ => unreachable!(),
+        }
+        mode.start_match();
+        assert!(!mode.check_win_condition(&swarms));
+    }
+}
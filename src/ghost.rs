@@ -0,0 +1,312 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate serde_json;
+
+use entity::{is_npc, Swarm};
+use event::WorldEvent;
+use plugin::WorldPlugin;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use world::World;
+
+/// Longest recording that can be played back, five minutes at 60 updates per second
+pub const MAX_GHOST_FRAMES: usize = 18000;
+
+/// Where a swarm was at the end of one tick of a recorded run
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct GhostFrame {
+    /// X position of the swarm
+    pub x: f32,
+    /// Y position of the swarm
+    pub y: f32,
+    /// Direction the swarm was facing
+    pub direction: f32,
+    /// Position of each member
+    pub members: Vec<(f32, f32)>,
+}
+
+/// Functions for GhostFrame
+impl GhostFrame {
+    /// Copies where a swarm is
+    pub fn of(swarm: &Swarm) -> Self {
+        GhostFrame {
+            x: swarm.x,
+            y: swarm.y,
+            direction: swarm.direction,
+            members: swarm
+                .members
+                .iter()
+                .map(|member| (member.x, member.y))
+                .collect(),
+        }
+    }
+}
+
+/// A swarm's trajectory through a run, one frame per tick
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Recording {
+    /// Each tick of the run, in order
+    pub frames: Vec<GhostFrame>,
+}
+
+/// Functions for Recording
+impl Recording {
+    /// Constructor
+    pub fn new() -> Self {
+        Recording::default()
+    }
+    /// Adds where a swarm is as the next frame
+    /// Frames past MAX_GHOST_FRAMES are dropped
+    pub fn record(&mut self, swarm: &Swarm) {
+        if self.frames.len() < MAX_GHOST_FRAMES {
+            self.frames.push(GhostFrame::of(swarm));
+        }
+    }
+    /// Number of ticks recorded
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+    /// Whether nothing was recorded
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+/// A recorded run played back in a world
+/// Ghosts are drawn for their owner, but can't be hit and don't score
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ghost {
+    /// ID of the player racing the ghost
+    pub owner: usize,
+    /// The run being played back
+    pub recording: Recording,
+    /// Index of the frame shown this tick
+    pub frame: usize,
+}
+
+/// Functions for Ghost
+impl Ghost {
+    /// Constructor
+    /// frame: the frame to start from
+    pub fn new(owner: usize, recording: Recording, frame: usize) -> Self {
+        Ghost {
+            owner: owner,
+            recording: recording,
+            frame: frame,
+        }
+    }
+    /// Where the ghost is this tick, if the recording hasn't ended
+    pub fn state(&self) -> Option<GhostState> {
+        self.recording
+            .frames
+            .get(self.frame)
+            .map(|frame| GhostState {
+                owner: self.owner,
+                frame: frame.clone(),
+            })
+    }
+    /// Moves on to the next frame
+    pub fn advance(&mut self) {
+        self.frame += 1;
+    }
+    /// Whether the recording has been played to the end
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.recording.len()
+    }
+}
+
+/// A ghost as sent to clients
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct GhostState {
+    /// ID of the player the ghost is shown to
+    pub owner: usize,
+    /// Where the ghost is
+    #[serde(flatten)]
+    pub frame: GhostFrame,
+}
+
+/// A message a client sends to the "ghosts" plugin
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GhostRequest {
+    /// Sends back the player's run so far, so it can be saved and raced later
+    #[serde(rename = "run")]
+    RUN,
+    /// Sends back the player's fastest finished race
+    #[serde(rename = "best")]
+    BEST,
+    /// Races the player's fastest finished race
+    #[serde(rename = "race_best")]
+    RACE_BEST,
+    /// Races a recording the client saved
+    #[serde(rename = "race")]
+    RACE(Recording),
+}
+
+/// Records every player's runs in races and practice worlds, keeping each player's fastest race,
+/// and starts ghosts when players ask for them
+/// Clients talk to it through plugin messages named "ghosts"
+#[derive(Debug, Default)]
+pub struct GhostRecorder {
+    /// The run each player is on
+    runs: Mutex<BTreeMap<usize, Recording>>,
+    /// Each player's fastest finished race
+    best: Mutex<BTreeMap<usize, Recording>>,
+}
+
+/// Functions for GhostRecorder
+impl GhostRecorder {
+    /// Constructor
+    pub fn new() -> Self {
+        GhostRecorder::default()
+    }
+    /// A player's fastest finished race, if they've finished one
+    pub fn best(&self, player: usize) -> Option<Recording> {
+        self.best
+            .lock()
+            .ok()
+            .and_then(|best| best.get(&player).cloned())
+    }
+    /// A player's run so far
+    pub fn run(&self, player: usize) -> Option<Recording> {
+        self.runs
+            .lock()
+            .ok()
+            .and_then(|runs| runs.get(&player).cloned())
+    }
+}
+
+/// Records a frame for each player every tick, and starts ghosts on request
+impl WorldPlugin for GhostRecorder {
+    fn name(&self) -> &str {
+        "ghosts"
+    }
+    fn on_tick_end(&self, world: &mut World) {
+        if !world.allows_ghosts() {
+            return;
+        }
+        let mut runs = match self.runs.lock() {
+            Ok(runs) => runs,
+            Err(_) => return,
+        };
+        for (&id, swarm) in world.swarms.iter().filter(|&(id, _)| !is_npc(*id)) {
+            runs.entry(id).or_default().record(swarm);
+        }
+    }
+    fn on_event(&self, _world: &World, event: &WorldEvent) {
+        match *event {
+            WorldEvent::MATCH_STARTED => {
+                if let Ok(mut runs) = self.runs.lock() {
+                    runs.clear();
+                }
+            }
+            WorldEvent::RACE_FINISHED { player, .. } => {
+                let run = match self.runs.lock() {
+                    Ok(mut runs) => runs.remove(&player),
+                    Err(_) => None,
+                };
+                if let (Some(run), Ok(mut best)) = (run, self.best.lock()) {
+                    let faster = best
+                        .get(&player)
+                        .map_or(true, |fastest| run.len() < fastest.len());
+                    if faster {
+                        best.insert(player, run);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    fn on_player_leave(&self, _world: &mut World, player: usize) {
+        if let Ok(mut runs) = self.runs.lock() {
+            runs.remove(&player);
+        }
+        if let Ok(mut best) = self.best.lock() {
+            best.remove(&player);
+        }
+    }
+    fn on_message(&self, world: &mut World, player: usize, data: &Value) -> Option<Value> {
+        let request: GhostRequest = match serde_json::from_value(data.clone()) {
+            Ok(request) => request,
+            Err(error) => {
+                warn!("Invalid ghost request from player {}: {}", player, error);
+                return None;
+            }
+        };
+        let recording = match request {
+            GhostRequest::RUN => return serde_json::to_value(self.run(player)).ok(),
+            GhostRequest::BEST => return serde_json::to_value(self.best(player)).ok(),
+            GhostRequest::RACE_BEST => match self.best(player) {
+                Some(recording) => recording,
+                None => return Some(Value::Bool(false)),
+            },
+            GhostRequest::RACE(recording) => recording,
+        };
+        Some(Value::Bool(world.add_ghost(player, recording).is_ok()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game_mode::{GameMode, Race};
+    use std::sync::Arc;
+
+    #[test]
+    fn record_and_race_ghost() {
+        let recorder = Arc::new(GhostRecorder::new());
+        let mut world = World::new(1000.0, 1000.0).with_seed(2);
+        world.add_plugin(recorder.clone());
+        world.add_player(1).unwrap();
+        // Free for all worlds don't record or play ghosts
+        world.update();
+        assert_eq!(recorder.run(1), None);
+        assert!(world.add_ghost(1, Recording::new()).is_err());
+
+        let mut world = World::new(1000.0, 1000.0)
+            .with_seed(2)
+            .with_mode(GameMode::RACE(Race::new(Vec::new(), 1)));
+        world.add_plugin(recorder.clone());
+        world.add_player(1).unwrap();
+        for _ in 0..3 {
+            world.update();
+        }
+        let run = recorder.run(1).unwrap();
+        assert_eq!(run.len(), 3);
+        assert_eq!(run.frames[2], GhostFrame::of(&world.swarms[&1]));
+        let reply = world.plugin_message(1, "ghosts", &Value::String("run".into()));
+        assert_eq!(
+            reply.and_then(|run| serde_json::from_value(run).ok()),
+            Some(run.clone())
+        );
+
+        // The ghost starts at the race's clock, and stops when the recording ends
+        world.add_ghost(1, run.clone()).unwrap();
+        assert_eq!(world.ghosts[0].frame, 2);
+        assert_eq!(world.get_state().ghosts.len(), 1);
+        world.update();
+        assert!(world.ghosts.is_empty());
+        let mut ghost = Ghost::new(1, run, 1);
+        assert_eq!(ghost.state().unwrap().owner, 1);
+        ghost.advance();
+        ghost.advance();
+        assert!(ghost.is_finished());
+        assert_eq!(ghost.state(), None);
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use entity::{is_npc, Swarm};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use slab::Slab;
+
+/// Multipliers on how hard a swarm hits and how fast it moves
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Handicap {
+    /// Damage dealt per hit
+    pub damage: f32,
+    /// Distance moved per tick
+    pub speed: f32,
+}
+
+/// Functions for Handicap
+impl Handicap {
+    /// Constructor
+    pub fn new(damage: f32, speed: f32) -> Self {
+        Handicap {
+            damage: damage,
+            speed: speed,
+        }
+    }
+    /// Whether the handicap changes nothing
+    pub fn is_none(&self) -> bool {
+        *self == Handicap::default()
+    }
+}
+
+/// No handicap multiplies by one
+impl Default for Handicap {
+    fn default() -> Self {
+        Handicap::new(1.0, 1.0)
+    }
+}
+
+/// Buffs for players far behind the leader, so casual lobbies stay close
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Comeback {
+    /// Least experience the leader needs before anyone is buffed
+    pub min_lead: i64,
+    /// Players with less than this fraction of the leader's experience are buffed
+    pub behind: f32,
+    /// Buff given to players far behind, on top of their handicap
+    pub buff: Handicap,
+}
+
+/// Functions for Comeback
+impl Comeback {
+    /// Constructor
+    /// Buffs players with under a quarter of the leader's experience,
+    /// once the leader has 20, with a quarter more damage and a tenth more speed
+    pub fn new() -> Self {
+        Comeback {
+            min_lead: 20,
+            behind: 0.25,
+            buff: Handicap::new(1.25, 1.1),
+        }
+    }
+    /// Buffs the players far behind the leader and takes the buff from everyone else
+    /// Computer-controlled swarms are never buffed and don't count as the leader
+    /// return: whether any player's buff changed
+    pub fn apply(&self, swarms: &mut Slab<Swarm>) -> bool {
+        let leader = swarms
+            .iter()
+            .filter(|&(id, _)| !is_npc(*id))
+            .map(|(_, swarm)| swarm.experience)
+            .max()
+            .unwrap_or(0);
+        let mut changed = false;
+        for (id, swarm) in swarms.iter_mut() {
+            let buffed = !is_npc(*id)
+                && leader >= self.min_lead
+                && (swarm.experience as f32) < leader as f32 * self.behind;
+            let boost = if buffed {
+                self.buff
+            } else {
+                Handicap::default()
+            };
+            if swarm.boost != boost {
+                swarm.boost = boost;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// The default buffs are mild
+impl Default for Comeback {
+    fn default() -> Self {
+        Comeback::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buff_players_far_behind() {
+        let comeback = Comeback::new();
+        let mut swarms = Slab::new();
+        swarms.insert(1, Swarm::new(0.0, 0.0, 1));
+        swarms.insert(2, Swarm::new(0.0, 0.0, 1));
+        swarms.insert(3, Swarm::new(0.0, 0.0, 1));
+        swarms.get_mut(&1).unwrap().experience = 10;
+        // Nobody is buffed until the leader is far enough ahead
+        assert!(!comeback.apply(&mut swarms));
+        swarms.get_mut(&1).unwrap().experience = 40;
+        swarms.get_mut(&2).unwrap().experience = 20;
+        assert!(comeback.apply(&mut swarms));
+        assert!(swarms[&1].boost.is_none());
+        assert!(swarms[&2].boost.is_none());
+        assert_eq!(swarms[&3].boost, comeback.buff);
+        // Catching up takes the buff away
+        swarms.get_mut(&3).unwrap().experience = 15;
+        assert!(comeback.apply(&mut swarms));
+        assert!(swarms[&3].boost.is_none());
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+#[cfg(feature = "server")]
+extern crate serde_json;
+
+#[cfg(feature = "server")]
+use base64;
+#[cfg(feature = "server")]
+use error::{Error, ProtocolError};
+#[cfg(feature = "server")]
+use hmac::{Hmac, Mac};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+#[cfg(feature = "server")]
+use sha2::Sha256;
+
+/// Number of seconds a handoff token can be redeemed for after it is issued
+pub const TOKEN_LIFETIME: u64 = 300;
+
+/// Everything about a player that moves with them to another server
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerSnapshot {
+    /// The player's ID on the server that issued the handoff
+    pub player_id: usize,
+    /// Display name, if the player chose one
+    pub name: Option<String>,
+    /// Source of the player's programs, with the running program first
+    pub programs: Vec<String>,
+    /// Experience gained by the player's swarm
+    pub experience: i64,
+    /// Color of the player's swarm
+    pub color: (u8, u8, u8),
+}
+
+/// Sent to a client to tell it to reconnect to another server
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct HandoffRedirect {
+    /// Address of the server to reconnect to
+    pub url: String,
+    /// Token to present to that server
+    pub token: String,
+}
+
+/// The signed contents of a handoff token
+#[cfg(feature = "server")]
+#[derive(Serialize, Deserialize)]
+struct HandoffClaims {
+    /// The player being handed off
+    snapshot: PlayerSnapshot,
+    /// Time the token was issued, in seconds since the unix epoch
+    issued_at: u64,
+}
+
+/// The signed contents of a resume token
+#[cfg(feature = "server")]
+#[derive(Serialize, Deserialize)]
+struct ResumeClaims {
+    /// The player's ID before the server restarted
+    player_id: usize,
+}
+
+/// Creates a token carrying a player snapshot, signed with a secret shared by the servers
+/// The token has the form <payload>.<signature>, both base64 encoded
+/// now: the current time, in seconds since the unix epoch
+#[cfg(feature = "server")]
+pub fn issue_token(
+    snapshot: &PlayerSnapshot,
+    secret: &[u8],
+    now: u64,
+) -> Result<String, Error> {
+    let claims = HandoffClaims {
+        snapshot: snapshot.clone(),
+        issued_at: now,
+    };
+    let payload = base64::encode_config(&serde_json::to_vec(&claims)?, base64::URL_SAFE_NO_PAD);
+    let signature = sign(payload.as_bytes(), secret)?;
+    let signature = base64::encode_config(&signature, base64::URL_SAFE_NO_PAD);
+    Ok(format!("{}.{}", payload, signature))
+}
+
+/// Checks a token's signature and age, and gets the player snapshot it carries
+/// now: the current time, in seconds since the unix epoch
+#[cfg(feature = "server")]
+pub fn redeem_token(token: &str, secret: &[u8], now: u64) -> Result<PlayerSnapshot, Error> {
+    let claims: HandoffClaims = serde_json::from_slice(&verify(token, secret)?)?;
+    if claims.issued_at > now || now - claims.issued_at > TOKEN_LIFETIME {
+        return Err(ProtocolError::EXPIRED_TOKEN.into());
+    }
+    Ok(claims.snapshot)
+}
+
+/// Creates a token a player can use to get their swarm back after the server restarts
+/// Resume tokens don't expire, since they're only useful while a save of the player exists
+#[cfg(feature = "server")]
+pub fn issue_resume_token(player_id: usize, secret: &[u8]) -> Result<String, Error> {
+    let claims = ResumeClaims {
+        player_id: player_id,
+    };
+    let payload = base64::encode_config(&serde_json::to_vec(&claims)?, base64::URL_SAFE_NO_PAD);
+    let signature = sign(payload.as_bytes(), secret)?;
+    let signature = base64::encode_config(&signature, base64::URL_SAFE_NO_PAD);
+    Ok(format!("{}.{}", payload, signature))
+}
+
+/// Checks a resume token's signature, and gets the ID the player had before the restart
+#[cfg(feature = "server")]
+pub fn redeem_resume_token(token: &str, secret: &[u8]) -> Result<usize, Error> {
+    let claims: ResumeClaims = serde_json::from_slice(&verify(token, secret)?)?;
+    Ok(claims.player_id)
+}
+
+/// Checks a token's signature, and gets its decoded payload
+#[cfg(feature = "server")]
+fn verify(token: &str, secret: &[u8]) -> Result<Vec<u8>, Error> {
+    let parts: Vec<&str> = token.trim().split('.').collect();
+    if parts.len() != 2 {
+        return Err(ProtocolError::MALFORMED_TOKEN.into());
+    }
+    let signature = match base64::decode_config(parts[1], base64::URL_SAFE_NO_PAD) {
+        Ok(signature) => signature,
+        Err(_) => return Err(ProtocolError::MALFORMED_TOKEN.into()),
+    };
+    // Compare signatures in constant time
+    let mut mac = new_mac(secret)?;
+    mac.input(parts[0].as_bytes());
+    if mac.verify(&signature).is_err() {
+        return Err(ProtocolError::INVALID_SIGNATURE.into());
+    }
+    match base64::decode_config(parts[0], base64::URL_SAFE_NO_PAD) {
+        Ok(payload) => Ok(payload),
+        Err(_) => Err(ProtocolError::MALFORMED_TOKEN.into()),
+    }
+}
+
+/// Creates a message authentication code keyed with the secret
+#[cfg(feature = "server")]
+fn new_mac(secret: &[u8]) -> Result<Hmac<Sha256>, Error> {
+    match Hmac::<Sha256>::new_varkey(secret) {
+        Ok(mac) => Ok(mac),
+        Err(_) => Err(ProtocolError::INVALID_SECRET.into()),
+    }
+}
+
+/// Signs data with the secret
+#[cfg(feature = "server")]
+fn sign(data: &[u8], secret: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut mac = new_mac(secret)?;
+    mac.input(data);
+    Ok(mac.result().code().to_vec())
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    fn snapshot() -> PlayerSnapshot {
+        PlayerSnapshot {
+            player_id: 4,
+            name: None,
+            programs: vec!["MOVE\nFIRE\n".into()],
+            experience: 12,
+            color: (10, 20, 30),
+        }
+    }
+
+    #[test]
+    fn redeem_token_round_trip() {
+        let token = issue_token(&snapshot(), b"secret", 1000).unwrap();
+        assert_eq!(redeem_token(&token, b"secret", 1010).unwrap(), snapshot());
+    }
+
+    #[test]
+    fn reject_tampered_token() {
+        let token = issue_token(&snapshot(), b"secret", 1000).unwrap();
+        assert!(redeem_token(&token, b"other secret", 1000).is_err());
+        let tampered = format!("x{}", token);
+        assert!(redeem_token(&tampered, b"secret", 1000).is_err());
+        assert!(redeem_token("not a token", b"secret", 1000).is_err());
+    }
+
+    #[test]
+    fn redeem_resume_token_round_trip() {
+        let token = issue_resume_token(4, b"secret").unwrap();
+        assert_eq!(redeem_resume_token(&token, b"secret").unwrap(), 4);
+        assert!(redeem_resume_token(&token, b"other secret").is_err());
+        // Handoff tokens can't be used to resume, and the other way around
+        let handoff = issue_token(&snapshot(), b"secret", 1000).unwrap();
+        assert!(redeem_resume_token(&handoff, b"secret").is_err());
+        assert!(redeem_token(&token, b"secret", 1000).is_err());
+    }
+
+    #[test]
+    fn reject_expired_token() {
+        let token = issue_token(&snapshot(), b"secret", 1000).unwrap();
+        assert!(redeem_token(&token, b"secret", 1000 + TOKEN_LIFETIME + 1).is_err());
+        assert!(redeem_token(&token, b"secret", 999).is_err());
+    }
+}
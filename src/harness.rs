@@ -0,0 +1,269 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate serde_json;
+
+use error::WorldConfigError;
+use event::WorldEvent;
+use rpc::{Configuration, Response, ResponseMessage};
+use serde_json::Value;
+use server::GameServer;
+use snapshot::SnapshotCache;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::{Arc, RwLock};
+use websocket::message::OwnedMessage;
+use world::{World, WorldConfig};
+use world_manager::{WorldManager, DEFAULT_ROOM};
+
+/// Update rate the harness's rooms budget their ticks against
+const HARNESS_UPDATE_FREQ: u64 = 60;
+
+/// An in-process server for integration tests
+/// Rooms only update when tick is called, and messages go through the same handler
+/// as websocket connections, so a scripted session runs the same way every time
+pub struct TestServer {
+    /// The rooms, shared with every connected client
+    manager: Arc<RwLock<WorldManager>>,
+    /// The ID the next connection gets
+    next_player_id: usize,
+    /// Every event from every tick, with the ID of the room it happened in
+    events: Vec<(usize, WorldEvent)>,
+}
+
+/// A scripted client connected to a TestServer
+pub struct TestClient {
+    /// The player ID the server gave this client
+    pub player_id: usize,
+    /// The room this client is in
+    pub room_id: usize,
+    /// The client's room
+    world: Arc<RwLock<World>>,
+    /// The room's world state cache
+    snapshots: Arc<SnapshotCache>,
+    /// The rooms, for requests that affect more than one room
+    manager: Arc<RwLock<WorldManager>>,
+    /// Every message the server sent this client, oldest first
+    pub received: Vec<String>,
+}
+
+/// Constructor
+impl TestServer {
+    /// world_config: dimensions of the default room
+    /// seed: seed for the default room's random number generator
+    pub fn new(world_config: WorldConfig, seed: u64) -> Result<Self, WorldConfigError> {
+        let manager = WorldManager::manual(world_config, HARNESS_UPDATE_FREQ)?;
+        if let Some(world) = manager.room(DEFAULT_ROOM) {
+            if let Ok(mut world) = world.write() {
+                world.rng = World::seeded_rng(seed);
+            }
+        }
+        Ok(TestServer {
+            manager: Arc::new(RwLock::new(manager)),
+            next_player_id: 0,
+            events: Vec::new(),
+        })
+    }
+}
+
+/// Functions for running a TestServer
+impl TestServer {
+    /// The rooms, for setting up rooms or plugins before clients connect
+    pub fn manager(&self) -> &Arc<RwLock<WorldManager>> {
+        &self.manager
+    }
+    /// Connects a client to a room, the same way the server accepts a websocket
+    /// return: the client, or None if the room doesn't exist or the player couldn't join
+    pub fn connect(&mut self, room_id: usize) -> Option<TestClient> {
+        let (world, snapshots) = {
+            let manager = self.manager.read().ok()?;
+            (manager.room(room_id)?, manager.snapshots(room_id)?)
+        };
+        let player_id = self.next_player_id;
+        world.write().ok()?.add_player(player_id).ok()?;
+        self.next_player_id += 1;
+        self.manager.write().ok()?.register_player(
+            player_id,
+            room_id,
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+        );
+        // Like a websocket client, start with the configuration message
+        let config = Response::new(ResponseMessage::CONFIG(Configuration::new(player_id)));
+        Some(TestClient {
+            player_id: player_id,
+            room_id: room_id,
+            world: world,
+            snapshots: snapshots,
+            manager: self.manager.clone(),
+            received: config.serialize().ok().into_iter().collect(),
+        })
+    }
+    /// Disconnects a client, the same way the server does when a websocket closes
+    pub fn disconnect(&mut self, client: TestClient) {
+        if let Ok(mut world) = client.world.write() {
+            world.remove_player(client.player_id);
+        }
+        if let Ok(mut manager) = self.manager.write() {
+            manager.unregister_player(client.player_id);
+        }
+    }
+    /// Updates every room a number of times
+    /// return: the events from these ticks, with the ID of the room they happened in
+    pub fn tick(&mut self, ticks: usize) -> Vec<(usize, WorldEvent)> {
+        let mut events = Vec::new();
+        for _ in 0..ticks {
+            match self.manager.write() {
+                Ok(mut manager) => events.extend(manager.tick()),
+                Err(error) => error!("Error getting write lock on rooms: {}", error),
+            }
+        }
+        self.events.extend(events.iter().cloned());
+        events
+    }
+    /// Every event since the server started, with the ID of the room it happened in
+    pub fn events(&self) -> &[(usize, WorldEvent)] {
+        &self.events
+    }
+    /// Runs a function on a room's world, for setting it up or checking on it
+    /// return: what the function returned, or None if the room doesn't exist
+    pub fn with_world<F, T>(&self, room_id: usize, f: F) -> Option<T>
+    where
+        F: FnOnce(&mut World) -> T,
+    {
+        let world = self.manager.read().ok()?.room(room_id)?;
+        let mut world = world.write().ok()?;
+        Some(f(&mut world))
+    }
+}
+
+/// Functions for scripting a TestClient
+impl TestClient {
+    /// Sends a raw text message, as if it came over the websocket
+    /// Unlocks sent after the reply are only kept in received
+    /// return: the server's reply, if it sent one
+    pub fn send(&mut self, message: &str) -> Option<String> {
+        let reply = GameServer::handle_message(
+            OwnedMessage::Text(message.into()),
+            self.player_id,
+            &self.world,
+            &self.snapshots,
+            &self.manager,
+        );
+        let reply = match reply {
+            Some(OwnedMessage::Text(reply)) => {
+                self.received.push(reply.clone());
+                Some(reply)
+            }
+            _ => None,
+        };
+        for unlock in GameServer::take_unlocks(self.player_id, &self.world) {
+            if let OwnedMessage::Text(unlock) = unlock {
+                self.received.push(unlock);
+            }
+        }
+        reply
+    }
+    /// Sends a raw text message and parses the reply as JSON
+    /// return: the server's reply, parsed, if it sent one
+    pub fn request(&mut self, message: &str) -> Option<Value> {
+        let reply = self.send(message)?;
+        serde_json::from_str(&reply).ok()
+    }
+    /// Uploads a program to be compiled and run
+    /// return: the compile result the server replied with
+    pub fn upload(&mut self, program: &str) -> Option<Value> {
+        let program = Value::String(program.into());
+        self.request(&format!(r#"{{"program":{}}}"#, program))
+    }
+    /// Asks for the world state inside a viewport
+    /// return: the world state the server replied with
+    pub fn viewport(&mut self, min: (f32, f32), max: (f32, f32)) -> Option<Value> {
+        self.request(&format!(
+            r#"[{{"x":{:?},"y":{:?}}},{{"x":{:?},"y":{:?}}}]"#,
+            min.0, min.1, max.0, max.1
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn scripted_session() {
+        let mut server = TestServer::new(WorldConfig::new(1000.0, 1000.0), 11).unwrap();
+        let mut shooter = server.connect(DEFAULT_ROOM).unwrap();
+        let mut bystander = server.connect(DEFAULT_ROOM).unwrap();
+        assert!(server.connect(DEFAULT_ROOM + 100).is_none());
+        assert!(shooter.received[0].contains(r#""player_id":0"#));
+        // Good programs are accepted and bad ones are turned away
+        let result = shooter.upload("FIRE\nMOVE\n").unwrap();
+        assert_eq!(result["message"]["compile"]["success"], Value::Bool(true));
+        let result = bystander.upload("JUMP\n").unwrap();
+        assert_eq!(result["message"]["compile"]["success"], Value::Bool(false));
+        // Nothing happens until the server ticks
+        assert!(server.events().is_empty());
+        let events = server.tick(2);
+        assert!(events.contains(&(DEFAULT_ROOM, WorldEvent::JOIN(bystander.player_id))));
+        assert!(events.iter().any(|&(_, ref event)| match *event {
+            WorldEvent::FIRED { player, .. } => player == shooter.player_id,
+            _ => false,
+        }));
+        assert_eq!(server.with_world(DEFAULT_ROOM, |world| world.tick), Some(2));
+        // Both players show up in the world state
+        let state = bystander.viewport((0.0, 0.0), (1000.0, 1000.0)).unwrap();
+        assert!(state.to_string().contains("swarms"));
+        let players = server.with_world(DEFAULT_ROOM, |world| world.swarms.len());
+        assert_eq!(players, Some(2));
+        // Leaving removes the player
+        let id = bystander.player_id;
+        server.disconnect(bystander);
+        server.tick(1);
+        assert!(server
+            .events()
+            .contains(&(DEFAULT_ROOM, WorldEvent::LEAVE(id))));
+        assert_eq!(
+            server.with_world(DEFAULT_ROOM, |world| world.swarms.len()),
+            Some(1)
+        );
+    }
+    #[test]
+    fn unlocks_follow_replies() {
+        use achievement::{Achievement, Achievements, MemoryAchievements};
+        let mut server = TestServer::new(WorldConfig::new(1000.0, 1000.0), 3).unwrap();
+        server.with_world(DEFAULT_ROOM, |world| {
+            world.achievements = Some(Achievements::new(Arc::new(MemoryAchievements::new())));
+        });
+        let mut client = server.connect(DEFAULT_ROOM).unwrap();
+        let player_id = client.player_id;
+        server.with_world(DEFAULT_ROOM, |world| {
+            if let Some(ref mut achievements) = world.achievements {
+                achievements.unlocked(player_id, Achievement::FIRST_KILL);
+                achievements.unlocked(player_id, Achievement::ONE_VOLLEY);
+            }
+        });
+        // The upload is answered and takes effect, with both unlocks sent after it
+        let result = client.upload("TURN 10\n").unwrap();
+        assert_eq!(result["message"]["compile"]["success"], Value::Bool(true));
+        let program = server.with_world(DEFAULT_ROOM, |world| {
+            world.swarms[&player_id].program.to_string()
+        });
+        assert_eq!(program, Some("TURN 10\n".into()));
+        assert_eq!(client.received.len(), 4);
+        assert!(client.received[2].contains("achievement"));
+        assert!(client.received[3].contains("achievement"));
+        // Nothing is sent twice
+        client.upload("FIRE\n").unwrap();
+        assert_eq!(client.received.len(), 5);
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use event::WorldEvent;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+
+/// A hit for clients to show as a floating damage number or hit marker
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct HitMarker {
+    /// The tick the hit happened on, so clients that see a tick twice show it once
+    pub tick: u64,
+    /// ID of the player that fired the bullet
+    pub attacker: usize,
+    /// ID of the player that was hit
+    pub victim: usize,
+    /// Health the member lost
+    pub damage: u32,
+    /// X position of the member that was hit
+    pub x: f32,
+    /// Y position of the member that was hit
+    pub y: f32,
+}
+
+/// Functions for HitMarker
+impl HitMarker {
+    /// Constructor
+    pub fn new(tick: u64, attacker: usize, victim: usize, damage: u32, x: f32, y: f32) -> Self {
+        HitMarker {
+            tick: tick,
+            attacker: attacker,
+            victim: victim,
+            damage: damage,
+            x: x,
+            y: y,
+        }
+    }
+}
+
+/// Turns a tick's hits into hit markers
+/// events: the events of the tick
+pub fn collect_markers(tick: u64, events: &[WorldEvent], markers: &mut Vec<HitMarker>) {
+    for event in events {
+        if let WorldEvent::HIT {
+            attacker,
+            victim,
+            damage,
+            x,
+            y,
+            ..
+        } = *event
+        {
+            markers.push(HitMarker::new(tick, attacker, victim, damage, x, y));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use player_stats::Weapon;
+
+    #[test]
+    fn markers_from_hits() {
+        let events = vec![
+            WorldEvent::FIRED {
+                player: 1,
+                bullets: 1,
+            },
+            WorldEvent::HIT {
+                attacker: 1,
+                victim: 2,
+                weapon: Weapon::BULLET,
+                damage: 3,
+                x: 10.0,
+                y: 20.0,
+            },
+            WorldEvent::KILL {
+                attacker: 1,
+                victim: 2,
+            },
+        ];
+        let mut markers = Vec::new();
+        collect_markers(7, &events, &mut markers);
+        assert_eq!(markers, vec![HitMarker::new(7, 1, 2, 3, 10.0, 20.0)]);
+    }
+}
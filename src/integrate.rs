@@ -0,0 +1,76 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use fixed::Fixed;
+#[cfg(feature = "simd")]
+use packed_simd::f32x8;
+
+/// Adds each velocity to the position at the same index, one element at a time
+/// positions and velocities must have the same length
+pub fn integrate_scalar(positions: &mut [f32], velocities: &[f32]) {
+    assert_eq!(positions.len(), velocities.len());
+    for (position, velocity) in positions.iter_mut().zip(velocities.iter()) {
+        *position += *velocity;
+    }
+}
+
+/// Adds each velocity to the position at the same index, 8 elements at a time
+/// The elements that don't fill a whole vector are added one at a time
+/// positions and velocities must have the same length
+#[cfg(feature = "simd")]
+pub fn integrate(positions: &mut [f32], velocities: &[f32]) {
+    assert_eq!(positions.len(), velocities.len());
+    let lanes = f32x8::lanes();
+    let packed = positions.len() - positions.len() % lanes;
+    for index in (0..packed).step_by(lanes) {
+        let position = f32x8::from_slice_unaligned(&positions[index..]);
+        let velocity = f32x8::from_slice_unaligned(&velocities[index..]);
+        (position + velocity).write_to_slice_unaligned(&mut positions[index..]);
+    }
+    integrate_scalar(&mut positions[packed..], &velocities[packed..]);
+}
+
+/// Adds each velocity to the position at the same index
+/// Uses packed operations when built with the simd feature
+/// positions and velocities must have the same length
+#[cfg(not(feature = "simd"))]
+pub fn integrate(positions: &mut [f32], velocities: &[f32]) {
+    integrate_scalar(positions, velocities);
+}
+
+/// Adds each fixed-point velocity to the position at the same index
+/// positions and velocities must have the same length
+pub fn integrate_fixed(positions: &mut [Fixed], velocities: &[Fixed]) {
+    assert_eq!(positions.len(), velocities.len());
+    for (position, velocity) in positions.iter_mut().zip(velocities.iter()) {
+        *position += *velocity;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn integrate_matches_scalar() {
+        // Long enough to have a partial vector at the end
+        let velocities: Vec<f32> = (0..21).map(|i| i as f32 * 0.5).collect();
+        let mut expected: Vec<f32> = (0..21).map(|i| i as f32).collect();
+        let mut positions = expected.clone();
+        integrate_scalar(&mut expected, &velocities);
+        integrate(&mut positions, &velocities);
+        assert_eq!(positions, expected);
+        assert_eq!(positions[20], 30.0);
+    }
+}
@@ -15,21 +15,173 @@
 // along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
 #![feature(duration_from_micros)]
 #![feature(iterator_step_by)]
+#![feature(non_exhaustive)]
+#![cfg_attr(feature = "python", feature(specialization))]
+#[cfg(feature = "server")]
+extern crate base64;
+#[cfg(feature = "server")]
 extern crate env_logger;
+#[cfg(feature = "server")]
 extern crate futures;
+#[cfg(feature = "server")]
+extern crate hmac;
 #[macro_use]
 extern crate log;
+#[cfg(feature = "simd")]
+extern crate packed_simd;
+#[cfg(feature = "render_png")]
+extern crate png;
+#[cfg(test)]
+#[macro_use]
+extern crate proptest;
+#[cfg(feature = "python")]
+#[macro_use]
+extern crate pyo3;
+#[cfg(not(target_arch = "wasm32"))]
 extern crate rand;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate rayon;
+#[cfg(feature = "schema")]
+#[macro_use]
+extern crate schemars;
+#[cfg(feature = "webhooks")]
+extern crate reqwest;
+#[cfg(feature = "scripting")]
+extern crate rhai;
+#[cfg(feature = "tls")]
+extern crate rustls;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+#[cfg(feature = "server")]
+extern crate sha2;
+#[cfg(feature = "server")]
 extern crate tokio_core;
+#[cfg(feature = "tls")]
+extern crate tokio_rustls;
+#[cfg(feature = "server")]
 extern crate websocket;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod achievement;
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+pub mod admin;
+#[cfg(test)]
+mod arbitrary;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod arena;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod audio;
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+pub mod autosave;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod boundary;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bounty;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod budget;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod checksum;
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+pub mod client;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod collision;
+pub mod completion;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod conformance;
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+pub mod console;
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+pub mod control;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod difficulty;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod duel;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod entity;
 pub mod error;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod event;
+#[cfg(all(feature = "ffi", not(target_arch = "wasm32")))]
+pub mod ffi;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod fixed;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod game_mode;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ghost;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod handicap;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod handoff;
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+pub mod harness;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod hit_marker;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod integrate;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod limits;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod manual;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod map;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod mapgen;
+pub mod messages;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod minimap;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod observer;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod palette;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod player_stats;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod plugin;
+#[cfg(all(feature = "python", not(target_arch = "wasm32")))]
+pub mod python;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod render;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod rpc;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod rules;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sandbox;
+#[cfg(all(feature = "schema", not(target_arch = "wasm32")))]
+pub mod schema;
+#[cfg(all(feature = "scripting", not(target_arch = "wasm32")))]
+pub mod script;
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
 pub mod server;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod signal;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod simulation;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod slab;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod streak;
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+pub mod snapshot;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod spatial;
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+pub mod spectator;
 pub mod swarm_language;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod telemetry;
+#[cfg(all(feature = "tls", not(target_arch = "wasm32")))]
+pub mod tls;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(all(feature = "webhooks", not(target_arch = "wasm32")))]
+pub mod webhook;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod world;
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+pub mod world_manager;
@@ -0,0 +1,348 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use entity::{Swarm, SwarmMember};
+use player_stats::Weapon;
+use swarm_language::{
+    Formation, SwarmCommand, MAX_CALL_DEPTH, MAX_SIERPINSKI_FOCI, MAX_WAIT_TICKS,
+};
+
+/// Most bullets a swarm can fire in one tick, by default
+pub const DEFAULT_BULLETS_PER_TICK: usize = 32;
+/// Most operations a swarm's script can run in one tick, by default
+pub const DEFAULT_SCRIPT_OPERATIONS: u64 = 10000;
+/// Most event handlers a swarm's program can start in a row, by default
+pub const DEFAULT_HANDLERS: u32 = 16;
+/// Most control flow commands a swarm's program can run in a row, by default,
+/// as long as the longest WAIT
+pub const DEFAULT_CONTROL_FLOW: u32 = MAX_WAIT_TICKS;
+
+/// A limit a swarm's program went over
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Limit {
+    /// Fired more bullets in one tick than allowed
+    #[serde(rename = "bullets")]
+    BULLETS,
+    /// Asked for a formation with more focal points than allowed
+    #[serde(rename = "formation")]
+    FORMATION,
+    /// Ran more script operations in one tick than allowed, or nested too deeply
+    #[serde(rename = "script_operations")]
+    SCRIPT_OPERATIONS,
+    /// Called more subroutines without returning than allowed
+    #[serde(rename = "call_depth")]
+    CALL_DEPTH,
+    /// Started more event handlers in a row than allowed
+    #[serde(rename = "handlers")]
+    HANDLERS,
+    /// Ran more control flow commands in a row than allowed
+    #[serde(rename = "control_flow")]
+    CONTROL_FLOW,
+}
+
+/// Hard limits on what one swarm's program can do in one tick
+/// A command that would go over a limit is replaced with NOOP,
+/// so no single program can slow the tick down for everyone
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionLimits {
+    /// Most bullets a swarm can spawn in one tick
+    pub bullets: usize,
+    /// Most focal points a generated formation can have
+    pub formation_foci: u32,
+    /// Most operations a swarm's script can run in one tick
+    #[serde(default = "default_script_operations")]
+    pub script_operations: u64,
+    /// Ticks a swarm waits between volleys, by weapon
+    #[serde(default)]
+    pub fire_rates: FireRates,
+    /// Most subroutines a swarm's program can be running at once
+    /// Programs can't go deeper than MAX_CALL_DEPTH whatever this is
+    #[serde(default = "default_call_depth")]
+    pub call_depth: usize,
+    /// How far bullets stray from where they're aimed, by weapon
+    #[serde(default)]
+    pub spreads: Spreads,
+    /// Most event handlers a swarm's program can start without running a command of its own
+    /// A swarm runs at most one handler a tick, so this stops events from starving its program
+    #[serde(default = "default_handlers")]
+    pub handlers: u32,
+    /// Most control flow commands a swarm's program can run without doing anything else
+    #[serde(default = "default_control_flow")]
+    pub control_flow: u32,
+}
+
+/// Ticks a swarm waits after firing before it can fire again, by weapon
+/// Enforced however often a program says FIRE
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FireRates {
+    /// Cooldown after firing bullets
+    pub bullet: u32,
+}
+
+/// Functions for FireRates
+impl FireRates {
+    /// The cooldown after firing a weapon
+    pub fn cooldown(&self, weapon: Weapon) -> u32 {
+        match weapon {
+            Weapon::BULLET => self.bullet,
+        }
+    }
+}
+
+/// Swarms wait as long as their members do
+impl Default for FireRates {
+    fn default() -> Self {
+        FireRates {
+            bullet: SwarmMember::FIRE_COOLDOWN,
+        }
+    }
+}
+
+/// How far a weapon's bullets stray from where they're aimed, growing as a swarm keeps firing
+/// Each volley fired before the swarm stops firing for `reset` ticks strays further,
+/// so firing as often as possible isn't always best
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Spread {
+    /// Degrees a bullet can stray either way, for each volley fired in a row before it
+    pub per_volley: f32,
+    /// Most degrees a bullet can stray either way
+    pub max: f32,
+    /// Ticks without firing after which bullets fly straight again
+    pub reset: u32,
+}
+
+/// Functions for Spread
+impl Spread {
+    /// Constructor
+    pub fn new(per_volley: f32, max: f32, reset: u32) -> Self {
+        Spread {
+            per_volley: per_volley,
+            max: max,
+            reset: reset,
+        }
+    }
+    /// Spread of a weapon whose bullets always fly where they're aimed
+    pub fn none() -> Self {
+        Spread::new(0.0, 0.0, 0)
+    }
+    /// Degrees a bullet can stray either way, after some volleys fired in a row
+    /// Values that aren't finite count as no spread
+    pub fn deviation(&self, volleys: u32) -> f32 {
+        let deviation = (self.per_volley * volleys as f32).min(self.max);
+        if deviation.is_finite() && deviation > 0.0 {
+            deviation
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Bullets fly straight unless given a spread
+impl Default for Spread {
+    fn default() -> Self {
+        Spread::none()
+    }
+}
+
+/// How far bullets stray from where they're aimed, by weapon
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Spreads {
+    /// Spread of bullets
+    pub bullet: Spread,
+}
+
+/// Functions for Spreads
+impl Spreads {
+    /// The spread of a weapon
+    pub fn spread(&self, weapon: Weapon) -> Spread {
+        match weapon {
+            Weapon::BULLET => self.bullet,
+        }
+    }
+}
+
+/// Script operation limit of limits that don't give one
+fn default_script_operations() -> u64 {
+    DEFAULT_SCRIPT_OPERATIONS
+}
+
+/// Call depth limit of limits that don't give one
+fn default_call_depth() -> usize {
+    MAX_CALL_DEPTH
+}
+
+/// Handler limit of limits that don't give one
+fn default_handlers() -> u32 {
+    DEFAULT_HANDLERS
+}
+
+/// Control flow limit of limits that don't give one
+fn default_control_flow() -> u32 {
+    DEFAULT_CONTROL_FLOW
+}
+
+/// Default limits
+impl Default for ExecutionLimits {
+    /// Limits no program written within the language's own ranges goes over
+    fn default() -> Self {
+        ExecutionLimits {
+            bullets: DEFAULT_BULLETS_PER_TICK,
+            formation_foci: MAX_SIERPINSKI_FOCI,
+            script_operations: DEFAULT_SCRIPT_OPERATIONS,
+            fire_rates: FireRates::default(),
+            call_depth: MAX_CALL_DEPTH,
+            spreads: Spreads::default(),
+            handlers: DEFAULT_HANDLERS,
+            control_flow: DEFAULT_CONTROL_FLOW,
+        }
+    }
+}
+
+/// Functions for ExecutionLimits
+impl ExecutionLimits {
+    /// Constructor
+    pub fn new(bullets: usize, formation_foci: u32) -> Self {
+        ExecutionLimits {
+            bullets: bullets,
+            formation_foci: formation_foci,
+            script_operations: DEFAULT_SCRIPT_OPERATIONS,
+            fire_rates: FireRates::default(),
+            call_depth: MAX_CALL_DEPTH,
+            spreads: Spreads::default(),
+            handlers: DEFAULT_HANDLERS,
+            control_flow: DEFAULT_CONTROL_FLOW,
+        }
+    }
+    /// Sets how long swarms wait between volleys
+    pub fn with_fire_rates(mut self, fire_rates: FireRates) -> Self {
+        self.fire_rates = fire_rates;
+        self
+    }
+    /// Sets how far bullets stray from where they're aimed
+    pub fn with_spreads(mut self, spreads: Spreads) -> Self {
+        self.spreads = spreads;
+        self
+    }
+    /// Sets the most operations a swarm's script can run in one tick
+    pub fn with_script_operations(mut self, script_operations: u64) -> Self {
+        self.script_operations = script_operations;
+        self
+    }
+    /// Sets the most subroutines a swarm's program can be running at once
+    pub fn with_call_depth(mut self, call_depth: usize) -> Self {
+        self.call_depth = call_depth;
+        self
+    }
+    /// Sets the most event handlers a swarm's program can start in a row
+    pub fn with_handlers(mut self, handlers: u32) -> Self {
+        self.handlers = handlers;
+        self
+    }
+    /// Sets the most control flow commands a swarm's program can run in a row
+    pub fn with_control_flow(mut self, control_flow: u32) -> Self {
+        self.control_flow = control_flow;
+        self
+    }
+    /// Finds the limit starting the handler for the swarm's pending event would go over, if any
+    pub fn check_dispatch(&self, swarm: &Swarm) -> Option<Limit> {
+        if swarm.pending.is_some()
+            && swarm.program.interrupted.is_none()
+            && swarm.handlers_in_a_row >= self.handlers
+        {
+            Some(Limit::HANDLERS)
+        } else {
+            None
+        }
+    }
+    /// Finds the limit the swarm's next command would go over, if any
+    /// Commands that would do nothing because of a cooldown can't go over a limit
+    pub fn check(&self, swarm: &Swarm) -> Option<Limit> {
+        match swarm.next_command() {
+            Some(SwarmCommand::FIRE)
+                if swarm.fire_cooldown == 0 && swarm.ready_members() > self.bullets =>
+            {
+                Some(Limit::BULLETS)
+            }
+            Some(SwarmCommand::FORMATION(Formation::SIERPINSKI(foci)))
+                if swarm.formation_cooldown == 0 && foci > self.formation_foci =>
+            {
+                Some(Limit::FORMATION)
+            }
+            Some(SwarmCommand::CALL(_)) if swarm.program.calls.len() >= self.call_depth => {
+                Some(Limit::CALL_DEPTH)
+            }
+            Some(command)
+                if command.is_control_flow() && swarm.control_steps >= self.control_flow =>
+            {
+                Some(Limit::CONTROL_FLOW)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_limits() {
+        let mut swarm = Swarm::new(100.0, 100.0, 1);
+        swarm.program.commands = vec![
+            SwarmCommand::FIRE,
+            SwarmCommand::FORMATION(Formation::SIERPINSKI(8)),
+        ];
+        assert_eq!(ExecutionLimits::default().check(&swarm), None);
+        let limits = ExecutionLimits::new(swarm.members.len() - 1, 4);
+        assert_eq!(limits.check(&swarm), Some(Limit::BULLETS));
+        // Members cooling down don't fire, so firing again wouldn't spawn as many bullets
+        swarm.members[0].cooldown = 5;
+        assert_eq!(limits.check(&swarm), None);
+        swarm.skip_command();
+        assert_eq!(swarm.members[0].cooldown, 4);
+        assert_eq!(limits.check(&swarm), Some(Limit::FORMATION));
+        swarm.program = "SUB a\nCALL a\nENDSUB\nCALL a".parse().unwrap();
+        swarm.program.program_counter = 3;
+        assert_eq!(limits.check(&swarm), None);
+        assert_eq!(
+            limits.with_call_depth(0).check(&swarm),
+            Some(Limit::CALL_DEPTH)
+        );
+    }
+
+    #[test]
+    fn check_handlers_and_control_flow() {
+        use swarm_language::Trigger;
+        let mut swarm = Swarm::new(100.0, 100.0, 1);
+        swarm.program = "LABEL a\nJUMP a\nON_HIT\nTURN 10\nENDON".parse().unwrap();
+        let limits = ExecutionLimits::default()
+            .with_handlers(2)
+            .with_control_flow(3);
+        // Handlers started back to back count up until the program runs a command of its own
+        swarm.handlers_in_a_row = 2;
+        assert_eq!(limits.check_dispatch(&swarm), None);
+        swarm.raise(Trigger::HIT);
+        assert_eq!(limits.check_dispatch(&swarm), Some(Limit::HANDLERS));
+        swarm.handlers_in_a_row = 1;
+        assert_eq!(limits.check_dispatch(&swarm), None);
+        // Jumping around without doing anything counts up too
+        swarm.control_steps = 2;
+        assert_eq!(limits.check(&swarm), None);
+        swarm.control_steps = 3;
+        assert_eq!(limits.check(&swarm), Some(Limit::CONTROL_FLOW));
+        swarm.skip_command();
+        assert_eq!(swarm.control_steps, 0);
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use swarm_language::{SwarmCommand, MAX_TURN};
+
+/// Ticks an input lasts when the client doesn't say, half a second at 60 ticks per second
+pub const DEFAULT_MANUAL_TICKS: u64 = 30;
+/// Longest an input can last, ten seconds at 60 ticks per second
+pub const MAX_MANUAL_TICKS: u64 = 600;
+
+/// How manual control shares the swarm with its program
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum Blend {
+    /// The program is paused while the input lasts
+    #[serde(rename = "override")]
+    OVERRIDE,
+    /// The input and the program take turns, starting with the input
+    #[serde(rename = "interleave")]
+    INTERLEAVE,
+}
+
+/// Manual control takes priority unless asked otherwise
+impl Default for Blend {
+    fn default() -> Self {
+        Blend::OVERRIDE
+    }
+}
+
+/// A direct control input from a player
+/// The swarm runs one command a tick, so the turn, thrust and fire take turns
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct ControlInput {
+    /// Degrees to turn each time the turn comes up, clockwise, up to the language's MAX_TURN
+    #[serde(default)]
+    pub turn: f32,
+    /// Whether to move forward
+    #[serde(default)]
+    pub thrust: bool,
+    /// Whether to fire
+    #[serde(default)]
+    pub fire: bool,
+    /// Ticks the input lasts, up to MAX_MANUAL_TICKS
+    #[serde(default = "default_ticks")]
+    pub ticks: u64,
+    /// How the input shares the swarm with its program
+    #[serde(default)]
+    pub blend: Blend,
+}
+
+/// Ticks an input lasts when the client doesn't say
+fn default_ticks() -> u64 {
+    DEFAULT_MANUAL_TICKS
+}
+
+/// Functions for ControlInput
+impl ControlInput {
+    /// The commands the input cycles through
+    /// An input that does nothing holds the swarm still
+    pub fn commands(&self) -> Vec<SwarmCommand> {
+        let mut commands = Vec::with_capacity(3);
+        if self.turn.is_finite() && self.turn != 0.0 {
+            commands.push(SwarmCommand::TURN(self.turn.max(-MAX_TURN).min(MAX_TURN)));
+        }
+        if self.thrust {
+            commands.push(SwarmCommand::MOVE);
+        }
+        if self.fire {
+            commands.push(SwarmCommand::FIRE);
+        }
+        if commands.is_empty() {
+            commands.push(SwarmCommand::NOOP);
+        }
+        commands
+    }
+}
+
+/// A control input being applied to a swarm
+#[derive(Clone, Debug, PartialEq)]
+pub struct ManualControl {
+    /// How the input shares the swarm with its program
+    blend: Blend,
+    /// The commands the input cycles through
+    commands: Vec<SwarmCommand>,
+    /// Index of the next command
+    next: usize,
+    /// Ticks the input has left
+    remaining: u64,
+    /// Ticks the input has been applied for
+    elapsed: u64,
+}
+
+/// Functions for ManualControl
+impl ManualControl {
+    /// Constructor
+    pub fn new(input: &ControlInput) -> Self {
+        ManualControl {
+            blend: input.blend,
+            commands: input.commands(),
+            next: 0,
+            remaining: input.ticks.min(MAX_MANUAL_TICKS),
+            elapsed: 0,
+        }
+    }
+    /// Uses up a tick of the input
+    /// return: the command to run in place of the program's, or None on the program's turn
+    pub fn next_command(&mut self) -> Option<SwarmCommand> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        self.elapsed += 1;
+        if self.blend == Blend::INTERLEAVE && self.elapsed % 2 == 0 {
+            return None;
+        }
+        let command = self.commands[self.next];
+        self.next = (self.next + 1) % self.commands.len();
+        Some(command)
+    }
+    /// Whether the input has run out
+    pub fn is_finished(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blend_inputs() {
+        let input = ControlInput {
+            turn: 90.0,
+            thrust: true,
+            fire: false,
+            ticks: 3,
+            blend: Blend::OVERRIDE,
+        };
+        let mut manual = ManualControl::new(&input);
+        assert_eq!(manual.next_command(), Some(SwarmCommand::TURN(MAX_TURN)));
+        assert_eq!(manual.next_command(), Some(SwarmCommand::MOVE));
+        assert_eq!(manual.next_command(), Some(SwarmCommand::TURN(MAX_TURN)));
+        assert!(manual.is_finished());
+        assert_eq!(manual.next_command(), None);
+
+        let input = ControlInput {
+            turn: 0.0,
+            thrust: false,
+            fire: true,
+            ticks: 4,
+            blend: Blend::INTERLEAVE,
+        };
+        let mut manual = ManualControl::new(&input);
+        let commands: Vec<_> = (0..4).map(|_| manual.next_command()).collect();
+        assert_eq!(
+            commands,
+            vec![
+                Some(SwarmCommand::FIRE),
+                None,
+                Some(SwarmCommand::FIRE),
+                None
+            ]
+        );
+    }
+}
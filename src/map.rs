@@ -0,0 +1,470 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate serde_json;
+
+use error::MapError;
+use game_mode::{Checkpoint, ControlPoint, Race};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use std::fs;
+use std::path::Path;
+use world::WorldConfig;
+
+/// A rectangle swarms can't move into and bullets can't pass through
+/// Positioned by its top left corner
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Obstacle {
+    /// X position of the left edge
+    pub x: f32,
+    /// Y position of the top edge
+    pub y: f32,
+    /// Width of the obstacle
+    pub width: f32,
+    /// Height of the obstacle
+    pub height: f32,
+}
+
+/// Functions for Obstacle
+impl Obstacle {
+    /// Constructor
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Obstacle {
+            x: x,
+            y: y,
+            width: width,
+            height: height,
+        }
+    }
+    /// Whether a point is inside the obstacle
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        rect_contains((self.x, self.y, self.width, self.height), x, y)
+    }
+    /// Whether the straight line between two points passes through the obstacle
+    pub fn blocks(&self, from: (f32, f32), to: (f32, f32)) -> bool {
+        // The part of the line inside the obstacle, as fractions of the way along it
+        let (mut enter, mut exit) = (0.0f32, 1.0f32);
+        let axes = [
+            (from.0, to.0 - from.0, self.x, self.x + self.width),
+            (from.1, to.1 - from.1, self.y, self.y + self.height),
+        ];
+        for &(start, delta, low, high) in axes.iter() {
+            if delta == 0.0 {
+                if start < low || start >= high {
+                    return false;
+                }
+                continue;
+            }
+            let (near, far) = ((low - start) / delta, (high - start) / delta);
+            let (near, far) = if near < far { (near, far) } else { (far, near) };
+            enter = enter.max(near);
+            exit = exit.min(far);
+            if enter > exit {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// A rectangle that changes how fast swarms move across it
+/// Positioned by its top left corner
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Terrain {
+    /// X position of the left edge
+    pub x: f32,
+    /// Y position of the top edge
+    pub y: f32,
+    /// Width of the patch
+    pub width: f32,
+    /// Height of the patch
+    pub height: f32,
+    /// Multiplier on the distance swarms move while their center is on the patch
+    pub speed: f32,
+}
+
+/// Functions for Terrain
+impl Terrain {
+    /// Constructor
+    pub fn new(x: f32, y: f32, width: f32, height: f32, speed: f32) -> Self {
+        Terrain {
+            x: x,
+            y: y,
+            width: width,
+            height: height,
+            speed: speed,
+        }
+    }
+    /// Whether a point is on the patch
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        rect_contains((self.x, self.y, self.width, self.height), x, y)
+    }
+}
+
+/// Distance a swarm is pushed out of another spawn point's safe zone each tick
+pub const SAFE_ZONE_PUSHBACK: f32 = 3.0;
+
+/// A place players can spawn
+/// A spawn point can have a safe zone around it, where only the swarms that spawned there
+/// can stay and fire: other swarms are pushed out and their bullets despawn
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct SpawnPoint {
+    /// X position
+    pub x: f32,
+    /// Y position
+    pub y: f32,
+    /// Radius of the safe zone, 0 if the spawn point has none
+    #[serde(default)]
+    pub safe_radius: f32,
+}
+
+/// Functions for SpawnPoint
+impl SpawnPoint {
+    /// Constructor
+    /// A spawn point without a safe zone
+    pub fn new(x: f32, y: f32) -> Self {
+        SpawnPoint {
+            x: x,
+            y: y,
+            safe_radius: 0.0,
+        }
+    }
+    /// Gives the spawn point a safe zone
+    pub fn with_safe_radius(mut self, safe_radius: f32) -> Self {
+        self.safe_radius = safe_radius;
+        self
+    }
+    /// Whether a point is inside the safe zone
+    pub fn protects(&self, x: f32, y: f32) -> bool {
+        (x - self.x).powi(2) + (y - self.y).powi(2) < self.safe_radius * self.safe_radius
+    }
+}
+
+/// What a zone is used for by the game modes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum ZoneKind {
+    /// A king of the hill control point
+    #[serde(rename = "control_point")]
+    CONTROL_POINT,
+    /// A race checkpoint, visited in the order the checkpoints are listed
+    #[serde(rename = "checkpoint")]
+    CHECKPOINT,
+}
+
+/// A circle the game modes give a meaning to
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Zone {
+    /// What the zone is used for
+    pub kind: ZoneKind,
+    /// X position of the center
+    pub x: f32,
+    /// Y position of the center
+    pub y: f32,
+    /// Radius of the zone
+    pub radius: f32,
+}
+
+/// A world layout, loaded from JSON so maps can be made without changing code
+/// Lists left out of the file are empty
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Map {
+    /// Name shown to players
+    #[serde(default)]
+    pub name: String,
+    /// The width of the world
+    pub width: f32,
+    /// The height of the world
+    pub height: f32,
+    /// Rectangles swarms and bullets can't pass through
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+    /// Rectangles that speed swarms up or slow them down
+    #[serde(default)]
+    pub terrain: Vec<Terrain>,
+    /// Where players spawn; anywhere inside the spawn margin if there are none
+    #[serde(default)]
+    pub spawns: Vec<SpawnPoint>,
+    /// Control points and checkpoints
+    #[serde(default)]
+    pub zones: Vec<Zone>,
+}
+
+/// Functions for Map
+impl Map {
+    /// Constructor
+    /// An empty map of the given size
+    pub fn new(width: f32, height: f32) -> Self {
+        Map {
+            name: String::new(),
+            width: width,
+            height: height,
+            obstacles: Vec::new(),
+            terrain: Vec::new(),
+            spawns: Vec::new(),
+            zones: Vec::new(),
+        }
+    }
+    /// Parses and validates a map from JSON text
+    pub fn from_json(text: &str) -> Result<Self, MapError> {
+        let map: Map = serde_json::from_str(text).map_err(MapError::PARSE)?;
+        map.validate()?;
+        Ok(map)
+    }
+    /// Parses and validates a map from the bytes of a JSON file
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MapError> {
+        let map: Map = serde_json::from_slice(bytes).map_err(MapError::PARSE)?;
+        map.validate()?;
+        Ok(map)
+    }
+    /// Reads, parses, and validates a map file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, MapError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|error| MapError::IO {
+            path: path.display().to_string(),
+            error: error,
+        })?;
+        Map::from_bytes(&bytes)
+    }
+    /// Checks every entry of the map
+    /// return: the first invalid entry, with where it is in the map
+    pub fn validate(&self) -> Result<(), MapError> {
+        if !(self.width.is_finite() && self.width > 0.0) {
+            return Err(invalid("width", "must be a positive number"));
+        }
+        if !(self.height.is_finite() && self.height > 0.0) {
+            return Err(invalid("height", "must be a positive number"));
+        }
+        // Without spawn points, players spawn anywhere inside the default margin
+        if self.spawns.is_empty() {
+            if let Err(error) = WorldConfig::new(self.width, self.height).validate() {
+                return Err(invalid("spawns", &error.to_string()));
+            }
+        }
+        for (index, obstacle) in self.obstacles.iter().enumerate() {
+            let rect = (obstacle.x, obstacle.y, obstacle.width, obstacle.height);
+            self.validate_rect(&format!("obstacles.{}", index), rect)?;
+        }
+        for (index, terrain) in self.terrain.iter().enumerate() {
+            let path = format!("terrain.{}", index);
+            let rect = (terrain.x, terrain.y, terrain.width, terrain.height);
+            self.validate_rect(&path, rect)?;
+            if !(terrain.speed.is_finite() && terrain.speed >= 0.0) {
+                return Err(invalid(
+                    &format!("{}.speed", path),
+                    "must be a number that isn't negative",
+                ));
+            }
+        }
+        for (index, spawn) in self.spawns.iter().enumerate() {
+            let path = format!("spawns.{}", index);
+            self.validate_point(&path, spawn.x, spawn.y)?;
+            if self.blocked(spawn.x, spawn.y) {
+                return Err(invalid(&path, "is inside an obstacle"));
+            }
+            if !(spawn.safe_radius.is_finite() && spawn.safe_radius >= 0.0) {
+                return Err(invalid(
+                    &format!("{}.safe_radius", path),
+                    "must be a number that isn't negative",
+                ));
+            }
+        }
+        for (index, zone) in self.zones.iter().enumerate() {
+            let path = format!("zones.{}", index);
+            self.validate_point(&path, zone.x, zone.y)?;
+            if !(zone.radius.is_finite() && zone.radius > 0.0) {
+                return Err(invalid(
+                    &format!("{}.radius", path),
+                    "must be a positive number",
+                ));
+            }
+        }
+        Ok(())
+    }
+    /// Checks that a rectangle has a positive size and lies inside the map
+    fn validate_rect(
+        &self,
+        path: &str,
+        (x, y, width, height): (f32, f32, f32, f32),
+    ) -> Result<(), MapError> {
+        self.validate_point(path, x, y)?;
+        if !(width.is_finite() && width > 0.0) {
+            return Err(invalid(
+                &format!("{}.width", path),
+                "must be a positive number",
+            ));
+        }
+        if !(height.is_finite() && height > 0.0) {
+            return Err(invalid(
+                &format!("{}.height", path),
+                "must be a positive number",
+            ));
+        }
+        if x + width > self.width || y + height > self.height {
+            return Err(invalid(path, "extends past the edge of the map"));
+        }
+        Ok(())
+    }
+    /// Checks that a point lies inside the map
+    fn validate_point(&self, path: &str, x: f32, y: f32) -> Result<(), MapError> {
+        if !(x.is_finite() && x >= 0.0 && x <= self.width) {
+            return Err(invalid(&format!("{}.x", path), "is outside the map"));
+        }
+        if !(y.is_finite() && y >= 0.0 && y <= self.height) {
+            return Err(invalid(&format!("{}.y", path), "is outside the map"));
+        }
+        Ok(())
+    }
+    /// Whether a point is inside an obstacle
+    pub fn blocked(&self, x: f32, y: f32) -> bool {
+        self.obstacles
+            .iter()
+            .any(|obstacle| obstacle.contains(x, y))
+    }
+    /// Whether no obstacle is in the way of the straight line between two points
+    pub fn line_of_sight(&self, from: (f32, f32), to: (f32, f32)) -> bool {
+        !self
+            .obstacles
+            .iter()
+            .any(|obstacle| obstacle.blocks(from, to))
+    }
+    /// The spawn point whose safe zone a point is in, unless it's the one given
+    /// home: index of the spawn point whose zone doesn't count, such as where a swarm spawned
+    pub fn safe_zone(&self, home: Option<usize>, x: f32, y: f32) -> Option<&SpawnPoint> {
+        self.spawns
+            .iter()
+            .enumerate()
+            .find(|&(index, spawn)| Some(index) != home && spawn.protects(x, y))
+            .map(|(_, spawn)| spawn)
+    }
+    /// Where a swarm centered at a point is pushed to this tick,
+    /// if it's in a safe zone other than its home's
+    /// Swarms are pushed straight away from the spawn point, and never past the zone's edge
+    pub fn pushback(&self, home: Option<usize>, x: f32, y: f32) -> Option<(f32, f32)> {
+        let spawn = match self.safe_zone(home, x, y) {
+            Some(spawn) => spawn,
+            None => return None,
+        };
+        let (dx, dy) = (x - spawn.x, y - spawn.y);
+        let distance = (dx * dx + dy * dy).sqrt();
+        // Swarms right on the spawn point are pushed along the x axis
+        let (dx, dy) = if distance > 0.0 {
+            (dx / distance, dy / distance)
+        } else {
+            (1.0, 0.0)
+        };
+        let step = SAFE_ZONE_PUSHBACK.min(spawn.safe_radius - distance);
+        Some((x + dx * step, y + dy * step))
+    }
+    /// Multiplier on the distance a swarm centered at a point moves
+    /// The first patch listed wins where patches overlap
+    pub fn speed_at(&self, x: f32, y: f32) -> f32 {
+        self.terrain
+            .iter()
+            .find(|terrain| terrain.contains(x, y))
+            .map_or(1.0, |terrain| terrain.speed)
+    }
+    /// The map's control points, for king of the hill
+    pub fn control_points(&self) -> Vec<ControlPoint> {
+        self.zones
+            .iter()
+            .filter(|zone| zone.kind == ZoneKind::CONTROL_POINT)
+            .map(|zone| ControlPoint::new(zone.x, zone.y, zone.radius))
+            .collect()
+    }
+    /// A race around the map's checkpoints, if it has any
+    pub fn race(&self, laps: u32) -> Option<Race> {
+        let checkpoints: Vec<Checkpoint> = self
+            .zones
+            .iter()
+            .filter(|zone| zone.kind == ZoneKind::CHECKPOINT)
+            .map(|zone| Checkpoint::new(zone.x, zone.y, zone.radius))
+            .collect();
+        if checkpoints.is_empty() {
+            None
+        } else {
+            Some(Race::new(checkpoints, laps))
+        }
+    }
+}
+
+/// An invalid entry error
+fn invalid(path: &str, reason: &str) -> MapError {
+    MapError::INVALID_ENTRY {
+        path: path.into(),
+        reason: reason.into(),
+    }
+}
+
+/// Whether a point is inside an (x, y, width, height) rectangle
+fn rect_contains((left, top, width, height): (f32, f32, f32, f32), x: f32, y: f32) -> bool {
+    x >= left && x < left + width && y >= top && y < top + height
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_maps() {
+        let mut map = Map::new(1000.0, 800.0);
+        map.obstacles
+            .push(Obstacle::new(400.0, 300.0, 200.0, 100.0));
+        map.terrain.push(Terrain::new(0.0, 0.0, 100.0, 100.0, 0.5));
+        map.spawns
+            .push(SpawnPoint::new(50.0, 50.0).with_safe_radius(30.0));
+        map.zones.push(Zone {
+            kind: ZoneKind::CHECKPOINT,
+            x: 800.0,
+            y: 400.0,
+            radius: 40.0,
+        });
+        assert!(map.validate().is_ok());
+        assert!(map.blocked(500.0, 350.0));
+        // Obstacles block sight through them, but not past their corners
+        assert!(!map.line_of_sight((300.0, 350.0), (700.0, 350.0)));
+        assert!(!map.line_of_sight((500.0, 200.0), (500.0, 500.0)));
+        assert!(!map.line_of_sight((350.0, 250.0), (650.0, 450.0)));
+        assert!(map.line_of_sight((300.0, 250.0), (700.0, 250.0)));
+        assert!(map.line_of_sight((300.0, 280.0), (450.0, 200.0)));
+        assert!(map.line_of_sight((300.0, 350.0), (390.0, 350.0)));
+        assert_eq!(map.speed_at(50.0, 50.0), 0.5);
+        assert_eq!(map.speed_at(500.0, 50.0), 1.0);
+        assert!(map.race(2).is_some());
+        assert!(map.control_points().is_empty());
+        // Only swarms that didn't spawn at the point are pushed out of its safe zone
+        assert!(map.safe_zone(Some(0), 60.0, 50.0).is_none());
+        assert_eq!(map.pushback(None, 60.0, 50.0), Some((63.0, 50.0)));
+        assert_eq!(map.pushback(None, 79.0, 50.0), Some((80.0, 50.0)));
+        assert_eq!(map.pushback(None, 90.0, 50.0), None);
+
+        map.obstacles.push(Obstacle::new(900.0, 0.0, 200.0, 10.0));
+        match map.validate() {
+            Err(MapError::INVALID_ENTRY { path, .. }) => assert_eq!(path, "obstacles.1"),
+            other => panic!("Expected an invalid entry, got {:?}", other),
+        }
+        map.obstacles.pop();
+        map.spawns.push(SpawnPoint::new(450.0, 350.0));
+        match map.validate() {
+            Err(MapError::INVALID_ENTRY { path, .. }) => assert_eq!(path, "spawns.1"),
+            other => panic!("Expected an invalid entry, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use map::{Map, Obstacle, SpawnPoint, Terrain};
+use rand::Rng;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use std::f32::consts::PI;
+use world::World;
+
+/// Candidates tried for each kind of rectangle before the generator settles for
+/// less coverage than it was asked for
+const MAX_ATTEMPTS: usize = 1000;
+
+/// How a generated map repeats itself, so no spawn point has an advantage
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum Symmetry {
+    /// The right half is the left half reflected across the vertical center line
+    #[serde(rename = "mirror")]
+    MIRROR,
+    /// The right half is the left half turned halfway around the center
+    #[serde(rename = "point")]
+    POINT,
+}
+
+/// Makes random maps from a seed
+/// The same parameters and seed always make the same map
+/// Fields left out when deserializing keep their defaults
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(default)]
+pub struct MapGenerator {
+    /// The width of the maps
+    pub width: f32,
+    /// The height of the maps
+    pub height: f32,
+    /// How the maps repeat themselves
+    pub symmetry: Symmetry,
+    /// Number of spawn points, rounded up to an even number so every spawn has a twin
+    pub spawns: usize,
+    /// Size of the ring of spawn points, as a fraction of the distance from the center to the edges
+    pub spawn_ring: f32,
+    /// Closest an obstacle gets to a spawn point
+    pub spawn_clearance: f32,
+    /// Fraction of the map covered by obstacles, at most
+    pub obstacle_density: f32,
+    /// Shortest and longest side of an obstacle
+    pub obstacle_size: (f32, f32),
+    /// Fraction of the map covered by terrain patches, at most
+    pub terrain_density: f32,
+    /// Shortest and longest side of a terrain patch
+    pub terrain_size: (f32, f32),
+    /// Slowest and fastest speed of a terrain patch
+    pub terrain_speed: (f32, f32),
+}
+
+/// Functions for MapGenerator
+impl MapGenerator {
+    /// Constructor
+    /// Mirrored maps with 8 spawns, with obstacles over 8% of the map and terrain over 10%
+    pub fn new(width: f32, height: f32) -> Self {
+        MapGenerator {
+            width: width,
+            height: height,
+            symmetry: Symmetry::MIRROR,
+            spawns: 8,
+            spawn_ring: 0.75,
+            spawn_clearance: 80.0,
+            obstacle_density: 0.08,
+            obstacle_size: (40.0, 160.0),
+            terrain_density: 0.1,
+            terrain_size: (80.0, 240.0),
+            terrain_speed: (0.5, 1.5),
+        }
+    }
+    /// Sets how the maps repeat themselves
+    pub fn with_symmetry(mut self, symmetry: Symmetry) -> Self {
+        self.symmetry = symmetry;
+        self
+    }
+    /// Sets the number of spawn points
+    pub fn with_spawns(mut self, spawns: usize) -> Self {
+        self.spawns = spawns;
+        self
+    }
+    /// Sets the fraction of the map covered by obstacles
+    pub fn with_obstacle_density(mut self, density: f32) -> Self {
+        self.obstacle_density = density;
+        self
+    }
+    /// Sets the fraction of the map covered by terrain patches
+    pub fn with_terrain_density(mut self, density: f32) -> Self {
+        self.terrain_density = density;
+        self
+    }
+    /// Makes a map
+    /// Everything is placed in the left half, then copied to the right half by the symmetry
+    pub fn generate(&self, seed: u64) -> Map {
+        let mut rng = World::seeded_rng(seed);
+        let mut map = Map::new(self.width, self.height);
+        map.name = format!("Generated {}", seed);
+        map.spawns = self.spawn_ring();
+        let half_area = self.width * self.height / 2.0;
+
+        // Obstacles stay clear of each other and of the spawn points
+        let mut covered = 0.0;
+        for _ in 0..MAX_ATTEMPTS {
+            if covered >= self.obstacle_density * half_area {
+                break;
+            }
+            let (x, y, width, height) = match self.random_rect(&mut rng, self.obstacle_size) {
+                Some(rect) => rect,
+                None => break,
+            };
+            let overlaps = map.obstacles.iter().any(|other| {
+                overlap(
+                    (x, y, width, height),
+                    (other.x, other.y, other.width, other.height),
+                )
+            });
+            let near_spawn = map.spawns.iter().any(|spawn| {
+                distance_to_rect((spawn.x, spawn.y), (x, y, width, height)) < self.spawn_clearance
+            });
+            if overlaps || near_spawn {
+                continue;
+            }
+            let (image_x, image_y) = self.image(x, y, width, height);
+            map.obstacles.push(Obstacle::new(x, y, width, height));
+            map.obstacles
+                .push(Obstacle::new(image_x, image_y, width, height));
+            covered += width * height;
+        }
+
+        // Terrain patches only stay clear of each other
+        let mut covered = 0.0;
+        for _ in 0..MAX_ATTEMPTS {
+            if covered >= self.terrain_density * half_area {
+                break;
+            }
+            let (x, y, width, height) = match self.random_rect(&mut rng, self.terrain_size) {
+                Some(rect) => rect,
+                None => break,
+            };
+            let overlaps = map.terrain.iter().any(|other| {
+                overlap(
+                    (x, y, width, height),
+                    (other.x, other.y, other.width, other.height),
+                )
+            });
+            if overlaps {
+                continue;
+            }
+            let speed = range(&mut rng, self.terrain_speed.0, self.terrain_speed.1);
+            let (image_x, image_y) = self.image(x, y, width, height);
+            map.terrain.push(Terrain::new(x, y, width, height, speed));
+            map.terrain
+                .push(Terrain::new(image_x, image_y, width, height, speed));
+            covered += width * height;
+        }
+        map
+    }
+    /// Spawn points evenly spaced around an ellipse at the center of the map
+    /// None are on the center line, and each one's image is another spawn point
+    fn spawn_ring(&self) -> Vec<SpawnPoint> {
+        let count = (self.spawns + self.spawns % 2).max(2);
+        let (center_x, center_y) = (self.width / 2.0, self.height / 2.0);
+        (0..count)
+            .map(|index| {
+                let angle = PI / 2.0 + (index as f32 + 0.5) * 2.0 * PI / count as f32;
+                SpawnPoint::new(
+                    center_x + self.spawn_ring * center_x * angle.cos(),
+                    center_y - self.spawn_ring * center_y * angle.sin(),
+                )
+            })
+            .collect()
+    }
+    /// A random (x, y, width, height) rectangle inside the left half of the map
+    /// return: None if rectangles of the smallest size don't fit
+    fn random_rect<R: Rng>(
+        &self,
+        rng: &mut R,
+        (min, max): (f32, f32),
+    ) -> Option<(f32, f32, f32, f32)> {
+        let width = range(rng, min, max);
+        let height = range(rng, min, max);
+        if !(width > 0.0 && height > 0.0) || width >= self.width / 2.0 || height >= self.height {
+            return None;
+        }
+        let x = range(rng, 0.0, self.width / 2.0 - width);
+        let y = range(rng, 0.0, self.height - height);
+        Some((x, y, width, height))
+    }
+    /// Where the symmetry copies a rectangle in the left half to
+    fn image(&self, x: f32, y: f32, width: f32, height: f32) -> (f32, f32) {
+        match self.symmetry {
+            Symmetry::MIRROR => (self.width - x - width, y),
+            Symmetry::POINT => (self.width - x - width, self.height - y - height),
+        }
+    }
+}
+
+/// Default maps are 2000x2000
+impl Default for MapGenerator {
+    fn default() -> Self {
+        MapGenerator::new(2000.0, 2000.0)
+    }
+}
+
+/// A random number from low up to high, or low if the range is empty
+fn range<R: Rng>(rng: &mut R, low: f32, high: f32) -> f32 {
+    if high > low {
+        rng.gen_range(low, high)
+    } else {
+        low
+    }
+}
+
+/// Whether two (x, y, width, height) rectangles overlap
+fn overlap(a: (f32, f32, f32, f32), b: (f32, f32, f32, f32)) -> bool {
+    a.0 < b.0 + b.2 && b.0 < a.0 + a.2 && a.1 < b.1 + b.3 && b.1 < a.1 + a.3
+}
+
+/// Distance from a point to the closest point of an (x, y, width, height) rectangle
+fn distance_to_rect((x, y): (f32, f32), (left, top, width, height): (f32, f32, f32, f32)) -> f32 {
+    let dx = (left - x).max(x - (left + width)).max(0.0);
+    let dy = (top - y).max(y - (top + height)).max(0.0);
+    (dx * dx + dy * dy).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_symmetric_maps() {
+        for &symmetry in [Symmetry::MIRROR, Symmetry::POINT].iter() {
+            let generator = MapGenerator::new(1600.0, 1200.0)
+                .with_symmetry(symmetry)
+                .with_spawns(5);
+            let map = generator.generate(7);
+            assert_eq!(map, generator.generate(7));
+            assert_ne!(map, generator.generate(8));
+            assert!(map.validate().is_ok());
+            assert_eq!(map.spawns.len(), 6);
+            assert!(!map.obstacles.is_empty());
+            // Every obstacle and spawn point has a twin
+            for obstacle in map.obstacles.iter() {
+                let (x, y) =
+                    generator.image(obstacle.x, obstacle.y, obstacle.width, obstacle.height);
+                assert!(map
+                    .obstacles
+                    .iter()
+                    .any(|other| { (other.x - x).abs() < 0.01 && (other.y - y).abs() < 0.01 }));
+            }
+            for spawn in map.spawns.iter() {
+                let (x, y) = generator.image(spawn.x, spawn.y, 0.0, 0.0);
+                assert!(map
+                    .spawns
+                    .iter()
+                    .any(|other| { (other.x - x).abs() < 0.01 && (other.y - y).abs() < 0.01 }));
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use std::collections::{BTreeMap, HashMap};
+
+/// English text of every message, by ID
+/// Parameters are written as {name}, and are replaced with the message's values
+pub static ENGLISH: &'static [(&'static str, &'static str)] = &[
+    ("empty_command", "Empty command."),
+    ("unknown_command", "Command not recognized: {command}"),
+    ("unknown_formation", "Invalid formation name: {formation}"),
+    ("unknown_condition", "Invalid condition name: {condition}"),
+    ("missing_parameter", "No parameters found for {command}."),
+    ("too_many_parameters", "Too many parameters for {command}, which takes {max}."),
+    (
+        "invalid_parameter",
+        "Invalid parameter for {command}: {parameter}",
+    ),
+    (
+        "out_of_range",
+        "Parameter for {command} should range from {min} to {max}.",
+    ),
+    (
+        "too_long",
+        "Program is too long: {commands} commands, but at most {max} are allowed.",
+    ),
+    ("unmatched_block", "{command} has nothing to match it."),
+    ("undefined_label", "No LABEL {label} to jump to."),
+    ("duplicate_label", "LABEL {label} is defined more than once."),
+    ("undefined_sub", "No SUB {name} to call."),
+    ("duplicate_sub", "SUB {name} is defined more than once."),
+    ("duplicate_handler", "{command} is defined more than once."),
+    ("misplaced", "{command} can't be used here."),
+    ("invalid_script", "Invalid script: {error}"),
+];
+
+/// A message identified by ID, so clients can show it in their own language
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Message {
+    /// Which message it is, one of the IDs in ENGLISH
+    pub id: String,
+    /// The values to put in the message, by parameter name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+}
+
+/// Functions for Message
+impl Message {
+    /// Constructor
+    pub fn new(id: &str) -> Self {
+        Message {
+            id: id.into(),
+            params: BTreeMap::new(),
+        }
+    }
+    /// Sets the value of a parameter
+    pub fn with_param<T: ToString>(mut self, name: &str, value: T) -> Self {
+        self.params.insert(name.into(), value.to_string());
+        self
+    }
+    /// Writes the message in English
+    pub fn english(&self) -> String {
+        match ENGLISH.iter().find(|&&(id, _)| id == self.id) {
+            Some(&(_, template)) => fill(template, &self.params),
+            None => self.id.clone(),
+        }
+    }
+}
+
+/// Text of messages in one language, by ID
+/// Messages without a translation are written in English
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Translations {
+    /// Text of each translated message, with {name} for each parameter
+    templates: HashMap<String, String>,
+}
+
+/// Functions for Translations
+impl Translations {
+    /// Constructor
+    /// templates: (message ID, text) for each translated message
+    pub fn new<I, S>(templates: I) -> Self
+    where
+        I: IntoIterator<Item = (S, S)>,
+        S: Into<String>,
+    {
+        Translations {
+            templates: templates
+                .into_iter()
+                .map(|(id, template)| (id.into(), template.into()))
+                .collect(),
+        }
+    }
+    /// IDs of the messages that have no translation
+    pub fn missing(&self) -> Vec<&'static str> {
+        ENGLISH
+            .iter()
+            .map(|&(id, _)| id)
+            .filter(|id| !self.templates.contains_key(*id))
+            .collect()
+    }
+    /// Writes a message in this language
+    pub fn format(&self, message: &Message) -> String {
+        match self.templates.get(&message.id) {
+            Some(template) => fill(template, &message.params),
+            None => message.english(),
+        }
+    }
+}
+
+/// Replaces each {name} in a template with the value of the parameter
+/// Names without a value are left as they are
+fn fill(template: &str, params: &BTreeMap<String, String>) -> String {
+    let mut text = template.to_string();
+    for (name, value) in params.iter() {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_messages() {
+        let message = Message::new("out_of_range")
+            .with_param("command", "TURN")
+            .with_param("min", "-30.0")
+            .with_param("max", "30.0");
+        assert_eq!(
+            message.english(),
+            "Parameter for TURN should range from -30.0 to 30.0."
+        );
+        let french = Translations::new(vec![(
+            "out_of_range",
+            "Le paramètre de {command} doit être entre {min} et {max}.",
+        )]);
+        assert_eq!(
+            french.format(&message),
+            "Le paramètre de TURN doit être entre -30.0 et 30.0."
+        );
+        // Untranslated messages fall back to English
+        let empty = Message::new("empty_command");
+        assert_eq!(french.format(&empty), "Empty command.");
+        assert!(french.missing().contains(&"empty_command"));
+        assert!(!french.missing().contains(&"out_of_range"));
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use entity::Swarm;
+use game_mode::{GameMode, Team};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use slab::Slab;
+use std::collections::BTreeMap;
+
+/// Columns and rows of the minimap grid
+pub const MINIMAP_SIZE: u32 = 32;
+/// Ticks between minimap updates
+pub const MINIMAP_INTERVAL: u64 = 10;
+
+/// Where one team's members are
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct MinimapLayer {
+    /// The team, or None for players that aren't on one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<Team>,
+    /// (cell index, members) for each cell with members in it, in cell order
+    /// Cells are numbered by row, from the top left corner
+    pub cells: Vec<(u32, u32)>,
+}
+
+/// A low resolution count of swarm members over the whole world, for drawing a minimap
+/// without sending every swarm outside the client's viewport
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Minimap {
+    /// The tick the counts were taken on
+    pub tick: u64,
+    /// Number of columns in the grid
+    pub columns: u32,
+    /// Number of rows in the grid
+    pub rows: u32,
+    /// Counts for each team, with players on no team last
+    pub layers: Vec<MinimapLayer>,
+}
+
+/// Functions for Minimap
+impl Minimap {
+    /// Counts the members in each cell of a MINIMAP_SIZE by MINIMAP_SIZE grid over the world
+    /// Members are counted in the layer of their player's team
+    pub fn compute(
+        swarms: &Slab<Swarm>,
+        mode: &GameMode,
+        width: f32,
+        height: f32,
+        tick: u64,
+    ) -> Self {
+        let (columns, rows) = (MINIMAP_SIZE, MINIMAP_SIZE);
+        // Teams sort before None, so players on no team come last
+        let mut counts: BTreeMap<(bool, Option<Team>), BTreeMap<u32, u32>> = BTreeMap::new();
+        for (id, swarm) in swarms.iter() {
+            if swarm.members.is_empty() {
+                continue;
+            }
+            let team = mode.team(*id);
+            let cells = counts
+                .entry((team.is_none(), team))
+                .or_insert_with(BTreeMap::new);
+            for member in swarm.members.iter() {
+                let column = cell(swarm.x + member.x, width, columns);
+                let row = cell(swarm.y + member.y, height, rows);
+                *cells.entry(row * columns + column).or_insert(0) += 1;
+            }
+        }
+        Minimap {
+            tick: tick,
+            columns: columns,
+            rows: rows,
+            layers: counts
+                .into_iter()
+                .map(|((_, team), cells)| MinimapLayer {
+                    team: team,
+                    cells: cells.into_iter().collect(),
+                })
+                .collect(),
+        }
+    }
+    /// Whether there's nothing to draw
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+    /// Members of a team in a cell
+    pub fn count(&self, team: Option<Team>, column: u32, row: u32) -> u32 {
+        let index = row * self.columns + column;
+        self.layers
+            .iter()
+            .filter(|layer| layer.team == team)
+            .flat_map(|layer| layer.cells.iter())
+            .find(|&&(cell, _)| cell == index)
+            .map_or(0, |&(_, members)| members)
+    }
+}
+
+/// The cell a coordinate falls in, along one axis
+/// Coordinates outside the world go in the nearest edge cell
+fn cell(coordinate: f32, length: f32, cells: u32) -> u32 {
+    let cell = (coordinate / length * cells as f32).floor();
+    if cell.is_finite() && cell > 0.0 {
+        (cell as u32).min(cells - 1)
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game_mode::KingOfTheHill;
+
+    #[test]
+    fn count_members_by_team() {
+        let mut swarms = Slab::new();
+        swarms.insert(1, Swarm::new(10.0, 10.0, 1));
+        swarms.insert(2, Swarm::new(990.0, 500.0, 2));
+        let minimap = Minimap::compute(&swarms, &GameMode::FREE_FOR_ALL, 1000.0, 1000.0, 20);
+        assert_eq!(minimap.layers.len(), 1);
+        let member = &swarms[&1].members[0];
+        let (column, row) = (
+            cell(10.0 + member.x, 1000.0, MINIMAP_SIZE),
+            cell(10.0 + member.y, 1000.0, MINIMAP_SIZE),
+        );
+        assert_eq!(minimap.count(None, column, row), 1);
+
+        let mut mode = GameMode::KING_OF_THE_HILL(KingOfTheHill::new(1000.0, 1000.0));
+        mode.add_player(1);
+        mode.add_player(2);
+        let minimap = Minimap::compute(&swarms, &mode, 1000.0, 1000.0, 20);
+        let teams: Vec<Option<Team>> = minimap.layers.iter().map(|layer| layer.team).collect();
+        assert_eq!(teams, vec![mode.team(1), mode.team(2)]);
+        assert_eq!(minimap.count(mode.team(1), column, row), 1);
+        assert_eq!(minimap.count(None, column, row), 0);
+    }
+}
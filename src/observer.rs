@@ -0,0 +1,144 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use event::WorldEvent;
+use std::fmt;
+
+/// Receives a world's events as they happen, for embedders that would otherwise
+/// poll drain_events or compare states, like stats collectors and webhooks
+/// Unlike plugins, observers can't change the world, and are owned by it
+pub trait WorldObserver: Send + Sync {
+    /// Handles an event, in the order events happened
+    /// tick: the world's tick when the event was seen
+    fn on_event(&mut self, tick: u64, event: &WorldEvent);
+}
+
+/// Closures can observe a world
+impl<F: FnMut(u64, &WorldEvent) + Send + Sync> WorldObserver for F {
+    /// Calls the closure
+    fn on_event(&mut self, tick: u64, event: &WorldEvent) {
+        self(tick, event)
+    }
+}
+
+/// The observers subscribed to a world
+/// Clones of a world start without observers, so rewinds, previews and
+/// replays of it don't report the same events twice
+#[derive(Default)]
+pub struct Observers {
+    /// Each observer, in the order they subscribed
+    observers: Vec<Box<WorldObserver>>,
+    /// Number of the world's pending events already sent
+    sent: usize,
+}
+
+/// Functions for Observers
+impl Observers {
+    /// Adds an observer, notified after the ones already added
+    pub fn subscribe(&mut self, observer: Box<WorldObserver>) {
+        self.observers.push(observer);
+    }
+    /// Whether there are no observers
+    pub fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+    /// Sends every observer the events it hasn't seen
+    /// events: the world's pending events, which only grow until they're drained
+    pub fn notify(&mut self, tick: u64, events: &[WorldEvent]) {
+        // Events cleared without draining can't be told apart from new ones
+        let start = self.sent.min(events.len());
+        for event in events[start..].iter() {
+            for observer in self.observers.iter_mut() {
+                observer.on_event(tick, event);
+            }
+        }
+        self.sent = events.len();
+    }
+    /// Notes that the world's pending events were drained
+    pub fn drained(&mut self) {
+        self.sent = 0;
+    }
+    /// Notes that the world's pending events were already sent,
+    /// such as when a rewound world gets back events from before
+    pub fn caught_up(&mut self, pending: usize) {
+        self.sent = pending;
+    }
+}
+
+/// Observers aren't cloned
+impl Clone for Observers {
+    fn clone(&self) -> Self {
+        Observers::default()
+    }
+}
+
+/// Observers are listed by count, since they needn't be Debug
+impl fmt::Debug for Observers {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "Observers({})", self.observers.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use swarm_language::SwarmCommand;
+    use world::World;
+
+    #[test]
+    fn observe_world_events() {
+        let seen: Arc<Mutex<Vec<(u64, WorldEvent)>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut world = World::new(1000.0, 1000.0);
+        let log = seen.clone();
+        world.subscribe(Box::new(move |tick: u64, event: &WorldEvent| {
+            log.lock().unwrap().push((tick, event.clone()));
+        }));
+        world.add_player(1).unwrap();
+        world.swarms.get_mut(&1).unwrap().program.commands = vec![SwarmCommand::FIRE];
+        world.update();
+        // Clones don't report events to the original's observers
+        world.clone().update();
+        let events = world.drain_events();
+        let seen = seen.lock().unwrap().clone();
+        assert_eq!(
+            seen.iter()
+                .map(|&(_, ref event)| event.clone())
+                .collect::<Vec<_>>(),
+            events
+        );
+        assert_eq!(seen[0], (0, WorldEvent::JOIN(1)));
+        assert_eq!(seen.last().unwrap().0, 1);
+    }
+
+    #[test]
+    fn observers_survive_rewind() {
+        let seen: Arc<Mutex<Vec<WorldEvent>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut world = World::new(1000.0, 1000.0).with_practice();
+        let log = seen.clone();
+        world.subscribe(Box::new(move |_: u64, event: &WorldEvent| {
+            log.lock().unwrap().push(event.clone());
+        }));
+        world.add_player(1).unwrap();
+        world.update();
+        world.update();
+        assert_eq!(world.rewind(1), 1);
+        world.drain_events();
+        let before = seen.lock().unwrap().len();
+        world.add_player(2).unwrap();
+        assert_eq!(seen.lock().unwrap().len(), before + 1);
+        assert_eq!(seen.lock().unwrap().last(), Some(&WorldEvent::JOIN(2)));
+    }
+}
@@ -0,0 +1,169 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use rand::Rng;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+
+/// An RGB color
+pub type Color = (u8, u8, u8);
+
+/// Ten colors that are easy to tell apart with typical color vision
+static DISTINCT: &'static [Color] = &[
+    (31, 119, 180),
+    (255, 127, 14),
+    (44, 160, 44),
+    (214, 39, 40),
+    (148, 103, 189),
+    (140, 86, 75),
+    (227, 119, 194),
+    (127, 127, 127),
+    (188, 189, 34),
+    (23, 190, 207),
+];
+/// The Okabe-Ito colors, which stay distinct with deuteranopia
+/// White stands in for black, which disappears on the dark background
+static DEUTERANOPIA: &'static [Color] = &[
+    (230, 159, 0),
+    (86, 180, 233),
+    (0, 158, 115),
+    (240, 228, 66),
+    (0, 114, 178),
+    (213, 94, 0),
+    (204, 121, 167),
+    (255, 255, 255),
+];
+/// Colors that differ in brightness as well as hue, so they stay distinct with protanopia,
+/// where reds look dark
+static PROTANOPIA: &'static [Color] = &[
+    (100, 143, 255),
+    (255, 176, 0),
+    (220, 38, 127),
+    (255, 255, 255),
+    (120, 94, 240),
+    (254, 97, 0),
+    (0, 158, 115),
+    (240, 228, 66),
+];
+
+/// Where swarms' colors come from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum Palette {
+    /// Any color, chosen at random
+    #[serde(rename = "random")]
+    RANDOM,
+    /// Colors that are easy to tell apart with typical color vision
+    #[serde(rename = "distinct")]
+    DISTINCT,
+    /// Colors that stay distinct with deuteranopia
+    #[serde(rename = "deuteranopia")]
+    DEUTERANOPIA,
+    /// Colors that stay distinct with protanopia
+    #[serde(rename = "protanopia")]
+    PROTANOPIA,
+}
+
+/// Colors are random unless a palette is chosen
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::RANDOM
+    }
+}
+
+/// How a client should redraw one color of the world's palette
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct ColorMapping {
+    /// Color as sent by the server
+    pub from: Color,
+    /// Color to draw instead
+    pub to: Color,
+}
+
+/// Functions for Palette
+impl Palette {
+    /// The palette's colors, in the order they're given out, or none for random colors
+    pub fn colors(&self) -> &'static [Color] {
+        match *self {
+            Palette::RANDOM => &[],
+            Palette::DISTINCT => DISTINCT,
+            Palette::DEUTERANOPIA => DEUTERANOPIA,
+            Palette::PROTANOPIA => PROTANOPIA,
+        }
+    }
+    /// Chooses a color for a new swarm
+    /// The color used by the fewest swarms is chosen, earliest in the palette on a tie
+    /// used: colors of the swarms already in the world
+    pub fn pick<R: Rng>(&self, used: &[Color], rng: &mut R) -> Color {
+        let colors = self.colors();
+        if colors.is_empty() {
+            return rng.gen();
+        }
+        let mut best = colors[0];
+        let mut fewest = usize::max_value();
+        for &color in colors.iter() {
+            let count = used.iter().filter(|&&other| other == color).count();
+            if count < fewest {
+                best = color;
+                fewest = count;
+            }
+        }
+        best
+    }
+    /// How to redraw this palette's colors in another palette
+    /// Colors are matched by position, wrapping around the shorter palette
+    /// Random colors can't be remapped, so they give no mappings
+    pub fn remap(&self, to: Palette) -> Vec<ColorMapping> {
+        let (from, to) = (self.colors(), to.colors());
+        if to.is_empty() {
+            return Vec::new();
+        }
+        from.iter()
+            .enumerate()
+            .map(|(index, &color)| ColorMapping {
+                from: color,
+                to: to[index % to.len()],
+            })
+            .filter(|mapping| mapping.from != mapping.to)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::XorShiftRng;
+
+    #[test]
+    fn pick_and_remap() {
+        let mut rng = XorShiftRng::new_unseeded();
+        let palette = Palette::DEUTERANOPIA;
+        let mut used = Vec::new();
+        for _ in 0..palette.colors().len() {
+            let color = palette.pick(&used, &mut rng);
+            assert!(!used.contains(&color));
+            used.push(color);
+        }
+        // Once every color is used, colors are shared evenly
+        assert_eq!(palette.pick(&used, &mut rng), palette.colors()[0]);
+
+        let remap = Palette::DISTINCT.remap(Palette::PROTANOPIA);
+        assert_eq!(remap.len(), Palette::DISTINCT.colors().len());
+        assert_eq!(remap[8].to, PROTANOPIA[0]);
+        assert!(Palette::RANDOM.remap(Palette::PROTANOPIA).is_empty());
+        assert!(Palette::PROTANOPIA.remap(Palette::PROTANOPIA).is_empty());
+    }
+}
@@ -0,0 +1,303 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use entity::{is_npc, Swarm};
+use event::WorldEvent;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use slab::Slab;
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "server")]
+use std::sync::Arc;
+use std::sync::RwLock;
+#[cfg(feature = "server")]
+use world_manager::{ServerEvent, ServerListener};
+
+/// Something a swarm deals damage with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum Weapon {
+    /// Bullets fired by the swarm's members
+    #[serde(rename = "bullet")]
+    BULLET,
+}
+
+/// Swarms fire bullets unless given another weapon
+impl Default for Weapon {
+    fn default() -> Self {
+        Weapon::BULLET
+    }
+}
+
+/// What a player did during a match
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct PlayerStats {
+    /// Bullets fired
+    pub shots: u64,
+    /// Bullets that hit another swarm
+    pub hits: u64,
+    /// Fraction of shots that hit, filled in when the stats are summarized
+    pub accuracy: f32,
+    /// Members of other swarms destroyed
+    pub kills: u64,
+    /// Members lost to other swarms
+    pub deaths: u64,
+    /// Damage dealt with each weapon
+    pub damage: BTreeMap<Weapon, u64>,
+    /// Damage taken
+    pub damage_taken: u64,
+    /// Distance the swarm moved on its own, in world units
+    pub distance: f32,
+    /// Ticks the player had at least one member
+    pub ticks_alive: u64,
+}
+
+/// Functions for PlayerStats
+impl PlayerStats {
+    /// Constructor
+    pub fn new() -> Self {
+        PlayerStats::default()
+    }
+    /// Damage dealt with every weapon
+    pub fn total_damage(&self) -> u64 {
+        self.damage.values().sum()
+    }
+    /// Recomputes the accuracy from the shots and hits
+    pub fn update_accuracy(&mut self) {
+        self.accuracy = if self.shots == 0 {
+            0.0
+        } else {
+            self.hits as f32 / self.shots as f32
+        };
+    }
+    /// Adds another set of stats to these, as when keeping totals across matches
+    pub fn merge(&mut self, other: &PlayerStats) {
+        self.shots += other.shots;
+        self.hits += other.hits;
+        self.kills += other.kills;
+        self.deaths += other.deaths;
+        for (weapon, damage) in other.damage.iter() {
+            *self.damage.entry(*weapon).or_insert(0) += damage;
+        }
+        self.damage_taken += other.damage_taken;
+        self.distance += other.distance;
+        self.ticks_alive += other.ticks_alive;
+        self.update_accuracy();
+    }
+}
+
+/// Collects every player's stats for the current match
+/// Computer-controlled swarms aren't tracked
+#[derive(Clone, Debug, Default)]
+pub struct StatsCollector {
+    /// Stats of each player that played in the match
+    players: HashMap<usize, PlayerStats>,
+}
+
+/// Functions for StatsCollector
+impl StatsCollector {
+    /// Constructor
+    pub fn new() -> Self {
+        StatsCollector::default()
+    }
+    /// A player's stats, if they're tracked
+    fn player(&mut self, player_id: usize) -> Option<&mut PlayerStats> {
+        if is_npc(player_id) {
+            return None;
+        }
+        Some(
+            self.players
+                .entry(player_id)
+                .or_insert_with(PlayerStats::new),
+        )
+    }
+    /// Adds up a tick's events
+    pub fn record(&mut self, events: &[WorldEvent]) {
+        for event in events {
+            match *event {
+                WorldEvent::FIRED { player, bullets } => {
+                    if let Some(stats) = self.player(player) {
+                        stats.shots += bullets as u64;
+                    }
+                }
+                WorldEvent::HIT {
+                    attacker,
+                    victim,
+                    weapon,
+                    damage,
+                    ..
+                } => {
+                    if let Some(stats) = self.player(attacker) {
+                        stats.hits += 1;
+                        *stats.damage.entry(weapon).or_insert(0) += damage as u64;
+                    }
+                    if let Some(stats) = self.player(victim) {
+                        stats.damage_taken += damage as u64;
+                    }
+                }
+                WorldEvent::KILL { attacker, victim } => {
+                    if let Some(stats) = self.player(attacker) {
+                        stats.kills += 1;
+                    }
+                    if let Some(stats) = self.player(victim) {
+                        stats.deaths += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    /// Adds distance a player's swarm moved on its own
+    pub fn travel(&mut self, player_id: usize, distance: f32) {
+        if let Some(stats) = self.player(player_id) {
+            stats.distance += distance;
+        }
+    }
+    /// Counts a tick played, for every player with at least one member
+    pub fn record_tick(&mut self, swarms: &Slab<Swarm>) {
+        for (id, swarm) in swarms.iter() {
+            if swarm.members.is_empty() {
+                continue;
+            }
+            if let Some(stats) = self.player(*id) {
+                stats.ticks_alive += 1;
+            }
+        }
+    }
+    /// Every player's stats, by player ID, with accuracy filled in
+    pub fn summary(&self) -> BTreeMap<usize, PlayerStats> {
+        self.players
+            .iter()
+            .map(|(id, stats)| {
+                let mut stats = stats.clone();
+                stats.update_accuracy();
+                (*id, stats)
+            })
+            .collect()
+    }
+    /// Forgets every player's stats, for a new match
+    pub fn clear(&mut self) {
+        self.players.clear();
+    }
+}
+
+/// Keeps players' stats across matches
+/// Shared between room update threads, so implementations handle their own locking
+pub trait StatsStore: Send + Sync {
+    /// The player's totals, if they've finished a match
+    fn totals(&self, player_id: usize) -> Option<PlayerStats>;
+    /// Adds a match's stats to the player's totals
+    fn add(&self, player_id: usize, stats: &PlayerStats);
+}
+
+/// Totals kept in memory, lost when the server stops
+#[derive(Debug, Default)]
+pub struct MemoryPlayerStats {
+    /// Totals of each player
+    totals: RwLock<HashMap<usize, PlayerStats>>,
+}
+
+/// Functions for MemoryPlayerStats
+impl MemoryPlayerStats {
+    /// Constructor
+    pub fn new() -> Self {
+        MemoryPlayerStats::default()
+    }
+}
+
+/// Keeps totals in a map, behind a lock
+impl StatsStore for MemoryPlayerStats {
+    fn totals(&self, player_id: usize) -> Option<PlayerStats> {
+        match self.totals.read() {
+            Ok(totals) => totals.get(&player_id).cloned(),
+            Err(_) => None,
+        }
+    }
+    fn add(&self, player_id: usize, stats: &PlayerStats) {
+        match self.totals.write() {
+            Ok(mut totals) => totals
+                .entry(player_id)
+                .or_insert_with(PlayerStats::new)
+                .merge(stats),
+            Err(error) => error!("Error getting write lock on player stats: {}", error),
+        }
+    }
+}
+
+/// Saves the stats from every finished match
+#[cfg(feature = "server")]
+pub struct StatsListener {
+    /// Where totals are kept
+    pub store: Arc<StatsStore>,
+}
+
+/// Adds each player's stats to their totals when a match ends
+#[cfg(feature = "server")]
+impl ServerListener for StatsListener {
+    fn on_event(&self, event: &ServerEvent) {
+        if let ServerEvent::RESULTS { ref results, .. } = *event {
+            for (player_id, stats) in results.stats.iter() {
+                self.store.add(*player_id, stats);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect_stats() {
+        let mut collector = StatsCollector::new();
+        collector.record(&[
+            WorldEvent::FIRED {
+                player: 1,
+                bullets: 4,
+            },
+            WorldEvent::HIT {
+                attacker: 1,
+                victim: 2,
+                weapon: Weapon::BULLET,
+                damage: 5,
+                x: 0.0,
+                y: 0.0,
+            },
+            WorldEvent::KILL {
+                attacker: 1,
+                victim: 2,
+            },
+        ]);
+        collector.travel(1, 12.5);
+        let mut swarms = Slab::new();
+        swarms.insert(1, Swarm::new(0.0, 0.0, 1));
+        swarms.insert(2, Swarm::new(0.0, 0.0, 0));
+        collector.record_tick(&swarms);
+        let summary = collector.summary();
+        assert_eq!(summary[&1].accuracy, 0.25);
+        assert_eq!(summary[&1].damage[&Weapon::BULLET], 5);
+        assert_eq!(summary[&1].ticks_alive, 1);
+        assert_eq!(summary[&2].damage_taken, 5);
+        assert_eq!(summary[&2].deaths, 1);
+        assert_eq!(summary[&2].ticks_alive, 0);
+
+        let store = MemoryPlayerStats::new();
+        store.add(1, &summary[&1]);
+        store.add(1, &summary[&1]);
+        assert_eq!(store.totals(1).unwrap().total_damage(), 10);
+        assert_eq!(store.totals(2), None);
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate serde_json;
+
+use event::WorldEvent;
+use serde_json::Value;
+use std::fmt::Debug;
+use world::World;
+
+/// Extends a world without changing the crate, for custom scoring, logging, and the like
+/// Hooks are called from the world's update thread while it's locked,
+/// so implementations should return quickly
+/// Plugins are shared by clones of the world, so they keep their state behind locks
+pub trait WorldPlugin: Send + Sync + Debug {
+    /// Name clients use to send the plugin messages
+    fn name(&self) -> &str;
+    /// Called at the start of each tick, before any swarm moves
+    fn on_tick_start(&self, _world: &mut World) {}
+    /// Called at the end of each tick, after every event of the tick was handled
+    fn on_tick_end(&self, _world: &mut World) {}
+    /// Handles an event raised during a tick, in the order they happened
+    /// Joins and leaves outside a tick have their own hooks
+    fn on_event(&self, _world: &World, _event: &WorldEvent) {}
+    /// Called after a player joins
+    fn on_player_join(&self, _world: &mut World, _player: usize) {}
+    /// Called after a player leaves
+    fn on_player_leave(&self, _world: &mut World, _player: usize) {}
+    /// Handles a message a client sent to this plugin
+    /// return: data to send back to the client, if any
+    fn on_message(&self, _world: &mut World, _player: usize, _data: &Value) -> Option<Value> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use swarm_language::SwarmCommand;
+
+    /// Counts what it sees, and gives every new player a head start
+    #[derive(Debug, Default)]
+    struct Counter {
+        ticks: AtomicUsize,
+        joins: AtomicUsize,
+        events: AtomicUsize,
+    }
+    impl WorldPlugin for Counter {
+        fn name(&self) -> &str {
+            "counter"
+        }
+        fn on_tick_end(&self, _world: &mut World) {
+            self.ticks.fetch_add(1, Ordering::SeqCst);
+        }
+        fn on_event(&self, _world: &World, _event: &WorldEvent) {
+            self.events.fetch_add(1, Ordering::SeqCst);
+        }
+        fn on_player_join(&self, world: &mut World, player: usize) {
+            self.joins.fetch_add(1, Ordering::SeqCst);
+            world.swarms.get_mut(&player).unwrap().experience = 5;
+        }
+        fn on_message(&self, _world: &mut World, _player: usize, data: &Value) -> Option<Value> {
+            Some(data.clone())
+        }
+    }
+
+    #[test]
+    fn plugin_hooks() {
+        let counter = Arc::new(Counter::default());
+        let mut world = World::new(1000.0, 1000.0);
+        world.add_plugin(counter.clone());
+        world.add_player(1).unwrap();
+        assert_eq!(world.swarms.get(&1).unwrap().experience, 5);
+        world.swarms.get_mut(&1).unwrap().program.commands = vec![SwarmCommand::FIRE];
+        world.update();
+        world.update();
+        assert_eq!(counter.joins.load(Ordering::SeqCst), 1);
+        assert_eq!(counter.ticks.load(Ordering::SeqCst), 2);
+        // The swarm fired once, then waited for its cooldown
+        assert_eq!(counter.events.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            world.plugin_message(1, "counter", &Value::Bool(true)),
+            Some(Value::Bool(true))
+        );
+        assert_eq!(world.plugin_message(1, "missing", &Value::Null), None);
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate serde_json;
+
+use pyo3::exceptions::ValueError;
+use pyo3::prelude::*;
+use simulation;
+use swarm_language::{check_program, SkillTier, SwarmProgram};
+use world::{World, WorldConfig};
+
+/// A world, driven from Python
+/// Worlds with the same size, players, programs, and seed always play out the same way
+#[pyclass(name = World)]
+pub struct PyWorld {
+    /// The simulated world
+    world: World,
+}
+
+/// Functions for PyWorld
+#[pymethods]
+impl PyWorld {
+    /// Constructor
+    /// seed: seeds the world's random choices; a random seed is used if it's None
+    #[new]
+    #[args(seed = "None")]
+    fn __new__(obj: &PyRawObject, width: f32, height: f32, seed: Option<u64>) -> PyResult<()> {
+        let world = match World::from_config(&WorldConfig::new(width, height)) {
+            Ok(world) => world,
+            Err(error) => return Err(ValueError::py_err(error.to_string())),
+        };
+        let world = match seed {
+            Some(seed) => world.with_seed(seed),
+            None => world,
+        };
+        obj.init(|| PyWorld { world: world })
+    }
+    /// Adds a player, whose swarm spawns at a random position
+    fn add_player(&mut self, player_id: usize) -> PyResult<()> {
+        self.world
+            .add_player(player_id)
+            .map_err(|error| ValueError::py_err(error.to_string()))
+    }
+    /// Removes a player and their swarm
+    fn remove_player(&mut self, player_id: usize) -> PyResult<()> {
+        if !self.world.has_player(player_id) {
+            return Err(ValueError::py_err(format!("No player with ID {}", player_id)));
+        }
+        self.world.remove_player(player_id);
+        Ok(())
+    }
+    /// IDs of the players in the world, in ascending order
+    fn player_ids(&self) -> PyResult<Vec<usize>> {
+        let mut ids = self.world.player_ids();
+        ids.sort();
+        Ok(ids)
+    }
+    /// Compiles swarm code and gives it to a player
+    /// Raises ValueError, and leaves the old program running, if the code doesn't compile
+    fn set_program(&mut self, player_id: usize, source: &str) -> PyResult<()> {
+        if !self.world.has_player(player_id) {
+            return Err(ValueError::py_err(format!("No player with ID {}", player_id)));
+        }
+        let program = source
+            .parse::<SwarmProgram>()
+            .map_err(|error| ValueError::py_err(error.to_string()))?;
+        self.world.update_program(player_id, program);
+        Ok(())
+    }
+    /// Performs some ticks, one by default
+    /// Events are dropped, since nothing in Python listens for them
+    #[args(ticks = "1")]
+    fn tick(&mut self, ticks: u32) -> PyResult<()> {
+        for _ in 0..ticks {
+            self.world.update();
+            self.world.clear_events();
+        }
+        Ok(())
+    }
+    /// The number of ticks performed so far
+    fn tick_count(&self) -> PyResult<u64> {
+        Ok(self.world.tick)
+    }
+    /// The world's state, as the JSON the server sends clients
+    /// Load it with json.loads
+    fn get_state(&self) -> PyResult<String> {
+        serde_json::to_string(&self.world.state_ref())
+            .map_err(|error| ValueError::py_err(error.to_string()))
+    }
+    /// (player ID, experience, members left) for each player, best first
+    /// Ranked the same way as the headless simulator ranks matches
+    fn scores(&self) -> PyResult<Vec<(usize, i64, usize)>> {
+        Ok(simulation::rank(&self.world.swarms)
+            .into_iter()
+            .map(|score| (score.player_id, score.experience, score.members))
+            .collect())
+    }
+}
+
+/// Checks swarm code the same way the server does
+/// return: a list of (line number, message), empty if the code compiles
+#[pyfunction]
+fn check(source: &str) -> PyResult<Vec<(usize, String)>> {
+    Ok(check_program(source)
+        .into_iter()
+        .map(|diagnostic| (diagnostic.line, diagnostic.error.to_string()))
+        .collect())
+}
+
+/// Rewrites swarm code as canonical swarm code, one command per line
+/// Raises ValueError if the code doesn't compile
+#[pyfunction]
+fn format(source: &str) -> PyResult<String> {
+    source
+        .parse::<SwarmProgram>()
+        .map(|program| program.to_string())
+        .map_err(|error| ValueError::py_err(error.to_string()))
+}
+
+/// Scores how complex and aggressive swarm code is
+/// return: (complexity from 0 to 100, fraction of commands that fire, tier, suspicious)
+/// Raises ValueError if the code doesn't compile
+#[pyfunction]
+fn profile(source: &str) -> PyResult<(u32, f32, String, bool)> {
+    let program = source
+        .parse::<SwarmProgram>()
+        .map_err(|error| ValueError::py_err(error.to_string()))?;
+    let profile = program.profile();
+    let tier = match profile.tier {
+        SkillTier::BEGINNER => "beginner",
+        SkillTier::INTERMEDIATE => "intermediate",
+        SkillTier::ADVANCED => "advanced",
+    };
+    Ok((
+        profile.complexity,
+        profile.aggression,
+        tier.into(),
+        profile.suspicious,
+    ))
+}
+
+/// The heroesoftheswarm Python module
+#[pymodule]
+fn heroesoftheswarm(_py: Python, module: &PyModule) -> PyResult<()> {
+    module.add_class::<PyWorld>()?;
+    module.add_wrapped(wrap_function!(check))?;
+    module.add_wrapped(wrap_function!(format))?;
+    module.add_wrapped(wrap_function!(profile))?;
+    Ok(())
+}
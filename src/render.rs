@@ -0,0 +1,290 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use collision::MEMBER_RADIUS;
+use entity::{Bullet, Swarm};
+#[cfg(feature = "render_png")]
+use png::{self, HasParameters};
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+use world::WorldState;
+
+/// Color of the world's background
+const BACKGROUND: (u8, u8, u8) = (16, 16, 24);
+/// Color of bullets whose owner has left
+const ORPHAN_BULLET: (u8, u8, u8) = (255, 255, 255);
+/// Radius bullets are drawn with
+const BULLET_RADIUS: f32 = 2.0;
+/// Length of the line showing which way a swarm is heading
+const HEADING_LENGTH: f32 = 3.0 * MEMBER_RADIUS;
+
+/// Color of a bullet, which is the color of the swarm that fired it
+fn bullet_color(state: &WorldState, bullet: &Bullet) -> (u8, u8, u8) {
+    state
+        .swarms
+        .get(&bullet.owner)
+        .map(|swarm| swarm.color)
+        .unwrap_or(ORPHAN_BULLET)
+}
+
+/// Where a swarm's heading line ends
+fn heading_end(swarm: &Swarm) -> (f32, f32) {
+    let radians = swarm.direction.to_radians();
+    (
+        swarm.x + HEADING_LENGTH * radians.cos(),
+        swarm.y + HEADING_LENGTH * radians.sin(),
+    )
+}
+
+/// Draws a world state as an SVG document, in world coordinates
+/// width: the width of the world
+/// height: the height of the world
+pub fn render_svg(state: &WorldState, width: f32, height: f32) -> String {
+    let mut svg = String::new();
+    // Writing to a String can't fail
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+        width, height, width, height
+    );
+    let _ = writeln!(
+        svg,
+        "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+        width,
+        height,
+        hex(BACKGROUND)
+    );
+    for (&id, swarm) in state.swarms.iter() {
+        let color = hex(swarm.color);
+        let _ = writeln!(svg, "<g id=\"swarm-{}\" fill=\"{}\" stroke=\"{}\">", id, color, color);
+        for member in swarm.members.iter() {
+            let _ = writeln!(
+                svg,
+                "<circle cx=\"{}\" cy=\"{}\" r=\"{}\"/>",
+                swarm.x + member.x,
+                swarm.y + member.y,
+                MEMBER_RADIUS
+            );
+        }
+        let (end_x, end_y) = heading_end(swarm);
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"/>",
+            swarm.x, swarm.y, end_x, end_y
+        );
+        let _ = writeln!(svg, "</g>");
+    }
+    for bullet in state.bullets.iter() {
+        let _ = writeln!(
+            svg,
+            "<circle class=\"bullet\" cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>",
+            bullet.x,
+            bullet.y,
+            BULLET_RADIUS,
+            hex(bullet_color(state, &bullet))
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Formats a color as #rrggbb
+fn hex(color: (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2)
+}
+
+/// A raster image, as rows of RGB pixels from the top left
+pub struct Image {
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+    /// Three bytes per pixel
+    pub pixels: Vec<u8>,
+}
+
+/// Functions for Image
+impl Image {
+    /// Constructor
+    /// Fills the image with a color
+    pub fn new(width: u32, height: u32, color: (u8, u8, u8)) -> Self {
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 3);
+        for _ in 0..(width as usize * height as usize) {
+            pixels.extend_from_slice(&[color.0, color.1, color.2]);
+        }
+        Image {
+            width: width,
+            height: height,
+            pixels: pixels,
+        }
+    }
+    /// The color of a pixel, or None if it's outside the image
+    pub fn pixel(&self, x: u32, y: u32) -> Option<(u8, u8, u8)> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let index = (y as usize * self.width as usize + x as usize) * 3;
+        Some((
+            self.pixels[index],
+            self.pixels[index + 1],
+            self.pixels[index + 2],
+        ))
+    }
+    /// Colors a pixel, ignoring pixels outside the image
+    fn set_pixel(&mut self, x: i64, y: i64, color: (u8, u8, u8)) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let index = (y as usize * self.width as usize + x as usize) * 3;
+        self.pixels[index] = color.0;
+        self.pixels[index + 1] = color.1;
+        self.pixels[index + 2] = color.2;
+    }
+    /// Fills a disc, in pixel coordinates
+    fn fill_disc(&mut self, center_x: f32, center_y: f32, radius: f32, color: (u8, u8, u8)) {
+        // Always draw at least the center pixel, so small things stay visible when scaled down
+        let radius = radius.max(0.5);
+        if !(center_x.is_finite() && center_y.is_finite()) {
+            return;
+        }
+        for y in (center_y - radius).floor() as i64..(center_y + radius).ceil() as i64 + 1 {
+            for x in (center_x - radius).floor() as i64..(center_x + radius).ceil() as i64 + 1 {
+                let dx = x as f32 + 0.5 - center_x;
+                let dy = y as f32 + 0.5 - center_y;
+                if dx * dx + dy * dy <= radius * radius {
+                    self.set_pixel(x, y, color);
+                }
+            }
+        }
+    }
+    /// Draws a one pixel wide line, in pixel coordinates
+    fn draw_line(&mut self, from: (f32, f32), to: (f32, f32), color: (u8, u8, u8)) {
+        let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs()).ceil();
+        if !steps.is_finite() {
+            return;
+        }
+        let steps = steps.max(1.0);
+        for step in 0..(steps as i64 + 1) {
+            let t = step as f32 / steps;
+            self.set_pixel(
+                (from.0 + t * (to.0 - from.0)) as i64,
+                (from.1 + t * (to.1 - from.1)) as i64,
+                color,
+            );
+        }
+    }
+    /// Writes the image as a binary PPM, which needs no encoder
+    pub fn write_ppm<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        writer.write_all(&self.pixels)
+    }
+    /// Writes the image as a PNG
+    #[cfg(feature = "render_png")]
+    pub fn write_png<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.pixels))
+            .map_err(|error| io::Error::new(io::ErrorKind::Other, error.to_string()))
+    }
+}
+
+/// Draws a world state as a raster image
+/// width: the width of the world
+/// height: the height of the world
+/// scale: pixels per unit of world distance
+pub fn render_image(state: &WorldState, width: f32, height: f32, scale: f32) -> Image {
+    let mut image = Image::new(
+        (width * scale).ceil().max(1.0) as u32,
+        (height * scale).ceil().max(1.0) as u32,
+        BACKGROUND,
+    );
+    for (_, swarm) in state.swarms.iter() {
+        for member in swarm.members.iter() {
+            image.fill_disc(
+                (swarm.x + member.x) * scale,
+                (swarm.y + member.y) * scale,
+                MEMBER_RADIUS * scale,
+                swarm.color,
+            );
+        }
+        let (end_x, end_y) = heading_end(swarm);
+        image.draw_line(
+            (swarm.x * scale, swarm.y * scale),
+            (end_x * scale, end_y * scale),
+            swarm.color,
+        );
+    }
+    for bullet in state.bullets.iter() {
+        let color = bullet_color(state, &bullet);
+        image.fill_disc(bullet.x * scale, bullet.y * scale, BULLET_RADIUS * scale, color);
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entity::Bullets;
+    use game_mode::GameMode;
+    use minimap::Minimap;
+    use rules::MatchPhase;
+    use slab::Slab;
+
+    fn state() -> WorldState {
+        let mut swarms = Slab::new();
+        swarms.insert(2, Swarm::new(50.0, 50.0, 3).with_color((200, 10, 10)));
+        let mut bullets = Bullets::new();
+        bullets.push(Bullet::new(2, 150.0, 20.0, 0.0));
+        bullets.push(Bullet::new(9, 20.0, 150.0, 0.0));
+        WorldState {
+            swarms: swarms,
+            bullets: bullets,
+            mode: GameMode::default(),
+            phase: MatchPhase::default(),
+            minimap: Minimap::default(),
+            sounds: Vec::new(),
+            hit_markers: Vec::new(),
+            signals: Vec::new(),
+            ghosts: Vec::new(),
+            checksum: None,
+        }
+    }
+
+    #[test]
+    fn render_state() {
+        let state = state();
+        let svg = render_svg(&state, 200.0, 200.0);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<circle").count(), 3 + 2);
+        assert!(svg.contains("fill=\"#c80a0a\""));
+
+        let image = render_image(&state, 200.0, 200.0, 0.5);
+        assert_eq!((image.width, image.height), (100, 100));
+        let member = &state.swarms.get(&2).unwrap().members[0];
+        let (x, y) = ((50.0 + member.x) * 0.5, (50.0 + member.y) * 0.5);
+        assert_eq!(image.pixel(x as u32, y as u32), Some((200, 10, 10)));
+        assert_eq!(image.pixel(75, 10), Some((200, 10, 10)));
+        assert_eq!(image.pixel(10, 75), Some(ORPHAN_BULLET));
+        assert_eq!(image.pixel(99, 99), Some(BACKGROUND));
+        assert_eq!(image.pixel(100, 0), None);
+
+        let mut ppm = Vec::new();
+        image.write_ppm(&mut ppm).unwrap();
+        assert_eq!(ppm.len(), "P6\n100 100\n255\n".len() + 100 * 100 * 3);
+    }
+}
@@ -1,18 +1,28 @@
 extern crate serde_json;
 
-use world::WorldState;
+use achievement::Achievement;
+use error::{LanguageError, ParseError, WorldError};
+use handoff::HandoffRedirect;
+use manual::ControlInput;
+use messages::Message;
+use palette::{ColorMapping, Palette};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use swarm_language::{check_program, Diagnostic};
+use world::WorldStateRef;
 
 /// Represents a response sent to the client
 #[derive(Serialize)]
-pub struct Response {
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Response<'a> {
     #[serde(rename = "mt")]
     /// The type of message
     message_type: String,
     /// The contents of the message
-    message: ResponseMessage,
+    message: ResponseMessage<'a>,
 }
-impl Response {
-    pub fn new(message: ResponseMessage) -> Self {
+impl<'a> Response<'a> {
+    pub fn new(message: ResponseMessage<'a>) -> Self {
         match message {
             ResponseMessage::WORLD(world) => Response {
                 message_type: "w".into(),
@@ -26,6 +36,26 @@ impl Response {
                 message_type: "c".into(),
                 message: ResponseMessage::COMPILE(compile_result),
             },
+            ResponseMessage::HANDOFF(redirect) => Response {
+                message_type: "h".into(),
+                message: ResponseMessage::HANDOFF(redirect),
+            },
+            ResponseMessage::ACHIEVEMENT(achievement) => Response {
+                message_type: "a".into(),
+                message: ResponseMessage::ACHIEVEMENT(achievement),
+            },
+            ResponseMessage::PLUGIN(reply) => Response {
+                message_type: "p".into(),
+                message: ResponseMessage::PLUGIN(reply),
+            },
+            ResponseMessage::REMAP(remap) => Response {
+                message_type: "r".into(),
+                message: ResponseMessage::REMAP(remap),
+            },
+            ResponseMessage::SQUAD(squad_result) => Response {
+                message_type: "s".into(),
+                message: ResponseMessage::SQUAD(squad_result),
+            },
         }
     }
     pub fn serialize(&self) -> Result<String, serde_json::Error> {
@@ -33,33 +63,62 @@ impl Response {
     }
 }
 /// Represents the contents of a message
+/// The world state is borrowed, so it is serialized without being copied
 #[derive(Serialize)]
-pub enum ResponseMessage {
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum ResponseMessage<'a> {
     /// Sends the world state
     #[serde(rename = "world")]
-    WORLD(WorldState),
+    WORLD(WorldStateRef<'a>),
     /// Sends configuration
     #[serde(rename = "config")]
     CONFIG(Configuration),
     /// Sends a compilation result
     #[serde(rename = "compile")]
     COMPILE(CompileResult),
+    /// Tells the client to reconnect to another server
+    #[serde(rename = "handoff")]
+    HANDOFF(HandoffRedirect),
+    /// Tells the client the player unlocked an achievement
+    #[serde(rename = "achievement")]
+    ACHIEVEMENT(Achievement),
+    /// Sends a plugin's reply to a message
+    #[serde(rename = "plugin")]
+    PLUGIN(PluginMessage),
+    /// Tells the client how to redraw swarm colors in the palette it asked for
+    #[serde(rename = "remap")]
+    REMAP(ColorRemap),
+    /// Sends the results of uploading programs for a squad
+    #[serde(rename = "squad")]
+    SQUAD(SquadResult),
 }
 
 /// Represents configuration
-#[derive(Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct Configuration {
     /// The player's ID
-    player_id: usize,
+    pub player_id: usize,
+    /// Token the client can send to get its swarm back if the server restarts
+    /// Left out when the server doesn't sign tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_token: Option<String>,
 }
 
 /// Represents the output of a compilation
-#[derive(Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct CompileResult {
     /// Whether the compilation succeeded
-    success: bool,
-    /// Error if applicable
-    error: String,
+    pub success: bool,
+    /// Error if applicable, in English
+    pub error: String,
+    /// The error as a message ID and parameters, for clients that translate it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<Message>,
+    /// Every problem found in the program, in line order, not only the first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<CompileDiagnostic>,
 }
 
 impl CompileResult {
@@ -68,6 +127,105 @@ impl CompileResult {
         CompileResult {
             success: success,
             error: error,
+            message: None,
+            diagnostics: Vec::new(),
+        }
+    }
+    /// Result of a program that didn't compile
+    pub fn failed(error: &LanguageError) -> Self {
+        CompileResult {
+            success: false,
+            error: error.to_string(),
+            message: Some(error.message()),
+            diagnostics: Vec::new(),
+        }
+    }
+    /// Result of a program that didn't compile, with every problem in its source
+    pub fn rejected(error: &ParseError, source: &str) -> Self {
+        let mut result = CompileResult::failed(&error.kind);
+        result.diagnostics = check_program(source)
+            .iter()
+            .map(CompileDiagnostic::new)
+            .collect();
+        result
+    }
+}
+
+/// The output of uploading programs for a squad
+/// Either every program was applied, or none were
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct SquadResult {
+    /// Whether the programs were applied
+    pub success: bool,
+    /// Why the squad was refused, in English, if every program compiled but wasn't applied
+    pub error: String,
+    /// How each program compiled, in the order they were sent
+    pub programs: Vec<SquadProgramResult>,
+}
+
+/// Functions for SquadResult
+impl SquadResult {
+    /// Constructor
+    /// Succeeds if every program compiled
+    pub fn new(programs: Vec<SquadProgramResult>) -> Self {
+        SquadResult {
+            success: programs.iter().all(|program| program.result.success),
+            error: String::new(),
+            programs: programs,
+        }
+    }
+    /// Result of programs that compiled, but couldn't be applied to the squad
+    pub fn refused(mut self, error: &WorldError) -> Self {
+        self.success = false;
+        self.error = error.to_string();
+        self
+    }
+}
+
+/// How one of a squad's programs compiled
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct SquadProgramResult {
+    /// Name the program was sent with
+    pub name: String,
+    /// ID of the player the program was for
+    pub player_id: usize,
+    /// Whether it compiled, and what was wrong with it if it didn't
+    pub result: CompileResult,
+}
+
+/// A problem found on one line of a program, as sent to its author
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct CompileDiagnostic {
+    /// What kind of problem it is, the ID of its message
+    pub kind: String,
+    /// Line number, starting from 1
+    pub line: usize,
+    /// Column the problem starts at, in characters starting from 1
+    pub column: usize,
+    /// The problem, in English
+    pub error: String,
+    /// The problem as a message ID and parameters, for clients that translate it
+    pub message: Message,
+    /// How the problem might be fixed, in English, if there's an obvious way
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+/// Functions for CompileDiagnostic
+impl CompileDiagnostic {
+    /// Constructor
+    pub fn new(diagnostic: &Diagnostic) -> Self {
+        let message = diagnostic.error.message();
+        CompileDiagnostic {
+            kind: message.id.clone(),
+            line: diagnostic.line,
+            column: diagnostic.column,
+            error: diagnostic.error.to_string(),
+            message: message,
+            hint: diagnostic.hint.clone(),
         }
     }
 }
@@ -77,21 +235,232 @@ impl Configuration {
     pub fn new(player_id: usize) -> Self {
         Configuration {
             player_id: player_id,
+            resume_token: None,
         }
     }
+    /// Gives the client a resume token
+    pub fn with_resume_token(mut self, resume_token: Option<String>) -> Self {
+        self.resume_token = resume_token;
+        self
+    }
 }
 
 /// A vector in 2d space
 /// Used for representing coordinates in
 /// the viewport sent for screen updates
-#[derive(Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct Vec2 {
     x: f32,
     y: f32,
 }
 
+/// Functions for Vec2
+impl Vec2 {
+    /// Constructor
+    pub fn new(x: f32, y: f32) -> Self {
+        Vec2 { x: x, y: y }
+    }
+    /// The x coordinate
+    pub fn x(&self) -> f32 {
+        self.x
+    }
+    /// The y coordinate
+    pub fn y(&self) -> f32 {
+        self.y
+    }
+    /// Whether both coordinates are finite
+    /// Numbers too large for an f32 are parsed as infinity
+    pub fn is_finite(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite()
+    }
+}
+
 /// A request for compilation
-#[derive(Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct CompileRequest {
     pub program: String,
 }
+
+/// A request for programs to be compiled and run by a squad: the player and the teammates
+/// following them
+/// Nothing is run unless every program compiles and is for a different member of the squad
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct SquadRequest {
+    /// One program for each member of the squad given one
+    pub squad: Vec<SquadProgram>,
+}
+
+/// A program for one member of a squad
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct SquadProgram {
+    /// Name of the program, to tell the results apart
+    pub name: String,
+    /// ID of the player to run it
+    pub player_id: usize,
+    /// Source of the program
+    pub program: String,
+}
+
+/// A request for a behavior script to be compiled and run instead of a program
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct ScriptRequest {
+    pub script: String,
+}
+
+/// A request to restore a player handed off from another server
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct HandoffRequest {
+    pub handoff_token: String,
+}
+
+/// A request to get a swarm back after the server restarted
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct ResumeRequest {
+    pub resume_token: String,
+}
+
+/// A request to pause or resume the player's own program
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct PauseRequest {
+    /// Whether the program should be paused
+    pub pause: bool,
+}
+
+/// A request to mark a position for the player's team
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct PingRequest {
+    /// The position to mark
+    pub ping: Vec2,
+}
+
+/// A request to let a teammate upload the player's programs as their squad leader
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct LeaderRequest {
+    /// ID of the teammate to follow, or the player's own ID to stop following anyone
+    pub squad_leader: usize,
+}
+
+/// A direct control input, wrapped so it can't be mistaken for another request
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct InputRequest {
+    pub input: ControlInput,
+}
+
+/// A request for swarm colors to be shown in another palette
+/// The world state is shared by every client, so the client redraws the colors itself
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct PaletteRequest {
+    pub palette: Palette,
+}
+
+/// How to redraw the world's colors in the palette a client asked for
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct ColorRemap {
+    /// The palette the world gives out colors from
+    pub world: Palette,
+    /// The palette the client asked for
+    pub palette: Palette,
+    /// Each color to redraw, empty if the world's colors are random
+    pub colors: Vec<ColorMapping>,
+}
+
+/// Functions for ColorRemap
+impl ColorRemap {
+    /// Constructor
+    pub fn new(world: Palette, palette: Palette) -> Self {
+        ColorRemap {
+            world: world,
+            palette: palette,
+            colors: world.remap(palette),
+        }
+    }
+}
+
+/// A message between a client and a server plugin
+/// The data is up to the plugin
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct PluginMessage {
+    /// Name of the plugin
+    pub plugin: String,
+    /// The contents of the message
+    pub data: serde_json::Value,
+}
+
+/// A command for a practice room
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum SandboxCommand {
+    /// Stops updating the world
+    #[serde(rename = "pause")]
+    PAUSE,
+    /// Starts updating the world again
+    #[serde(rename = "resume")]
+    RESUME,
+    /// Runs some number of updates while paused
+    #[serde(rename = "step")]
+    STEP(u32),
+    /// Puts the world back some number of ticks, and pauses it
+    #[serde(rename = "rewind")]
+    REWIND(u32),
+    /// Adds a swarm that doesn't move or fire, to shoot at
+    #[serde(rename = "dummy")]
+    DUMMY(Vec2),
+    /// Adds a bullet that hits any swarm
+    #[serde(rename = "bullet")]
+    BULLET { position: Vec2, direction: f32 },
+}
+
+/// A request to control a practice room
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct SandboxRequest {
+    pub sandbox: SandboxCommand,
+}
+
+/// Represents a request sent by the client
+/// Requests are told apart by their shape rather than a type field
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum ClientMessage {
+    /// Asks for the world state
+    VIEWPORT(Vec<Vec2>),
+    /// Asks for a program to be compiled and run
+    COMPILE(CompileRequest),
+    /// Asks for programs to be compiled and run by the player's squad, all at once
+    SQUAD(SquadRequest),
+    /// Presents a handoff token from another server
+    HANDOFF(HandoffRequest),
+    /// Controls a practice room
+    SANDBOX(SandboxRequest),
+    /// Sends a message to a server plugin
+    PLUGIN(PluginMessage),
+    /// Asks for a behavior script to be compiled and run, on servers built with scripting
+    SCRIPT(ScriptRequest),
+    /// Asks how to show swarm colors in another palette
+    PALETTE(PaletteRequest),
+    /// Drives the swarm directly for a while
+    INPUT(InputRequest),
+    /// Presents a resume token from before the server restarted
+    RESUME(ResumeRequest),
+    /// Pauses or resumes the player's program, while they edit it
+    PAUSE(PauseRequest),
+    /// Marks a position for the player's team
+    PING(PingRequest),
+    /// Chooses the teammate allowed to upload the player's programs
+    LEADER(LeaderRequest),
+}
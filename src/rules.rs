@@ -0,0 +1,211 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use entity::Swarm;
+use player_stats::PlayerStats;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use simulation::{rank, Score};
+use slab::Slab;
+use std::collections::BTreeMap;
+
+/// Default length of the pause between matches, 5 seconds at 60 ticks per second
+pub const DEFAULT_FREEZE: u64 = 300;
+
+/// When a match ends, and how long the world waits before starting the next one
+/// Fields left out when deserializing keep their defaults
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatchRules {
+    /// Longest a match lasts, in ticks, if it has a limit
+    pub duration: Option<u64>,
+    /// Experience that wins the match, if there is a target
+    pub score_target: Option<i64>,
+    /// Ticks the world stays frozen on the results before the next match starts
+    pub freeze: u64,
+}
+
+/// Functions for MatchRules
+impl MatchRules {
+    /// Constructor
+    /// Matches never end until a limit is set
+    pub fn new() -> Self {
+        MatchRules {
+            duration: None,
+            score_target: None,
+            freeze: DEFAULT_FREEZE,
+        }
+    }
+    /// Ends matches after a number of ticks
+    pub fn with_duration(mut self, duration: u64) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+    /// Ends matches when a player reaches an amount of experience
+    pub fn with_score_target(mut self, score_target: i64) -> Self {
+        self.score_target = Some(score_target);
+        self
+    }
+    /// Sets how many ticks the world stays frozen between matches
+    pub fn with_freeze(mut self, freeze: u64) -> Self {
+        self.freeze = freeze;
+        self
+    }
+    /// Whether matches ever end
+    pub fn is_endless(&self) -> bool {
+        self.duration.is_none() && self.score_target.is_none()
+    }
+    /// Checks whether a match that started on a tick is over
+    /// started: the tick the match started on
+    /// tick: the tick that was just played
+    pub fn check(&self, swarms: &Slab<Swarm>, started: u64, tick: u64) -> Option<EndReason> {
+        if let Some(target) = self.score_target {
+            if swarms.values().any(|swarm| swarm.experience >= target) {
+                return Some(EndReason::SCORE_TARGET);
+            }
+        }
+        match self.duration {
+            Some(duration) if tick - started >= duration => Some(EndReason::TIME_LIMIT),
+            _ => None,
+        }
+    }
+}
+
+/// Endless matches with the default freeze
+impl Default for MatchRules {
+    fn default() -> Self {
+        MatchRules::new()
+    }
+}
+
+/// Why a match ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EndReason {
+    /// The match ran for its whole duration
+    #[serde(rename = "time_limit")]
+    TIME_LIMIT,
+    /// A player reached the score target
+    #[serde(rename = "score_target")]
+    SCORE_TARGET,
+    /// The game mode's win condition was met
+    #[serde(rename = "mode")]
+    MODE,
+}
+
+/// Where the world is in the cycle of matches
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum MatchPhase {
+    /// A match is being played
+    #[serde(rename = "playing")]
+    PLAYING {
+        /// The tick the match started on
+        started: u64,
+    },
+    /// A match ended, and the world is frozen on the results
+    #[serde(rename = "frozen")]
+    FROZEN {
+        /// The tick the match ended on
+        ended: u64,
+    },
+}
+
+/// Functions for MatchPhase
+impl MatchPhase {
+    /// Whether the world is frozen between matches
+    pub fn is_frozen(&self) -> bool {
+        match *self {
+            MatchPhase::FROZEN { .. } => true,
+            MatchPhase::PLAYING { .. } => false,
+        }
+    }
+    /// Whether a match is being played
+    pub fn is_playing(&self) -> bool {
+        !self.is_frozen()
+    }
+}
+
+/// A match that started when the world was created
+impl Default for MatchPhase {
+    fn default() -> Self {
+        MatchPhase::PLAYING { started: 0 }
+    }
+}
+
+/// How a match finished
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MatchResults {
+    /// The tick the match ended on
+    pub tick: u64,
+    /// Why the match ended
+    pub reason: EndReason,
+    /// ID of the player in first place, or None if first place is tied or nobody played
+    pub winner: Option<usize>,
+    /// Every player's score, best first
+    pub scores: Vec<Score>,
+    /// What each player did during the match, by player ID
+    #[serde(default)]
+    pub stats: BTreeMap<usize, PlayerStats>,
+}
+
+/// Functions for MatchResults
+impl MatchResults {
+    /// Constructor
+    /// Ranks every swarm; a tie on experience for first place has no winner
+    pub fn new(swarms: &Slab<Swarm>, tick: u64, reason: EndReason) -> Self {
+        let scores = rank(swarms);
+        let winner = match (scores.get(0), scores.get(1)) {
+            (Some(first), Some(second)) if first.experience == second.experience => None,
+            (Some(first), _) => Some(first.player_id),
+            (None, _) => None,
+        };
+        MatchResults {
+            tick: tick,
+            reason: reason,
+            winner: winner,
+            scores: scores,
+            stats: BTreeMap::new(),
+        }
+    }
+    /// Adds what each player did during the match
+    pub fn with_stats(mut self, stats: BTreeMap<usize, PlayerStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_rules() {
+        let mut swarms = Slab::new();
+        swarms.insert(1, Swarm::new(0.0, 0.0, 1));
+        swarms.insert(2, Swarm::new(0.0, 0.0, 1));
+        let rules = MatchRules::new().with_duration(100).with_score_target(5);
+        assert_eq!(rules.check(&swarms, 50, 149), None);
+        assert_eq!(rules.check(&swarms, 50, 150), Some(EndReason::TIME_LIMIT));
+        assert!(MatchRules::new().check(&swarms, 0, 1_000_000).is_none());
+        // A tie for first place has no winner
+        let results = MatchResults::new(&swarms, 150, EndReason::TIME_LIMIT);
+        assert_eq!(results.winner, None);
+        swarms.get_mut(&2).unwrap().experience = 5;
+        assert_eq!(rules.check(&swarms, 50, 60), Some(EndReason::SCORE_TARGET));
+        let results = MatchResults::new(&swarms, 60, EndReason::SCORE_TARGET);
+        assert_eq!(results.winner, Some(2));
+        assert_eq!(results.scores[0].player_id, 2);
+    }
+}
@@ -0,0 +1,353 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use rpc::SandboxCommand;
+use std::collections::VecDeque;
+use std::fmt::Write as FmtWrite;
+use std::io::{self, BufRead, Write};
+use swarm_language::SwarmProgram;
+use world::World;
+
+/// Size of the sandbox world
+const SANDBOX_SIZE: f32 = 1000.0;
+/// ID of the sandbox's only player
+const PLAYER_ID: usize = 0;
+/// Updates kept for rewinding, 5 seconds at 60 ticks per second
+pub const HISTORY_TICKS: usize = 300;
+/// Owner of bullets added to a practice world, which hit every swarm
+pub const SPAWNED_BULLET_OWNER: usize = ::std::usize::MAX;
+
+/// Help text for the sandbox
+const HELP: &str = "Type swarm commands separated by ';' to run each of them once, for example \
+                    MOVE; TURN 10; FIRE\n\
+                    :step [N]  runs the current program for N more ticks (default 1)\n\
+                    :show      shows the swarm\n\
+                    :program   shows the current program\n\
+                    :rewind N  goes back N ticks\n\
+                    :dummy X Y adds a target that doesn't move or fire\n\
+                    :bullet X Y DIRECTION adds a bullet\n\
+                    :reset     starts over with a new swarm\n\
+                    :quit      leaves the sandbox\n";
+
+/// Controls for practicing in a world: pausing, stepping and rewinding
+/// A world with practice controls records itself before each update, so it can be rewound
+#[derive(Clone, Debug)]
+pub struct Practice {
+    /// Whether updates are skipped
+    pub paused: bool,
+    /// Updates left to run while paused
+    pub steps: u32,
+    /// Number of changes made outside of updates, so they can be noticed while paused
+    pub revision: u64,
+    /// The world before each recent update, oldest first
+    history: VecDeque<World>,
+    /// Most updates kept in the history
+    capacity: usize,
+}
+
+/// Functions for Practice
+impl Practice {
+    /// Constructor
+    /// capacity: most updates that can be rewound
+    pub fn new(capacity: usize) -> Self {
+        Practice {
+            paused: false,
+            steps: 0,
+            revision: 0,
+            history: VecDeque::with_capacity(capacity),
+            capacity: capacity,
+        }
+    }
+    /// Whether the next update should run, using up a step if paused
+    pub fn should_update(&mut self) -> bool {
+        if !self.paused {
+            return true;
+        }
+        if self.steps > 0 {
+            self.steps -= 1;
+            return true;
+        }
+        false
+    }
+    /// Records the world as it was before an update
+    pub fn record(&mut self, world: &World) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.history.len() == self.capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(world.clone());
+    }
+    /// Number of updates that can be rewound
+    pub fn available(&self) -> usize {
+        self.history.len()
+    }
+    /// Takes the world from some number of updates ago, dropping the history after it
+    /// return: None if ticks is 0 or more than are available
+    pub fn rewind(&mut self, ticks: usize) -> Option<World> {
+        if ticks == 0 || ticks > self.history.len() {
+            return None;
+        }
+        let keep = self.history.len() - ticks;
+        self.history.truncate(keep + 1);
+        self.history.pop_back()
+    }
+}
+
+/// Applies a command from a player in a practice world
+/// return: false if the world has no practice controls, in which case nothing changes
+pub fn apply(world: &mut World, command: &SandboxCommand) -> bool {
+    {
+        let practice = match world.practice {
+            Some(ref mut practice) => practice,
+            None => return false,
+        };
+        match *command {
+            SandboxCommand::PAUSE => practice.paused = true,
+            SandboxCommand::RESUME => practice.paused = false,
+            SandboxCommand::STEP(ticks) => {
+                practice.paused = true;
+                practice.steps += ticks;
+            }
+            _ => {}
+        }
+    }
+    match *command {
+        SandboxCommand::REWIND(ticks) => {
+            world.rewind(ticks as usize);
+        }
+        SandboxCommand::DUMMY(position) => {
+            world.spawn_dummy(position.x(), position.y());
+        }
+        SandboxCommand::BULLET {
+            position,
+            direction,
+        } => world.spawn_bullet(position.x(), position.y(), direction),
+        _ => {}
+    }
+    true
+}
+
+/// A world with a single swarm, for trying out the swarm language
+pub struct Sandbox {
+    /// The sandbox world
+    pub world: World,
+}
+
+/// Functions for the sandbox
+impl Sandbox {
+    /// Constructor
+    pub fn new() -> Self {
+        let mut world = World::new(SANDBOX_SIZE, SANDBOX_SIZE)
+            .with_seed(0)
+            .with_practice();
+        world
+            .add_player(PLAYER_ID)
+            .expect("A new sandbox should be empty");
+        // Start in the middle, so the first moves are easy to follow
+        if let Some(swarm) = world.swarms.get_mut(&PLAYER_ID) {
+            swarm.x = SANDBOX_SIZE / 2.0;
+            swarm.y = SANDBOX_SIZE / 2.0;
+            swarm.program = SwarmProgram::default();
+        }
+        Sandbox { world: world }
+    }
+    /// Handles a single line of input
+    /// return: the text to show the player
+    pub fn handle_line(&mut self, line: &str) -> String {
+        let mut words = line.trim().split_whitespace();
+        match words.next() {
+            Some(":step") => match words.next().map(|ticks| ticks.parse::<u32>()) {
+                None => self.step(1),
+                Some(Ok(ticks)) => self.step(ticks),
+                Some(Err(_)) => ":step takes a number of ticks\n".into(),
+            },
+            Some(":show") => self.describe(),
+            Some(":program") => match self.world.swarms.get(&PLAYER_ID) {
+                Some(swarm) if !swarm.program.commands.is_empty() => swarm.program.to_string(),
+                _ => "No program yet\n".into(),
+            },
+            Some(":reset") => {
+                *self = Sandbox::new();
+                self.describe()
+            }
+            Some(":rewind") => match words.next().map(|ticks| ticks.parse::<usize>()) {
+                Some(Ok(ticks)) => {
+                    let rewound = self.world.rewind(ticks);
+                    // The sandbox only runs when asked to, so it doesn't stay paused
+                    if let Some(ref mut practice) = self.world.practice {
+                        practice.paused = false;
+                    }
+                    format!("Rewound {} ticks\n{}", rewound, self.describe())
+                }
+                _ => ":rewind takes a number of ticks\n".into(),
+            },
+            Some(":dummy") => match parse_numbers(words) {
+                Some(ref numbers) if numbers.len() == 2 => {
+                    self.world.spawn_dummy(numbers[0], numbers[1]);
+                    format!(
+                        "Added a dummy at x {:.1}, y {:.1}\n",
+                        numbers[0], numbers[1]
+                    )
+                }
+                _ => ":dummy takes a position\n".into(),
+            },
+            Some(":bullet") => match parse_numbers(words) {
+                Some(ref numbers) if numbers.len() == 3 => {
+                    self.world.spawn_bullet(numbers[0], numbers[1], numbers[2]);
+                    format!(
+                        "Added a bullet at x {:.1}, y {:.1}\n",
+                        numbers[0], numbers[1]
+                    )
+                }
+                _ => ":bullet takes a position and a direction\n".into(),
+            },
+            Some(":help") => HELP.into(),
+            _ => self.run_commands(line),
+        }
+    }
+    /// Makes the commands the swarm's program, and runs each of them once
+    fn run_commands(&mut self, line: &str) -> String {
+        let program: SwarmProgram = match line.replace(';', "\n").parse() {
+            Ok(program) => program,
+            Err(error) => return format!("{} Try \":help\".\n", error),
+        };
+        let mut output = String::new();
+        let commands = program.commands.clone();
+        if let Some(swarm) = self.world.swarms.get_mut(&PLAYER_ID) {
+            swarm.program = program;
+        }
+        for command in commands.iter() {
+            self.world.update();
+            write!(output, "{:<16}{}", command.to_string(), self.describe()).unwrap();
+        }
+        output
+    }
+    /// Runs the current program for some number of ticks
+    fn step(&mut self, ticks: u32) -> String {
+        match self.world.swarms.get(&PLAYER_ID) {
+            Some(swarm) if !swarm.program.commands.is_empty() => {}
+            _ => return "No program yet\n".into(),
+        }
+        for _ in 0..ticks {
+            self.world.update();
+        }
+        self.describe()
+    }
+    /// Describes the swarm's position and heading
+    fn describe(&self) -> String {
+        match self.world.swarms.get(&PLAYER_ID) {
+            Some(swarm) => format!(
+                "tick {}: x {:.1}, y {:.1}, heading {:.1}, {} members, {} bullets\n",
+                self.world.tick,
+                swarm.x,
+                swarm.y,
+                swarm.direction,
+                swarm.members.len(),
+                self.world.bullets.len()
+            ),
+            None => "The swarm is gone. Use :reset to start over\n".into(),
+        }
+    }
+}
+
+/// Parses every word as a number
+fn parse_numbers<'a, I: Iterator<Item = &'a str>>(words: I) -> Option<Vec<f32>> {
+    words.map(|word| word.parse().ok()).collect()
+}
+
+/// Reads lines from stdin and runs them in a sandbox until stdin is closed
+/// Blocks the calling thread
+pub fn run_stdin() -> io::Result<()> {
+    let mut sandbox = Sandbox::new();
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    write!(stdout, "{}{}> ", HELP, sandbox.describe())?;
+    stdout.flush()?;
+    for line in stdin.lock().lines() {
+        let line = line?;
+        match line.trim() {
+            ":quit" => break,
+            "" => {}
+            _ => write!(stdout, "{}", sandbox.handle_line(&line))?,
+        }
+        write!(stdout, "> ")?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entity::NPC_ID_START;
+    use rpc::Vec2;
+    #[test]
+    fn run_commands_once() {
+        let mut sandbox = Sandbox::new();
+        let output = sandbox.handle_line("TURN 30; MOVE");
+        assert_eq!(output.lines().count(), 2);
+        assert_eq!(sandbox.world.tick, 2);
+        assert_eq!(sandbox.world.swarms[&PLAYER_ID].direction, 30.0);
+        assert!(sandbox.world.swarms[&PLAYER_ID].x > SANDBOX_SIZE / 2.0);
+        // Stepping repeats the program
+        sandbox.handle_line(":step 2");
+        assert_eq!(sandbox.world.tick, 4);
+        assert_eq!(sandbox.world.swarms[&PLAYER_ID].direction, 60.0);
+        // Bad commands leave the world alone
+        assert!(sandbox.handle_line("DANCE").starts_with("Command not recognized"));
+        assert_eq!(sandbox.world.tick, 4);
+        sandbox.handle_line(":reset");
+        assert_eq!(sandbox.world.tick, 0);
+    }
+    #[test]
+    fn rewind_and_spawn() {
+        let mut world = World::new(1000.0, 1000.0).with_seed(1).with_practice();
+        world.add_player(0).unwrap();
+        for _ in 0..5 {
+            world.update();
+        }
+        let position = (world.swarms[&0].x, world.swarms[&0].y);
+        world.update();
+        world.update();
+        // Rewinding goes back to before the updates, and pauses
+        assert_eq!(world.rewind(2), 2);
+        assert_eq!(world.tick, 5);
+        assert_eq!((world.swarms[&0].x, world.swarms[&0].y), position);
+        world.update();
+        assert_eq!(world.tick, 5);
+        assert!(apply(&mut world, &SandboxCommand::STEP(1)));
+        world.update();
+        world.update();
+        assert_eq!(world.tick, 6);
+        // Dummies stay put, and are removed once destroyed
+        let position = Vec2::new(100.0, 100.0);
+        assert!(apply(&mut world, &SandboxCommand::DUMMY(position)));
+        assert!(apply(&mut world, &SandboxCommand::RESUME));
+        world.update();
+        let dummy = NPC_ID_START;
+        assert_eq!(
+            (world.swarms[&dummy].x, world.swarms[&dummy].y),
+            (100.0, 100.0)
+        );
+        world.swarms.get_mut(&dummy).unwrap().members.clear();
+        world.update();
+        assert!(!world.has_player(dummy));
+        // Worlds without practice controls ignore commands
+        let mut world = World::new(1000.0, 1000.0);
+        assert!(!apply(&mut world, &SandboxCommand::PAUSE));
+    }
+}
@@ -0,0 +1,44 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate serde_json;
+
+use rpc::{ClientMessage, Response};
+use std::collections::BTreeMap;
+use world::WorldState;
+
+/// Builds JSON Schemas for the messages exchanged with clients, derived from the protocol types
+/// return: a JSON object mapping "ClientMessage", "ServerMessage", and "WorldState"
+/// to their schemas
+pub fn protocol_schema() -> Result<String, serde_json::Error> {
+    let mut schemas = BTreeMap::new();
+    schemas.insert("ClientMessage", schema_for!(ClientMessage));
+    schemas.insert("ServerMessage", schema_for!(Response<'static>));
+    schemas.insert("WorldState", schema_for!(WorldState));
+    serde_json::to_string_pretty(&schemas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn generate_schema() {
+        let schema = protocol_schema().unwrap();
+        assert!(schema.contains("\"ClientMessage\""));
+        assert!(schema.contains("\"handoff_token\""));
+        // Fields the server never sends are left out
+        assert!(!schema.contains("\"fire_cooldown\""));
+    }
+}
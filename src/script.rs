@@ -0,0 +1,204 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use entity::Swarm;
+use error::LanguageError;
+use limits::{ExecutionLimits, Limit};
+use rhai::{Dynamic, Engine, EvalAltResult, Map, Scope, AST, FLOAT};
+use slab::Slab;
+use swarm_language::{Formation, SwarmCommand, MAX_TURN};
+
+/// Longest script a player can upload, in bytes
+pub const MAX_SCRIPT_LENGTH: usize = 4096;
+/// Deepest a script's function calls can nest
+const MAX_CALL_LEVELS: usize = 8;
+/// Deepest an expression can nest, at the top level and in functions
+const MAX_EXPR_DEPTH: usize = 32;
+/// Longest string a script can build
+const MAX_STRING_SIZE: usize = 256;
+/// Function each script defines, called once per tick
+const TICK_FN: &str = "tick";
+
+/// Why a script didn't give a command
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScriptFailure {
+    /// The script went over an execution limit
+    LIMIT(Limit),
+    /// The script raised an error
+    ERROR(String),
+}
+
+/// Makes an engine that can only run swarm scripts
+/// Nothing but the core language and the swarm API is available,
+/// and each call stops after the limits' number of operations
+pub fn engine(limits: &ExecutionLimits) -> Engine {
+    let mut engine = Engine::new_raw();
+    engine.set_max_operations(limits.script_operations);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    engine.set_max_expr_depths(MAX_EXPR_DEPTH, MAX_EXPR_DEPTH);
+    engine.set_max_string_size(MAX_STRING_SIZE);
+    engine.on_print(|_| {});
+    engine.on_debug(|_| {});
+    engine.register_type_with_name::<SwarmCommand>("Command");
+    engine.register_fn("move", || SwarmCommand::MOVE);
+    engine.register_fn("left", || SwarmCommand::LEFT);
+    engine.register_fn("right", || SwarmCommand::RIGHT);
+    engine.register_fn("up", || SwarmCommand::UP);
+    engine.register_fn("down", || SwarmCommand::DOWN);
+    engine.register_fn("fire", || SwarmCommand::FIRE);
+    engine.register_fn("noop", || SwarmCommand::NOOP);
+    engine.register_fn("gather", || SwarmCommand::FORMATION(Formation::GATHER));
+    engine.register_fn("spread", || SwarmCommand::FORMATION(Formation::SPREAD));
+    // Turns are clamped to the range the swarm language allows
+    engine.register_fn("turn", |degrees: FLOAT| {
+        let max = MAX_TURN as FLOAT;
+        SwarmCommand::TURN(degrees.max(-max).min(max) as f32)
+    });
+    engine
+}
+
+/// A swarm's behavior written as a rhai script, instead of in the swarm language
+/// The script defines tick(me, enemy), which returns the command to run this tick
+/// me has x, y, direction, members, and experience; enemy has found, x, y,
+/// distance, bearing (degrees to turn to face it), and members
+#[derive(Clone, Debug)]
+pub struct SwarmScript {
+    /// The script as uploaded
+    source: String,
+    /// The compiled script
+    ast: AST,
+}
+
+/// Functions for SwarmScript
+impl SwarmScript {
+    /// Compiles a script
+    pub fn compile(source: &str) -> Result<Self, LanguageError> {
+        if source.len() > MAX_SCRIPT_LENGTH {
+            return Err(LanguageError::SCRIPT(format!(
+                "Script is too long: {} bytes, but at most {} are allowed.",
+                source.len(),
+                MAX_SCRIPT_LENGTH
+            )));
+        }
+        let ast = engine(&ExecutionLimits::default())
+            .compile(source)
+            .map_err(|error| LanguageError::SCRIPT(error.to_string()))?;
+        Ok(SwarmScript {
+            source: source.into(),
+            ast: ast,
+        })
+    }
+    /// The script as uploaded
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+    /// Runs the script for a swarm, with what it senses of the others
+    /// return: the command the swarm runs this tick
+    pub fn next_command(
+        &self,
+        engine: &Engine,
+        swarm_id: usize,
+        swarms: &Slab<Swarm>,
+    ) -> Result<SwarmCommand, ScriptFailure> {
+        let swarm = match swarms.get(&swarm_id) {
+            Some(swarm) => swarm,
+            None => return Ok(SwarmCommand::NOOP),
+        };
+        let me = SwarmScript::sense_self(swarm);
+        let enemy = SwarmScript::sense_enemy(swarm_id, swarm, swarms);
+        engine
+            .call_fn(&mut Scope::new(), &self.ast, TICK_FN, (me, enemy))
+            .map_err(|error| match *error {
+                EvalAltResult::ErrorTooManyOperations(_)
+                | EvalAltResult::ErrorStackOverflow(_)
+                | EvalAltResult::ErrorDataTooLarge(..) => {
+                    ScriptFailure::LIMIT(Limit::SCRIPT_OPERATIONS)
+                }
+                ref error => ScriptFailure::ERROR(error.to_string()),
+            })
+    }
+    /// What a swarm knows about itself
+    fn sense_self(swarm: &Swarm) -> Map {
+        let mut me = Map::new();
+        me.insert("x".into(), Dynamic::from(swarm.x as FLOAT));
+        me.insert("y".into(), Dynamic::from(swarm.y as FLOAT));
+        me.insert("direction".into(), Dynamic::from(swarm.direction as FLOAT));
+        me.insert("members".into(), Dynamic::from(swarm.members.len() as i64));
+        me.insert("experience".into(), Dynamic::from(swarm.experience));
+        me
+    }
+    /// What a swarm knows about the closest other swarm with members
+    fn sense_enemy(swarm_id: usize, swarm: &Swarm, swarms: &Slab<Swarm>) -> Map {
+        let closest = swarms
+            .iter()
+            .filter(|&(id, other)| *id != swarm_id && !other.members.is_empty())
+            .map(|(_, other)| {
+                let distance = ((other.x - swarm.x).powi(2) + (other.y - swarm.y).powi(2)).sqrt();
+                (distance, other)
+            })
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+        let mut enemy = Map::new();
+        enemy.insert("found".into(), Dynamic::from(closest.is_some()));
+        if let Some((distance, other)) = closest {
+            // Directions are counterclockwise, with y increasing downward
+            let heading = (swarm.y - other.y).atan2(other.x - swarm.x).to_degrees();
+            let mut bearing = (heading - swarm.direction) % 360.0;
+            if bearing > 180.0 {
+                bearing -= 360.0;
+            } else if bearing < -180.0 {
+                bearing += 360.0;
+            }
+            enemy.insert("x".into(), Dynamic::from(other.x as FLOAT));
+            enemy.insert("y".into(), Dynamic::from(other.y as FLOAT));
+            enemy.insert("distance".into(), Dynamic::from(distance as FLOAT));
+            enemy.insert("bearing".into(), Dynamic::from(bearing as FLOAT));
+            enemy.insert("members".into(), Dynamic::from(other.members.len() as i64));
+        }
+        enemy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_scripts() {
+        let mut swarms = Slab::new();
+        swarms.insert(1, Swarm::new(100.0, 100.0, 1));
+        let engine = engine(&ExecutionLimits::default());
+        let script = SwarmScript::compile(
+            "fn tick(me, enemy) { if enemy.found { fire() } else { turn(90.0) } }",
+        )
+        .unwrap();
+        // Turns are clamped, like in the swarm language
+        assert_eq!(
+            script.next_command(&engine, 1, &swarms),
+            Ok(SwarmCommand::TURN(MAX_TURN))
+        );
+        swarms.insert(2, Swarm::new(200.0, 100.0, 1));
+        assert_eq!(
+            script.next_command(&engine, 1, &swarms),
+            Ok(SwarmCommand::FIRE)
+        );
+        // Scripts that never return are stopped
+        let spin = SwarmScript::compile("fn tick(me, enemy) { loop { } }").unwrap();
+        assert_eq!(
+            spin.next_command(&engine, 1, &swarms),
+            Err(ScriptFailure::LIMIT(Limit::SCRIPT_OPERATIONS))
+        );
+        assert!(SwarmScript::compile("fn tick(me, enemy) {").is_err());
+    }
+}
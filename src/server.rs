@@ -15,19 +15,117 @@
 // along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
 extern crate serde_json;
 
-use futures::{Future, Sink, Stream};
-use rpc::{CompileRequest, CompileResult, Configuration, Response, ResponseMessage, Vec2};
+use autosave::{self, FileSaves, SaveStore};
+use budget::TickBudget;
+use console;
+use control;
+use error::ParseError;
+use futures::{stream, Future, Sink, Stream};
+use ghost::GhostRecorder;
+use rpc::{
+    ClientMessage, ColorRemap, CompileResult, Configuration, PluginMessage, Response,
+    ResponseMessage, SquadProgramResult, SquadResult, Vec2,
+};
+use sandbox;
 use std::fmt::Debug;
+use std::net::SocketAddr;
+#[cfg(feature = "tls")]
+use std::net::ToSocketAddrs;
 use std::ops::DerefMut;
 use std::sync::{Arc, RwLock};
 use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 use std::thread;
 use std::time::Duration;
+use snapshot::SnapshotCache;
+#[cfg(feature = "scripting")]
+use script::SwarmScript;
 use swarm_language::SwarmProgram;
+use telemetry::{LogSink, Telemetry};
+#[cfg(feature = "tls")]
+use tls::{self, TlsConfig};
+#[cfg(feature = "tls")]
+use tokio_core::net::TcpListener;
 use tokio_core::reactor::{Core, Handle};
+#[cfg(feature = "tls")]
+use tokio_rustls::ServerConfigExt;
 use websocket::message::{Message, OwnedMessage};
-use websocket::async::Server;
-use world::World;
+use websocket::result::WebSocketError;
+use websocket::async::{Server, Stream as AsyncStream};
+#[cfg(feature = "tls")]
+use websocket::server::upgrade::async::IntoWs;
+use websocket::server::upgrade::async::Upgrade;
+#[cfg(feature = "webhooks")]
+use webhook::{WebhookConfig, Webhooks};
+use world::{World, WorldConfig};
+use world_manager::{room_from_path, WorldManager, DEFAULT_ROOM};
+
+/// Parameters used to run the server
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// Hostname for the websocket to listen on
+    pub hostname: String,
+    /// Port for the websocket to listen on
+    pub port: u16,
+    /// Number of server updates per second
+    pub update_freq: u64,
+    /// Dimensions of the default room
+    pub world: WorldConfig,
+    /// Whether to read admin commands from stdin
+    pub console: bool,
+    /// Loopback address to accept admin console connections on, if any
+    pub console_address: Option<String>,
+    /// Address to accept control plane (JSON-RPC) connections on, if any
+    pub control_address: Option<String>,
+    /// Secret shared with sibling servers for signing handoff tokens
+    /// Handoffs are disabled without one
+    pub handoff_secret: Option<String>,
+    /// Longest an update should take, if different from the time between updates
+    pub tick_budget: Option<Duration>,
+    /// Whether rooms that are repeatedly over budget skip optional work
+    pub shed_load: bool,
+    /// Ticks covered by each anonymous telemetry report written to the log
+    /// Telemetry is off without one
+    pub telemetry: Option<u64>,
+    /// File every room is saved to, so matches survive a crash
+    /// Autosaves are off without one
+    pub autosave: Option<String>,
+    /// Time between autosaves
+    pub autosave_interval: Duration,
+    /// Whether to restore the rooms in the autosave file when starting
+    pub restore: bool,
+    /// Certificates used to serve wss:// directly, if any
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsConfig>,
+    /// Where to send match lifecycle events, if anywhere
+    #[cfg(feature = "webhooks")]
+    pub webhooks: Option<WebhookConfig>,
+}
+
+/// Default server parameters
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            hostname: "0.0.0.0".into(),
+            port: 8080,
+            update_freq: 60,
+            world: WorldConfig::new(1600.0, 900.0),
+            console: false,
+            console_address: None,
+            control_address: None,
+            handoff_secret: None,
+            tick_budget: None,
+            shed_load: false,
+            telemetry: None,
+            autosave: None,
+            autosave_interval: Duration::from_secs(autosave::DEFAULT_AUTOSAVE_INTERVAL),
+            restore: false,
+            #[cfg(feature = "tls")]
+            tls: None,
+            #[cfg(feature = "webhooks")]
+            webhooks: None,
+        }
+    }
+}
 
 /// Represents a server for the game
 // TODO: populate this with parameters
@@ -61,63 +159,128 @@ impl GameServer {
     }*/
     /// Starts the server
     pub fn start() {}
+    /// Takes the achievements a player unlocked, as messages to send after the reply
+    /// to their last message, so unlocks never take the place of a reply
+    pub fn take_unlocks(player_id: usize, world: &Arc<RwLock<World>>) -> Vec<OwnedMessage> {
+        let unlocked = match world.read() {
+            Ok(world) => world
+                .achievements
+                .as_ref()
+                .map_or(false, |achievements| achievements.has_unlocks(player_id)),
+            Err(_) => false,
+        };
+        if !unlocked {
+            return Vec::new();
+        }
+        let achievements = match world.write() {
+            Ok(mut write_lock) => match write_lock.achievements {
+                Some(ref mut achievements) => achievements.take_unlocks(player_id),
+                None => Vec::new(),
+            },
+            Err(error) => {
+                warn!("Failed to get write lock on world. Not sending unlocks");
+                return Vec::new();
+            }
+        };
+        achievements
+            .into_iter()
+            .filter_map(|achievement| {
+                match Response::new(ResponseMessage::ACHIEVEMENT(achievement)).serialize() {
+                    Ok(message) => Some(OwnedMessage::Text(message)),
+                    Err(error) => {
+                        error!("Failed to serialize achievement: {}", error);
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
     /// Handles an incoming websocket message
-    fn handle_message(
+    /// Public so in-process clients like the test harness go through the same path
+    pub fn handle_message(
         message: OwnedMessage,
         player_id: usize,
         world: &Arc<RwLock<World>>,
+        snapshots: &SnapshotCache,
+        manager: &Arc<RwLock<WorldManager>>,
     ) -> Option<OwnedMessage> {
         match message {
             // Handle incoming text data
             OwnedMessage::Text(data) => {
-                // Try to parse it as a request for updates
-                match serde_json::from_str::<Vec<Vec2>>(&data) {
-                    Ok(coords) => match world.read() {
-                        Ok(world) => {
-                            // Create a message type
-                            let message = Response::new(ResponseMessage::WORLD(world.get_state()));
+                // Tell players that were handed off where to go
+                let redirect = match world.read() {
+                    Ok(world) => world.pending_handoffs.contains_key(&player_id),
+                    Err(_) => false,
+                };
+                if redirect {
+                    if let Ok(mut write_lock) = world.write() {
+                        if let Some(redirect) = write_lock.pending_handoffs.remove(&player_id) {
+                            let message = Response::new(ResponseMessage::HANDOFF(redirect));
                             match message.serialize() {
                                 Ok(message) => return Some(OwnedMessage::Text(message)),
-                                Err(error) => {}
+                                Err(_) => {}
                             }
                         }
+                    }
+                }
+                // Parse the request, then handle it by type
+                let request: ClientMessage = match serde_json::from_str(&data) {
+                    Ok(request) => request,
+                    Err(_) => {
+                        debug!("Failed to parse request: {}", data);
+                        return None;
+                    }
+                };
+                let serialized = match request {
+                    // A request for updates
+                    // The state is serialized once per tick and shared between connections,
+                    // unless fog of war hides part of it from each player.
+                    // The websocket codec needs an owned message, so only the bytes are copied
+                    ClientMessage::VIEWPORT(ref coords) if !coords.iter().all(Vec2::is_finite) => {
+                        warn!("Player {} sent a viewport that isn't finite", player_id);
+                        return None;
+                    }
+                    ClientMessage::VIEWPORT(coords) => match world.read() {
+                        Ok(world) => snapshots
+                            .get_for(&world, player_id)
+                            .map(|message| (*message).clone()),
                         Err(error) => {
                             warn!("Failed to get read lock on world. Not sending world state");
                             return None;
                         }
                     },
-                    Err(_) => debug!("Failed to parse request as a viewport"),
-                };
-                // Try to parse it as a request for compilation
-                match serde_json::from_str::<CompileRequest>(&data) {
-                    Ok(compile_request) => match world.write() {
+                    // A request for compilation
+                    ClientMessage::COMPILE(compile_request) => match world.write() {
                         Ok(mut write_lock) => {
                             let world_ref = write_lock.deref_mut();
                             info!("Compile request: {}", data);
                             // Create a message type
                             match compile_request.program.parse::<SwarmProgram>() {
                                 Ok(compiled_program) => {
+                                    let profile = compiled_program.profile();
+                                    if profile.suspicious {
+                                        warn!(
+                                            "Player {} uploaded a suspicious program: {:?}",
+                                            player_id, profile
+                                        );
+                                    }
                                     // Update the program
                                     world_ref.update_program(player_id, compiled_program);
                                     // Respond with success
-                                    let message = Response::new(ResponseMessage::COMPILE(
-                                        CompileResult::new(true, "".into()),
-                                    ));
-                                    match message.serialize() {
-                                        Ok(message) => return Some(OwnedMessage::Text(message)),
-                                        Err(_) => {}
-                                    }
+                                    Response::new(ResponseMessage::COMPILE(CompileResult::new(
+                                        true,
+                                        "".into(),
+                                    )))
+                                    .serialize()
                                 }
                                 Err(error) => {
                                     info!("Failed to compile program: {}", error);
                                     // Generate an output message
-                                    let message = Response::new(ResponseMessage::COMPILE(
-                                        CompileResult::new(false, error.to_string()),
-                                    ));
-                                    match message.serialize() {
-                                        Ok(message) => return Some(OwnedMessage::Text(message)),
-                                        Err(_) => {}
-                                    }
+                                    // The old program keeps running
+                                    Response::new(ResponseMessage::COMPILE(
+                                        CompileResult::rejected(&error, &compile_request.program),
+                                    ))
+                                    .serialize()
                                 }
                             }
                         }
@@ -126,10 +289,265 @@ impl GameServer {
                             return None;
                         }
                     },
-                    Err(_) => debug!("Failed to parse request as a viewport"),
+                    // Programs for the player's squad, run together or not at all
+                    ClientMessage::SQUAD(squad_request) => {
+                        let compiled: Vec<Result<SwarmProgram, ParseError>> = squad_request
+                            .squad
+                            .iter()
+                            .map(|entry| entry.program.parse())
+                            .collect();
+                        let results = squad_request
+                            .squad
+                            .iter()
+                            .zip(compiled.iter())
+                            .map(|(entry, compiled)| SquadProgramResult {
+                                name: entry.name.clone(),
+                                player_id: entry.player_id,
+                                result: match *compiled {
+                                    Ok(_) => CompileResult::new(true, "".into()),
+                                    Err(ref error) => {
+                                        CompileResult::rejected(error, &entry.program)
+                                    }
+                                },
+                            })
+                            .collect();
+                        let mut result = SquadResult::new(results);
+                        if result.success {
+                            let programs: Vec<(usize, SwarmProgram)> = squad_request
+                                .squad
+                                .iter()
+                                .zip(compiled.into_iter())
+                                .filter_map(|(entry, compiled)| {
+                                    compiled.ok().map(|program| (entry.player_id, program))
+                                })
+                                .collect();
+                            for &(squadmate, ref program) in programs.iter() {
+                                let profile = program.profile();
+                                if profile.suspicious {
+                                    warn!(
+                                        "Player {} uploaded a suspicious program for {}: {:?}",
+                                        player_id, squadmate, profile
+                                    );
+                                }
+                            }
+                            match world.write() {
+                                Ok(mut write_lock) => {
+                                    match write_lock.update_squad_programs(player_id, programs) {
+                                        Ok(()) => {}
+                                        Err(error) => {
+                                            info!("Failed to update squad programs: {}", error);
+                                            result = result.refused(&error);
+                                        }
+                                    }
+                                }
+                                Err(error) => {
+                                    warn!("Failed to get write lock on world. Not updating squad");
+                                    return None;
+                                }
+                            }
+                        }
+                        Response::new(ResponseMessage::SQUAD(result)).serialize()
+                    }
+                    // A command for a practice room
+                    ClientMessage::SANDBOX(sandbox_request) => {
+                        match world.write() {
+                            Ok(mut write_lock) => {
+                                let world_ref = write_lock.deref_mut();
+                                if !sandbox::apply(world_ref, &sandbox_request.sandbox) {
+                                    info!("Player {} isn't in a practice room", player_id);
+                                }
+                            }
+                            Err(error) => warn!(
+                                "Failed to get write lock on world. Not running practice command"
+                            ),
+                        }
+                        return None;
+                    }
+                    // A request to drive the swarm with a script, answered like a compile request
+                    #[cfg(feature = "scripting")]
+                    ClientMessage::SCRIPT(script_request) => {
+                        let result = match SwarmScript::compile(&script_request.script) {
+                            Ok(script) => {
+                                match world.write() {
+                                    Ok(mut write_lock) => {
+                                        match write_lock.update_script(player_id, script) {
+                                            Ok(()) => {}
+                                            Err(error) => info!("Failed to set script: {}", error),
+                                        }
+                                    }
+                                    Err(error) => {
+                                        warn!("Failed to get write lock. Not setting script");
+                                        return None;
+                                    }
+                                }
+                                CompileResult::new(true, "".into())
+                            }
+                            Err(error) => {
+                                info!("Failed to compile script: {}", error);
+                                CompileResult::failed(&error)
+                            }
+                        };
+                        Response::new(ResponseMessage::COMPILE(result)).serialize()
+                    }
+                    #[cfg(not(feature = "scripting"))]
+                    ClientMessage::SCRIPT(_) => {
+                        let error = "This server doesn't run scripts".into();
+                        Response::new(ResponseMessage::COMPILE(CompileResult::new(false, error)))
+                            .serialize()
+                    }
+                    // A player coming back after the server restarted, which isn't answered
+                    ClientMessage::RESUME(resume_request) => {
+                        let snapshot = match manager.write() {
+                            Ok(mut manager) => manager.resume(&resume_request.resume_token),
+                            Err(error) => {
+                                warn!("Failed to get write lock on rooms. Not resuming player");
+                                return None;
+                            }
+                        };
+                        match snapshot {
+                            Ok(snapshot) => match world.write() {
+                                Ok(mut write_lock) => {
+                                    match write_lock.restore_player(player_id, &snapshot) {
+                                        Ok(_) => {}
+                                        Err(error) => info!("Failed to resume player: {}", error),
+                                    }
+                                }
+                                Err(error) => {
+                                    warn!("Failed to get write lock on world. Not resuming player")
+                                }
+                            },
+                            Err(error) => info!("Rejected resume token: {}", error),
+                        }
+                        return None;
+                    }
+                    // Direct control of the swarm, which isn't answered
+                    ClientMessage::INPUT(input_request) => {
+                        match world.write() {
+                            Ok(mut write_lock) => {
+                                match write_lock.control(player_id, &input_request.input) {
+                                    Ok(()) => {}
+                                    Err(error) => info!("Failed to apply input: {}", error),
+                                }
+                            }
+                            Err(error) => {
+                                warn!("Failed to get write lock on world. Not applying input");
+                            }
+                        }
+                        return None;
+                    }
+                    // Pausing the program, which isn't answered
+                    ClientMessage::PAUSE(pause_request) => {
+                        match world.write() {
+                            Ok(mut write_lock) => {
+                                match write_lock.set_paused(player_id, pause_request.pause) {
+                                    Ok(()) => {}
+                                    Err(error) => info!("Failed to pause program: {}", error),
+                                }
+                            }
+                            Err(error) => {
+                                warn!("Failed to get write lock on world. Not pausing program");
+                            }
+                        }
+                        return None;
+                    }
+                    // A ping for the player's team, sent out with the next world state
+                    ClientMessage::PING(ping_request) => {
+                        let position = ping_request.ping;
+                        match world.write() {
+                            Ok(mut write_lock) => {
+                                match write_lock.ping(player_id, position.x(), position.y()) {
+                                    Ok(()) => {}
+                                    Err(error) => info!("Failed to ping: {}", error),
+                                }
+                            }
+                            Err(error) => {
+                                warn!("Failed to get write lock on world. Not pinging");
+                            }
+                        }
+                        return None;
+                    }
+                    // Choosing a squad leader, which isn't answered
+                    ClientMessage::LEADER(leader_request) => {
+                        match world.write() {
+                            Ok(mut write_lock) => {
+                                match write_lock
+                                    .follow_leader(player_id, leader_request.squad_leader)
+                                {
+                                    Ok(()) => {}
+                                    Err(error) => info!("Failed to follow leader: {}", error),
+                                }
+                            }
+                            Err(error) => {
+                                warn!("Failed to get write lock on world. Not following leader");
+                            }
+                        }
+                        return None;
+                    }
+                    // A request for colors to be shown in another palette
+                    ClientMessage::PALETTE(palette_request) => match world.read() {
+                        Ok(world) => Response::new(ResponseMessage::REMAP(ColorRemap::new(
+                            world.palette,
+                            palette_request.palette,
+                        )))
+                        .serialize(),
+                        Err(error) => {
+                            warn!("Failed to get read lock on world. Not remapping colors");
+                            return None;
+                        }
+                    },
+                    // A message for a plugin, which may reply
+                    ClientMessage::PLUGIN(message) => {
+                        let reply = match world.write() {
+                            Ok(mut write_lock) => {
+                                write_lock.plugin_message(player_id, &message.plugin, &message.data)
+                            }
+                            Err(error) => {
+                                warn!("Failed to get write lock on world. Not handling message");
+                                return None;
+                            }
+                        };
+                        match reply {
+                            Some(data) => Response::new(ResponseMessage::PLUGIN(PluginMessage {
+                                plugin: message.plugin,
+                                data: data,
+                            }))
+                            .serialize(),
+                            None => return None,
+                        }
+                    }
+                    // A player arriving from another server
+                    ClientMessage::HANDOFF(handoff_request) => {
+                        let snapshot = match manager.read() {
+                            Ok(manager) => manager.redeem_handoff(&handoff_request.handoff_token),
+                            Err(error) => {
+                                warn!("Failed to get read lock on rooms. Not redeeming handoff");
+                                return None;
+                            }
+                        };
+                        match snapshot {
+                            Ok(snapshot) => match world.write() {
+                                Ok(mut write_lock) => {
+                                    match write_lock.restore_player(player_id, &snapshot) {
+                                        Ok(_) => {}
+                                        Err(error) => info!("Failed to restore player: {}", error),
+                                    }
+                                }
+                                Err(error) => {
+                                    warn!("Failed to get write lock on world. Not restoring player")
+                                }
+                            },
+                            Err(error) => info!("Rejected handoff token: {}", error),
+                        }
+                        return None;
+                    }
                 };
-                // If it matches none of the cases, just return None
-                None
+                match serialized {
+                    Ok(message) => Some(OwnedMessage::Text(message)),
+                    Err(error) => {
+                        error!("Failed to serialize response: {}", error);
+                        None
+                    }
+                }
             }
             // Handle incoming binary data
             OwnedMessage::Binary(_) => None,
@@ -145,56 +563,160 @@ impl GameServer {
 
 /// Runs the server
 // TODO: Move all of this into impl for GameServer
-pub fn run() {
+pub fn run(config: ServerConfig) {
     // Server parameters
-    let hostname = "0.0.0.0";
-    let port: u16 = 8080;
-    let update_freq: u64 = 60;
-    // Create the world
-    let world: Arc<RwLock<World>> = Arc::new(RwLock::new(World::new(1600.0, 900.0)));
-    // Copy a reference to world for the clients to use
-    let world_client = world.clone();
-    // Start the world's main thread
-    thread::spawn(move || {
-        // TODO: nanoseconds accuracy for this
-        let update_delta = Duration::from_micros(1000000 / update_freq);
-        // Get reference to the world
-        let world = world.clone();
-        // Elapsed time of last update
-        let mut last_update_time = Duration::from_millis(0);
-        // Main loop
-        loop {
-            // Log time elapsed in previous update
-            debug!(
-                "Last update took {}s, {}ns",
-                last_update_time.as_secs(),
-                last_update_time.subsec_nanos()
-            );
-            // Sleep for some amount of time
-            if last_update_time <= update_delta {
-                thread::sleep(update_delta - last_update_time);
-            } else {
-                // If the thread update took too long
-                warn!("Update thread took too long!")
-            }
-            // Lock the world for writing
-            match world.write() {
+    let hostname = config.hostname.as_str();
+    let port: u16 = config.port;
+    let update_freq: u64 = config.update_freq;
+    // Create the rooms, starting with the default one
+    let manager: Arc<RwLock<WorldManager>> = match WorldManager::new(config.world, update_freq) {
+        Ok(manager) => Arc::new(RwLock::new(manager)),
+        Err(error) => {
+            error!("Invalid world: {}", error);
+            return;
+        }
+    };
+    // Warn about, and optionally shed load from, rooms that can't keep up
+    let tick_budget = match config.tick_budget {
+        Some(limit) => TickBudget::new(limit),
+        None => TickBudget::from_update_freq(update_freq),
+    }.with_shed_load(config.shed_load);
+    match manager.write() {
+        Ok(mut write_lock) => write_lock.set_tick_budget(tick_budget),
+        Err(error) => error!("Error getting write lock on rooms: {}", error),
+    }
+    // Allow handoffs between sibling servers
+    if let Some(ref secret) = config.handoff_secret {
+        match manager.write() {
+            Ok(mut write_lock) => write_lock.set_handoff_secret(secret.as_bytes().to_vec()),
+            Err(error) => error!("Error getting write lock on rooms: {}", error),
+        }
+    }
+    // Record runs in races and practice rooms, so players can race their ghosts
+    match manager.write() {
+        Ok(mut write_lock) => write_lock.add_plugin(Arc::new(GhostRecorder::new())),
+        Err(error) => error!("Error getting write lock on rooms: {}", error),
+    }
+    // Log gameplay metrics if the operator opted in
+    if let Some(interval) = config.telemetry {
+        let telemetry = Telemetry::new(Box::new(LogSink)).with_interval(interval);
+        match manager.write() {
+            Ok(mut write_lock) => write_lock.add_plugin(Arc::new(telemetry)),
+            Err(error) => error!("Error getting write lock on rooms: {}", error),
+        }
+    }
+    // Send events to webhooks if they were configured
+    #[cfg(feature = "webhooks")]
+    {
+        if let Some(ref webhook_config) = config.webhooks {
+            match manager.write() {
                 Ok(mut write_lock) => {
-                    // Get a mutable reference to the world
-                    let world_ref = write_lock.deref_mut();
-                    // Update the world
-                    last_update_time = world_ref.update();
-                    // Write lock goes out of scope, world is again available to be read
+                    write_lock.add_listener(Box::new(Webhooks::new(webhook_config.clone())))
                 }
-                Err(error) => error!("Error retrieving write lock in update thread: {}", error),
+                Err(error) => error!("Error getting write lock on rooms: {}", error),
             }
         }
-    });
+    }
+    // Copy a reference to the rooms for the clients to use
+    let manager_client = manager.clone();
+    // Start the admin consoles, which manage the default room
+    let default_world = manager
+        .read()
+        .ok()
+        .and_then(|manager| manager.room(DEFAULT_ROOM))
+        .expect("Failed to create the default room");
+    if config.console {
+        let world = default_world.clone();
+        thread::spawn(move || console::run_stdin(world));
+    }
+    if let Some(console_address) = config.console_address.clone() {
+        let world = default_world.clone();
+        thread::spawn(move || match console::run_tcp(&console_address, world) {
+            Ok(_) => {}
+            Err(error) => error!("Failed to start admin console: {}", error),
+        });
+    }
+    // Start the control plane
+    if let Some(control_address) = config.control_address.clone() {
+        let manager = manager.clone();
+        thread::spawn(move || match control::run_tcp(&control_address, manager) {
+            Ok(_) => {}
+            Err(error) => error!("Failed to start control plane: {}", error),
+        });
+    }
+    // Save the rooms periodically, and restore the last save if asked to
+    let mut first_player_id = 0;
+    if let Some(ref path) = config.autosave {
+        let store: Arc<SaveStore> = Arc::new(FileSaves::new(path.as_str()));
+        match store.latest() {
+            Ok(Some(save)) => if config.restore {
+                first_player_id = save.next_player_id();
+                match manager.write() {
+                    Ok(mut write_lock) => match write_lock.restore(save) {
+                        Ok(_) => {}
+                        Err(error) => error!("Failed to restore rooms: {}", error),
+                    },
+                    Err(error) => error!("Error getting write lock on rooms: {}", error),
+                }
+            } else {
+                info!(
+                    "Found {} rooms saved at {} in {}. Start with --restore to restore them",
+                    save.rooms.len(),
+                    save.saved_at,
+                    path
+                );
+            },
+            Ok(None) => {}
+            Err(error) => error!("Failed to read autosave: {}", error),
+        }
+        autosave::spawn(&manager, store, config.autosave_interval);
+    }
     // Used to assign IDs to connections (players)
-    let id_counter: AtomicUsize = AtomicUsize::new(0);
+    // Saved players' IDs are skipped, so their resume tokens stay theirs
+    let id_counter: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(first_player_id));
     // Used for serving
     let mut core = Core::new().expect("Failed to initialize core");
     let handle = core.handle();
+    // Serve over TLS if it was configured
+    #[cfg(feature = "tls")]
+    {
+        if let Some(ref tls_config) = config.tls {
+            let acceptor = tls::build_config(tls_config).expect("Failed to configure TLS");
+            // Bind to an address
+            let address = format!("{}:{}", hostname, port)
+                .to_socket_addrs()
+                .ok()
+                .and_then(|mut addresses| addresses.next())
+                .expect("Failed to resolve the address");
+            let listener =
+                TcpListener::bind(&address, &handle).expect("Failed to bind to an address");
+            // Handles a stream of incoming connections, performing the TLS handshake
+            // and websocket upgrade before handing them off like any other connection
+            let server_future = listener.incoming().for_each(move |(stream, addr)| {
+                let manager = manager_client.clone();
+                let id_counter = id_counter.clone();
+                let connection_handle = handle.clone();
+                let upgrade_future = acceptor
+                    .accept_async(stream)
+                    .map_err(move |error| info!("TLS handshake with {} failed: {}", addr, error))
+                    .and_then(move |stream| {
+                        stream
+                            .into_ws()
+                            .map_err(move |(_, _, _, error)| {
+                                info!("Websocket upgrade from {} failed: {}", addr, error)
+                            })
+                    })
+                    .map(move |upgrade| {
+                        accept_connection(upgrade, addr, &manager, &id_counter, &connection_handle)
+                    });
+                handle.spawn(upgrade_future);
+                Ok(())
+            });
+            info!("Starting the server at wss://{}:{}", hostname, port);
+            core.run(server_future).expect("Failed to start server");
+            return;
+        }
+    }
     // Bind to an address
     let server = Server::bind(format!("{}:{}", hostname, port), &handle)
         .expect("Failed to bind to an address");
@@ -208,96 +730,161 @@ pub fn run() {
         //.map_err(move |InvalidConnection { error, .. }| error)
         // Handle connections
         .for_each(move |(upgrade, addr)| {
-            // Log the connection
-            info!("Got a connection from: {}", addr);
-            // Verify protocol
-            if !upgrade.protocols().iter().any(|protocol| protocol == "heroesoftheswarm") {
-                // Reject connecitons that don't have the supported protocol
-                spawn_future(upgrade.reject(), "Upgrade Rejection", &handle);
-                return Ok(());
+            accept_connection(upgrade, addr, &manager_client, &id_counter, &handle);
+            Ok(())
+        });
+    info!("Starting the server at {}:{}", hostname, port);
+    core.run(server_future).expect("Failed to start server");
+}
+
+/// Handles a websocket upgrade request from a client
+/// Generic over the underlying stream so plain and TLS connections are handled the same way
+fn accept_connection<S>(
+    upgrade: Upgrade<S>,
+    addr: SocketAddr,
+    manager: &Arc<RwLock<WorldManager>>,
+    id_counter: &AtomicUsize,
+    handle: &Handle,
+) where
+    S: AsyncStream + 'static,
+{
+    // Log the connection
+    info!("Got a connection from: {}", addr);
+    // Verify protocol
+    if !upgrade.protocols().iter().any(|protocol| protocol == "heroesoftheswarm") {
+        // Reject connecitons that don't have the supported protocol
+        spawn_future(upgrade.reject(), "Upgrade Rejection", handle);
+        return;
+    }
+    // Find the room the client asked for, turning away banned addresses
+    let room_id = room_from_path(&upgrade.request.subject.1.to_string());
+    let room = match manager.read() {
+        Ok(manager) => if manager.is_banned(&addr.ip()) {
+            info!("Rejecting banned address {}", addr);
+            None
+        } else {
+            room_id.and_then(|room_id| match (manager.room(room_id), manager.snapshots(room_id)) {
+                (Some(world), Some(snapshots)) => Some((world, snapshots)),
+                _ => None,
+            })
+        },
+        Err(error) => {
+            error!("Error getting read lock on rooms: {}", error);
+            None
+        }
+    };
+    let (room_id, world, snapshots) = match (room_id, room) {
+        (Some(room_id), Some((world, snapshots))) => (room_id, world, snapshots),
+        _ => {
+            spawn_future(upgrade.reject(), "Upgrade Rejection", handle);
+            return;
+        }
+    };
+    // Get a reference to the world for this connection
+    let w = world.clone();
+    let m = manager.clone();
+    let message_manager = manager.clone();
+    // Get an ID for this connection
+    let session_id: usize = id_counter.fetch_add(1, AtomicOrdering::SeqCst);
+    let resume_token = match manager.read() {
+        Ok(manager) => manager.resume_token(session_id),
+        Err(_) => None,
+    };
+    // Create a swarm for this session
+    match world.write() {
+        Ok(mut write_lock) => {
+            // Get a mutable reference to the world
+            let world_ref = write_lock.deref_mut();
+            if let Err(error) = world_ref.add_player(session_id) {
+                error!("Error adding player: {}", error);
+                spawn_future(upgrade.reject(), "Failed to add player to world", handle);
+                return;
             }
-            // Get a reference to the world for this connection
-            let world = world_client.clone();
-            let w = world.clone();
-            // Get an ID for this connection
-            let session_id: usize = id_counter.fetch_add(1, AtomicOrdering::SeqCst);
-            // Create a swarm for this session
-            match world.write() {
-                Ok(mut write_lock) => {
-                    // Get a mutable reference to the world
-                    let world_ref = write_lock.deref_mut();
-                    world_ref.add_player(session_id);
-                    // Write lock goes out of scope, world is again available to be read
-                },
+            // Write lock goes out of scope, world is again available to be read
+        },
+        Err(error) => {
+            error!("Error getting write lock: {}. Player not added", error);
+            spawn_future(upgrade.reject(), "Failed to add player to world", handle);
+            return;
+        }
+    }
+    match manager.write() {
+        Ok(mut write_lock) => write_lock.register_player(session_id, room_id, addr.ip()),
+        Err(error) => error!("Error getting write lock on rooms: {}", error),
+    }
+    // accept the request to be a ws connection if it does
+    let message_handler = upgrade
+        // Use our protocol
+        .use_protocol("heroesoftheswarm")
+        // Accept the message
+        .accept()
+        // Respond so the client knows the connection succeeded 
+        .and_then(move |(socket, _)| {
+            //socket.send(Message::text(session_id.to_string()).into());
+            // Create a config object and send it to the client
+            let config = Configuration::new(session_id).with_resume_token(resume_token);
+            // Create a response
+            let response = Response::new(ResponseMessage::CONFIG(config));
+            match response.serialize() {
+                Ok(serialized) => socket.send(Message::text(serialized).into()),
                 Err(error) => {
-                    error!("Error getting write lock: {}. Player not added", error);
-                    spawn_future(upgrade.reject(), "Failed to add player to world", &handle);
-                    return Ok(());
+                    error!("Failed to serialize config");
+                    socket.send(Message::text(r#"{"mt": "error", "message": {"error": "Failed to serialize config"}}"#).into())
                 }
             }
-            // accept the request to be a ws connection if it does
-            let message_handler = upgrade
-                // Use our protocol
-                .use_protocol("heroesoftheswarm")
-                // Accept the message
-                .accept()
-                // Respond so the client knows the connection succeeded 
-                .and_then(move |(socket, _)| {
-                    //socket.send(Message::text(session_id.to_string()).into());
-                    // Create a config object and send it to the client
-                    let config = Configuration::new(session_id); 
-                    // Create a response
-                    let response = Response::new(ResponseMessage::CONFIG(config));
-                    match response.serialize() {
-                        Ok(serialized) => socket.send(Message::text(serialized).into()),
+        })
+        // Build a message responder
+        .and_then(move |socket| {
+            // Get sink and stream
+            let (sink, stream) = socket.split();
+            stream
+                // For all messages until the connection closes
+                .take_while(move |message| Ok(!message.is_close()))
+                // Handle the input and generate output
+                .map(move |message| {
+                    // Log the message
+                    debug!("Message from Client {}: {:?}", session_id, message);
+                    // Handle the message by type, then follow the reply with any unlocks
+                    let mut replies: Vec<OwnedMessage> = GameServer::handle_message(
+                        message,
+                        session_id,
+                        &world,
+                        &snapshots,
+                        &message_manager,
+                    )
+                    .into_iter()
+                    .collect();
+                    replies.extend(GameServer::take_unlocks(session_id, &world));
+                    stream::iter_ok::<_, WebSocketError>(replies)
+                })
+                .flatten()
+                .forward(sink)
+                .and_then(move |(_, sink)| {
+
+                    // Delete the swarm from this session
+                    match w.write() {
+                        Ok(mut write_lock) => {
+                            // Get a mutable reference to the world
+                            let world_ref = write_lock.deref_mut();
+                            // Remove the player
+                            world_ref.remove_player(session_id);
+                            // Write lock goes out of scope, world is again available to be read
+                        },
                         Err(error) => {
-                            error!("Failed to serialize config");
-                            socket.send(Message::text(r#"{"mt": "error", "message": {"error": "Failed to serialize config"}}"#).into())
+                            error!("Error getting write lock: {}. Player not removed", error);
+                        
                         }
-                    }
+                    };
+                    match m.write() {
+                        Ok(mut write_lock) => write_lock.unregister_player(session_id),
+                        Err(error) => error!("Error getting write lock on rooms: {}", error),
+                    };
+                    // Send the close message
+                    sink.send(OwnedMessage::Close(None))
                 })
-                // Build a message responder
-                .and_then(move |socket| {
-                    // Get sink and stream
-                    let (sink, stream) = socket.split();
-                    stream
-                        // For all messages until the connection closes
-                        .take_while(move |message| Ok(!message.is_close()))
-                        // Handle the input and generate output
-                        .filter_map(move |message| {
-                            // Log the message
-                            debug!("Message from Client {}: {:?}", session_id, message);
-                            // Handle the message by type
-                            GameServer::handle_message(message, session_id, &world)
-                        })
-                        .forward(sink)
-                        .and_then(move |(_, sink)| {
-
-                            // Delete the swarm from this session
-                            match w.write() {
-                                Ok(mut write_lock) => {
-                                    // Get a mutable reference to the world
-                                    let world_ref = write_lock.deref_mut();
-                                    // Remove the player
-                                    world_ref.remove_player(session_id);
-                                    // Write lock goes out of scope, world is again available to be read
-                                },
-                                Err(error) => {
-                                    error!("Error getting write lock: {}. Player not removed", error);
-                                
-                                }
-                            };
-                            // Send the close message
-                            sink.send(OwnedMessage::Close(None))
-                        })
-                });
-
-            spawn_future(message_handler, "Client Status", &handle);
-            
-            Ok(())
         });
-    info!("Starting the server at {}:{}", hostname, port);
-    core.run(server_future).expect("Failed to start server");
+
+    spawn_future(message_handler, "Client Status", handle);
 }
 
 // TODO: learn what this does and how it works
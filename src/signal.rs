@@ -0,0 +1,137 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use entity::Swarm;
+use event::WorldEvent;
+use game_mode::{GameMode, Team};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use slab::Slab;
+
+/// What a player signalled
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum SignalKind {
+    /// The player's program ran EMOTE with the emote's number
+    /// Clients decide what each number looks like
+    #[serde(rename = "emote")]
+    EMOTE(u32),
+    /// The player marked a position for their team
+    #[serde(rename = "ping")]
+    PING,
+}
+
+/// A short-lived message from a player, for clients to show without chat
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct Signal {
+    /// The tick the signal was made on, so clients that see a tick twice show it once
+    pub tick: u64,
+    /// ID of the player that made it
+    pub player: usize,
+    /// What they signalled
+    pub kind: SignalKind,
+    /// X position of the swarm that emoted, or of the ping
+    pub x: f32,
+    /// Y position of the swarm that emoted, or of the ping
+    pub y: f32,
+    /// Team of the player that pinged, whose members are the only ones to show it
+    /// Left out for emotes, which everyone sees, and for pings in modes without teams,
+    /// which only the player that pinged sees
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<Team>,
+}
+
+/// Functions for Signal
+impl Signal {
+    /// Constructor
+    pub fn new(tick: u64, player: usize, kind: SignalKind, x: f32, y: f32) -> Self {
+        Signal {
+            tick: tick,
+            player: player,
+            kind: kind,
+            x: x,
+            y: y,
+            team: None,
+        }
+    }
+    /// Sets the team the signal is shown to
+    pub fn with_team(mut self, team: Option<Team>) -> Self {
+        self.team = team;
+        self
+    }
+}
+
+/// Turns a tick's emotes and pings into signals
+/// events: the events of the tick
+pub fn collect_signals(
+    tick: u64,
+    events: &[WorldEvent],
+    swarms: &Slab<Swarm>,
+    mode: &GameMode,
+    signals: &mut Vec<Signal>,
+) {
+    for event in events {
+        match *event {
+            WorldEvent::EMOTE { player, emote } => {
+                if let Some(swarm) = swarms.get(&player) {
+                    let kind = SignalKind::EMOTE(emote);
+                    signals.push(Signal::new(tick, player, kind, swarm.x, swarm.y));
+                }
+            }
+            WorldEvent::PING { player, x, y } => {
+                let signal = Signal::new(tick, player, SignalKind::PING, x, y);
+                signals.push(signal.with_team(mode.team(player)));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game_mode::CaptureTheFlag;
+
+    #[test]
+    fn signals_from_events() {
+        let mut swarms = Slab::new();
+        swarms.insert(3, Swarm::new(40.0, 60.0, 1));
+        let mut mode = GameMode::CAPTURE_THE_FLAG(CaptureTheFlag::new(1000.0, 500.0));
+        mode.add_player(4);
+        let events = vec![
+            WorldEvent::EMOTE {
+                player: 3,
+                emote: 2,
+            },
+            WorldEvent::JOIN(4),
+            WorldEvent::PING {
+                player: 4,
+                x: 5.0,
+                y: 6.0,
+            },
+        ];
+        let mut signals = Vec::new();
+        collect_signals(9, &events, &swarms, &mode, &mut signals);
+        assert_eq!(
+            signals,
+            vec![
+                Signal::new(9, 3, SignalKind::EMOTE(2), 40.0, 60.0),
+                Signal::new(9, 4, SignalKind::PING, 5.0, 6.0).with_team(mode.team(4)),
+            ]
+        );
+        assert!(signals[1].team.is_some());
+    }
+}
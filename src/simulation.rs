@@ -0,0 +1,110 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use entity::Swarm;
+use error::Error;
+use slab::Slab;
+use swarm_language::SwarmProgram;
+use world::{World, WorldConfig};
+
+/// How a player finished a match
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Score {
+    /// ID of the player, which is the index of its program
+    pub player_id: usize,
+    /// Experience gained during the match
+    pub experience: i64,
+    /// Bounty on the player at the end of the match
+    pub bounty: i64,
+    /// Swarm members left at the end of the match
+    pub members: usize,
+}
+
+/// A match between programs, run without a server
+/// Matches with the same programs, config, and seed always play out the same way
+pub struct Match {
+    /// The match's world
+    pub world: World,
+}
+
+/// Functions for Match
+impl Match {
+    /// Constructor
+    /// programs: one program per player; player IDs are the programs' indices
+    pub fn new(programs: &[SwarmProgram], config: &WorldConfig, seed: u64) -> Result<Self, Error> {
+        let mut world = World::from_config(config)?.with_seed(seed);
+        for (id, program) in programs.iter().enumerate() {
+            world.add_player(id)?;
+            world.update_program(id, program.clone());
+        }
+        Ok(Match { world: world })
+    }
+    /// Performs one tick
+    pub fn tick(&mut self) {
+        self.world.update();
+        // Nothing listens for events, so don't let them pile up
+        self.world.clear_events();
+    }
+    /// The scores so far, best first
+    pub fn scores(&self) -> Vec<Score> {
+        rank(&self.world.swarms)
+    }
+}
+
+/// Scores every swarm, best first
+/// Ranked by experience, then members left, then player ID
+pub fn rank(swarms: &Slab<Swarm>) -> Vec<Score> {
+    let mut scores: Vec<Score> = swarms
+        .iter()
+        .map(|(&id, swarm)| Score {
+            player_id: id,
+            experience: swarm.experience,
+            bounty: swarm.bounty,
+            members: swarm.members.len(),
+        })
+        .collect();
+    scores.sort_by(|a, b| {
+        b.experience
+            .cmp(&a.experience)
+            .then(b.members.cmp(&a.members))
+            .then(a.player_id.cmp(&b.player_id))
+    });
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn matches_repeat() {
+        let programs: Vec<SwarmProgram> = vec![
+            "TURN 10\nFIRE\nMOVE".parse().unwrap(),
+            "MOVE\nTURN -20\nFIRE".parse().unwrap(),
+            "FIRE".parse().unwrap(),
+        ];
+        let config = WorldConfig::new(300.0, 300.0);
+        let play = || {
+            let mut game = Match::new(&programs, &config, 9).unwrap();
+            for _ in 0..200 {
+                game.tick();
+            }
+            game.scores()
+        };
+        let scores = play();
+        assert_eq!(scores.len(), 3);
+        assert_eq!(scores, play());
+        assert!(scores.windows(2).all(|pair| pair[0].experience >= pair[1].experience));
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+#[cfg(feature = "schema")]
+use schemars::gen::SchemaGenerator;
+#[cfg(feature = "schema")]
+use schemars::schema::Schema;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+use std::ops::Index;
+
+/// Values keyed by player ID, stored contiguously
+/// Iteration walks the storage in slot order, which only depends on the order
+/// players were added and removed, unlike a HashMap
+#[derive(Clone, Debug)]
+pub struct Slab<T> {
+    /// Storage slots, each holding a player ID and its value, or nothing if free
+    slots: Vec<Option<(usize, T)>>,
+    /// Indices of free slots, reused before the storage grows
+    free: Vec<usize>,
+    /// Map of player ID to slot index
+    index: HashMap<usize, usize>,
+}
+
+/// Functions for the slab
+impl<T> Slab<T> {
+    /// Constructor
+    pub fn new() -> Self {
+        Slab {
+            slots: Vec::new(),
+            free: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+    /// Capacity constructor
+    /// capacity: the number of values to allocate space for
+    pub fn with_capacity(capacity: usize) -> Self {
+        Slab {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::new(),
+            index: HashMap::with_capacity(capacity),
+        }
+    }
+    /// Number of values stored
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+    /// Whether nothing is stored
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+    /// Whether a value is stored for the ID
+    pub fn contains_key(&self, id: &usize) -> bool {
+        self.index.contains_key(id)
+    }
+    /// Gets the value stored for the ID
+    pub fn get(&self, id: &usize) -> Option<&T> {
+        match self.index.get(id) {
+            Some(&slot) => self.slots[slot].as_ref().map(|&(_, ref value)| value),
+            None => None,
+        }
+    }
+    /// Gets the value stored for the ID, mutably
+    pub fn get_mut(&mut self, id: &usize) -> Option<&mut T> {
+        match self.index.get(id) {
+            Some(&slot) => self.slots[slot]
+                .as_mut()
+                .map(|&mut (_, ref mut value)| value),
+            None => None,
+        }
+    }
+    /// Stores a value for the ID
+    /// return: the value previously stored for the ID, if any
+    pub fn insert(&mut self, id: usize, value: T) -> Option<T> {
+        if let Some(&slot) = self.index.get(&id) {
+            return mem::replace(&mut self.slots[slot], Some((id, value)))
+                .map(|(_, previous)| previous);
+        }
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.slots[slot] = Some((id, value));
+                slot
+            }
+            None => {
+                self.slots.push(Some((id, value)));
+                self.slots.len() - 1
+            }
+        };
+        self.index.insert(id, slot);
+        None
+    }
+    /// Removes the value stored for the ID
+    /// Its slot is reused by the next insertion
+    pub fn remove(&mut self, id: &usize) -> Option<T> {
+        match self.index.remove(id) {
+            Some(slot) => {
+                self.free.push(slot);
+                self.slots[slot].take().map(|(_, value)| value)
+            }
+            None => None,
+        }
+    }
+    /// Iterates over (ID, value) pairs in slot order
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a usize, &'a T)> {
+        self.slots
+            .iter()
+            .filter_map(|slot| slot.as_ref().map(|&(ref id, ref value)| (id, value)))
+    }
+    /// Iterates over (ID, value) pairs in slot order, with mutable values
+    pub fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (&'a usize, &'a mut T)> {
+        self.slots.iter_mut().filter_map(|slot| {
+            slot.as_mut()
+                .map(|&mut (ref id, ref mut value)| (id, value))
+        })
+    }
+    /// Iterates over the IDs in slot order
+    pub fn keys<'a>(&'a self) -> impl Iterator<Item = &'a usize> {
+        self.iter().map(|(id, _)| id)
+    }
+    /// Iterates over the values in slot order
+    pub fn values<'a>(&'a self) -> impl Iterator<Item = &'a T> {
+        self.iter().map(|(_, value)| value)
+    }
+    /// Iterates over the values in slot order, mutably
+    pub fn values_mut<'a>(&'a mut self) -> impl Iterator<Item = &'a mut T> {
+        self.iter_mut().map(|(_, value)| value)
+    }
+    /// Approximate bytes allocated by the slab, not counting memory owned by the values
+    pub fn memory_usage(&self) -> usize {
+        self.slots.capacity() * mem::size_of::<Option<(usize, T)>>()
+            + self.free.capacity() * mem::size_of::<usize>()
+            + self.index.capacity() * 2 * mem::size_of::<usize>()
+    }
+}
+
+/// Empty slab
+impl<T> Default for Slab<T> {
+    fn default() -> Self {
+        Slab::new()
+    }
+}
+
+/// Allows slab[&id], panicking if nothing is stored for the ID
+impl<'a, T> Index<&'a usize> for Slab<T> {
+    type Output = T;
+    fn index(&self, id: &usize) -> &T {
+        self.get(id).expect("No value stored for ID")
+    }
+}
+
+/// Serializes the same way as a map of ID to value, in slot order
+impl<T: Serialize> Serialize for Slab<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (id, value) in self.iter() {
+            map.serialize_entry(id, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Deserializes from a map of ID to value
+/// Values are stored in ascending ID order, the order swarms are updated in
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Slab<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let map = BTreeMap::<usize, T>::deserialize(deserializer)?;
+        let mut slab = Slab::with_capacity(map.len());
+        for (id, value) in map {
+            slab.insert(id, value);
+        }
+        Ok(slab)
+    }
+}
+
+/// Described the same way as a map of ID to value
+#[cfg(feature = "schema")]
+impl<T: JsonSchema> JsonSchema for Slab<T> {
+    fn schema_name() -> String {
+        HashMap::<usize, T>::schema_name()
+    }
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        HashMap::<usize, T>::json_schema(gen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn insert_get_remove() {
+        let mut slab: Slab<&str> = Slab::new();
+        assert_eq!(slab.insert(7, "a"), None);
+        assert_eq!(slab.insert(3, "b"), None);
+        assert_eq!(slab.insert(7, "c"), Some("a"));
+        assert_eq!(slab.len(), 2);
+        assert_eq!(slab[&7], "c");
+        assert_eq!(slab.get(&5), None);
+        assert_eq!(slab.remove(&7), Some("c"));
+        assert_eq!(slab.remove(&7), None);
+        assert!(!slab.contains_key(&7));
+        assert_eq!(slab.len(), 1);
+    }
+    #[test]
+    fn stable_iteration_order() {
+        let mut slab: Slab<usize> = Slab::new();
+        for id in vec![9, 2, 5, 1] {
+            slab.insert(id, id * 10);
+        }
+        slab.remove(&2);
+        // The freed slot is reused in place
+        slab.insert(4, 40);
+        assert_eq!(
+            slab.keys().cloned().collect::<Vec<usize>>(),
+            vec![9, 4, 5, 1]
+        );
+        for value in slab.values_mut() {
+            *value += 1;
+        }
+        assert_eq!(
+            slab.iter().map(|(_, value)| *value).collect::<Vec<usize>>(),
+            vec![91, 41, 51, 11]
+        );
+    }
+}
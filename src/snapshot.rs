@@ -0,0 +1,92 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate serde_json;
+
+use rpc::{Response, ResponseMessage};
+use std::sync::{Arc, Mutex};
+use world::World;
+
+/// Holds the most recently serialized world state message for a room
+/// Every connection asking for the state during the same tick shares one
+/// serialized copy instead of encoding the world again
+#[derive(Debug, Default)]
+pub struct SnapshotCache {
+    /// The tick and revision the message was serialized at, and the message
+    latest: Mutex<Option<(u64, u64, Arc<String>)>>,
+}
+
+/// Functions for SnapshotCache
+impl SnapshotCache {
+    /// Constructor
+    pub fn new() -> Self {
+        SnapshotCache::default()
+    }
+    /// Gets the world state message for the world's current tick
+    /// The world is only serialized by the first caller each tick
+    /// Changes made between ticks show up in the next tick's message,
+    /// except in practice worlds, where they show up right away
+    pub fn get(&self, world: &World) -> Result<Arc<String>, serde_json::Error> {
+        let mut latest = match self.latest.lock() {
+            Ok(latest) => latest,
+            // A panic while serializing can't leave a partial message behind
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Some((tick, revision, ref message)) = *latest {
+            if tick == world.tick && revision == world.revision() {
+                return Ok(message.clone());
+            }
+        }
+        let message =
+            Arc::new(Response::new(ResponseMessage::WORLD(world.state_ref())).serialize()?);
+        *latest = Some((world.tick, world.revision(), message.clone()));
+        Ok(message)
+    }
+    /// Gets the world state message for a player
+    /// With fog of war, players with a swarm are sent only what they can see,
+    /// so their message is serialized for them rather than shared
+    pub fn get_for(
+        &self,
+        world: &World,
+        player_id: usize,
+    ) -> Result<Arc<String>, serde_json::Error> {
+        if !world.fog_of_war || !world.swarms.contains_key(&player_id) {
+            return self.get(world);
+        }
+        let state = world.state_for(player_id);
+        Ok(Arc::new(
+            Response::new(ResponseMessage::WORLD(state.state_ref())).serialize()?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn share_message_within_tick() {
+        let mut world = World::new(1000.0, 1000.0);
+        world.add_player(0).unwrap();
+        let cache = SnapshotCache::new();
+        let first = cache.get(&world).unwrap();
+        let second = cache.get(&world).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        world.update();
+        let third = cache.get(&world).unwrap();
+        assert!(!Arc::ptr_eq(&first, &third));
+        // Without fog of war, every player shares the message
+        assert!(Arc::ptr_eq(&third, &cache.get_for(&world, 0).unwrap()));
+    }
+}
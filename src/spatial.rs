@@ -0,0 +1,230 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem;
+
+/// A cell of a uniform grid, as (column, row)
+pub type Cell = (i64, i64);
+
+/// Counts of the work done maintaining a spatial grid
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct IndexStats {
+    /// Entries that stayed in the same cell during the last tick
+    pub unchanged: usize,
+    /// Entries that crossed into another cell during the last tick
+    pub moved: usize,
+    /// Entries added during the last tick
+    pub inserted: usize,
+    /// Entries removed during the last tick
+    pub removed: usize,
+    /// Number of times the grid was rebuilt from scratch
+    pub rebuilds: u64,
+}
+
+/// A uniform grid of entries that is kept up to date between ticks
+/// Only entries that cross a cell boundary are moved, and the grid is
+/// periodically rebuilt to drop the space left behind by emptied cells
+///
+/// Each tick, call begin, then place every entry that still exists, then finish.
+/// Entries that weren't placed since begin are removed by finish
+#[derive(Clone, Debug)]
+pub struct SpatialGrid<K: Copy + Eq + Hash> {
+    /// Width and height of a cell
+    cell_size: f32,
+    /// Entries in each cell
+    cells: HashMap<Cell, Vec<K>>,
+    /// The cell each entry is in, and the tick it was last placed in
+    entries: HashMap<K, (Cell, u64)>,
+    /// Number of ticks begun
+    tick: u64,
+    /// Number of ticks between full rebuilds
+    rebuild_interval: u64,
+    /// Work done so far
+    stats: IndexStats,
+    /// Entries found to be stale by finish, kept to reuse its space
+    stale: Vec<(K, Cell)>,
+}
+
+/// Functions for SpatialGrid
+impl<K: Copy + Eq + Hash> SpatialGrid<K> {
+    /// Number of ticks between full rebuilds, by default
+    pub const DEFAULT_REBUILD_INTERVAL: u64 = 600;
+
+    /// Constructor
+    /// cell_size: width and height of a cell
+    pub fn new(cell_size: f32) -> Self {
+        SpatialGrid {
+            cell_size: cell_size,
+            cells: HashMap::new(),
+            entries: HashMap::new(),
+            tick: 0,
+            rebuild_interval: SpatialGrid::<K>::DEFAULT_REBUILD_INTERVAL,
+            stats: IndexStats::default(),
+            stale: Vec::new(),
+        }
+    }
+    /// Sets the number of ticks between full rebuilds
+    pub fn with_rebuild_interval(mut self, rebuild_interval: u64) -> Self {
+        self.rebuild_interval = rebuild_interval;
+        self
+    }
+    /// Gets the cell containing a position
+    pub fn cell_of(&self, x: f32, y: f32) -> Cell {
+        (
+            (x / self.cell_size).floor() as i64,
+            (y / self.cell_size).floor() as i64,
+        )
+    }
+    /// Starts a tick, resetting the per-tick counts
+    pub fn begin(&mut self) {
+        self.tick += 1;
+        self.stats.unchanged = 0;
+        self.stats.moved = 0;
+        self.stats.inserted = 0;
+        self.stats.removed = 0;
+    }
+    /// Records an entry's position, moving it if it crossed into another cell
+    pub fn place(&mut self, key: K, x: f32, y: f32) {
+        let cell = self.cell_of(x, y);
+        let tick = self.tick;
+        let previous = match self.entries.get_mut(&key) {
+            Some(entry) => {
+                entry.1 = tick;
+                if entry.0 == cell {
+                    self.stats.unchanged += 1;
+                    return;
+                }
+                let previous = entry.0;
+                entry.0 = cell;
+                Some(previous)
+            }
+            None => None,
+        };
+        match previous {
+            Some(previous) => {
+                self.remove_from_cell(previous, key);
+                self.stats.moved += 1;
+            }
+            None => {
+                self.entries.insert(key, (cell, tick));
+                self.stats.inserted += 1;
+            }
+        }
+        self.cells.entry(cell).or_insert_with(Vec::new).push(key);
+    }
+    /// Ends a tick, removing entries that weren't placed during it
+    /// Rebuilds the grid if the rebuild interval has passed
+    pub fn finish(&mut self) {
+        let tick = self.tick;
+        let mut stale = mem::replace(&mut self.stale, Vec::new());
+        self.entries.retain(|key, &mut (cell, placed)| {
+            if placed == tick {
+                true
+            } else {
+                stale.push((*key, cell));
+                false
+            }
+        });
+        self.stats.removed = stale.len();
+        for &(key, cell) in stale.iter() {
+            self.remove_from_cell(cell, key);
+        }
+        stale.clear();
+        self.stale = stale;
+        if self.rebuild_interval > 0 && self.tick % self.rebuild_interval == 0 {
+            self.rebuild();
+        }
+    }
+    /// Rebuilds every cell from the entries, dropping empty cells
+    pub fn rebuild(&mut self) {
+        self.cells.clear();
+        for (key, &(cell, _)) in self.entries.iter() {
+            self.cells.entry(cell).or_insert_with(Vec::new).push(*key);
+        }
+        self.stats.rebuilds += 1;
+    }
+    /// Gets the entries in a cell
+    pub fn query(&self, cell: Cell) -> &[K] {
+        match self.cells.get(&cell) {
+            Some(keys) => keys,
+            None => &[],
+        }
+    }
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    /// Whether there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    /// Gets the work done so far
+    pub fn stats(&self) -> IndexStats {
+        self.stats
+    }
+    /// Approximate bytes allocated by the grid
+    pub fn memory_usage(&self) -> usize {
+        self.cells.capacity() * (mem::size_of::<Cell>() + mem::size_of::<Vec<K>>())
+            + self
+                .cells
+                .values()
+                .map(|keys| keys.capacity() * mem::size_of::<K>())
+                .sum::<usize>()
+            + self.entries.capacity() * (mem::size_of::<K>() + mem::size_of::<(Cell, u64)>())
+            + self.stale.capacity() * mem::size_of::<(K, Cell)>()
+    }
+    /// Removes an entry from a cell's list
+    fn remove_from_cell(&mut self, cell: Cell, key: K) {
+        if let Some(keys) = self.cells.get_mut(&cell) {
+            if let Some(index) = keys.iter().position(|other| *other == key) {
+                keys.swap_remove(index);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn move_only_across_cells() {
+        let mut grid: SpatialGrid<usize> = SpatialGrid::new(10.0).with_rebuild_interval(3);
+        grid.begin();
+        grid.place(0, 1.0, 1.0);
+        grid.place(1, 15.0, 1.0);
+        grid.finish();
+        assert_eq!(grid.stats().inserted, 2);
+        assert_eq!(grid.query((0, 0)), &[0]);
+        grid.begin();
+        // Entry 0 stays in its cell and entry 1 moves
+        grid.place(0, 5.0, 5.0);
+        grid.place(1, 25.0, 1.0);
+        grid.finish();
+        assert_eq!(grid.stats().unchanged, 1);
+        assert_eq!(grid.stats().moved, 1);
+        assert!(grid.query((1, 0)).is_empty());
+        assert_eq!(grid.query((2, 0)), &[1]);
+        // Entry 0 is gone
+        grid.begin();
+        grid.place(1, 25.0, 1.0);
+        grid.finish();
+        assert_eq!(grid.stats().removed, 1);
+        assert_eq!(grid.stats().rebuilds, 1);
+        assert!(grid.query((0, 0)).is_empty());
+        assert_eq!(grid.len(), 1);
+    }
+}
@@ -0,0 +1,230 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use simulation::rank;
+use world::WorldState;
+
+/// How the map is drawn
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MapStyle {
+    /// Braille patterns, with 2x4 dots per character
+    BRAILLE,
+    /// One plain character per cell, for terminals without Unicode fonts
+    ASCII,
+}
+
+/// The part of the world shown on the map
+/// Characters are about twice as tall as they are wide, so rows cover twice the distance columns do
+#[derive(Clone, Debug, PartialEq)]
+pub struct Viewport {
+    /// X position of the world shown at the center of the map
+    pub center_x: f32,
+    /// Y position of the world shown at the center of the map
+    pub center_y: f32,
+    /// World distance covered by one column
+    pub scale: f32,
+    /// Width of the map in characters
+    pub columns: usize,
+    /// Height of the map in characters
+    pub rows: usize,
+}
+
+/// Functions for Viewport
+impl Viewport {
+    /// Constructor
+    /// Shows a whole world of the given size
+    pub fn fit(width: f32, height: f32, columns: usize, rows: usize) -> Self {
+        let columns = columns.max(1);
+        let rows = rows.max(1);
+        Viewport {
+            center_x: width / 2.0,
+            center_y: height / 2.0,
+            scale: (width / columns as f32).max(height / (2.0 * rows as f32)),
+            columns: columns,
+            rows: rows,
+        }
+    }
+    /// Scrolls the map to center on a player's swarm
+    /// return: whether the player has a swarm to follow
+    pub fn follow(&mut self, state: &WorldState, player_id: usize) -> bool {
+        match state.swarms.get(&player_id) {
+            Some(swarm) => {
+                self.center_x = swarm.x;
+                self.center_y = swarm.y;
+                true
+            }
+            None => false,
+        }
+    }
+    /// Which dot a world position falls on, if it's on the map
+    /// Dots are half a column wide and a quarter of a row tall
+    fn dot(&self, x: f32, y: f32) -> Option<(usize, usize)> {
+        let dot_size = self.scale / 2.0;
+        let left = self.center_x - self.columns as f32 * self.scale / 2.0;
+        let top = self.center_y - self.rows as f32 * self.scale;
+        let dot_x = ((x - left) / dot_size).floor();
+        let dot_y = ((y - top) / dot_size).floor();
+        if !(dot_x >= 0.0 && dot_y >= 0.0) {
+            return None;
+        }
+        let (dot_x, dot_y) = (dot_x as usize, dot_y as usize);
+        if dot_x < 2 * self.columns && dot_y < 4 * self.rows {
+            Some((dot_x, dot_y))
+        } else {
+            None
+        }
+    }
+}
+
+/// A character on the map
+#[derive(Clone, Copy, Default)]
+struct Cell {
+    /// Raised braille dots
+    dots: u8,
+    /// Whether a swarm member is in the cell
+    member: bool,
+    /// Whether a bullet is in the cell
+    bullet: bool,
+    /// Color of whatever was drawn last in the cell
+    color: Option<(u8, u8, u8)>,
+}
+
+/// Bit for each dot in a braille character, indexed by [x][y] within the character
+const BRAILLE_DOTS: [[u8; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+
+/// Draws the world as lines of text
+/// color: whether to color swarms and bullets with ANSI escape codes
+pub fn draw_map(state: &WorldState, viewport: &Viewport, style: MapStyle, color: bool) -> Vec<String> {
+    let mut cells = vec![Cell::default(); viewport.columns * viewport.rows];
+    {
+        let mut plot = |x: f32, y: f32, swarm_color: Option<(u8, u8, u8)>, member: bool| {
+            if let Some((dot_x, dot_y)) = viewport.dot(x, y) {
+                let cell = &mut cells[(dot_y / 4) * viewport.columns + dot_x / 2];
+                cell.dots |= BRAILLE_DOTS[dot_x % 2][dot_y % 4];
+                cell.member |= member;
+                cell.bullet |= !member;
+                cell.color = swarm_color.or(cell.color);
+            }
+        };
+        for (_, swarm) in state.swarms.iter() {
+            for member in swarm.members.iter() {
+                plot(swarm.x + member.x, swarm.y + member.y, Some(swarm.color), true);
+            }
+        }
+        for bullet in state.bullets.iter() {
+            let owner_color = state.swarms.get(&bullet.owner).map(|swarm| swarm.color);
+            plot(bullet.x, bullet.y, owner_color, false);
+        }
+    }
+    cells
+        .chunks(viewport.columns)
+        .map(|row| {
+            let mut line = String::new();
+            for cell in row.iter() {
+                let character = match style {
+                    MapStyle::BRAILLE if cell.dots != 0 => {
+                        ::std::char::from_u32(0x2800 + cell.dots as u32).unwrap_or('?')
+                    }
+                    MapStyle::ASCII if cell.member => 'o',
+                    MapStyle::ASCII if cell.bullet => '.',
+                    _ => ' ',
+                };
+                match cell.color {
+                    Some((r, g, b)) if color && character != ' ' => {
+                        line.push_str(&format!("\x1b[38;2;{};{};{}m{}\x1b[0m", r, g, b, character))
+                    }
+                    _ => line.push(character),
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+/// Lists the best swarms, one per line, with a heading
+/// limit: the most swarms to list
+pub fn scoreboard(state: &WorldState, limit: usize) -> Vec<String> {
+    let mut lines = vec![format!(
+        "{:<6}{:<8}{:<12}{:<8}{}",
+        "Rank", "Player", "Experience", "Members", "Bounty"
+    )];
+    for (place, score) in rank(&state.swarms).iter().take(limit).enumerate() {
+        lines.push(format!(
+            "{:<6}{:<8}{:<12}{:<8}{}",
+            place + 1,
+            score.player_id,
+            score.experience,
+            score.members,
+            score.bounty
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entity::{Bullet, Bullets, Swarm};
+    use game_mode::GameMode;
+    use minimap::Minimap;
+    use rules::MatchPhase;
+    use slab::Slab;
+
+    #[test]
+    fn draw_state() {
+        let mut swarms = Slab::new();
+        let mut swarm = Swarm::new(2.0, 12.0, 1);
+        swarm.members[0].x = 0.0;
+        swarm.members[0].y = 0.0;
+        swarm.experience = 5;
+        swarms.insert(4, swarm);
+        swarms.insert(1, Swarm::new(500.0, 500.0, 2));
+        let mut bullets = Bullets::new();
+        bullets.push(Bullet::new(4, 90.0, 10.0, 0.0));
+        let state = WorldState {
+            swarms: swarms,
+            bullets: bullets,
+            mode: GameMode::default(),
+            phase: MatchPhase::default(),
+            minimap: Minimap::default(),
+            sounds: Vec::new(),
+            hit_markers: Vec::new(),
+            signals: Vec::new(),
+            ghosts: Vec::new(),
+            checksum: None,
+        };
+        // Each column covers 10 units and each row 20, so dots are 5 units square
+        let mut viewport = Viewport::fit(100.0, 40.0, 10, 2);
+        assert_eq!(viewport.scale, 10.0);
+
+        let ascii = draw_map(&state, &viewport, MapStyle::ASCII, false);
+        assert_eq!(ascii, vec!["o        .", "          "]);
+        // The member is on the first column's third dot row
+        let braille = draw_map(&state, &viewport, MapStyle::BRAILLE, false);
+        assert_eq!(braille[0].chars().next(), Some('\u{2804}'));
+        assert_eq!(braille[1].trim(), "");
+
+        // Following the far swarm scrolls the near one off the map
+        assert!(viewport.follow(&state, 1));
+        assert!(!viewport.follow(&state, 7));
+        assert!(draw_map(&state, &viewport, MapStyle::ASCII, false)
+            .iter()
+            .all(|line| !line.contains('.')));
+
+        let scores = scoreboard(&state, 1);
+        assert_eq!(scores.len(), 2);
+        assert!(scores[1].starts_with("1     4 "));
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use entity::Swarm;
+use event::WorldEvent;
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use slab::Slab;
+
+/// How kill streaks are scored
+/// A swarm's streak is the number of members it has destroyed since it last lost one
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct StreakConfig {
+    /// Whether streaks are tracked at all; without them every kill is worth 1
+    pub enabled: bool,
+    /// (streak, multiplier) pairs in increasing order of streak
+    /// A kill that brings a swarm's streak to at least the streak is worth the multiplier
+    pub multipliers: Vec<(u32, i64)>,
+    /// Whether reaching one of the streaks is announced with a STREAK event
+    pub announce: bool,
+}
+
+/// Functions for StreakConfig
+impl StreakConfig {
+    /// Constructor
+    /// Doubles kills from a streak of 3, triples from 6, and quintuples from 10,
+    /// announcing each
+    pub fn new() -> Self {
+        StreakConfig {
+            enabled: true,
+            multipliers: vec![(3, 2), (6, 3), (10, 5)],
+            announce: true,
+        }
+    }
+    /// Constructor for modes without streaks
+    pub fn disabled() -> Self {
+        StreakConfig {
+            enabled: false,
+            multipliers: Vec::new(),
+            announce: false,
+        }
+    }
+    /// What a kill that brings a swarm to a streak is worth
+    pub fn multiplier(&self, streak: u32) -> i64 {
+        if !self.enabled {
+            return 1;
+        }
+        self.multipliers
+            .iter()
+            .rev()
+            .find(|&&(threshold, _)| streak >= threshold)
+            .map_or(1, |&(_, multiplier)| multiplier)
+    }
+    /// Rewards the attacker for a kill and breaks the victim's streak
+    pub fn apply_kill(
+        &self,
+        swarms: &mut Slab<Swarm>,
+        attacker: usize,
+        victim: usize,
+        events: &mut Vec<WorldEvent>,
+    ) {
+        if let Some(swarm) = swarms.get_mut(&attacker) {
+            if self.enabled {
+                swarm.streak += 1;
+            }
+            let streak = swarm.streak;
+            swarm.add_experience(&self.multiplier(streak));
+            if self.announce
+                && self
+                    .multipliers
+                    .iter()
+                    .any(|&(threshold, _)| threshold == streak)
+            {
+                events.push(WorldEvent::STREAK {
+                    player: attacker,
+                    streak: streak,
+                });
+            }
+        }
+        if let Some(swarm) = swarms.get_mut(&victim) {
+            if swarm.streak > 0 {
+                events.push(WorldEvent::STREAK_BROKEN {
+                    player: victim,
+                    streak: swarm.streak,
+                    by: attacker,
+                });
+                swarm.streak = 0;
+            }
+        }
+    }
+}
+
+/// Streaks are on by default
+impl Default for StreakConfig {
+    fn default() -> Self {
+        StreakConfig::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_streaks() {
+        let config = StreakConfig::new();
+        assert_eq!(config.multiplier(2), 1);
+        assert_eq!(config.multiplier(3), 2);
+        assert_eq!(config.multiplier(9), 3);
+        assert_eq!(config.multiplier(50), 5);
+        assert_eq!(StreakConfig::disabled().multiplier(50), 1);
+
+        let mut swarms = Slab::new();
+        swarms.insert(1, Swarm::new(0.0, 0.0, 1));
+        swarms.insert(2, Swarm::new(0.0, 0.0, 1));
+        let mut events = Vec::new();
+        for _ in 0..3 {
+            config.apply_kill(&mut swarms, 1, 2, &mut events);
+        }
+        // 1 + 1 + 2 for the third kill
+        assert_eq!(swarms[&1].experience, 4);
+        assert_eq!(
+            events,
+            vec![WorldEvent::STREAK {
+                player: 1,
+                streak: 3,
+            }]
+        );
+        events.clear();
+        config.apply_kill(&mut swarms, 2, 1, &mut events);
+        assert_eq!(swarms[&1].streak, 0);
+        assert_eq!(
+            events,
+            vec![WorldEvent::STREAK_BROKEN {
+                player: 1,
+                streak: 3,
+                by: 2,
+            }]
+        );
+    }
+}
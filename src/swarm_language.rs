@@ -13,19 +13,46 @@
 //
 // You should have received a copy of the GNU General Public License
 // along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
-use error::GenericError;
+use error::{LanguageError, ParseError};
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::str::FromStr;
 use std::f32;
 
 /// The maximum number of commands that can exist in a swarm program
-const MAX_NUM_COMMANDS: usize = 20;
+pub const MAX_NUM_COMMANDS: usize = 20;
+/// The largest number of focal points a sierpinski formation can have
+/// Generating the formation takes time proportional to this
+pub const MAX_SIERPINSKI_FOCI: u32 = 32;
+/// The most a swarm can turn in one command, in degrees
+pub const MAX_TURN: f32 = 30.0;
+/// The farthest one MOVE can take a swarm
+pub const MAX_MOVE_DISTANCE: f32 = 1000.0;
+/// The most ticks one WAIT can idle for
+pub const MAX_WAIT_TICKS: u32 = 600;
+/// The most times a LOOP block can repeat
+pub const MAX_LOOP_COUNT: u32 = 100;
+/// The longest a label's name can be
+pub const MAX_LABEL_LENGTH: usize = 16;
+/// The most CALLs that can be run without returning, counting recursive ones
+pub const MAX_CALL_DEPTH: usize = 8;
+/// Number of registers each program has, named R0 onwards
+pub const NUM_REGISTERS: usize = 8;
+/// Number of emotes, numbered from 0
+pub const NUM_EMOTES: u32 = 16;
+/// Names of the registers, in order
+const REGISTER_NAMES: &[&str] = &["R0", "R1", "R2", "R3", "R4", "R5", "R6", "R7"];
 
 /// Represents a single command in the swarm language
 // TODO: Fully design this language
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
 pub enum SwarmCommand {
     /// Move the swarm forward
     MOVE,
+    /// Move the swarm forward some distance, no faster than its speed,
+    /// over as many ticks as it takes
+    MOVE_BY(f32),
     /// Move the swarm left
     LEFT,
     /// Move the swarm right
@@ -38,12 +65,53 @@ pub enum SwarmCommand {
     FIRE,
     /// Rotate the swarm some number of degrees
     TURN(f32),
+    /// Rotate the swarm by the degrees in a register, up to MAX_TURN either way
+    TURN_REGISTER(Register),
     /// Do nothing
     NOOP,
+    /// Do nothing for some number of ticks
+    WAIT(u32),
     /// Move into a formation
     FORMATION(Formation),
+    /// Run the commands up to the matching ELSE or ENDIF only if the condition holds
+    IF(Condition),
+    /// Run the commands up to the matching ENDIF only if the IF's condition didn't hold
+    ELSE,
+    /// End the block started by the matching IF
+    ENDIF,
+    /// Run the commands up to the matching ENDLOOP some number of times
+    LOOP(u32),
+    /// End the block started by the matching LOOP
+    ENDLOOP,
+    /// Mark a place in the program to jump to
+    LABEL(Label),
+    /// Go on from the LABEL with the name
+    JUMP(Label),
+    /// Define a subroutine, run only when called, up to the matching ENDSUB
+    SUB(Label),
+    /// End the subroutine started by the matching SUB, returning to its CALL
+    ENDSUB,
+    /// Run the SUB with the name, then go on after the CALL
+    CALL(Label),
+    /// Return from the subroutine being run before reaching its ENDSUB
+    RETURN,
+    /// Change a register's value
+    CALC(Operation, Register, Operand),
+    /// Store the number of other swarms in sensing range in a register
+    SCAN(Register),
+    /// Store the distance and bearing of the closest other swarm in range in two registers
+    NEAREST_ENEMY(Register, Register),
+    /// Store the distance and bearing of the closest enemy bullet in range in two registers
+    NEAREST_BULLET(Register, Register),
+    /// Show an emote by its number to everyone watching
+    EMOTE(u32),
+    /// Define a handler, run in place of the rest of the program when the event happens,
+    /// up to the matching ENDON
+    ON(Trigger),
+    /// End the handler started by the matching ON, going back to where the program was
+    ENDON,
 }
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
 /// A formation
 pub enum Formation {
     /// Gather together
@@ -58,21 +126,385 @@ pub enum Formation {
 impl FromStr for Formation {
     /// The type of error returned if the conversion fails
     /// Must be implemented
-    type Err = GenericError;
+    type Err = LanguageError;
     /// Converts a string to a SwarmCommand
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_uppercase().as_str() {
             "GATHER" => Ok(Formation::GATHER),
             "SPREAD" => Ok(Formation::SPREAD),
-            _ => Err(GenericError::new("Invalid formation name".into())),
+            _ => Err(LanguageError::UNKNOWN_FORMATION(s.into())),
         }
     }
 }
+/// The name of a place in a program, up to MAX_LABEL_LENGTH letters, digits and underscores
+/// Kept inline rather than as a String, so commands stay Copy
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Label {
+    /// The name's letters, followed by zeros
+    name: [u8; MAX_LABEL_LENGTH],
+    /// Number of letters in the name
+    length: u8,
+}
+
+/// Functions for Label
+impl Label {
+    /// The label's name, as written
+    pub fn as_str(&self) -> &str {
+        // Only ASCII is ever stored
+        ::std::str::from_utf8(&self.name[..self.length as usize]).unwrap_or("")
+    }
+}
+
+/// Allows conversion of a string to a label
+impl FromStr for Label {
+    /// The type of error returned if the conversion fails
+    type Err = LanguageError;
+    /// Checks the name and stores it, keeping its case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let valid = !s.is_empty()
+            && s.len() <= MAX_LABEL_LENGTH
+            && s.chars().all(|letter| letter.is_ascii_alphanumeric() || letter == '_');
+        if !valid {
+            return Err(LanguageError::INVALID_PARAMETER {
+                command: "LABEL".into(),
+                parameter: s.into(),
+            });
+        }
+        let mut name = [0; MAX_LABEL_LENGTH];
+        name[..s.len()].copy_from_slice(s.as_bytes());
+        Ok(Label {
+            name: name,
+            length: s.len() as u8,
+        })
+    }
+}
+/// Allows a label to be printed as swarm code
+impl fmt::Display for Label {
+    /// Writes the label's name using a formatter
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+/// Labels are debugged by name
+impl fmt::Debug for Label {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "Label({:?})", self.as_str())
+    }
+}
+/// Labels are serialized by name
+impl Serialize for Label {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// One of a program's whole number registers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Register(u8);
+
+/// Functions for Register
+impl Register {
+    /// Position of the register in the program's registers
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Allows conversion of a string to a register
+impl FromStr for Register {
+    /// The type of error returned if the conversion fails
+    type Err = LanguageError;
+    /// Converts a register's name, such as R0, to a Register
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_uppercase();
+        match REGISTER_NAMES.iter().position(|register| *register == name) {
+            Some(index) => Ok(Register(index as u8)),
+            None => Err(LanguageError::INVALID_PARAMETER {
+                command: "REGISTER".into(),
+                parameter: s.into(),
+            }),
+        }
+    }
+}
+/// Allows a register to be printed as swarm code
+impl fmt::Display for Register {
+    /// Writes the register's name using a formatter
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(REGISTER_NAMES[self.index()])
+    }
+}
+
+/// A value an operation is done with
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum Operand {
+    /// The value in a register
+    REGISTER(Register),
+    /// A whole number written in the program
+    VALUE(i32),
+}
+
+/// Allows conversion of a string to an operand
+impl FromStr for Operand {
+    /// The type of error returned if the conversion fails
+    type Err = LanguageError;
+    /// Converts a register's name or a whole number to an Operand
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<i32>() {
+            Ok(value) => Ok(Operand::VALUE(value)),
+            Err(_) => s.parse().map(Operand::REGISTER),
+        }
+    }
+}
+/// Allows an operand to be printed as swarm code
+impl fmt::Display for Operand {
+    /// Writes the operand using a formatter
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Operand::REGISTER(register) => write!(formatter, "{}", register),
+            Operand::VALUE(value) => write!(formatter, "{}", value),
+        }
+    }
+}
+
+/// What a CALC command does to its register
+/// Results that don't fit in a register wrap around
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum Operation {
+    /// Store the operand
+    SET,
+    /// Add the operand
+    ADD,
+    /// Subtract the operand
+    SUB,
+    /// Multiply by the operand
+    MUL,
+}
+
+/// Functions for Operation
+impl Operation {
+    /// The operation's opcode
+    pub fn opcode(&self) -> &'static str {
+        match *self {
+            Operation::SET => "SET",
+            Operation::ADD => "ADD",
+            Operation::SUB => "SUB",
+            Operation::MUL => "MUL",
+        }
+    }
+    /// Does the operation
+    /// value: the register's value
+    /// operand: the value the operation is done with
+    pub fn apply(&self, value: i32, operand: i32) -> i32 {
+        match *self {
+            Operation::SET => operand,
+            Operation::ADD => value.wrapping_add(operand),
+            Operation::SUB => value.wrapping_sub(operand),
+            Operation::MUL => value.wrapping_mul(operand),
+        }
+    }
+}
+
+/// Something that happens to a swarm, which an ON block can handle
+/// Earlier triggers are more urgent, and replace later ones waiting to be handled
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum Trigger {
+    /// The swarm respawned
+    SPAWN,
+    /// A member of the swarm took damage
+    HIT,
+    /// Another swarm came close, after none was
+    ENEMY_SIGHTED,
+}
+
+/// Functions for Trigger
+impl Trigger {
+    /// The opcode of the ON block that handles it
+    pub fn opcode(&self) -> &'static str {
+        match *self {
+            Trigger::SPAWN => "ON_SPAWN",
+            Trigger::HIT => "ON_HIT",
+            Trigger::ENEMY_SIGHTED => "ON_ENEMY_SIGHTED",
+        }
+    }
+}
+
+/// Something about a swarm a program can branch on
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum Condition {
+    /// The swarm's weapon is ready, and at least one member can fire
+    CAN_FIRE,
+    /// Another swarm is close by
+    ENEMY_NEAR,
+    /// The edge of the world or an obstacle is a short way ahead
+    WALL_AHEAD,
+    /// The members have less than half the health of a new swarm's
+    HEALTH_LOW,
+}
+
+/// Allows conversion of a string to a condition
+impl FromStr for Condition {
+    /// The type of error returned if the conversion fails
+    type Err = LanguageError;
+    /// Converts a string to a Condition
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "CAN_FIRE" => Ok(Condition::CAN_FIRE),
+            "ENEMY_NEAR" => Ok(Condition::ENEMY_NEAR),
+            "WALL_AHEAD" => Ok(Condition::WALL_AHEAD),
+            "HEALTH_LOW" => Ok(Condition::HEALTH_LOW),
+            _ => Err(LanguageError::UNKNOWN_CONDITION(s.into())),
+        }
+    }
+}
+/// Allows a condition to be printed as swarm code
+impl fmt::Display for Condition {
+    /// Writes the condition using a formatter
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Condition::CAN_FIRE => write!(formatter, "CAN_FIRE"),
+            Condition::ENEMY_NEAR => write!(formatter, "ENEMY_NEAR"),
+            Condition::WALL_AHEAD => write!(formatter, "WALL_AHEAD"),
+            Condition::HEALTH_LOW => write!(formatter, "HEALTH_LOW"),
+        }
+    }
+}
+
+/// Allows a formation to be printed as swarm code
+impl fmt::Display for Formation {
+    /// Writes the formation using a formatter
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Formation::GATHER => write!(formatter, "GATHER"),
+            Formation::SPREAD => write!(formatter, "SPREAD"),
+            Formation::SIERPINSKI(focal_count) => write!(formatter, "SIERPINSKI {}", focal_count),
+        }
+    }
+}
+
+/// Allows a command to be printed as a line of swarm code
+impl fmt::Display for SwarmCommand {
+    /// Writes the command using a formatter
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SwarmCommand::MOVE => write!(formatter, "MOVE"),
+            SwarmCommand::MOVE_BY(distance) => write!(formatter, "MOVE {}", distance),
+            SwarmCommand::LEFT => write!(formatter, "LEFT"),
+            SwarmCommand::RIGHT => write!(formatter, "RIGHT"),
+            SwarmCommand::UP => write!(formatter, "UP"),
+            SwarmCommand::DOWN => write!(formatter, "DOWN"),
+            SwarmCommand::FIRE => write!(formatter, "FIRE"),
+            SwarmCommand::TURN(turn_amt) => write!(formatter, "TURN {}", turn_amt),
+            SwarmCommand::TURN_REGISTER(register) => write!(formatter, "TURN {}", register),
+            SwarmCommand::NOOP => write!(formatter, "NOOP"),
+            SwarmCommand::WAIT(ticks) => write!(formatter, "WAIT {}", ticks),
+            SwarmCommand::FORMATION(formation) => write!(formatter, "FORMATION {}", formation),
+            SwarmCommand::IF(condition) => write!(formatter, "IF {}", condition),
+            SwarmCommand::ELSE => write!(formatter, "ELSE"),
+            SwarmCommand::ENDIF => write!(formatter, "ENDIF"),
+            SwarmCommand::LOOP(count) => write!(formatter, "LOOP {}", count),
+            SwarmCommand::ENDLOOP => write!(formatter, "ENDLOOP"),
+            SwarmCommand::LABEL(label) => write!(formatter, "LABEL {}", label),
+            SwarmCommand::JUMP(label) => write!(formatter, "JUMP {}", label),
+            SwarmCommand::SUB(name) => write!(formatter, "SUB {}", name),
+            SwarmCommand::ENDSUB => write!(formatter, "ENDSUB"),
+            SwarmCommand::CALL(name) => write!(formatter, "CALL {}", name),
+            SwarmCommand::RETURN => write!(formatter, "RETURN"),
+            SwarmCommand::CALC(operation, register, operand) => {
+                write!(formatter, "{} {} {}", operation.opcode(), register, operand)
+            }
+            SwarmCommand::SCAN(count) => write!(formatter, "SCAN {}", count),
+            SwarmCommand::NEAREST_ENEMY(distance, bearing) => {
+                write!(formatter, "NEAREST_ENEMY {} {}", distance, bearing)
+            }
+            SwarmCommand::NEAREST_BULLET(distance, bearing) => {
+                write!(formatter, "NEAREST_BULLET {} {}", distance, bearing)
+            }
+            SwarmCommand::EMOTE(emote) => write!(formatter, "EMOTE {}", emote),
+            SwarmCommand::ON(trigger) => write!(formatter, "{}", trigger.opcode()),
+            SwarmCommand::ENDON => write!(formatter, "ENDON"),
+        }
+    }
+}
+
+/// Functions for SwarmCommand
+impl SwarmCommand {
+    /// The command's opcode, without its parameters
+    pub fn opcode(&self) -> &'static str {
+        match *self {
+            SwarmCommand::MOVE | SwarmCommand::MOVE_BY(_) => "MOVE",
+            SwarmCommand::LEFT => "LEFT",
+            SwarmCommand::RIGHT => "RIGHT",
+            SwarmCommand::UP => "UP",
+            SwarmCommand::DOWN => "DOWN",
+            SwarmCommand::FIRE => "FIRE",
+            SwarmCommand::TURN(_) | SwarmCommand::TURN_REGISTER(_) => "TURN",
+            SwarmCommand::NOOP => "NOOP",
+            SwarmCommand::WAIT(_) => "WAIT",
+            SwarmCommand::FORMATION(_) => "FORMATION",
+            SwarmCommand::IF(_) => "IF",
+            SwarmCommand::ELSE => "ELSE",
+            SwarmCommand::ENDIF => "ENDIF",
+            SwarmCommand::LOOP(_) => "LOOP",
+            SwarmCommand::ENDLOOP => "ENDLOOP",
+            SwarmCommand::LABEL(_) => "LABEL",
+            SwarmCommand::JUMP(_) => "JUMP",
+            SwarmCommand::SUB(_) => "SUB",
+            SwarmCommand::ENDSUB => "ENDSUB",
+            SwarmCommand::CALL(_) => "CALL",
+            SwarmCommand::RETURN => "RETURN",
+            SwarmCommand::CALC(operation, _, _) => operation.opcode(),
+            SwarmCommand::SCAN(_) => "SCAN",
+            SwarmCommand::NEAREST_ENEMY(..) => "NEAREST_ENEMY",
+            SwarmCommand::NEAREST_BULLET(..) => "NEAREST_BULLET",
+            SwarmCommand::EMOTE(_) => "EMOTE",
+            SwarmCommand::ON(trigger) => trigger.opcode(),
+            SwarmCommand::ENDON => "ENDON",
+        }
+    }
+    /// Whether the command only decides which command runs next
+    pub fn is_control_flow(&self) -> bool {
+        match *self {
+            SwarmCommand::IF(_)
+            | SwarmCommand::ELSE
+            | SwarmCommand::ENDIF
+            | SwarmCommand::LOOP(_)
+            | SwarmCommand::ENDLOOP
+            | SwarmCommand::LABEL(_)
+            | SwarmCommand::JUMP(_)
+            | SwarmCommand::SUB(_)
+            | SwarmCommand::ENDSUB
+            | SwarmCommand::CALL(_)
+            | SwarmCommand::RETURN
+            | SwarmCommand::ON(_)
+            | SwarmCommand::ENDON => true,
+            _ => false,
+        }
+    }
+    /// Whether the command needs what the swarm senses of the world, set before it runs
+    pub fn senses(&self) -> bool {
+        match *self {
+            SwarmCommand::IF(_)
+            | SwarmCommand::SCAN(_)
+            | SwarmCommand::NEAREST_ENEMY(..)
+            | SwarmCommand::NEAREST_BULLET(..) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Makes the error for a command given more than its max parameters
+fn too_many(command: &str, max: usize) -> LanguageError {
+    LanguageError::TOO_MANY_PARAMETERS {
+        command: command.into(),
+        max: max,
+    }
+}
+
 /// Allows conversion of a string to a command
 impl FromStr for SwarmCommand {
     /// The type of error returned if the conversion fails
     /// Must be implemented
-    type Err = GenericError;
+    type Err = LanguageError;
     /// Converts a string to a SwarmCommand
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         // TODO: Parse a line of swarm code as an enum
@@ -80,22 +512,64 @@ impl FromStr for SwarmCommand {
         let command: Vec<&str> = s.trim().split_whitespace().collect();
 
         if (command.len() == 0) {
-            return Err(GenericError {
-                description: "Command is white space (should be non-error).".into(),
-            });
+            return Err(LanguageError::EMPTY_COMMAND);
         };
 
 		let opcode: String = command[0].to_uppercase().into();
 		
         // Match
         match command[0].to_uppercase().as_str() {
-            "MOVE" => Ok(SwarmCommand::MOVE), // Move command case
+            "MOVE" => match command.len() {
+                1 => Ok(SwarmCommand::MOVE), // Move command case
+                2 => match command[1].parse::<f32>() {
+                    Ok(val) if val.is_normal() && val > 0.0 && val <= MAX_MOVE_DISTANCE => {
+                        Ok(SwarmCommand::MOVE_BY(val))
+                    }
+                    Ok(val) if val.is_finite() => Err(LanguageError::OUT_OF_RANGE {
+                        command: "MOVE".into(),
+                        min: 0.0,
+                        max: MAX_MOVE_DISTANCE,
+                    }),
+                    _ => Err(LanguageError::INVALID_PARAMETER {
+                        command: "MOVE".into(),
+                        parameter: command[1].into(),
+                    }),
+                },
+                _ => Err(LanguageError::INVALID_PARAMETER {
+                    command: "MOVE".into(),
+                    parameter: command[2..].join(" "),
+                }),
+            },
             "LEFT" => Ok(SwarmCommand::LEFT), // left strafe
             "RIGHT" => Ok(SwarmCommand::RIGHT), // left strafe
             "UP" => Ok(SwarmCommand::UP), // left strafe
             "DOWN" => Ok(SwarmCommand::DOWN), // left strafe
             "FIRE" => Ok(SwarmCommand::FIRE), // Fire Command case
             "NOOP" => Ok(SwarmCommand::NOOP), // Noop command case
+            "WAIT" => {
+                if command.len() < 2 {
+                    return Err(LanguageError::MISSING_PARAMETER("WAIT".into()));
+                }
+                if command.len() > 2 {
+                    return Err(too_many("WAIT", 1));
+                }
+                match command[1].parse::<u32>() {
+                    Ok(val) if val >= 1 && val <= MAX_WAIT_TICKS => Ok(SwarmCommand::WAIT(val)),
+                    Ok(_) => Err(LanguageError::OUT_OF_RANGE {
+                        command: "WAIT".into(),
+                        min: 1.0,
+                        max: MAX_WAIT_TICKS as f32,
+                    }),
+                    Err(_) => Err(LanguageError::INVALID_PARAMETER {
+                        command: "WAIT".into(),
+                        parameter: command[1].into(),
+                    }),
+                }
+            }
+            // Registers hold whole numbers, so can't be mistaken for an angle
+            "TURN" if command.len() == 2 && command[1].parse::<Register>().is_ok() => {
+                command[1].parse().map(SwarmCommand::TURN_REGISTER)
+            }
             "TURN" => {
                 if command.len() == 2
                 // Check if turn parameter was provided
@@ -103,71 +577,188 @@ impl FromStr for SwarmCommand {
                     match command[1].parse::<f32>() {
                         Ok(val) => {
                             if val.is_normal() {
-                                if (val.abs() <= 30.0_f32) {
+                                if (val.abs() <= MAX_TURN) {
                                     Ok(SwarmCommand::TURN(val)) // If value satisfies clamp conditions,
                                 } else {
-                                    Err(GenericError::new(
-                                        "Input parameter float should range from -30.0 to 30.0."
-                                            .into(),
-                                    )) // Otherwise, throw compilation error
+                                    Err(LanguageError::OUT_OF_RANGE {
+                                        command: "TURN".into(),
+                                        min: -MAX_TURN,
+                                        max: MAX_TURN,
+                                    }) // Otherwise, throw compilation error
                                 }
                             } else {
-                                Err(GenericError::new(
-                                    "Invalid float parameter for TURN.".into(), // If parameter is not normal, throw error
-                                ))
+                                // If parameter is not normal, throw error
+                                Err(LanguageError::INVALID_PARAMETER {
+                                    command: "TURN".into(),
+                                    parameter: command[1].into(),
+                                })
                             }
                         }
 
-                        Err(_) => Err(GenericError::new(
-                            "Invalid float parameter for TURN.".into(),
-                        )), // If parameter cannot be converted to float, throw error
+                        Err(_) => Err(LanguageError::INVALID_PARAMETER {
+                            command: "TURN".into(),
+                            parameter: command[1].into(),
+                        }), // If parameter cannot be converted to float, throw error
                     }
+                } else if command.len() > 2 {
+                    Err(too_many("TURN", 1))
                 } else {
-                    Err(GenericError::new("No parameters found for TURN.".into())) // No parameter provided
+                    Err(LanguageError::MISSING_PARAMETER("TURN".into())) // No parameter provided
                 }
             }
 			
 			
 			
             "FORMATION" => {
-                if command.len() >= 2 {
-                    let formation: Formation = match command[1].parse() {
-                        Ok(formation) => formation,
-                        Err(err) => return Err(err),
-                    };
-					
-					let form_string = match command[1].parse::<String>()
-					{
-						Ok(val) => val,
-						Err(_) => return Err(GenericError::new("Formation specification failed to parse.".into())),
-					};
-					
-					if (form_string.to_uppercase() == "SIERPINSKI")
-					{
-						if (command.len() == 3)
-						{
-							match command[2].parse::<u32>()
-							{
-								Ok(val) => return Ok(SwarmCommand::FORMATION(Formation::SIERPINSKI(val))),
-								Err(_) => return Err(GenericError::new("Invalid parameter for Sierpinski formation.".into()))
-							}
-						}
-						else
-						{
-							return Err(GenericError::new("Insufficient parameters for Sierpinski formation".into()))
-						}
-					}
-					else
-					{
-					    Ok(SwarmCommand::FORMATION(formation))
-					}
-
-                } else {
-                    Err(GenericError::new("Invalid number of arguments for command FORMATION. FORMATION requires 1 argument".into()))
+                if command.len() < 2 {
+                    return Err(LanguageError::MISSING_PARAMETER("FORMATION".into()));
+                }
+                // SIERPINSKI is the only formation with a parameter
+                if command[1].to_uppercase() != "SIERPINSKI" {
+                    return command[1].parse().map(SwarmCommand::FORMATION);
+                }
+                if command.len() < 3 {
+                    return Err(LanguageError::MISSING_PARAMETER("SIERPINSKI".into()));
+                }
+                if command.len() > 3 {
+                    return Err(too_many("FORMATION", 2));
+                }
+                match command[2].parse::<u32>() {
+                    Ok(val) if val > MAX_SIERPINSKI_FOCI => Err(LanguageError::OUT_OF_RANGE {
+                        command: "SIERPINSKI".into(),
+                        min: 0.0,
+                        max: MAX_SIERPINSKI_FOCI as f32,
+                    }),
+                    Ok(val) => Ok(SwarmCommand::FORMATION(Formation::SIERPINSKI(val))),
+                    Err(_) => Err(LanguageError::INVALID_PARAMETER {
+                        command: "SIERPINSKI".into(),
+                        parameter: command[2].into(),
+                    }),
+                }
+            }
+            "IF" => match command.len() {
+                1 => Err(LanguageError::MISSING_PARAMETER("IF".into())),
+                2 => command[1].parse().map(SwarmCommand::IF),
+                _ => Err(LanguageError::INVALID_PARAMETER {
+                    command: "IF".into(),
+                    parameter: command[2..].join(" "),
+                }),
+            },
+            "ELSE" => Ok(SwarmCommand::ELSE),
+            "ENDIF" => Ok(SwarmCommand::ENDIF),
+            "ENDLOOP" => Ok(SwarmCommand::ENDLOOP),
+            "ENDSUB" => Ok(SwarmCommand::ENDSUB),
+            "RETURN" => Ok(SwarmCommand::RETURN),
+            "ON_SPAWN" => Ok(SwarmCommand::ON(Trigger::SPAWN)),
+            "ON_HIT" => Ok(SwarmCommand::ON(Trigger::HIT)),
+            "ON_ENEMY_SIGHTED" => Ok(SwarmCommand::ON(Trigger::ENEMY_SIGHTED)),
+            "ENDON" => Ok(SwarmCommand::ENDON),
+            // SUB with a register and a value subtracts, rather than defining a subroutine
+            "SET" | "ADD" | "MUL" | "SUB" if opcode != "SUB" || command.len() > 2 => {
+                let operation = match opcode.as_str() {
+                    "SET" => Operation::SET,
+                    "ADD" => Operation::ADD,
+                    "SUB" => Operation::SUB,
+                    _ => Operation::MUL,
+                };
+                if command.len() < 3 {
+                    return Err(LanguageError::MISSING_PARAMETER(opcode));
+                }
+                let invalid = |parameter: &str| LanguageError::INVALID_PARAMETER {
+                    command: opcode.clone(),
+                    parameter: parameter.into(),
+                };
+                if command.len() > 3 {
+                    return Err(invalid(&command[3..].join(" ")));
+                }
+                let register = command[1].parse().map_err(|_| invalid(command[1]))?;
+                let operand = command[2].parse().map_err(|_| invalid(command[2]))?;
+                Ok(SwarmCommand::CALC(operation, register, operand))
+            }
+            "SCAN" | "NEAREST_ENEMY" | "NEAREST_BULLET" => {
+                let wanted = if opcode == "SCAN" { 1 } else { 2 };
+                if command.len() < wanted + 1 {
+                    return Err(LanguageError::MISSING_PARAMETER(opcode));
+                }
+                let invalid = |parameter: &str| LanguageError::INVALID_PARAMETER {
+                    command: opcode.clone(),
+                    parameter: parameter.into(),
+                };
+                if command.len() > wanted + 1 {
+                    return Err(invalid(&command[wanted + 1..].join(" ")));
+                }
+                let mut registers: Vec<Register> = Vec::with_capacity(wanted);
+                for parameter in command[1..].iter() {
+                    registers.push(parameter.parse().map_err(|_| invalid(*parameter))?);
+                }
+                Ok(match opcode.as_str() {
+                    "SCAN" => SwarmCommand::SCAN(registers[0]),
+                    "NEAREST_ENEMY" => SwarmCommand::NEAREST_ENEMY(registers[0], registers[1]),
+                    _ => SwarmCommand::NEAREST_BULLET(registers[0], registers[1]),
+                })
+            }
+            "LABEL" | "JUMP" | "SUB" | "CALL" => {
+                if command.len() < 2 {
+                    return Err(LanguageError::MISSING_PARAMETER(opcode));
+                }
+                if command.len() > 2 {
+                    return Err(too_many(&opcode, 1));
+                }
+                let label = command[1].parse::<Label>().map_err(|_| {
+                    LanguageError::INVALID_PARAMETER {
+                        command: opcode.clone(),
+                        parameter: command[1].into(),
+                    }
+                })?;
+                Ok(match opcode.as_str() {
+                    "LABEL" => SwarmCommand::LABEL(label),
+                    "JUMP" => SwarmCommand::JUMP(label),
+                    "SUB" => SwarmCommand::SUB(label),
+                    _ => SwarmCommand::CALL(label),
+                })
+            }
+            "EMOTE" => {
+                if command.len() < 2 {
+                    return Err(LanguageError::MISSING_PARAMETER("EMOTE".into()));
+                }
+                if command.len() > 2 {
+                    return Err(too_many("EMOTE", 1));
+                }
+                match command[1].parse::<u32>() {
+                    Ok(val) if val < NUM_EMOTES => Ok(SwarmCommand::EMOTE(val)),
+                    Ok(_) => Err(LanguageError::OUT_OF_RANGE {
+                        command: "EMOTE".into(),
+                        min: 0.0,
+                        max: (NUM_EMOTES - 1) as f32,
+                    }),
+                    Err(_) => Err(LanguageError::INVALID_PARAMETER {
+                        command: "EMOTE".into(),
+                        parameter: command[1].into(),
+                    }),
+                }
+            }
+            "LOOP" => {
+                if command.len() < 2 {
+                    return Err(LanguageError::MISSING_PARAMETER("LOOP".into()));
+                }
+                if command.len() > 2 {
+                    return Err(too_many("LOOP", 1));
+                }
+                match command[1].parse::<u32>() {
+                    Ok(val) if val >= 1 && val <= MAX_LOOP_COUNT => Ok(SwarmCommand::LOOP(val)),
+                    Ok(_) => Err(LanguageError::OUT_OF_RANGE {
+                        command: "LOOP".into(),
+                        min: 1.0,
+                        max: MAX_LOOP_COUNT as f32,
+                    }),
+                    Err(_) => Err(LanguageError::INVALID_PARAMETER {
+                        command: "LOOP".into(),
+                        parameter: command[1].into(),
+                    }),
                 }
             }
             _ => Err(
-                GenericError::new("Command not recognized.".into()), // Invalid command case
+                LanguageError::UNKNOWN_COMMAND(command[0].into()), // Invalid command case
             ),
         }
     }
@@ -202,23 +793,381 @@ fn test_verifier() {
 } END BROKEN IN MERGE */
 
 /// A swarm program is a list of swarm commands
-#[derive(Clone, Debug)]
+/// Commands opening and closing blocks stay in the list, and are matched up as the program runs
+#[derive(Clone, Debug, Default)]
 pub struct SwarmProgram {
     /// The list of commands
     pub commands: Vec<SwarmCommand>,
 
     /// Program counter pointing to current command
     pub program_counter: usize,
+    /// Loops being run, innermost last
+    /// Tuple of (index of the LOOP, repetitions left)
+    pub loops: Vec<(usize, u32)>,
+    /// Index of each label's LABEL, resolved when the program is made
+    /// Programs whose commands are changed afterwards should be made again with new
+    pub labels: BTreeMap<Label, usize>,
+    /// Subroutines being run, innermost last
+    /// Tuple of (index of the CALL, number of loops being run when it was made)
+    pub calls: Vec<(usize, usize)>,
+    /// Index of each subroutine's SUB, resolved along with the labels
+    pub subroutines: BTreeMap<Label, usize>,
+    /// Values of the registers, R0 first
+    /// Kept when the program starts over, so they can count passes through it
+    pub registers: [i32; NUM_REGISTERS],
+    /// Index of each trigger's ON, resolved along with the labels
+    pub handlers: BTreeMap<Trigger, usize>,
+    /// Where the program was when the handler being run interrupted it
+    /// Tuple of (program counter to go back to, number of loops, number of calls)
+    pub interrupted: Option<(usize, usize, usize)>,
+    /// The MOVE being run over several ticks
+    /// Tuple of (index of the MOVE, distance left)
+    pub moving: Option<(usize, f32)>,
+    /// The WAIT being run
+    /// Tuple of (index of the WAIT, ticks left)
+    pub waiting: Option<(usize, u32)>,
+    /// Whether the player paused the program, so the swarm idles where it is
+    pub paused: bool,
 }
 
 /// Some functions for SwarmProgram
 impl SwarmProgram {
     /// Constructor (empty)
     pub fn new(commands: Vec<SwarmCommand>) -> Self {
+        // The first of two LABELs, SUBs or handlers with the same name wins,
+        // though parsing rejects them
+        let mut labels: BTreeMap<Label, usize> = BTreeMap::new();
+        let mut subroutines: BTreeMap<Label, usize> = BTreeMap::new();
+        let mut handlers: BTreeMap<Trigger, usize> = BTreeMap::new();
+        for (index, command) in commands.iter().enumerate() {
+            match *command {
+                SwarmCommand::LABEL(label) => {
+                    labels.entry(label).or_insert(index);
+                }
+                SwarmCommand::SUB(name) => {
+                    subroutines.entry(name).or_insert(index);
+                }
+                SwarmCommand::ON(trigger) => {
+                    handlers.entry(trigger).or_insert(index);
+                }
+                _ => {}
+            }
+        }
         SwarmProgram {
             commands: commands,
             program_counter: 0,
+            loops: Vec::new(),
+            labels: labels,
+            calls: Vec::new(),
+            subroutines: subroutines,
+            registers: [0; NUM_REGISTERS],
+            handlers: handlers,
+            interrupted: None,
+            moving: None,
+            waiting: None,
+            paused: false,
+        }
+    }
+    /// Parses a program, going on past bad lines to find every problem at once
+    /// Only the first command past MAX_NUM_COMMANDS is reported for the length,
+    /// and blocks are checked using the lines that parsed
+    /// return: the program, or every error in line order
+    pub fn compile_all(source: &str) -> Result<SwarmProgram, Vec<ParseError>> {
+        let lines = command_lines(source);
+        let mut errors: Vec<ParseError> = Vec::new();
+        // Line number and text of each command that parsed, to check blocks once every line is read
+        let mut parsed: Vec<(usize, &str)> = Vec::with_capacity(lines.len());
+        let mut commands: Vec<SwarmCommand> = Vec::with_capacity(lines.len());
+        for (count, &(line, text)) in lines.iter().enumerate() {
+            if count == MAX_NUM_COMMANDS {
+                let error = LanguageError::TOO_LONG {
+                    commands: lines.len(),
+                    max: MAX_NUM_COMMANDS,
+                };
+                errors.push(parse_error(line, text, error));
+            }
+            match text.parse() {
+                Ok(command) => {
+                    parsed.push((line, text));
+                    commands.push(command);
+                }
+                Err(error) => errors.push(parse_error(line, text, error)),
+            }
+        }
+        for (index, error) in structure_errors(&commands) {
+            let (line, text) = parsed[index];
+            errors.push(parse_error(line, text, error));
+        }
+        if errors.is_empty() {
+            Ok(SwarmProgram::new(commands))
+        } else {
+            errors.sort_by_key(|error| error.line);
+            Err(errors)
+        }
+    }
+    /// Runs the JUMP at the program counter, going on from the LABEL with the name
+    /// Loops the LABEL is outside of are left, and a jump to no LABEL does nothing
+    /// Loops run by the callers of a subroutine go on, since jumps stay in the subroutine
+    pub fn jump(&mut self, label: Label) {
+        let target = match self.labels.get(&label) {
+            Some(&target) => target,
+            None => return,
+        };
+        let callers = self.callers_loops();
+        let loops = self.loops.split_off(callers);
+        let kept: Vec<(usize, u32)> = loops
+            .into_iter()
+            .filter(|&(start, _)| {
+                start < target && self.loop_end(start).map_or(false, |end| target < end)
+            })
+            .collect();
+        self.loops.extend(kept);
+        self.program_counter = target;
+    }
+    /// Finds the ENDLOOP that closes the LOOP at an index
+    fn loop_end(&self, start: usize) -> Option<usize> {
+        let mut depth = 0;
+        for index in start + 1..self.commands.len() {
+            match self.commands[index] {
+                SwarmCommand::LOOP(_) => depth += 1,
+                SwarmCommand::ENDLOOP if depth == 0 => return Some(index),
+                SwarmCommand::ENDLOOP => depth -= 1,
+                _ => {}
+            }
+        }
+        None
+    }
+    /// Moves the program counter to the next command, starting over after the last
+    pub fn advance(&mut self) {
+        self.program_counter += 1;
+        if self.program_counter >= self.commands.len() {
+            self.restart();
+        }
+    }
+    /// Goes back to the first command, leaving any loops, subroutines and handler being run
+    pub fn restart(&mut self) {
+        self.program_counter = 0;
+        self.loops.clear();
+        self.calls.clear();
+        self.interrupted = None;
+        self.moving = None;
+        self.waiting = None;
+    }
+    /// Distance left for the MOVE at the program counter to go,
+    /// all of it unless the MOVE was already being run
+    pub fn distance_left(&self, distance: f32) -> f32 {
+        match self.moving {
+            Some((index, left)) if index == self.program_counter => left,
+            _ => distance,
+        }
+    }
+    /// Ticks left for the WAIT at the program counter to idle,
+    /// all of them unless the WAIT was already being run
+    pub fn ticks_left(&self, ticks: u32) -> u32 {
+        match self.waiting {
+            Some((index, left)) if index == self.program_counter => left,
+            _ => ticks,
+        }
+    }
+    /// Runs a CALC command, storing the result in its register
+    pub fn calculate(&mut self, operation: Operation, register: Register, operand: Operand) {
+        let operand = self.value(operand);
+        let value = &mut self.registers[register.index()];
+        *value = operation.apply(*value, operand);
+    }
+    /// The value of an operand, read from the registers if it names one
+    pub fn value(&self, operand: Operand) -> i32 {
+        match operand {
+            Operand::REGISTER(register) => self.registers[register.index()],
+            Operand::VALUE(value) => value,
+        }
+    }
+    /// Runs the CALL at the program counter, going on from the first command of the SUB
+    /// A call to no SUB, or one more than MAX_CALL_DEPTH deep, does nothing
+    pub fn call(&mut self, name: Label) {
+        let start = match self.subroutines.get(&name) {
+            Some(&start) => start,
+            None => return,
+        };
+        if self.calls.len() >= MAX_CALL_DEPTH {
+            return;
+        }
+        self.calls.push((self.program_counter, self.loops.len()));
+        // Advancing past the SUB lands on the first command of its block
+        self.program_counter = start;
+    }
+    /// Runs the RETURN or ENDSUB at the program counter, going on after the latest CALL
+    /// Loops started in the subroutine are left
+    /// Nothing happens if no subroutine is being run, as when a program is changed mid-call
+    pub fn return_from_call(&mut self) {
+        if let Some((call, loops)) = self.calls.pop() {
+            self.loops.truncate(loops);
+            self.program_counter = call;
+        }
+    }
+    /// Number of loops being run by the callers of the subroutine being run,
+    /// or by the commands the handler being run interrupted,
+    /// which their own loops come after
+    fn callers_loops(&self) -> usize {
+        let called = self.calls.last().map_or(0, |&(_, loops)| loops);
+        let interrupted = self.interrupted.map_or(0, |(_, loops, _)| loops);
+        called.max(interrupted).min(self.loops.len())
+    }
+    /// Runs the SUB at the program counter, reached without a CALL,
+    /// by going on after its ENDSUB
+    pub fn skip_sub(&mut self) {
+        let start = self.program_counter;
+        let end = self.commands[start + 1..]
+            .iter()
+            .position(|command| *command == SwarmCommand::ENDSUB);
+        if let Some(end) = end {
+            self.program_counter = start + 1 + end;
+        }
+    }
+    /// Whether the program has an ON block for a trigger
+    pub fn handles(&self, trigger: Trigger) -> bool {
+        self.handlers.contains_key(&trigger)
+    }
+    /// Starts running the handler for a trigger from its first command,
+    /// coming back to the command at the program counter once it ends
+    /// Handlers don't interrupt each other, so nothing happens while one is being run
+    /// return: whether the handler was started
+    pub fn interrupt(&mut self, trigger: Trigger) -> bool {
+        let start = match self.handlers.get(&trigger) {
+            Some(&start) => start,
+            None => return false,
+        };
+        if self.interrupted.is_some() {
+            return false;
+        }
+        self.interrupted = Some((self.program_counter, self.loops.len(), self.calls.len()));
+        self.program_counter = start + 1;
+        true
+    }
+    /// Runs the ENDON at the program counter, going back to where the handler interrupted
+    /// Loops and subroutines started in the handler are left
+    /// return: whether a handler was being run, in which case the program counter
+    /// is on the interrupted command and shouldn't advance
+    pub fn end_handler(&mut self) -> bool {
+        match self.interrupted.take() {
+            Some((resume, loops, calls)) => {
+                self.loops.truncate(loops);
+                self.calls.truncate(calls);
+                self.program_counter = resume;
+                true
+            }
+            None => false,
+        }
+    }
+    /// Runs the ON at the program counter, reached without its event happening,
+    /// by going on after its ENDON
+    pub fn skip_handler(&mut self) {
+        let start = self.program_counter;
+        let end = self.commands[start + 1..]
+            .iter()
+            .position(|command| *command == SwarmCommand::ENDON);
+        if let Some(end) = end {
+            self.program_counter = start + 1 + end;
+        }
+    }
+    /// Starts running the LOOP at the program counter
+    /// count: how many times the block runs
+    pub fn enter_loop(&mut self, count: u32) {
+        // A loop entered again is started over
+        let start = self.program_counter;
+        let callers = self.callers_loops();
+        if let Some(position) = self.loops[callers..]
+            .iter()
+            .rposition(|&(index, _)| index == start)
+        {
+            self.loops.truncate(callers + position);
+        }
+        self.loops.push((start, count));
+    }
+    /// Ends a pass through the loop closed by the ENDLOOP at the program counter,
+    /// jumping back to its LOOP if it has repetitions left
+    /// Nothing happens if the LOOP wasn't run, as when a program is changed mid-loop
+    pub fn end_loop(&mut self) {
+        let start = match self.matching_loop(self.program_counter) {
+            Some(start) => start,
+            None => return,
+        };
+        // Inner loops whose ENDLOOP was skipped end with this one
+        let callers = self.callers_loops();
+        let position = match self.loops[callers..]
+            .iter()
+            .rposition(|&(index, _)| index == start)
+        {
+            Some(position) => callers + position,
+            None => return,
+        };
+        self.loops.truncate(position + 1);
+        self.loops[position].1 -= 1;
+        if self.loops[position].1 > 0 {
+            // Advancing past the LOOP lands on the first command of its block
+            self.program_counter = start;
+        } else {
+            self.loops.pop();
+        }
+    }
+    /// Runs the IF at the program counter
+    /// holds: whether its condition holds; if not, the program goes on after its ELSE or ENDIF
+    pub fn branch(&mut self, holds: bool) {
+        if !holds {
+            if let Some(end) = self.block_end(self.program_counter, true) {
+                self.program_counter = end;
+            }
+        }
+    }
+    /// Runs the ELSE at the program counter, reached at the end of its IF's block,
+    /// by going on after its ENDIF
+    pub fn skip_else(&mut self) {
+        if let Some(end) = self.block_end(self.program_counter, false) {
+            self.program_counter = end;
+        }
+    }
+    /// Finds the ENDIF that closes the IF or ELSE at an index
+    /// stop_at_else: whether an ELSE of the same IF is found first
+    fn block_end(&self, start: usize, stop_at_else: bool) -> Option<usize> {
+        let mut depth = 0;
+        for index in start + 1..self.commands.len() {
+            match self.commands[index] {
+                SwarmCommand::IF(_) => depth += 1,
+                SwarmCommand::ELSE if depth == 0 && stop_at_else => return Some(index),
+                SwarmCommand::ENDIF if depth == 0 => return Some(index),
+                SwarmCommand::ENDIF => depth -= 1,
+                _ => {}
+            }
+        }
+        None
+    }
+    /// Finds the LOOP that the ENDLOOP at an index closes
+    fn matching_loop(&self, end: usize) -> Option<usize> {
+        let mut depth = 0;
+        for index in (0..end).rev() {
+            match self.commands[index] {
+                SwarmCommand::ENDLOOP => depth += 1,
+                SwarmCommand::LOOP(_) if depth == 0 => return Some(index),
+                SwarmCommand::LOOP(_) => depth -= 1,
+                _ => {}
+            }
+        }
+        None
+    }
+    /// Scores how complex and aggressive the program is
+    pub fn profile(&self) -> ProgramProfile {
+        ProgramProfile::analyze(self)
+    }
+}
+
+/// Allows a program to be printed as swarm code, one command per line
+impl fmt::Display for SwarmProgram {
+    /// Writes the program using a formatter
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for command in self.commands.iter() {
+            writeln!(formatter, "{}", command)?;
         }
+        Ok(())
     }
 }
 
@@ -226,40 +1175,956 @@ impl SwarmProgram {
 impl FromStr for SwarmProgram {
     /// The type of error returned if the conversion fails
     /// Must be implemented
-    type Err = GenericError;
+    type Err = ParseError;
     /// Converts a string to a SwarmProgram
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // TODO: Split the input and use SwarmCommand's from_str
+        let lines = command_lines(s);
 
-        // Vector of SwarmCommands
-        let mut command_list: Vec<SwarmCommand> = Vec::new();
+        // Check the length before parsing anything, pointing at the first command past it
+        if lines.len() > MAX_NUM_COMMANDS {
+            let (line, text) = lines[MAX_NUM_COMMANDS];
+            let error = LanguageError::TOO_LONG {
+                commands: lines.len(),
+                max: MAX_NUM_COMMANDS,
+            };
+            return Err(parse_error(line, text, error));
+        }
 
         // Turn lines into commands
-        for line in s.trim().lines() {
-            command_list.push(match line.parse() {
-                Ok(comm) => comm, // If the command is valid, add it to the list
-                Err(error) => {
-                    if line.trim().is_empty() {
-                        continue;
-                    } else {
-                        return Err(error);
-                    }
-                } // If the command is invalid, throw an error
-            });
-
-            // If the command list size is exceeded, throw an error
-            if (command_list.len() > MAX_NUM_COMMANDS) {
-                return Err(GenericError {
-                    description: "Program is too long: use fewer commands.".into(),
-                });
+        let mut command_list: Vec<SwarmCommand> = Vec::with_capacity(lines.len());
+        for &(line, text) in lines.iter() {
+            match text.parse() {
+                Ok(command) => command_list.push(command),
+                Err(error) => return Err(parse_error(line, text, error)),
             }
         }
+        if let Some((index, error)) = structure_errors(&command_list).into_iter().next() {
+            let (line, text) = lines[index];
+            return Err(parse_error(line, text, error));
+        }
 
         // Return command list
         Ok(SwarmProgram::new(command_list))
     }
 }
 
+/// Finds the line number and text of each line with a command, leaving out blank lines
+fn command_lines(s: &str) -> Vec<(usize, &str)> {
+    s.lines()
+        .enumerate()
+        .filter(|&(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| (index + 1, line))
+        .collect()
+}
+
+/// Finds the problems with how a program's commands fit together,
+/// rather than with any one command
+/// return: the index of each command with a problem, in order, with its error
+fn structure_errors(commands: &[SwarmCommand]) -> Vec<(usize, LanguageError)> {
+    let mut errors = unmatched_blocks(commands);
+    errors.extend(label_errors(commands));
+    errors.sort_by_key(|&(index, _)| index);
+    errors
+}
+
+/// Finds each LABEL or SUB whose name was already used, each second handler for a trigger,
+/// each JUMP to no LABEL, and each CALL to no SUB
+/// Jumps can't go into or out of a subroutine or handler, so only LABELs in the same one count
+/// return: the index of each command with a problem, in order, with its error
+fn label_errors(commands: &[SwarmCommand]) -> Vec<(usize, LanguageError)> {
+    let scopes = sub_scopes(commands);
+    let mut errors: Vec<(usize, LanguageError)> = Vec::new();
+    // Tuple of (name, index of the SUB it's in) for each LABEL
+    let mut defined: Vec<(Label, Option<usize>)> = Vec::new();
+    let mut subroutines: Vec<Label> = Vec::new();
+    let mut handlers: Vec<Trigger> = Vec::new();
+    for (index, command) in commands.iter().enumerate() {
+        match *command {
+            SwarmCommand::LABEL(label) => {
+                if defined.iter().any(|&(name, _)| name == label) {
+                    errors.push((index, LanguageError::DUPLICATE_LABEL(label.to_string())));
+                }
+                defined.push((label, scopes[index]));
+            }
+            SwarmCommand::SUB(name) => {
+                if subroutines.contains(&name) {
+                    errors.push((index, LanguageError::DUPLICATE_SUB(name.to_string())));
+                }
+                subroutines.push(name);
+            }
+            SwarmCommand::ON(trigger) => {
+                if handlers.contains(&trigger) {
+                    let error = LanguageError::DUPLICATE_HANDLER(trigger.opcode().into());
+                    errors.push((index, error));
+                }
+                handlers.push(trigger);
+            }
+            _ => {}
+        }
+    }
+    for (index, command) in commands.iter().enumerate() {
+        match *command {
+            SwarmCommand::JUMP(label) if !defined.contains(&(label, scopes[index])) => {
+                errors.push((index, LanguageError::UNDEFINED_LABEL(label.to_string())));
+            }
+            SwarmCommand::CALL(name) if !subroutines.contains(&name) => {
+                errors.push((index, LanguageError::UNDEFINED_SUB(name.to_string())));
+            }
+            _ => {}
+        }
+    }
+    errors.sort_by_key(|&(index, _)| index);
+    errors
+}
+
+/// Finds the SUB or ON block each command is in, counting the commands opening and closing it
+/// return: the index of the SUB or ON, for each command
+fn sub_scopes(commands: &[SwarmCommand]) -> Vec<Option<usize>> {
+    let mut scope = None;
+    commands
+        .iter()
+        .enumerate()
+        .map(|(index, command)| match *command {
+            SwarmCommand::SUB(_) | SwarmCommand::ON(_) => {
+                scope = Some(index);
+                scope
+            }
+            SwarmCommand::ENDSUB | SwarmCommand::ENDON => scope.take(),
+            _ => scope,
+        })
+        .collect()
+}
+
+/// Finds each command opening or closing a block that has no partner,
+/// and each SUB or ON inside another block or RETURN outside a SUB
+/// Blocks have to nest, so an ENDLOOP inside an IF's block doesn't close a LOOP outside it,
+/// and an IF has at most one ELSE
+/// return: the index of each unmatched or misplaced command, in order, with its error
+fn unmatched_blocks(commands: &[SwarmCommand]) -> Vec<(usize, LanguageError)> {
+    let mut unmatched: Vec<(usize, LanguageError)> = Vec::new();
+    // Tuple of (index, command) of the blocks opened and not yet closed, innermost last
+    // An IF is replaced by its ELSE once it has one
+    let mut open: Vec<(usize, SwarmCommand)> = Vec::new();
+    for (index, command) in commands.iter().enumerate() {
+        let innermost = open.last().map(|&(_, opener)| opener);
+        let closes = match (*command, innermost) {
+            (SwarmCommand::LOOP(_), _) | (SwarmCommand::IF(_), _) => {
+                open.push((index, *command));
+                continue;
+            }
+            (SwarmCommand::SUB(_), _) | (SwarmCommand::ON(_), _) => {
+                if !open.is_empty() {
+                    unmatched.push((index, LanguageError::MISPLACED(command.opcode().into())));
+                }
+                open.push((index, *command));
+                continue;
+            }
+            (SwarmCommand::RETURN, _) => {
+                let in_sub = open.iter().any(|&(_, opener)| opener.opcode() == "SUB");
+                if !in_sub {
+                    unmatched.push((index, LanguageError::MISPLACED("RETURN".into())));
+                }
+                continue;
+            }
+            (SwarmCommand::ENDLOOP, Some(SwarmCommand::LOOP(_)))
+            | (SwarmCommand::ENDSUB, Some(SwarmCommand::SUB(_)))
+            | (SwarmCommand::ENDON, Some(SwarmCommand::ON(_))) => true,
+            (SwarmCommand::ELSE, Some(SwarmCommand::IF(_))) => {
+                open.last_mut().unwrap().1 = SwarmCommand::ELSE;
+                continue;
+            }
+            (SwarmCommand::ENDIF, Some(SwarmCommand::IF(_)))
+            | (SwarmCommand::ENDIF, Some(SwarmCommand::ELSE)) => true,
+            (SwarmCommand::ENDLOOP, _)
+            | (SwarmCommand::ELSE, _)
+            | (SwarmCommand::ENDIF, _)
+            | (SwarmCommand::ENDSUB, _)
+            | (SwarmCommand::ENDON, _) => false,
+            _ => continue,
+        };
+        if closes {
+            open.pop();
+        } else {
+            unmatched.push((index, LanguageError::UNMATCHED_BLOCK(command.opcode().into())));
+        }
+    }
+    for (index, _) in open {
+        unmatched.push((
+            index,
+            LanguageError::UNMATCHED_BLOCK(commands[index].opcode().into()),
+        ));
+    }
+    unmatched.sort_by_key(|&(index, _)| index);
+    unmatched
+}
+
+/// Programs scoring below this are beginner programs
+pub const INTERMEDIATE_COMPLEXITY: u32 = 25;
+/// Programs scoring at least this are advanced programs
+pub const ADVANCED_COMPLEXITY: u32 = 50;
+/// Programs that loop this quickly and fire at least this often are flagged
+pub const SUSPICIOUS_CYCLE: usize = 2;
+/// Fraction of commands that are FIRE, at or above which a short loop is flagged
+pub const SUSPICIOUS_AGGRESSION: f32 = 0.5;
+
+/// How experienced the author of a program seems to be, for matchmaking
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum SkillTier {
+    /// Short, repetitive programs
+    #[serde(rename = "beginner")]
+    BEGINNER,
+    /// Programs using a few kinds of commands
+    #[serde(rename = "intermediate")]
+    INTERMEDIATE,
+    /// Long, varied programs
+    #[serde(rename = "advanced")]
+    ADVANCED,
+}
+
+/// What a program does and how complex it is
+/// Programs restart when they reach the end, so the loop structure is the shortest repeat
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ProgramProfile {
+    /// Number of commands
+    pub commands: usize,
+    /// MOVE, LEFT, RIGHT, UP and DOWN commands
+    pub movement: usize,
+    /// FIRE commands
+    pub fire: usize,
+    /// TURN commands
+    pub turns: usize,
+    /// FORMATION commands
+    pub formations: usize,
+    /// NOOP commands
+    pub noops: usize,
+    /// Number of different opcodes used
+    pub distinct: usize,
+    /// Length of the shortest run of commands the program repeats
+    pub cycle: usize,
+    /// Fraction of commands that are FIRE
+    pub aggression: f32,
+    /// Complexity score, from 0 to 100
+    pub complexity: u32,
+    /// The tier the score puts the program in
+    pub tier: SkillTier,
+    /// Whether the program does little but fire as fast as it can
+    pub suspicious: bool,
+}
+
+/// Functions for ProgramProfile
+impl ProgramProfile {
+    /// Scores a program
+    pub fn analyze(program: &SwarmProgram) -> Self {
+        let commands = &program.commands;
+        let mut opcodes: Vec<&'static str> = Vec::new();
+        let (mut movement, mut fire, mut turns, mut formations, mut noops) = (0, 0, 0, 0, 0);
+        for command in commands.iter() {
+            let opcode = command.opcode();
+            match *command {
+                SwarmCommand::FIRE => fire += 1,
+                SwarmCommand::TURN(_) | SwarmCommand::TURN_REGISTER(_) => turns += 1,
+                SwarmCommand::NOOP | SwarmCommand::WAIT(_) => noops += 1,
+                SwarmCommand::FORMATION(_) => formations += 1,
+                SwarmCommand::IF(_)
+                | SwarmCommand::ELSE
+                | SwarmCommand::ENDIF
+                | SwarmCommand::LOOP(_)
+                | SwarmCommand::ENDLOOP
+                | SwarmCommand::LABEL(_)
+                | SwarmCommand::JUMP(_)
+                | SwarmCommand::SUB(_)
+                | SwarmCommand::ENDSUB
+                | SwarmCommand::CALL(_)
+                | SwarmCommand::RETURN
+                | SwarmCommand::CALC(..)
+                | SwarmCommand::SCAN(_)
+                | SwarmCommand::NEAREST_ENEMY(..)
+                | SwarmCommand::NEAREST_BULLET(..)
+                | SwarmCommand::EMOTE(_)
+                | SwarmCommand::ON(_)
+                | SwarmCommand::ENDON => {}
+                _ => movement += 1,
+            }
+            if !opcodes.contains(&opcode) {
+                opcodes.push(opcode);
+            }
+        }
+        // The shortest prefix that, repeated, makes up the whole program
+        let cycle = (1..commands.len() + 1)
+            .find(|&length| {
+                commands.len() % length == 0
+                    && commands
+                        .iter()
+                        .enumerate()
+                        .all(|(index, command)| *command == commands[index % length])
+            })
+            .unwrap_or(0);
+        let aggression = if commands.is_empty() {
+            0.0
+        } else {
+            fire as f32 / commands.len() as f32
+        };
+        // Variety counts most, then how long the loop is, then parameterized commands in it
+        let parameterized = commands[..cycle]
+            .iter()
+            .filter(|command| match **command {
+                SwarmCommand::TURN(_)
+                | SwarmCommand::FORMATION(_)
+                | SwarmCommand::IF(_)
+                | SwarmCommand::LOOP(_)
+                | SwarmCommand::JUMP(_)
+                | SwarmCommand::CALL(_)
+                | SwarmCommand::TURN_REGISTER(_)
+                | SwarmCommand::CALC(..)
+                | SwarmCommand::SCAN(_)
+                | SwarmCommand::NEAREST_ENEMY(..)
+                | SwarmCommand::NEAREST_BULLET(..)
+                | SwarmCommand::EMOTE(_)
+                | SwarmCommand::WAIT(_) => true,
+                _ => false,
+            })
+            .count();
+        let complexity = (opcodes.len() * 8 + cycle * 3 + parameterized * 2).min(100) as u32;
+        let tier = if complexity >= ADVANCED_COMPLEXITY {
+            SkillTier::ADVANCED
+        } else if complexity >= INTERMEDIATE_COMPLEXITY {
+            SkillTier::INTERMEDIATE
+        } else {
+            SkillTier::BEGINNER
+        };
+        // Short loops that mostly fire are how players cheese the fire rate
+        let suspicious =
+            cycle > 0 && cycle <= SUSPICIOUS_CYCLE && aggression >= SUSPICIOUS_AGGRESSION;
+        ProgramProfile {
+            commands: commands.len(),
+            movement: movement,
+            fire: fire,
+            turns: turns,
+            formations: formations,
+            noops: noops,
+            distinct: opcodes.len(),
+            cycle: cycle,
+            aggression: aggression,
+            complexity: complexity,
+            tier: tier,
+            suspicious: suspicious,
+        }
+    }
+}
+
+/// The kind of value a parameter takes
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum ParameterKind {
+    /// A decimal number
+    #[serde(rename = "number")]
+    NUMBER,
+    /// A whole number
+    #[serde(rename = "integer")]
+    INTEGER,
+    /// One of a list of names
+    #[serde(rename = "name")]
+    NAME,
+    /// The name of a LABEL or SUB in the program
+    #[serde(rename = "label")]
+    LABEL,
+}
+
+/// Describes a parameter of an opcode
+#[derive(Clone, Debug, Serialize)]
+pub struct ParameterInfo {
+    /// Name of the parameter, for documentation
+    pub name: &'static str,
+    /// The kind of value it takes
+    pub kind: ParameterKind,
+    /// Smallest value allowed, for numbers
+    pub min: Option<f32>,
+    /// Largest value allowed, for numbers
+    pub max: Option<f32>,
+    /// Names allowed, for names
+    pub values: &'static [&'static str],
+    /// Whether the parameter can be left out
+    pub optional: bool,
+    /// What the parameter means
+    pub doc: &'static str,
+}
+
+/// Describes an opcode of the swarm language
+/// Opcodes and names are case insensitive, but are written in upper case
+#[derive(Clone, Debug, Serialize)]
+pub struct OpcodeInfo {
+    /// The opcode
+    pub name: &'static str,
+    /// Parameters, in order
+    pub parameters: &'static [ParameterInfo],
+    /// What the command does
+    pub doc: &'static str,
+    /// A line using the command
+    pub example: &'static str,
+}
+
+/// Parameters of the commands that change a register
+static REGISTER_PARAMETERS: &'static [ParameterInfo] = &[
+    ParameterInfo {
+        name: "register",
+        kind: ParameterKind::NAME,
+        min: None,
+        max: None,
+        values: REGISTER_NAMES,
+        optional: false,
+        doc: "The register to change",
+    },
+    ParameterInfo {
+        name: "value",
+        kind: ParameterKind::INTEGER,
+        min: None,
+        max: None,
+        values: &[],
+        optional: false,
+        doc: "A whole number, or a register holding one",
+    },
+];
+
+/// Parameters of the commands that find the closest thing of a kind
+static NEAREST_PARAMETERS: &'static [ParameterInfo] = &[
+    ParameterInfo {
+        name: "distance",
+        kind: ParameterKind::NAME,
+        min: None,
+        max: None,
+        values: REGISTER_NAMES,
+        optional: false,
+        doc: "The register to store the distance in, or -1 if nothing is in range",
+    },
+    ParameterInfo {
+        name: "bearing",
+        kind: ParameterKind::NAME,
+        min: None,
+        max: None,
+        values: REGISTER_NAMES,
+        optional: false,
+        doc: "The register to store the bearing in, in degrees counterclockwise from the heading",
+    },
+];
+
+/// Every opcode of the swarm language, for editors and documentation
+/// The ranges are the ones the parser enforces
+pub static OPCODES: &'static [OpcodeInfo] = &[
+    OpcodeInfo {
+        name: "MOVE",
+        parameters: &[ParameterInfo {
+            name: "distance",
+            kind: ParameterKind::NUMBER,
+            min: Some(0.0),
+            max: Some(MAX_MOVE_DISTANCE),
+            values: &[],
+            optional: true,
+            doc: "Distance to go, more than 0; one tick's step if left out",
+        }],
+        doc: "Moves the swarm forward in the direction it is facing, over several ticks \
+              if given a distance",
+        example: "MOVE 150",
+    },
+    OpcodeInfo {
+        name: "LEFT",
+        parameters: &[],
+        doc: "Moves the swarm left, whichever way it is facing",
+        example: "LEFT",
+    },
+    OpcodeInfo {
+        name: "RIGHT",
+        parameters: &[],
+        doc: "Moves the swarm right, whichever way it is facing",
+        example: "RIGHT",
+    },
+    OpcodeInfo {
+        name: "UP",
+        parameters: &[],
+        doc: "Moves the swarm up, whichever way it is facing",
+        example: "UP",
+    },
+    OpcodeInfo {
+        name: "DOWN",
+        parameters: &[],
+        doc: "Moves the swarm down, whichever way it is facing",
+        example: "DOWN",
+    },
+    OpcodeInfo {
+        name: "FIRE",
+        parameters: &[],
+        doc: "Fires a bullet from each ready member, unless the swarm's weapon is cooling down",
+        example: "FIRE",
+    },
+    OpcodeInfo {
+        name: "TURN",
+        parameters: &[ParameterInfo {
+            name: "degrees",
+            kind: ParameterKind::NUMBER,
+            min: Some(-MAX_TURN),
+            max: Some(MAX_TURN),
+            values: &[],
+            optional: false,
+            doc: "Degrees to turn, counterclockwise; can't be 0, or a register holding them",
+        }],
+        doc: "Turns the swarm",
+        example: "TURN 15",
+    },
+    OpcodeInfo {
+        name: "NOOP",
+        parameters: &[],
+        doc: "Does nothing for a tick",
+        example: "NOOP",
+    },
+    OpcodeInfo {
+        name: "WAIT",
+        parameters: &[ParameterInfo {
+            name: "ticks",
+            kind: ParameterKind::NUMBER,
+            min: Some(1.0),
+            max: Some(MAX_WAIT_TICKS as f32),
+            values: &[],
+            optional: false,
+            doc: "Ticks to do nothing for, a whole number",
+        }],
+        doc: "Does nothing for several ticks, without going on to the next command",
+        example: "WAIT 30",
+    },
+    OpcodeInfo {
+        name: "FORMATION",
+        parameters: &[
+            ParameterInfo {
+                name: "formation",
+                kind: ParameterKind::NAME,
+                min: None,
+                max: None,
+                values: &["GATHER", "SPREAD", "SIERPINSKI"],
+                optional: false,
+                doc: "The formation to move into",
+            },
+            ParameterInfo {
+                name: "foci",
+                kind: ParameterKind::INTEGER,
+                min: Some(0.0),
+                max: Some(MAX_SIERPINSKI_FOCI as f32),
+                values: &[],
+                optional: true,
+                doc: "Number of focal points, only for SIERPINSKI",
+            },
+        ],
+        doc: "Rearranges the swarm's members, unless the swarm changed formation recently",
+        example: "FORMATION SPREAD",
+    },
+    OpcodeInfo {
+        name: "IF",
+        parameters: &[ParameterInfo {
+            name: "condition",
+            kind: ParameterKind::NAME,
+            min: None,
+            max: None,
+            values: &["CAN_FIRE", "ENEMY_NEAR", "WALL_AHEAD", "HEALTH_LOW"],
+            optional: false,
+            doc: "CAN_FIRE holds when the swarm's weapon is ready, ENEMY_NEAR when another \
+                  swarm is close, WALL_AHEAD when the edge or an obstacle is just ahead, \
+                  and HEALTH_LOW when the members have under half a new swarm's health",
+        }],
+        doc: "Runs the commands up to the matching ELSE or ENDIF only if the condition holds",
+        example: "IF CAN_FIRE",
+    },
+    OpcodeInfo {
+        name: "ELSE",
+        parameters: &[],
+        doc: "Runs the commands up to the matching ENDIF only if the IF's condition didn't hold",
+        example: "ELSE",
+    },
+    OpcodeInfo {
+        name: "ENDIF",
+        parameters: &[],
+        doc: "Ends an IF block",
+        example: "ENDIF",
+    },
+    OpcodeInfo {
+        name: "LOOP",
+        parameters: &[ParameterInfo {
+            name: "count",
+            kind: ParameterKind::INTEGER,
+            min: Some(1.0),
+            max: Some(MAX_LOOP_COUNT as f32),
+            values: &[],
+            optional: false,
+            doc: "Times to run the block",
+        }],
+        doc: "Runs the commands up to the matching ENDLOOP some number of times",
+        example: "LOOP 5",
+    },
+    OpcodeInfo {
+        name: "ENDLOOP",
+        parameters: &[],
+        doc: "Ends a LOOP block, going back to its start if it has repetitions left",
+        example: "ENDLOOP",
+    },
+    OpcodeInfo {
+        name: "LABEL",
+        parameters: &[ParameterInfo {
+            name: "label",
+            kind: ParameterKind::LABEL,
+            min: None,
+            max: None,
+            values: &[],
+            optional: false,
+            doc: "A name no other LABEL in the program has",
+        }],
+        doc: "Marks a place in the program for JUMP to go to",
+        example: "LABEL start",
+    },
+    OpcodeInfo {
+        name: "JUMP",
+        parameters: &[ParameterInfo {
+            name: "label",
+            kind: ParameterKind::LABEL,
+            min: None,
+            max: None,
+            values: &[],
+            optional: false,
+            doc: "The name of a LABEL in the program",
+        }],
+        doc: "Goes on from the LABEL with the name, leaving any loops it is outside of",
+        example: "JUMP start",
+    },
+    OpcodeInfo {
+        name: "SUB",
+        parameters: &[ParameterInfo {
+            name: "name",
+            kind: ParameterKind::LABEL,
+            min: None,
+            max: None,
+            values: &[],
+            optional: false,
+            doc: "A name no other SUB in the program has",
+        }],
+        doc: "Defines a subroutine up to the matching ENDSUB, run only when called; \
+              SUB with a register and a value subtracts instead, like ADD",
+        example: "SUB strafe",
+    },
+    OpcodeInfo {
+        name: "ENDSUB",
+        parameters: &[],
+        doc: "Ends a SUB block, returning to the CALL that ran it",
+        example: "ENDSUB",
+    },
+    OpcodeInfo {
+        name: "CALL",
+        parameters: &[ParameterInfo {
+            name: "name",
+            kind: ParameterKind::LABEL,
+            min: None,
+            max: None,
+            values: &[],
+            optional: false,
+            doc: "The name of a SUB in the program",
+        }],
+        doc: "Runs the SUB with the name, then goes on after the CALL",
+        example: "CALL strafe",
+    },
+    OpcodeInfo {
+        name: "RETURN",
+        parameters: &[],
+        doc: "Returns from a subroutine before reaching its ENDSUB",
+        example: "RETURN",
+    },
+    OpcodeInfo {
+        name: "SET",
+        parameters: REGISTER_PARAMETERS,
+        doc: "Stores a value in a register",
+        example: "SET R0 10",
+    },
+    OpcodeInfo {
+        name: "ADD",
+        parameters: REGISTER_PARAMETERS,
+        doc: "Adds a value to a register",
+        example: "ADD R0 1",
+    },
+    OpcodeInfo {
+        name: "MUL",
+        parameters: REGISTER_PARAMETERS,
+        doc: "Multiplies a register by a value",
+        example: "MUL R0 -1",
+    },
+    OpcodeInfo {
+        name: "SCAN",
+        parameters: &[ParameterInfo {
+            name: "count",
+            kind: ParameterKind::NAME,
+            min: None,
+            max: None,
+            values: REGISTER_NAMES,
+            optional: false,
+            doc: "The register to store the count in",
+        }],
+        doc: "Counts the other swarms in sensing range",
+        example: "SCAN R0",
+    },
+    OpcodeInfo {
+        name: "NEAREST_ENEMY",
+        parameters: NEAREST_PARAMETERS,
+        doc: "Finds the closest other swarm in sensing range",
+        example: "NEAREST_ENEMY R0 R1",
+    },
+    OpcodeInfo {
+        name: "NEAREST_BULLET",
+        parameters: NEAREST_PARAMETERS,
+        doc: "Finds the closest bullet fired by another swarm in sensing range",
+        example: "NEAREST_BULLET R0 R1",
+    },
+    OpcodeInfo {
+        name: "EMOTE",
+        parameters: &[ParameterInfo {
+            name: "emote",
+            kind: ParameterKind::INTEGER,
+            min: Some(0.0),
+            max: Some((NUM_EMOTES - 1) as f32),
+            values: &[],
+            optional: false,
+            doc: "Number of the emote",
+        }],
+        doc: "Shows an emote over the swarm to everyone watching, including replays",
+        example: "EMOTE 3",
+    },
+    OpcodeInfo {
+        name: "ON_SPAWN",
+        parameters: &[],
+        doc: "Defines a handler up to the matching ENDON, run when the swarm respawns",
+        example: "ON_SPAWN",
+    },
+    OpcodeInfo {
+        name: "ON_HIT",
+        parameters: &[],
+        doc: "Defines a handler up to the matching ENDON, run the tick after the swarm is damaged",
+        example: "ON_HIT",
+    },
+    OpcodeInfo {
+        name: "ON_ENEMY_SIGHTED",
+        parameters: &[],
+        doc: "Defines a handler up to the matching ENDON, \
+              run the tick after another swarm comes close when none was",
+        example: "ON_ENEMY_SIGHTED",
+    },
+    OpcodeInfo {
+        name: "ENDON",
+        parameters: &[],
+        doc: "Ends an ON block, going back to where the program was when the handler started",
+        example: "ENDON",
+    },
+];
+
+/// A problem found on one line of a program
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// Line number, starting from 1
+    pub line: usize,
+    /// Column the problem starts at, in characters starting from 1
+    pub column: usize,
+    /// The problem
+    pub error: LanguageError,
+    /// How the problem might be fixed, in English, if there's an obvious way
+    pub hint: Option<String>,
+}
+/// Functions for Diagnostic
+impl Diagnostic {
+    /// Constructor
+    /// line: line number, starting from 1
+    /// text: the line, to find the column of the problem in
+    pub fn new(line: usize, text: &str, error: LanguageError) -> Self {
+        Diagnostic {
+            line: line,
+            column: column(text, &error),
+            hint: hint(&error),
+            error: error,
+        }
+    }
+}
+/// Allows a parse error to be turned into a diagnostic, with a hint for fixing it
+impl From<ParseError> for Diagnostic {
+    fn from(error: ParseError) -> Self {
+        Diagnostic {
+            line: error.line,
+            column: error.column,
+            hint: hint(&error.kind),
+            error: error.kind,
+        }
+    }
+}
+/// Allows a diagnostic to be printed
+impl fmt::Display for Diagnostic {
+    /// Writes the diagnostic using a formatter
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "line {}: {}", self.line, self.error)
+    }
+}
+
+/// Checks every line of a program, rather than stopping at the first error
+/// A program with no diagnostics parses successfully
+pub fn check_program(s: &str) -> Vec<Diagnostic> {
+    match SwarmProgram::compile_all(s) {
+        Ok(_) => Vec::new(),
+        Err(errors) => errors.into_iter().map(Diagnostic::from).collect(),
+    }
+}
+
+/// Finds the column of a line where the problem is, starting from 1
+/// Problems with a parameter point at the parameter, and missing parameters past the end
+fn column(text: &str, error: &LanguageError) -> usize {
+    let start = text.len() - text.trim_left().len();
+    let end = text.trim_right().len();
+    let offset = match *error {
+        LanguageError::UNKNOWN_FORMATION(ref parameter)
+        | LanguageError::UNKNOWN_CONDITION(ref parameter)
+        | LanguageError::INVALID_PARAMETER { ref parameter, .. } => {
+            // Parameters are looked for after the opcode, which might contain them
+            let opcode = text[start..]
+                .find(char::is_whitespace)
+                .map_or(end, |length| start + length);
+            text[opcode..]
+                .find(parameter.as_str())
+                .map_or(start, |found| opcode + found)
+        }
+        // The parameter out of range is always the last one
+        LanguageError::OUT_OF_RANGE { .. } => text[..end]
+            .char_indices()
+            .rev()
+            .find(|&(_, letter)| letter.is_whitespace())
+            .map_or(start, |(space, letter)| space + letter.len_utf8()),
+        // After the space the parameter would be written past
+        LanguageError::MISSING_PARAMETER(_) => return text[..end].chars().count() + 2,
+        // The first parameter past the max, counting from the opcode
+        LanguageError::TOO_MANY_PARAMETERS { max, .. } => {
+            word_start(text, max + 1).unwrap_or(start)
+        }
+        _ => start,
+    };
+    text[..offset].chars().count() + 1
+}
+
+/// Finds where a word of a line starts, counting words from 0
+fn word_start(text: &str, index: usize) -> Option<usize> {
+    let mut spaced = true;
+    text.char_indices()
+        .filter(|&(_, letter)| {
+            let starts = spaced && !letter.is_whitespace();
+            spaced = letter.is_whitespace();
+            starts
+        })
+        .map(|(offset, _)| offset)
+        .nth(index)
+}
+
+/// Finds the word of a line a problem is with, from the column it starts at
+/// A missing parameter is a problem with the command that needs it
+fn token(text: &str, column: usize) -> String {
+    let word: String = text
+        .chars()
+        .skip(column - 1)
+        .take_while(|letter| !letter.is_whitespace())
+        .collect();
+    if word.is_empty() {
+        text.split_whitespace().next().unwrap_or("").into()
+    } else {
+        word
+    }
+}
+
+/// Makes the error for a problem with a line
+/// line: line number, starting from 1
+/// text: the line, to find the column and word of the problem in
+fn parse_error(line: usize, text: &str, error: LanguageError) -> ParseError {
+    let column = column(text, &error);
+    ParseError::new(line, column, token(text, column), error)
+}
+
+/// Suggests how a problem might be fixed, if there's an obvious way
+fn hint(error: &LanguageError) -> Option<String> {
+    match *error {
+        LanguageError::UNKNOWN_COMMAND(ref command) => {
+            // Typos of a letter or two, for every three letters typed
+            let command = command.to_uppercase();
+            let most = (command.chars().count() / 3).max(1);
+            OPCODES
+                .iter()
+                .map(|opcode| (edit_distance(&command, opcode.name), opcode.name))
+                .filter(|&(distance, _)| distance <= most)
+                .min()
+                .map(|(_, name)| format!("Did you mean {}?", name))
+        }
+        LanguageError::UNKNOWN_FORMATION(_) => allowed_values("FORMATION", "Formations"),
+        LanguageError::UNKNOWN_CONDITION(_) => allowed_values("IF", "Conditions"),
+        LanguageError::MISSING_PARAMETER(ref command)
+        | LanguageError::TOO_MANY_PARAMETERS { ref command, .. } => OPCODES
+            .iter()
+            .find(|opcode| opcode.name == command.as_str() && !opcode.parameters.is_empty())
+            .map(|opcode| format!("For example: {}", opcode.example)),
+        LanguageError::UNDEFINED_LABEL(ref label) => {
+            Some(format!("Add LABEL {} where the jump should go", label))
+        }
+        LanguageError::DUPLICATE_LABEL(_) => Some("Give each LABEL its own name".into()),
+        LanguageError::UNDEFINED_SUB(ref name) => {
+            Some(format!("Add SUB {} with the commands to run", name))
+        }
+        LanguageError::DUPLICATE_SUB(_) => Some("Give each SUB its own name".into()),
+        LanguageError::DUPLICATE_HANDLER(ref command) => {
+            Some(format!("Put everything {} should do in one block", command))
+        }
+        LanguageError::MISPLACED(ref command) => Some(match command.as_str() {
+            "SUB" => "Define subroutines outside any other block".into(),
+            "ON_SPAWN" | "ON_HIT" | "ON_ENEMY_SIGHTED" => {
+                "Define handlers outside any other block".into()
+            }
+            _ => format!("Use {} inside a SUB block", command),
+        }),
+        LanguageError::UNMATCHED_BLOCK(ref command) => Some(match command.as_str() {
+            "LOOP" => "End the block with ENDLOOP".into(),
+            "IF" => "End the block with ENDIF".into(),
+            "SUB" => "End the block with ENDSUB".into(),
+            "ON_SPAWN" | "ON_HIT" | "ON_ENEMY_SIGHTED" => "End the block with ENDON".into(),
+            _ => format!("Open a block before {}, and close any blocks inside it", command),
+        }),
+        _ => None,
+    }
+}
+
+/// Lists the values an opcode's first parameter can take
+fn allowed_values(opcode: &str, kind: &str) -> Option<String> {
+    OPCODES
+        .iter()
+        .find(|info| info.name == opcode)
+        .and_then(|info| info.parameters.first())
+        .map(|parameter| format!("{} are {}", kind, parameter.values.join(", ")))
+}
+
+/// Number of letters to add, remove, replace or swap to turn one word into another
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // distances[i][j] is the distance between the first i letters of a and first j of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in 0..a.len() + 1 {
+        distances[i][0] = i;
+    }
+    for j in 0..b.len() + 1 {
+        distances[0][j] = j;
+    }
+    for i in 1..a.len() + 1 {
+        for j in 1..b.len() + 1 {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
 #[test]
 fn test_comlist_generator() {
     let mut program: String = String::new();
@@ -296,4 +2161,482 @@ mod tests {
     fn parse_swarm_program() {
         let command: SwarmProgram = "test".parse().unwrap();
     }
+
+    #[test]
+    fn parse_hostile_input() {
+        // Inputs that once looked likely to panic must only be rejected
+        for input in [
+            "TURN",
+            "TURN NaN",
+            "TURN 1e40",
+            "TURN -0",
+            "FORMATION",
+            "FORMATION SIERPINSKI",
+            "FORMATION SIERPINSKI 4294967295",
+            "\u{0}",
+            "İ",
+        ].iter()
+        {
+            assert!(input.parse::<SwarmCommand>().is_err(), "{:?} parsed", input);
+        }
+        assert!("MOVE\n".repeat(MAX_NUM_COMMANDS + 1).parse::<SwarmProgram>().is_err());
+    }
+
+    #[test]
+    fn check_program_lines() {
+        let diagnostics = check_program("MOVE\n\nTURN 90\nFIRE\nDANCE\n");
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic {
+                    line: 3,
+                    column: 6,
+                    error: LanguageError::OUT_OF_RANGE {
+                        command: "TURN".into(),
+                        min: -30.0,
+                        max: 30.0,
+                    },
+                    hint: None,
+                },
+                Diagnostic {
+                    line: 5,
+                    column: 1,
+                    error: LanguageError::UNKNOWN_COMMAND("DANCE".into()),
+                    hint: None,
+                },
+            ]
+        );
+        let program = "MOVE\n".repeat(MAX_NUM_COMMANDS + 2);
+        assert_eq!(check_program(&program)[0].line, MAX_NUM_COMMANDS + 1);
+        assert!(check_program("MOVE\nFIRE").is_empty());
+    }
+
+    #[test]
+    fn diagnostic_columns_and_hints() {
+        let diagnostics = check_program("  MOEV\nFORMATION  CIRCLE\nTURN\nIF CAN_FIRE\nLOOP 500");
+        let found: Vec<(usize, usize)> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.column))
+            .collect();
+        assert_eq!(found, vec![(1, 3), (2, 12), (3, 6), (4, 1), (5, 6)]);
+        assert_eq!(diagnostics[0].hint, Some("Did you mean MOVE?".into()));
+        assert_eq!(
+            diagnostics[1].hint,
+            Some("Formations are GATHER, SPREAD, SIERPINSKI".into())
+        );
+        assert_eq!(diagnostics[2].hint, Some("For example: TURN 15".into()));
+        assert_eq!(diagnostics[3].hint, Some("End the block with ENDIF".into()));
+        assert_eq!(diagnostics[4].hint, None);
+    }
+
+    #[test]
+    fn opcode_examples_parse() {
+        for opcode in OPCODES.iter() {
+            let command: SwarmCommand = opcode.example.parse().unwrap();
+            assert_eq!(command.to_string().split_whitespace().next(), Some(opcode.name));
+        }
+        // The documented range is the one enforced
+        let turn = &OPCODES.iter().find(|opcode| opcode.name == "TURN").unwrap().parameters[0];
+        let max = turn.max.unwrap();
+        assert!(format!("TURN {}", max).parse::<SwarmCommand>().is_ok());
+        assert!(format!("TURN {}", max + 1.0).parse::<SwarmCommand>().is_err());
+    }
+
+    #[test]
+    fn parse_formations() {
+        assert_eq!(
+            "formation sierpinski 5".parse::<SwarmCommand>().unwrap(),
+            SwarmCommand::FORMATION(Formation::SIERPINSKI(5))
+        );
+        assert_eq!(
+            "FORMATION SIERPINSKI".parse::<SwarmCommand>(),
+            Err(LanguageError::MISSING_PARAMETER("SIERPINSKI".into()))
+        );
+        assert_eq!(
+            "FORMATION gather".parse::<SwarmCommand>().unwrap(),
+            SwarmCommand::FORMATION(Formation::GATHER)
+        );
+    }
+
+    #[test]
+    fn parse_conditions() {
+        let command = "if can_fire".parse::<SwarmCommand>().unwrap();
+        assert_eq!(command, SwarmCommand::IF(Condition::CAN_FIRE));
+        assert_eq!(command.to_string(), "IF CAN_FIRE");
+        assert_eq!(
+            "IF".parse::<SwarmCommand>(),
+            Err(LanguageError::MISSING_PARAMETER("IF".into()))
+        );
+        assert_eq!(
+            "IF HUNGRY".parse::<SwarmCommand>(),
+            Err(LanguageError::UNKNOWN_CONDITION("HUNGRY".into()))
+        );
+    }
+
+    #[test]
+    fn parse_loops() {
+        let program: SwarmProgram = "loop 3\nMOVE\nLOOP 2\nFIRE\nENDLOOP\nendloop"
+            .parse()
+            .unwrap();
+        assert_eq!(program.commands[0], SwarmCommand::LOOP(3));
+        assert_eq!(program.commands[5], SwarmCommand::ENDLOOP);
+        assert_eq!(program.to_string().parse::<SwarmProgram>().unwrap().commands, program.commands);
+        assert!(match "LOOP 0".parse::<SwarmCommand>() {
+            Err(LanguageError::OUT_OF_RANGE { .. }) => true,
+            _ => false,
+        });
+        assert_eq!(
+            "LOOP 2\nMOVE".parse::<SwarmProgram>().unwrap_err().kind,
+            LanguageError::UNMATCHED_BLOCK("LOOP".into())
+        );
+        let diagnostics = check_program("ENDLOOP\nLOOP 2\nMOVE\nENDLOOP\nLOOP 4");
+        assert_eq!(
+            diagnostics.iter().map(|diagnostic| diagnostic.line).collect::<Vec<_>>(),
+            vec![1, 5]
+        );
+    }
+
+    #[test]
+    fn parse_if_blocks() {
+        let program: SwarmProgram = "IF enemy_near\nFIRE\nELSE\nIF WALL_AHEAD\nTURN 30\nENDIF\n\
+                                     MOVE\nendif"
+            .parse()
+            .unwrap();
+        assert_eq!(program.commands[0], SwarmCommand::IF(Condition::ENEMY_NEAR));
+        assert_eq!(program.commands[2], SwarmCommand::ELSE);
+        assert_eq!(program.commands[7], SwarmCommand::ENDIF);
+        assert_eq!(
+            "IF CAN_FIRE\nELSE\nELSE\nENDIF".parse::<SwarmProgram>().unwrap_err().kind,
+            LanguageError::UNMATCHED_BLOCK("ELSE".into())
+        );
+        // Blocks have to nest
+        let diagnostics = check_program("LOOP 2\nIF HEALTH_LOW\nENDLOOP\nENDIF");
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.line, diagnostic.error.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, LanguageError::UNMATCHED_BLOCK("LOOP".into())),
+                (3, LanguageError::UNMATCHED_BLOCK("ENDLOOP".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_labels() {
+        let program: SwarmProgram = "LABEL start\nMOVE\njump start".parse().unwrap();
+        let start: Label = "start".parse().unwrap();
+        assert_eq!(program.commands[2], SwarmCommand::JUMP(start));
+        assert_eq!(program.labels.get(&start), Some(&0));
+        assert_eq!(program.to_string(), "LABEL start\nMOVE\nJUMP start\n");
+        // Labels keep their case
+        assert_eq!(
+            "LABEL start\nJUMP START".parse::<SwarmProgram>().unwrap_err().kind,
+            LanguageError::UNDEFINED_LABEL("START".into())
+        );
+        assert!(match "LABEL no-dashes".parse::<SwarmCommand>() {
+            Err(LanguageError::INVALID_PARAMETER { .. }) => true,
+            _ => false,
+        });
+        let diagnostics = check_program("LABEL a\nJUMP b\nLABEL a");
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.line, diagnostic.error.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (2, LanguageError::UNDEFINED_LABEL("b".into())),
+                (3, LanguageError::DUPLICATE_LABEL("a".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_subroutines() {
+        let program: SwarmProgram = "CALL zig\nMOVE\nSUB zig\nTURN 10\nreturn\nENDSUB"
+            .parse()
+            .unwrap();
+        let zig: Label = "zig".parse().unwrap();
+        assert_eq!(program.commands[0], SwarmCommand::CALL(zig));
+        assert_eq!(program.subroutines.get(&zig), Some(&2));
+        assert!(program.labels.is_empty());
+        assert_eq!(
+            program.to_string(),
+            "CALL zig\nMOVE\nSUB zig\nTURN 10\nRETURN\nENDSUB\n"
+        );
+        let diagnostics = check_program(
+            "CALL a\nRETURN\nLOOP 2\nSUB b\nENDSUB\nENDLOOP\nSUB b\nJUMP c\nENDSUB\nLABEL c",
+        );
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.line, diagnostic.error.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, LanguageError::UNDEFINED_SUB("a".into())),
+                (2, LanguageError::MISPLACED("RETURN".into())),
+                (4, LanguageError::MISPLACED("SUB".into())),
+                (7, LanguageError::DUPLICATE_SUB("b".into())),
+                // Jumps can't leave a subroutine
+                (8, LanguageError::UNDEFINED_LABEL("c".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_registers() {
+        let source = "set r0 5\nADD R1 R0\nSUB R1 -2\nMUL R1 3\nTURN R1\nSUB zig\nENDSUB";
+        let program: SwarmProgram = source.parse().unwrap();
+        let (r0, r1) = (Register(0), Register(1));
+        assert_eq!(
+            program.commands[..5].to_vec(),
+            vec![
+                SwarmCommand::CALC(Operation::SET, r0, Operand::VALUE(5)),
+                SwarmCommand::CALC(Operation::ADD, r1, Operand::REGISTER(r0)),
+                SwarmCommand::CALC(Operation::SUB, r1, Operand::VALUE(-2)),
+                SwarmCommand::CALC(Operation::MUL, r1, Operand::VALUE(3)),
+                SwarmCommand::TURN_REGISTER(r1),
+            ]
+        );
+        // SUB with one name still defines a subroutine
+        assert!(program.subroutines.contains_key(&"zig".parse().unwrap()));
+        assert_eq!(
+            program.to_string(),
+            "SET R0 5\nADD R1 R0\nSUB R1 -2\nMUL R1 3\nTURN R1\nSUB zig\nENDSUB\n"
+        );
+        assert_eq!(
+            "SET R8 1".parse::<SwarmCommand>().unwrap_err(),
+            LanguageError::INVALID_PARAMETER {
+                command: "SET".into(),
+                parameter: "R8".into(),
+            }
+        );
+        assert_eq!(
+            "ADD R0".parse::<SwarmCommand>().unwrap_err(),
+            LanguageError::MISSING_PARAMETER("ADD".into())
+        );
+        let scan: SwarmProgram = "scan r2\nNEAREST_ENEMY R0 R1".parse().unwrap();
+        assert_eq!(scan.commands[0], SwarmCommand::SCAN(Register(2)));
+        assert!(scan.commands[1].senses());
+        assert_eq!(scan.to_string(), "SCAN R2\nNEAREST_ENEMY R0 R1\n");
+        assert_eq!(
+            "NEAREST_BULLET R0".parse::<SwarmCommand>().unwrap_err(),
+            LanguageError::MISSING_PARAMETER("NEAREST_BULLET".into())
+        );
+    }
+
+    #[test]
+    fn parse_handlers() {
+        let program: SwarmProgram = "MOVE\non_hit\nTURN 10\nENDON\nON_SPAWN\nFIRE\nendon"
+            .parse()
+            .unwrap();
+        assert_eq!(program.commands[1], SwarmCommand::ON(Trigger::HIT));
+        assert_eq!(program.handlers.get(&Trigger::HIT), Some(&1));
+        assert_eq!(program.handlers.get(&Trigger::SPAWN), Some(&4));
+        assert!(!program.handles(Trigger::ENEMY_SIGHTED));
+        assert_eq!(
+            program.to_string(),
+            "MOVE\nON_HIT\nTURN 10\nENDON\nON_SPAWN\nFIRE\nENDON\n"
+        );
+        let diagnostics = check_program(concat!(
+            "LABEL a\nON_HIT\nJUMP a\nENDON\n",
+            "LOOP 2\nON_SPAWN\nENDON\nENDLOOP\n",
+            "ON_HIT\nENDON\nON_ENEMY_SIGHTED",
+        ));
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.line, diagnostic.error.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                // Jumps can't leave a handler
+                (3, LanguageError::UNDEFINED_LABEL("a".into())),
+                (6, LanguageError::MISPLACED("ON_SPAWN".into())),
+                (9, LanguageError::DUPLICATE_HANDLER("ON_HIT".into())),
+                (11, LanguageError::UNMATCHED_BLOCK("ON_ENEMY_SIGHTED".into())),
+            ]
+        );
+        assert_eq!(
+            diagnostics[3].hint,
+            Some("End the block with ENDON".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_move_distances() {
+        assert_eq!("move".parse::<SwarmCommand>().unwrap(), SwarmCommand::MOVE);
+        assert_eq!(
+            "MOVE 150".parse::<SwarmCommand>().unwrap(),
+            SwarmCommand::MOVE_BY(150.0)
+        );
+        assert_eq!(SwarmCommand::MOVE_BY(12.5).to_string(), "MOVE 12.5");
+        assert_eq!(SwarmCommand::MOVE_BY(12.5).opcode(), "MOVE");
+        for distance in &["0", "-5", "1001"] {
+            assert_eq!(
+                format!("MOVE {}", distance).parse::<SwarmCommand>().unwrap_err(),
+                LanguageError::OUT_OF_RANGE {
+                    command: "MOVE".into(),
+                    min: 0.0,
+                    max: MAX_MOVE_DISTANCE,
+                }
+            );
+        }
+        assert!("MOVE far".parse::<SwarmCommand>().is_err());
+        assert!("MOVE 10 20".parse::<SwarmCommand>().is_err());
+    }
+
+    #[test]
+    fn parse_error_positions() {
+        let error = "MOVE\n\n  TURN 45\nFIRE".parse::<SwarmProgram>().unwrap_err();
+        assert_eq!((error.line, error.column), (3, 8));
+        assert_eq!(error.token, "45");
+        assert_eq!(
+            error.kind,
+            LanguageError::OUT_OF_RANGE {
+                command: "TURN".into(),
+                min: -MAX_TURN,
+                max: MAX_TURN,
+            }
+        );
+        let error = "MOVE\nFORMATION".parse::<SwarmProgram>().unwrap_err();
+        assert_eq!((error.line, error.column), (2, 11));
+        assert_eq!(error.token, "FORMATION");
+        // Blocks are checked once every line parses, and point at the command left open
+        let error = "MOVE\nLOOP 2\nMOVE".parse::<SwarmProgram>().unwrap_err();
+        assert_eq!((error.line, error.column), (2, 1));
+        assert_eq!(error.token, "LOOP");
+        assert_eq!(error.kind, LanguageError::UNMATCHED_BLOCK("LOOP".into()));
+        assert!(error.to_string().starts_with("line 2, column 1: "));
+    }
+
+    #[test]
+    fn compile_all_errors() {
+        let errors = SwarmProgram::compile_all("MOEV\nTURN 45\n\nLOOP 2\nFIRE").unwrap_err();
+        assert_eq!(
+            errors.iter().map(|error| error.line).collect::<Vec<_>>(),
+            vec![1, 2, 4]
+        );
+        assert_eq!(errors[0].kind, LanguageError::UNKNOWN_COMMAND("MOEV".into()));
+        assert_eq!(errors[1].token, "45");
+        assert_eq!(errors[2].kind, LanguageError::UNMATCHED_BLOCK("LOOP".into()));
+        // The first error is the one parsing stops at
+        assert_eq!(
+            errors[0],
+            "MOEV\nTURN 45\n\nLOOP 2\nFIRE".parse::<SwarmProgram>().unwrap_err()
+        );
+        let source = "LOOP 2\nFIRE\nENDLOOP";
+        let program = SwarmProgram::compile_all(source).unwrap();
+        assert_eq!(program.commands, source.parse::<SwarmProgram>().unwrap().commands);
+    }
+
+    #[test]
+    fn parse_waits() {
+        let command = "wait 30".parse::<SwarmCommand>().unwrap();
+        assert_eq!(command, SwarmCommand::WAIT(30));
+        assert_eq!(command.to_string(), "WAIT 30");
+        for ticks in &["0", "601"] {
+            assert_eq!(
+                format!("WAIT {}", ticks).parse::<SwarmCommand>().unwrap_err(),
+                LanguageError::OUT_OF_RANGE {
+                    command: "WAIT".into(),
+                    min: 1.0,
+                    max: MAX_WAIT_TICKS as f32,
+                }
+            );
+        }
+        assert!("WAIT 1.5".parse::<SwarmCommand>().is_err());
+        assert_eq!(
+            "WAIT".parse::<SwarmCommand>().unwrap_err(),
+            LanguageError::MISSING_PARAMETER("WAIT".into())
+        );
+        assert_eq!(
+            "WAIT 3 4".parse::<SwarmCommand>().unwrap_err(),
+            LanguageError::TOO_MANY_PARAMETERS {
+                command: "WAIT".into(),
+                max: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn extra_parameters() {
+        // The extra parameter is pointed at, rather than the end of the line
+        let errors =
+            SwarmProgram::compile_all("WAIT 3 4
+  LOOP 2  9
+FORMATION SIERPINSKI 3 1
+CALL a b")
+                .unwrap_err();
+        let found: Vec<(usize, usize, &str)> = errors
+            .iter()
+            .map(|error| (error.line, error.column, error.token.as_str()))
+            .collect();
+        assert_eq!(found, vec![(1, 8, "4"), (2, 11, "9"), (3, 24, "1"), (4, 8, "b")]);
+        assert_eq!(
+            errors[2].kind,
+            LanguageError::TOO_MANY_PARAMETERS {
+                command: "FORMATION".into(),
+                max: 2,
+            }
+        );
+        let diagnostics = check_program("EMOTE 1 2");
+        assert_eq!(diagnostics[0].column, 9);
+        assert_eq!(diagnostics[0].hint, Some("For example: EMOTE 3".into()));
+    }
+
+    #[test]
+    fn parse_emotes() {
+        let command = "emote 3".parse::<SwarmCommand>().unwrap();
+        assert_eq!(command, SwarmCommand::EMOTE(3));
+        assert_eq!(command.to_string(), "EMOTE 3");
+        assert!(match format!("EMOTE {}", NUM_EMOTES).parse::<SwarmCommand>() {
+            Err(LanguageError::OUT_OF_RANGE { .. }) => true,
+            _ => false,
+        });
+        assert_eq!(
+            "EMOTE".parse::<SwarmCommand>().unwrap_err(),
+            LanguageError::MISSING_PARAMETER("EMOTE".into())
+        );
+    }
+
+    #[test]
+    fn profile_programs() {
+        let spam: SwarmProgram = "FIRE\nTURN 30\nFIRE\nTURN 30".parse().unwrap();
+        let profile = spam.profile();
+        assert_eq!(profile.cycle, 2);
+        assert_eq!(profile.aggression, 0.5);
+        assert_eq!(profile.tier, SkillTier::BEGINNER);
+        assert!(profile.suspicious);
+
+        let varied: SwarmProgram = "MOVE\nTURN 10\nMOVE\nFIRE\nFORMATION SPREAD\nLEFT\n\
+                                    TURN -10\nFORMATION GATHER\nNOOP"
+            .parse()
+            .unwrap();
+        let profile = varied.profile();
+        assert_eq!(profile.cycle, 9);
+        assert_eq!(profile.distinct, 6);
+        assert_eq!(profile.complexity, 6 * 8 + 9 * 3 + 4 * 2);
+        assert_eq!(profile.tier, SkillTier::ADVANCED);
+        assert!(!profile.suspicious);
+    }
+
+    #[test]
+    fn program_limits() {
+        // Blank lines don't count toward the limit
+        let program = "MOVE\n\n  \t\n".repeat(MAX_NUM_COMMANDS);
+        assert_eq!(
+            program.parse::<SwarmProgram>().unwrap().commands.len(),
+            MAX_NUM_COMMANDS
+        );
+        // The limit is checked before any line is parsed
+        let program = "NOT A COMMAND\n".repeat(MAX_NUM_COMMANDS + 1);
+        assert_eq!(
+            program.parse::<SwarmProgram>().unwrap_err().kind,
+            LanguageError::TOO_LONG {
+                commands: MAX_NUM_COMMANDS + 1,
+                max: MAX_NUM_COMMANDS,
+            }
+        );
+    }
 }
@@ -0,0 +1,271 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use entity::is_npc;
+use event::WorldEvent;
+use plugin::WorldPlugin;
+use rules::MatchPhase;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::sync::{Mutex, RwLock};
+use std::thread::{self, ThreadId};
+use std::time::Instant;
+use world::World;
+
+/// Ticks covered by each report, by default; a minute at 60 updates per second
+pub const DEFAULT_REPORT_INTERVAL: u64 = 3600;
+
+/// Coarse gameplay metrics, gathered over some number of ticks
+/// Nothing in a report identifies a player
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct TelemetryReport {
+    /// Ticks covered, summed over every world reporting
+    pub ticks: u64,
+    /// Number of times each opcode was run by a player's swarm
+    pub commands: BTreeMap<&'static str, u64>,
+    /// Number of matches that ended
+    pub matches: u64,
+    /// Average length of the matches that ended, in ticks
+    pub average_match_ticks: f64,
+    /// Average time taken by a tick, in microseconds
+    pub average_tick_micros: f64,
+    /// Longest time taken by a tick, in microseconds
+    pub max_tick_micros: u64,
+}
+
+/// Receives telemetry reports
+/// Called from a room's update thread, so implementations should return quickly
+pub trait TelemetrySink: Send + Sync + Debug {
+    /// Handles a report
+    fn report(&self, report: &TelemetryReport);
+}
+
+/// Writes reports to the log, as JSON
+#[derive(Debug, Default)]
+pub struct LogSink;
+
+/// Logs each report at info level
+impl TelemetrySink for LogSink {
+    fn report(&self, report: &TelemetryReport) {
+        match ::serde_json::to_string(report) {
+            Ok(json) => info!("Telemetry: {}", json),
+            Err(error) => error!("Failed to serialize telemetry: {}", error),
+        }
+    }
+}
+
+/// Keeps reports in memory, for embedders that send them elsewhere
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    /// Every report, oldest first
+    reports: RwLock<Vec<TelemetryReport>>,
+}
+
+/// Functions for MemorySink
+impl MemorySink {
+    /// Constructor
+    pub fn new() -> Self {
+        MemorySink::default()
+    }
+    /// Removes and returns every report so far
+    pub fn take(&self) -> Vec<TelemetryReport> {
+        match self.reports.write() {
+            Ok(mut reports) => reports.drain(..).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+/// Keeps reports in a list, behind a lock
+impl TelemetrySink for MemorySink {
+    fn report(&self, report: &TelemetryReport) {
+        match self.reports.write() {
+            Ok(mut reports) => reports.push(report.clone()),
+            Err(error) => error!("Error getting write lock on telemetry: {}", error),
+        }
+    }
+}
+
+/// What's being measured in one world
+/// Each room updates on its own thread, so worlds are told apart by thread
+#[derive(Debug, Default)]
+struct InProgress {
+    /// When the current tick started
+    tick_started: Option<Instant>,
+    /// The tick the current match started on, if one is being played
+    match_started: Option<u64>,
+}
+
+/// Totals for the report being gathered
+#[derive(Debug, Default)]
+struct Totals {
+    /// The report, with sums in place of averages
+    report: TelemetryReport,
+    /// Sum of the lengths of the matches that ended
+    match_ticks: u64,
+    /// Sum of the time taken by ticks, in microseconds
+    tick_micros: u64,
+    /// What's being measured in each world
+    worlds: HashMap<ThreadId, InProgress>,
+}
+
+/// Gathers telemetry from the worlds it's added to, and reports it periodically
+/// Telemetry is opt in: nothing is gathered unless the plugin is added
+#[derive(Debug)]
+pub struct Telemetry {
+    /// Where reports are sent
+    sink: Box<TelemetrySink>,
+    /// Ticks covered by each report
+    interval: u64,
+    /// Totals for the report being gathered
+    totals: Mutex<Totals>,
+}
+
+/// Functions for Telemetry
+impl Telemetry {
+    /// Constructor
+    /// sink: where reports are sent
+    pub fn new(sink: Box<TelemetrySink>) -> Self {
+        Telemetry {
+            sink: sink,
+            interval: DEFAULT_REPORT_INTERVAL,
+            totals: Mutex::new(Totals::default()),
+        }
+    }
+    /// Sets the ticks covered by each report
+    pub fn with_interval(mut self, interval: u64) -> Self {
+        self.interval = interval.max(1);
+        self
+    }
+    /// Sends a report of everything gathered since the last one, if anything was
+    pub fn flush(&self) {
+        match self.totals.lock() {
+            Ok(mut totals) => self.send(&mut totals),
+            Err(error) => error!("Error getting lock on telemetry: {}", error),
+        }
+    }
+    /// Finishes the report being gathered and sends it
+    fn send(&self, totals: &mut Totals) {
+        if totals.report.ticks == 0 {
+            return;
+        }
+        let mut report = totals.report.clone();
+        if report.matches > 0 {
+            report.average_match_ticks = totals.match_ticks as f64 / report.matches as f64;
+        }
+        report.average_tick_micros = totals.tick_micros as f64 / report.ticks as f64;
+        self.sink.report(&report);
+        totals.report = TelemetryReport::default();
+        totals.match_ticks = 0;
+        totals.tick_micros = 0;
+    }
+}
+
+/// Counts commands and times ticks and matches
+impl WorldPlugin for Telemetry {
+    fn name(&self) -> &str {
+        "telemetry"
+    }
+    fn on_tick_start(&self, world: &mut World) {
+        let mut totals = match self.totals.lock() {
+            Ok(totals) => totals,
+            Err(_) => return,
+        };
+        {
+            let in_progress = totals.worlds.entry(thread::current().id()).or_default();
+            in_progress.tick_started = Some(Instant::now());
+            in_progress.match_started = match world.phase {
+                MatchPhase::PLAYING { started } => Some(started),
+                MatchPhase::FROZEN { .. } => None,
+            };
+        }
+        // The command each swarm is about to run
+        for (_, swarm) in world.swarms.iter().filter(|&(id, _)| !is_npc(*id)) {
+            if let Some(command) = swarm.next_command() {
+                *totals.report.commands.entry(command.opcode()).or_insert(0) += 1;
+            }
+        }
+    }
+    fn on_event(&self, _world: &World, event: &WorldEvent) {
+        if let WorldEvent::MATCH_ENDED(ref results) = *event {
+            if let Ok(mut totals) = self.totals.lock() {
+                let started = totals
+                    .worlds
+                    .get(&thread::current().id())
+                    .and_then(|in_progress| in_progress.match_started);
+                if let Some(started) = started {
+                    totals.report.matches += 1;
+                    totals.match_ticks += results.tick.saturating_sub(started);
+                }
+            }
+        }
+    }
+    fn on_tick_end(&self, _world: &mut World) {
+        let mut totals = match self.totals.lock() {
+            Ok(totals) => totals,
+            Err(_) => return,
+        };
+        let started = totals
+            .worlds
+            .get_mut(&thread::current().id())
+            .and_then(|in_progress| in_progress.tick_started.take());
+        if let Some(started) = started {
+            let elapsed = Instant::now().duration_since(started);
+            let micros = elapsed.as_secs() * 1000000 + elapsed.subsec_micros() as u64;
+            totals.tick_micros += micros;
+            totals.report.max_tick_micros = totals.report.max_tick_micros.max(micros);
+        }
+        totals.report.ticks += 1;
+        if totals.report.ticks >= self.interval {
+            self.send(&mut totals);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use swarm_language::SwarmCommand;
+
+    /// Sends reports to a shared list, so the test can read them
+    #[derive(Debug)]
+    struct SharedSink(Arc<MemorySink>);
+    impl TelemetrySink for SharedSink {
+        fn report(&self, report: &TelemetryReport) {
+            self.0.report(report);
+        }
+    }
+
+    #[test]
+    fn report_telemetry() {
+        let sink = Arc::new(MemorySink::new());
+        let telemetry = Telemetry::new(Box::new(SharedSink(sink.clone()))).with_interval(3);
+        let mut world = World::new(1000.0, 1000.0);
+        world.add_plugin(Arc::new(telemetry));
+        world.add_player(1).unwrap();
+        world.swarms.get_mut(&1).unwrap().program.commands =
+            vec![SwarmCommand::MOVE, SwarmCommand::NOOP];
+        for _ in 0..4 {
+            world.update();
+        }
+        let reports = sink.take();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].ticks, 3);
+        assert_eq!(reports[0].commands.get("MOVE"), Some(&2));
+        assert_eq!(reports[0].commands.get("NOOP"), Some(&1));
+        assert!(reports[0].max_tick_micros as f64 >= reports[0].average_tick_micros);
+    }
+}
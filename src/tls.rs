@@ -0,0 +1,162 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use error::{Error, StorageError};
+use rustls::internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
+use rustls::sign::{CertifiedKey, RSASigningKey, SigningKey};
+use rustls::{Certificate, NoClientAuth, PrivateKey, ResolvesServerCertUsingSNI, ServerConfig};
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+/// Certificates used to terminate TLS on the websocket listener
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    /// Path to the PEM encoded certificate chain
+    pub cert_path: String,
+    /// Path to the PEM encoded private key (PKCS8 or RSA)
+    pub key_path: String,
+    /// Hostname of the main certificate
+    /// Only needed when serving extra certificates with SNI
+    pub hostname: Option<String>,
+    /// Extra certificates, selected by the hostname the client asks for
+    pub sni: Vec<SniCertificate>,
+}
+
+/// A certificate served to clients that ask for a specific hostname
+#[derive(Clone, Debug)]
+pub struct SniCertificate {
+    /// The hostname this certificate is for
+    pub hostname: String,
+    /// Path to the PEM encoded certificate chain
+    pub cert_path: String,
+    /// Path to the PEM encoded private key (PKCS8 or RSA)
+    pub key_path: String,
+}
+
+/// Functions for TlsConfig
+impl TlsConfig {
+    /// Constructor for a single certificate
+    pub fn new(cert_path: String, key_path: String) -> Self {
+        TlsConfig {
+            cert_path: cert_path,
+            key_path: key_path,
+            hostname: None,
+            sni: Vec::new(),
+        }
+    }
+}
+
+/// Builds a rustls configuration from the TLS parameters
+pub fn build_config(tls_config: &TlsConfig) -> Result<Arc<ServerConfig>, Error> {
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    if tls_config.sni.is_empty() {
+        // A single certificate is served to everyone
+        config.set_single_cert(
+            load_certs(&tls_config.cert_path)?,
+            load_key(&tls_config.key_path)?,
+        );
+    } else {
+        // Certificates are chosen by the hostname the client asks for
+        let hostname = match tls_config.hostname {
+            Some(ref hostname) => hostname,
+            None => return Err(StorageError::MISSING_HOSTNAME.into()),
+        };
+        let mut resolver = ResolvesServerCertUsingSNI::new();
+        add_certificate(
+            &mut resolver,
+            hostname,
+            &tls_config.cert_path,
+            &tls_config.key_path,
+        )?;
+        for certificate in tls_config.sni.iter() {
+            add_certificate(
+                &mut resolver,
+                &certificate.hostname,
+                &certificate.cert_path,
+                &certificate.key_path,
+            )?;
+        }
+        config.cert_resolver = Arc::new(resolver);
+    }
+    Ok(Arc::new(config))
+}
+
+/// Adds a certificate for a hostname to an SNI resolver
+fn add_certificate(
+    resolver: &mut ResolvesServerCertUsingSNI,
+    hostname: &str,
+    cert_path: &str,
+    key_path: &str,
+) -> Result<(), StorageError> {
+    let signing_key = match RSASigningKey::new(&load_key(key_path)?) {
+        Ok(signing_key) => signing_key,
+        Err(_) => return Err(StorageError::UNSUPPORTED_KEY(key_path.into())),
+    };
+    let signing_key: Box<SigningKey> = Box::new(signing_key);
+    let certified_key = CertifiedKey::new(load_certs(cert_path)?, Arc::new(signing_key));
+    match resolver.add(hostname, certified_key) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(StorageError::INVALID_CERTIFICATE {
+            hostname: hostname.into(),
+            reason: format!("{:?}", error),
+        }),
+    }
+}
+
+/// Reads a PEM encoded certificate chain
+fn load_certs(path: &str) -> Result<Vec<Certificate>, StorageError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(error) => {
+            return Err(StorageError::IO {
+                path: path.into(),
+                error: error,
+            })
+        }
+    };
+    match certs(&mut BufReader::new(file)) {
+        Ok(ref chain) if chain.is_empty() => {
+            Err(StorageError::NO_CERTIFICATES(path.into()))
+        }
+        Ok(chain) => Ok(chain),
+        Err(_) => Err(StorageError::INVALID_CERTIFICATES(path.into())),
+    }
+}
+
+/// Reads a PEM encoded private key, trying PKCS8 first and then RSA
+fn load_key(path: &str) -> Result<PrivateKey, StorageError> {
+    // Each attempt consumes the reader, so the file is opened for each format
+    let parsers: [fn(&mut ::std::io::BufRead) -> Result<Vec<PrivateKey>, ()>; 2] =
+        [pkcs8_private_keys, rsa_private_keys];
+    for parser in parsers.iter() {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(error) => {
+                return Err(StorageError::IO {
+                    path: path.into(),
+                    error: error,
+                })
+            }
+        };
+        match parser(&mut BufReader::new(file)) {
+            Ok(mut keys) => if !keys.is_empty() {
+                return Ok(keys.remove(0));
+            },
+            Err(_) => {}
+        }
+    }
+    Err(StorageError::NO_PRIVATE_KEY(path.into()))
+}
@@ -0,0 +1,102 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate serde_json;
+
+use completion;
+use messages::Message;
+use swarm_language::{check_program, SwarmProgram, OPCODES};
+use wasm_bindgen::prelude::*;
+
+/// A problem with a program, as shown by the editor
+#[derive(Serialize)]
+struct EditorDiagnostic {
+    /// Line number, starting from 1
+    line: usize,
+    /// Column the problem starts at, starting from 1
+    column: usize,
+    /// Description of the problem, in English
+    message: String,
+    /// The problem as a message ID and parameters, for editors that translate it
+    localized: Message,
+    /// How the problem might be fixed, if there's an obvious way
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+}
+
+/// Checks a program the same way the server does
+/// return: a JSON array of {line, column, message, localized, hint}, empty if the program compiles
+#[wasm_bindgen]
+pub fn check(source: &str) -> String {
+    let diagnostics: Vec<EditorDiagnostic> = check_program(source)
+        .into_iter()
+        .map(|diagnostic| EditorDiagnostic {
+            line: diagnostic.line,
+            column: diagnostic.column,
+            message: diagnostic.error.to_string(),
+            localized: diagnostic.error.message(),
+            hint: diagnostic.hint,
+        })
+        .collect();
+    serde_json::to_string(&diagnostics).unwrap_or_else(|_| "[]".into())
+}
+
+/// Rewrites a program as canonical swarm code, one command per line
+/// return: the formatted program, or undefined if it doesn't compile
+#[wasm_bindgen]
+pub fn format(source: &str) -> Option<String> {
+    source
+        .parse::<SwarmProgram>()
+        .ok()
+        .map(|program| program.to_string())
+}
+
+/// Scores how complex and aggressive a program is
+/// return: a JSON object describing the program, or undefined if it doesn't compile
+#[wasm_bindgen]
+pub fn profile(source: &str) -> Option<String> {
+    source
+        .parse::<SwarmProgram>()
+        .ok()
+        .and_then(|program| serde_json::to_string(&program.profile()).ok())
+}
+
+/// Describes every opcode, with its parameters, ranges, and docs
+/// return: a JSON array, for highlighting, hover docs, and completion
+#[wasm_bindgen]
+pub fn syntax() -> String {
+    serde_json::to_string(OPCODES).unwrap_or_else(|_| "[]".into())
+}
+
+/// Finds completions and signature help for a cursor position in a line
+/// cursor: offset of the cursor in the line, in bytes of UTF-8
+/// return: a JSON object of {start, candidates, signature}
+#[wasm_bindgen]
+pub fn complete(line: &str, cursor: usize) -> String {
+    serde_json::to_string(&completion::complete(line, cursor)).unwrap_or_else(|_| "null".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn format_program() {
+        assert_eq!(
+            format("  move\n\nturn   -10\nFire").unwrap(),
+            "MOVE\nTURN -10\nFIRE\n"
+        );
+        assert_eq!(format("DANCE"), None);
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+extern crate serde_json;
+
+use reqwest::header::ContentType;
+use reqwest::Client;
+use std::sync::Mutex;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use world_manager::{ServerEvent, ServerListener};
+
+/// Parameters for webhook notifications
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+    /// URLs that every event is POSTed to as JSON
+    pub urls: Vec<String>,
+    /// Experience totals that are reported as player milestones
+    pub milestones: Vec<i64>,
+    /// Number of times to try delivering an event before giving up
+    pub max_attempts: u32,
+}
+
+/// Default webhook parameters
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            urls: Vec::new(),
+            milestones: vec![10, 50, 100],
+            max_attempts: 5,
+        }
+    }
+}
+
+/// An event waiting to be delivered to a URL
+#[derive(Clone, Debug)]
+struct Delivery {
+    /// Where to send the event
+    url: String,
+    /// The serialized event
+    body: String,
+    /// Number of failed attempts so far
+    attempts: u32,
+    /// When to next try sending it
+    due: Instant,
+}
+
+/// Sends server events to webhook URLs from a background thread
+/// Failed deliveries are retried with exponential backoff
+pub struct Webhooks {
+    /// Queues events for the background thread
+    sender: Mutex<Sender<ServerEvent>>,
+    /// Experience totals that are reported as player milestones
+    milestones: Vec<i64>,
+}
+
+/// Functions for Webhooks
+impl Webhooks {
+    /// Starts the background thread that delivers events
+    pub fn new(config: WebhookConfig) -> Self {
+        let (sender, receiver) = channel::<ServerEvent>();
+        let milestones = config.milestones.clone();
+        thread::spawn(move || {
+            let client = match Client::builder().timeout(Duration::from_secs(10)).build() {
+                Ok(client) => client,
+                Err(error) => {
+                    error!("Failed to create webhook client: {}", error);
+                    return;
+                }
+            };
+            let mut queue: Vec<Delivery> = Vec::new();
+            loop {
+                // Wait for a new event, or until the next retry is due
+                let timeout = match queue.iter().map(|delivery| delivery.due).min() {
+                    Some(due) => {
+                        let now = Instant::now();
+                        if due > now {
+                            due - now
+                        } else {
+                            Duration::from_millis(0)
+                        }
+                    }
+                    None => Duration::from_secs(60),
+                };
+                match receiver.recv_timeout(timeout) {
+                    Ok(event) => match serde_json::to_string(&event) {
+                        Ok(body) => for url in config.urls.iter() {
+                            queue.push(Delivery {
+                                url: url.clone(),
+                                body: body.clone(),
+                                attempts: 0,
+                                due: Instant::now(),
+                            });
+                        },
+                        Err(error) => error!("Failed to serialize webhook event: {}", error),
+                    },
+                    Err(RecvTimeoutError::Timeout) => {}
+                    // The server is shutting down
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+                process_queue(&mut queue, Instant::now(), config.max_attempts, |url, body| {
+                    match client
+                        .post(url)
+                        .header(ContentType::json())
+                        .body(body.to_string())
+                        .send()
+                    {
+                        Ok(response) => if response.status().is_success() {
+                            Ok(())
+                        } else {
+                            Err(format!("status {}", response.status()))
+                        },
+                        Err(error) => Err(error.to_string()),
+                    }
+                });
+            }
+        });
+        Webhooks {
+            sender: Mutex::new(sender),
+            milestones: milestones,
+        }
+    }
+}
+
+/// Queues events for delivery
+impl ServerListener for Webhooks {
+    fn on_event(&self, event: &ServerEvent) {
+        match self.sender.lock() {
+            Ok(sender) => match sender.send(event.clone()) {
+                Ok(_) => {}
+                Err(_) => warn!("Webhook thread stopped. Event not sent"),
+            },
+            Err(error) => error!("Error getting webhook lock: {}", error),
+        }
+    }
+    fn milestones(&self) -> &[i64] {
+        &self.milestones
+    }
+}
+
+/// Attempts every delivery that is due, rescheduling the ones that fail
+/// Deliveries that fail max_attempts times are dropped
+fn process_queue<F>(queue: &mut Vec<Delivery>, now: Instant, max_attempts: u32, post: F)
+where
+    F: Fn(&str, &str) -> Result<(), String>,
+{
+    let mut index: usize = 0;
+    while index < queue.len() {
+        if queue[index].due > now {
+            index += 1;
+            continue;
+        }
+        match post(&queue[index].url, &queue[index].body) {
+            Ok(_) => {
+                queue.swap_remove(index);
+            }
+            Err(error) => {
+                let delivery = &mut queue[index];
+                delivery.attempts += 1;
+                if delivery.attempts >= max_attempts {
+                    error!(
+                        "Giving up on webhook to {} after {} attempts: {}",
+                        delivery.url, delivery.attempts, error
+                    );
+                    queue.swap_remove(index);
+                } else {
+                    warn!("Webhook to {} failed: {}. Retrying", delivery.url, error);
+                    // Back off exponentially: 1s, 2s, 4s, ...
+                    delivery.due = now + Duration::from_secs(1 << (delivery.attempts - 1));
+                    index += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn retry_failed_deliveries() {
+        let now = Instant::now();
+        let mut queue = vec![
+            Delivery {
+                url: "http://ok".into(),
+                body: "{}".into(),
+                attempts: 0,
+                due: now,
+            },
+            Delivery {
+                url: "http://down".into(),
+                body: "{}".into(),
+                attempts: 0,
+                due: now,
+            },
+        ];
+        let post = |url: &str, _: &str| {
+            if url == "http://ok" {
+                Ok(())
+            } else {
+                Err("connection refused".to_string())
+            }
+        };
+        process_queue(&mut queue, now, 2, &post);
+        // The failed delivery is rescheduled, not retried immediately
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].attempts, 1);
+        process_queue(&mut queue, now, 2, &post);
+        assert_eq!(queue[0].attempts, 1);
+        // Once it is due again and fails, it is dropped
+        process_queue(&mut queue, now + Duration::from_secs(1), 2, &post);
+        assert!(queue.is_empty());
+    }
+}
@@ -14,11 +14,204 @@
 // You should have received a copy of the GNU General Public License
 // along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
 extern crate serde_json;
-use entity::{Bullet, Swarm};
-use swarm_language::SwarmProgram;
-use std::collections::HashMap;
-use rand::{thread_rng, Rng};
+use achievement::Achievements;
+use arena::FrameArena;
+use audio::{self, SoundCue};
+use boundary::Boundary;
+use bounty::{Bounty, ScoreDecay};
+use budget::TickBudget;
+use checksum::{self, ChecksumHistory};
+use duel::{RatingStore, DEFAULT_RATING};
+use collision;
+use entity::{is_npc, Bullet, Bullets, Dirty, Senses, Swarm, INITIAL_SWARM_SIZE, NPC_ID_START};
+use error::{Error, MapError, WorldConfigError, WorldError};
+use event::WorldEvent;
+use game_mode::{GameMode, Ruleset};
+use ghost::{Ghost, GhostState, Recording, MAX_GHOST_FRAMES};
+use handicap::{Comeback, Handicap};
+use handoff::{HandoffRedirect, PlayerSnapshot};
+use hit_marker::{self, HitMarker};
+use limits::ExecutionLimits;
+use manual::{ControlInput, ManualControl};
+use map::Map;
+use palette::{Color, Palette};
+use minimap::{Minimap, MINIMAP_INTERVAL};
+use observer::{Observers, WorldObserver};
+use plugin::WorldPlugin;
+use player_stats::{StatsCollector, Weapon};
+use rules::{EndReason, MatchPhase, MatchResults, MatchRules};
+use sandbox::{Practice, HISTORY_TICKS, SPAWNED_BULLET_OWNER};
+use serde_json::Value;
+use signal::{self, Signal};
+use slab::Slab;
+use spatial::{IndexStats, SpatialGrid};
+use streak::StreakConfig;
+#[cfg(feature = "scripting")]
+use script::{self, ScriptFailure, SwarmScript};
+use swarm_language::{SwarmProgram, Trigger};
+#[cfg(feature = "scripting")]
+use swarm_language::SwarmCommand;
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+use std::path::Path;
+use std::sync::Arc;
+use rand::{thread_rng, Rng, SeedableRng, XorShiftRng};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 use std::time::{Duration, Instant};
+/// Parameters used to create a world
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldConfig {
+    /// The width of the world
+    pub width: f32,
+    /// The height of the world
+    pub height: f32,
+    /// Closest a player can spawn to the edge of the world
+    pub spawn_margin: f32,
+    /// When matches end
+    pub rules: MatchRules,
+    /// Whether the world has practice controls
+    pub practice: bool,
+    /// Buffs for players far behind the leader, if any
+    pub comeback: Option<Comeback>,
+    /// Bounty put on the leader, if any
+    pub bounty: Option<Bounty>,
+    /// How the experience of players that stop scoring decays, if it does
+    pub decay: Option<ScoreDecay>,
+    /// What each swarm's program can do in one tick
+    pub limits: ExecutionLimits,
+    /// Where swarms' colors come from
+    pub palette: Palette,
+    /// What happens to swarms at the edge of the world
+    pub boundary: Boundary,
+    /// Whether clients are sent where hits landed and for how much
+    pub hit_markers: bool,
+    /// Whether players are only sent the swarms and bullets they can see
+    pub fog_of_war: bool,
+    /// Number of past ticks to keep checksums of, none by default
+    pub checksum_history: usize,
+    /// The mode the world is played under
+    pub ruleset: Ruleset,
+}
+/// Functions for WorldConfig
+impl WorldConfig {
+    /// Spawn margin used unless another is given
+    pub const DEFAULT_SPAWN_MARGIN: f32 = 50.0;
+    /// Constructor
+    /// width: the width of the world
+    /// height: the height of the world
+    /// The dimensions are checked by validate, and by World::add_player for worlds
+    /// that weren't made from a config
+    pub fn new(width: f32, height: f32) -> Self {
+        WorldConfig {
+            width: width,
+            height: height,
+            spawn_margin: WorldConfig::DEFAULT_SPAWN_MARGIN,
+            rules: MatchRules::default(),
+            practice: false,
+            comeback: None,
+            bounty: None,
+            decay: None,
+            limits: ExecutionLimits::default(),
+            palette: Palette::default(),
+            boundary: Boundary::default(),
+            hit_markers: false,
+            fog_of_war: false,
+            checksum_history: 0,
+            ruleset: Ruleset::default(),
+        }
+    }
+    /// Sets the closest a player can spawn to the edge of the world
+    pub fn with_spawn_margin(mut self, spawn_margin: f32) -> Self {
+        self.spawn_margin = spawn_margin;
+        self
+    }
+    /// Sets when matches end
+    pub fn with_rules(mut self, rules: MatchRules) -> Self {
+        self.rules = rules;
+        self
+    }
+    /// Gives the world practice controls
+    pub fn with_practice(mut self) -> Self {
+        self.practice = true;
+        self
+    }
+    /// Buffs players far behind the leader
+    pub fn with_comeback(mut self, comeback: Comeback) -> Self {
+        self.comeback = Some(comeback);
+        self
+    }
+    /// Puts a bounty on the leader
+    pub fn with_bounty(mut self, bounty: Bounty) -> Self {
+        self.bounty = Some(bounty);
+        self
+    }
+    /// Decays the experience of players that stop scoring
+    pub fn with_decay(mut self, decay: ScoreDecay) -> Self {
+        self.decay = Some(decay);
+        self
+    }
+    /// Sets what each swarm's program can do in one tick
+    pub fn with_limits(mut self, limits: ExecutionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+    /// Sets where swarms' colors come from
+    pub fn with_palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+    /// Sets what happens to swarms at the edge of the world
+    pub fn with_boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+    /// Sends clients a marker for each hit
+    /// Left off by default, since competitive servers may not want to show damage
+    pub fn with_hit_markers(mut self) -> Self {
+        self.hit_markers = true;
+        self
+    }
+    /// Hides swarms and bullets behind obstacles from players
+    pub fn with_fog_of_war(mut self) -> Self {
+        self.fog_of_war = true;
+        self
+    }
+    /// Keeps checksums of the last few ticks, and sends clients the latest one
+    pub fn with_checksum_history(mut self, ticks: usize) -> Self {
+        self.checksum_history = ticks;
+        self
+    }
+    /// Sets the mode the world is played under
+    pub fn with_ruleset(mut self, ruleset: Ruleset) -> Self {
+        self.ruleset = ruleset;
+        self
+    }
+    /// Checks that players can be spawned inside the margin
+    pub fn validate(&self) -> Result<(), WorldConfigError> {
+        if !(self.width.is_finite() && self.height.is_finite())
+            || self.width <= 0.0
+            || self.height <= 0.0
+        {
+            return Err(WorldConfigError::INVALID_SIZE {
+                width: self.width,
+                height: self.height,
+            });
+        }
+        if !self.spawn_margin.is_finite() || self.spawn_margin < 0.0 {
+            return Err(WorldConfigError::INVALID_MARGIN(self.spawn_margin));
+        }
+        if self.width <= 2.0 * self.spawn_margin || self.height <= 2.0 * self.spawn_margin {
+            return Err(WorldConfigError::TOO_SMALL {
+                width: self.width,
+                height: self.height,
+                spawn_margin: self.spawn_margin,
+            });
+        }
+        Ok(())
+    }
+}
+
 /// Represents the state of the game's world
 #[derive(Clone, Debug)]
 pub struct World {
@@ -26,56 +219,402 @@ pub struct World {
     pub width: f32,
     /// The height of the world
     pub height: f32,
+    /// Closest a player can spawn to the edge of the world
+    pub spawn_margin: f32,
     /// Each swarm in the world
-    /// Map of player ID to swarm
-    pub swarms: HashMap<usize, Swarm>,
+    /// Map of player ID to swarm, iterated in a stable order
+    pub swarms: Slab<Swarm>,
     /// Each bullet in the world
-    /// TODO: vec and element swap
-    pub bullets: Vec<Bullet>,
+    pub bullets: Bullets,
+    /// Obstacles, terrain, spawn points and zones, if the world was made from a map
+    pub map: Option<Map>,
 
     /// Leaderboard of players, from 1st place to 10th place
-    /// Tuple of (ID, experience)
-    pub leaderboard: Vec<(usize, i64)>,
+    /// Tuple of (ID, experience, bounty, rating)
+    /// Ratings are only shown when the world has a rating store
+    pub leaderboard: Vec<(usize, i64, i64, Option<f64>)>,
+    /// Number of ticks performed since the world was created
+    pub tick: u64,
+    /// Events that happened since the last call to drain_events
+    /// Empty it with drain_events or clear_events, so observers are sent every event
+    pub events: Vec<WorldEvent>,
+    /// Rules layered on the simulation, and their state
+    pub mode: GameMode,
+    /// When matches end
+    pub rules: MatchRules,
+    /// Whether a match is being played, or the world is frozen between matches
+    pub phase: MatchPhase,
+    /// Pause, step and rewind controls, for worlds used to practice
+    pub practice: Option<Practice>,
+    /// How kill streaks are scored, if not the way the mode scores them
+    pub streaks: Option<StreakConfig>,
+    /// Buffs for players far behind the leader, if any
+    pub comeback: Option<Comeback>,
+    /// Bounty put on the leader, if any
+    pub bounty: Option<Bounty>,
+    /// How the experience of players that stop scoring decays, if it does
+    pub decay: Option<ScoreDecay>,
+    /// What each swarm's program can do in one tick
+    pub limits: ExecutionLimits,
+    /// Where swarms' colors come from
+    pub palette: Palette,
+    /// What happens to swarms at the edge of the world
+    pub boundary: Boundary,
+    /// Whether hit markers are sent to clients
+    pub show_hit_markers: bool,
+    /// Whether players are only sent the swarms and bullets they can see
+    pub fog_of_war: bool,
+    /// Checksums of the last few ticks, to find where runs of the world stopped agreeing
+    pub checksums: ChecksumHistory,
+    /// Extensions called during updates, shared with clones of the world
+    plugins: Vec<Arc<WorldPlugin>>,
+    /// Receivers of the world's events, owned by this world and not its clones
+    observers: Observers,
+    /// Direct control inputs being applied in place of programs, by player ID
+    pub manual: BTreeMap<usize, ManualControl>,
+    /// Scripts driving swarms in place of their programs, by player ID
+    #[cfg(feature = "scripting")]
+    pub scripts: BTreeMap<usize, SwarmScript>,
+    /// Achievements of the players, if they're tracked
+    pub achievements: Option<Achievements>,
+    /// Where the players' duel ratings are kept, if they're shown in the leaderboard
+    pub ratings: Option<Arc<RatingStore>>,
+    /// What each player did during the current match
+    pub player_stats: StatsCollector,
+    /// Members counted over a coarse grid, refreshed every MINIMAP_INTERVAL ticks
+    pub minimap: Minimap,
+    /// Sounds made during the last update
+    pub sounds: Vec<SoundCue>,
+    /// Hits landed during the last update, if hit markers are shown
+    pub hit_markers: Vec<HitMarker>,
+    /// Emotes and pings made during the last update
+    pub signals: Vec<Signal>,
+    /// Pings waiting to be made on the next tick, as (x, y) by player ID
+    /// A player's later ping replaces one still waiting, so pings can't be spammed
+    pings: BTreeMap<usize, (f32, f32)>,
+    /// The teammate each player lets upload their programs, by player ID
+    /// Set with follow_leader, so no one can replace a teammate's program unasked
+    squad_leaders: BTreeMap<usize, usize>,
+    /// Recorded runs being raced, in races and practice worlds
+    pub ghosts: Vec<Ghost>,
+    /// Where each ghost is, as sent to clients
+    ghost_states: Vec<GhostState>,
+    /// Redirects waiting to be sent to players that were handed off to another server
+    pub pending_handoffs: HashMap<usize, HandoffRedirect>,
+    /// Time budget for each update
+    pub budget: TickBudget,
+    /// Ticks simulated per update, between MIN_TIME_SCALE and MAX_TIME_SCALE
+    /// Set it with set_time_scale
+    time_scale: f32,
+    /// Fraction of a tick carried over to the next update
+    time_owed: f32,
+    /// Scratch space for temporaries used during an update
+    arena: FrameArena,
+    /// Grid of every swarm member, keyed by (player ID, member index)
+    member_index: SpatialGrid<(usize, usize)>,
+    /// Source of every random value the world uses
+    /// Seed it with with_seed to make a world's updates reproducible
+    pub rng: XorShiftRng,
+    /// IDs of players removed since the last delta was recorded
+    departed: Vec<usize>,
+    /// Swarms that changed during the last tick, with what changed
+    changed: Vec<(usize, Dirty)>,
+    /// IDs of players removed during the last tick
+    removed: Vec<usize>,
 }
 /// Functions for the world
 impl World {
+    /// While shedding load, optional work is only done once every this many ticks
+    const SHED_INTERVAL: u64 = 10;
+    /// Random positions tried before a player is spawned inside an obstacle
+    const SPAWN_TRIES: usize = 10;
+    /// Slowest the world can be run, a tenth of a tick per update
+    pub const MIN_TIME_SCALE: f32 = 0.1;
+    /// Fastest the world can be run, four ticks per update
+    pub const MAX_TIME_SCALE: f32 = 4.0;
     /// Constructor
     /// width: the width of the world
     /// height: the height of the world
+    /// The dimensions aren't checked, so add_player refuses players if the world is too
+    /// small for its spawn margin; use from_config to check them up front
     pub fn new(width: f32, height: f32) -> Self {
         World {
             width: width,
             height: height,
-            swarms: HashMap::new(),
-            bullets: Vec::new(),
+            spawn_margin: WorldConfig::DEFAULT_SPAWN_MARGIN,
+            swarms: Slab::new(),
+            bullets: Bullets::new(),
+            map: None,
             leaderboard: Vec::new(),
+            tick: 0,
+            events: Vec::new(),
+            mode: GameMode::FREE_FOR_ALL,
+            rules: MatchRules::default(),
+            phase: MatchPhase::default(),
+            practice: None,
+            streaks: None,
+            comeback: None,
+            bounty: None,
+            decay: None,
+            limits: ExecutionLimits::default(),
+            palette: Palette::default(),
+            boundary: Boundary::default(),
+            show_hit_markers: false,
+            fog_of_war: false,
+            checksums: ChecksumHistory::default(),
+            plugins: Vec::new(),
+            observers: Observers::default(),
+            manual: BTreeMap::new(),
+            #[cfg(feature = "scripting")]
+            scripts: BTreeMap::new(),
+            achievements: None,
+            ratings: None,
+            player_stats: StatsCollector::new(),
+            minimap: Minimap::default(),
+            sounds: Vec::new(),
+            hit_markers: Vec::new(),
+            signals: Vec::new(),
+            pings: BTreeMap::new(),
+            squad_leaders: BTreeMap::new(),
+            ghosts: Vec::new(),
+            ghost_states: Vec::new(),
+            pending_handoffs: HashMap::new(),
+            budget: TickBudget::default(),
+            time_scale: 1.0,
+            time_owed: 0.0,
+            arena: FrameArena::new(),
+            member_index: SpatialGrid::new(collision::CELL_SIZE),
+            rng: thread_rng().gen(),
+            departed: Vec::new(),
+            changed: Vec::new(),
+            removed: Vec::new(),
         }
     }
     /// Capacity constructor
     /// width: the width of the world
     /// height: the height of the world
     /// capacity: the number players to allocate space for
-    /// Space is allocated for 100x the number of bullets
+    /// Space is allocated for 10x the number of bullets
     pub fn with_capacity(width: f32, height: f32, capacity: usize) -> Self {
-        World {
-            width: width,
-            height: height,
-            swarms: HashMap::with_capacity(capacity),
-            bullets: Vec::with_capacity(capacity * 10),
-            leaderboard: Vec::new(),
+        let mut world = World::new(width, height);
+        world.swarms = Slab::with_capacity(capacity);
+        world.bullets = Bullets::with_capacity(capacity * 10);
+        world
+    }
+    /// Checked constructor
+    /// config: the dimensions of the world and where players spawn
+    pub fn from_config(config: &WorldConfig) -> Result<Self, WorldConfigError> {
+        config.validate()?;
+        let mut world = World::new(config.width, config.height);
+        world.spawn_margin = config.spawn_margin;
+        world.rules = config.rules;
+        if config.practice {
+            world = world.with_practice();
+        }
+        world.comeback = config.comeback;
+        world.bounty = config.bounty;
+        world.decay = config.decay;
+        world.limits = config.limits;
+        world.palette = config.palette;
+        world.boundary = config.boundary;
+        world.show_hit_markers = config.hit_markers;
+        world.fog_of_war = config.fog_of_war;
+        world.checksums = ChecksumHistory::new(config.checksum_history);
+        world.mode = config.ruleset.mode(config.width, config.height);
+        Ok(world)
+    }
+    /// Map constructor
+    /// map: the layout of the world, checked before the world is made
+    pub fn from_map(map: Map) -> Result<Self, MapError> {
+        map.validate()?;
+        let mut world = World::new(map.width, map.height);
+        world.map = Some(map);
+        Ok(world)
+    }
+    /// Map constructor, from the bytes of a JSON map
+    pub fn from_map_bytes(bytes: &[u8]) -> Result<Self, MapError> {
+        World::from_map(Map::from_bytes(bytes)?)
+    }
+    /// Map constructor, from a JSON map file
+    pub fn from_map_file<P: AsRef<Path>>(path: P) -> Result<Self, MapError> {
+        World::from_map(Map::load(path)?)
+    }
+    /// Reseeds the world's random number generator
+    /// Worlds with the same seed given the same inputs make the same random choices
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = World::seeded_rng(seed);
+        self
+    }
+    /// A random number generator that makes the same choices for the same seed
+    pub fn seeded_rng(seed: u64) -> XorShiftRng {
+        // The constants keep the state from being all zeroes, which XorShift can't leave
+        XorShiftRng::from_seed([
+            (seed >> 32) as u32,
+            seed as u32,
+            0x9E37_79B9,
+            0x7F4A_7C15,
+        ])
+    }
+    /// Plays the world under a mode's rules
+    /// Players already in the world join the mode in ascending ID order
+    pub fn with_mode(mut self, mode: GameMode) -> Self {
+        self.mode = mode;
+        let mut ids: Vec<usize> = self.swarms.keys().cloned().collect();
+        ids.sort();
+        for id in ids {
+            self.mode.add_player(id);
+        }
+        self
+    }
+    /// Ends matches by the given rules
+    pub fn with_rules(mut self, rules: MatchRules) -> Self {
+        self.rules = rules;
+        self
+    }
+    /// Scores kill streaks the given way, whatever the mode
+    pub fn with_streaks(mut self, streaks: StreakConfig) -> Self {
+        self.streaks = Some(streaks);
+        self
+    }
+    /// Adds practice controls, so the world can be paused and rewound
+    pub fn with_practice(mut self) -> Self {
+        self.practice = Some(Practice::new(HISTORY_TICKS));
+        self
+    }
+    /// Hides swarms and bullets behind obstacles from players
+    pub fn with_fog_of_war(mut self) -> Self {
+        self.fog_of_war = true;
+        self
+    }
+    /// Keeps checksums of the last few ticks, and sends clients the latest one
+    pub fn with_checksum_history(mut self, ticks: usize) -> Self {
+        self.checksums = ChecksumHistory::new(ticks);
+        self
+    }
+    /// Buffs players far behind the leader
+    pub fn with_comeback(mut self, comeback: Comeback) -> Self {
+        self.comeback = Some(comeback);
+        self
+    }
+    /// Puts a bounty on the leader
+    pub fn with_bounty(mut self, bounty: Bounty) -> Self {
+        self.bounty = Some(bounty);
+        self
+    }
+    /// Decays the experience of players that stop scoring
+    pub fn with_decay(mut self, decay: ScoreDecay) -> Self {
+        self.decay = Some(decay);
+        self
+    }
+    /// Whether ghosts can be raced, which they can in races and practice worlds
+    pub fn allows_ghosts(&self) -> bool {
+        match self.mode {
+            GameMode::RACE(_) => true,
+            _ => self.practice.is_some(),
+        }
+    }
+    /// Plays a recorded run back as a ghost for a player to race, replacing their last one
+    /// In a race, the ghost starts from the race's clock
+    pub fn add_ghost(&mut self, owner: usize, recording: Recording) -> Result<(), WorldError> {
+        if !self.allows_ghosts() {
+            return Err(WorldError::GHOSTS_DISABLED);
+        }
+        if recording.len() > MAX_GHOST_FRAMES {
+            return Err(WorldError::GHOST_TOO_LONG(recording.len()));
         }
+        let frame = match self.mode {
+            GameMode::RACE(ref race) => race.ticks.saturating_sub(1) as usize,
+            _ => 0,
+        };
+        self.ghosts.retain(|ghost| ghost.owner != owner);
+        self.ghosts.push(Ghost::new(owner, recording, frame));
+        self.refresh_ghosts();
+        Ok(())
+    }
+    /// Copies where each ghost is, for clients
+    fn refresh_ghosts(&mut self) {
+        self.ghost_states = self.ghosts.iter().filter_map(Ghost::state).collect();
+    }
+    /// Number of ticks simulated per update
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+    /// Slows down or speeds up the world
+    /// Everything is timed in ticks, so movement, cooldowns and spawn timers scale together
+    pub fn set_time_scale(&mut self, time_scale: f32) -> Result<(), WorldError> {
+        if !(time_scale >= World::MIN_TIME_SCALE && time_scale <= World::MAX_TIME_SCALE) {
+            return Err(WorldError::INVALID_TIME_SCALE(time_scale));
+        }
+        self.time_scale = time_scale;
+        Ok(())
+    }
+    /// Adds a plugin, called after the ones already added
+    pub fn add_plugin(&mut self, plugin: Arc<WorldPlugin>) {
+        self.plugins.push(plugin);
+    }
+    /// Sends every event to an observer as it happens, from the next one on
+    /// Events are sent at the end of each tick and when players join or leave,
+    /// and any others before they're drained
+    pub fn subscribe(&mut self, observer: Box<WorldObserver>) {
+        self.observers.notify(self.tick, &self.events);
+        self.observers.subscribe(observer);
+    }
+    /// Sends observers the events they haven't seen
+    fn notify_observers(&mut self) {
+        if !self.observers.is_empty() {
+            self.observers.notify(self.tick, &self.events);
+        }
+    }
+    /// Sends a message from a client to the plugin with the given name
+    /// return: the plugin's reply, or None if it has none or there's no such plugin
+    pub fn plugin_message(&mut self, player: usize, name: &str, data: &Value) -> Option<Value> {
+        let plugin = self.plugins.iter().find(|plugin| plugin.name() == name).cloned();
+        plugin.and_then(|plugin| plugin.on_message(self, player, data))
+    }
+    /// Tracks the players' achievements
+    pub fn with_achievements(mut self, achievements: Achievements) -> Self {
+        self.achievements = Some(achievements);
+        self
+    }
+    /// Shows the players' duel ratings in the leaderboard
+    pub fn with_ratings(mut self, ratings: Arc<RatingStore>) -> Self {
+        self.ratings = Some(ratings);
+        self
     }
     /// Adds a player to the server with the given ID
-    pub fn add_player(&mut self, id: usize) {
+    /// The existing swarm is kept if the ID is already taken
+    pub fn add_player(&mut self, id: usize) -> Result<(), WorldError> {
+        if self.swarms.contains_key(&id) {
+            warn!("Player {} is already in the world", id);
+            return Err(WorldError::DUPLICATE_PLAYER(id));
+        }
         info!("Adding player {} to the server", id);
         // TODO: determine the initial number of members to make
-        let initial_num_members: usize = 10;
+        let initial_num_members: usize = INITIAL_SWARM_SIZE;
         // Get a random position
-        let (x, y) = self.random_position();
+        let (x, y, home) = self.random_position().map_err(WorldError::INVALID_CONFIG)?;
         // Get a random color
-        let color = World::random_color();
-        self.swarms
-            .insert(id, Swarm::new(x, y, initial_num_members).with_color(color));
+        let color = self.random_color();
+        let mut swarm = Swarm::new(x, y, initial_num_members).with_color(color);
+        swarm.home = home;
+        self.swarms.insert(id, swarm);
+        self.mode.add_player(id);
+        self.events.push(WorldEvent::JOIN(id));
+        self.notify_observers();
+        for plugin in self.plugins.clone().iter() {
+            plugin.on_player_join(self, id);
+        }
+        Ok(())
+    }
+
+    /// Whether a player with the given ID is in the world
+    pub fn has_player(&self, id: usize) -> bool {
+        self.swarms.contains_key(&id)
+    }
+
+    /// Number of players in the world
+    pub fn player_count(&self) -> usize {
+        self.swarms.len()
     }
 
     /// Removes a player to the server with the given ID
@@ -83,178 +622,1956 @@ impl World {
         info!("Removing player {} from the server", id);
         // Remove the player's data
         match self.swarms.remove(&id) {
-            _ => {}
+            Some(_) => {
+                self.departed.push(id);
+                self.mode.remove_player(id, &mut self.events);
+                if let Some(ref mut achievements) = self.achievements {
+                    achievements.leave(id);
+                }
+                #[cfg(feature = "scripting")]
+                self.scripts.remove(&id);
+                self.manual.remove(&id);
+                self.pings.remove(&id);
+                self.squad_leaders
+                    .retain(|&follower, &mut leader| follower != id && leader != id);
+                self.events.push(WorldEvent::LEAVE(id));
+                // Remove the player's bullets and ghost
+                self.bullets.remove_owner(id);
+                self.ghosts.retain(|ghost| ghost.owner != id);
+                self.refresh_ghosts();
+                self.notify_observers();
+                for plugin in self.plugins.clone().iter() {
+                    plugin.on_player_leave(self, id);
+                }
+            }
+            None => self.bullets.remove_owner(id),
+        }
+    }
+
+    /// Respawns a player's swarm at a new random position
+    /// The swarm keeps its color and program, but loses its members and experience
+    /// return: whether the player exists and there was somewhere to respawn them
+    pub fn respawn_player(&mut self, id: usize) -> bool {
+        // Get a random position
+        let (x, y, home) = match self.random_position() {
+            Ok(position) => position,
+            Err(error) => {
+                warn!("Failed to respawn player {}: {}", id, error);
+                return false;
+            }
+        };
+        match self.swarms.get_mut(&id) {
+            Some(swarm) => {
+                info!("Respawning player {}", id);
+                *swarm = swarm.respawned(x, y);
+                swarm.home = home;
+                true
+            }
+            None => false,
         }
-        // Remove the player's bullets
-        let mut index: usize = 0;
+    }
 
-        while index < self.bullets.len() {
-            if self.bullets[index].owner == id {
-                self.bullets.swap_remove(index);
+    /// Sets how hard a player hits and how fast they move
+    pub fn set_handicap(&mut self, id: usize, handicap: Handicap) -> Result<(), WorldError> {
+        match self.swarms.get_mut(&id) {
+            Some(swarm) => {
+                info!("Handicapping player {}: {:?}", id, handicap);
+                swarm.handicap = handicap;
+                Ok(())
             }
-            index += 1;
+            None => Err(WorldError::NO_PLAYER(id)),
         }
     }
 
-    /// Keep track of top 10 players
+    /// Handicaps and comeback buffs of every player that has one, in ascending ID order
+    pub fn handicaps(&self) -> Vec<PlayerHandicap> {
+        self.player_ids()
+            .into_iter()
+            .map(|id| (id, &self.swarms[&id]))
+            .filter(|&(_, swarm)| !(swarm.handicap.is_none() && swarm.boost.is_none()))
+            .map(|(id, swarm)| PlayerHandicap {
+                player: id,
+                handicap: swarm.handicap,
+                boost: swarm.boost,
+            })
+            .collect()
+    }
+
+    /// Captures the parts of a player that move with them to another server
+    pub fn export_player(&self, id: usize) -> Option<PlayerSnapshot> {
+        self.swarms.get(&id).map(|swarm| PlayerSnapshot {
+            player_id: id,
+            name: None,
+            programs: vec![swarm.program.to_string()],
+            experience: swarm.experience,
+            color: swarm.color,
+        })
+    }
+
+    /// Restores a player that was handed off from another server onto a player in this world
+    /// The snapshot's running program is compiled again, since servers may differ in version
+    pub fn restore_player(&mut self, id: usize, snapshot: &PlayerSnapshot) -> Result<(), Error> {
+        let program: Option<SwarmProgram> = match snapshot.programs.first() {
+            Some(source) => Some(source.parse()?),
+            None => None,
+        };
+        match self.swarms.get_mut(&id) {
+            Some(swarm) => {
+                info!(
+                    "Restoring player {} from handed off player {}",
+                    id, snapshot.player_id
+                );
+                swarm.experience = snapshot.experience;
+                swarm.color = snapshot.color;
+                swarm.dirty = Dirty::all();
+                if let Some(program) = program {
+                    swarm.program = program;
+                }
+                Ok(())
+            }
+            None => Err(WorldError::NO_PLAYER(id).into()),
+        }
+    }
+
+    /// Returns the IDs of every player in the world, in ascending order
+    pub fn player_ids(&self) -> Vec<usize> {
+        let mut ids: Vec<usize> = self.swarms.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Collects statistics about the current state of the world
+    pub fn stats(&self) -> WorldStats {
+        WorldStats {
+            tick: self.tick,
+            num_players: self.swarms.len(),
+            num_members: self.swarms.values().map(|swarm| swarm.members.len()).sum(),
+            num_bullets: self.bullets.len(),
+            width: self.width,
+            height: self.height,
+            overloaded: self.budget.is_overloaded(),
+            over_budget: self.budget.total_over_budget(),
+            skipped_ticks: self.budget.total_skipped(),
+            member_index: self.member_index.stats(),
+            memory: self.memory_stats(),
+            handicaps: self.handicaps(),
+            time_scale: self.time_scale,
+        }
+    }
+
+    /// Approximates the memory used by each part of the world
+    /// Counts allocated capacity, so space kept for reuse is included
+    pub fn memory_stats(&self) -> MemoryStats {
+        let swarms = self.swarms.memory_usage()
+            + self
+                .swarms
+                .values()
+                .map(|swarm| swarm.memory_usage())
+                .sum::<usize>();
+        let bullets = self.bullets.memory_usage();
+        let events = self.events.capacity() * mem::size_of::<WorldEvent>()
+            + (self.departed.capacity() + self.removed.capacity()) * mem::size_of::<usize>()
+            + self.changed.capacity() * mem::size_of::<(usize, Dirty)>();
+        let spatial_index = self.member_index.memory_usage();
+        let scratch = self.arena.memory_usage();
+        MemoryStats {
+            swarms: swarms,
+            bullets: bullets,
+            events: events,
+            spatial_index: spatial_index,
+            scratch: scratch,
+            total: swarms + bullets + events + spatial_index + scratch,
+        }
+    }
+
+    /// Takes the events that happened since the last call
+    pub fn drain_events(&mut self) -> Vec<WorldEvent> {
+        self.events_taken();
+        self.events.drain(..).collect()
+    }
+    /// Drops the events that happened since the last call, keeping the buffer's space
+    /// Observers are still sent them first
+    pub fn clear_events(&mut self) {
+        self.events_taken();
+        self.events.clear();
+    }
+    /// Sends observers the pending events, which are about to be emptied out,
+    /// so they start from the first event the world has afterwards
+    fn events_taken(&mut self) {
+        self.notify_observers();
+        self.observers.drained();
+    }
+
+    /// Keep track of top 10 players, by experience and then by ID
     pub fn update_leaderboard(&mut self) {
-        let mut scores: Vec<(usize, i64)> = Vec::new();
+        let mut scores: Vec<(usize, i64, i64, Option<f64>)> = Vec::new();
 
         for (id, swarm) in self.swarms.iter() {
-            scores.push((*id, swarm.experience));
+            // Players that haven't dueled yet are shown with the rating they'd start with
+            let rating = self
+                .ratings
+                .as_ref()
+                .map(|ratings| ratings.rating(*id).unwrap_or(DEFAULT_RATING));
+            scores.push((*id, swarm.experience, swarm.bounty, rating));
         }
 
-        self.leaderboard = scores.into_iter().collect::<Vec<(usize, i64)>>();
-        self.leaderboard.sort();
-        self.leaderboard.reverse();
-        self.leaderboard = self.leaderboard.iter().cloned().take(10).collect();
+        scores.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        scores.truncate(10);
+        self.leaderboard = scores;
     }
 
     /// Updates a player's program
+    /// A paused program stays paused, so players can upload edits before resuming
     pub fn update_program(&mut self, player_id: usize, program: SwarmProgram) {
         match self.swarms.get_mut(&player_id) {
-            Some(swarm) => swarm.program = program,
+            Some(swarm) => {
+                let paused = swarm.program.paused;
+                swarm.program = program;
+                swarm.program.paused = paused;
+            }
             None => warn!("Invalid player id: {}", player_id),
         }
+        #[cfg(feature = "scripting")]
+        self.scripts.remove(&player_id);
+    }
+    /// Pauses or resumes a player's program
+    /// While paused, the swarm stays where it is and its cooldowns keep counting down
+    pub fn set_paused(&mut self, player_id: usize, paused: bool) -> Result<(), WorldError> {
+        match self.swarms.get_mut(&player_id) {
+            Some(swarm) => {
+                swarm.program.paused = paused;
+                Ok(())
+            }
+            None => Err(WorldError::NO_PLAYER(player_id)),
+        }
+    }
+    /// Marks a position for a player's team, made on the next tick
+    pub fn ping(&mut self, player_id: usize, x: f32, y: f32) -> Result<(), WorldError> {
+        if !self.swarms.contains_key(&player_id) {
+            return Err(WorldError::NO_PLAYER(player_id));
+        }
+        if !(x >= 0.0 && x <= self.width && y >= 0.0 && y <= self.height) {
+            return Err(WorldError::OUT_OF_BOUNDS { x: x, y: y });
+        }
+        self.pings.insert(player_id, (x, y));
+        Ok(())
+    }
+    /// Lets a teammate upload a player's programs with update_squad_programs
+    /// Following themselves stops the player following anyone
+    pub fn follow_leader(&mut self, player_id: usize, leader: usize) -> Result<(), WorldError> {
+        for &id in [player_id, leader].iter() {
+            if !self.swarms.contains_key(&id) {
+                return Err(WorldError::NO_PLAYER(id));
+            }
+        }
+        if player_id == leader {
+            self.squad_leaders.remove(&player_id);
+            return Ok(());
+        }
+        let team = self.mode.team(player_id);
+        if team.is_none() || self.mode.team(leader) != team {
+            return Err(WorldError::NOT_SQUADMATE(leader));
+        }
+        info!("Player {} is following squad leader {}", player_id, leader);
+        self.squad_leaders.insert(player_id, leader);
+        Ok(())
+    }
+    /// Updates the programs of a player's squad at once, such as at the start of a round
+    /// The squad is the player and the teammates following them, so players without a team
+    /// only have themselves
+    /// Nothing is updated unless every player is in the squad and is given one program
+    /// leader: ID of the player uploading the programs
+    /// programs: (player ID, program) pairs
+    pub fn update_squad_programs(
+        &mut self,
+        leader: usize,
+        programs: Vec<(usize, SwarmProgram)>,
+    ) -> Result<(), WorldError> {
+        let team = self.mode.team(leader);
+        for (index, &(player_id, _)) in programs.iter().enumerate() {
+            if !self.swarms.contains_key(&player_id) {
+                return Err(WorldError::NO_PLAYER(player_id));
+            }
+            if player_id != leader
+                && (team.is_none()
+                    || self.mode.team(player_id) != team
+                    || self.squad_leaders.get(&player_id) != Some(&leader))
+            {
+                return Err(WorldError::NOT_SQUADMATE(player_id));
+            }
+            if programs[..index].iter().any(|&(other, _)| other == player_id) {
+                return Err(WorldError::DUPLICATE_SQUADMATE(player_id));
+            }
+        }
+        info!("Player {} updated {} programs for their squad", leader, programs.len());
+        for (player_id, program) in programs {
+            self.update_program(player_id, program);
+        }
+        Ok(())
+    }
+    /// Drives a player's swarm directly for a while, replacing any input still being applied
+    /// The swarm's program picks up where it left off once the input runs out
+    pub fn control(&mut self, player_id: usize, input: &ControlInput) -> Result<(), WorldError> {
+        if !self.swarms.contains_key(&player_id) {
+            return Err(WorldError::NO_PLAYER(player_id));
+        }
+        self.manual.insert(player_id, ManualControl::new(input));
+        Ok(())
+    }
+    /// Drives a player's swarm with a script instead of its program
+    #[cfg(feature = "scripting")]
+    pub fn update_script(
+        &mut self,
+        player_id: usize,
+        script: SwarmScript,
+    ) -> Result<(), WorldError> {
+        if !self.swarms.contains_key(&player_id) {
+            return Err(WorldError::NO_PLAYER(player_id));
+        }
+        self.scripts.insert(player_id, script);
+        Ok(())
+    }
+    /// Runs each script, and makes the command it gives its swarm's program for the tick
+    /// Every script senses the swarms as they were before any of them moved
+    #[cfg(feature = "scripting")]
+    fn run_scripts(&mut self) {
+        if self.scripts.is_empty() {
+            return;
+        }
+        let engine = script::engine(&self.limits);
+        let mut commands: Vec<(usize, SwarmCommand)> = Vec::with_capacity(self.scripts.len());
+        for (id, script) in self.scripts.iter() {
+            let command = match script.next_command(&engine, *id, &self.swarms) {
+                Ok(command) => command,
+                Err(ScriptFailure::LIMIT(limit)) => {
+                    debug!("Player {}'s script went over the {:?} limit", id, limit);
+                    self.events.push(WorldEvent::LIMIT_EXCEEDED {
+                        player: *id,
+                        limit: limit,
+                    });
+                    SwarmCommand::NOOP
+                }
+                Err(ScriptFailure::ERROR(error)) => {
+                    debug!("Player {}'s script failed: {}", id, error);
+                    SwarmCommand::NOOP
+                }
+            };
+            commands.push((*id, command));
+        }
+        for (id, command) in commands {
+            if let Some(swarm) = self.swarms.get_mut(&id) {
+                let paused = swarm.program.paused;
+                swarm.program = SwarmProgram::new(vec![command]);
+                swarm.program.paused = paused;
+            }
+        }
     }
     /// Generates a random position
-    fn random_position(&self) -> (f32, f32) {
-        // Get the rng
-        let mut rng = thread_rng();
-        // Defines a margin
-        // TODO: make this an associated const
-        let margin: f32 = 50.0;
+    /// Maps with spawn points spawn players at one of them, and elsewhere
+    /// positions inside obstacles are avoided when a few tries allow it
+    /// return: the position, and the index of the spawn point it's at, if any,
+    /// or why there's no room to spawn anywhere
+    fn random_position(&mut self) -> Result<(f32, f32, Option<usize>), WorldConfigError> {
+        if let Some(ref map) = self.map {
+            if !map.spawns.is_empty() {
+                let index = self.rng.gen_range(0, map.spawns.len());
+                return Ok((map.spawns[index].x, map.spawns[index].y, Some(index)));
+            }
+        }
+        // Without spawn points, there has to be room inside the spawn margin
+        WorldConfig::new(self.width, self.height)
+            .with_spawn_margin(self.spawn_margin)
+            .validate()?;
+        let rng = &mut self.rng;
+        let margin = self.spawn_margin;
         // Generate the position
-        (
-            rng.gen_range(margin, self.width - margin),
-            rng.gen_range(margin, self.height - margin),
-        )
-    }
-    /// Generates a random color
-    fn random_color() -> (u8, u8, u8) {
-        // Get the RNG
-        let mut rng = thread_rng();
-        // Generate the color
-        (rng.gen(), rng.gen(), rng.gen())
+        let mut position = (0.0, 0.0);
+        for _ in 0..World::SPAWN_TRIES {
+            position = (
+                rng.gen_range(margin, self.width - margin),
+                rng.gen_range(margin, self.height - margin),
+            );
+            match self.map {
+                Some(ref map) if map.blocked(position.0, position.1) => {}
+                _ => break,
+            }
+        }
+        Ok((position.0, position.1, None))
+    }
+    /// Chooses a color from the palette, preferring colors no swarm has
+    fn random_color(&mut self) -> (u8, u8, u8) {
+        let used: Vec<Color> = self.swarms.values().map(|swarm| swarm.color).collect();
+        self.palette.pick(&used, &mut self.rng)
     }
     /// Performs one "tick" of the world
     /// return: The amount of time elapsed during the tick
     /// Executes each swarm's program on itself
     /// Moves bullets
     /// Does bullet collision
+    ///
+    /// Ties are broken the same way every time, so replays and tests can rely on them:
+    /// - Swarms are updated in ascending player ID order, whatever order players joined in
+    /// - Bullets fired this tick come after older bullets, in the order their swarms were updated
+    /// - Each bullet only hits once, in bullet order: the older bullet lands first
+    /// - A bullet overlapping several members hits the lowest player ID, then the lowest member
     pub fn update(&mut self) -> Duration {
         // Record time at beginning of update
         let start_time = Instant::now();
-        let mut exp_queue: Vec<(usize, i64)> = Vec::new();
+        // Practice worlds can be paused, and record each update so it can be rewound
+        if let Some(mut practice) = self.practice.take() {
+            let update = practice.should_update();
+            if update {
+                practice.record(self);
+            }
+            self.practice = Some(practice);
+            if !update {
+                return Duration::from_secs(0);
+            }
+        }
+        // Slowed down worlds skip updates, sped up ones run several ticks in one
+        self.time_owed += self.time_scale;
+        if self.time_owed < 1.0 {
+            return Duration::from_secs(0);
+        }
+        self.sounds.clear();
+        self.hit_markers.clear();
+        self.signals.clear();
+        while self.time_owed >= 1.0 {
+            self.time_owed -= 1.0;
+            self.step();
+        }
+        self.refresh_ghosts();
+        self.record_delta();
+        // Record time at end of update and return the time elapsed
+        let elapsed = Instant::now().duration_since(start_time);
+        self.budget.record(elapsed);
+        elapsed
+    }
+    /// Performs a single tick
+    /// Swarms stay marked as changed until the update's delta is recorded
+    fn step(&mut self) {
+        self.tick += 1;
+        self.arena.reset();
+        let first_event = self.events.len();
+        for plugin in self.plugins.clone().iter() {
+            plugin.on_tick_start(self);
+        }
 
+        match self.phase {
+            MatchPhase::PLAYING { started } => {
+                self.simulate();
+                for ghost in self.ghosts.iter_mut() {
+                    ghost.advance();
+                }
+                self.ghosts.retain(|ghost| !ghost.is_finished());
+                let reason = match self.rules.check(&self.swarms, started, self.tick) {
+                    Some(reason) => Some(reason),
+                    None if self.mode.check_win_condition(&self.swarms) => Some(EndReason::MODE),
+                    None => None,
+                };
+                if let Some(reason) = reason {
+                    self.end_match(reason);
+                }
+            }
+            // Nothing moves while the world is frozen on a match's results
+            MatchPhase::FROZEN { ended } => {
+                if self.tick - ended >= self.rules.freeze {
+                    self.start_match();
+                }
+            }
+        }
+        // Refresh the summaries clients draw distant swarms with
+        for swarm in self.swarms.values_mut() {
+            swarm.summarize();
+        }
+        if !self.plugins.is_empty() {
+            let plugins = self.plugins.clone();
+            for event in self.events[first_event..].iter() {
+                for plugin in plugins.iter() {
+                    plugin.on_event(self, event);
+                }
+            }
+            for plugin in plugins.iter() {
+                plugin.on_tick_end(self);
+            }
+        }
+        if self.checksums.is_enabled() {
+            let checksum = checksum::checksum(self.tick, &self.swarms, &self.bullets);
+            self.checksums.record(self.tick, checksum);
+        }
+        self.notify_observers();
+    }
+    /// Checksum of the world as it was at the end of a tick,
+    /// if checksums are kept and the tick is recent enough
+    pub fn state_checksum(&self, tick: u64) -> Option<u32> {
+        self.checksums.get(tick)
+    }
+    /// Whether no obstacle is in the way of the straight line between two points
+    pub fn line_of_sight(&self, from: (f32, f32), to: (f32, f32)) -> bool {
+        self.map
+            .as_ref()
+            .map_or(true, |map| map.line_of_sight(from, to))
+    }
+    /// What a player's swarm senses of the world around it
+    /// Swarms and bullets behind obstacles can't be sensed
+    pub fn sense(&self, id: usize) -> Senses {
+        let swarm = match self.swarms.get(&id) {
+            Some(swarm) => swarm,
+            None => return Senses::default(),
+        };
+        let range = Swarm::SENSE_RANGE * Swarm::SENSE_RANGE;
+        let distance = |x: f32, y: f32| (x - swarm.x).powi(2) + (y - swarm.y).powi(2);
+        // Tuple of (squared distance, x, y) of the closest thing found so far
+        let mut nearest_enemy: Option<(f32, f32, f32)> = None;
+        let mut enemies = 0;
+        for (other_id, other) in self.swarms.iter() {
+            let away = distance(other.x, other.y);
+            if *other_id == id
+                || other.members.is_empty()
+                || away > range
+                || !self.line_of_sight((swarm.x, swarm.y), (other.x, other.y))
+            {
+                continue;
+            }
+            enemies += 1;
+            if nearest_enemy.map_or(true, |(closest, _, _)| away < closest) {
+                nearest_enemy = Some((away, other.x, other.y));
+            }
+        }
+        let mut nearest_bullet: Option<(f32, f32, f32)> = None;
+        for index in 0..self.bullets.len() {
+            let (x, y) = self.bullets.position(index);
+            let away = distance(x, y);
+            if self.bullets.owner[index] == id
+                || away > range
+                || !self.line_of_sight((swarm.x, swarm.y), (x, y))
+            {
+                continue;
+            }
+            if nearest_bullet.map_or(true, |(closest, _, _)| away < closest) {
+                nearest_bullet = Some((away, x, y));
+            }
+        }
+        let (x, y) = swarm.ahead(Swarm::WALL_DISTANCE);
+        let wall_ahead = x < 0.0
+            || x > self.width
+            || y < 0.0
+            || y > self.height
+            || self.map.as_ref().map_or(false, |map| map.blocked(x, y));
+        Senses {
+            enemy_near: enemies > 0,
+            wall_ahead: wall_ahead,
+            enemies: enemies,
+            nearest_enemy: nearest_enemy.map(|(_, x, y)| (x, y)),
+            nearest_bullet: nearest_bullet.map(|(_, x, y)| (x, y)),
+        }
+    }
+    /// Moves every swarm and bullet, and applies the hits
+    fn simulate(&mut self) {
+        let first_event = self.events.len();
         // start by updating leaderboard
-        self.update_leaderboard();
+        // While shedding load, the leaderboard is only updated occasionally
+        if !self.budget.is_shedding() || self.tick % World::SHED_INTERVAL == 0 {
+            self.update_leaderboard();
+        }
 
-        // Update each member of the swarm with its own program
-        for (id, swarm) in self.swarms.iter_mut() {
-            swarm.update(*id, self.width, self.height, &mut self.bullets);
+        // Pings are made on the tick after they're sent, so replays see them in order
+        for (player, (x, y)) in mem::replace(&mut self.pings, BTreeMap::new()) {
+            self.events.push(WorldEvent::PING {
+                player: player,
+                x: x,
+                y: y,
+            });
         }
 
-        // Update each bullet
-        let mut i: usize = 0;
-        let mut upper_bound_bullets: usize = self.bullets.len();
-		
-        'outer: while i < upper_bound_bullets {
-            // position update bullets
+        // Buff players far behind before they move and fire
+        if let Some(comeback) = self.comeback {
+            comeback.apply(&mut self.swarms);
+        }
 
-            self.bullets[i].update();
+        // Scripted swarms decide what to do before any swarm moves
+        #[cfg(feature = "scripting")]
+        self.run_scripts();
 
-            // remove expired bullets
-            if self.bullets[i].duration == 0 {
-                self.bullets.swap_remove(i);
-                upper_bound_bullets -= 1;
-                continue;
+        // Update each member of the swarm with its own program, in player ID order
+        self.arena.order.extend(self.swarms.keys());
+        self.arena.order.sort_unstable();
+        for id in self.arena.order.iter() {
+            // Events from earlier ticks are handled first, so a handler's first command can sense
+            // A handler over the limit isn't started, and its event is dropped
+            let mut refused = None;
+            if let Some(swarm) = self.swarms.get_mut(id) {
+                if !swarm.program.paused {
+                    refused = self.limits.check_dispatch(swarm);
+                    if refused.is_some() {
+                        swarm.pending = None;
+                    } else {
+                        swarm.dispatch();
+                    }
+                }
+            }
+            // Only branches, sensor commands and ENEMY_SIGHTED handlers need to sense
+            // the rest of the world
+            let sensing = self.swarms.get(id).map_or(false, |swarm| {
+                swarm.next_command().map_or(false, |command| command.senses())
+                    || swarm.program.handles(Trigger::ENEMY_SIGHTED)
+            });
+            let senses = if sensing {
+                self.sense(*id)
+            } else {
+                Senses::default()
+            };
+            if let Some(swarm) = self.swarms.get_mut(id) {
+                swarm.sense(senses);
+                let (x, y, direction) = (swarm.x, swarm.y, swarm.direction);
+                let bullets = self.bullets.len();
+                // Manual input runs in place of the program, which keeps its place
+                let program = self
+                    .manual
+                    .get_mut(id)
+                    .and_then(ManualControl::next_command)
+                    .map(|command| {
+                        mem::replace(&mut swarm.program, SwarmProgram::new(vec![command]))
+                    });
+                swarm.fire_rate = self.limits.fire_rates.cooldown(swarm.weapon);
+                swarm.spread = self.limits.spreads.spread(swarm.weapon);
+                // Paused programs don't run, though manual input still does
+                if swarm.program.paused {
+                    swarm.idle();
+                } else if let Some(limit) = refused.or(self.limits.check(swarm)) {
+                    // Commands over a limit are skipped rather than run
+                    debug!("Player {} went over the {:?} limit", id, limit);
+                    self.events.push(WorldEvent::LIMIT_EXCEEDED {
+                        player: *id,
+                        limit: limit,
+                    });
+                    swarm.skip_command();
+                } else {
+                    swarm.update(
+                        *id,
+                        self.width,
+                        self.height,
+                        self.boundary,
+                        &mut self.bullets,
+                        &mut self.rng,
+                    );
+                }
+                if let Some(program) = program {
+                    swarm.program = program;
+                }
+                if let Some(emote) = swarm.emote.take() {
+                    self.events.push(WorldEvent::EMOTE {
+                        player: *id,
+                        emote: emote,
+                    });
+                }
+                if self.bullets.len() > bullets {
+                    self.events.push(WorldEvent::FIRED {
+                        player: *id,
+                        bullets: self.bullets.len() - bullets,
+                    });
+                }
+                // Terrain scales the move, and obstacles undo it
+                if let Some(ref map) = self.map {
+                    let speed = map.speed_at(x, y);
+                    if speed != 1.0 {
+                        swarm.x = x + (swarm.x - x) * speed;
+                        swarm.y = y + (swarm.y - y) * speed;
+                    }
+                    if map.blocked(swarm.x, swarm.y) {
+                        swarm.x = x;
+                        swarm.y = y;
+                    }
+                }
+                swarm.record_velocity(x, y, direction);
+                // Only moves made by the swarm's program count as travel
+                let distance = ((swarm.x - x).powi(2) + (swarm.y - y).powi(2)).sqrt();
+                self.player_stats.travel(*id, distance);
+                if let Some(ref mut achievements) = self.achievements {
+                    if let Some(achievement) = achievements.travel(*id, distance) {
+                        self.events.push(WorldEvent::ACHIEVEMENT {
+                            player: *id,
+                            achievement: achievement,
+                        });
+                    }
+                }
             }
+        }
 
-            // collision detection here
-
-            // check each swarm
-            for (id, swarm) in self.swarms.iter_mut() {
-                // TODO: choose the epsilon to consider as "incoming dangerous
-                // bullets"
-                let epsilon: f32 = 60.0;
-                if (self.bullets[i].x - swarm.x).abs() <= epsilon
-                    && (self.bullets[i].y - swarm.y).abs() <= epsilon
-                {
-                    let mut j: usize = 0;
-                    let mut upper_bound_members = swarm.members.len();
-                    while j < upper_bound_members {
-                        // collision detection
-                        let swarm_member_radius: f32 = 10.0;
-                        // unwrap member
-                        // detect colllision
-                        // for now detects if the bullet passes within a
-                        // square hitbox around the swarm member
-                        if (self.bullets[i].x - (swarm.x + swarm.members[j].x)).abs()
-                            <= swarm_member_radius
-                            && (self.bullets[i].y - (swarm.y + swarm.members[j].y)).abs()
-                                <= swarm_member_radius
-                            && self.bullets[i].owner != *id
-                        {
-                            swarm.members[j].health -= 1;
-                            debug!("HIT");
-                            if swarm.members[j].health == 0 {
-                                debug!("KILL");
-                                swarm.members.swap_remove(j);
-                                upper_bound_members -= 1;
-                            }
-                            // delete bullet
-                            self.bullets.swap_remove(i);
-                            upper_bound_bullets -= 1;
-                            continue 'outer;
-                        }
-                        j += 1;
+        self.manual.retain(|_, manual| !manual.is_finished());
+
+        // Swarms in the safe zone of a spawn point they didn't spawn at are pushed out of it
+        if let Some(ref map) = self.map {
+            for swarm in self.swarms.values_mut() {
+                if let Some((x, y)) = map.pushback(swarm.home, swarm.x, swarm.y) {
+                    if !map.blocked(x, y) {
+                        swarm.x = x;
+                        swarm.y = y;
+                        swarm.dirty.moved = true;
                     }
                 }
             }
-            // update appropriate experience
-            for &(id, exp) in exp_queue.iter() {
-                if let Some(mut e_swarm) = self.swarms.get_mut(&id) {
-                    e_swarm.experience += exp;
+        }
+
+        // Move each bullet and remove expired bullets, bullets that hit an obstacle,
+        // and bullets in the safe zone of a spawn point their owner didn't spawn at
+        self.bullets.update();
+        if let Some(ref map) = self.map {
+            let swarms = &self.swarms;
+            self.bullets.remove_owned_blocked(|owner, x, y| {
+                let home = swarms.get(&owner).and_then(|swarm| swarm.home);
+                map.blocked(x, y) || map.safe_zone(home, x, y).is_some()
+            });
+        }
+
+        // Find bullets overlapping other players' members, then apply the hits in order
+        collision::index_members(&self.swarms, &mut self.member_index);
+        collision::find_hits(
+            &self.swarms,
+            &self.member_index,
+            &self.bullets,
+            &mut self.arena,
+        );
+        collision::resolve_hits(&mut self.swarms, &mut self.bullets, &mut self.arena);
+        let hits = self.arena.damage.iter().zip(self.arena.hit_at.iter());
+        for (&(attacker, victim, damage), &(x, y)) in hits {
+            // Handled on the victim's next update
+            if let Some(swarm) = self.swarms.get_mut(&victim) {
+                swarm.raise(Trigger::HIT);
+            }
+            self.events.push(WorldEvent::HIT {
+                attacker: attacker,
+                victim: victim,
+                weapon: Weapon::BULLET,
+                damage: damage,
+                x: x,
+                y: y,
+            });
+        }
+        if !self.arena.kills.is_empty() {
+            let streaks = match self.streaks {
+                Some(ref streaks) => streaks.clone(),
+                None => self.mode.streak_config(),
+            };
+            for &(attacker, victim) in self.arena.kills.iter() {
+                self.events.push(WorldEvent::KILL {
+                    attacker: attacker,
+                    victim: victim,
+                });
+                // Reward the player that fired the bullet, and break the victim's streak
+                streaks.apply_kill(&mut self.swarms, attacker, victim, &mut self.events);
+                if let Some(bounty) = self.bounty {
+                    bounty.claim(&mut self.swarms, attacker, victim, &mut self.events);
+                }
+            }
+            // Before the mode's rules run, so wiped out swarms haven't respawned yet
+            if let Some(ref mut achievements) = self.achievements {
+                let unlocked = achievements.evaluate(&self.events[first_event..], &self.swarms);
+                for (player, achievement) in unlocked {
+                    self.events.push(WorldEvent::ACHIEVEMENT {
+                        player: player,
+                        achievement: achievement,
+                    });
                 }
             }
-            exp_queue.clear();
-            // increment to next bullet
-            i += 1;
         }
-        // Record time at end of update and return the time elapsed
-        Instant::now().duration_since(start_time)
+        // Idle scores decay and the leader's bounty grows once the tick's kills are scored
+        if let Some(decay) = self.decay {
+            decay.apply(&mut self.swarms);
+        }
+        if let Some(bounty) = self.bounty {
+            bounty.apply(&mut self.swarms, self.tick);
+        }
+        // Apply the mode's rules, now that the tick's hits are known
+        self.mode.update(
+            &mut self.swarms,
+            &self.arena.kills,
+            &mut self.events,
+            &mut self.rng,
+        );
+        self.player_stats.record(&self.events[first_event..]);
+        self.player_stats.record_tick(&self.swarms);
+        if self.tick % MINIMAP_INTERVAL == 0 {
+            self.minimap =
+                Minimap::compute(&self.swarms, &self.mode, self.width, self.height, self.tick);
+        }
+        audio::collect_cues(
+            self.tick,
+            &self.events[first_event..],
+            &self.swarms,
+            &self.arena.lost,
+            &mut self.sounds,
+        );
+        if self.show_hit_markers {
+            hit_marker::collect_markers(
+                self.tick,
+                &self.events[first_event..],
+                &mut self.hit_markers,
+            );
+        }
+        signal::collect_signals(
+            self.tick,
+            &self.events[first_event..],
+            &self.swarms,
+            &self.mode,
+            &mut self.signals,
+        );
+        self.remove_destroyed_npcs();
+        self.sanitize();
+    }
+    /// Puts a practice world back some number of ticks, and pauses it
+    /// return: the number of ticks rewound, fewer than asked for if the history is shorter
+    pub fn rewind(&mut self, ticks: usize) -> usize {
+        let mut practice = match self.practice.take() {
+            Some(practice) => practice,
+            None => return 0,
+        };
+        let ticks = ticks.min(practice.available());
+        if let Some(world) = practice.rewind(ticks) {
+            // Snapshots are clones, which have no observers, so the current ones are kept
+            let observers = mem::replace(&mut self.observers, Observers::default());
+            *self = world;
+            self.observers = observers;
+            // The restored world's pending events were sent when they happened
+            self.observers.caught_up(self.events.len());
+        }
+        practice.paused = true;
+        practice.steps = 0;
+        practice.revision += 1;
+        self.practice = Some(practice);
+        ticks
+    }
+    /// Adds a swarm controlled by the game that doesn't move or fire, to shoot at
+    /// return: the ID of the swarm
+    pub fn spawn_dummy(&mut self, x: f32, y: f32) -> usize {
+        let id = self
+            .swarms
+            .keys()
+            .cloned()
+            .filter(|&id| is_npc(id))
+            .max()
+            .map_or(NPC_ID_START, |id| id + 1);
+        let mut dummy = Swarm::new(x, y, INITIAL_SWARM_SIZE);
+        dummy.program = SwarmProgram::default();
+        self.swarms.insert(id, dummy);
+        self.mark_revised();
+        id
+    }
+    /// Adds a bullet that doesn't belong to any player, so it can hit every swarm
+    pub fn spawn_bullet(&mut self, x: f32, y: f32, direction: f32) {
+        self.bullets
+            .push(Bullet::new(SPAWNED_BULLET_OWNER, x, y, direction));
+        self.mark_revised();
+    }
+    /// Number of changes made to a practice world outside of updates
+    /// Changes made while paused don't advance the tick, so caches check this too
+    pub fn revision(&self) -> u64 {
+        self.practice
+            .as_ref()
+            .map_or(0, |practice| practice.revision)
+    }
+    /// Counts a change made to a practice world outside of an update
+    fn mark_revised(&mut self) {
+        if let Some(ref mut practice) = self.practice {
+            practice.revision += 1;
+        }
+    }
+    /// Removes swarms controlled by the game that lost every member
+    fn remove_destroyed_npcs(&mut self) {
+        let destroyed: Vec<usize> = self
+            .swarms
+            .iter()
+            .filter(|&(&id, swarm)| is_npc(id) && swarm.members.is_empty())
+            .map(|(&id, _)| id)
+            .collect();
+        for id in destroyed {
+            self.remove_player(id);
+        }
+    }
+    /// Freezes the world and reports how the match finished
+    fn end_match(&mut self, reason: EndReason) {
+        info!("Match ended on tick {}", self.tick);
+        self.update_leaderboard();
+        self.phase = MatchPhase::FROZEN { ended: self.tick };
+        let results = MatchResults::new(&self.swarms, self.tick, reason)
+            .with_stats(self.player_stats.summary());
+        self.events.push(WorldEvent::MATCH_ENDED(results));
+    }
+    /// Starts a new match, respawning every player without their experience
+    fn start_match(&mut self) {
+        info!("Match started on tick {}", self.tick);
+        self.bullets.clear();
+        let mut ids: Vec<usize> = self.swarms.keys().cloned().collect();
+        ids.sort();
+        for id in ids {
+            self.respawn_player(id);
+        }
+        self.mode.start_match();
+        for ghost in self.ghosts.iter_mut() {
+            ghost.frame = 0;
+        }
+        self.player_stats.clear();
+        self.phase = MatchPhase::PLAYING { started: self.tick };
+        self.events.push(WorldEvent::MATCH_STARTED);
+    }
+    /// Resets values that aren't finite and keeps swarms inside the world,
+    /// so a bad value can't spread through collisions or reach clients
+    fn sanitize(&mut self) {
+        for (id, swarm) in self.swarms.iter_mut() {
+            if swarm.sanitize(self.width, self.height) {
+                warn!("Reset a value of player {} that wasn't a number", id);
+                self.events.push(WorldEvent::SANITIZED(*id));
+            }
+        }
+        let removed = self.bullets.remove_non_finite();
+        if removed > 0 {
+            warn!("Removed {} bullets that weren't at a number", removed);
+        }
     }
-    /// Returns the world in byte representation
-    /// Used to render the world on a client
+    /// Collects the swarms changed and players removed since the last tick,
+    /// and clears the swarms' dirty flags
+    /// Changes made between ticks, such as players joining, count toward the next tick
+    fn record_delta(&mut self) {
+        self.changed.clear();
+        for (id, swarm) in self.swarms.iter_mut() {
+            if swarm.dirty.any() {
+                self.changed.push((*id, swarm.dirty));
+                swarm.dirty = Dirty::default();
+            }
+        }
+        self.removed.clear();
+        self.removed.append(&mut self.departed);
+    }
+    /// Borrows what changed during the last tick
+    /// Only the swarms that changed are visited, so the cost follows how much happened
+    pub fn delta(&self) -> WorldDelta {
+        WorldDelta {
+            tick: self.tick,
+            removed: &self.removed,
+            swarms: self
+                .changed
+                .iter()
+                .filter_map(|&(id, dirty)| {
+                    self.swarms.get(&id).map(|swarm| SwarmDelta {
+                        id: id,
+                        dirty: dirty,
+                        swarm: swarm,
+                    })
+                })
+                .collect(),
+            bullets: &self.bullets,
+            mode: &self.mode,
+            phase: self.phase,
+            minimap: if self.minimap.tick == self.tick && !self.minimap.is_empty() {
+                Some(&self.minimap)
+            } else {
+                None
+            },
+            sounds: &self.sounds,
+            hit_markers: &self.hit_markers,
+            signals: &self.signals,
+            ghosts: &self.ghost_states,
+            checksum: self.state_checksum(self.tick),
+        }
+    }
+    /// Returns a copy of the parts of the world clients render
     pub fn get_state(&self) -> WorldState {
         WorldState {
             swarms: self.swarms.clone(),
             bullets: self.bullets.clone(),
+            mode: self.mode.clone(),
+            phase: self.phase,
+            minimap: self.minimap.clone(),
+            sounds: self.sounds.clone(),
+            hit_markers: self.hit_markers.clone(),
+            signals: self.signals.clone(),
+            ghosts: self.ghost_states.clone(),
+            checksum: self.state_checksum(self.tick),
+        }
+    }
+    /// Returns a copy of the parts of the world a player can see
+    /// With fog of war, other players' swarms and bullets behind obstacles are left out;
+    /// players without a swarm see everything
+    pub fn state_for(&self, player_id: usize) -> WorldState {
+        let eye = match self.swarms.get(&player_id) {
+            Some(swarm) if self.fog_of_war => (swarm.x, swarm.y),
+            _ => return self.get_state(),
+        };
+        let mut swarms = Slab::with_capacity(self.swarms.len());
+        for (&id, swarm) in self.swarms.iter() {
+            if id == player_id || self.line_of_sight(eye, (swarm.x, swarm.y)) {
+                swarms.insert(id, swarm.clone());
+            }
+        }
+        let mut bullets = self.bullets.clone();
+        bullets.remove_owned_blocked(|owner, x, y| {
+            owner != player_id && !self.line_of_sight(eye, (x, y))
+        });
+        WorldState {
+            swarms: swarms,
+            bullets: bullets,
+            mode: self.mode.clone(),
+            phase: self.phase,
+            minimap: self.minimap.clone(),
+            sounds: self.sounds.clone(),
+            hit_markers: self.hit_markers.clone(),
+            signals: self.signals.clone(),
+            ghosts: self.ghost_states.clone(),
+            checksum: self.state_checksum(self.tick),
+        }
+    }
+    /// Borrows the parts of the world clients render
+    /// Serializes the same as get_state, without copying every swarm and bullet
+    pub fn state_ref(&self) -> WorldStateRef {
+        WorldStateRef {
+            swarms: &self.swarms,
+            bullets: &self.bullets,
+            mode: &self.mode,
+            phase: self.phase,
+            minimap: &self.minimap,
+            sounds: &self.sounds,
+            hit_markers: &self.hit_markers,
+            signals: &self.signals,
+            ghosts: &self.ghost_states,
+            checksum: self.state_checksum(self.tick),
         }
     }
 }
 
+/// Statistics about the world, used for monitoring
 #[derive(Clone, Debug, Serialize)]
+pub struct WorldStats {
+    /// Number of ticks performed
+    pub tick: u64,
+    /// Number of players in the world
+    pub num_players: usize,
+    /// Number of swarm members across all swarms
+    pub num_members: usize,
+    /// Number of bullets in flight
+    pub num_bullets: usize,
+    /// The width of the world
+    pub width: f32,
+    /// The height of the world
+    pub height: f32,
+    /// Whether updates have been going over the time budget
+    pub overloaded: bool,
+    /// Number of updates that went over the time budget
+    pub over_budget: u64,
+    /// Number of ticks dropped because updates fell too far behind
+    pub skipped_ticks: u64,
+    /// Work done keeping the grid of swarm members up to date
+    pub member_index: IndexStats,
+    /// Approximate memory used
+    pub memory: MemoryStats,
+    /// Players with a handicap or comeback buff
+    pub handicaps: Vec<PlayerHandicap>,
+    /// Ticks simulated per update
+    pub time_scale: f32,
+}
+
+/// A player's handicap and comeback buff, as shown in stats
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct PlayerHandicap {
+    /// ID of the player
+    pub player: usize,
+    /// Handicap set by an admin
+    pub handicap: Handicap,
+    /// Comeback buff
+    pub boost: Handicap,
+}
+
+/// Approximate bytes used by each part of a world
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct MemoryStats {
+    /// Swarms, their members, and their programs
+    pub swarms: usize,
+    /// Bullet arrays
+    pub bullets: usize,
+    /// Pending events and the lists of changes kept for deltas
+    pub events: usize,
+    /// Grid of swarm members
+    pub spatial_index: usize,
+    /// Scratch space for temporaries used during an update
+    pub scratch: usize,
+    /// Sum of the above
+    pub total: usize,
+}
+
+/// The state of the world as sent to clients
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct WorldState {
-    swarms: HashMap<usize, Swarm>,
-    bullets: Vec<Bullet>,
+    /// Every swarm, by player ID
+    pub swarms: Slab<Swarm>,
+    /// Every bullet
+    pub bullets: Bullets,
+    /// The mode's state, left out when the world is free for all
+    #[serde(default, skip_serializing_if = "GameMode::is_free_for_all")]
+    pub mode: GameMode,
+    /// Whether the world is frozen between matches, left out while a match is played
+    #[serde(default, skip_serializing_if = "MatchPhase::is_playing")]
+    pub phase: MatchPhase,
+    /// Members counted over a coarse grid, left out until the first count
+    #[serde(default, skip_serializing_if = "Minimap::is_empty")]
+    pub minimap: Minimap,
+    /// Sounds made during the tick, left out when it was quiet
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sounds: Vec<SoundCue>,
+    /// Hits landed during the tick, left out when there were none or they're hidden
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hit_markers: Vec<HitMarker>,
+    /// Emotes and pings made during the tick, left out when there were none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signals: Vec<Signal>,
+    /// Recorded runs being raced, left out when there are none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ghosts: Vec<GhostState>,
+    /// Checksum of the whole world at the end of the tick, left out unless checksums are kept
+    /// Players sent only what they can see get the checksum of everything
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<u32>,
+}
+
+/// Functions for WorldState
+impl WorldState {
+    /// Borrows the state, to be sent the same way as the world's
+    pub fn state_ref(&self) -> WorldStateRef {
+        WorldStateRef {
+            swarms: &self.swarms,
+            bullets: &self.bullets,
+            mode: &self.mode,
+            phase: self.phase,
+            minimap: &self.minimap,
+            sounds: &self.sounds,
+            hit_markers: &self.hit_markers,
+            signals: &self.signals,
+            ghosts: &self.ghosts,
+            checksum: self.checksum,
+        }
+    }
+}
+
+/// The state of the world as sent to clients, borrowed from the world
+#[derive(Clone, Copy, Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct WorldStateRef<'a> {
+    swarms: &'a Slab<Swarm>,
+    bullets: &'a Bullets,
+    #[serde(skip_serializing_if = "free_for_all")]
+    mode: &'a GameMode,
+    #[serde(skip_serializing_if = "MatchPhase::is_playing")]
+    phase: MatchPhase,
+    #[serde(skip_serializing_if = "empty_minimap")]
+    minimap: &'a Minimap,
+    #[serde(skip_serializing_if = "no_sounds")]
+    sounds: &'a [SoundCue],
+    #[serde(skip_serializing_if = "no_hit_markers")]
+    hit_markers: &'a [HitMarker],
+    #[serde(skip_serializing_if = "no_signals")]
+    signals: &'a [Signal],
+    #[serde(skip_serializing_if = "no_ghosts")]
+    ghosts: &'a [GhostState],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checksum: Option<u32>,
+}
+
+/// The changes made to the world during one tick, borrowed from the world
+/// Clients apply the removals first, then replace each changed swarm
+#[derive(Clone, Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct WorldDelta<'a> {
+    /// The tick the changes were made in
+    tick: u64,
+    /// IDs of players that left
+    removed: &'a [usize],
+    /// Every swarm that changed
+    swarms: Vec<SwarmDelta<'a>>,
+    /// Every bullet, since bullets move every tick
+    bullets: &'a Bullets,
+    /// The mode's state, since flags move with their carriers
+    #[serde(skip_serializing_if = "free_for_all")]
+    mode: &'a GameMode,
+    /// Whether the world is frozen between matches
+    #[serde(skip_serializing_if = "MatchPhase::is_playing")]
+    phase: MatchPhase,
+    /// Members counted over a coarse grid, sent on the ticks it's refreshed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    minimap: Option<&'a Minimap>,
+    /// Sounds made during the tick
+    #[serde(skip_serializing_if = "no_sounds")]
+    sounds: &'a [SoundCue],
+    /// Hits landed during the tick, if hit markers are shown
+    #[serde(skip_serializing_if = "no_hit_markers")]
+    hit_markers: &'a [HitMarker],
+    /// Emotes and pings made during the tick
+    #[serde(skip_serializing_if = "no_signals")]
+    signals: &'a [Signal],
+    /// Where each ghost is, sent every tick while there are any
+    #[serde(skip_serializing_if = "no_ghosts")]
+    ghosts: &'a [GhostState],
+    /// Checksum of the world at the end of the tick, if checksums are kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checksum: Option<u32>,
+}
+
+/// Whether a borrowed mode is free for all, so it can be left out of messages
+fn free_for_all(mode: &&GameMode) -> bool {
+    mode.is_free_for_all()
+}
+
+/// Whether a borrowed minimap is empty, so it can be left out of messages
+fn empty_minimap(minimap: &&Minimap) -> bool {
+    minimap.is_empty()
+}
+
+/// Whether no sounds were made, so they can be left out of messages
+fn no_sounds(sounds: &&[SoundCue]) -> bool {
+    sounds.is_empty()
+}
+
+/// Whether no hits are to be shown, so they can be left out of messages
+fn no_hit_markers(hit_markers: &&[HitMarker]) -> bool {
+    hit_markers.is_empty()
+}
+
+/// Whether no emotes or pings were made, so they can be left out of messages
+fn no_signals(signals: &&[Signal]) -> bool {
+    signals.is_empty()
+}
+
+/// Whether no ghosts are being raced, so they can be left out of messages
+fn no_ghosts(ghosts: &&[GhostState]) -> bool {
+    ghosts.is_empty()
+}
+
+/// A swarm that changed during a tick
+#[derive(Clone, Copy, Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct SwarmDelta<'a> {
+    /// ID of the player that owns the swarm
+    id: usize,
+    /// What changed
+    dirty: Dirty,
+    /// The swarm's new state
+    swarm: &'a Swarm,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use entity::Bullet;
     #[test]
     fn initialize_world() {
         let world = World::new(1000.0, 1000.0);
     }
     #[test]
+    fn test_state_ref() {
+        let mut world = World::new(1000.0, 1000.0);
+        world.add_player(0).unwrap();
+        world.add_player(1).unwrap();
+        let state = world.state_ref();
+        assert_eq!(state.swarms.len(), 2);
+        assert_eq!(state.bullets.len(), 0);
+        assert_eq!(
+            serde_json::to_string(&state).unwrap(),
+            serde_json::to_string(&world.get_state()).unwrap()
+        );
+    }
+    #[test]
     fn test_leaderboard() {
+        use duel::MemoryRatings;
         let mut world = World::new(1000.0, 1000.0);
         for i in 0..20 {
-            world.add_player(i);
-            println!("{:?}", world.leaderboard);
+            world.add_player(i).unwrap();
         }
         assert!(world.leaderboard.len() <= 10);
+        // The leader comes first with their bounty, and ties go to the lower ID
+        world.swarms.get_mut(&2).unwrap().experience = 30;
+        world.swarms.get_mut(&2).unwrap().bounty = 6;
+        world.swarms.get_mut(&15).unwrap().experience = 10;
+        world.update_leaderboard();
+        assert_eq!(world.leaderboard.len(), 10);
+        assert_eq!(world.leaderboard[0], (2, 30, 6, None));
+        assert_eq!(world.leaderboard[1], (15, 10, 0, None));
+        assert_eq!(world.leaderboard[2], (0, 0, 0, None));
+        assert_eq!(world.leaderboard[9], (7, 0, 0, None));
+        // Ratings are shown once there's a store for them
+        let store = Arc::new(MemoryRatings::new());
+        store.set_rating(15, 1620.0);
+        let mut world = world.with_ratings(store);
+        world.update_leaderboard();
+        assert_eq!(world.leaderboard[1], (15, 10, 0, Some(1620.0)));
+        assert_eq!(world.leaderboard[2], (0, 0, 0, Some(DEFAULT_RATING)));
+    }
+    #[test]
+    fn test_respawn_player() {
+        let mut world = World::new(1000.0, 1000.0);
+        world.add_player(3).unwrap();
+        world.swarms.get_mut(&3).unwrap().members.clear();
+        world.swarms.get_mut(&3).unwrap().experience = 40;
+        assert!(world.respawn_player(3));
+        assert_eq!(world.swarms[&3].members.len(), INITIAL_SWARM_SIZE);
+        assert_eq!(world.swarms[&3].experience, 0);
+        assert!(!world.respawn_player(4));
+        assert_eq!(world.player_ids(), vec![3]);
+        assert_eq!(world.stats().num_members, INITIAL_SWARM_SIZE);
+    }
+    #[test]
+    fn test_export_restore_player() {
+        let mut world = World::new(1000.0, 1000.0);
+        world.add_player(0).unwrap();
+        world.add_player(1).unwrap();
+        world.swarms.get_mut(&0).unwrap().experience = 7;
+        world.swarms.get_mut(&0).unwrap().program = "MOVE\nTURN 15".parse().unwrap();
+        let snapshot = world.export_player(0).unwrap();
+        world.restore_player(1, &snapshot).unwrap();
+        assert_eq!(world.swarms[&1].experience, 7);
+        assert_eq!(world.swarms[&1].color, world.swarms[&0].color);
+        assert_eq!(
+            world.swarms[&1].program.commands,
+            world.swarms[&0].program.commands
+        );
+        assert!(world.export_player(2).is_none());
+        assert!(world.restore_player(2, &snapshot).is_err());
+    }
+    #[test]
+    fn test_hit_markers() {
+        let config = WorldConfig::new(1000.0, 1000.0).with_hit_markers();
+        let mut world = World::from_config(&config).unwrap();
+        world.add_player(0).unwrap();
+        world.add_player(1).unwrap();
+        world.swarms.get_mut(&1).unwrap().program.commands.clear();
+        world.swarms.get_mut(&0).unwrap().program.commands.clear();
+        let x = world.swarms[&1].x + world.swarms[&1].members[0].x;
+        let y = world.swarms[&1].y + world.swarms[&1].members[0].y;
+        world.bullets.push(Bullet::new(0, x - 5.0, y, 0.0));
+        world.update();
+        let marker = HitMarker::new(world.tick, 0, 1, 1, x, y);
+        assert_eq!(world.hit_markers, vec![marker]);
+        assert_eq!(world.get_state().hit_markers, vec![marker]);
+        // Markers only last for the update they happened in
+        world.update();
+        assert!(world.hit_markers.is_empty());
+    }
+    #[test]
+    fn test_signals() {
+        use signal::SignalKind;
+        let mut world = World::new(1000.0, 1000.0);
+        world.add_player(0).unwrap();
+        world.add_player(1).unwrap();
+        world.update_program(0, "EMOTE 4\nNOOP".parse().unwrap());
+        world.swarms.get_mut(&1).unwrap().program.commands.clear();
+        world.ping(1, 100.0, 200.0).unwrap();
+        world.ping(1, 300.0, 400.0).unwrap();
+        assert_eq!(
+            world.ping(1, -1.0, 0.0),
+            Err(WorldError::OUT_OF_BOUNDS { x: -1.0, y: 0.0 })
+        );
+        assert_eq!(world.ping(2, 0.0, 0.0), Err(WorldError::NO_PLAYER(2)));
+        world.update();
+        let (x, y) = (world.swarms[&0].x, world.swarms[&0].y);
+        // Only the latest ping is made, before any swarm moves
+        assert_eq!(
+            world.get_state().signals,
+            vec![
+                Signal::new(1, 1, SignalKind::PING, 300.0, 400.0),
+                Signal::new(1, 0, SignalKind::EMOTE(4), x, y),
+            ]
+        );
+        world.update();
+        assert!(world.signals.is_empty());
+    }
+    #[test]
+    fn test_kill_experience() {
+        let mut world = World::new(1000.0, 1000.0);
+        world.add_player(0).unwrap();
+        world.add_player(1).unwrap();
+        assert_eq!(
+            world.drain_events(),
+            vec![WorldEvent::JOIN(0), WorldEvent::JOIN(1)]
+        );
+        // Put a dying member of player 1 in the path of player 0's bullet
+        let (x, y) = (world.swarms[&1].x, world.swarms[&1].y);
+        world.swarms.get_mut(&1).unwrap().program.commands.clear();
+        world.swarms.get_mut(&0).unwrap().program.commands.clear();
+        world.swarms.get_mut(&1).unwrap().members[0].health = 1;
+        let offset = world.swarms[&1].members[0].x;
+        let member_y = y + world.swarms[&1].members[0].y;
+        world
+            .bullets
+            .push(Bullet::new(0, x + offset - 5.0, member_y, 0.0));
+        world.update();
+        assert_eq!(world.swarms[&0].experience, 1);
+        // Hit markers are off unless the world is configured to show them
+        assert!(world.hit_markers.is_empty());
+        assert_eq!(
+            world.drain_events(),
+            vec![
+                WorldEvent::HIT {
+                    attacker: 0,
+                    victim: 1,
+                    weapon: Weapon::BULLET,
+                    damage: 1,
+                    x: x + offset,
+                    y: member_y,
+                },
+                WorldEvent::KILL {
+                    attacker: 0,
+                    victim: 1,
+                },
+            ]
+        );
+        assert_eq!(world.player_stats.summary()[&0].hits, 1);
+    }
+    #[test]
+    fn limit_handler_storms() {
+        use limits::Limit;
+        let mut world = World::new(1000.0, 1000.0).with_seed(4);
+        world.limits = ExecutionLimits::default().with_handlers(2);
+        world.add_player(0).unwrap();
+        world.update_program(0, "MOVE\nON_HIT\nTURN 10\nENDON".parse().unwrap());
+        world.drain_events();
+        // Getting hit every tick would keep the program in its handler for good
+        for _ in 0..5 {
+            world.swarms.get_mut(&0).unwrap().raise(Trigger::HIT);
+            world.update();
+        }
+        assert!(world.drain_events().contains(&WorldEvent::LIMIT_EXCEEDED {
+            player: 0,
+            limit: Limit::HANDLERS,
+        }));
+        assert_eq!(world.swarms[&0].handlers_in_a_row, 0);
+    }
+    #[test]
+    fn test_claim_bounty() {
+        let mut world = World::new(1000.0, 1000.0).with_bounty(Bounty::new());
+        world.add_player(0).unwrap();
+        world.add_player(1).unwrap();
+        world.drain_events();
+        // Player 1 has a bounty on them and one dying member left in the path of a bullet
+        let (x, y) = (world.swarms[&1].x, world.swarms[&1].y);
+        world.swarms.get_mut(&1).unwrap().program.commands.clear();
+        world.swarms.get_mut(&0).unwrap().program.commands.clear();
+        world.swarms.get_mut(&1).unwrap().members.truncate(1);
+        world.swarms.get_mut(&1).unwrap().members[0].health = 1;
+        world.swarms.get_mut(&1).unwrap().bounty = 4;
+        let offset = world.swarms[&1].members[0].x;
+        let member_y = y + world.swarms[&1].members[0].y;
+        world
+            .bullets
+            .push(Bullet::new(0, x + offset - 5.0, member_y, 0.0));
+        world.update();
+        // The kill is worth a point, and the bounty is paid on top of it
+        assert_eq!(world.swarms[&0].experience, 5);
+        assert_eq!(world.swarms[&1].bounty, 0);
+        assert!(world.drain_events().contains(&WorldEvent::BOUNTY_CLAIMED {
+            player: 0,
+            victim: 1,
+            bounty: 4,
+        }));
+    }
+    #[test]
+    fn test_seeded_worlds_match() {
+        let mut first = World::new(1000.0, 1000.0).with_seed(42);
+        let mut second = World::new(1000.0, 1000.0).with_seed(42);
+        for id in 0..5 {
+            first.add_player(id).unwrap();
+            second.add_player(id).unwrap();
+        }
+        for id in 0..5 {
+            assert_eq!(first.swarms[&id].x, second.swarms[&id].x);
+            assert_eq!(first.swarms[&id].y, second.swarms[&id].y);
+            assert_eq!(first.swarms[&id].color, second.swarms[&id].color);
+        }
+    }
+    #[test]
+    fn checksums_find_desync() {
+        let mut first = World::new(1000.0, 1000.0).with_seed(42).with_checksum_history(4);
+        let mut second = World::new(1000.0, 1000.0).with_seed(42).with_checksum_history(4);
+        for id in 0..3 {
+            for world in [&mut first, &mut second].iter_mut() {
+                world.add_player(id).unwrap();
+                world.update_program(id, "MOVE\nFIRE\nTURN 10".parse().unwrap());
+            }
+        }
+        for _ in 0..5 {
+            first.update();
+            second.update();
+        }
+        assert!(first.state_checksum(5).is_some());
+        assert_eq!(first.state_checksum(5), second.state_checksum(5));
+        assert_eq!(first.get_state().checksum, first.state_checksum(5));
+        // A swarm knocked off course shows up in the next tick's checksum
+        second.swarms.get_mut(&2).unwrap().x += 1.0;
+        first.update();
+        second.update();
+        assert_eq!(first.state_checksum(5), second.state_checksum(5));
+        assert!(first.state_checksum(6) != second.state_checksum(6));
+        // Only the last 4 ticks are kept
+        assert_eq!(first.state_checksum(2), None);
+        assert!(first.state_checksum(3).is_some());
+        assert_eq!(World::new(1000.0, 1000.0).get_state().checksum, None);
+    }
+    #[test]
+    fn test_duplicate_player() {
+        let mut world = World::new(1000.0, 1000.0);
+        assert!(!world.has_player(5));
+        world.add_player(5).unwrap();
+        world.swarms.get_mut(&5).unwrap().experience = 3;
+        // The second add is refused and the swarm is left alone
+        match world.add_player(5) {
+            Err(WorldError::DUPLICATE_PLAYER(5)) => {}
+            other => panic!("Expected a duplicate player error, got {:?}", other),
+        }
+        assert!(world.has_player(5));
+        assert_eq!(world.player_count(), 1);
+        assert_eq!(world.swarms[&5].experience, 3);
+        assert_eq!(world.events.len(), 1);
+    }
+    #[test]
+    fn test_update_order() {
+        let mut world = World::new(1000.0, 1000.0).with_seed(3);
+        // Join out of order, and reuse a slot, so slot order isn't ID order
+        for &id in [4, 2, 9, 0].iter() {
+            world.add_player(id).unwrap();
+        }
+        world.remove_player(2);
+        world.add_player(1).unwrap();
+        for swarm in world.swarms.values_mut() {
+            swarm.program = "FIRE".parse().unwrap();
+        }
+        world.update();
+        let mut owners = world.bullets.owner.clone();
+        owners.dedup();
+        assert_eq!(owners, vec![0, 1, 4, 9]);
+    }
+    #[test]
+    fn test_time_scale() {
+        let mut world = World::new(1000.0, 1000.0).with_seed(5);
+        world.add_player(0).unwrap();
+        assert!(world.set_time_scale(0.05).is_err());
+        assert!(world.set_time_scale(std::f32::NAN).is_err());
+        // Slow motion runs a tick every other update
+        world.set_time_scale(0.5).unwrap();
+        world.update();
+        assert_eq!(world.tick, 0);
+        world.update();
+        assert_eq!(world.tick, 1);
+        // Fast forward runs several ticks in one update, and reports every swarm they moved
+        world.set_time_scale(3.0).unwrap();
+        world.update();
+        assert_eq!(world.tick, 4);
+        assert_eq!(world.delta().swarms.len(), 1);
+        assert_eq!(world.stats().time_scale, 3.0);
+    }
+    #[test]
+    fn test_manual_control() {
+        let mut world = World::new(1000.0, 1000.0).with_seed(6);
+        world.add_player(0).unwrap();
+        world.update_program(0, "MOVE\nTURN 30".parse().unwrap());
+        let input = ControlInput {
+            turn: 0.0,
+            thrust: false,
+            fire: true,
+            ticks: 2,
+            blend: Default::default(),
+        };
+        assert!(world.control(1, &input).is_err());
+        world.control(0, &input).unwrap();
+        let (x, y) = (world.swarms[&0].x, world.swarms[&0].y);
+        // The input fires and then waits out the cooldown, without moving the program on
+        world.update();
+        world.update();
+        assert!(world.bullets.len() > 0);
+        assert_eq!((world.swarms[&0].x, world.swarms[&0].y), (x, y));
+        assert_eq!(world.swarms[&0].program.program_counter, 0);
+        assert!(world.manual.is_empty());
+        // Then the program picks up where it was
+        world.update();
+        assert_eq!(world.swarms[&0].program.program_counter, 1);
+    }
+    #[test]
+    fn test_delta() {
+        let mut world = World::new(1000.0, 1000.0).with_seed(7);
+        world.add_player(0).unwrap();
+        world.add_player(1).unwrap();
+        world.swarms.get_mut(&1).unwrap().program.commands.clear();
+        world.update();
+        // Both swarms are new
+        assert_eq!(world.delta().swarms.len(), 2);
+        world.update();
+        // Only the swarm with a program moved
+        {
+            let delta = world.delta();
+            assert_eq!(delta.tick, 2);
+            assert_eq!(delta.swarms.len(), 1);
+            assert_eq!(delta.swarms[0].id, 0);
+            assert!(delta.swarms[0].dirty.moved);
+            assert!(!delta.swarms[0].dirty.damaged);
+        }
+        world.remove_player(0);
+        world.update();
+        let delta = world.delta();
+        assert!(delta.swarms.is_empty());
+        assert_eq!(delta.removed, &[0]);
+    }
+    #[test]
+    fn test_memory_stats() {
+        let mut world = World::new(1000.0, 1000.0);
+        let empty = world.memory_stats();
+        world.add_player(0).unwrap();
+        for _ in 0..100 {
+            world.bullets.push(Bullet::new(0, 10.0, 10.0, 0.0));
+        }
+        let stats = world.memory_stats();
+        assert!(stats.swarms > empty.swarms);
+        assert!(stats.bullets >= 100 * (4 * mem::size_of::<f32>() + 16));
+        assert_eq!(
+            stats.total,
+            stats.swarms + stats.bullets + stats.events + stats.spatial_index + stats.scratch
+        );
+    }
+    #[test]
+    fn test_world_config() {
+        assert!(World::from_config(&WorldConfig::new(400.0, 300.0)).is_ok());
+        assert_eq!(
+            WorldConfig::new(100.0, 300.0).validate(),
+            Err(WorldConfigError::TOO_SMALL {
+                width: 100.0,
+                height: 300.0,
+                spawn_margin: 50.0,
+            })
+        );
+        // A smaller margin makes room
+        let mut world = World::from_config(&WorldConfig::new(100.0, 300.0).with_spawn_margin(10.0))
+            .unwrap();
+        world.add_player(0).unwrap();
+        assert!(world.swarms[&0].x >= 10.0 && world.swarms[&0].x <= 90.0);
+        assert!(WorldConfig::new(0.0, 300.0).validate().is_err());
+        assert!(WorldConfig::new(f32::NAN, 300.0).validate().is_err());
+        assert!(WorldConfig::new(400.0, 300.0).with_spawn_margin(-1.0).validate().is_err());
+        // Worlds that weren't checked refuse players instead of panicking
+        let mut world = World::new(100.0, 300.0);
+        assert_eq!(
+            world.add_player(0),
+            Err(WorldError::INVALID_CONFIG(WorldConfigError::TOO_SMALL {
+                width: 100.0,
+                height: 300.0,
+                spawn_margin: 50.0,
+            }))
+        );
+        assert!(world.swarms.is_empty());
+        let mut world = World::with_capacity(f32::NAN, 300.0, 4);
+        assert!(world.add_player(0).is_err());
+    }
+    #[test]
+    fn pause_program() {
+        use entity::SwarmMember;
+        let mut world = World::new(1000.0, 1000.0);
+        world.add_player(1).unwrap();
+        world.update_program(1, "FIRE\nMOVE".parse().unwrap());
+        world.update();
+        world.set_paused(1, true).unwrap();
+        // Edits made while paused stay paused
+        world.update_program(1, "MOVE\nTURN 10".parse().unwrap());
+        let (x, y) = (world.swarms[&1].x, world.swarms[&1].y);
+        world.update();
+        world.update();
+        assert_eq!((world.swarms[&1].x, world.swarms[&1].y), (x, y));
+        assert_eq!(world.swarms[&1].program.program_counter, 0);
+        // Cooldowns carried on
+        assert_eq!(world.swarms[&1].members[0].cooldown, SwarmMember::FIRE_COOLDOWN - 3);
+        world.set_paused(1, false).unwrap();
+        world.update();
+        assert!(world.swarms[&1].x != x);
+        assert_eq!(world.set_paused(2, true), Err(WorldError::NO_PLAYER(2)));
+    }
+    #[test]
+    fn sense_surroundings() {
+        let mut world = World::new(1000.0, 1000.0);
+        world.swarms.insert(1, Swarm::new(500.0, 500.0, 3));
+        world.swarms.insert(2, Swarm::new(960.0, 500.0, 3));
+        assert_eq!(world.sense(1), Senses::default());
+        // Facing right, 40 from the edge
+        assert!(world.sense(2).wall_ahead);
+        world.swarms.get_mut(&2).unwrap().x = 650.0;
+        assert!(world.sense(1).enemy_near);
+        // Swarms sense the world when they branch on it
+        world.swarms.get_mut(&1).unwrap().program =
+            "IF ENEMY_NEAR\nTURN 10\nENDIF".parse().unwrap();
+        world.update();
+        world.update();
+        assert_eq!(world.swarms[&1].direction, 10.0);
+    }
+    #[test]
+    fn obstacles_block_sight() {
+        use map::Obstacle;
+        let mut map = Map::new(1000.0, 1000.0);
+        map.obstacles.push(Obstacle::new(550.0, 400.0, 20.0, 200.0));
+        let mut world = World::from_map(map).unwrap();
+        world.swarms.insert(1, Swarm::new(500.0, 500.0, 3));
+        world.swarms.insert(2, Swarm::new(650.0, 500.0, 3));
+        world.bullets.push(Bullet::new(2, 640.0, 500.0, 0.0));
+        world.bullets.push(Bullet::new(2, 500.0, 450.0, 0.0));
+        // The other swarm is in range, but behind the wall
+        let senses = world.sense(1);
+        assert!(!senses.enemy_near);
+        assert_eq!(senses.nearest_bullet, Some((500.0, 450.0)));
+        // Without fog of war, players are sent everything
+        assert_eq!(world.state_for(1).swarms.len(), 2);
+        world = world.with_fog_of_war();
+        let state = world.state_for(1);
+        assert_eq!(state.swarms.len(), 1);
+        assert!(state.swarms.contains_key(&1));
+        assert_eq!(state.bullets.len(), 1);
+        // Players without a swarm, like spectators, see everything
+        assert_eq!(world.state_for(3).bullets.len(), 2);
+        // Both above the wall
+        world.swarms.get_mut(&1).unwrap().y = 390.0;
+        world.swarms.get_mut(&2).unwrap().y = 390.0;
+        assert!(world.sense(1).enemy_near);
+        assert_eq!(world.state_for(1).swarms.len(), 2);
+    }
+    #[test]
+    fn sensor_commands() {
+        let mut world = World::new(1000.0, 1000.0);
+        world.swarms.insert(1, Swarm::new(500.0, 500.0, 3));
+        world.swarms.insert(2, Swarm::new(500.0, 400.0, 3));
+        let source = "NEAREST_ENEMY R0 R1\nSCAN R2\nNEAREST_BULLET R3 R4";
+        world.swarms.get_mut(&1).unwrap().program = source.parse().unwrap();
+        world.swarms.get_mut(&2).unwrap().program.commands.clear();
+        world.bullets.push(Bullet::new(2, 600.0, 500.0, 0.0));
+        for _ in 0..3 {
+            world.update();
+        }
+        // The other swarm is straight up, a quarter turn counterclockwise from the heading,
+        // and the bullet had moved on twice by the time it was looked for
+        assert_eq!(
+            world.swarms[&1].program.registers[..5],
+            [100, 90, 1, 110, 0]
+        );
+        // Nothing in range is found as -1
+        world.swarms.get_mut(&2).unwrap().y = 100.0;
+        world.swarms.get_mut(&1).unwrap().program.program_counter = 0;
+        world.update();
+        assert_eq!(world.swarms[&1].program.registers[..2], [-1, 0]);
+    }
+    #[test]
+    fn test_sanitize() {
+        let mut world = World::new(1000.0, 1000.0);
+        world.add_player(0).unwrap();
+        world.add_player(1).unwrap();
+        world.drain_events();
+        {
+            let swarm = world.swarms.get_mut(&0).unwrap();
+            swarm.program.commands.clear();
+            swarm.x = f32::NAN;
+            swarm.members[0].direction = f32::INFINITY;
+        }
+        {
+            let swarm = world.swarms.get_mut(&1).unwrap();
+            swarm.program.commands.clear();
+            swarm.x = 5000.0;
+        }
+        world.bullets.push(Bullet::new(0, f32::NAN, 10.0, 0.0));
+        world.update();
+        assert_eq!(world.drain_events(), vec![WorldEvent::SANITIZED(0)]);
+        assert_eq!((world.swarms[&0].x, world.swarms[&0].y), (500.0, 500.0));
+        assert_eq!(world.swarms[&0].members[0].direction, 0.0);
+        // Out of bounds is only clamped
+        assert_eq!(world.swarms[&1].x, 1000.0);
+        assert!(world.bullets.is_empty());
+    }
+    #[test]
+    fn update_squad_programs() {
+        use game_mode::CaptureTheFlag;
+        let mut world = World::new(1000.0, 500.0).with_seed(2);
+        world = world.with_mode(GameMode::CAPTURE_THE_FLAG(CaptureTheFlag::new(1000.0, 500.0)));
+        // Players join red and blue in turn
+        for id in 1..5 {
+            world.add_player(id).unwrap();
+        }
+        let program = |source: &str| source.parse::<SwarmProgram>().unwrap();
+        // Only teammates can be followed
+        assert_eq!(world.follow_leader(2, 1), Err(WorldError::NOT_SQUADMATE(1)));
+        world.follow_leader(3, 1).unwrap();
+        // One program for someone on the other team refuses the whole upload
+        assert_eq!(
+            world.update_squad_programs(1, vec![(1, program("FIRE")), (2, program("FIRE"))]),
+            Err(WorldError::NOT_SQUADMATE(2))
+        );
+        // A teammate who isn't followed can't replace a program
+        assert_eq!(
+            world.update_squad_programs(3, vec![(1, program("MOVE"))]),
+            Err(WorldError::NOT_SQUADMATE(1))
+        );
+        assert_eq!(
+            world.update_squad_programs(1, vec![(3, program("FIRE")), (3, program("MOVE"))]),
+            Err(WorldError::DUPLICATE_SQUADMATE(3))
+        );
+        assert_ne!(world.swarms[&1].program.to_string(), "FIRE\n");
+        world
+            .update_squad_programs(1, vec![(1, program("FIRE")), (3, program("TURN 10"))])
+            .unwrap();
+        assert_eq!(world.swarms[&1].program.to_string(), "FIRE\n");
+        assert_eq!(world.swarms[&3].program.to_string(), "TURN 10\n");
+        // Once 3 stops following, 1 can't upload for them
+        world.follow_leader(3, 3).unwrap();
+        assert_eq!(
+            world.update_squad_programs(1, vec![(3, program("FIRE"))]),
+            Err(WorldError::NOT_SQUADMATE(3))
+        );
+        // Without teams, a squad is only the player
+        let mut world = World::new(1000.0, 500.0);
+        world.add_player(1).unwrap();
+        world.add_player(2).unwrap();
+        assert!(world.update_squad_programs(1, vec![(1, program("FIRE"))]).is_ok());
+        assert_eq!(
+            world.update_squad_programs(1, vec![(2, program("FIRE"))]),
+            Err(WorldError::NOT_SQUADMATE(2))
+        );
+    }
+    #[test]
+    fn test_mode_hooks() {
+        use entity::SwarmMember;
+        use game_mode::{CaptureTheFlag, Team};
+        let mut world = World::new(1000.0, 500.0).with_seed(2);
+        world.add_player(1).unwrap();
+        let mut world = world.with_mode(GameMode::CAPTURE_THE_FLAG(CaptureTheFlag::new(
+            1000.0, 500.0,
+        )));
+        world.add_player(2).unwrap();
+        // Put player 1 on blue's flag
+        {
+            let swarm = world.swarms.get_mut(&1).unwrap();
+            swarm.x = 900.0;
+            swarm.y = 250.0;
+            swarm.members = vec![SwarmMember::new(0.0, 0.0)];
+        }
+        world.clear_events();
+        world.update();
+        assert_eq!(
+            world.events,
+            vec![WorldEvent::FLAG_TAKEN {
+                player: 1,
+                team: Team::BLUE,
+            }]
+        );
+        match world.get_state().mode {
+            GameMode::CAPTURE_THE_FLAG(ctf) => {
+                assert_eq!(ctf.team(1), Some(Team::RED));
+                assert_eq!(ctf.team(2), Some(Team::BLUE));
+                assert_eq!(ctf.flags[Team::BLUE.index()].carrier, Some(1));
+            }
+            mode => panic!("Expected capture the flag, got {:?}", mode),
+        }
+        // Leaving drops the flag
+        world.clear_events();
+        world.remove_player(1);
+        assert_eq!(
+            world.events,
+            vec![
+                WorldEvent::FLAG_DROPPED {
+                    player: 1,
+                    team: Team::BLUE,
+                },
+                WorldEvent::LEAVE(1),
+            ]
+        );
+    }
+    #[test]
+    fn test_world_from_map() {
+        use map::{Obstacle, SpawnPoint};
+        let mut map = Map::new(1000.0, 1000.0);
+        map.obstacles.push(Obstacle::new(400.0, 400.0, 100.0, 100.0));
+        map.spawns.push(SpawnPoint::new(100.0, 200.0));
+        let mut world = World::from_map(map).unwrap();
+        world.add_player(1).unwrap();
+        assert_eq!((world.swarms[&1].x, world.swarms[&1].y), (100.0, 200.0));
+        world.swarms.get_mut(&1).unwrap().program.commands.clear();
+        // A bullet heading into the obstacle stops there
+        world.bullets.push(Bullet::new(2, 395.0, 450.0, 0.0));
+        world.update();
+        assert!(world.bullets.is_empty());
+        assert!(World::from_map(Map::new(-5.0, 1000.0)).is_err());
+    }
+    #[test]
+    fn test_safe_zones() {
+        use map::{SpawnPoint, SAFE_ZONE_PUSHBACK};
+        let mut map = Map::new(1000.0, 1000.0);
+        map.spawns
+            .push(SpawnPoint::new(500.0, 500.0).with_safe_radius(100.0));
+        let mut world = World::from_map(map).unwrap();
+        world.add_player(1).unwrap();
+        world.add_player(2).unwrap();
+        assert_eq!(world.swarms[&1].home, Some(0));
+        for swarm in world.swarms.values_mut() {
+            swarm.program.commands.clear();
+        }
+        // Player 2 didn't spawn here, so is pushed out and can't fire into the zone
+        {
+            let intruder = world.swarms.get_mut(&2).unwrap();
+            intruder.home = None;
+            intruder.x = 550.0;
+        }
+        world.bullets.push(Bullet::new(1, 440.0, 430.0, 0.0));
+        world.bullets.push(Bullet::new(2, 440.0, 430.0, 0.0));
+        world.update();
+        assert_eq!(world.swarms[&1].x, 500.0);
+        assert_eq!(world.swarms[&2].x, 550.0 + SAFE_ZONE_PUSHBACK);
+        assert_eq!(world.bullets.len(), 1);
+        assert_eq!(world.bullets.owner[0], 1);
+    }
+    #[test]
+    fn test_match_flow() {
+        use rules::MatchResults;
+        let rules = MatchRules::new().with_duration(3).with_freeze(2);
+        let mut world = World::new(1000.0, 1000.0).with_seed(4).with_rules(rules);
+        world.add_player(1).unwrap();
+        world.add_player(2).unwrap();
+        world.swarms.get_mut(&2).unwrap().experience = 7;
+        world.clear_events();
+        // The match runs out of time on tick 3, and player 2 is ahead
+        for _ in 0..3 {
+            world.update();
+        }
+        assert_eq!(world.phase, MatchPhase::FROZEN { ended: 3 });
+        let results = match world.drain_events().pop() {
+            Some(WorldEvent::MATCH_ENDED(results)) => results,
+            event => panic!("Expected match results, got {:?}", event),
+        };
+        assert_eq!(
+            results,
+            MatchResults::new(&world.swarms, 3, EndReason::TIME_LIMIT)
+                .with_stats(world.player_stats.summary())
+        );
+        assert_eq!(results.winner, Some(2));
+        assert_eq!(results.stats[&1].ticks_alive, 3);
+        // Nothing moves during the freeze
+        let position = (world.swarms[&1].x, world.swarms[&1].y);
+        world.update();
+        assert_eq!((world.swarms[&1].x, world.swarms[&1].y), position);
+        assert!(world.drain_events().is_empty());
+        // Then everyone respawns for the next match
+        world.update();
+        assert_eq!(world.drain_events(), vec![WorldEvent::MATCH_STARTED]);
+        assert_eq!(world.phase, MatchPhase::PLAYING { started: 5 });
+        assert_eq!(world.swarms[&2].experience, 0);
+    }
+    #[test]
+    fn test_remove_destroyed_npcs() {
+        use entity::NPC_ID_START;
+        let mut world = World::new(1000.0, 1000.0).with_seed(5);
+        world.add_player(1).unwrap();
+        let mut npc = Swarm::new(500.0, 500.0, 1);
+        npc.members.clear();
+        world.swarms.insert(NPC_ID_START, npc);
+        world.clear_events();
+        world.update();
+        // Only the swarm controlled by the game is removed when it has no members
+        assert!(!world.has_player(NPC_ID_START));
+        assert!(world.has_player(1));
+        assert_eq!(world.drain_events(), vec![WorldEvent::LEAVE(NPC_ID_START)]);
+        assert_eq!(world.delta().removed, &[NPC_ID_START][..]);
     }
 }
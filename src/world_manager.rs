@@ -0,0 +1,1004 @@
+// Copyright 2018 Steven Sheffey
+// This file is part of heroesoftheswarm.
+//
+// heroesoftheswarm is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// heroesoftheswarm is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with heroesoftheswarm.  If not, see <http://www.gnu.org/licenses/>.
+use achievement::{AchievementStore, Achievements};
+use autosave::{RoomSave, ServerSave};
+use budget::{TickBudget, TickClock};
+use duel::{RatingStore, DEFAULT_RATING};
+use error::{Error, WorldConfigError, WorldError};
+use event::WorldEvent;
+use game_mode::{Duel, GameMode, Ruleset, Team, DUEL_SIZE};
+use handoff::{self, HandoffRedirect, PlayerSnapshot};
+use plugin::WorldPlugin;
+use rules::MatchResults;
+use snapshot::SnapshotCache;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::ops::DerefMut;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use world::{World, WorldConfig};
+
+/// The room players join when they don't ask for one
+/// This room can't be closed
+pub const DEFAULT_ROOM: usize = 0;
+
+/// A room is a world with its own update thread
+struct Room {
+    /// Name of the room
+    name: String,
+    /// What the room's world was made with
+    config: WorldConfig,
+    /// The room's world
+    world: Arc<RwLock<World>>,
+    /// Cleared to stop the room's update thread
+    running: Arc<AtomicBool>,
+    /// The room's latest serialized state, shared by its connections
+    snapshots: Arc<SnapshotCache>,
+}
+
+/// Something that happened on the server, of interest to outside services
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum ServerEvent {
+    /// A room started
+    #[serde(rename = "match_start")]
+    START { room_id: usize, name: String },
+    /// A room was closed
+    #[serde(rename = "match_end")]
+    END { room_id: usize },
+    /// A player's experience reached a milestone
+    #[serde(rename = "milestone")]
+    MILESTONE {
+        room_id: usize,
+        player_id: usize,
+        experience: i64,
+    },
+    /// A room fell so far behind that ticks were dropped
+    #[serde(rename = "overrun")]
+    OVERRUN { room_id: usize, skipped: u64 },
+    /// A match in a room ended
+    #[serde(rename = "match_results")]
+    RESULTS {
+        room_id: usize,
+        results: MatchResults,
+    },
+    /// A ranked duel in a room ended
+    #[serde(rename = "duel_over")]
+    DUEL_OVER {
+        room_id: usize,
+        winner: usize,
+        loser: usize,
+        winner_rating: f64,
+        loser_rating: f64,
+    },
+    /// A team game in a room reached its win condition
+    #[serde(rename = "match_over")]
+    MATCH_OVER {
+        room_id: usize,
+        winner: Option<Team>,
+    },
+    /// Something went wrong while running a room
+    #[serde(rename = "error")]
+    ERROR { room_id: usize, message: String },
+}
+
+/// Receives server events as they happen
+/// Called from room update threads, so implementations should return quickly
+pub trait ServerListener: Send + Sync {
+    /// Handles an event
+    fn on_event(&self, event: &ServerEvent);
+    /// Experience totals that count as milestones for this listener
+    fn milestones(&self) -> &[i64] {
+        &[]
+    }
+}
+
+/// Listeners shared between the manager and the room update threads
+type Listeners = Arc<RwLock<Vec<Box<ServerListener>>>>;
+
+/// Information about a room, used for listing rooms
+#[derive(Clone, Debug, Serialize)]
+pub struct RoomInfo {
+    /// ID of the room
+    pub room_id: usize,
+    /// Name of the room
+    pub name: String,
+    /// Number of players in the room
+    pub num_players: usize,
+    /// The width of the room's world
+    pub width: f32,
+    /// The height of the room's world
+    pub height: f32,
+    /// Name of the mode the room is played under
+    pub mode: String,
+    /// Whether the room has practice controls
+    pub practice: bool,
+}
+
+/// Manages every room on the server, and which players are allowed to connect
+pub struct WorldManager {
+    /// Map of room ID to room
+    rooms: HashMap<usize, Room>,
+    /// ID given to the next room created
+    next_room_id: usize,
+    /// Number of updates per second for each room
+    update_freq: u64,
+    /// Map of player ID to (room ID, address) for connected players
+    players: HashMap<usize, (usize, IpAddr)>,
+    /// Addresses that may not connect
+    banned: HashSet<IpAddr>,
+    /// Receive events from the manager and every room
+    listeners: Listeners,
+    /// Secret shared with sibling servers, used to sign handoff tokens
+    handoff_secret: Option<Vec<u8>>,
+    /// Time budget given to each room's updates
+    tick_budget: TickBudget,
+    /// Where each room keeps its players' achievements, if they're tracked
+    achievements: Option<Arc<AchievementStore>>,
+    /// Where ratings shown in each room's leaderboard are kept, if they're shown
+    ratings: Option<Arc<RatingStore>>,
+    /// Plugins added to every room
+    plugins: Vec<Arc<WorldPlugin>>,
+    /// Whether rooms only update when tick is called, instead of on their own threads
+    manual: bool,
+    /// Players from a restored save that haven't come back yet
+    /// Map of the player's ID before the restart to (room ID, snapshot)
+    restored: HashMap<usize, (usize, PlayerSnapshot)>,
+}
+
+/// Functions for WorldManager
+impl WorldManager {
+    /// Constructor
+    /// Creates the default room with the given dimensions
+    /// world_config: dimensions of the default room
+    /// update_freq: updates per second for each room
+    pub fn new(world_config: WorldConfig, update_freq: u64) -> Result<Self, WorldConfigError> {
+        WorldManager::build(world_config, update_freq, false)
+    }
+
+    /// Manual constructor
+    /// Rooms don't start update threads, and only update when tick is called,
+    /// so tests run the same way every time
+    /// world_config: dimensions of the default room
+    /// update_freq: updates per second the rooms' time budgets are based on
+    pub fn manual(world_config: WorldConfig, update_freq: u64) -> Result<Self, WorldConfigError> {
+        WorldManager::build(world_config, update_freq, true)
+    }
+
+    /// Creates the manager and its default room
+    fn build(
+        world_config: WorldConfig,
+        update_freq: u64,
+        manual: bool,
+    ) -> Result<Self, WorldConfigError> {
+        let mut manager = WorldManager {
+            rooms: HashMap::new(),
+            next_room_id: DEFAULT_ROOM,
+            update_freq: update_freq,
+            players: HashMap::new(),
+            banned: HashSet::new(),
+            listeners: Arc::new(RwLock::new(Vec::new())),
+            handoff_secret: None,
+            tick_budget: TickBudget::from_update_freq(update_freq),
+            achievements: None,
+            ratings: None,
+            plugins: Vec::new(),
+            manual: manual,
+            restored: HashMap::new(),
+        };
+        manager.create_room("default".into(), world_config)?;
+        Ok(manager)
+    }
+
+    /// Creates a room and starts its update thread
+    /// return: the ID of the new room, or why the world couldn't be created
+    pub fn create_room(
+        &mut self,
+        name: String,
+        world_config: WorldConfig,
+    ) -> Result<usize, WorldConfigError> {
+        let mut world = World::from_config(&world_config)?;
+        let room_id = self.next_room_id;
+        self.next_room_id += 1;
+        info!("Creating room {} ({})", room_id, name);
+        world.budget = self.tick_budget.clone();
+        if let Some(ref store) = self.achievements {
+            world.achievements = Some(Achievements::new(store.clone()));
+        }
+        world.ratings = self.ratings.clone();
+        for plugin in self.plugins.iter() {
+            world.add_plugin(plugin.clone());
+        }
+        let world = Arc::new(RwLock::new(world));
+        let running = Arc::new(AtomicBool::new(true));
+        // Start the room's main thread
+        if !self.manual {
+            let world = world.clone();
+            let running = running.clone();
+            let listeners = self.listeners.clone();
+            let update_freq = self.update_freq;
+            thread::spawn(move || run_updates(room_id, world, update_freq, running, listeners));
+        }
+        notify(
+            &self.listeners,
+            &ServerEvent::START {
+                room_id: room_id,
+                name: name.clone(),
+            },
+        );
+        self.rooms.insert(
+            room_id,
+            Room {
+                name: name,
+                config: world_config,
+                world: world,
+                running: running,
+                snapshots: Arc::new(SnapshotCache::new()),
+            },
+        );
+        Ok(room_id)
+    }
+
+    /// Creates a room for a ranked duel between two players
+    /// The duelists join the room like any other, and their ratings are read from the store
+    /// rounds: rounds in the duel, best of
+    /// return: the ID of the new room
+    pub fn create_duel(
+        &mut self,
+        players: (usize, usize),
+        rounds: u32,
+        ratings: &RatingStore,
+    ) -> Result<usize, Error> {
+        let name = format!("Duel {} vs {}", players.0, players.1);
+        let room_id = self.create_room(name, WorldConfig::new(DUEL_SIZE, DUEL_SIZE))?;
+        let rating = |id| ratings.rating(id).unwrap_or(DEFAULT_RATING);
+        let duel = Duel::new(DUEL_SIZE, DUEL_SIZE, rounds).with_players([
+            (players.0, rating(players.0)),
+            (players.1, rating(players.1)),
+        ]);
+        match self.rooms[&room_id].world.write() {
+            Ok(mut world) => world.mode = GameMode::DUEL(duel),
+            Err(_) => return Err(WorldError::LOCK_POISONED("the world").into()),
+        }
+        Ok(room_id)
+    }
+
+    /// Updates every room once, in room ID order, and sends their events to the listeners
+    /// Meant for managers built with manual, whose rooms don't update themselves
+    /// return: each room's events from the update, with the room's ID
+    pub fn tick(&mut self) -> Vec<(usize, WorldEvent)> {
+        let mut room_ids: Vec<usize> = self.rooms.keys().cloned().collect();
+        room_ids.sort();
+        let mut events = Vec::new();
+        for room_id in room_ids {
+            match self.rooms[&room_id].world.write() {
+                Ok(mut world) => {
+                    world.update();
+                    events.extend(world.events.iter().map(|event| (room_id, event.clone())));
+                    dispatch_world_events(room_id, &mut world, &self.listeners);
+                }
+                Err(error) => error!("Error getting write lock on room {}: {}", room_id, error),
+            }
+        }
+        events
+    }
+
+    /// Stops a room's update thread and removes it
+    pub fn close_room(&mut self, room_id: usize) -> Result<(), Error> {
+        if room_id == DEFAULT_ROOM {
+            return Err(WorldError::PERMANENT_ROOM(room_id).into());
+        }
+        match self.rooms.remove(&room_id) {
+            Some(room) => {
+                info!("Closing room {} ({})", room_id, room.name);
+                room.running.store(false, AtomicOrdering::SeqCst);
+                self.players.retain(|_, &mut (player_room, _)| player_room != room_id);
+                notify(&self.listeners, &ServerEvent::END { room_id: room_id });
+                Ok(())
+            }
+            None => Err(WorldError::NO_ROOM(room_id).into()),
+        }
+    }
+
+    /// Sets the time budget for updates in every room, including rooms created later
+    pub fn set_tick_budget(&mut self, budget: TickBudget) {
+        for (room_id, room) in self.rooms.iter() {
+            match room.world.write() {
+                Ok(mut world) => world.budget = budget.clone(),
+                Err(error) => error!("Error getting write lock on room {}: {}", room_id, error),
+            }
+        }
+        self.tick_budget = budget;
+    }
+
+    /// Tracks achievements in every room, keeping progress in the given store
+    pub fn set_achievement_store(&mut self, store: Arc<AchievementStore>) {
+        for (room_id, room) in self.rooms.iter() {
+            match room.world.write() {
+                Ok(mut world) => world.achievements = Some(Achievements::new(store.clone())),
+                Err(error) => error!("Error getting write lock on room {}: {}", room_id, error),
+            }
+        }
+        self.achievements = Some(store);
+    }
+
+    /// Shows duel ratings in every room's leaderboard, reading them from the given store
+    pub fn set_rating_store(&mut self, store: Arc<RatingStore>) {
+        for (room_id, room) in self.rooms.iter() {
+            match room.world.write() {
+                Ok(mut world) => world.ratings = Some(store.clone()),
+                Err(error) => error!("Error getting write lock on room {}: {}", room_id, error),
+            }
+        }
+        self.ratings = Some(store);
+    }
+
+    /// Adds a plugin to every room, including rooms created later
+    pub fn add_plugin(&mut self, plugin: Arc<WorldPlugin>) {
+        for (room_id, room) in self.rooms.iter() {
+            match room.world.write() {
+                Ok(mut world) => world.add_plugin(plugin.clone()),
+                Err(error) => error!("Error getting write lock on room {}: {}", room_id, error),
+            }
+        }
+        self.plugins.push(plugin);
+    }
+
+    /// Adds a listener for events from the manager and every room
+    pub fn add_listener(&mut self, listener: Box<ServerListener>) {
+        match self.listeners.write() {
+            Ok(mut listeners) => listeners.push(listener),
+            Err(error) => error!("Error getting write lock on listeners: {}", error),
+        }
+    }
+
+    /// Gets a reference to a room's world
+    pub fn room(&self, room_id: usize) -> Option<Arc<RwLock<World>>> {
+        self.rooms.get(&room_id).map(|room| room.world.clone())
+    }
+
+    /// Gets a reference to a room's serialized state cache
+    pub fn snapshots(&self, room_id: usize) -> Option<Arc<SnapshotCache>> {
+        self.rooms.get(&room_id).map(|room| room.snapshots.clone())
+    }
+
+    /// Lists every room, in order of ID
+    pub fn rooms(&self) -> Vec<RoomInfo> {
+        let mut rooms: Vec<RoomInfo> = self.rooms
+            .iter()
+            .map(|(room_id, room)| {
+                // The mode is read from the world, since duels are set up after it's made
+                let config = &room.config;
+                let (num_players, mode) = match room.world.read() {
+                    Ok(world) => (world.swarms.len(), world.mode.name().to_string()),
+                    Err(_) => {
+                        let mode = config.ruleset.mode(config.width, config.height);
+                        (0, mode.name().to_string())
+                    }
+                };
+                RoomInfo {
+                    room_id: *room_id,
+                    name: room.name.clone(),
+                    num_players: num_players,
+                    width: room.config.width,
+                    height: room.config.height,
+                    mode: mode,
+                    practice: room.config.practice,
+                }
+            })
+            .collect();
+        rooms.sort_by_key(|room| room.room_id);
+        rooms
+    }
+
+    /// Records that a player has connected to a room
+    pub fn register_player(&mut self, player_id: usize, room_id: usize, address: IpAddr) {
+        self.players.insert(player_id, (room_id, address));
+    }
+
+    /// Records that a player has disconnected
+    pub fn unregister_player(&mut self, player_id: usize) {
+        self.players.remove(&player_id);
+    }
+
+    /// Bans a player's address and removes them from their room
+    /// return: the banned address
+    pub fn ban(&mut self, player_id: usize) -> Result<IpAddr, Error> {
+        let (room_id, address) = match self.players.remove(&player_id) {
+            Some(player) => player,
+            None => return Err(WorldError::NO_PLAYER(player_id).into()),
+        };
+        info!("Banning player {} ({})", player_id, address);
+        self.banned.insert(address);
+        if let Some(world) = self.room(room_id) {
+            match world.write() {
+                Ok(mut write_lock) => write_lock.remove_player(player_id),
+                Err(error) => error!("Error getting write lock: {}. Player not removed", error),
+            }
+        }
+        Ok(address)
+    }
+
+    /// Sets the secret shared with sibling servers, enabling handoffs
+    pub fn set_handoff_secret(&mut self, secret: Vec<u8>) {
+        self.handoff_secret = Some(secret);
+    }
+
+    /// Hands a player off to another server
+    /// The player is removed, and told to reconnect to the given URL with a token
+    /// return: the token
+    pub fn handoff(&mut self, player_id: usize, url: String) -> Result<String, Error> {
+        let secret = match self.handoff_secret {
+            Some(ref secret) => secret.clone(),
+            None => return Err(WorldError::HANDOFFS_DISABLED.into()),
+        };
+        let world = match self.players.get(&player_id) {
+            Some(&(room_id, _)) => self.room(room_id),
+            None => None,
+        };
+        let world = match world {
+            Some(world) => world,
+            None => return Err(WorldError::NO_PLAYER(player_id).into()),
+        };
+        let mut world = match world.write() {
+            Ok(write_lock) => write_lock,
+            Err(_) => return Err(WorldError::LOCK_POISONED("the world").into()),
+        };
+        let snapshot = match world.export_player(player_id) {
+            Some(snapshot) => snapshot,
+            None => return Err(WorldError::NO_PLAYER(player_id).into()),
+        };
+        let token = handoff::issue_token(&snapshot, &secret, unix_time())?;
+        info!("Handing off player {} to {}", player_id, url);
+        world.remove_player(player_id);
+        world.pending_handoffs.insert(
+            player_id,
+            HandoffRedirect {
+                url: url,
+                token: token.clone(),
+            },
+        );
+        Ok(token)
+    }
+
+    /// Checks a handoff token presented by a player arriving from a sibling server
+    pub fn redeem_handoff(&self, token: &str) -> Result<PlayerSnapshot, Error> {
+        match self.handoff_secret {
+            Some(ref secret) => handoff::redeem_token(token, secret, unix_time()),
+            None => Err(WorldError::HANDOFFS_DISABLED.into()),
+        }
+    }
+
+    /// Creates a token the player can use to get their swarm back if the server restarts
+    /// Resume tokens are signed with the handoff secret, so they need one too
+    pub fn resume_token(&self, player_id: usize) -> Option<String> {
+        let secret = self.handoff_secret.as_ref()?;
+        match handoff::issue_resume_token(player_id, secret) {
+            Ok(token) => Some(token),
+            Err(error) => {
+                error!("Failed to issue resume token: {}", error);
+                None
+            }
+        }
+    }
+
+    /// Checks a resume token, and takes the saved player it was issued for
+    /// Each saved player can only be resumed once
+    pub fn resume(&mut self, token: &str) -> Result<PlayerSnapshot, Error> {
+        let player_id = match self.handoff_secret {
+            Some(ref secret) => handoff::redeem_resume_token(token, secret)?,
+            None => return Err(WorldError::HANDOFFS_DISABLED.into()),
+        };
+        match self.restored.remove(&player_id) {
+            Some((_, snapshot)) => Ok(snapshot),
+            None => Err(WorldError::NO_PLAYER(player_id).into()),
+        }
+    }
+
+    /// Captures every room, and the players in it, so they can be restored after a restart
+    /// Players from a restored save that haven't come back yet are kept in their rooms
+    pub fn save(&self) -> ServerSave {
+        let mut room_ids: Vec<usize> = self.rooms.keys().cloned().collect();
+        room_ids.sort();
+        let mut rooms = Vec::new();
+        for room_id in room_ids {
+            let room = &self.rooms[&room_id];
+            let world = match room.world.read() {
+                Ok(world) => world,
+                Err(error) => {
+                    error!("Error getting read lock on room {}: {}", room_id, error);
+                    continue;
+                }
+            };
+            let mut players: Vec<PlayerSnapshot> = world
+                .player_ids()
+                .into_iter()
+                .filter_map(|player_id| world.export_player(player_id))
+                .collect();
+            players.extend(
+                self.restored
+                    .values()
+                    .filter(|&&(restored_room, _)| restored_room == room_id)
+                    .map(|&(_, ref snapshot)| snapshot.clone()),
+            );
+            rooms.push(RoomSave {
+                room_id: room_id,
+                name: room.name.clone(),
+                width: world.width,
+                height: world.height,
+                ruleset: room.config.ruleset,
+                tick: world.tick,
+                phase: world.phase,
+                players: players,
+            });
+        }
+        ServerSave {
+            saved_at: unix_time(),
+            rooms: rooms,
+        }
+    }
+
+    /// Recreates the rooms in a save, and waits for their players to resume
+    /// Rooms keep their IDs, so players can reconnect to the same paths
+    /// Rooms other than the default are recreated with their ruleset, but default match rules
+    pub fn restore(&mut self, save: ServerSave) -> Result<(), Error> {
+        info!("Restoring {} rooms saved at {}", save.rooms.len(), save.saved_at);
+        for room in save.rooms {
+            if !self.rooms.contains_key(&room.room_id) {
+                let next_room_id = cmp::max(self.next_room_id, room.room_id + 1);
+                self.next_room_id = room.room_id;
+                let created = self.create_room(
+                    room.name.clone(),
+                    WorldConfig::new(room.width, room.height).with_ruleset(room.ruleset),
+                );
+                self.next_room_id = next_room_id;
+                created?;
+            }
+            if let Some(world) = self.room(room.room_id) {
+                match world.write() {
+                    Ok(mut world) => {
+                        world.tick = room.tick;
+                        world.phase = room.phase;
+                    }
+                    Err(_) => return Err(WorldError::LOCK_POISONED("the world").into()),
+                }
+            }
+            for player in room.players {
+                self.restored.insert(player.player_id, (room.room_id, player));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether an address is banned
+    pub fn is_banned(&self, address: &IpAddr) -> bool {
+        self.banned.contains(address)
+    }
+}
+
+/// Stops every room's update thread when the manager goes away
+impl Drop for WorldManager {
+    fn drop(&mut self) {
+        for room in self.rooms.values() {
+            room.running.store(false, AtomicOrdering::SeqCst);
+        }
+    }
+}
+
+/// The current time, in seconds since the unix epoch
+fn unix_time() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs(),
+        Err(_) => 0,
+    }
+}
+
+/// Gets the room a client asked for from the path it connected to
+/// "/" joins the default room, "/rooms/<id>" joins a specific room
+pub fn room_from_path(path: &str) -> Option<usize> {
+    let path = path.trim_right_matches('/');
+    if path.is_empty() {
+        return Some(DEFAULT_ROOM);
+    }
+    if path.starts_with("/rooms/") {
+        path["/rooms/".len()..].parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Sends an event to every listener
+fn notify(listeners: &Listeners, event: &ServerEvent) {
+    match listeners.read() {
+        Ok(listeners) => for listener in listeners.iter() {
+            listener.on_event(event);
+        },
+        Err(error) => error!("Error getting read lock on listeners: {}", error),
+    }
+}
+
+/// Sends a room's world events to the listeners that care about them
+fn dispatch_world_events(
+    room_id: usize,
+    world: &mut World,
+    listeners: &Listeners,
+) {
+    let listeners = match listeners.read() {
+        Ok(listeners) => listeners,
+        Err(error) => {
+            error!("Error getting read lock on listeners: {}", error);
+            world.clear_events();
+            return;
+        }
+    };
+    for event in world.events.iter() {
+        match *event {
+            WorldEvent::KILL { attacker, .. } => {
+                let experience = match world.swarms.get(&attacker) {
+                    Some(swarm) => swarm.experience,
+                    None => continue,
+                };
+                let milestone = ServerEvent::MILESTONE {
+                    room_id: room_id,
+                    player_id: attacker,
+                    experience: experience,
+                };
+                for listener in listeners.iter() {
+                    if listener.milestones().contains(&experience) {
+                        listener.on_event(&milestone);
+                    }
+                }
+            }
+            WorldEvent::MATCH_ENDED(ref results) => {
+                let ended = ServerEvent::RESULTS {
+                    room_id: room_id,
+                    results: results.clone(),
+                };
+                for listener in listeners.iter() {
+                    listener.on_event(&ended);
+                }
+            }
+            WorldEvent::DUEL_OVER {
+                winner,
+                loser,
+                winner_rating,
+                loser_rating,
+            } => {
+                let over = ServerEvent::DUEL_OVER {
+                    room_id: room_id,
+                    winner: winner,
+                    loser: loser,
+                    winner_rating: winner_rating,
+                    loser_rating: loser_rating,
+                };
+                for listener in listeners.iter() {
+                    listener.on_event(&over);
+                }
+            }
+            WorldEvent::MATCH_OVER { winner } => {
+                let over = ServerEvent::MATCH_OVER {
+                    room_id: room_id,
+                    winner: winner,
+                };
+                for listener in listeners.iter() {
+                    listener.on_event(&over);
+                }
+            }
+            _ => {}
+        }
+    }
+    // Cleared in place, so the event buffer keeps its space for the next tick
+    world.clear_events();
+}
+
+/// Updates a world at a fixed rate until it is told to stop
+/// Late updates are caught up on a few at a time, and ticks further behind are dropped
+fn run_updates(
+    room_id: usize,
+    world: Arc<RwLock<World>>,
+    update_freq: u64,
+    running: Arc<AtomicBool>,
+    listeners: Listeners,
+) {
+    // TODO: nanoseconds accuracy for this
+    let update_delta = Duration::from_micros(1000000 / update_freq);
+    let mut clock = TickClock::new(update_delta, Instant::now() + update_delta);
+    // Main loop
+    while running.load(AtomicOrdering::SeqCst) {
+        // Sleep until the next update is due
+        if let Some(wait) = clock.until_next(Instant::now()) {
+            thread::sleep(wait);
+        }
+        let due = clock.advance(Instant::now());
+        if due.run > 1 {
+            debug!("Catching up on {} updates", due.run);
+        }
+        // Lock the world for writing
+        match world.write() {
+            Ok(mut write_lock) => {
+                // Get a mutable reference to the world
+                let world_ref = write_lock.deref_mut();
+                if due.skipped > 0 {
+                    warn!(
+                        "Room {} fell behind, skipping {} ticks",
+                        room_id, due.skipped
+                    );
+                    world_ref.budget.record_skipped(due.skipped);
+                    notify(
+                        &listeners,
+                        &ServerEvent::OVERRUN {
+                            room_id: room_id,
+                            skipped: due.skipped,
+                        },
+                    );
+                }
+                for _ in 0..due.run {
+                    // Update the world
+                    let update_time = world_ref.update();
+                    // Log time elapsed in the update
+                    debug!(
+                        "Update took {}s, {}ns",
+                        update_time.as_secs(),
+                        update_time.subsec_nanos()
+                    );
+                    dispatch_world_events(room_id, world_ref, &listeners);
+                }
+                // Write lock goes out of scope, world is again available to be read
+            }
+            Err(error) => {
+                error!("Error retrieving write lock in update thread: {}", error);
+                notify(
+                    &listeners,
+                    &ServerEvent::ERROR {
+                        room_id: room_id,
+                        message: error.to_string(),
+                    },
+                );
+                // The world is poisoned, so every following update would fail too
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    #[test]
+    fn create_and_close_rooms() {
+        let mut manager = WorldManager::new(WorldConfig::new(1000.0, 1000.0), 60).unwrap();
+        let room_id = manager
+            .create_room("duel".into(), WorldConfig::new(400.0, 400.0))
+            .unwrap();
+        assert_eq!(manager.rooms().len(), 2);
+        assert!(manager.room(room_id).is_some());
+        let arena = manager
+            .create_room(
+                "arena".into(),
+                WorldConfig::new(4000.0, 3000.0).with_ruleset(Ruleset::KING_OF_THE_HILL),
+            )
+            .unwrap();
+        let info = manager.rooms().into_iter().find(|room| room.room_id == arena).unwrap();
+        assert_eq!((info.width, info.height), (4000.0, 3000.0));
+        assert_eq!(info.mode, "king_of_the_hill");
+        assert!(manager.close_room(arena).is_ok());
+        assert!(manager.close_room(DEFAULT_ROOM).is_err());
+        assert!(manager.close_room(room_id).is_ok());
+        assert!(manager.room(room_id).is_none());
+        assert!(manager.close_room(room_id).is_err());
+    }
+
+    #[test]
+    fn ban_player() {
+        let mut manager = WorldManager::new(WorldConfig::new(1000.0, 1000.0), 60).unwrap();
+        let address = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        manager
+            .room(DEFAULT_ROOM)
+            .unwrap()
+            .write()
+            .unwrap()
+            .add_player(7)
+            .unwrap();
+        manager.register_player(7, DEFAULT_ROOM, address);
+        assert_eq!(manager.ban(7).unwrap(), address);
+        assert!(manager.is_banned(&address));
+        assert!(!manager.room(DEFAULT_ROOM).unwrap().read().unwrap().swarms.contains_key(&7));
+        assert!(manager.ban(7).is_err());
+    }
+
+    #[test]
+    fn manual_tick() {
+        let mut manager = WorldManager::manual(WorldConfig::new(1000.0, 1000.0), 60).unwrap();
+        let room_id = manager
+            .create_room("practice".into(), WorldConfig::new(400.0, 400.0))
+            .unwrap();
+        manager.room(room_id).unwrap().write().unwrap().add_player(3).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(manager.room(room_id).unwrap().read().unwrap().tick, 0);
+        assert_eq!(manager.tick(), vec![(room_id, WorldEvent::JOIN(3))]);
+        assert!(manager.tick().is_empty());
+        for &id in [DEFAULT_ROOM, room_id].iter() {
+            assert_eq!(manager.room(id).unwrap().read().unwrap().tick, 2);
+        }
+    }
+
+    /// Records every event it receives
+    struct RecordingListener {
+        events: Arc<RwLock<Vec<ServerEvent>>>,
+    }
+    impl ServerListener for RecordingListener {
+        fn on_event(&self, event: &ServerEvent) {
+            self.events.write().unwrap().push(event.clone());
+        }
+        fn milestones(&self) -> &[i64] {
+            &[1]
+        }
+    }
+
+    #[test]
+    fn notify_listeners() {
+        let mut manager = WorldManager::new(WorldConfig::new(1000.0, 1000.0), 60).unwrap();
+        let events = Arc::new(RwLock::new(Vec::new()));
+        manager.add_listener(Box::new(RecordingListener {
+            events: events.clone(),
+        }));
+        let room_id = manager
+            .create_room("duel".into(), WorldConfig::new(400.0, 400.0))
+            .unwrap();
+        manager.close_room(room_id).unwrap();
+        assert_eq!(
+            *events.read().unwrap(),
+            vec![
+                ServerEvent::START {
+                    room_id: room_id,
+                    name: "duel".into(),
+                },
+                ServerEvent::END { room_id: room_id },
+            ]
+        );
+        // A kill that brings a player to 1 experience is a milestone
+        let mut world = World::new(1000.0, 1000.0);
+        world.add_player(2).unwrap();
+        world.swarms.get_mut(&2).unwrap().experience = 1;
+        world.events.push(WorldEvent::KILL {
+            attacker: 2,
+            victim: 3,
+        });
+        dispatch_world_events(room_id, &mut world, &manager.listeners);
+        assert_eq!(
+            events.read().unwrap().last(),
+            Some(&ServerEvent::MILESTONE {
+                room_id: room_id,
+                player_id: 2,
+                experience: 1,
+            })
+        );
+        // Every listener hears when a team game ends
+        world.events.push(WorldEvent::MATCH_OVER {
+            winner: Some(Team::BLUE),
+        });
+        dispatch_world_events(room_id, &mut world, &manager.listeners);
+        assert_eq!(
+            events.read().unwrap().last(),
+            Some(&ServerEvent::MATCH_OVER {
+                room_id: room_id,
+                winner: Some(Team::BLUE),
+            })
+        );
+    }
+
+    #[test]
+    fn observers_see_dispatched_events() {
+        let mut manager = WorldManager::manual(WorldConfig::new(1000.0, 1000.0), 60).unwrap();
+        let seen = Arc::new(RwLock::new(Vec::new()));
+        let log = seen.clone();
+        let world = manager.room(DEFAULT_ROOM).unwrap();
+        world
+            .write()
+            .unwrap()
+            .subscribe(Box::new(move |_: u64, event: &WorldEvent| {
+                log.write().unwrap().push(event.clone());
+            }));
+        world.write().unwrap().add_player(1).unwrap();
+        world.write().unwrap().add_player(2).unwrap();
+        manager.tick();
+        assert!(world.read().unwrap().events.is_empty());
+        // Events after the room loop emptied the buffer still reach the observer
+        world.write().unwrap().add_player(3).unwrap();
+        manager.tick();
+        let joins: Vec<WorldEvent> = seen
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|event| match **event {
+                WorldEvent::JOIN(_) => true,
+                _ => false,
+            })
+            .cloned()
+            .collect();
+        assert_eq!(
+            joins,
+            vec![WorldEvent::JOIN(1), WorldEvent::JOIN(2), WorldEvent::JOIN(3)]
+        );
+    }
+
+    #[test]
+    fn handoff_player() {
+        let mut manager = WorldManager::new(WorldConfig::new(1000.0, 1000.0), 60).unwrap();
+        let address = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let world = manager.room(DEFAULT_ROOM).unwrap();
+        world.write().unwrap().add_player(7).unwrap();
+        manager.register_player(7, DEFAULT_ROOM, address);
+        // Handoffs need a secret
+        assert!(manager.handoff(7, "wss://sibling".into()).is_err());
+        manager.set_handoff_secret(b"secret".to_vec());
+        assert!(manager.handoff(7, "wss://sibling".into()).is_ok());
+        assert!(!world.read().unwrap().swarms.contains_key(&7));
+        assert!(world.read().unwrap().pending_handoffs.contains_key(&7));
+        assert!(manager.redeem_handoff("garbage").is_err());
+    }
+
+    #[test]
+    fn duel_ratings() {
+        use duel::{MemoryRatings, RatingListener};
+        let mut manager = WorldManager::new(WorldConfig::new(1000.0, 1000.0), 60).unwrap();
+        let store = Arc::new(MemoryRatings::new());
+        store.set_rating(4, 1600.0);
+        manager.add_listener(Box::new(RatingListener {
+            store: store.clone(),
+        }));
+        manager.set_rating_store(store.clone());
+        let room_id = manager.create_duel((4, 5), 3, &*store).unwrap();
+        let world = manager.room(room_id).unwrap();
+        let mut world = world.write().unwrap();
+        match world.mode {
+            GameMode::DUEL(ref duel) => {
+                assert_eq!(duel.players, vec![4, 5]);
+                assert_eq!(duel.ratings[&4], 1600.0);
+                assert_eq!(duel.ratings[&5], DEFAULT_RATING);
+            }
+            ref mode => panic!("Expected a duel, got {:?}", mode),
+        }
+        // The listener saves the ratings when the duel ends
+        world.events.push(WorldEvent::DUEL_OVER {
+            winner: 5,
+            loser: 4,
+            winner_rating: 1520.0,
+            loser_rating: 1580.0,
+        });
+        dispatch_world_events(room_id, &mut world, &manager.listeners);
+        assert_eq!(store.rating(5), Some(1520.0));
+        assert_eq!(store.rating(4), Some(1580.0));
+        // The leaderboard shows the new rating
+        world.add_player(5).unwrap();
+        world.update_leaderboard();
+        assert_eq!(world.leaderboard[0].3, Some(1520.0));
+    }
+
+    #[test]
+    fn parse_room_path() {
+        assert_eq!(room_from_path("/"), Some(DEFAULT_ROOM));
+        assert_eq!(room_from_path(""), Some(DEFAULT_ROOM));
+        assert_eq!(room_from_path("/rooms/3"), Some(3));
+        assert_eq!(room_from_path("/rooms/3/"), Some(3));
+        assert_eq!(room_from_path("/rooms/three"), None);
+        assert_eq!(room_from_path("/elsewhere"), None);
+    }
+}